@@ -7,13 +7,13 @@ pub fn build() -> Command {
         .about(clap::crate_description!())
         .version(clap::crate_version!())
         // .author(clap::crate_authors!("\n"))
-        .long_about("This program will read a .fit file and output session information to a .json file, the lap information (if any is found) to a .laps.csv file, and the individual records to a .records.csv file. Additionally, a summary sessions.csv file will be produced.")
+        .long_about("This program will read a .fit file and output session information to a .json file, the lap information (if any is found) to a .laps.csv file, the individual records to a .records.csv file, the events and devices to .events.csv and .devices.csv files, and the splits recorded by the device to a .device_splits.csv file. Additionally, a summary sessions.csv file will be produced.")
         .arg(
             Arg::new("read")
                 .value_name("FILE(S)")
                 .help("One or more .fit file(s) to process. Wildcards and multiple_occurrences files (e.g. 2019*.fit 2020*.fit) are supported.")
                 .num_args(1..)
-                .required(true)
+                .required_unless_present_any(["csv-schema", "watch"])
                 .action(ArgAction::Append)
         )
         .arg( // Hidden debug parameter
@@ -61,6 +61,354 @@ pub fn build() -> Command {
                 .required(false)
                 .action(ArgAction::Set)
         )
+        .arg( // Memory limit
+            Arg::new("max-memory")
+                .short('m')
+                .long("max-memory")
+                .value_name("MB")
+                .help("Export in chunks instead of holding the whole file in memory if parsing a file is estimated to need more than this many megabytes.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set)
+        )
+        .arg( // Per-file timeout
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECONDS")
+                .help("Skip files that take longer than this many seconds to process. Skipped files are listed with the error in the manifest.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .action(ArgAction::Set)
+        )
+        .arg( // Carry on after bad files
+            Arg::new("keep-going")
+                .long("keep-going")
+                .value_name("true|false")
+                .help("Carry on with the rest of the files when one can't be read or processed, listing the files that failed at the end and exiting with code 2. On by default. Use --keep-going=false to stop at the first bad file instead, exiting with code 1.")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_value("true")
+                .default_missing_value("true")
+                .value_parser(clap::value_parser!(bool))
+                .action(ArgAction::Set)
+        )
+        .arg( // Skip short activities
+            Arg::new("min-duration")
+                .long("min-duration")
+                .value_name("hh:mm:ss")
+                .help("Skip activities shorter than this, e.g. 00:01:00, leaving them out of the summary and writing no details for them. Skipped files are listed in the manifest.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Skip short activities
+            Arg::new("min-distance")
+                .long("min-distance")
+                .value_name("DISTANCE")
+                .help("Skip activities shorter than this distance, in m, km or mi, e.g. 200m, leaving them out of the summary and writing no details for them. Kilometers if no unit is given.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Watch a directory
+            Arg::new("watch")
+                .long("watch")
+                .value_name("DIR")
+                .help("Keep watching the directory, and the ones below it, and export the details of each new .fit file once it has been written, e.g. as the watch syncs it. The files already there are exported first. Each file is only exported once, as recorded in the --ledger file. Each file's sessions are added to the end of the summary file, and the manifest is rewritten, if requested.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // The files processed while watching
+            Arg::new("ledger")
+                .long("ledger")
+                .value_name("FILE")
+                .help("The file recording the files exported by --watch, .fit2csv-processed in the directory watched by default.")
+                .num_args(1)
+                .requires("watch")
+                .action(ArgAction::Set)
+        )
+        .arg( // Single JSON document per file
+            Arg::new("bundle")
+                .short('b')
+                .long("bundle")
+                .help("Export the details from each file as a single .bundle.json document instead of separate files.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Split the summary file
+            Arg::new("split-summary-by")
+                .long("split-summary-by")
+                .value_name("sport|year|device")
+                .help("Write the summary as one file per sport, year or device instead of a single file.")
+                .num_args(1)
+                .value_parser(utilities::SummarySplit::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // Which altitude field to read from the records
+            Arg::new("altitude-source")
+                .long("altitude-source")
+                .value_name("enhanced|standard")
+                .help("Which FIT altitude field to prefer for the records. The other field is used when the preferred one is missing. The field used is written to the altitude_source column.")
+                .num_args(1)
+                .default_value("enhanced")
+                .value_parser(utilities::AltitudeSource::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // Rounding of the exported numbers
+            Arg::new("precision")
+                .long("precision")
+                .value_name("DECIMALS")
+                .help("Round the decimal numbers in the exported files to this many decimals.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u32))
+                .action(ArgAction::Set)
+        )
+        .arg( // Rounding of individual fields
+            Arg::new("field-precision")
+                .long("field-precision")
+                .value_name("FIELD=DECIMALS")
+                .help("Round a single field to this many decimals, e.g. latitude=6. Overrides --precision. May be given more than once.")
+                .num_args(1)
+                .action(ArgAction::Append)
+        )
+        .arg( // CSV delimiter
+            Arg::new("delimiter")
+                .long("delimiter")
+                .value_name("CHAR")
+                .help("Separate the values in the CSV files with this character instead of a comma, e.g. ; or |. Use tab for tab separated values.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Decimal comma
+            Arg::new("decimal-comma")
+                .long("decimal-comma")
+                .help("Write the decimals in the CSV files with a comma, e.g. 12,5, as spreadsheets set up for most European locales expect. The values are separated by semicolons unless --delimiter is given.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Stable CSV columns
+            Arg::new("columns-version")
+                .long("columns-version")
+                .value_name("VERSION")
+                .help("Write the CSV columns as they were in an earlier version, leaving out the columns added since. Use 1 for the columns before the UUIDs, GPS checks, pacing and recording interval were added.")
+                .num_args(1)
+                .value_parser(utilities::ColumnsVersion::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // CSV schema
+            Arg::new("csv-schema")
+                .long("csv-schema")
+                .help("Print the columns of each CSV file written, with the type and unit of each, as JSON and exit. The columns follow --columns-version and --units.")
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Summary JSON layout
+            Arg::new("schema")
+                .long("schema")
+                .value_name("VERSION")
+                .help("The layout of the summary JSON files. v2 adds the unit of each field, writes durations as seconds and nests the heart rate zones. v1, the default, keeps the layout of earlier versions.")
+                .num_args(1)
+                .value_parser(utilities::SummarySchema::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // Parse statistics
+            Arg::new("stats")
+                .long("stats")
+                .help("Print counts of the messages by kind and of the unknown fields in each file, including what was ignored.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Parse warnings
+            Arg::new("show-warnings")
+                .long("show-warnings")
+                .help("Print the warnings found in each file, e.g. missing or repeated messages, after the file is processed.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Validation
+            Arg::new("validate")
+                .long("validate")
+                .value_name("PERCENT")
+                .help("Work out the distance, time and ascent again from the records and print them next to the totals in the Session message, to find corrupted or edited files. The totals that differ by more than this percentage, 5% by default, and by more than 50 m, 30 seconds or 10 m of ascent, are added to the warnings of the file. Files read in chunks with --max-memory have no records to check against.")
+                .num_args(0..=1)
+                .default_missing_value("5")
+                .value_parser(clap::value_parser!(f64))
+                .conflicts_with("cache")
+                .action(ArgAction::Set)
+        )
+        .arg( // Manifest
+            Arg::new("manifest")
+                .long("manifest")
+                .value_name("FILE")
+                .help("Write a JSON manifest listing each file processed, with the files written and the warnings found.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Split table
+            Arg::new("splits")
+                .long("splits")
+                .value_name("km|mi")
+                .help("Export a table of the time, pace, grade-adjusted pace, heart rate and elevation change for each kilometer or mile to a .splits.csv file, worked out from the records rather than the laps. The time in each pace zone is added if --pace-zones is given.")
+                .num_args(1)
+                .value_parser(utilities::SplitUnit::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // Pace zones
+            Arg::new("pace-zones")
+                .long("pace-zones")
+                .value_name("PACE|PACE,PACE,PACE,PACE")
+                .help("The pace zones used for the time in each zone in the splits CSV: either the threshold pace, with the zones starting at 129, 114, 106 and 99% of it, or the paces where zones 1 to 4 start, in min/km. Without it, there are no pace zones.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Heart rate zones
+            Arg::new("hr-zones")
+                .long("hr-zones")
+                .value_name("MAX|lthr:BPM|BPM,BPM,BPM,BPM")
+                .help("Work out the time in each heart rate zone in the summary and laps CSV from the records instead of using the times recorded by the device: either the maximum heart rate, with the zones starting at 60, 70, 80 and 90% of it, the lactate threshold heart rate as lthr:BPM, with the zones starting at 85, 90, 95 and 100% of it, or the heart rates where zones 1 to 4 start. Files without recorded times use a maximum heart rate of 190.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // GPS spikes
+            Arg::new("clean-gps")
+                .long("clean-gps")
+                .value_name("M/S")
+                .help("Find the GPS spikes, where the position jumps away faster than this speed in m/s and comes back within 5 points, and drop them. FIT records keep their other values and only lose the position. The default speed is 50 m/s. Without it, the spikes are only counted in the gps_outliers column of the summary.")
+                .num_args(0..=1)
+                .default_missing_value("50")
+                .value_parser(clap::value_parser!(f64))
+                .action(ArgAction::Set)
+        )
+        .arg( // Records format
+            Arg::new("format")
+                .long("format")
+                .value_name("csv|parquet|jsonl")
+                .help("The format of the records file: csv, parquet for loading large files straight into pandas, polars or DuckDB, or jsonl for one JSON object per line for jq and log pipelines. The other files are always CSV.")
+                .num_args(1)
+                .default_value("csv")
+                .value_parser(utilities::ExportFormat::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // Mean-maximal curve
+            Arg::new("mean-max")
+                .long("mean-max")
+                .help("Export the best average power and heart rate held over durations from 1 second to the length of the activity, every second up to a minute and more sparsely after that, to a .mean_max.csv file. TCX files only have the heart rate.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Heart rate scatter
+            Arg::new("hr-scatter")
+                .long("hr-scatter")
+                .help("Export the heart rate against the speed, pace and power averaged over each 30 seconds to a .hr_scatter.csv file, for plotting the heart rate at the same effort. The first 5 minutes, the stops and the outliers are left out. TCX files have no power.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // GeoJSON track
+            Arg::new("geojson")
+                .long("geojson")
+                .help("Export the track to a .geojson file as a GeoJSON FeatureCollection with a line for each lap, along with the start time, distance and sport of the lap, for maps such as Leaflet, Mapbox or QGIS.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Elevation profile
+            Arg::new("export-profile-svg")
+                .long("export-profile-svg")
+                .help("Export the elevation against the distance to a .profile.svg image, with the title, a grid and labelled axes, that opens in any browser and can go straight into a web page or Markdown document. The distances and elevations are in the unit system given with --units, or kilometers and meters.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Smart recording gaps
+            Arg::new("interpolate")
+                .long("interpolate")
+                .help("Fill in the gaps between the records of files made with smart recording with a record for every second, interpolated between the recorded ones. The records added are marked in the synthetic column. Gaps over 10 seconds are pauses and are left as they are.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Functional Threshold Power
+            Arg::new("ftp")
+                .long("ftp")
+                .value_name("WATTS")
+                .help("The Functional Threshold Power used for the Intensity Factor and Training Stress Score in the summary. The default is the threshold power recorded in each file.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .action(ArgAction::Set)
+        )
+        .arg( // Serial number hashing
+            Arg::new("hash-serials")
+                .long("hash-serials")
+                .help("Replace the device serial numbers in the output with a short hash, so devices can be told apart without revealing the serial numbers.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Unit system
+            Arg::new("units")
+                .long("units")
+                .value_name("metric|imperial")
+                .help("Convert the distances, heights and speeds in the exports and the printed summaries from meters and m/s to kilometers, meters and km/h, or miles, feet and mph. The columns are renamed to match, e.g. distance_km or speed_avg_mph.")
+                .num_args(1)
+                .value_parser(utilities::UnitSystem::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // Placeholder for values that aren't known
+            Arg::new("placeholder")
+                .long("placeholder")
+                .value_name("TEXT")
+                .help("Use this text for values that aren't known, e.g. a missing manufacturer or start time, instead of the defaults such as Unknown and 0000. Use \"\" to leave them blank.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Summary cache
+            Arg::new("cache")
+                .long("cache")
+                .value_name("FILE")
+                .help("Keep the file summaries in this cache file, so runs with --detail-off only parse the files that are new or changed since the last run.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Activity tags
+            Arg::new("tag")
+                .long("tag")
+                .value_name("TAG")
+                .help("Add this tag to each activity processed, e.g. race or brevet. The tags are kept in the tag file and added to the summary. May be given more than once.")
+                .num_args(1)
+                .action(ArgAction::Append)
+        )
+        .arg( // Tag file
+            Arg::new("tags-file")
+                .long("tags-file")
+                .value_name("FILE")
+                .help("Keep the tags of the activities in this file, keyed by the activity UUID, so they stay with the activity when the file is renamed or moved.")
+                .num_args(1)
+                .default_value(utilities::DEFAULT_TAG_FILE)
+                .action(ArgAction::Set)
+        )
+        .arg( // Output directory
+            Arg::new("output-dir")
+                .long("output-dir")
+                .value_name("DIR")
+                .help("Write the exported files for each activity to this directory instead of next to the input file. The summary file is still written where --summary-file says.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Folder per activity
+            Arg::new("subfolders")
+                .long("subfolders")
+                .help("Write the exported files for each activity to a folder named after the input file, e.g. running/running.laps.csv.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Output suffixes
+            Arg::new("output-suffix")
+                .long("output-suffix")
+                .value_name("SUFFIX=NEW")
+                .help("Name the exported files with another suffix, e.g. laps.csv=lap-table.csv. May be given more than once.")
+                .num_args(1)
+                .action(ArgAction::Append)
+        )
+        .arg( // Flush to disk
+            Arg::new("fsync")
+                .long("fsync")
+                .help("Flush each file to disk before it replaces the old one, so it survives a crash or power failure. Slower.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
 }
 
 #[cfg(test)]
@@ -80,6 +428,74 @@ mod tests {
             "--detail-off",
             "--summary-file",
             "summary.csv",
+            "--max-memory",
+            "512",
+            "--timeout",
+            "30",
+            "--keep-going=false",
+            "--min-duration",
+            "00:01:00",
+            "--min-distance",
+            "200m",
+            "--bundle",
+            "--split-summary-by",
+            "sport",
+            "--precision",
+            "2",
+            "--delimiter",
+            ";",
+            "--decimal-comma",
+            "--field-precision",
+            "latitude=6",
+            "--field-precision",
+            "longitude=6",
+            "--columns-version",
+            "1",
+            "--schema",
+            "v2",
+            "--csv-schema",
+            "--stats",
+            "--show-warnings",
+            "--manifest",
+            "manifest.json",
+            "--splits",
+            "mi",
+            "--pace-zones",
+            "4:30",
+            "--hr-zones",
+            "111,130,148,167",
+            "--mean-max",
+            "--hr-scatter",
+            "--geojson",
+            "--export-profile-svg",
+            "--interpolate",
+            "--clean-gps",
+            "40",
+            "--ftp",
+            "250",
+            "--format",
+            "parquet",
+            "--hash-serials",
+            "--altitude-source",
+            "standard",
+            "--cache",
+            "cache.json",
+            "--tag",
+            "race",
+            "--tag",
+            "brevet",
+            "--tags-file",
+            "tags.json",
+            "--output-dir",
+            "out",
+            "--subfolders",
+            "--output-suffix",
+            "laps.csv=lap-table.csv",
+            "--fsync",
+            "--placeholder",
+            "",
+            "--units",
+            "metric",
         ]);
         assert!(args.contains_id("read"));
         assert!(args.contains_id("debug"));
@@ -87,6 +503,112 @@ mod tests {
         assert!(args.contains_id("print-summary"));
         assert!(args.contains_id("detail-off"));
         assert!(args.contains_id("summary-file"));
+        assert_eq!(args.get_one::<u64>("max-memory"), Some(&512));
+        assert_eq!(args.get_one::<u64>("timeout"), Some(&30));
+        assert_eq!(args.get_one::<bool>("keep-going"), Some(&false));
+        assert_eq!(
+            args.get_one::<String>("min-duration").map(String::as_str),
+            Some("00:01:00")
+        );
+        assert_eq!(
+            args.get_one::<String>("min-distance").map(String::as_str),
+            Some("200m")
+        );
+        assert!(args.get_flag("bundle"));
+        assert_eq!(
+            args.get_one::<String>("split-summary-by")
+                .map(String::as_str),
+            Some("sport")
+        );
+        assert_eq!(args.get_one::<u32>("precision"), Some(&2));
+        assert_eq!(
+            args.get_one::<String>("delimiter").map(String::as_str),
+            Some(";")
+        );
+        assert!(args.get_flag("decimal-comma"));
+        assert!(args.get_flag("csv-schema"));
+        assert!(args.get_flag("stats"));
+        assert!(args.get_flag("show-warnings"));
+        assert_eq!(
+            args.get_one::<String>("manifest").map(String::as_str),
+            Some("manifest.json")
+        );
+        assert_eq!(
+            args.get_one::<String>("splits").map(String::as_str),
+            Some("mi")
+        );
+        assert_eq!(
+            args.get_one::<String>("pace-zones").map(String::as_str),
+            Some("4:30")
+        );
+        assert_eq!(
+            args.get_one::<String>("hr-zones").map(String::as_str),
+            Some("111,130,148,167")
+        );
+        assert!(args.get_flag("mean-max"));
+        assert!(args.get_flag("hr-scatter"));
+        assert!(args.get_flag("geojson"));
+        assert!(args.get_flag("export-profile-svg"));
+        assert!(args.get_flag("interpolate"));
+        assert_eq!(args.get_one::<u16>("ftp"), Some(&250));
+        assert_eq!(args.get_one::<f64>("clean-gps"), Some(&40.0));
+        assert_eq!(
+            args.get_one::<String>("format").map(String::as_str),
+            Some("parquet")
+        );
+        assert!(args.get_flag("hash-serials"));
+        assert_eq!(
+            args.get_one::<String>("placeholder").map(String::as_str),
+            Some("")
+        );
+        assert_eq!(
+            args.get_one::<String>("units").map(String::as_str),
+            Some("metric")
+        );
+        assert_eq!(
+            args.get_one::<String>("cache").map(String::as_str),
+            Some("cache.json")
+        );
+        assert_eq!(
+            args.get_many::<String>("tag")
+                .unwrap_or_default()
+                .collect::<Vec<_>>(),
+            ["race", "brevet"]
+        );
+        assert_eq!(
+            args.get_one::<String>("tags-file").map(String::as_str),
+            Some("tags.json")
+        );
+        assert_eq!(
+            args.get_one::<String>("output-dir").map(String::as_str),
+            Some("out")
+        );
+        assert!(args.get_flag("subfolders"));
+        assert!(args.get_flag("fsync"));
+        assert_eq!(
+            args.get_one::<String>("output-suffix").map(String::as_str),
+            Some("laps.csv=lap-table.csv")
+        );
+        assert_eq!(
+            args.get_one::<String>("altitude-source")
+                .map(String::as_str),
+            Some("standard")
+        );
+        assert_eq!(
+            args.get_many::<String>("field-precision")
+                .unwrap_or_default()
+                .count(),
+            2
+        );
+        assert_eq!(
+            args.get_one::<String>("columns-version")
+                .map(String::as_str),
+            Some("1")
+        );
+        assert_eq!(
+            args.get_one::<String>("schema").map(String::as_str),
+            Some("v2")
+        );
         assert_eq!(args.get_count("debug"), 2);
 
         // Test short form arguments
@@ -100,6 +622,9 @@ mod tests {
             "-o",
             "-s",
             "summary.csv",
+            "-m",
+            "512",
+            "-b",
         ]);
         assert!(args2.contains_id("read"));
         assert!(args2.contains_id("debug"));
@@ -107,6 +632,61 @@ mod tests {
         assert!(args2.contains_id("print-summary"));
         assert!(args2.contains_id("detail-off"));
         assert!(args2.contains_id("summary-file"));
+        assert_eq!(args2.get_one::<u64>("max-memory"), Some(&512));
+        assert!(args2.get_flag("bundle"));
         assert_eq!(args2.get_count("debug"), 2);
+
+        // Validation, which needs the records rather than the cached summaries
+        let args3 = build().get_matches_from(vec!["fit2csv", "test.fit", "--validate"]);
+        assert_eq!(args3.get_one::<f64>("validate"), Some(&5.0));
+        assert_eq!(args3.get_one::<bool>("keep-going"), Some(&true));
+        let args3 = build().get_matches_from(vec!["fit2csv", "test.fit", "--validate", "2"]);
+        assert_eq!(args3.get_one::<f64>("validate"), Some(&2.0));
+        assert!(build()
+            .try_get_matches_from(vec![
+                "fit2csv",
+                "test.fit",
+                "--validate",
+                "--cache",
+                "c.json"
+            ])
+            .is_err());
+
+        // Watching a directory, which runs until stopped and so has no summary file
+        let args4 = build().get_matches_from(vec![
+            "fit2csv",
+            "--watch",
+            "GARMIN/Activity",
+            "--ledger",
+            "ledger.txt",
+        ]);
+        assert_eq!(
+            args4.get_one::<String>("watch").map(String::as_str),
+            Some("GARMIN/Activity")
+        );
+        assert_eq!(
+            args4.get_one::<String>("ledger").map(String::as_str),
+            Some("ledger.txt")
+        );
+        let args5 = build().get_matches_from(vec![
+            "fit2csv",
+            "--watch",
+            "dir",
+            "-s",
+            "summary.csv",
+            "--manifest",
+            "manifest.json",
+        ]);
+        assert_eq!(
+            args5.get_one::<String>("summary-file").map(String::as_str),
+            Some("summary.csv")
+        );
+        assert_eq!(
+            args5.get_one::<String>("manifest").map(String::as_str),
+            Some("manifest.json")
+        );
+        assert!(build()
+            .try_get_matches_from(vec!["fit2csv", "test.fit", "--ledger", "ledger.txt"])
+            .is_err());
     }
 }
@@ -1,19 +1,33 @@
 use clap::parser::ValueSource;
 use env_logger::Target;
 use std::error::Error;
+use std::path::Path;
 
-use utilities::{FITActivities, FITActivity};
+use utilities::{
+    AltitudeSource, FITActivities, FITActivity, ProcessingOptions, ProcessingResult, SummarySplit,
+};
 mod cli;
 
+/// The ledger of the files exported by `--watch`, in the directory watched, unless `--ledger` is given.
+const DEFAULT_LEDGER: &str = ".fit2csv-processed";
+
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// This is where the magic happens.
-fn run() -> Result<(), Box<dyn Error>> {
+/// Returns the exit code, which is `EXIT_PARTIAL_FAILURE` if any of the files couldn't be processed.
+fn run() -> Result<i32, Box<dyn Error>> {
     // Set up the command line. Ref https://docs.rs/clap for details.
-    let cli_args = cli::build().get_matches();
+    let cli_args = utilities::with_config(cli::build()).get_matches();
 
     // Initialize logging
     let mut logbuilder = utilities::build_log(&cli_args);
-    logbuilder.target(Target::Stdout).init();
+    // Keep stdout for the schema when it's printed
+    logbuilder
+        .target(if cli_args.get_flag("csv-schema") {
+            Target::Stderr
+        } else {
+            Target::Stdout
+        })
+        .init();
 
     // If tracing, output the names of the files being processed
     for argument in cli_args
@@ -39,51 +53,331 @@ fn run() -> Result<(), Box<dyn Error>> {
         log::info!("Writing detail files.");
     }
 
+    let mut settings = utilities::Settings::default();
+
+    // Round the numbers in the exports if requested
+    settings.set_float_precision_from_args(&cli_args)?;
+    settings.set_output_naming_from_args(&cli_args)?;
+    settings.set_csv_format_from_args(&cli_args)?;
+    settings.set_sync_outputs(cli_args.get_flag("fsync"));
+
+    // Keep the CSV columns of an earlier version if requested
+    if let Some(version) = cli_args.get_one::<String>("columns-version") {
+        settings.set_columns_version(version.parse::<utilities::ColumnsVersion>()?);
+    }
+
+    // Write the summary JSON in the layout requested
+    if let Some(schema) = cli_args.get_one::<String>("schema") {
+        settings.set_summary_schema(schema.parse::<utilities::SummarySchema>()?);
+    }
+
+    // Reuse the summaries from earlier runs if requested
+    if let Some(cache) = cli_args.get_one::<String>("cache") {
+        utilities::open_summary_cache(cache)?;
+    }
+
+    // Read the tags of the activities, adding the tags given to each activity processed
+    let tags: Vec<String> = cli_args
+        .get_many::<String>("tag")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    if let Some(tags_file) = cli_args.get_one::<String>("tags-file") {
+        utilities::open_tag_store(tags_file, &tags)?;
+    }
+
+    // Hide the device serial numbers in the exports if requested
+    settings.set_hash_serials(cli_args.get_flag("hash-serials"));
+
+    // Show the distances and speeds in another unit system if requested
+    settings.set_unit_system(
+        cli_args
+            .get_one::<String>("units")
+            .map(|units| units.parse::<utilities::UnitSystem>())
+            .transpose()?,
+    );
+
+    // Use the same text for all the values that aren't known if requested
+    settings.set_placeholder(
+        cli_args
+            .get_one::<String>("placeholder")
+            .map(String::as_str),
+    );
+
+    // Work out the training load against the FTP given, rather than the one recorded in each file
+    settings.set_ftp(cli_args.get_one::<u16>("ftp").copied().map(f64::from));
+
+    // The pace zones for the time in each zone in the splits
+    if let Some(zones) = cli_args.get_one::<String>("pace-zones") {
+        settings.set_pace_zones(zones.parse::<utilities::PaceZoneLimits>()?);
+    }
+
+    // Drop the GPS spikes if requested
+    let clean_gps = cli_args.get_one::<f64>("clean-gps").copied();
+    if clean_gps.is_some_and(|speed| speed <= 0.0) {
+        return Err("The --clean-gps speed must be above 0 m/s.".into());
+    }
+    settings.set_gps_cleaning(clean_gps);
+
+    // Work out the time in the heart rate zones from the records with the zones requested
+    if let Some(zones) = cli_args.get_one::<String>("hr-zones") {
+        settings.set_hr_zones(zones.parse::<utilities::HrZoneLimits>()?);
+    }
+
+    // Check the session totals against the records if requested
+    let validate = cli_args.get_one::<f64>("validate").copied();
+    if validate.is_some_and(|pct| pct < 0.0) {
+        return Err("The --validate tolerance can't be negative.".into());
+    }
+
+    // Pick the altitude field to read from the records
+    if let Some(source) = cli_args.get_one::<String>("altitude-source") {
+        settings.set_altitude_source(source.parse::<AltitudeSource>()?);
+    }
+
+    // Use the settings for the files processed
+    let _settings = settings.apply();
+
+    // Print the columns of the CSV files for downstream tooling if requested
+    if cli_args.get_flag("csv-schema") {
+        utilities::write_csv_schema(
+            utilities::CsvFile::of_format(utilities::ActivityFormat::Fit),
+            std::io::stdout().lock(),
+        )?;
+        return Ok(0);
+    }
+
     ///////////////////////////////////
     // Working section
 
+    // How to process each file
+    let options = ProcessingOptions {
+        export_detail: cli_args.value_source("detail-off") != Some(ValueSource::CommandLine),
+        bundle: cli_args.get_flag("bundle"),
+        parse_stats: cli_args.get_flag("stats"),
+        max_memory: cli_args.get_one::<u64>("max-memory").copied(),
+        timeout: cli_args
+            .get_one::<u64>("timeout")
+            .map(|secs| std::time::Duration::from_secs(*secs)),
+        min_activity: utilities::min_activity_from_args(&cli_args)?,
+        splits: cli_args
+            .get_one::<String>("splits")
+            .map(|unit| unit.parse::<utilities::SplitUnit>())
+            .transpose()?,
+        mean_max: cli_args.get_flag("mean-max"),
+        hr_scatter: cli_args.get_flag("hr-scatter"),
+        geojson: cli_args.get_flag("geojson"),
+        profile_svg: cli_args.get_flag("export-profile-svg"),
+        interpolate: cli_args.get_flag("interpolate"),
+        format: cli_args
+            .get_one::<String>("format")
+            .map(|format| format.parse::<utilities::ExportFormat>())
+            .transpose()?
+            .unwrap_or_default(),
+        ..ProcessingOptions::default()
+    };
+
+    // Split the summary into several files if requested
+    let split = cli_args
+        .get_one::<String>("split-summary-by")
+        .map(|s| s.parse::<SummarySplit>())
+        .transpose()?;
+
+    // Keep exporting the files synced into the directory if requested, until stopped
+    if let Some(dir) = cli_args.get_one::<String>("watch") {
+        let ledger = cli_args.get_one::<String>("ledger").map_or_else(
+            || {
+                Path::new(dir)
+                    .join(DEFAULT_LEDGER)
+                    .to_string_lossy()
+                    .to_string()
+            },
+            String::clone,
+        );
+
+        // What was done for each file since the watching started, for the manifest
+        let mut watched_results: Vec<ProcessingResult> = Vec::new();
+
+        return utilities::watch_directory(
+            dir,
+            &ledger,
+            &[utilities::ActivityFormat::Fit],
+            |filename| {
+                let mut activities = FITActivities::default();
+                let mut results = Vec::new();
+                process_file(
+                    filename,
+                    &cli_args,
+                    &options,
+                    validate,
+                    &mut activities,
+                    &mut results,
+                )?;
+                utilities::save_summary_cache()?;
+                utilities::save_tag_store()?;
+                let error = results.iter().find_map(|result| result.error.clone());
+
+                // Add the sessions to the summary, unless the file is to be tried again
+                if error.is_none() {
+                    if let Some(split) = split {
+                        for summary_file in
+                            activities.append_summary_csv_split(sessionfile, split)?
+                        {
+                            log::info!("Summary information added to: {summary_file}");
+                        }
+                    } else if cli_args.value_source("summary-file")
+                        == Some(ValueSource::CommandLine)
+                    {
+                        activities.append_summary_csv(sessionfile)?;
+                        log::info!("Summary information added to: {sessionfile}");
+                    }
+                }
+
+                // The manifest is rewritten with the latest result for each file
+                if let Some(manifest) = cli_args.get_one::<String>("manifest") {
+                    watched_results.retain(|watched| {
+                        results.iter().all(|result| result.input != watched.input)
+                    });
+                    watched_results.extend(results);
+                    utilities::export_manifest(&watched_results, manifest)?;
+                    log::info!("Manifest written to: {manifest}");
+                }
+
+                // Files that couldn't be processed are tried again the next time they change
+                match error {
+                    Some(error) => Err(error.into()),
+                    None => Ok(Vec::new()),
+                }
+            },
+        )
+        .map(|()| 0);
+    }
+
     // Create an empty placeholder for all the activities
     let mut activities = FITActivities::default();
 
-    for filename in cli_args
-        .get_many::<String>("read")
-        .unwrap_or_default()
-        .map(std::string::String::as_str)
-    {
-        log::info!("Processing file: {filename}");
+    // What was done for each file, for the manifest
+    let mut results = Vec::new();
 
-        // Parse the FIT file
-        let activity = FITActivity::from_file(filename)?;
+    // Zip archives are read as the activity files inside them
+    let filenames = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
 
-        // Output the files
-        if cli_args.value_source("print-summary") == Some(ValueSource::CommandLine) {
-            activity.session.print_summary();
+    for filename in filenames.iter().map(std::string::String::as_str) {
+        process_file(
+            filename,
+            &cli_args,
+            &options,
+            validate,
+            &mut activities,
+            &mut results,
+        )?;
+    }
+
+    // Export the summary information
+    if let Some(split) = split {
+        for summary_file in activities.export_summary_csv_split(sessionfile, split)? {
+            log::info!("Summary information written to: {summary_file}");
         }
+    } else if cli_args.value_source("summary-file") == Some(ValueSource::CommandLine) {
+        log::info!("Summary information written to: {sessionfile}");
+        activities.export_summary_csv(sessionfile)?;
+    }
 
-        // Export the data if requested
-        if cli_args.value_source("detail-off") != Some(ValueSource::CommandLine) {
-            activity.export()?;
+    // Flag the activities that overlap in time, since adding up both would count the distance twice
+    for overlap in utilities::flag_overlaps(&mut results, &activities.time_windows()) {
+        if cli_args.get_flag("show-warnings") {
+            println!("Warning: {overlap}");
         }
+    }
 
-        // Push the session onto the summary vector
-        activities.activities_list.push(activity);
+    // What was written, to check nothing was dropped
+    utilities::log_written(&results);
+
+    if let Some(manifest) = cli_args.get_one::<String>("manifest") {
+        utilities::export_manifest(&results, manifest)?;
+        log::info!("Manifest written to: {manifest}");
     }
 
-    // Export the summary information
-    if cli_args.value_source("summary-file") == Some(ValueSource::CommandLine) {
-        log::info!("Summary information written to: {sessionfile}");
-        activities.export_summary_csv(sessionfile)?;
+    utilities::save_summary_cache()?;
+    utilities::save_tag_store()?;
+
+    // Everything is a-okay in the end, unless some of the files couldn't be processed
+    Ok(utilities::report_failures(&results))
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Parses a FIT file and exports the details, adding its activities to the summary and what was done to the results.
+fn process_file(
+    filename: &str,
+    cli_args: &clap::ArgMatches,
+    options: &ProcessingOptions,
+    validate: Option<f64>,
+    activities: &mut FITActivities,
+    results: &mut Vec<ProcessingResult>,
+) -> Result<(), Box<dyn Error>> {
+    log::info!("Processing file: {filename}");
+
+    // Parse the FIT file and export the data if requested, skipping it if it takes too long or can't be processed
+    let (file_activities, mut result) =
+        match utilities::process_with_timeout(filename, options, utilities::process_fit_file) {
+            Ok(processed) => processed,
+            Err(err) if cli_args.get_one::<bool>("keep-going") != Some(&false) => {
+                (None, ProcessingResult::failed(filename, &err))
+            }
+            Err(err) => return Err(err.into()),
+        };
+    log::debug!("main::run() -- {result:?}");
+
+    // Check the session totals against the records, adding the differences to the warnings of the file
+    if let Some(tolerance) = validate {
+        for validation in file_activities
+            .iter()
+            .flatten()
+            .flat_map(FITActivity::validate)
+        {
+            if validation.num_records == 0 {
+                log::warn!("{filename}: No records to validate the session totals against.");
+                continue;
+            }
+            if !cli_args.get_flag("quiet") {
+                validation.print(tolerance);
+            }
+            for deviation in validation.deviations(tolerance) {
+                log::warn!("{filename}: {deviation}");
+                result.warnings.push(deviation);
+            }
+        }
+    }
+    if cli_args.get_flag("stats") {
+        result.print_stats();
+    } else if cli_args.get_flag("show-warnings") {
+        result.print_warnings();
+    }
+    results.push(result);
+
+    // A file may hold several chained FIT files, each with its own summary
+    for activity in file_activities.unwrap_or_default() {
+        if cli_args.value_source("print-summary") == Some(ValueSource::CommandLine) {
+            activity.session.print_summary();
+        }
+
+        // Push the session onto the summary vector
+        activities.activities_list.push(activity);
     }
 
-    // Everything is a-okay in the end
     Ok(())
-} // fn run()
+}
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// The actual executable function that gets called when the program in invoked.
 fn main() {
     std::process::exit(match run() {
-        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Ok(code) => code, // everying is hunky dory, or some of the files couldn't be processed
         Err(err) => {
             log::error!("{}", err.to_string().replace('\"', ""));
             1 // exit with a non-zero return code, indicating a problem
@@ -0,0 +1,85 @@
+use env_logger::Target;
+use std::error::Error;
+use std::path::Path;
+use utilities::ActivityFormat;
+
+mod cli;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+    let force = cli_args.get_flag("force");
+
+    // Initialize logging
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stdout).init();
+
+    let mut settings = utilities::Settings::default();
+    for setting in cli_args
+        .get_many::<String>("track-color")
+        .unwrap_or_default()
+    {
+        settings.set_track_color(setting)?;
+    }
+    if let Some(mapping) = cli_args.get_one::<String>("lap-mapping") {
+        settings.set_lap_mapping(mapping.parse::<utilities::LapMapping>()?);
+    }
+    let _settings = settings.apply();
+
+    let expanded = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+    let filenames = expanded.iter().map(std::string::String::as_str);
+    log::trace!("main::run() -- Files: {filenames:?}");
+
+    let mut total_files: usize = 0;
+    let mut converted_files: usize = 0;
+
+    for filename in filenames {
+        total_files += 1;
+        if ActivityFormat::from_filename(filename) != Some(ActivityFormat::Fit) {
+            log::warn!("{filename}: Not a FIT file. Skipping.");
+            continue;
+        }
+
+        let output = utilities::set_extension(filename, "gpx");
+        if !force && Path::new(&output).exists() {
+            log::warn!("{output} already exists. Use --force to overwrite it. Skipping.");
+            continue;
+        }
+
+        log::debug!("Converting {filename} to {output}");
+        utilities::convert_file(filename, &output)?;
+        log::info!("{filename} converted to {output}");
+        converted_files += 1;
+    }
+
+    if cli_args.get_flag("print-summary") {
+        log::info!("Total files examined:        {total_files:6}");
+        log::info!("Files converted:             {converted_files:6}");
+        log::info!(
+            "Files skipped:               {:6}",
+            total_files - converted_files
+        );
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
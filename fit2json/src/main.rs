@@ -1,6 +1,6 @@
 //! Read one or more FIT files and dump their contents as JSON
 
-use std::{error::Error, fs::File, path::PathBuf};
+use std::{collections::HashSet, error::Error, path::PathBuf};
 use structopt::StructOpt;
 
 // Application-specific types
@@ -25,6 +25,18 @@ struct Cli {
     /// a "-" as the output file name will result in all content being printed to STDOUT.
     #[structopt(short, long, parse(from_os_str))]
     output: Option<PathBuf>,
+
+    /// Output format: json writes a single JSON array, while jsonl writes one JSON object per message and line (with
+    /// a '.jsonl' extension), for jq, Elasticsearch and log pipelines.
+    #[structopt(short, long, default_value = "json", possible_values = &["json", "jsonl"])]
+    format: types::OutputFormat,
+
+    /// Name each output file from a pattern using the fitrename tokens, e.g. "%stem-%start_time" or
+    /// "{stem}-{start_time}.json". %stem is the name of the FIT file without its extension. The files are written
+    /// alongside the FIT files, or in the output directory if one is given. The extension is added if the name
+    /// doesn't end with it.
+    #[structopt(short = "p", long)]
+    output_pattern: Option<String>,
 }
 
 /// Performs the actual work.
@@ -41,35 +53,70 @@ fn run() -> Result<(), Box<dyn Error>> {
         .map_or(types::OutputLocation::Inplace, types::OutputLocation::new);
     let collect_all = matches!(output_loc, types::OutputLocation::LocalFile(_));
 
+    // The pattern names one file per FIT file, so it can't be used when everything goes to one place
+    if cli.output_pattern.is_some()
+        && (collect_all
+            || matches!(output_loc, types::OutputLocation::Stdout)
+            || cli.files.is_empty())
+    {
+        return Err(
+            "--output-pattern needs FIT files to read, and the output to be a directory if given."
+                .into(),
+        );
+    }
+
     // If no files have been provided, read from STDIN
     if cli.files.is_empty() {
         log::info!("No files supplied. Reading from STDIN.");
         output_loc.write_json_file(
             &PathBuf::from("<stdin>"),
+            None,
             fitparser::from_reader(&mut std::io::stdin())?,
+            cli.format,
         )?;
         return Ok(());
     }
 
     // Read each FIT file and output it
     let mut all_fit_data: Vec<fitparser::FitDataRecord> = Vec::new();
-    for file in cli.files {
+    let mut written = HashSet::new();
+    let names: Vec<String> = cli
+        .files
+        .iter()
+        .map(|file| file.to_string_lossy().to_string())
+        .collect();
+    let files = utilities::expand_archives(names.iter().map(String::as_str));
+    for file in files.iter().map(PathBuf::from) {
         // open file and parse data
         log::info!("Processing file: {}", &file.to_str().unwrap_or_default());
-        let mut fp = File::open(&file)?;
+        let mut fp = utilities::open_input(&file.to_string_lossy())?;
         let mut data = fitparser::from_reader(&mut fp)?;
 
         // output a single fit file's data into a single output file
         if collect_all {
             all_fit_data.append(&mut data);
         } else {
-            output_loc.write_json_file(&file, data)?;
+            let name = cli
+                .output_pattern
+                .as_deref()
+                .map(|pattern| types::pattern_name(pattern, &file, cli.format))
+                .transpose()?;
+            if let Some(name) = &name {
+                if !written.insert(name.clone()) {
+                    return Err(format!(
+                        "{}: The output pattern gives {name}, which has already been written.",
+                        file.display()
+                    )
+                    .into());
+                }
+            }
+            output_loc.write_json_file(&file, name.as_deref(), data, cli.format)?;
         }
     }
     // output fit data from all files into a single file
     if collect_all {
         log::info!("Summary information collected in specified output location.");
-        output_loc.write_json_file(&PathBuf::new(), all_fit_data)?;
+        output_loc.write_json_file(&PathBuf::new(), None, all_fit_data, cli.format)?;
     }
 
     Ok(())
@@ -80,10 +127,11 @@ fn main() {
     std::process::exit(match run() {
         Ok(()) => 0,
         Err(err) => {
-            Builder::new()
+            // The logger is already set up unless the command line couldn't be read
+            let _ = Builder::new()
                 .filter_level(LevelFilter::Error)
                 .target(Target::Stdout)
-                .init();
+                .try_init();
             log::error!("{}", err.to_string().replace('\"', ""));
             1
         }
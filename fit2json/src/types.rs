@@ -4,9 +4,9 @@ use serde::Serialize;
 use std::{
     collections::BTreeMap,
     error::Error,
-    fs::File,
     io::prelude::*,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 /// Alternate serialization format
@@ -30,6 +30,39 @@ impl FitDataMap {
     }
 }
 
+/// Output format alternatives
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A single JSON array holding all the messages
+    #[default]
+    Json,
+
+    /// JSON Lines, one JSON object per message and line
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// The file extension used for the format
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            _ => Err(format!("Unknown format {s}. Use json or jsonl.")),
+        }
+    }
+}
+
 /// Output location alternatives
 #[derive(Clone, Debug)]
 pub enum OutputLocation {
@@ -64,8 +97,12 @@ impl OutputLocation {
     ///
     /// `filename: &Path` -- The file(s) or directory where we wish to save.
     ///
+    /// `name: Option<&str>` -- The name of the output file from `--output-pattern`, used instead of the FIT file name.
+    ///
     /// `data: Vec<fitparser::FitDataRecord>` -- A vector (list) of `FitDataRecords`
     ///
+    /// `format: OutputFormat` -- Whether to write a single JSON array or one JSON object per line.
+    ///
     /// # Returns
     ///
     /// - `Ok(()` if everything went well.
@@ -73,31 +110,114 @@ impl OutputLocation {
     pub fn write_json_file(
         &self,
         filename: &Path,
+        name: Option<&str>,
         data: Vec<fitparser::FitDataRecord>,
+        format: OutputFormat,
     ) -> Result<(), Box<dyn Error>> {
         // convert data to a name: {value, units} map before serializing
         let data: Vec<FitDataMap> = data.into_iter().map(FitDataMap::new).collect();
-        let json = serde_json::to_string(&data)?;
+        let mut json = match format {
+            OutputFormat::Json => serde_json::to_string(&data)?,
+            OutputFormat::Jsonl => data
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n"),
+        };
 
         // Figure out where to send the output
-        let outname = match self {
-            Self::Inplace => filename.with_extension("json"),
-            Self::LocalDirectory(dest) => dest
-                .clone()
-                .join(filename.file_name().unwrap_or_default())
-                .with_extension("json"),
-            Self::LocalFile(dest) => dest.clone(),
-            Self::Stdout => {
+        let outname = match (self, name) {
+            (Self::Inplace, Some(name)) => filename.with_file_name(name),
+            (Self::LocalDirectory(dest), Some(name)) => dest.join(name),
+            (Self::Inplace, None) => PathBuf::from(utilities::set_extension(
+                &filename.to_string_lossy(),
+                format.extension(),
+            )),
+            (Self::LocalDirectory(dest), None) => dest.join(utilities::set_extension(
+                &filename.file_name().unwrap_or_default().to_string_lossy(),
+                format.extension(),
+            )),
+            (Self::LocalFile(dest), _) => dest.clone(),
+            (Self::Stdout, _) => {
                 println!("{json}");
                 return Ok(());
             }
         };
 
         // Write the data to the selected output and return the result.
-        let mut fp = File::create(outname)?;
-        match fp.write_all(json.as_bytes()) {
-            Ok(()) => Ok(()),
-            Err(e) => Err(Box::new(e)),
+        if let Some(parent) = outname
+            .parent()
+            .filter(|parent| name.is_some() && !parent.exists())
+        {
+            std::fs::create_dir_all(parent)?;
         }
+        if format == OutputFormat::Jsonl {
+            json.push('\n');
+        }
+        utilities::write_atomically(&outname, |file| Ok(file.write_all(json.as_bytes())?))
+    }
+}
+
+/// The name of the output file for the FIT file from the output pattern. The pattern can use the fitrename tokens,
+/// e.g. `%year` or `{start_time}`, and `%stem` for the name of the FIT file without its extension.
+///
+/// # Parameters
+///
+/// `pattern: &str` -- The output pattern, e.g. `{stem}-{start_time}.json`.
+///
+/// `filename: &Path` -- The FIT file the output is written for.
+///
+/// `format: OutputFormat` -- The output format, whose extension is added if the name doesn't end with it.
+///
+/// # Returns
+///
+/// - The name of the output file, relative to where it is written.
+/// - `Error` if the FIT file can't be read.
+pub fn pattern_name(
+    pattern: &str,
+    filename: &Path,
+    format: OutputFormat,
+) -> Result<String, Box<dyn Error>> {
+    let mut values = utilities::fit_to_hashmap(&filename.to_string_lossy())?;
+    values.insert(
+        "%stem".to_string(),
+        utilities::set_extension(
+            &filename.file_name().unwrap_or_default().to_string_lossy(),
+            "",
+        ),
+    );
+
+    let mut name = utilities::fill_template(pattern, &values);
+    if !utilities::has_extension(&name, format.extension()) {
+        name.push('.');
+        name.push_str(format.extension());
+    }
+
+    Ok(name)
+}
+
+#[cfg(test)]
+/// Tests for the types module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test naming the output file from the pattern
+    fn test_pattern_name() {
+        let filename = Path::new("../data/rowing.fit");
+        let name = pattern_name("{stem}-{start_time}", filename, OutputFormat::Json).unwrap();
+        assert!(name.starts_with("rowing-20"));
+        assert_eq!(name.len(), "rowing-20220210T065010.json".len());
+
+        assert_eq!(
+            pattern_name("%stem.jsonl", filename, OutputFormat::Jsonl).unwrap(),
+            "rowing.jsonl"
+        );
+        assert!(pattern_name(
+            "%stem",
+            Path::new("../data/missing.fit"),
+            OutputFormat::Json
+        )
+        .is_err());
     }
 }
@@ -0,0 +1,146 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        // .author(clap::crate_authors!("\n"))
+        .long_about("This program will find the activities saved more than once in .fit, .gpx and .tcx files, e.g. when a head unit exports both FIT and TCX. Two activities are copies if they start within a minute of each other, their durations are within a minute and their distances within 2% (see the --*-tolerance options), whatever their formats. Of each set of copies, the FIT file is kept, or the TCX file if there is no FIT file. The copies are only reported unless --delete or --move-to is given. Files holding more than one activity, e.g. multisport FIT files, are never marked redundant.")
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("One or more .fit, .gpx or .tcx file(s), or directories of them, to look for copies in. Wildcards and multiple files (e.g. 2019*.fit 2019*.tcx) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // How far apart the start times may be
+            Arg::new("start-tolerance")
+                .long("start-tolerance")
+                .value_name("SECONDS")
+                .help("The most the start times of two copies may differ by, in seconds.")
+                .num_args(1)
+                .default_value("60")
+                .value_parser(clap::value_parser!(f64))
+                .action(ArgAction::Set)
+        )
+        .arg( // How far apart the durations may be
+            Arg::new("duration-tolerance")
+                .long("duration-tolerance")
+                .value_name("SECONDS")
+                .help("The most the durations of two copies may differ by, in seconds.")
+                .num_args(1)
+                .default_value("60")
+                .value_parser(clap::value_parser!(f64))
+                .action(ArgAction::Set)
+        )
+        .arg( // How far apart the distances may be
+            Arg::new("distance-tolerance")
+                .long("distance-tolerance")
+                .value_name("PERCENT")
+                .help("The most the distances of two copies may differ by, in percent of the longer distance.")
+                .num_args(1)
+                .default_value("2")
+                .value_parser(clap::value_parser!(f64))
+                .action(ArgAction::Set)
+        )
+        .arg( // Delete the redundant copies
+            Arg::new("delete")
+                .long("delete")
+                .help("Delete the redundant copies.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Move the redundant copies
+            Arg::new("move-to")
+                .short('m')
+                .long("move-to")
+                .value_name("DIRECTORY")
+                .help("Move the redundant copies to this directory, which is created if needed.")
+                .num_args(1)
+                .conflicts_with("delete")
+                .action(ArgAction::Set)
+        )
+        .arg( // Dry-run
+            Arg::new("dry-run")
+                .short('r')
+                .long("dry-run")
+                .help("Perform a dry-run. This will output which copies would be deleted or moved without deleting or moving them.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "fitdedup",
+            "rides",
+            "ride.fit",
+            "--start-tolerance",
+            "30",
+            "--duration-tolerance",
+            "120",
+            "--distance-tolerance",
+            "5",
+            "--delete",
+            "--dry-run",
+            "--debug",
+            "--debug",
+            "--quiet",
+        ]);
+
+        assert_eq!(args.get_many::<String>("read").unwrap().count(), 2);
+        assert_eq!(args.get_one::<f64>("start-tolerance"), Some(&30.0));
+        assert_eq!(args.get_one::<f64>("duration-tolerance"), Some(&120.0));
+        assert_eq!(args.get_one::<f64>("distance-tolerance"), Some(&5.0));
+        assert!(args.get_flag("delete"));
+        assert!(args.get_flag("dry-run"));
+        assert_eq!(args.get_count("debug"), 2);
+        assert!(args.get_flag("quiet"));
+
+        // Short form and defaults
+        let args2 = build().get_matches_from(vec!["fitdedup", "rides", "-m", "dupes", "-r", "-d"]);
+
+        assert_eq!(
+            args2.get_one::<String>("move-to").map(String::as_str),
+            Some("dupes")
+        );
+        assert_eq!(args2.get_one::<f64>("start-tolerance"), Some(&60.0));
+        assert_eq!(args2.get_one::<f64>("distance-tolerance"), Some(&2.0));
+        assert!(!args2.get_flag("delete"));
+        assert!(args2.get_flag("dry-run"));
+        assert_eq!(args2.get_count("debug"), 1);
+
+        assert!(build()
+            .try_get_matches_from(vec!["fitdedup", "rides", "--delete", "--move-to", "dupes"])
+            .is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use env_logger::Target;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use utilities::{DedupTolerance, ProcessingOptions};
+
+mod cli;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+    let dry_run = cli_args.get_flag("dry-run");
+    let delete = cli_args.get_flag("delete");
+    let move_to = cli_args.get_one::<String>("move-to").map(Path::new);
+
+    // Initialize logging
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stdout).init();
+
+    let tolerance = DedupTolerance {
+        start_sec: cli_args
+            .get_one::<f64>("start-tolerance")
+            .copied()
+            .unwrap_or(60.0),
+        duration_sec: cli_args
+            .get_one::<f64>("duration-tolerance")
+            .copied()
+            .unwrap_or(60.0),
+        distance_pct: cli_args
+            .get_one::<f64>("distance-tolerance")
+            .copied()
+            .unwrap_or(2.0),
+    };
+    if tolerance.start_sec < 0.0 || tolerance.duration_sec < 0.0 || tolerance.distance_pct < 0.0 {
+        return Err("The tolerances can't be negative.".into());
+    }
+
+    let filenames = utilities::activity_files(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(String::as_str),
+        false,
+    )?;
+    log::trace!("main::run() -- Files: {filenames:?}");
+
+    // Only the summaries are needed
+    let options = ProcessingOptions {
+        export_detail: false,
+        ..ProcessingOptions::default()
+    };
+    let mut activities = Vec::new();
+    for filename in &filenames {
+        match utilities::activity_summaries(filename, &options) {
+            Ok(summaries) => activities.extend(summaries),
+            Err(err) => log::warn!("{filename}: Skipped. {err}"),
+        }
+    }
+
+    let sets = utilities::duplicate_sets(&activities, &tolerance);
+    for set in &sets {
+        println!("{set}");
+    }
+    let num_redundant: usize = sets.iter().map(|set| set.redundant.len()).sum();
+    log::info!(
+        "{} files read, {num_redundant} redundant copies of {} activities found.",
+        filenames.len(),
+        sets.len()
+    );
+
+    if dry_run && (delete || move_to.is_some()) {
+        log::info!("Dry-run. Will not delete or move any files.");
+        return Ok(());
+    }
+    for filename in sets.iter().flat_map(|set| &set.redundant) {
+        if utilities::is_archive_member(filename) {
+            log::warn!("{filename} is inside an archive. Not deleted or moved.");
+            continue;
+        }
+        if delete {
+            fs::remove_file(filename)?;
+            log::info!("{filename} deleted.");
+        } else if let Some(dir) = move_to {
+            fs::create_dir_all(dir)?;
+            let target = dir.join(Path::new(filename).file_name().unwrap_or_default());
+            if target.exists() {
+                log::warn!("{} already exists. {filename} not moved.", target.display());
+                continue;
+            }
+            fs::rename(filename, &target)?;
+            log::info!("{filename} moved to {}.", target.display());
+        }
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
@@ -0,0 +1,156 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        // .author(clap::crate_authors!("\n"))
+        .long_about("This program will cut a single lap or a time range out of one or more .fit, .gpx or .tcx files and write it to a new file, with the summary worked out again for the part cut out. The new file is named after the original, e.g. running.lap3.fit or running.extract.gpx, unless --output is given. FIT files are cut into FIT files. GPX and TCX files are cut into the same format, or into the other one if --output says so. The laps of GPX files are their track segments.")
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("One or more .fit, .gpx or .tcx file(s) to cut from. Wildcards and multiple_occurrences files (e.g. 2019*.fit 2020*.fit) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Print summary information
+            Arg::new("print-summary")
+                .short('s')
+                .long("print-summary")
+                .help("Print the summary of each part cut out.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Overwrite existing files
+            Arg::new("force")
+                .short('f')
+                .long("force")
+                .help("Overwrite files that already exist. Without this, the files that would be overwritten are skipped.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // The lap to cut out
+            Arg::new("lap")
+                .short('l')
+                .long("lap")
+                .value_name("NUMBER")
+                .help("The lap to cut out, numbered from 1.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .conflicts_with_all(["from", "to"])
+                .required_unless_present_any(["from", "to"])
+                .action(ArgAction::Set)
+        )
+        .arg( // The start of the time range
+            Arg::new("from")
+                .long("from")
+                .value_name("hh:mm:ss")
+                .help("Cut out from this far into the activity, e.g. 00:10:00. Without it, the part starts at the start.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // The end of the time range
+            Arg::new("to")
+                .long("to")
+                .value_name("hh:mm:ss")
+                .help("Cut out to this far into the activity, e.g. 00:25:00. Without it, the part ends at the finish.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // The file to write
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("The file to write the part to. The extension gives the format. Only for a single file.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "--read",
+            "test.fit",
+            "--debug",
+            "--debug",
+            "--quiet",
+            "--print-summary",
+            "--force",
+            "--from",
+            "00:10:00",
+            "--to",
+            "00:25:00",
+            "--output",
+            "interval.fit",
+        ]);
+
+        assert!(args.contains_id("read"));
+        assert_eq!(args.get_count("debug"), 2);
+        assert!(args.get_flag("quiet"));
+        assert!(args.get_flag("print-summary"));
+        assert!(args.get_flag("force"));
+        assert_eq!(
+            args.get_one::<String>("from").map(String::as_str),
+            Some("00:10:00")
+        );
+        assert_eq!(
+            args.get_one::<String>("to").map(String::as_str),
+            Some("00:25:00")
+        );
+        assert_eq!(
+            args.get_one::<String>("output").map(String::as_str),
+            Some("interval.fit")
+        );
+        assert!(!args.contains_id("lap"));
+
+        // Short form
+        let args2 = build().get_matches_from(vec![
+            "--read", "test.fit", "-d", "-q", "-s", "-f", "-l", "3", "-o", "lap.fit",
+        ]);
+
+        assert_eq!(args2.get_count("debug"), 1);
+        assert!(args2.get_flag("quiet"));
+        assert!(args2.get_flag("print-summary"));
+        assert!(args2.get_flag("force"));
+        assert_eq!(args2.get_one::<u64>("lap"), Some(&3));
+        assert!(args2.contains_id("output"));
+
+        // A lap and a time range can't both be given, and one of them must be
+        assert!(build()
+            .try_get_matches_from(vec!["--read", "test.fit", "--lap", "3", "--to", "00:25:00"])
+            .is_err());
+        assert!(build()
+            .try_get_matches_from(vec!["--read", "test.fit"])
+            .is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use env_logger::Target;
+use std::error::Error;
+use std::path::Path;
+use utilities::{Duration, ExtractRange};
+
+mod cli;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+    let force = cli_args.get_flag("force");
+    let print_summary = cli_args.get_flag("print-summary");
+
+    // Initialize logging
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stdout).init();
+
+    let range = match cli_args.get_one::<u64>("lap") {
+        Some(lap) => ExtractRange::Lap(usize::try_from(*lap)?),
+        None => ExtractRange::Time {
+            from: cli_args
+                .get_one::<String>("from")
+                .map(|from| from.parse::<Duration>())
+                .transpose()?,
+            to: cli_args
+                .get_one::<String>("to")
+                .map(|to| to.parse::<Duration>())
+                .transpose()?,
+        },
+    };
+    log::debug!("main::run() -- Cutting out {range}");
+
+    let filenames = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+    log::trace!("main::run() -- Files: {filenames:?}");
+
+    let output = cli_args.get_one::<String>("output");
+    if output.is_some() && filenames.len() > 1 {
+        return Err("--output can only be used with a single file.".into());
+    }
+
+    for filename in filenames.iter().map(std::string::String::as_str) {
+        let output = output.cloned().unwrap_or_else(|| {
+            let extension = utilities::get_extension(filename).to_lowercase();
+            utilities::set_extension(filename, &format!("{}.{extension}", range.suffix()))
+        });
+        if !force && Path::new(&output).exists() {
+            log::warn!("{output} already exists. Use --force to overwrite it. Skipping.");
+            continue;
+        }
+
+        log::debug!("Cutting {range} out of {filename} into {output}");
+        let extracted = utilities::extract_file(filename, &output, &range)?;
+        log::info!("{range} of {filename} written to {output}");
+        if print_summary {
+            extracted.print(false);
+        }
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
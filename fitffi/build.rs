@@ -0,0 +1,24 @@
+//! Generates the C header for the library from `src/lib.rs`.
+
+use std::path::Path;
+
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let crate_dir = Path::new(&crate_dir);
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    // Only the library source is parsed, so the header can be generated without resolving the whole workspace
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap_or_default();
+    match cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/lib.rs"))
+        .generate()
+    {
+        Ok(bindings) => {
+            bindings.write_to_file(crate_dir.join("include/fitutils.h"));
+        }
+        // Keep the header that's there rather than failing the build
+        Err(err) => println!("cargo:warning=Unable to generate the C header: {err}"),
+    }
+}
@@ -0,0 +1,57 @@
+#ifndef FITUTILS_H
+#define FITUTILS_H
+
+/* Generated by cbindgen from fitffi/src/lib.rs -- do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Returned by `fitutils_convert()` when the conversion succeeded.
+#define FITUTILS_OK 0
+
+// Returned by `fitutils_convert()` when the conversion failed.
+#define FITUTILS_ERROR -1
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Reads a FIT, GPX or TCX file and returns the activity as a JSON document.
+//
+// Returns `NULL` if the file can't be read. The returned string must be freed using `fitutils_string_free()`.
+//
+// # Safety
+//
+// `path` must be `NULL` or point to a valid, NUL-terminated UTF-8 string.
+char *fitutils_parse_to_json(const char *path);
+
+// Converts a FIT, GPX or TCX file to a JSON (`.json`) or CSV (`.csv`) file, depending on the extension of `output`.
+// TCX files can also be converted to GPX (`.gpx`), and GPX files to TCX (`.tcx`).
+//
+// Returns `FITUTILS_OK` (0) if the conversion succeeded and `FITUTILS_ERROR` (-1) if not.
+//
+// # Safety
+//
+// `input` and `output` must be `NULL` or point to valid, NUL-terminated UTF-8 strings.
+int fitutils_convert(const char *input,
+                     const char *output);
+
+// Returns the reason the last call on this thread failed, or `NULL` if it succeeded.
+//
+// The string is owned by the library and stays valid until the next call on the same thread. Do not free it.
+const char *fitutils_last_error(void);
+
+// Frees a string returned by the library. Passing `NULL` does nothing.
+//
+// # Safety
+//
+// `string` must be `NULL` or a string returned by `fitutils_parse_to_json()` that hasn't been freed already.
+void fitutils_string_free(char *string);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* FITUTILS_H */
@@ -0,0 +1,160 @@
+//! A minimal C interface to the converters, so desktop applications written in other languages can read and convert
+//! activity files without shelling out to the command line tools.
+//!
+//! The C header is generated into `include/fitutils.h` when the crate is built.
+//!
+//! Strings returned by the library are owned by the library and must be handed back using `fitutils_string_free()`.
+//! When a call fails, the reason can be read using `fitutils_last_error()`.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+thread_local! {
+    /// The reason the last call on this thread failed.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Returned by `fitutils_convert()` when the conversion succeeded.
+pub const FITUTILS_OK: c_int = 0;
+
+/// Returned by `fitutils_convert()` when the conversion failed.
+pub const FITUTILS_ERROR: c_int = -1;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Reads a FIT, GPX or TCX file and returns the activity as a JSON document.
+///
+/// Returns `NULL` if the file can't be read. The returned string must be freed using `fitutils_string_free()`.
+///
+/// # Safety
+///
+/// `path` must be `NULL` or point to a valid, NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn fitutils_parse_to_json(path: *const c_char) -> *mut c_char {
+    let path = unsafe { to_str(path) };
+    guard(|| {
+        let json = utilities::activity_json(path?)?;
+        Ok(CString::new(json)?.into_raw())
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Converts a FIT, GPX or TCX file to a JSON (`.json`) or CSV (`.csv`) file, depending on the extension of `output`.
+/// TCX files can also be converted to GPX (`.gpx`), and GPX files to TCX (`.tcx`).
+///
+/// Returns `FITUTILS_OK` (0) if the conversion succeeded and `FITUTILS_ERROR` (-1) if not.
+///
+/// # Safety
+///
+/// `input` and `output` must be `NULL` or point to valid, NUL-terminated UTF-8 strings.
+#[no_mangle]
+pub unsafe extern "C" fn fitutils_convert(input: *const c_char, output: *const c_char) -> c_int {
+    let (input, output) = unsafe { (to_str(input), to_str(output)) };
+    guard(|| utilities::convert_file(input?, output?)).map_or(FITUTILS_ERROR, |()| FITUTILS_OK)
+}
+
+/// Returns the reason the last call on this thread failed, or `NULL` if it succeeded.
+///
+/// The string is owned by the library and stays valid until the next call on the same thread. Do not free it.
+#[no_mangle]
+pub extern "C" fn fitutils_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.as_ptr())
+    })
+}
+
+/// Frees a string returned by the library. Passing `NULL` does nothing.
+///
+/// # Safety
+///
+/// `string` must be `NULL` or a string returned by `fitutils_parse_to_json()` that hasn't been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn fitutils_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Borrows the C string as a `&str`.
+///
+/// # Safety
+///
+/// `string` must be `NULL` or point to a valid, NUL-terminated string that outlives the returned `&str`.
+unsafe fn to_str<'a>(string: *const c_char) -> Result<&'a str, Box<dyn Error>> {
+    if string.is_null() {
+        return Err("No file name given.".into());
+    }
+    Ok(unsafe { CStr::from_ptr(string) }.to_str()?)
+}
+
+/// Runs the call, keeping a panic from unwinding into the caller, and remembers why it failed.
+fn guard<T>(call: impl FnOnce() -> Result<T, Box<dyn Error>>) -> Option<T> {
+    // Nothing is shared with the caller if the call panics, so it's safe to carry on
+    let result = catch_unwind(AssertUnwindSafe(call))
+        .unwrap_or_else(|_| Err("The file could not be parsed.".into()));
+
+    let error = result.as_ref().err().map(|err| {
+        // A message with a NUL in it can't be passed to C, so the NULs are dropped
+        CString::new(err.to_string().replace('\0', "")).unwrap_or_default()
+    });
+    LAST_ERROR.with(|last| *last.borrow_mut() = error);
+
+    result.ok()
+}
+
+#[cfg(test)]
+/// Tests for the C interface
+mod tests {
+    use super::*;
+
+    /// Reads the last error as a Rust string.
+    fn last_error() -> Option<String> {
+        let err = fitutils_last_error();
+        (!err.is_null()).then(|| unsafe { CStr::from_ptr(err) }.to_string_lossy().to_string())
+    }
+
+    #[test]
+    /// Test parsing a file to JSON through the C interface
+    fn test_parse_to_json() {
+        let path = CString::new("../data/running.gpx").unwrap();
+        let json = unsafe { fitutils_parse_to_json(path.as_ptr()) };
+        assert!(!json.is_null());
+        assert!(unsafe { CStr::from_ptr(json) }
+            .to_string_lossy()
+            .contains("\"tracks\""));
+        assert!(last_error().is_none());
+        unsafe { fitutils_string_free(json) };
+
+        let missing = CString::new("../data/missing.fit").unwrap();
+        assert!(unsafe { fitutils_parse_to_json(missing.as_ptr()) }.is_null());
+        assert!(last_error().is_some());
+        assert!(unsafe { fitutils_parse_to_json(ptr::null()) }.is_null());
+    }
+
+    #[test]
+    /// Test converting a file through the C interface
+    fn test_convert() {
+        let input = CString::new("../data/running.tcx").unwrap();
+        let output = std::env::temp_dir().join("fitutils-ffi-test.json");
+        let output_c = CString::new(output.to_str().unwrap_or_default()).unwrap();
+
+        assert_eq!(
+            unsafe { fitutils_convert(input.as_ptr(), output_c.as_ptr()) },
+            FITUTILS_OK
+        );
+        assert!(output.exists());
+        let _ = std::fs::remove_file(output);
+
+        let unknown = CString::new("running.xlsx").unwrap();
+        assert_eq!(
+            unsafe { fitutils_convert(input.as_ptr(), unknown.as_ptr()) },
+            FITUTILS_ERROR
+        );
+        assert!(last_error().is_some_and(|err| err.contains("running.xlsx")));
+    }
+}
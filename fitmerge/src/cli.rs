@@ -0,0 +1,115 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        // .author(clap::crate_authors!("\n"))
+        .long_about("This program will merge two or more .fit, .gpx or .tcx files from the same workout, e.g. when the watch crashed half way and the rest ended up in a second file, into a single activity with the summary worked out again. The files are put in order by their start time, and where they overlap, the records of the later file are left out. The laps of each file are kept. The new file is named after the first file, e.g. running.merged.fit, unless --output is given. FIT files can only be merged with other FIT files, into a FIT file. GPX and TCX files can be mixed, and are merged into GPX, or into TCX if --output says so.")
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("Two or more .fit, .gpx or .tcx files to merge, in any order. Wildcards and multiple_occurrences files (e.g. ride1.fit ride2.fit) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Print summary information
+            Arg::new("print-summary")
+                .short('s')
+                .long("print-summary")
+                .help("Print the summary of the activity merged.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Overwrite existing files
+            Arg::new("force")
+                .short('f')
+                .long("force")
+                .help("Overwrite the file if it already exists.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // The file to write
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("The file to write the merged activity to. The extension gives the format.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "--read",
+            "ride1.fit",
+            "ride2.fit",
+            "--debug",
+            "--debug",
+            "--quiet",
+            "--print-summary",
+            "--force",
+            "--output",
+            "ride.fit",
+        ]);
+
+        assert_eq!(args.get_many::<String>("read").unwrap().count(), 2);
+        assert_eq!(args.get_count("debug"), 2);
+        assert!(args.get_flag("quiet"));
+        assert!(args.get_flag("print-summary"));
+        assert!(args.get_flag("force"));
+        assert_eq!(
+            args.get_one::<String>("output").map(String::as_str),
+            Some("ride.fit")
+        );
+
+        // Short form
+        let args2 = build().get_matches_from(vec![
+            "--read",
+            "ride1.gpx",
+            "ride2.tcx",
+            "-d",
+            "-q",
+            "-s",
+            "-f",
+            "-o",
+            "ride.tcx",
+        ]);
+
+        assert_eq!(args2.get_count("debug"), 1);
+        assert!(args2.get_flag("quiet"));
+        assert!(args2.get_flag("print-summary"));
+        assert!(args2.get_flag("force"));
+        assert!(args2.contains_id("output"));
+    }
+}
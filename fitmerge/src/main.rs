@@ -0,0 +1,61 @@
+use env_logger::Target;
+use std::error::Error;
+use std::path::Path;
+
+mod cli;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+
+    // Initialize logging
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stdout).init();
+
+    let expanded = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+    let filenames: Vec<&str> = expanded.iter().map(std::string::String::as_str).collect();
+    log::trace!("main::run() -- Files: {filenames:?}");
+    if filenames.len() < 2 {
+        return Err("At least two files are needed to merge.".into());
+    }
+
+    let output = cli_args
+        .get_one::<String>("output")
+        .cloned()
+        .unwrap_or_else(|| {
+            let extension = utilities::get_extension(filenames[0]).to_lowercase();
+            utilities::set_extension(filenames[0], &format!("merged.{extension}"))
+        });
+    if !cli_args.get_flag("force") && Path::new(&output).exists() {
+        return Err(format!("{output} already exists. Use --force to overwrite it.").into());
+    }
+
+    log::debug!("Merging {} files into {output}", filenames.len());
+    let merged = utilities::merge_files(&filenames, &output)?;
+    log::info!("{} files merged into {output}", filenames.len());
+    if cli_args.get_flag("print-summary") {
+        merged.print(false);
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
@@ -0,0 +1,145 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        .long_about("Finds the activities in FIT, GPX and TCX files matching a query, and prints the names of the files. The summaries are kept in an index file, so later queries only read the files that are new or changed.\n\nA query is one or more conditions joined by AND and OR, e.g. \"sport=cycling AND distance>100km AND year=2023\". The fields are sport, distance (m, km or mi), duration (s, min or h), year, month, date (YYYY-MM-DD), device, environment (outdoor, indoor or virtual), tag and file. The operators are =, !=, <, <=, >, >= and ~ (contains).")
+        .arg(
+            Arg::new("query")
+                .value_name("QUERY")
+                .help("The query, e.g. \"sport=cycling AND distance>100km AND year=2023\". Quote it so the shell doesn't treat < and > as redirection.")
+                .num_args(1)
+                .required(true)
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("One or more .fit, .gpx or .tcx file(s) to search. Wildcards and multiple files (e.g. 2019*.fit 2020*.gpx) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Where to keep the summaries
+            Arg::new("index")
+                .short('i')
+                .long("index")
+                .value_name("FILE")
+                .help("Keep the file summaries in this index file, so later queries only read the files that are new or changed.")
+                .num_args(1)
+                .default_value("fitquery-index.json")
+                .action(ArgAction::Set)
+        )
+        .arg( // Don't keep an index
+            Arg::new("no-index")
+                .long("no-index")
+                .help("Read every file instead of keeping the summaries in an index file.")
+                .num_args(0)
+                .conflicts_with("index")
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Where the tags are kept
+            Arg::new("tags-file")
+                .long("tags-file")
+                .value_name("FILE")
+                .help("Read the tags of the activities from this file, as written by fit2csv, gpx2csv and tcx2csv with --tag.")
+                .num_args(1)
+                .default_value(utilities::DEFAULT_TAG_FILE)
+                .action(ArgAction::Set)
+        )
+        .arg( // Only print the number of matches
+            Arg::new("count")
+                .short('c')
+                .long("count")
+                .help("Print the number of matching files instead of their names.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .hide(true)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "fitquery",
+            "sport=cycling AND year=2023",
+            "test.fit",
+            "test.gpx",
+            "--index",
+            "index.json",
+            "--tags-file",
+            "tags.json",
+            "--count",
+            "--debug",
+            "--debug",
+            "--quiet",
+        ]);
+
+        assert_eq!(
+            args.get_one::<String>("query").map(String::as_str),
+            Some("sport=cycling AND year=2023")
+        );
+        assert_eq!(
+            args.get_many::<String>("read").unwrap_or_default().count(),
+            2
+        );
+        assert_eq!(
+            args.get_one::<String>("index").map(String::as_str),
+            Some("index.json")
+        );
+        assert_eq!(
+            args.get_one::<String>("tags-file").map(String::as_str),
+            Some("tags.json")
+        );
+        assert!(args.get_flag("count"));
+        assert!(args.contains_id("quiet"));
+        assert_eq!(args.get_count("debug"), 2);
+
+        // Short form and defaults
+        let args2 = build().get_matches_from(vec!["fitquery", "year=2023", "test.fit", "-c", "-d"]);
+
+        assert!(args2.get_flag("count"));
+        assert!(!args2.get_flag("no-index"));
+        assert_eq!(args2.get_count("debug"), 1);
+        assert_eq!(
+            args2.get_one::<String>("index").map(String::as_str),
+            Some("fitquery-index.json")
+        );
+        assert_eq!(
+            args2.get_one::<String>("tags-file").map(String::as_str),
+            Some(utilities::DEFAULT_TAG_FILE)
+        );
+
+        let args3 =
+            build().get_matches_from(vec!["fitquery", "year=2023", "test.fit", "--no-index"]);
+        assert!(args3.get_flag("no-index"));
+    }
+}
@@ -0,0 +1,88 @@
+//! The main program file.
+use env_logger::Target;
+use std::error::Error;
+use utilities::{ProcessingOptions, Query};
+
+mod cli;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+
+    // Initialize logging. The matching files go to stdout, so the logs go to stderr.
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stderr).init();
+
+    let query = cli_args
+        .get_one::<String>("query")
+        .map_or("", String::as_str)
+        .parse::<Query>()?;
+    log::debug!("main::run() -- query = {query:?}");
+
+    // Reuse the summaries from earlier queries unless told not to
+    let use_index = !cli_args.get_flag("no-index");
+    if use_index {
+        if let Some(index) = cli_args.get_one::<String>("index") {
+            utilities::open_summary_cache(index)?;
+        }
+    }
+
+    // The tags are only read, so the tag file is left as it is
+    if let Some(tags_file) = cli_args.get_one::<String>("tags-file") {
+        utilities::open_tag_store(tags_file, &[])?;
+    }
+
+    // Only the summaries are needed
+    let options = ProcessingOptions {
+        export_detail: false,
+        ..ProcessingOptions::default()
+    };
+
+    let mut num_matches: usize = 0;
+    let filenames = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(String::as_str),
+    );
+    for filename in filenames.iter().map(String::as_str) {
+        // One bad file shouldn't keep the rest of the archive from being searched
+        match utilities::activity_summaries(filename, &options) {
+            Ok(activities) => {
+                if activities.iter().any(|fields| query.matches(fields)) {
+                    num_matches += 1;
+                    if !cli_args.get_flag("count") {
+                        println!("{filename}");
+                    }
+                }
+            }
+            Err(err) => log::warn!("{filename}: Skipped. {err}"),
+        }
+    }
+
+    if cli_args.get_flag("count") {
+        println!("{num_matches}");
+    }
+    log::debug!("main::run() -- {num_matches} matching files.");
+
+    if use_index {
+        utilities::save_summary_cache()?;
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
@@ -13,7 +13,7 @@ pub fn build() -> Command {
                 .value_name("FILE(S)")
                 .help("One or more .fit, .gpx or .tcx file(s) to process. Wildcards and multiple_occurrences files (e.g. 2019*.fit 2020*.gpx) are supported.")
                 .num_args(1..)
-                .required(true)
+                .required_unless_present("watch")
                 .action(ArgAction::Append),
         )
         .arg( // Rename pattern}
@@ -70,10 +70,97 @@ pub fn build() -> Command {
                 .num_args(0)
                 .action(ArgAction::SetTrue)
         )
+        .arg( // Where the date and time tokens come from
+            Arg::new("date-source")
+                .long("date-source")
+                .value_name("start|created|file-mtime")
+                .help("Take the date and time tokens from the start of the activity, the time the file was created according to the file, or the time the file was last modified.")
+                .num_args(1)
+                .default_value("start")
+                .value_parser(utilities::DateSource::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // When the %index counter starts over
+            Arg::new("index-reset")
+                .long("index-reset")
+                .value_name("never|directory|day")
+                .help("When the %index and %idxN counters start over from 1: never, for each directory, or for each day.")
+                .num_args(1)
+                .default_value("never")
+                .value_parser(crate::index_token::IndexReset::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // What to do when the new name is taken
+            Arg::new("on-conflict")
+                .long("on-conflict")
+                .value_name("skip|number|overwrite|fail")
+                .help("What to do when the new name is already taken, by an existing file or another file in the run: leave the file as it is, append -01, -02 etc. to the name, replace the existing file, or stop.")
+                .num_args(1)
+                .default_value("number")
+                .value_parser(crate::rename_file::OnConflict::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // Companion files
+            Arg::new("companions")
+                .long("companions")
+                .value_name("EXT,EXT")
+                .help("Rename and move the files next to each activity file that have the same name and one of these extensions along with it, e.g. gpx,tcx,jpg.")
+                .num_args(1)
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+        )
+        .arg( // Serial number hashing
+            Arg::new("hash-serials")
+                .long("hash-serials")
+                .help("Use a short hash instead of the device serial number for the %serial_number and %sn tokens.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Placeholder for values that aren't known
+            Arg::new("placeholder")
+                .long("placeholder")
+                .value_name("TEXT")
+                .help("Use this text for tokens whose value isn't known, e.g. a missing manufacturer or start time, instead of the defaults such as Unknown and 0000. Use \"\" to leave them blank.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Skip short activities
+            Arg::new("min-duration")
+                .long("min-duration")
+                .value_name("hh:mm:ss")
+                .help("Skip activities shorter than this, e.g. 00:01:00, leaving them as they are.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Skip short activities
+            Arg::new("min-distance")
+                .long("min-distance")
+                .value_name("DISTANCE")
+                .help("Skip activities shorter than this distance, in m, km or mi, e.g. 200m, leaving them as they are. Kilometers if no unit is given.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Watch a directory
+            Arg::new("watch")
+                .long("watch")
+                .value_name("DIR")
+                .help("Keep watching the directory, and the ones below it, and rename each new .fit, .gpx or .tcx file once it has been written, e.g. as the watch syncs it. The files already there are renamed first. Each file is only renamed once, as recorded in the --ledger file. Runs until stopped.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // The files processed while watching
+            Arg::new("ledger")
+                .long("ledger")
+                .value_name("FILE")
+                .help("The file recording the files renamed by --watch, .fitrename-processed in the directory watched by default.")
+                .num_args(1)
+                .requires("watch")
+                .action(ArgAction::Set)
+        )
 }
 
 #[cfg(test)]
-///
+/// Tests for the CLI module
 mod tests {
     use super::*;
 
@@ -92,6 +179,21 @@ mod tests {
             "--quiet",
             "--print-summary",
             "--dry-run",
+            "--hash-serials",
+            "--date-source",
+            "file-mtime",
+            "--index-reset",
+            "day",
+            "--placeholder",
+            "n-a",
+            "--on-conflict",
+            "skip",
+            "--companions",
+            "gpx,jpg",
+            "--min-duration",
+            "00:01:00",
+            "--min-distance",
+            "200m",
         ]);
 
         assert!(args.contains_id("read"));
@@ -101,6 +203,37 @@ mod tests {
         assert!(args.contains_id("quiet"));
         assert!(args.contains_id("print-summary"));
         assert!(args.contains_id("dry-run"));
+        assert!(args.get_flag("hash-serials"));
+        assert_eq!(
+            args.get_one::<String>("date-source").map(String::as_str),
+            Some("file-mtime")
+        );
+        assert_eq!(
+            args.get_one::<String>("index-reset").map(String::as_str),
+            Some("day")
+        );
+        assert_eq!(
+            args.get_one::<String>("placeholder").map(String::as_str),
+            Some("n-a")
+        );
+        assert_eq!(
+            args.get_one::<String>("on-conflict").map(String::as_str),
+            Some("skip")
+        );
+        assert_eq!(
+            args.get_many::<String>("companions")
+                .unwrap_or_default()
+                .collect::<Vec<_>>(),
+            ["gpx", "jpg"]
+        );
+        assert_eq!(
+            args.get_one::<String>("min-duration").map(String::as_str),
+            Some("00:01:00")
+        );
+        assert_eq!(
+            args.get_one::<String>("min-distance").map(String::as_str),
+            Some("200m")
+        );
 
         // Test using short form arguments/flags.
         let args2 = build().get_matches_from(vec![
@@ -123,5 +256,27 @@ mod tests {
         assert!(args2.contains_id("quiet"));
         assert!(args2.contains_id("print-summary"));
         assert!(args2.contains_id("dry-run"));
+
+        // Watching a directory instead of renaming the files given
+        let args3 = build().get_matches_from(vec![
+            "fitrename",
+            "-p",
+            "{%yr}-{%mn}-{%dy}",
+            "--watch",
+            "GARMIN/Activity",
+            "--ledger",
+            "ledger.txt",
+        ]);
+        assert_eq!(
+            args3.get_one::<String>("watch").map(String::as_str),
+            Some("GARMIN/Activity")
+        );
+        assert_eq!(
+            args3.get_one::<String>("ledger").map(String::as_str),
+            Some("ledger.txt")
+        );
+        assert!(build()
+            .try_get_matches_from(vec!["fitrename", "-p", "{%yr}"])
+            .is_err());
     }
 }
@@ -0,0 +1,152 @@
+//! Finds the companion files of an activity file, i.e. the files next to it with the same stem and one of the
+//! extensions given with `--companions`, e.g. the `.gpx` export or the `.jpg` photo of a FIT file. The companions are
+//! renamed and moved along with the activity file, so each set stays together.
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::rename_file::{resolve_conflict, OnConflict};
+
+/// Finds the companion files of the activity file. The extensions are matched whatever their case.
+///
+/// # Arguments
+///
+/// - `filename: &str` - the activity file
+/// - `extensions: &[String]` - the extensions of the companion files, with or without the leading dot
+///
+/// # Returns
+///
+/// `Vec<PathBuf>` with the companion files found, sorted by name. The activity file itself is never one of them.
+pub fn find_companions(filename: &str, extensions: &[String]) -> Vec<PathBuf> {
+    let path = Path::new(filename);
+    // The companions of `run.fit.gz` are named `run`, like those of `run.fit`
+    let inner = if utilities::is_gzipped(filename) {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    };
+    let (Some(stem), Some(parent)) = (inner.file_stem(), path.parent()) else {
+        return Vec::new();
+    };
+    let dir = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut companions: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| parent.join(entry.file_name()))
+        .filter(|companion| companion.is_file() && companion.file_name() != path.file_name())
+        .filter(|companion| companion.file_stem() == Some(stem))
+        .filter(|companion| {
+            let name = companion.to_string_lossy();
+            extensions
+                .iter()
+                .any(|ext| utilities::has_extension(&name, ext))
+        })
+        .collect();
+    companions.sort();
+
+    companions
+}
+
+/// Renames or moves the companion file so it has the same name and directory as the activity file, with its own
+/// extension.
+///
+/// # Arguments
+///
+/// - `companion: &Path` - the companion file
+/// - `activity_file: &str` - the new name of the activity file, after any move
+/// - `on_conflict: OnConflict` - what to do if the new name of the companion is taken
+/// - `taken: &mut HashSet<PathBuf>` - the file names handed out earlier in the run
+/// - `dry_run: bool` - An indicator of whether this is a dry run or not.
+///
+/// # Returns
+///
+/// `Result<Option<String>>` containing the new name of the companion, or `None` if it was left as it is since the
+/// name was taken.
+///
+/// # Errors
+///
+/// An error message if the companion can't be renamed, or a `ConflictError` if the name is taken and `on_conflict` is
+/// `Fail`.
+pub fn rename_companion(
+    companion: &Path,
+    activity_file: &str,
+    on_conflict: OnConflict,
+    taken: &mut HashSet<PathBuf>,
+    dry_run: bool,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let filename = companion.to_string_lossy();
+    let extension = companion.extension().unwrap_or_default().to_string_lossy();
+    let target = PathBuf::from(utilities::set_extension(activity_file, &extension));
+    let Some(target) = resolve_conflict(&filename, target, on_conflict, taken)? else {
+        return Ok(None);
+    };
+
+    let npl = target.to_string_lossy();
+    if dry_run {
+        log::debug!("dr: {filename} --> {npl}");
+    } else {
+        fs::rename(companion, &target)
+            .map_err(|err| format!("Unable to rename {filename} to {npl}. Error message: {err}"))?;
+        log::debug!("{filename} --> {npl}");
+    }
+
+    Ok(Some(npl.to_string()))
+}
+
+#[cfg(test)]
+/// Tests for the companions module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test finding the companions and moving them along with the activity file
+    fn test_companions() {
+        let dir = std::env::temp_dir().join("fitrename_test_companions");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("2022")).unwrap();
+        for file in [
+            "run.fit", "run.GPX", "run.jpg", "run.txt", "run2.gpx", "walk.gpx",
+        ] {
+            fs::write(dir.join(file), file).unwrap();
+        }
+
+        let filename = dir.join("run.fit").to_string_lossy().to_string();
+        let extensions = vec!["gpx".to_string(), ".JPG".to_string()];
+        let companions = find_companions(&filename, &extensions);
+        assert_eq!(companions, vec![dir.join("run.GPX"), dir.join("run.jpg")]);
+
+        let activity_file = dir.join("2022").join("Running.fit");
+        let mut taken = HashSet::new();
+        let renamed = rename_companion(
+            &companions[0],
+            &activity_file.to_string_lossy(),
+            OnConflict::Number,
+            &mut taken,
+            false,
+        );
+        assert_eq!(
+            renamed.unwrap(),
+            Some(
+                dir.join("2022")
+                    .join("Running.GPX")
+                    .to_string_lossy()
+                    .to_string()
+            )
+        );
+        assert!(dir.join("2022").join("Running.GPX").exists());
+        assert!(!dir.join("run.GPX").exists());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
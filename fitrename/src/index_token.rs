@@ -0,0 +1,162 @@
+//! Numbers the files processed in a run, so batches can be renamed sequentially using the `%index` and `%idxN`
+//! tokens. `%index` inserts the number as-is, while `%idxN` pads it with zeros to `N` digits, e.g. `%idx3` gives `007`.
+
+use std::{collections::HashMap, path::Path};
+
+/// The token that inserts the counter without padding.
+const INDEX_TOKEN: &str = "%index";
+
+/// The prefix of the tokens that insert the counter padded with zeros.
+const PADDED_INDEX_PREFIX: &str = "%idx";
+
+/// When the counter starts over from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexReset {
+    /// Count all the files in the run.
+    Never,
+    /// Count the files in each directory separately.
+    Directory,
+    /// Count the activities from each day separately, based on the `%year`, `%month` and `%day` tokens.
+    Day,
+}
+
+impl IndexReset {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 3] = ["never", "directory", "day"];
+}
+
+impl std::str::FromStr for IndexReset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "never" => Ok(Self::Never),
+            "directory" | "dir" => Ok(Self::Directory),
+            "day" => Ok(Self::Day),
+            _ => Err(format!("Unknown index reset {s}.")),
+        }
+    }
+}
+
+/// Hands out the next number for each file.
+#[derive(Debug)]
+pub struct IndexCounter {
+    /// When the counter starts over.
+    reset: IndexReset,
+
+    /// The last number handed out, by directory or day.
+    counts: HashMap<String, usize>,
+}
+
+impl IndexCounter {
+    /// Creates a counter that starts at 1.
+    pub fn new(reset: IndexReset) -> Self {
+        Self {
+            reset,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Counts the file and inserts the index tokens used in the patterns into the values.
+    ///
+    /// # Arguments
+    ///
+    /// - `filename: &str` -- The file being renamed.
+    /// - `patterns: &[&str]` -- The rename and move patterns, which are searched for `%idxN` tokens.
+    /// - `values: &mut HashMap<String, String>` -- The tokens read from the file, which get the index tokens added.
+    pub fn insert_tokens(
+        &mut self,
+        filename: &str,
+        patterns: &[&str],
+        values: &mut HashMap<String, String>,
+    ) {
+        let key = match self.reset {
+            IndexReset::Never => String::new(),
+            IndexReset::Directory => Path::new(filename)
+                .parent()
+                .map(|dir| dir.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            IndexReset::Day => ["%year", "%month", "%day"]
+                .iter()
+                .map(|token| values.get(*token).map_or("", String::as_str))
+                .collect::<Vec<&str>>()
+                .join("-"),
+        };
+
+        let count = self.counts.entry(key).or_default();
+        *count += 1;
+        let index = *count;
+
+        values.insert(INDEX_TOKEN.to_string(), index.to_string());
+        for width in padded_widths(patterns) {
+            values.insert(
+                format!("{PADDED_INDEX_PREFIX}{width}"),
+                format!("{index:0width$}"),
+            );
+        }
+    }
+}
+
+/// Finds the widths of the `%idxN` tokens used in the patterns.
+fn padded_widths(patterns: &[&str]) -> Vec<usize> {
+    let mut widths = Vec::new();
+
+    for pattern in patterns {
+        for (pos, _) in pattern.match_indices(PADDED_INDEX_PREFIX) {
+            let digits: String = pattern[pos + PADDED_INDEX_PREFIX.len()..]
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+            if let Ok(width) = digits.parse::<usize>() {
+                if !widths.contains(&width) {
+                    widths.push(width);
+                }
+            }
+        }
+    }
+
+    widths
+}
+
+#[cfg(test)]
+/// Tests for the index_token module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test finding the padded tokens in the patterns
+    fn test_padded_widths() {
+        assert_eq!(padded_widths(&["%idx3 %yr", "%idx12/%idx3"]), vec![3, 12]);
+        assert!(padded_widths(&["%index %idx"]).is_empty());
+    }
+
+    #[test]
+    /// Test the counting and the resets
+    fn test_insert_tokens() {
+        let mut counter = IndexCounter::new(IndexReset::Never);
+        let mut values = HashMap::new();
+        counter.insert_tokens("a/1.fit", &["%idx3"], &mut values);
+        counter.insert_tokens("b/2.fit", &["%idx3"], &mut values);
+        assert_eq!(values["%index"], "2");
+        assert_eq!(values["%idx3"], "002");
+
+        let mut counter = IndexCounter::new(IndexReset::Directory);
+        counter.insert_tokens("a/1.fit", &[], &mut values);
+        counter.insert_tokens("b/2.fit", &[], &mut values);
+        assert_eq!(values["%index"], "1");
+
+        let mut counter = IndexCounter::new(IndexReset::Day);
+        let mut day = |d: &str| {
+            let mut values = HashMap::from([
+                ("%year".to_string(), "2022".to_string()),
+                ("%month".to_string(), "02".to_string()),
+                ("%day".to_string(), d.to_string()),
+            ]);
+            counter.insert_tokens("a/1.fit", &[], &mut values);
+            values["%index"].clone()
+        };
+        assert_eq!(day("10"), "1");
+        assert_eq!(day("10"), "2");
+        assert_eq!(day("11"), "1");
+    }
+}
@@ -1,19 +1,28 @@
 use env_logger::Target;
-use std::{error::Error, path::Path};
+use std::{
+    collections::HashSet,
+    error::Error,
+    path::{Path, PathBuf},
+};
 
 use clap::parser::ValueSource;
 
 mod cli;
+mod companions;
+mod index_token;
 mod move_file;
 mod rename_file;
 
+/// The ledger of the files renamed by `--watch`, in the directory watched, unless `--ledger` is given.
+const DEFAULT_LEDGER: &str = ".fitrename-processed";
+
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// This is where the magic happens.
 // This #allow is needed for Clippy to shut up. There has to be a bug in Clippy for this one.
 #[allow(clippy::unnecessary_wraps)]
 fn run() -> Result<(), Box<dyn Error>> {
     // Set up the command line. Ref https://docs.rs/clap for details.
-    let cli_args = cli::build().get_matches();
+    let cli_args = utilities::with_config(cli::build()).get_matches();
     let dry_run = cli_args.value_source("dry-run") == Some(ValueSource::CommandLine);
     let print_summary = cli_args.value_source("print-summary") == Some(ValueSource::CommandLine);
 
@@ -31,6 +40,24 @@ fn run() -> Result<(), Box<dyn Error>> {
         log::info!("Dry-run. Will not perform actual rename or move.");
     }
 
+    let mut settings = utilities::Settings::default();
+
+    // Hide the device serial numbers in the file names if requested
+    settings.set_hash_serials(cli_args.get_flag("hash-serials"));
+
+    // Use the same text for all the values that aren't known if requested
+    settings.set_placeholder(
+        cli_args
+            .get_one::<String>("placeholder")
+            .map(String::as_str),
+    );
+
+    // Pick where the date and time tokens come from
+    if let Some(source) = cli_args.get_one::<String>("date-source") {
+        settings.set_date_source(source.parse::<utilities::DateSource>()?);
+    }
+    let _settings = settings.apply();
+
     let default_pattern = String::new();
     let pattern = cli_args
         .get_one::<String>("pattern")
@@ -48,19 +75,174 @@ fn run() -> Result<(), Box<dyn Error>> {
         ""
     };
 
-    let mut total_files: usize = 0;
-    let mut processed_files: usize = 0;
-    let mut skipped_files: usize = 0;
+    // Number the files for the %index and %idxN tokens
+    let index_reset = cli_args
+        .get_one::<String>("index-reset")
+        .map_or(Ok(index_token::IndexReset::Never), |reset| reset.parse())?;
+
+    // What to do when two files end up with the same name
+    let on_conflict = cli_args
+        .get_one::<String>("on-conflict")
+        .map_or(Ok(rename_file::OnConflict::default()), |on_conflict| {
+            on_conflict.parse()
+        })?;
+
+    let mut renamer = Renamer {
+        pattern,
+        move_pattern,
+        move_files,
+        dry_run,
+        on_conflict,
+        index_counter: index_token::IndexCounter::new(index_reset),
+        taken: HashSet::new(),
+        // The extensions of the files renamed along with each activity file
+        companion_exts: cli_args
+            .get_many::<String>("companions")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        companions_done: HashSet::new(),
+        // The activities too short to keep are left as they are
+        min_activity: utilities::min_activity_from_args(&cli_args)?,
+        counts: Counts::default(),
+    };
 
     ///////////////////////////////////
     // Working section
+
+    // Keep renaming the files synced into the directory if requested, until stopped
+    if let Some(dir) = cli_args.get_one::<String>("watch") {
+        let ledger = cli_args.get_one::<String>("ledger").map_or_else(
+            || {
+                Path::new(dir)
+                    .join(DEFAULT_LEDGER)
+                    .to_string_lossy()
+                    .to_string()
+            },
+            String::clone,
+        );
+        return utilities::watch_directory(
+            dir,
+            &ledger,
+            &[
+                utilities::ActivityFormat::Fit,
+                utilities::ActivityFormat::Gpx,
+                utilities::ActivityFormat::Tcx,
+            ],
+            |filename| Ok(renamer.rename(filename)?.into_iter().collect()),
+        );
+    }
+
     for filename in filenames {
+        renamer.rename(filename)?;
+    }
+
+    if print_summary {
+        renamer.print_summary();
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// How many files were renamed, moved or skipped, for the summary.
+#[derive(Debug, Default)]
+struct Counts {
+    /// The activity files examined.
+    total: usize,
+
+    /// The activity files renamed or moved.
+    processed: usize,
+
+    /// The activity files that couldn't be renamed or moved.
+    skipped: usize,
+
+    /// The activity files left as they are since the new name was taken.
+    conflicted: usize,
+
+    /// The activity files left as they are since they were too short.
+    short: usize,
+
+    /// The companion files renamed along with their activity file.
+    companions: usize,
+}
+
+/// Renames, and moves if requested, the activity files one by one, keeping track of the names handed out so far.
+struct Renamer<'a> {
+    /// The pattern for the new file names.
+    pattern: &'a str,
+
+    /// The pattern for the directory to move the files to.
+    move_pattern: &'a str,
+
+    /// Whether to move the files as well.
+    move_files: bool,
+
+    /// Whether to only show what would be done.
+    dry_run: bool,
+
+    /// What to do when two files end up with the same name.
+    on_conflict: rename_file::OnConflict,
+
+    /// Numbers the files for the %index and %idxN tokens.
+    index_counter: index_token::IndexCounter,
+
+    /// The names handed out so far.
+    taken: HashSet<PathBuf>,
+
+    /// The extensions of the files renamed along with each activity file.
+    companion_exts: Vec<String>,
+
+    /// The companion files renamed so far.
+    companions_done: HashSet<PathBuf>,
+
+    /// The shortest activity to rename.
+    min_activity: utilities::MinActivity,
+
+    /// What was done so far.
+    counts: Counts,
+}
+
+impl Renamer<'_> {
+    /// Renames, and moves if requested, the file along with its companions.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Option<String>>` containing the new name of the file, or `None` if it was left as it is.
+    ///
+    /// # Errors
+    ///
+    /// A `ConflictError` if the new name is taken and `--on-conflict fail` was given.
+    // TODO: Refactor - move the file rename and file move code into a separate function
+    fn rename(&mut self, filename: &str) -> Result<Option<String>, Box<dyn Error>> {
         log::debug!("Processing file: {filename}");
 
+        // Files given on the command line may already have been renamed as the companion of another file
+        if self.companions_done.contains(Path::new(filename)) {
+            log::debug!("{filename} was renamed along with its activity file.");
+            return Ok(None);
+        }
+
         // Check if the target file exists, otherwise just continue
         if !Path::new(&filename).exists() {
             log::warn!("File not found: {filename}");
-            continue;
+            return Ok(None);
+        }
+        self.counts.total += 1;
+
+        if self.min_activity.is_set() {
+            let summary_options = utilities::ProcessingOptions {
+                export_detail: false,
+                ..utilities::ProcessingOptions::default()
+            };
+            let summaries =
+                utilities::activity_summaries(filename, &summary_options).unwrap_or_default();
+            if let Some(reason) = self.min_activity.skip_reason_all(&summaries) {
+                log::warn!("{filename}: Skipped. {reason}");
+                self.counts.short += 1;
+                return Ok(None);
+            }
         }
 
         // Read the metadata from files
@@ -78,75 +260,159 @@ fn run() -> Result<(), Box<dyn Error>> {
                 value_res = utilities::tcx_to_hashmap(filename);
                 log::debug!("TCX: {value_res:?}");
             }
+            "zip" => {
+                log::warn!(
+                    "{filename}: The files in zip archives can't be renamed. Unzip it first."
+                );
+                value_res = Err("Zip archive".into());
+            }
             _ => {
                 log::warn!("Unknown file type: {filename}.");
                 value_res = Err("Unknown file type".into());
             }
         }
 
-        // Process the result of reading metadata
-        let new_filename;
-
-        // TODO: Refactor - move the file rename and file move code into a separate function
-        match value_res {
-            // Metadata read OK - try to rename and move
-            Ok(values) => {
-                let result =
-                    rename_file::rename_file(filename, pattern, &values, total_files, dry_run);
-                match result {
-                    // How did the rename go?
-                    Ok(result) => {
-                        new_filename = result.clone();
-                        log::info!("{filename} --> {new_filename}");
-                        if !move_files {
-                            // If we're not moving the file, we're done with this file.
-                            processed_files += 1;
-                            continue;
-                        }
-                    }
-                    Err(err) => {
-                        log::error!("Unable to rename {filename} : {}", err.to_string());
-                        skipped_files += 1;
-                        continue;
-                    }
-                }
-
-                if move_files {
-                    let result = move_file::move_file(
-                        &new_filename,
-                        move_pattern,
-                        &values,
-                        total_files,
-                        dry_run,
-                    );
-                    match result {
-                        // How did the move go?
-                        Ok(result) => {
-                            log::info!("{new_filename} --> {result}");
-                            processed_files += 1;
-                        }
-                        Err(err) => {
-                            log::error!("Unable to move {new_filename} : {}", err.to_string());
-                            skipped_files += 1;
-                        }
-                    }
-                }
+        // Problem reading metadata - let the user know.
+        let mut values = match value_res {
+            Ok(values) => values,
+            Err(err) => {
+                log::error!("Unable to process {filename} : {err}");
+                return Ok(None);
+            }
+        };
+
+        // Metadata read OK - try to rename and move
+        self.index_counter
+            .insert_tokens(filename, &[self.pattern, self.move_pattern], &mut values);
+        let companions = companions::find_companions(filename, &self.companion_exts);
+        let result = rename_file::rename_file(
+            filename,
+            self.pattern,
+            &values,
+            self.on_conflict,
+            &mut self.taken,
+            self.dry_run,
+        );
+        let new_filename = match result {
+            // How did the rename go?
+            Ok(None) => {
+                self.counts.conflicted += 1;
+                return Ok(None);
+            }
+            Ok(Some(result)) => {
+                log::info!("{filename} --> {result}");
+                result
+            }
+            Err(err) if err.is::<rename_file::ConflictError>() => return Err(err),
+            Err(err) => {
+                log::error!("Unable to rename {filename} : {err}");
+                self.counts.skipped += 1;
+                return Ok(None);
+            }
+        };
+
+        // If we're not moving the file, we're done with this file once the companions follow.
+        if !self.move_files {
+            self.counts.processed += 1;
+            self.follow(companions, &new_filename)?;
+            return Ok(Some(new_filename));
+        }
+
+        let result = move_file::move_file(
+            &new_filename,
+            self.move_pattern,
+            &values,
+            self.on_conflict,
+            &mut self.taken,
+            self.dry_run,
+        );
+        match result {
+            // How did the move go?
+            Ok(Some(result)) => {
+                log::info!("{new_filename} --> {result}");
+                self.taken.remove(Path::new(&new_filename));
+                self.counts.processed += 1;
+                self.follow(companions, &result)?;
+                Ok(Some(result))
+            }
+            Ok(None) => {
+                self.counts.conflicted += 1;
+                Ok(None)
+            }
+            Err(err) if err.is::<rename_file::ConflictError>() => Err(err),
+            Err(err) => {
+                log::error!("Unable to move {new_filename} : {err}");
+                self.counts.skipped += 1;
+                Ok(None)
             }
-            // Problem reading metadata - let the user know.
-            Err(err) => log::error!("Unable to process {filename} : {}", err.to_string()),
         }
-        total_files += 1;
     }
 
-    if print_summary {
-        log::info!("Total files examined:        {total_files:6}");
-        log::info!("Files processed:             {processed_files:6}");
-        log::info!("Files skipped due to errors: {skipped_files:6}");
+    /// Renames or moves the companions along with the activity file, and remembers them so they aren't renamed again.
+    fn follow(
+        &mut self,
+        companions: Vec<PathBuf>,
+        activity_file: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.counts.companions += rename_companions(
+            &companions,
+            activity_file,
+            self.on_conflict,
+            &mut self.taken,
+            self.dry_run,
+        )?;
+        self.companions_done.extend(companions);
+        Ok(())
     }
 
-    // Everything is a-okay in the end
-    Ok(())
-} // fn run()
+    /// Prints how many files were renamed, moved or skipped.
+    fn print_summary(&self) {
+        log::info!("Total files examined:        {:6}", self.counts.total);
+        log::info!("Files processed:             {:6}", self.counts.processed);
+        log::info!("Files skipped due to errors: {:6}", self.counts.skipped);
+        log::info!("Files skipped as name taken: {:6}", self.counts.conflicted);
+        if self.min_activity.is_set() {
+            log::info!("Files skipped as too short:  {:6}", self.counts.short);
+        }
+        if !self.companion_exts.is_empty() {
+            log::info!("Companion files renamed:     {:6}", self.counts.companions);
+        }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Renames or moves the companion files along with their activity file. A companion that can't be renamed is
+/// reported and left as it is.
+///
+/// # Returns
+///
+/// `Result<usize>` containing the number of companions renamed.
+///
+/// # Errors
+///
+/// A `ConflictError` if the new name of a companion is taken and `--on-conflict fail` was given.
+fn rename_companions(
+    companions: &[PathBuf],
+    activity_file: &str,
+    on_conflict: rename_file::OnConflict,
+    taken: &mut HashSet<PathBuf>,
+    dry_run: bool,
+) -> Result<usize, Box<dyn Error>> {
+    let mut renamed = 0;
+    for companion in companions {
+        match companions::rename_companion(companion, activity_file, on_conflict, taken, dry_run) {
+            Ok(Some(result)) => {
+                log::info!("{} --> {result}", companion.to_string_lossy());
+                renamed += 1;
+            }
+            Ok(None) => (),
+            Err(err) if err.is::<rename_file::ConflictError>() => return Err(err),
+            Err(err) => log::error!("Unable to rename {} : {}", companion.to_string_lossy(), err),
+        }
+    }
+
+    Ok(renamed)
+}
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// The actual executable function that gets called when the program in invoked.
@@ -1,28 +1,29 @@
-use std::{collections::HashMap, error::Error, fs, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+use utilities::fill_template;
 
+use crate::rename_file::{resolve_conflict, OnConflict};
+
+/// Moves the file to the directory given by the pattern, which is created if needed. Returns `None` if the file was
+/// left where it is since its name was taken in the directory.
 pub fn move_file<S: ::std::hash::BuildHasher>(
     filename: &str,
     target_pattern: &str,
     values: &HashMap<String, String, S>,
-    unique_val: usize,
+    on_conflict: OnConflict,
+    taken: &mut HashSet<PathBuf>,
     dry_run: bool,
-) -> Result<String, Box<dyn Error>> {
+) -> Result<Option<String>, Box<dyn Error>> {
     log::debug!("filename: {filename}");
     log::debug!("target_pattern: {target_pattern}");
     log::debug!("values: {values:?}");
 
-    let mut target = target_pattern.to_string();
-
     // Perform substitutions on the target path
-    for (key, value) in values {
-        let fixed_value = value.clone().trim().to_string();
-        log::debug!("key: {key}, fixed_value: {fixed_value}");
-
-        // Do the actual target path replacement
-        let nt = target.replace(key, &fixed_value);
-        target = nt;
-        log::debug!("target: {target}");
-    }
+    let target = fill_template(target_pattern, values);
 
     log::debug!("final target: {target}");
 
@@ -52,18 +53,13 @@ pub fn move_file<S: ::std::hash::BuildHasher>(
     let target_filename = Path::new(&filename).file_name().unwrap_or_default();
     log::debug!("target_filename = {target_filename:?}");
 
-    let mut target_file = target_path.join(target_filename);
+    let target_file = target_path.join(target_filename);
     log::debug!("target_file = {target_file:?}");
 
-    // Check if a file with the new filename already exists - make the filename unique if it does.
-    if Path::new(&target_file).exists() {
-        log::warn!(
-            "{} already exists. Appending unique identifier.",
-            target_file.to_string_lossy()
-        );
-        let target_filename = format!("{} ({unique_val})", target_filename.to_string_lossy());
-        target_file = target_path.join(target_filename);
-    }
+    // Check if a file with the new filename already exists and handle it as we've been asked to.
+    let Some(target_file) = resolve_conflict(filename, target_file, on_conflict, taken)? else {
+        return Ok(None);
+    };
 
     // Perform the actual move
     if dry_run {
@@ -74,5 +70,5 @@ pub fn move_file<S: ::std::hash::BuildHasher>(
             fs::rename(filename, &target_file).map_err(|e| format!("Unable to move file: {e}",));
     }
 
-    Ok(target_file.to_string_lossy().to_string())
+    Ok(Some(target_file.to_string_lossy().to_string()))
 }
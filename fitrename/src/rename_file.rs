@@ -1,23 +1,174 @@
-use std::{collections::HashMap, error::Error, path::Path};
-use utilities::get_extension;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+use utilities::{fill_template, get_extension, is_gzipped};
 
-/// Renames the target file based on the provided patterntar
+/// The most numbered names tried before giving up on finding a free one.
+const MAX_NUMBER: usize = 9_999;
+
+/// What to do when the new name of a file is already taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Leave the file as it is and carry on with the next one.
+    Skip,
+    /// Append `-01`, `-02` etc. to the name until it is free.
+    #[default]
+    Number,
+    /// Replace the file that has the name.
+    Overwrite,
+    /// Stop the run.
+    Fail,
+}
+
+impl OnConflict {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 4] = ["skip", "number", "overwrite", "fail"];
+}
+
+impl std::str::FromStr for OnConflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "number" => Ok(Self::Number),
+            "overwrite" => Ok(Self::Overwrite),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!("Unknown conflict handling {s}.")),
+        }
+    }
+}
+
+/// The new name of a file is already taken, and `--on-conflict fail` was given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictError {
+    /// The file being renamed or moved.
+    pub filename: String,
+    /// The name that is taken.
+    pub target: PathBuf,
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} is already taken.",
+            self.filename,
+            self.target.to_string_lossy()
+        )
+    }
+}
+
+impl Error for ConflictError {}
+
+/// Works out where the file goes when the target may already be taken, either by a file on disk or by another file
+/// renamed earlier in the run. The name handed out is added to the names taken.
+///
+/// # Arguments
+///
+/// - `filename: &str` - the file being renamed or moved
+/// - `target: PathBuf` - where the file should go
+/// - `on_conflict: OnConflict` - what to do if the target is taken
+/// - `taken: &mut HashSet<PathBuf>` - the names handed out earlier in the run
+///
+/// # Returns
+///
+/// `Result<Option<PathBuf>>` containing where the file goes, or `None` if it should be left where it is.
+///
+/// # Errors
+///
+/// A `ConflictError` if the target is taken and `on_conflict` is `Fail`, or no free numbered name is found.
+pub fn resolve_conflict(
+    filename: &str,
+    target: PathBuf,
+    on_conflict: OnConflict,
+    taken: &mut HashSet<PathBuf>,
+) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let is_taken = |path: &Path, taken: &HashSet<PathBuf>| {
+        path != Path::new(filename) && (path.exists() || taken.contains(path))
+    };
+
+    let target = if !is_taken(&target, taken) {
+        target
+    } else {
+        match on_conflict {
+            OnConflict::Skip => {
+                log::warn!(
+                    "{} already exists. Skipping {filename}.",
+                    target.to_string_lossy()
+                );
+                return Ok(None);
+            }
+            OnConflict::Overwrite => {
+                log::warn!("{} already exists. Overwriting.", target.to_string_lossy());
+                target
+            }
+            OnConflict::Fail => {
+                return Err(Box::new(ConflictError {
+                    filename: filename.to_string(),
+                    target,
+                }))
+            }
+            OnConflict::Number => {
+                let numbered = (1..=MAX_NUMBER)
+                    .map(|number| numbered_path(&target, number))
+                    .find(|path| !is_taken(path, taken))
+                    .ok_or_else(|| ConflictError {
+                        filename: filename.to_string(),
+                        target: target.clone(),
+                    })?;
+                log::warn!(
+                    "{} already exists. Using {} instead.",
+                    target.to_string_lossy(),
+                    numbered.to_string_lossy()
+                );
+                numbered
+            }
+        }
+    };
+
+    taken.insert(target.clone());
+    Ok(Some(target))
+}
+
+/// The path with a number appended to the file name, before the extension, e.g. `run-01.fit` or `run-01.fit.gz`.
+fn numbered_path(path: &Path, number: usize) -> PathBuf {
+    if is_gzipped(&path.to_string_lossy()) {
+        let numbered = numbered_path(&path.with_extension(""), number);
+        let gz = path.extension().unwrap_or_default().to_string_lossy();
+        return PathBuf::from(format!("{}.{gz}", numbered.to_string_lossy()));
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = path.extension().map_or_else(
+        || format!("{stem}-{number:02}"),
+        |ext| format!("{stem}-{number:02}.{}", ext.to_string_lossy()),
+    );
+    path.with_file_name(name)
+}
+
+/// Renames the target file based on the provided pattern
 ///
 /// # Arguments
 ///
 /// - `filename: &str` - the file to be renamed
 /// - `pattern: &str` - the pattern upon which the new file name will be based
 /// - `values: &HashMap<String, String>` - a `HashMap` with key/value pairs of the replacement values for the pattern
-/// - `unique_val: usize` - If necessary, we can append a unique value to ensure file name uniqueness.
+/// - `on_conflict: OnConflict` - what to do if the new file name is already taken
+/// - `taken: &mut HashSet<PathBuf>` - the file names handed out earlier in the run
 /// - `dry_run: bool` - An indicator of whether this is a dry run or not.
 ///
 /// # Returns
 ///
-/// Result<String> containing the new file name.
+/// Result<Option<String>> containing the new file name, or `None` if the file was skipped since the name was taken.
 ///
 /// # Errors
 ///
-/// An error message if we're unable to rename the file.
+/// An error message if we're unable to rename the file, or a `ConflictError` if the name is taken and
+/// `on_conflict` is `Fail`.
 ///
 /// # Panics
 ///
@@ -26,23 +177,15 @@ pub fn rename_file<S: ::std::hash::BuildHasher>(
     filename: &str,
     pattern: &str,
     values: &HashMap<String, String, S>,
-    unique_val: usize,
+    on_conflict: OnConflict,
+    taken: &mut HashSet<PathBuf>,
     dry_run: bool,
-) -> Result<String, Box<dyn Error>> {
-    let mut new_filename = pattern.to_string();
-
+) -> Result<Option<String>, Box<dyn Error>> {
     log::debug!("rename_file() -- values: {values:?}");
     log::debug!("rename_file() -- pattern: {pattern}");
 
-    for (key, value) in values {
-        let fixed_value = value.clone().trim().to_string();
-        log::debug!("rename_file() -- key: {key}, fixed_value: {fixed_value}");
-
-        // Do the actual filename replacement
-        let nf = new_filename.replace(key, &fixed_value).replace('/', "-");
-        new_filename = nf;
-        log::debug!("rename_file() -- new_filename: {new_filename}");
-    }
+    // Do the actual filename replacement
+    let new_filename = fill_template(pattern, values).replace(['/', '\\'], "-");
 
     log::debug!("rename_file() -- final new_filename: {new_filename}");
 
@@ -51,17 +194,17 @@ pub fn rename_file<S: ::std::hash::BuildHasher>(
         .parent()
         .unwrap_or_else(|| Path::new("."));
 
-    // Create the new filename
-    let mut new_path =
-        parent.join(Path::new(&new_filename).with_extension(get_extension(filename)));
-    log::debug!("new_path = {new_path:?}");
-
-    // Check if a file with the new filename already exists - make the filename unique if it does.
-    if Path::new(&new_path).exists() {
-        log::warn!("{new_filename} already exists. Appending unique identifier.");
-        new_filename = format!("{new_filename} ({unique_val})");
-        new_path = parent.join(Path::new(&new_filename).with_extension(get_extension(filename)));
+    // Create the new filename, making it unique if it's taken and that's what we've been asked to do. The extension is
+    // added rather than set, since tokens such as `%distance` put a dot in the name. Gzipped files stay gzipped.
+    let mut extension = get_extension(filename);
+    if is_gzipped(filename) {
+        extension.push_str(".gz");
     }
+    let new_path = parent.join(format!("{new_filename}.{extension}"));
+    log::debug!("new_path = {new_path:?}");
+    let Some(new_path) = resolve_conflict(filename, new_path, on_conflict, taken)? else {
+        return Ok(None);
+    };
 
     // Perform the actual rename
     let npl = new_path.to_string_lossy();
@@ -81,5 +224,131 @@ pub fn rename_file<S: ::std::hash::BuildHasher>(
     }
 
     // return safely
-    Ok(new_path.to_string_lossy().to_string())
+    Ok(Some(new_path.to_string_lossy().to_string()))
+}
+
+#[cfg(test)]
+/// Tests for the rename_file module
+mod tests {
+    use super::*;
+
+    /// A scratch directory with the files given.
+    fn scratch_dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fitrename_test_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            std::fs::write(dir.join(file), file).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    /// Test the numbered names
+    fn test_numbered_path() {
+        assert_eq!(
+            numbered_path(Path::new("dir/run.fit"), 1),
+            PathBuf::from("dir/run-01.fit")
+        );
+        assert_eq!(
+            numbered_path(Path::new("run"), 123),
+            PathBuf::from("run-123")
+        );
+        assert_eq!(
+            numbered_path(Path::new("activities/1234.fit.gz"), 2),
+            PathBuf::from("activities/1234-02.fit.gz")
+        );
+        assert_eq!("Overwrite".parse(), Ok(OnConflict::Overwrite));
+        assert!("rename".parse::<OnConflict>().is_err());
+    }
+
+    #[test]
+    /// Test numbering past the names taken on disk and earlier in the run
+    fn test_resolve_number() {
+        let dir = scratch_dir("number", &["a.fit", "run.fit", "run-01.fit"]);
+        let source = dir.join("a.fit").to_string_lossy().to_string();
+        let mut taken = HashSet::from([dir.join("run-02.fit")]);
+
+        let target = resolve_conflict(&source, dir.join("run.fit"), OnConflict::Number, &mut taken);
+        assert_eq!(target.unwrap(), Some(dir.join("run-03.fit")));
+        assert!(taken.contains(&dir.join("run-03.fit")));
+
+        // A free name, and the file's own name, are used as they are
+        let target = resolve_conflict(&source, dir.join("b.fit"), OnConflict::Number, &mut taken);
+        assert_eq!(target.unwrap(), Some(dir.join("b.fit")));
+        let target = resolve_conflict(&source, dir.join("a.fit"), OnConflict::Fail, &mut taken);
+        assert_eq!(target.unwrap(), Some(dir.join("a.fit")));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    /// Test skipping, overwriting and failing when the name is taken
+    fn test_resolve_strategies() {
+        let dir = scratch_dir("strategies", &["a.fit", "run.fit"]);
+        let source = dir.join("a.fit").to_string_lossy().to_string();
+        let mut taken = HashSet::new();
+
+        let skipped = resolve_conflict(&source, dir.join("run.fit"), OnConflict::Skip, &mut taken);
+        assert_eq!(skipped.unwrap(), None);
+
+        let failed = resolve_conflict(&source, dir.join("run.fit"), OnConflict::Fail, &mut taken);
+        assert!(failed.unwrap_err().is::<ConflictError>());
+        assert!(taken.is_empty());
+
+        let overwritten = resolve_conflict(
+            &source,
+            dir.join("run.fit"),
+            OnConflict::Overwrite,
+            &mut taken,
+        );
+        assert_eq!(overwritten.unwrap(), Some(dir.join("run.fit")));
+
+        // Renaming for real, with the second file numbered
+        let mut taken = HashSet::new();
+        let values = HashMap::from([("%activity".to_string(), "Running".to_string())]);
+        let first = rename_file(
+            &source,
+            "%activity",
+            &values,
+            OnConflict::Number,
+            &mut taken,
+            false,
+        );
+        assert_eq!(
+            first.unwrap(),
+            Some(dir.join("Running.fit").to_string_lossy().to_string())
+        );
+        let second = dir.join("run.fit").to_string_lossy().to_string();
+        let renamed = rename_file(
+            &second,
+            "%activity",
+            &values,
+            OnConflict::Number,
+            &mut taken,
+            false,
+        );
+        assert_eq!(
+            renamed.unwrap(),
+            Some(dir.join("Running-01.fit").to_string_lossy().to_string())
+        );
+        assert!(dir.join("Running-01.fit").exists());
+
+        // A dot or a Windows separator in the values doesn't cut the name short
+        let values = HashMap::from([("%distance".to_string(), r"10.2\km".to_string())]);
+        let renamed = rename_file(
+            &dir.join("Running.fit").to_string_lossy(),
+            "Run %distance",
+            &values,
+            OnConflict::Number,
+            &mut taken,
+            true,
+        );
+        assert_eq!(
+            renamed.unwrap(),
+            Some(dir.join("Run 10.2-km.fit").to_string_lossy().to_string())
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
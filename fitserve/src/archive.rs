@@ -0,0 +1,267 @@
+//! Keeps track of the activities being served. Only the summaries are held in memory -- the records are read from the
+//! file again each time they are asked for, so large archives can be served without running out of memory.
+
+use serde_json::{json, Value};
+use std::error::Error;
+use utilities::{
+    geojson_position as position, ActivityFormat, FITActivity, GPXActivity, ProcessingOptions,
+    TCXTrackpointList,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A single activity file and its summary.
+#[derive(Debug, Clone)]
+pub struct Activity {
+    /// The file the activity was read from.
+    pub filename: String,
+
+    /// The kind of file.
+    pub format: ActivityFormat,
+
+    /// The number of the activity among the FIT files chained in the file, starting at 1, or `None` if the file holds
+    /// a single activity.
+    pub file_num: Option<usize>,
+
+    /// The session summary -- a `FITSession`, `GPXMetadata` or `TCXActivity` as JSON.
+    pub summary: Value,
+}
+
+impl Activity {
+    /// Reads the summaries of the activities in the file. A FIT file holding several chained FIT files gives one
+    /// activity for each of them.
+    ///
+    /// # Errors
+    ///
+    /// The file may not be a FIT, GPX or TCX file. Reading or parsing the file may fail.
+    pub fn from_file(filename: &str) -> Result<Vec<Self>, Box<dyn Error>> {
+        let format = ActivityFormat::from_filename(filename).ok_or("Unknown file type.")?;
+        let options = ProcessingOptions {
+            export_detail: false,
+            ..ProcessingOptions::default()
+        };
+        let activity = |file_num, summary| Self {
+            filename: filename.to_string(),
+            format,
+            file_num,
+            summary,
+        };
+
+        Ok(match format {
+            ActivityFormat::Fit => {
+                let activities = utilities::process_fit_file(filename, &options)?.0;
+                let chained = activities.len() > 1;
+                activities
+                    .into_iter()
+                    .enumerate()
+                    .map(|(num, fit)| {
+                        Ok(activity(
+                            chained.then_some(num + 1),
+                            serde_json::to_value(fit.session)?,
+                        ))
+                    })
+                    .collect::<Result<_, Box<dyn Error>>>()?
+            }
+            ActivityFormat::Gpx => vec![activity(
+                None,
+                serde_json::to_value(utilities::process_gpx_file(filename, &options)?.0.metadata)?,
+            )],
+            ActivityFormat::Tcx => vec![activity(
+                None,
+                serde_json::to_value(
+                    utilities::process_tcx_file(filename, &options)?
+                        .0
+                        .unwrap_or_default(),
+                )?,
+            )],
+        })
+    }
+
+    /// Reads the FIT activity from the file, picking out the chained file if there are several.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the file may fail, or the chained file may no longer be there.
+    fn fit_activity(&self) -> Result<FITActivity, Box<dyn Error>> {
+        match self.file_num {
+            Some(num) => FITActivity::from_file_chained(&self.filename)?
+                .into_iter()
+                .nth(num - 1)
+                .ok_or_else(|| format!("Chained file {num} not found.").into()),
+            None => FITActivity::from_file(&self.filename),
+        }
+    }
+
+    /// Reads the records from the file -- FIT records, GPX track waypoints or TCX trackpoints.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the file may fail.
+    pub fn records(&self) -> Result<Value, Box<dyn Error>> {
+        match self.format {
+            ActivityFormat::Fit => Ok(serde_json::to_value(self.fit_activity()?.records)?),
+            _ => utilities::Activity::from_file(&self.filename)?.records_json(),
+        }
+    }
+
+    /// Reads the track from the file as a GeoJSON `Feature` with a `LineString` geometry. The summary is used as the
+    /// properties of the feature. Points without a position are left out.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the file may fail.
+    pub fn geojson(&self) -> Result<Value, Box<dyn Error>> {
+        let coordinates: Vec<Vec<f64>> = match self.format {
+            ActivityFormat::Fit => self
+                .fit_activity()?
+                .records
+                .iter()
+                .filter_map(|rec| position(rec.lat, rec.lon, rec.altitude.map(|alt| alt.value)))
+                .collect(),
+            ActivityFormat::Gpx => GPXActivity::from_file(&self.filename)?
+                .tracks
+                .iter()
+                .flat_map(|track| track.waypoints.iter())
+                .filter_map(|wp| position(wp.latitude, wp.longitude, wp.elevation))
+                .collect(),
+            ActivityFormat::Tcx => TCXTrackpointList::from_file(&self.filename)?
+                .trackpoints
+                .iter()
+                .filter_map(|tp| position(tp.latitude, tp.longitude, tp.altitude_meters))
+                .collect(),
+        };
+
+        Ok(json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": coordinates,
+            },
+            "properties": self.summary,
+        }))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The activities being served. Activities are identified by their position in the list, so each of the FIT files
+/// chained in a file has an ID of its own.
+#[derive(Debug, Default)]
+pub struct Archive {
+    /// The activities, in the order the files were given.
+    pub activities: Vec<Activity>,
+}
+
+impl Archive {
+    /// Reads the summaries of the files. Files that can't be read are skipped with a warning, so one bad file doesn't
+    /// keep the rest of the archive from being served.
+    pub fn load<'a>(filenames: impl Iterator<Item = &'a str>) -> Self {
+        let mut archive = Self::default();
+
+        for filename in filenames {
+            log::debug!("Reading file: {filename}");
+            match Activity::from_file(filename) {
+                Ok(activities) => archive.activities.extend(activities),
+                Err(err) => log::warn!("{filename}: Skipped. {err}"),
+            }
+        }
+
+        archive
+    }
+
+    /// Gets the activity with the ID, if there is one.
+    pub fn get(&self, id: usize) -> Option<&Activity> {
+        self.activities.get(id)
+    }
+
+    /// Lists the activities with their IDs, files and summaries, along with the number of the chained file if the file
+    /// holds several.
+    pub fn list(&self) -> Value {
+        self.activities
+            .iter()
+            .enumerate()
+            .map(|(id, activity)| {
+                json!({
+                    "id": id,
+                    "filename": activity.filename,
+                    "format": activity.format.name(),
+                    "file_num": activity.file_num,
+                    "summary": activity.summary,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+/// Tests for the archive module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the GeoJSON positions
+    fn test_position() {
+        assert_eq!(
+            position(Some(59.0), Some(10.0), None),
+            Some(vec![10.0, 59.0])
+        );
+        assert_eq!(
+            position(Some(59.0), Some(10.0), Some(12.5)),
+            Some(vec![10.0, 59.0, 12.5])
+        );
+        assert!(position(None, Some(10.0), Some(12.5)).is_none());
+    }
+
+    #[test]
+    /// Test loading and listing the activities, skipping the files that can't be read
+    fn test_load() {
+        let archive = Archive::load(
+            [
+                "../data/running.gpx",
+                "../data/running.tcx",
+                "../data/missing.fit",
+                "readme.md",
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(archive.activities.len(), 2);
+        assert_eq!(archive.get(1).map(|a| a.format), Some(ActivityFormat::Tcx));
+        assert!(archive.get(2).is_none());
+        assert_eq!(archive.list()[0]["format"], "gpx");
+
+        let geojson = archive.activities[0].geojson().unwrap();
+        assert_eq!(geojson["geometry"]["type"], "LineString");
+        assert!(geojson["geometry"]["coordinates"]
+            .as_array()
+            .is_some_and(|c| !c.is_empty()));
+        assert!(archive.activities[1]
+            .records()
+            .unwrap()
+            .as_array()
+            .is_some_and(|r| !r.is_empty()));
+    }
+
+    #[test]
+    /// Test listing each of the FIT files chained in a file as an activity of its own
+    fn test_load_chained() {
+        let single = std::fs::read("../data/rowing.fit").unwrap();
+        let chained = std::env::temp_dir().join("fitserve-chained-test.fit");
+        std::fs::write(&chained, [single.clone(), single].concat()).unwrap();
+        let filename = chained.to_str().unwrap_or_default();
+
+        let archive = Archive::load(["../data/rowing.fit", filename].into_iter());
+        assert_eq!(archive.activities.len(), 3);
+        assert_eq!(archive.activities[0].file_num, None);
+        assert_eq!(archive.activities[1].file_num, Some(1));
+        assert_eq!(archive.activities[2].file_num, Some(2));
+        assert_eq!(archive.list()[2]["file_num"], 2);
+        assert!(archive.activities[2].summary["filename"]
+            .as_str()
+            .is_some_and(|name| name.ends_with(".file2.fit")));
+
+        let records = |id: usize| archive.activities[id].records().unwrap();
+        assert_eq!(records(2), records(0));
+        assert!(records(2).as_array().is_some_and(|r| !r.is_empty()));
+
+        std::fs::remove_file(chained).unwrap();
+    }
+}
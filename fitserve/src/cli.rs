@@ -0,0 +1,120 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        .long_about("Reads the summaries of FIT, GPX and TCX files and serves them over a small local HTTP API, so a local web dashboard can browse the activities without exporting them first.")
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("One or more .fit, .gpx or .tcx file(s) to serve. Wildcards and multiple files (e.g. 2019*.fit 2020*.gpx) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .hide(true)
+        )
+        .arg( // Address to listen on
+            Arg::new("address")
+                .short('a')
+                .long("address")
+                .value_name("ADDRESS")
+                .help("The address to listen on. Use 0.0.0.0 to allow connections from other machines.")
+                .num_args(1)
+                .default_value("127.0.0.1")
+                .action(ArgAction::Set)
+        )
+        .arg( // Port to listen on
+            Arg::new("port")
+                .short('p')
+                .long("port")
+                .value_name("PORT")
+                .help("The port to listen on.")
+                .num_args(1)
+                .default_value("8080")
+                .value_parser(clap::value_parser!(u16))
+                .action(ArgAction::Set)
+        )
+        .arg( // Web page allowed to use the API
+            Arg::new("allow-origin")
+                .long("allow-origin")
+                .value_name("ORIGIN")
+                .help("Let the web pages from this origin, e.g. http://localhost:3000, read the activities. By default no web page can, since the tracks show where you live and train.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "--read",
+            "test.fit",
+            "--debug",
+            "--debug",
+            "--quiet",
+            "--address",
+            "0.0.0.0",
+            "--port",
+            "9000",
+            "--allow-origin",
+            "http://localhost:3000",
+        ]);
+
+        assert!(args.contains_id("read"));
+        assert!(args.contains_id("quiet"));
+        assert_eq!(args.get_count("debug"), 2);
+        assert_eq!(
+            args.get_one::<String>("address").map(String::as_str),
+            Some("0.0.0.0")
+        );
+        assert_eq!(args.get_one::<u16>("port"), Some(&9000));
+        assert_eq!(
+            args.get_one::<String>("allow-origin").map(String::as_str),
+            Some("http://localhost:3000")
+        );
+
+        // Short form and defaults
+        let args2 = build().get_matches_from(vec!["--read", "test.fit", "-d", "-q"]);
+
+        assert!(args2.contains_id("quiet"));
+        assert_eq!(args2.get_count("debug"), 1);
+        assert_eq!(
+            args2.get_one::<String>("address").map(String::as_str),
+            Some("127.0.0.1")
+        );
+        assert_eq!(args2.get_one::<u16>("port"), Some(&8080));
+        assert!(args2.get_one::<String>("allow-origin").is_none());
+
+        let args3 =
+            build().get_matches_from(vec!["fitserve", "test.fit", "-a", "::1", "-p", "9001"]);
+        assert_eq!(args3.get_one::<u16>("port"), Some(&9001));
+    }
+}
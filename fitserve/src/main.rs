@@ -0,0 +1,76 @@
+//! The main program file.
+use env_logger::Target;
+use std::error::Error;
+use tiny_http::{Header, Response, Server};
+
+mod archive;
+mod cli;
+mod routes;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+
+    // Initialize logging
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stdout).init();
+
+    let expanded = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+    let filenames = expanded.iter().map(std::string::String::as_str);
+
+    // Read the summaries up front so the list of activities is quick to serve
+    let archive = archive::Archive::load(filenames);
+    log::info!("Serving {} activities.", archive.activities.len());
+
+    let address = cli_args
+        .get_one::<String>("address")
+        .map_or("127.0.0.1", String::as_str);
+    let port = cli_args.get_one::<u16>("port").copied().unwrap_or(8080);
+    let allow_origin = cli_args.get_one::<String>("allow-origin");
+    let server = Server::http((address, port)).map_err(|err| err.to_string())?;
+    log::info!("Listening on http://{}/activities", server.server_addr());
+
+    for request in server.incoming_requests() {
+        let reply = routes::route(&archive, request.method().as_str(), request.url());
+        log::debug!("{} {} -> {}", request.method(), request.url(), reply.status);
+
+        let mut response = Response::from_string(reply.body)
+            .with_status_code(reply.status)
+            .with_header(header("Content-Type", reply.content_type)?);
+        // Only the dashboard allowed on the command line may read the tracks from another origin
+        if let Some(origin) = allow_origin {
+            response = response.with_header(header("Access-Control-Allow-Origin", origin)?);
+        }
+        if let Err(err) = request.respond(response) {
+            log::warn!("Unable to send the response: {err}");
+        }
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+/// Creates a response header.
+fn header(field: &str, value: &str) -> Result<Header, Box<dyn Error>> {
+    Header::from_bytes(field.as_bytes(), value.as_bytes())
+        .map_err(|()| format!("Invalid header {field}: {value}").into())
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
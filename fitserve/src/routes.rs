@@ -0,0 +1,133 @@
+//! Maps the requests to the activities in the archive. Kept apart from the HTTP server so the routes can be tested
+//! without opening a port.
+//!
+//! | Path                                | Returns                                         |
+//! |:------------------------------------|:------------------------------------------------|
+//! | `/activities`                       | The list of activities with their summaries.    |
+//! | `/activities/{id}/session`          | The session summary of the activity.            |
+//! | `/activities/{id}/records`          | The records of the activity as JSON.            |
+//! | `/activities/{id}/records.geojson`  | The track of the activity as a GeoJSON Feature. |
+
+use serde_json::{json, Value};
+
+use crate::archive::{Activity, Archive};
+
+/// The content type of the JSON responses.
+const JSON: &str = "application/json";
+
+/// The content type of the GeoJSON responses.
+const GEOJSON: &str = "application/geo+json";
+
+/// What to send back for a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    /// The HTTP status code.
+    pub status: u16,
+
+    /// The content type of the body.
+    pub content_type: &'static str,
+
+    /// The body of the response.
+    pub body: String,
+}
+
+impl Reply {
+    /// A successful reply with the value as the body.
+    fn ok(value: &Value, content_type: &'static str) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body: value.to_string(),
+        }
+    }
+
+    /// An error reply with the message in the body.
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: JSON,
+            body: json!({ "error": message }).to_string(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the reply to a request.
+///
+/// # Arguments
+///
+/// - `archive: &Archive` -- The activities being served.
+/// - `method: &str` -- The HTTP method, e.g. `GET`.
+/// - `url: &str` -- The path and query of the request, e.g. `/activities/3/records`. The query is ignored.
+///
+/// # Returns
+///
+/// `Reply` -- The status, content type and body to send back.
+pub fn route(archive: &Archive, method: &str, url: &str) -> Reply {
+    if method != "GET" {
+        return Reply::error(405, "Only GET requests are supported.");
+    }
+
+    let path = url.split('?').next().unwrap_or_default();
+    let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+
+    match parts.as_slice() {
+        [] | ["activities"] => Reply::ok(&archive.list(), JSON),
+        ["activities", id, resource] => {
+            let Some(activity) = id.parse::<usize>().ok().and_then(|id| archive.get(id)) else {
+                return Reply::error(404, &format!("No activity with ID {id}."));
+            };
+            activity_reply(activity, resource)
+        }
+        _ => Reply::error(404, &format!("Unknown path {path}.")),
+    }
+}
+
+/// Works out the reply for a resource of a single activity.
+fn activity_reply(activity: &Activity, resource: &str) -> Reply {
+    let (value, content_type) = match resource {
+        "session" => (Ok(activity.summary.clone()), JSON),
+        "records" => (activity.records(), JSON),
+        "records.geojson" => (activity.geojson(), GEOJSON),
+        _ => return Reply::error(404, &format!("Unknown resource {resource}.")),
+    };
+
+    match value {
+        Ok(value) => Reply::ok(&value, content_type),
+        Err(err) => {
+            log::error!("{}: {err}", activity.filename);
+            Reply::error(500, &format!("Unable to read {}.", activity.filename))
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests for the routes module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the routing of the requests
+    fn test_route() {
+        let archive = Archive::load(["../data/running.gpx"].into_iter());
+
+        let list = route(&archive, "GET", "/activities");
+        assert_eq!(list.status, 200);
+        assert!(list.body.contains("\"format\":\"gpx\""));
+        assert_eq!(route(&archive, "GET", "/").body, list.body);
+
+        let session = route(&archive, "GET", "/activities/0/session?pretty=1");
+        assert_eq!(session.status, 200);
+        assert_eq!(session.content_type, JSON);
+
+        let geojson = route(&archive, "GET", "/activities/0/records.geojson");
+        assert_eq!(geojson.status, 200);
+        assert_eq!(geojson.content_type, GEOJSON);
+
+        assert_eq!(route(&archive, "GET", "/activities/1/session").status, 404);
+        assert_eq!(route(&archive, "GET", "/activities/x/records").status, 404);
+        assert_eq!(route(&archive, "GET", "/activities/0/laps").status, 404);
+        assert_eq!(route(&archive, "GET", "/sessions").status, 404);
+        assert_eq!(route(&archive, "POST", "/activities").status, 405);
+    }
+}
@@ -0,0 +1,239 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        .long_about("Adds up the activities in FIT, GPX and TCX files by week, month or year, prints the totals and writes them to a CSV or JSON file: the number of activities, the distance, the time, the ascent, the calories and the Training Stress Score (TSS), for all sports and for each sport. Directories are searched for files, including the directories below them. The weeks are ISO weeks starting on a Monday, unless --week-start says otherwise. The summaries are kept in an index file, so later runs only read the files that are new or changed.")
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("One or more .fit, .gpx or .tcx file(s), or directories of them, to add up. Wildcards and multiple files (e.g. 2019*.fit 2020*.gpx) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // The period to add up by
+            Arg::new("period")
+                .short('p')
+                .long("period")
+                .value_name("PERIOD")
+                .help("Add up the activities by week, month or year.")
+                .num_args(1)
+                .default_value("week")
+                .value_parser(["week", "month", "year"])
+                .action(ArgAction::Set)
+        )
+        .arg( // The day the weeks start on
+            Arg::new("week-start")
+                .long("week-start")
+                .value_name("DAY")
+                .help("The day the weeks start on.")
+                .num_args(1)
+                .default_value("monday")
+                .value_parser(["monday", "sunday"])
+                .action(ArgAction::Set)
+        )
+        .arg( // Where to write the totals
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("Write the totals to this file. The totals are written as JSON if the file name ends in .json, and as CSV otherwise.")
+                .num_args(1)
+                .default_value("fitstats.csv")
+                .action(ArgAction::Set)
+        )
+        .arg( // Unit system
+            Arg::new("units")
+                .long("units")
+                .value_name("metric|imperial")
+                .help("Print the distances and the ascents in kilometers and meters, or in miles and feet. The file written is always in kilometers and meters.")
+                .num_args(1)
+                .default_value("metric")
+                .value_parser(utilities::UnitSystem::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // CSV delimiter
+            Arg::new("delimiter")
+                .long("delimiter")
+                .value_name("CHAR")
+                .help("Separate the values in the CSV files with this character instead of a comma, e.g. ; or |. Use tab for tab separated values.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Decimal comma
+            Arg::new("decimal-comma")
+                .long("decimal-comma")
+                .help("Write the decimals in the CSV files with a comma, e.g. 12,5, as spreadsheets set up for most European locales expect. The values are separated by semicolons unless --delimiter is given.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Combined mean-maximal curve
+            Arg::new("mean-max")
+                .long("mean-max")
+                .value_name("FILE")
+                .help("Also write the best average power and heart rate of all the activities combined, held over durations from 1 second to the length of the longest activity, to this CSV file, and print it at a few common durations. The records of every file are read for this, so it takes longer.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Where to keep the summaries
+            Arg::new("index")
+                .short('i')
+                .long("index")
+                .value_name("FILE")
+                .help("Keep the file summaries in this index file, so later runs only read the files that are new or changed.")
+                .num_args(1)
+                .default_value("fitstats-index.json")
+                .action(ArgAction::Set)
+        )
+        .arg( // Don't keep an index
+            Arg::new("no-index")
+                .long("no-index")
+                .help("Read every file instead of keeping the summaries in an index file.")
+                .num_args(0)
+                .conflicts_with("index")
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Skip short activities
+            Arg::new("min-duration")
+                .long("min-duration")
+                .value_name("hh:mm:ss")
+                .help("Skip activities shorter than this, e.g. 00:01:00, leaving them out of the totals.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Skip short activities
+            Arg::new("min-distance")
+                .long("min-distance")
+                .value_name("DISTANCE")
+                .help("Skip activities shorter than this distance, in m, km or mi, e.g. 200m, leaving them out of the totals. Kilometers if no unit is given.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .hide(true)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "fitstats",
+            "test.fit",
+            "test.gpx",
+            "--period",
+            "month",
+            "--week-start",
+            "sunday",
+            "--output",
+            "monthly.csv",
+            "--units",
+            "imperial",
+            "--decimal-comma",
+            "--index",
+            "index.json",
+            "--mean-max",
+            "best.csv",
+            "--min-duration",
+            "00:01:00",
+            "--min-distance",
+            "200m",
+            "--debug",
+            "--debug",
+            "--quiet",
+        ]);
+
+        assert_eq!(
+            args.get_many::<String>("read").unwrap_or_default().count(),
+            2
+        );
+        assert_eq!(
+            args.get_one::<String>("period").map(String::as_str),
+            Some("month")
+        );
+        assert_eq!(
+            args.get_one::<String>("week-start").map(String::as_str),
+            Some("sunday")
+        );
+        assert_eq!(
+            args.get_one::<String>("output").map(String::as_str),
+            Some("monthly.csv")
+        );
+        assert_eq!(
+            args.get_one::<String>("units").map(String::as_str),
+            Some("imperial")
+        );
+        assert!(args.get_flag("decimal-comma"));
+        assert_eq!(
+            args.get_one::<String>("index").map(String::as_str),
+            Some("index.json")
+        );
+        assert_eq!(
+            args.get_one::<String>("mean-max").map(String::as_str),
+            Some("best.csv")
+        );
+        assert_eq!(
+            args.get_one::<String>("min-duration").map(String::as_str),
+            Some("00:01:00")
+        );
+        assert_eq!(
+            args.get_one::<String>("min-distance").map(String::as_str),
+            Some("200m")
+        );
+        assert!(args.contains_id("quiet"));
+        assert_eq!(args.get_count("debug"), 2);
+
+        // Short form and defaults
+        let args2 = build().get_matches_from(vec!["fitstats", "test.fit", "-p", "year", "-d"]);
+
+        assert_eq!(
+            args2.get_one::<String>("period").map(String::as_str),
+            Some("year")
+        );
+        assert_eq!(
+            args2.get_one::<String>("week-start").map(String::as_str),
+            Some("monday")
+        );
+        assert_eq!(
+            args2.get_one::<String>("output").map(String::as_str),
+            Some("fitstats.csv")
+        );
+        assert_eq!(
+            args2.get_one::<String>("units").map(String::as_str),
+            Some("metric")
+        );
+        assert!(!args2.get_flag("no-index"));
+        assert!(args2.get_one::<String>("mean-max").is_none());
+        assert_eq!(args2.get_count("debug"), 1);
+
+        assert!(build()
+            .try_get_matches_from(vec!["fitstats", "test.fit", "--period", "fortnight"])
+            .is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! The main program file.
+use env_logger::Target;
+use std::error::Error;
+use std::path::Path;
+use utilities::{ProcessingOptions, StatsPeriod, WeekStart};
+
+mod cli;
+mod mean_max;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+
+    // create a log builder
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stdout).init();
+
+    let period = cli_args
+        .get_one::<String>("period")
+        .map_or("week", String::as_str)
+        .parse::<StatsPeriod>()?;
+    let week_start = cli_args
+        .get_one::<String>("week-start")
+        .map_or("monday", String::as_str)
+        .parse::<WeekStart>()?;
+
+    let mut settings = utilities::Settings::default();
+    settings.set_unit_system(
+        cli_args
+            .get_one::<String>("units")
+            .map(|units| units.parse::<utilities::UnitSystem>())
+            .transpose()?,
+    );
+
+    settings.set_csv_format_from_args(&cli_args)?;
+    let _settings = settings.apply();
+
+    // Reuse the summaries from earlier runs unless told not to
+    let use_index = !cli_args.get_flag("no-index");
+    if use_index {
+        if let Some(index) = cli_args.get_one::<String>("index") {
+            utilities::open_summary_cache(index)?;
+        }
+    }
+
+    // Only the summaries are needed
+    let options = ProcessingOptions {
+        export_detail: false,
+        min_activity: utilities::min_activity_from_args(&cli_args)?,
+        ..ProcessingOptions::default()
+    };
+
+    let filenames = utilities::activity_files(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(String::as_str),
+        true,
+    )?;
+    log::trace!("main::run() -- Files: {filenames:?}");
+
+    let mut activities = Vec::new();
+    let mut added_files = Vec::new();
+    for filename in &filenames {
+        // One bad file shouldn't keep the rest of the log from being added up
+        match utilities::activity_summaries(filename, &options) {
+            Ok(summaries) => {
+                if !summaries.is_empty() {
+                    added_files.push(filename.as_str());
+                }
+                activities.extend(summaries);
+            }
+            Err(err) => log::warn!("{filename}: Skipped. {err}"),
+        }
+    }
+
+    let rollups = utilities::rollups(&activities, period, week_start);
+    log::debug!(
+        "main::run() -- {} activities in {} periods",
+        activities.len(),
+        rollups.len()
+    );
+
+    if !cli_args.get_flag("quiet") {
+        utilities::print_rollups(&rollups);
+    }
+
+    if let Some(output) = cli_args.get_one::<String>("output") {
+        let path = Path::new(output);
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        {
+            utilities::write_atomically(path, |file| {
+                utilities::write_rollups_json(file, &rollups)
+            })?;
+        } else {
+            utilities::write_atomically(path, |file| utilities::write_rollups_csv(file, &rollups))?;
+        }
+        log::info!("Totals by {period} written to {output}");
+    }
+
+    // The best power and heart rate of all the activities added up
+    if let Some(output) = cli_args.get_one::<String>("mean-max") {
+        let curve = mean_max::best_curve(&added_files);
+        if !cli_args.get_flag("quiet") {
+            utilities::print_mean_max(&curve);
+        }
+        utilities::export_mean_max_csv(&curve, output)?;
+        log::info!("Mean-maximal curve of all the activities written to {output}");
+    }
+
+    if use_index {
+        utilities::save_summary_cache()?;
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
@@ -0,0 +1,80 @@
+//! Works out the best mean-maximal power and heart rate curve of all the activities added up, for following the form
+//! over a season. The records aren't kept in the index, so each file is read in full for this.
+
+use std::error::Error;
+use utilities::{ActivityFormat, FITActivity, MeanMax};
+
+/// Works out the mean-maximal curve of each activity in the file. Chained FIT files give one curve for each file.
+///
+/// # Errors
+///
+/// The file may not be a FIT, GPX or TCX file. Reading or parsing the file may fail.
+fn file_curves(filename: &str) -> Result<Vec<Vec<MeanMax>>, Box<dyn Error>> {
+    if ActivityFormat::from_filename(filename) == Some(ActivityFormat::Fit) {
+        Ok(FITActivity::from_file_chained(filename)?
+            .iter()
+            .map(FITActivity::mean_max)
+            .collect())
+    } else {
+        Ok(vec![utilities::Activity::from_file(filename)?.mean_max()])
+    }
+}
+
+/// Combines the mean-maximal curves of the activities in the files into one with the best power and heart rate for
+/// each duration. Files that can't be read are skipped with a warning, the same way as for the totals.
+///
+/// # Arguments
+///
+/// `filenames: &[&str]` -- The files to read.
+///
+/// # Returns
+///
+/// `Vec<MeanMax>` -- The combined curve, ordered by duration. Empty if none of the activities have power or heart rate.
+pub fn best_curve(filenames: &[&str]) -> Vec<MeanMax> {
+    let mut curves = Vec::new();
+    for filename in filenames {
+        match file_curves(filename) {
+            Ok(file_curves) => curves.extend(file_curves),
+            Err(err) => log::warn!("{filename}: Left out of the mean-maximal curve. {err}"),
+        }
+    }
+    log::debug!(
+        "mean_max::best_curve() -- {} curves from {} files",
+        curves.len(),
+        filenames.len()
+    );
+
+    utilities::best_mean_max(&curves)
+}
+
+#[cfg(test)]
+/// Tests for the mean_max module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test combining the curves of the files, skipping the files that can't be read
+    fn test_best_curve() {
+        let rowing = FITActivity::from_file("../data/rowing.fit")
+            .unwrap()
+            .mean_max();
+        assert!(!rowing.is_empty());
+
+        assert_eq!(best_curve(&["../data/rowing.fit"]), rowing);
+        assert_eq!(
+            best_curve(&["../data/rowing.fit", "../data/missing.fit"]),
+            rowing
+        );
+        assert!(best_curve(&[]).is_empty());
+
+        let combined = best_curve(&["../data/rowing.fit", "../data/running.tcx"]);
+        assert!(combined.len() >= rowing.len());
+        for point in &rowing {
+            let best = combined
+                .iter()
+                .find(|best| best.duration_sec == point.duration_sec)
+                .unwrap();
+            assert!(best.heartrate_avg_bpm >= point.heartrate_avg_bpm);
+        }
+    }
+}
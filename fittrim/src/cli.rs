@@ -0,0 +1,194 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        // .author(clap::crate_authors!("\n"))
+        .long_about("This program will trim the standing still at the start and the finish, e.g. while waiting for the GPS or forgetting to stop the watch, off one or more .fit, .gpx or .tcx files and write the rest to a new file, with the summary worked out again. A time or distance range can be given to trim off everything outside it as well. The laps are kept. The new file is named after the original, e.g. running.trim.fit, unless --output is given. FIT files are trimmed into FIT files. GPX and TCX files are trimmed into the same format, or into the other one if --output says so.")
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("One or more .fit, .gpx or .tcx file(s) to trim. Wildcards and multiple_occurrences files (e.g. 2019*.fit 2020*.fit) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Print summary information
+            Arg::new("print-summary")
+                .short('s')
+                .long("print-summary")
+                .help("Print the summary of each activity trimmed.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Overwrite existing files
+            Arg::new("force")
+                .short('f')
+                .long("force")
+                .help("Overwrite files that already exist. Without this, the files that would be overwritten are skipped.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Keep the standing still
+            Arg::new("keep-idle")
+                .short('k')
+                .long("keep-idle")
+                .help("Keep the standing still at the start and the finish, and only trim off what is outside the range.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // The start of the time range
+            Arg::new("from")
+                .long("from")
+                .value_name("hh:mm:ss")
+                .help("Trim off everything before this far into the activity, e.g. 00:05:00.")
+                .num_args(1)
+                .conflicts_with_all(["from-distance", "to-distance"])
+                .action(ArgAction::Set)
+        )
+        .arg( // The end of the time range
+            Arg::new("to")
+                .long("to")
+                .value_name("hh:mm:ss")
+                .help("Trim off everything after this far into the activity, e.g. 01:30:00.")
+                .num_args(1)
+                .conflicts_with_all(["from-distance", "to-distance"])
+                .action(ArgAction::Set)
+        )
+        .arg( // The start of the distance range
+            Arg::new("from-distance")
+                .long("from-distance")
+                .value_name("DISTANCE")
+                .help("Trim off everything before this distance into the activity, in m, km or mi, e.g. 800m. Kilometers if no unit is given.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // The end of the distance range
+            Arg::new("to-distance")
+                .long("to-distance")
+                .value_name("DISTANCE")
+                .help("Trim off everything after this distance into the activity, in m, km or mi, e.g. 42.2km. Kilometers if no unit is given.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // The file to write
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("The file to write the trimmed activity to. The extension gives the format. Only for a single file.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "--read",
+            "test.fit",
+            "--debug",
+            "--debug",
+            "--quiet",
+            "--print-summary",
+            "--force",
+            "--keep-idle",
+            "--from",
+            "00:05:00",
+            "--to",
+            "01:30:00",
+            "--output",
+            "trimmed.fit",
+        ]);
+
+        assert!(args.contains_id("read"));
+        assert_eq!(args.get_count("debug"), 2);
+        assert!(args.get_flag("quiet"));
+        assert!(args.get_flag("print-summary"));
+        assert!(args.get_flag("force"));
+        assert!(args.get_flag("keep-idle"));
+        assert_eq!(
+            args.get_one::<String>("from").map(String::as_str),
+            Some("00:05:00")
+        );
+        assert_eq!(
+            args.get_one::<String>("to").map(String::as_str),
+            Some("01:30:00")
+        );
+        assert_eq!(
+            args.get_one::<String>("output").map(String::as_str),
+            Some("trimmed.fit")
+        );
+
+        // Short form and a distance range
+        let args2 = build().get_matches_from(vec![
+            "--read",
+            "test.fit",
+            "-d",
+            "-q",
+            "-s",
+            "-f",
+            "--from-distance",
+            "800m",
+            "--to-distance",
+            "42.2km",
+            "-o",
+            "trimmed.fit",
+        ]);
+
+        assert_eq!(args2.get_count("debug"), 1);
+        assert!(args2.get_flag("quiet"));
+        assert!(args2.get_flag("print-summary"));
+        assert!(args2.get_flag("force"));
+        assert!(!args2.get_flag("keep-idle"));
+        assert_eq!(
+            args2.get_one::<String>("from-distance").map(String::as_str),
+            Some("800m")
+        );
+        assert_eq!(
+            args2.get_one::<String>("to-distance").map(String::as_str),
+            Some("42.2km")
+        );
+        assert!(args2.contains_id("output"));
+
+        // A time and a distance range can't both be given
+        assert!(build()
+            .try_get_matches_from(vec![
+                "--read",
+                "test.fit",
+                "--from",
+                "00:05:00",
+                "--to-distance",
+                "5km"
+            ])
+            .is_err());
+    }
+}
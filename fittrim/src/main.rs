@@ -0,0 +1,99 @@
+use env_logger::Target;
+use std::error::Error;
+use std::path::Path;
+use utilities::{Duration, ExtractRange, TrimOptions};
+
+mod cli;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+    let force = cli_args.get_flag("force");
+    let print_summary = cli_args.get_flag("print-summary");
+
+    // Initialize logging
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stdout).init();
+
+    let duration = |name: &str| {
+        cli_args
+            .get_one::<String>(name)
+            .map(|value| value.parse::<Duration>())
+            .transpose()
+    };
+    let distance = |name: &str| {
+        cli_args
+            .get_one::<String>(name)
+            .map(|value| utilities::parse_distance(value))
+            .transpose()
+    };
+    let range = if cli_args.contains_id("from-distance") || cli_args.contains_id("to-distance") {
+        Some(ExtractRange::Distance {
+            from: distance("from-distance")?,
+            to: distance("to-distance")?,
+        })
+    } else if cli_args.contains_id("from") || cli_args.contains_id("to") {
+        Some(ExtractRange::Time {
+            from: duration("from")?,
+            to: duration("to")?,
+        })
+    } else {
+        None
+    };
+    let options = TrimOptions {
+        idle: !cli_args.get_flag("keep-idle"),
+        range,
+    };
+    if !options.idle && options.range.is_none() {
+        return Err("--keep-idle needs a time or distance range to trim.".into());
+    }
+    log::debug!("main::run() -- Trimming with {options:?}");
+
+    let filenames = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+    log::trace!("main::run() -- Files: {filenames:?}");
+
+    let output = cli_args.get_one::<String>("output");
+    if output.is_some() && filenames.len() > 1 {
+        return Err("--output can only be used with a single file.".into());
+    }
+
+    for filename in filenames.iter().map(std::string::String::as_str) {
+        let output = output.cloned().unwrap_or_else(|| {
+            let extension = utilities::get_extension(filename).to_lowercase();
+            utilities::set_extension(filename, &format!("trim.{extension}"))
+        });
+        if !force && Path::new(&output).exists() {
+            log::warn!("{output} already exists. Use --force to overwrite it. Skipping.");
+            continue;
+        }
+
+        log::debug!("Trimming {filename} into {output}");
+        let trimmed = utilities::trim_file(filename, &output, &options)?;
+        log::info!("{filename} trimmed into {output}");
+        if print_summary {
+            trimmed.print(false);
+        }
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
@@ -37,9 +37,116 @@ pub fn build() -> Command {
             Arg::new("print-detail")
                 .short('l')
                 .long("print-detail")
-                .help("Print more detail for each file processed.")
+                .help("Print more detail for each file processed, including the grade-adjusted pace and the time in each pace zone.")
                 .action(ArgAction::SetTrue)
         )
+        .arg( // Lap table
+            Arg::new("laps")
+                .long("laps")
+                .help("Print the time, distance, pace, average heart rate and average power of each lap recorded by the device. FIT and TCX files only.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Side by side comparison
+            Arg::new("compare")
+                .long("compare")
+                .help("Compare the distance, time, pace, average heart rate and power, and ascent of two files side by side, with the change from the first to the second, e.g. repeat efforts on the same route.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Sparkline charts
+            Arg::new("chart")
+                .long("chart")
+                .value_name("elevation|hr|power|speed")
+                .help("Print a chart of the elevation, heart rate, power or speed over time, as wide as the terminal. Separate several charts with commas.")
+                .num_args(1)
+                .value_delimiter(',')
+                .value_parser(utilities::RecordSeries::VALUES)
+                .action(ArgAction::Append)
+        )
+        .arg( // Smart recording gaps
+            Arg::new("interpolate")
+                .long("interpolate")
+                .help("Fill in the gaps between the records of FIT files made with smart recording with a record for every second before drawing the charts, for smoother lines.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Functional Threshold Power
+            Arg::new("ftp")
+                .long("ftp")
+                .value_name("WATTS")
+                .help("The Functional Threshold Power used for the Intensity Factor and Training Stress Score of FIT files. The default is the threshold power recorded in each file.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .action(ArgAction::Set)
+        )
+        .arg( // Split table
+            Arg::new("splits")
+                .long("splits")
+                .value_name("km|mi")
+                .help("Print the time, pace, grade-adjusted pace, heart rate and elevation change for each kilometer or mile, worked out from the records rather than the laps.")
+                .num_args(1)
+                .value_parser(utilities::SplitUnit::VALUES)
+                .action(ArgAction::Set)
+        )
+        .arg( // Pace zones
+            Arg::new("pace-zones")
+                .long("pace-zones")
+                .value_name("PACE|PACE,PACE,PACE,PACE")
+                .help("The pace zones used for the time in each zone in the detail output (see --print-detail): either the threshold pace, with the zones starting at 129, 114, 106 and 99% of it, or the paces where zones 1 to 4 start, in min/km. Without it, there are no pace zones.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Heart rate zones
+            Arg::new("hr-zones")
+                .long("hr-zones")
+                .value_name("MAX|lthr:BPM|BPM,BPM,BPM,BPM")
+                .help("Work out the time in each heart rate zone from the records instead of showing the times recorded by the device: either the maximum heart rate, with the zones starting at 60, 70, 80 and 90% of it, the lactate threshold heart rate as lthr:BPM, with the zones starting at 85, 90, 95 and 100% of it, or the heart rates where zones 1 to 4 start.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Race report
+            Arg::new("race")
+                .long("race")
+                .value_name("5k|10k|half|marathon")
+                .help("Print the time each race distance was reached and the predicted finish time for it. Separate several distances with commas.")
+                .num_args(1)
+                .value_delimiter(',')
+                .value_parser(utilities::RaceDistance::VALUES)
+                .action(ArgAction::Append)
+        )
+        .arg( // Mean-maximal curve
+            Arg::new("mean-max")
+                .long("mean-max")
+                .help("Print the best average power and heart rate held over 5 seconds to 2 hours for each file, and the best of all the files combined.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Combined mean-maximal curve
+            Arg::new("mean-max-csv")
+                .long("mean-max-csv")
+                .value_name("FILE")
+                .help("Write the best average power and heart rate of all the files combined, for every duration from 1 second, to a CSV file.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Title template
+            Arg::new("title")
+                .long("title")
+                .value_name("TEMPLATE")
+                .help("Print a title above each file, built from the same tokens as the fitrename patterns, e.g. \"%activity %year-%month-%day %hour:%minute\".")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Unit system
+            Arg::new("units")
+                .long("units")
+                .value_name("metric|imperial")
+                .help("Print the distances, heights and speeds in kilometers, meters and km/h, or miles, feet and mph, instead of meters and m/s. Running, walking and hiking also get the pace per kilometer or mile.")
+                .num_args(1)
+                .value_parser(utilities::UnitSystem::VALUES)
+                .action(ArgAction::Set)
+        )
         .arg( // Don't print any information
             Arg::new("quiet")
                 .short('q')
@@ -67,6 +174,28 @@ mod tests {
             "--debug",
             "--print-summary",
             "--print-detail",
+            "--laps",
+            "--compare",
+            "--chart",
+            "hr,elevation",
+            "--interpolate",
+            "--ftp",
+            "250",
+            "--splits",
+            "km",
+            "--pace-zones",
+            "6:00,5:15,4:45,4:25",
+            "--hr-zones",
+            "185",
+            "--race",
+            "5k,half",
+            "--mean-max",
+            "--mean-max-csv",
+            "best.csv",
+            "--title",
+            "%activity %year",
+            "--units",
+            "imperial",
             "--quiet",
         ]);
 
@@ -74,6 +203,49 @@ mod tests {
         assert!(args.contains_id("debug"));
         assert!(args.contains_id("print-summary"));
         assert!(args.contains_id("print-detail"));
+        assert!(args.get_flag("laps"));
+        assert!(args.get_flag("compare"));
+        assert_eq!(
+            args.get_many::<String>("chart")
+                .unwrap_or_default()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            vec!["hr", "elevation"]
+        );
+        assert!(args.get_flag("interpolate"));
+        assert_eq!(args.get_one::<u16>("ftp"), Some(&250));
+        assert_eq!(
+            args.get_one::<String>("splits").map(String::as_str),
+            Some("km")
+        );
+        assert_eq!(
+            args.get_one::<String>("pace-zones").map(String::as_str),
+            Some("6:00,5:15,4:45,4:25")
+        );
+        assert_eq!(
+            args.get_one::<String>("hr-zones").map(String::as_str),
+            Some("185")
+        );
+        assert_eq!(
+            args.get_many::<String>("race")
+                .unwrap_or_default()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            vec!["5k", "half"]
+        );
+        assert!(args.get_flag("mean-max"));
+        assert_eq!(
+            args.get_one::<String>("mean-max-csv").map(String::as_str),
+            Some("best.csv")
+        );
+        assert_eq!(
+            args.get_one::<String>("title").map(String::as_str),
+            Some("%activity %year")
+        );
+        assert_eq!(
+            args.get_one::<String>("units").map(String::as_str),
+            Some("imperial")
+        );
         assert!(args.contains_id("quiet"));
         assert_eq!(args.get_count("debug"), 2);
 
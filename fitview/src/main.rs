@@ -1,57 +1,134 @@
 use env_logger::Target;
 use std::error::Error;
-use utilities::{FITActivity, GPXActivity, TCXActivity};
+use utilities::{Activity, ActivityFormat, RaceDistance, RecordSeries, SplitUnit, UnitSystem};
 
 use clap::parser::ValueSource;
 
 mod cli;
 
+/// The width of the charts if the width of the terminal isn't known.
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// This is where the magic happens.
 fn run() -> Result<(), Box<dyn Error>> {
     // Set up the command line. Ref https://docs.rs/clap for details.
-    let cli_args = cli::build().get_matches();
+    let cli_args = utilities::with_config(cli::build()).get_matches();
     let detailed = cli_args.value_source("print-detail") == Some(ValueSource::CommandLine);
     let print_summary = cli_args.value_source("print-summary") == Some(ValueSource::CommandLine);
+    let split_unit = cli_args
+        .get_one::<String>("splits")
+        .map(|unit| unit.parse::<SplitUnit>())
+        .transpose()?;
+    let race_distances = cli_args
+        .get_many::<String>("race")
+        .unwrap_or_default()
+        .map(|distance| distance.parse::<RaceDistance>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let print_laps = cli_args.get_flag("laps");
+    let compare = cli_args.get_flag("compare");
+    let charts = cli_args
+        .get_many::<String>("chart")
+        .unwrap_or_default()
+        .map(|series| series.parse::<RecordSeries>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let interpolate = cli_args.get_flag("interpolate");
+    let print_mean_max = cli_args.get_flag("mean-max");
+    let mean_max_csv = cli_args.get_one::<String>("mean-max-csv");
+    let title = cli_args.get_one::<String>("title");
+    let mut settings = utilities::Settings::default();
+    settings.set_unit_system(
+        cli_args
+            .get_one::<String>("units")
+            .map(|units| units.parse::<UnitSystem>())
+            .transpose()?,
+    );
+    settings.set_ftp(cli_args.get_one::<u16>("ftp").copied().map(f64::from));
+    if let Some(zones) = cli_args.get_one::<String>("pace-zones") {
+        settings.set_pace_zones(zones.parse::<utilities::PaceZoneLimits>()?);
+    }
+    if let Some(zones) = cli_args.get_one::<String>("hr-zones") {
+        settings.set_hr_zones(zones.parse::<utilities::HrZoneLimits>()?);
+    }
+    let _settings = settings.apply();
 
     // Initialize logging
     let mut logbuilder = utilities::build_log(&cli_args);
     logbuilder.target(Target::Stdout).init();
 
-    let filenames = cli_args
-        .get_many::<String>("read")
-        .unwrap_or_default()
-        .map(std::string::String::as_str);
+    let expanded = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+    let filenames = expanded.iter().map(std::string::String::as_str);
     log::trace!("main::run() -- Files: {filenames:?}");
 
+    if compare {
+        return compare_files(&filenames.collect::<Vec<_>>());
+    }
+
     let mut total_files: usize = 0;
     let mut processed_files: usize = 0;
     let skipped_files: usize = 0;
+    let mut curves = Vec::new();
 
     // The good stuff goes here
     for filename in filenames {
         log::debug!("Processing file: {filename}");
-        match utilities::get_extension(filename).as_ref() {
-            "fit" => {
-                let act = FITActivity::from_file(filename)?;
-                act.print(detailed);
-                processed_files += 1;
+        if ActivityFormat::from_filename(filename).is_some() {
+            let mut activity = Activity::from_file(filename)?;
+            if interpolate {
+                activity.interpolate_records();
+            }
+            if let Some(template) = title {
+                let title = utilities::activity_title(filename, template)?;
+                println!("\n{title}\n{}", "=".repeat(title.chars().count()));
+            }
+            activity.print(detailed);
+            if detailed {
+                utilities::print_pace_analysis(&activity.split_points());
+            }
+            if print_laps {
+                utilities::print_laps(&activity.lap_summaries());
+            }
+            for series in &charts {
+                utilities::print_chart(*series, &activity.series(*series), terminal_width());
             }
-            "gpx" => {
-                let act = GPXActivity::from_file(filename)?;
-                act.print(detailed);
-                processed_files += 1;
+            if let Some(unit) = split_unit {
+                utilities::print_splits(&activity.splits(unit));
             }
-            "tcx" => {
-                let act = TCXActivity::from_file(filename)?;
-                act.print(detailed);
-                processed_files += 1;
+            if !race_distances.is_empty() {
+                utilities::print_race_report(&activity.race_report(&race_distances));
             }
-            _ => log::warn!("Unknown file type: {filename}."),
+            if print_mean_max || mean_max_csv.is_some() {
+                let curve = activity.mean_max();
+                if print_mean_max {
+                    utilities::print_mean_max(&curve);
+                }
+                curves.push(curve);
+            }
+            processed_files += 1;
+        } else {
+            log::warn!("Unknown file type: {filename}.");
         }
         total_files += 1;
     }
 
+    // The best of all the files, for following the form over many activities
+    if !curves.is_empty() {
+        let best = utilities::best_mean_max(&curves);
+        if print_mean_max && curves.len() > 1 {
+            println!("\nBest of all {} files:", curves.len());
+            utilities::print_mean_max(&best);
+        }
+        if let Some(filename) = mean_max_csv {
+            utilities::export_mean_max_csv(&best, filename)?;
+            log::info!("Mean-maximal curve of all the files written to {filename}");
+        }
+    }
+
     if print_summary {
         log::info!("Total files examined:        {total_files:6}");
         log::info!("Files processed:             {processed_files:6}");
@@ -62,6 +139,41 @@ fn run() -> Result<(), Box<dyn Error>> {
     Ok(())
 } // fn run()
 
+/// Prints the totals of two files side by side, with the change from the first to the second.
+fn compare_files(filenames: &[&str]) -> Result<(), Box<dyn Error>> {
+    let [first, second] = filenames else {
+        return Err(format!(
+            "--compare needs two files, but {} were given.",
+            filenames.len()
+        )
+        .into());
+    };
+
+    let name = |filename: &str| {
+        std::path::Path::new(filename).file_name().map_or_else(
+            || filename.to_string(),
+            |name| name.to_string_lossy().to_string(),
+        )
+    };
+    utilities::print_comparison(
+        [&name(first), &name(second)],
+        [
+            Activity::from_file(first)?.totals(),
+            Activity::from_file(second)?.totals(),
+        ],
+    );
+
+    Ok(())
+}
+
+/// The width of the terminal from the `COLUMNS` environment variable, or 80 characters if it isn't set.
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse().ok())
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// The actual executable function that gets called when the program in invoked.
 fn main() {
@@ -0,0 +1,10 @@
+//! Parses arbitrary bytes as a FIT file. Decoding errors are fine -- panics are not.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use utilities::{FITActivity, FITParseStats};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = FITActivity::from_reader(&mut &data[..], "fuzz.fit");
+    let _ = FITParseStats::from_bytes(data);
+});
@@ -0,0 +1,65 @@
+//! Converts arbitrary field values into records, laps and heart rate zones. The FIT decoder only produces values that
+//! fit the profile, so this reaches the unit, position and duration conversions with values a decoded file can't.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chrono::{Local, TimeZone};
+use fitparser::{FitDataField, Value};
+use libfuzzer_sys::fuzz_target;
+use utilities::{FITHrZones, FITLap, FITRecord, FITSession};
+
+/// The field values that go through a conversion rather than being copied as-is.
+#[derive(Arbitrary, Debug)]
+struct Fields {
+    timestamp: i64,
+    time_created: i64,
+    lat: i32,
+    lon: i32,
+    distance: f64,
+    speed: f64,
+    stance_time: f64,
+    elapsed_time: f64,
+    timer_time: f64,
+    moving_time: f64,
+    hr_zones: Vec<u32>,
+}
+
+/// Creates a field with the name and value.
+fn field(name: &str, value: Value) -> FitDataField {
+    FitDataField::new(name.to_string(), 0, value, String::new())
+}
+
+fuzz_target!(|input: Fields| {
+    let Some(timestamp) = Local.timestamp_opt(input.timestamp, 0).single() else {
+        return;
+    };
+    let mut session = FITSession {
+        time_created: Local.timestamp_opt(input.time_created, 0).single(),
+        ..FITSession::default()
+    };
+
+    let hr_zones = Value::Array(input.hr_zones.iter().copied().map(Value::UInt32).collect());
+    let fields = vec![
+        field("timestamp", Value::Timestamp(timestamp)),
+        field("start_time", Value::Timestamp(timestamp)),
+        field("position_lat", Value::SInt32(input.lat)),
+        field("position_long", Value::SInt32(input.lon)),
+        field("start_position_lat", Value::SInt32(input.lat)),
+        field("start_position_long", Value::SInt32(input.lon)),
+        field("distance", Value::Float64(input.distance)),
+        field("total_distance", Value::Float64(input.distance)),
+        field("enhanced_speed", Value::Float64(input.speed)),
+        field("enhanced_avg_speed", Value::Float64(input.speed)),
+        field("stance_time", Value::Float64(input.stance_time)),
+        field("avg_stance_time", Value::Float64(input.stance_time)),
+        field("total_elapsed_time", Value::Float64(input.elapsed_time)),
+        field("total_timer_time", Value::Float64(input.timer_time)),
+        field("total_moving_time", Value::Float64(input.moving_time)),
+        field("time_in_hr_zone", hr_zones.clone()),
+    ];
+
+    let _ = FITRecord::from_fit_record(&fields, &session);
+    let _ = FITLap::from_fit_lap(&fields, &session);
+    let _ = FITHrZones::from(Some(&&hr_zones));
+    session.parse_session(&fields);
+});
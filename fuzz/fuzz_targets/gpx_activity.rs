@@ -0,0 +1,9 @@
+//! Parses arbitrary bytes as a GPX file. Parsing errors are fine -- panics are not.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use utilities::GPXActivity;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = GPXActivity::from_reader(data, "fuzz.gpx");
+});
@@ -0,0 +1,9 @@
+//! Parses arbitrary bytes as a TCX file. Parsing errors are fine -- panics are not.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use utilities::TCXActivity;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = TCXActivity::from_reader(data, "fuzz.tcx");
+});
@@ -0,0 +1,121 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        // .author(clap::crate_authors!("\n"))
+        .long_about("This program will convert one or more .gpx files to FIT course files named after them, e.g. ride.course.fit, for navigating on a Garmin or Wahoo unit. The first route in the file is used, or the tracks if there are no routes. The turns and the categorized climbs are added as course points for the turn-by-turn directions and the climb prompts.")
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("One or more .gpx file(s) to convert. Wildcards and multiple_occurrences files (e.g. 2019*.gpx 2020*.gpx) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Print summary information
+            Arg::new("print-summary")
+                .short('s')
+                .long("print-summary")
+                .help("Print a summary of the number of files converted and skipped.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Overwrite existing files
+            Arg::new("force")
+                .short('f')
+                .long("force")
+                .help("Overwrite .course.fit files that already exist. Without this, the files that would be overwritten are skipped.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Name of the course
+            Arg::new("name")
+                .short('n')
+                .long("name")
+                .value_name("NAME")
+                .help("The name of the course, as listed on the unit. Defaults to the name of the route or track, or of the file.")
+                .num_args(1)
+                .action(ArgAction::Set)
+        )
+        .arg( // Speed of the virtual partner
+            Arg::new("speed")
+                .long("speed")
+                .value_name("KM/H")
+                .help("The speed the times of the course are worked out from, in km/h, for routes without times. Sets the pace of the virtual partner. Defaults to 20.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(f64))
+                .action(ArgAction::Set)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "--read",
+            "test.gpx",
+            "--debug",
+            "--debug",
+            "--quiet",
+            "--print-summary",
+            "--force",
+            "--name",
+            "Sunday loop",
+            "--speed",
+            "25.5",
+        ]);
+
+        assert!(args.contains_id("read"));
+        assert_eq!(args.get_count("debug"), 2);
+        assert!(args.get_flag("quiet"));
+        assert!(args.get_flag("print-summary"));
+        assert!(args.get_flag("force"));
+        assert_eq!(
+            args.get_one::<String>("name").map(String::as_str),
+            Some("Sunday loop")
+        );
+        assert_eq!(args.get_one::<f64>("speed").copied(), Some(25.5));
+
+        // Short form
+        let args2 = build().get_matches_from(vec![
+            "--read", "test.gpx", "-d", "-q", "-s", "-f", "-n", "Loop",
+        ]);
+
+        assert_eq!(args2.get_count("debug"), 1);
+        assert!(args2.get_flag("quiet"));
+        assert!(args2.get_flag("print-summary"));
+        assert!(args2.get_flag("force"));
+        assert_eq!(
+            args2.get_one::<String>("name").map(String::as_str),
+            Some("Loop")
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use env_logger::Target;
+use std::error::Error;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use utilities::{ActivityFormat, CoursePointType, GPXActivity};
+
+mod cli;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// This is where the magic happens.
+fn run() -> Result<(), Box<dyn Error>> {
+    // Set up the command line. Ref https://docs.rs/clap for details.
+    let cli_args = utilities::with_config(cli::build()).get_matches();
+    let force = cli_args.get_flag("force");
+    let speed = cli_args
+        .get_one::<f64>("speed")
+        .map_or(utilities::DEFAULT_COURSE_SPEED_MS, |kmh| kmh / 3.6);
+
+    // Initialize logging
+    let mut logbuilder = utilities::build_log(&cli_args);
+    logbuilder.target(Target::Stdout).init();
+
+    let expanded = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+    let filenames = expanded.iter().map(std::string::String::as_str);
+    log::trace!("main::run() -- Files: {filenames:?}");
+
+    let mut total_files: usize = 0;
+    let mut converted_files: usize = 0;
+
+    for filename in filenames {
+        total_files += 1;
+        if ActivityFormat::from_filename(filename) != Some(ActivityFormat::Gpx) {
+            log::warn!("{filename}: Not a GPX file. Skipping.");
+            continue;
+        }
+
+        let output = utilities::set_extension(filename, "course.fit");
+        if !force && Path::new(&output).exists() {
+            log::warn!("{output} already exists. Use --force to overwrite it. Skipping.");
+            continue;
+        }
+
+        log::debug!("Converting {filename} to {output}");
+        let Some(mut course) = GPXActivity::from_file(filename)?.to_course() else {
+            log::warn!("{filename}: No route or track with at least two positions. Skipping.");
+            continue;
+        };
+        if let Some(name) = cli_args.get_one::<String>("name") {
+            course.name.clone_from(name);
+        }
+
+        utilities::write_atomically(Path::new(&output), |file| {
+            let mut writer = BufWriter::new(file);
+            course.write_fit(&mut writer, speed)?;
+            Ok(writer.flush()?)
+        })?;
+
+        let turns = course
+            .course_points
+            .iter()
+            .filter(|point| point.point_type.is_turn())
+            .count();
+        let climbs = course
+            .course_points
+            .iter()
+            .filter(|point| !point.point_type.is_turn())
+            .filter(|point| point.point_type != CoursePointType::Summit)
+            .count();
+        log::info!(
+            "{filename} converted to {output}: {:.1} km with {turns} turns and {climbs} climbs",
+            course.distance() / 1_000.0
+        );
+        converted_files += 1;
+    }
+
+    if cli_args.get_flag("print-summary") {
+        log::info!("Total files examined:        {total_files:6}");
+        log::info!("Files converted:             {converted_files:6}");
+        log::info!(
+            "Files skipped:               {:6}",
+            total_files - converted_files
+        );
+    }
+
+    // Everything is a-okay in the end
+    Ok(())
+} // fn run()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The actual executable function that gets called when the program in invoked.
+fn main() {
+    std::process::exit(match run() {
+        Ok(()) => 0, // everying is hunky dory - exit with code 0 (success)
+        Err(err) => {
+            log::error!("{}", err.to_string().replace('\"', ""));
+            1 // exit with a non-zero return code, indicating a problem
+        }
+    });
+}
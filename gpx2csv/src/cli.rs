@@ -13,7 +13,7 @@ pub fn build() -> Command {
             .value_name("FILE(S)")
             .help("One or more .gpx file(s) to process. Wildcards and multiple_occurrences files (e.g. 2019*.gpx 2020*.gpx) are supported.")
             .num_args(1..)
-            .required(true)
+            .required_unless_present("csv-schema")
             .action(ArgAction::Append)
     )
     .arg( // Hidden debug parameter
@@ -51,6 +51,282 @@ pub fn build() -> Command {
             .num_args(1)
             .action(ArgAction::Set)
     )
+    .arg( // Per-file timeout
+        Arg::new("timeout")
+            .long("timeout")
+            .value_name("SECONDS")
+            .help("Skip files that take longer than this many seconds to process. Skipped files are listed with the error in the manifest.")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .action(ArgAction::Set)
+    )
+    .arg( // Carry on after bad files
+        Arg::new("keep-going")
+            .long("keep-going")
+            .value_name("true|false")
+            .help("Carry on with the rest of the files when one can't be read or processed, listing the files that failed at the end and exiting with code 2. On by default. Use --keep-going=false to stop at the first bad file instead, exiting with code 1.")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_value("true")
+            .default_missing_value("true")
+            .value_parser(clap::value_parser!(bool))
+            .action(ArgAction::Set)
+    )
+    .arg( // Skip short activities
+        Arg::new("min-duration")
+            .long("min-duration")
+            .value_name("hh:mm:ss")
+            .help("Skip activities shorter than this, e.g. 00:01:00, leaving them out of the summary and writing no details for them. Skipped files are listed in the manifest.")
+            .num_args(1)
+            .action(ArgAction::Set)
+    )
+    .arg( // Skip short activities
+        Arg::new("min-distance")
+            .long("min-distance")
+            .value_name("DISTANCE")
+            .help("Skip activities shorter than this distance, in m, km or mi, e.g. 200m, leaving them out of the summary and writing no details for them. Kilometers if no unit is given.")
+            .num_args(1)
+            .action(ArgAction::Set)
+    )
+    .arg( // Single JSON document per file
+        Arg::new("bundle")
+            .short('b')
+            .long("bundle")
+            .help("Export the details from each file as a single .bundle.json document instead of separate files.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // One activity per track
+        Arg::new("split-tracks")
+            .long("split-tracks")
+            .help("Treat each track as its own activity, with its own summary row and detail files, instead of merging the tracks into one activity.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Split the summary file
+        Arg::new("split-summary-by")
+            .long("split-summary-by")
+            .value_name("sport|year|device")
+            .help("Write the summary as one file per sport, year or device instead of a single file.")
+            .num_args(1)
+            .value_parser(utilities::SummarySplit::VALUES)
+            .action(ArgAction::Set)
+    )
+    .arg( // Rounding of the exported numbers
+        Arg::new("precision")
+            .long("precision")
+            .value_name("DECIMALS")
+            .help("Round the decimal numbers in the exported files to this many decimals.")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32))
+            .action(ArgAction::Set)
+    )
+    .arg( // Rounding of individual fields
+        Arg::new("field-precision")
+            .long("field-precision")
+            .value_name("FIELD=DECIMALS")
+            .help("Round a single field to this many decimals, e.g. latitude=6. Overrides --precision. May be given more than once.")
+            .num_args(1)
+            .action(ArgAction::Append)
+    )
+    .arg( // CSV delimiter
+        Arg::new("delimiter")
+            .long("delimiter")
+            .value_name("CHAR")
+            .help("Separate the values in the CSV files with this character instead of a comma, e.g. ; or |. Use tab for tab separated values.")
+            .num_args(1)
+            .action(ArgAction::Set)
+    )
+    .arg( // Decimal comma
+        Arg::new("decimal-comma")
+            .long("decimal-comma")
+            .help("Write the decimals in the CSV files with a comma, e.g. 12,5, as spreadsheets set up for most European locales expect. The values are separated by semicolons unless --delimiter is given.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Stable CSV columns
+        Arg::new("columns-version")
+            .long("columns-version")
+            .value_name("VERSION")
+            .help("Write the CSV columns as they were in an earlier version, leaving out the columns added since. Use 1 for the columns before the UUIDs, GPS checks, pacing and recording interval were added.")
+            .num_args(1)
+            .value_parser(utilities::ColumnsVersion::VALUES)
+            .action(ArgAction::Set)
+    )
+    .arg( // CSV schema
+        Arg::new("csv-schema")
+            .long("csv-schema")
+            .help("Print the columns of each CSV file written, with the type and unit of each, as JSON and exit. The columns follow --columns-version and --units.")
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Summary JSON layout
+        Arg::new("schema")
+            .long("schema")
+            .value_name("VERSION")
+            .help("The layout of the summary JSON files. v2 adds the unit of each field, writes durations as seconds and nests the heart rate zones. v1, the default, keeps the layout of earlier versions.")
+            .num_args(1)
+            .value_parser(utilities::SummarySchema::VALUES)
+            .action(ArgAction::Set)
+    )
+    .arg( // Heart rate zones
+        Arg::new("hr-zones")
+            .long("hr-zones")
+            .value_name("MAX|lthr:BPM|BPM,BPM,BPM,BPM")
+            .help("The heart rate zones used for the time in each zone in the summary: either the maximum heart rate, with the zones starting at 60, 70, 80 and 90% of it, the lactate threshold heart rate as lthr:BPM, with the zones starting at 85, 90, 95 and 100% of it, or the heart rates where zones 1 to 4 start. The default is a maximum heart rate of 190.")
+            .num_args(1)
+            .action(ArgAction::Set)
+    )
+    .arg( // GPS spikes
+        Arg::new("clean-gps")
+            .long("clean-gps")
+            .value_name("M/S")
+            .help("Find the GPS spikes, where the position jumps away faster than this speed in m/s and comes back within 5 waypoints, and drop them. The default speed is 50 m/s. Without it, the spikes are only counted in the gps_outliers column of the summary.")
+            .num_args(0..=1)
+            .default_missing_value("50")
+            .value_parser(clap::value_parser!(f64))
+            .action(ArgAction::Set)
+    )
+    .arg( // Parse statistics
+        Arg::new("stats")
+            .long("stats")
+            .help("Print counts of what was found in each file, including what was ignored.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Parse warnings
+        Arg::new("show-warnings")
+            .long("show-warnings")
+            .help("Print the warnings found in each file, e.g. tracks without waypoints, after the file is processed.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Manifest
+        Arg::new("manifest")
+            .long("manifest")
+            .value_name("FILE")
+            .help("Write a JSON manifest listing each file processed, with the files written and the warnings found.")
+            .num_args(1)
+            .action(ArgAction::Set)
+    )
+    .arg( // Split table
+        Arg::new("splits")
+            .long("splits")
+            .value_name("km|mi")
+            .help("Export a table of the time, pace, heart rate and elevation change for each kilometer or mile to a .splits.csv file, worked out from the records rather than the laps.")
+            .num_args(1)
+            .value_parser(utilities::SplitUnit::VALUES)
+            .action(ArgAction::Set)
+    )
+    .arg( // Records format
+        Arg::new("format")
+            .long("format")
+            .value_name("csv|parquet|jsonl")
+            .help("The format of the waypoints file: csv, parquet for loading large files straight into pandas, polars or DuckDB, or jsonl for one JSON object per line for jq and log pipelines. The other files are always CSV.")
+            .num_args(1)
+            .default_value("csv")
+            .value_parser(utilities::ExportFormat::VALUES)
+            .action(ArgAction::Set)
+    )
+    .arg( // Mean-maximal curve
+        Arg::new("mean-max")
+            .long("mean-max")
+            .help("Export the best average power and heart rate held over durations from 1 second to the length of the activity, every second up to a minute and more sparsely after that, to a .mean_max.csv file. TCX files only have the heart rate.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Heart rate scatter
+        Arg::new("hr-scatter")
+            .long("hr-scatter")
+            .help("Export the heart rate against the speed, pace and power averaged over each 30 seconds to a .hr_scatter.csv file, for plotting the heart rate at the same effort. The first 5 minutes, the stops and the outliers are left out. TCX files have no power.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // GeoJSON track
+        Arg::new("geojson")
+            .long("geojson")
+            .help("Export the track to a .geojson file as a GeoJSON FeatureCollection with a line for each track, along with the start time, distance and sport of the track, for maps such as Leaflet, Mapbox or QGIS.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Elevation profile
+        Arg::new("export-profile-svg")
+            .long("export-profile-svg")
+            .help("Export the elevation against the distance to a .profile.svg image, with the title, a grid and labelled axes, that opens in any browser and can go straight into a web page or Markdown document. The distances and elevations are in the unit system given with --units, or kilometers and meters.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Unit system
+        Arg::new("units")
+            .long("units")
+            .value_name("metric|imperial")
+            .help("Convert the distances, heights and speeds in the exports and the printed summaries from meters and m/s to kilometers, meters and km/h, or miles, feet and mph. The columns are renamed to match, e.g. distance_km or speed_avg_mph.")
+            .num_args(1)
+            .value_parser(utilities::UnitSystem::VALUES)
+            .action(ArgAction::Set)
+    )
+    .arg( // Placeholder for values that aren't known
+        Arg::new("placeholder")
+            .long("placeholder")
+            .value_name("TEXT")
+            .help("Use this text for values that aren't known, e.g. a missing manufacturer or start time, instead of the defaults such as Unknown and 0000. Use \"\" to leave them blank.")
+            .num_args(1)
+            .action(ArgAction::Set)
+    )
+    .arg( // Summary cache
+        Arg::new("cache")
+            .long("cache")
+            .value_name("FILE")
+            .help("Keep the file summaries in this cache file, so runs with --detail-off only parse the files that are new or changed since the last run.")
+            .num_args(1)
+            .action(ArgAction::Set)
+    )
+    .arg( // Activity tags
+        Arg::new("tag")
+            .long("tag")
+            .value_name("TAG")
+            .help("Add this tag to each activity processed, e.g. race or brevet. The tags are kept in the tag file and added to the summary. May be given more than once.")
+            .num_args(1)
+            .action(ArgAction::Append)
+    )
+    .arg( // Tag file
+        Arg::new("tags-file")
+            .long("tags-file")
+            .value_name("FILE")
+            .help("Keep the tags of the activities in this file, keyed by the activity UUID, so they stay with the activity when the file is renamed or moved.")
+            .num_args(1)
+            .default_value(utilities::DEFAULT_TAG_FILE)
+            .action(ArgAction::Set)
+    )
+    .arg( // Output directory
+        Arg::new("output-dir")
+            .long("output-dir")
+            .value_name("DIR")
+            .help("Write the exported files for each activity to this directory instead of next to the input file. The summary file is still written where --summary-file says.")
+            .num_args(1)
+            .action(ArgAction::Set)
+    )
+    .arg( // Folder per activity
+        Arg::new("subfolders")
+            .long("subfolders")
+            .help("Write the exported files for each activity to a folder named after the input file, e.g. running/running.laps.csv.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
+    .arg( // Output suffixes
+        Arg::new("output-suffix")
+            .long("output-suffix")
+            .value_name("SUFFIX=NEW")
+            .help("Name the exported files with another suffix, e.g. laps.csv=lap-table.csv. May be given more than once.")
+            .num_args(1)
+            .action(ArgAction::Append)
+    )
+    .arg( // Flush to disk
+        Arg::new("fsync")
+            .long("fsync")
+            .help("Flush each file to disk before it replaces the old one, so it survives a crash or power failure. Slower.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+    )
 }
 
 #[cfg(test)]
@@ -70,6 +346,64 @@ mod tests {
             "--detail-off",
             "--summary-file",
             "test.csv",
+            "--timeout",
+            "30",
+            "--keep-going=false",
+            "--min-duration",
+            "00:01:00",
+            "--min-distance",
+            "200m",
+            "--bundle",
+            "--split-summary-by",
+            "sport",
+            "--precision",
+            "2",
+            "--delimiter",
+            ";",
+            "--decimal-comma",
+            "--field-precision",
+            "latitude=6",
+            "--field-precision",
+            "longitude=6",
+            "--columns-version",
+            "1",
+            "--schema",
+            "v2",
+            "--csv-schema",
+            "--hr-zones",
+            "lthr:165",
+            "--clean-gps",
+            "--stats",
+            "--show-warnings",
+            "--split-tracks",
+            "--manifest",
+            "manifest.json",
+            "--splits",
+            "mi",
+            "--mean-max",
+            "--hr-scatter",
+            "--geojson",
+            "--export-profile-svg",
+            "--format",
+            "jsonl",
+            "--cache",
+            "cache.json",
+            "--tag",
+            "race",
+            "--tag",
+            "brevet",
+            "--tags-file",
+            "tags.json",
+            "--output-dir",
+            "out",
+            "--subfolders",
+            "--output-suffix",
+            "laps.csv=lap-table.csv",
+            "--fsync",
+            "--placeholder",
+            "",
+            "--units",
+            "metric",
         ]);
 
         assert!(args.contains_id("read"));
@@ -78,17 +412,116 @@ mod tests {
         assert!(args.contains_id("quiet"));
         assert!(args.contains_id("detail-off"));
         assert!(args.contains_id("summary-file"));
+        assert_eq!(args.get_one::<u64>("timeout"), Some(&30));
+        assert_eq!(args.get_one::<bool>("keep-going"), Some(&false));
+        assert_eq!(
+            args.get_one::<String>("min-duration").map(String::as_str),
+            Some("00:01:00")
+        );
+        assert_eq!(
+            args.get_one::<String>("min-distance").map(String::as_str),
+            Some("200m")
+        );
+        assert!(args.get_flag("bundle"));
+        assert_eq!(
+            args.get_one::<String>("split-summary-by")
+                .map(String::as_str),
+            Some("sport")
+        );
+        assert_eq!(args.get_one::<u32>("precision"), Some(&2));
+        assert_eq!(
+            args.get_one::<String>("delimiter").map(String::as_str),
+            Some(";")
+        );
+        assert!(args.get_flag("decimal-comma"));
+        assert!(args.get_flag("csv-schema"));
+        assert!(args.get_flag("stats"));
+        assert!(args.get_flag("show-warnings"));
+        assert!(args.get_flag("split-tracks"));
+        assert_eq!(
+            args.get_one::<String>("hr-zones").map(String::as_str),
+            Some("lthr:165")
+        );
+        assert_eq!(
+            args.get_one::<f64>("clean-gps"),
+            Some(&utilities::DEFAULT_MAX_GPS_SPEED_MS)
+        );
+        assert_eq!(
+            args.get_one::<String>("manifest").map(String::as_str),
+            Some("manifest.json")
+        );
+        assert_eq!(
+            args.get_one::<String>("splits").map(String::as_str),
+            Some("mi")
+        );
+        assert!(args.get_flag("mean-max"));
+        assert!(args.get_flag("hr-scatter"));
+        assert!(args.get_flag("geojson"));
+        assert!(args.get_flag("export-profile-svg"));
+        assert_eq!(
+            args.get_one::<String>("format").map(String::as_str),
+            Some("jsonl")
+        );
+        assert_eq!(
+            args.get_one::<String>("cache").map(String::as_str),
+            Some("cache.json")
+        );
+        assert_eq!(
+            args.get_many::<String>("tag")
+                .unwrap_or_default()
+                .collect::<Vec<_>>(),
+            ["race", "brevet"]
+        );
+        assert_eq!(
+            args.get_one::<String>("tags-file").map(String::as_str),
+            Some("tags.json")
+        );
+        assert_eq!(
+            args.get_one::<String>("output-dir").map(String::as_str),
+            Some("out")
+        );
+        assert!(args.get_flag("subfolders"));
+        assert!(args.get_flag("fsync"));
+        assert_eq!(
+            args.get_one::<String>("output-suffix").map(String::as_str),
+            Some("laps.csv=lap-table.csv")
+        );
+        assert_eq!(
+            args.get_one::<String>("placeholder").map(String::as_str),
+            Some("")
+        );
+        assert_eq!(
+            args.get_one::<String>("units").map(String::as_str),
+            Some("metric")
+        );
+        assert_eq!(
+            args.get_many::<String>("field-precision")
+                .unwrap_or_default()
+                .count(),
+            2
+        );
+        assert_eq!(
+            args.get_one::<String>("columns-version")
+                .map(String::as_str),
+            Some("1")
+        );
+        assert_eq!(
+            args.get_one::<String>("schema").map(String::as_str),
+            Some("v2")
+        );
         assert_eq!(args.get_count("debug"), 2);
 
         let args2 = build().get_matches_from(vec![
-            "--read", "test.gpx", "-d", "-d", "-q", "-o", "-s", "test.csv",
+            "--read", "test.gpx", "-d", "-d", "-q", "-o", "-s", "test.csv", "-b",
         ]);
+        assert_eq!(args2.get_one::<bool>("keep-going"), Some(&true));
 
         assert!(args2.contains_id("read"));
         assert!(args2.contains_id("debug"));
         assert!(args2.contains_id("quiet"));
         assert!(args2.contains_id("detail-off"));
         assert!(args2.contains_id("summary-file"));
+        assert!(args2.get_flag("bundle"));
         assert_eq!(args2.get_count("debug"), 2);
     }
 }
@@ -6,18 +6,29 @@ use std::error::Error; // Command line
 mod cli;
 
 /// This is where the actual processing takes place.
-fn run() -> Result<(), Box<dyn Error>> {
+/// Returns the exit code, which is `EXIT_PARTIAL_FAILURE` if any of the files couldn't be processed.
+fn run() -> Result<i32, Box<dyn Error>> {
     // Set up the command line. Ref https://docs.rs/clap for details.
-    let cli_args = cli::build().get_matches();
+    let cli_args = utilities::with_config(cli::build()).get_matches();
 
     // Initialize logging
     let mut logbuilder = utilities::build_log(&cli_args);
-    logbuilder.target(Target::Stdout).init();
-
-    let filenames = cli_args
-        .get_many::<String>("read")
-        .unwrap_or_default()
-        .map(std::string::String::as_str);
+    // Keep stdout for the schema when it's printed
+    logbuilder
+        .target(if cli_args.get_flag("csv-schema") {
+            Target::Stderr
+        } else {
+            Target::Stdout
+        })
+        .init();
+
+    let expanded = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+    let filenames = expanded.iter().map(std::string::String::as_str);
 
     log::trace!("main::run() -- Files to be read: {filenames:?}");
 
@@ -35,43 +46,192 @@ fn run() -> Result<(), Box<dyn Error>> {
         log::info!("Writing summary file {sessionfile} and details.");
     }
 
+    let mut settings = utilities::Settings::default();
+
+    // Round the numbers in the exports if requested
+    settings.set_float_precision_from_args(&cli_args)?;
+    settings.set_output_naming_from_args(&cli_args)?;
+    settings.set_csv_format_from_args(&cli_args)?;
+    settings.set_sync_outputs(cli_args.get_flag("fsync"));
+
+    // Keep the CSV columns of an earlier version if requested
+    if let Some(version) = cli_args.get_one::<String>("columns-version") {
+        settings.set_columns_version(version.parse::<utilities::ColumnsVersion>()?);
+    }
+
+    // Write the summary JSON in the layout requested
+    if let Some(schema) = cli_args.get_one::<String>("schema") {
+        settings.set_summary_schema(schema.parse::<utilities::SummarySchema>()?);
+    }
+
+    // Drop the GPS spikes if requested
+    let clean_gps = cli_args.get_one::<f64>("clean-gps").copied();
+    if clean_gps.is_some_and(|speed| speed <= 0.0) {
+        return Err("The --clean-gps speed must be above 0 m/s.".into());
+    }
+    settings.set_gps_cleaning(clean_gps);
+
+    // Work out the time in the heart rate zones with the zones requested
+    if let Some(zones) = cli_args.get_one::<String>("hr-zones") {
+        settings.set_hr_zones(zones.parse::<utilities::HrZoneLimits>()?);
+    }
+
+    // Reuse the summaries from earlier runs if requested
+    if let Some(cache) = cli_args.get_one::<String>("cache") {
+        utilities::open_summary_cache(cache)?;
+    }
+
+    // Read the tags of the activities, adding the tags given to each activity processed
+    let tags: Vec<String> = cli_args
+        .get_many::<String>("tag")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    if let Some(tags_file) = cli_args.get_one::<String>("tags-file") {
+        utilities::open_tag_store(tags_file, &tags)?;
+    }
+
+    // Show the distances and speeds in another unit system if requested
+    settings.set_unit_system(
+        cli_args
+            .get_one::<String>("units")
+            .map(|units| units.parse::<utilities::UnitSystem>())
+            .transpose()?,
+    );
+
+    // Use the same text for all the values that aren't known if requested
+    settings.set_placeholder(
+        cli_args
+            .get_one::<String>("placeholder")
+            .map(String::as_str),
+    );
+
+    // Use the settings for the files processed
+    let _settings = settings.apply();
+
+    // Print the columns of the CSV files for downstream tooling if requested
+    if cli_args.get_flag("csv-schema") {
+        utilities::write_csv_schema(
+            utilities::CsvFile::of_format(utilities::ActivityFormat::Gpx),
+            std::io::stdout().lock(),
+        )?;
+        return Ok(0);
+    }
+
     ///////////////////////////////////
     // Working section
 
+    // How to process each file
+    let options = utilities::ProcessingOptions {
+        export_detail: cli_args.value_source("detail-off") != Some(ValueSource::CommandLine),
+        bundle: cli_args.get_flag("bundle"),
+        parse_stats: cli_args.get_flag("stats"),
+        timeout: cli_args
+            .get_one::<u64>("timeout")
+            .map(|secs| std::time::Duration::from_secs(*secs)),
+        min_activity: utilities::min_activity_from_args(&cli_args)?,
+        splits: cli_args
+            .get_one::<String>("splits")
+            .map(|unit| unit.parse::<utilities::SplitUnit>())
+            .transpose()?,
+        mean_max: cli_args.get_flag("mean-max"),
+        hr_scatter: cli_args.get_flag("hr-scatter"),
+        geojson: cli_args.get_flag("geojson"),
+        profile_svg: cli_args.get_flag("export-profile-svg"),
+        format: cli_args
+            .get_one::<String>("format")
+            .map(|format| format.parse::<utilities::ExportFormat>())
+            .transpose()?
+            .unwrap_or_default(),
+        ..utilities::ProcessingOptions::default()
+    };
+
+    // Split the summary into several files if requested
+    let split = cli_args
+        .get_one::<String>("split-summary-by")
+        .map(|s| s.parse::<utilities::SummarySplit>())
+        .transpose()?;
+
     // Create an empty placeholder for all the activities
     let mut activities = utilities::GPXActivities::new();
 
+    // What was done for each file, for the manifest
+    let mut results = Vec::new();
+
+    // Carry on with the rest of the files when one can't be processed, unless asked to stop
+    let keep_going = cli_args.get_one::<bool>("keep-going") != Some(&false);
+
     // Do the parsing
     for filename in filenames {
         log::info!("Processing file: {filename}");
 
-        // Extract the activity from the file
-        let activity = utilities::GPXActivity::from_file(filename)?;
-
-        // Export the data if requested
-        if cli_args.value_source("detail-off") != Some(ValueSource::CommandLine) {
-            activity.export()?; // metadata, tracks, waypoints
+        // Extract the activities from the file and export the data if requested, skipping it if it takes too long
+        let processed = if cli_args.get_flag("split-tracks") {
+            utilities::process_with_timeout(filename, &options, utilities::process_gpx_file_tracks)
+                .map(|(activities, result)| (activities.unwrap_or_default(), result))
+        } else {
+            utilities::process_with_timeout(filename, &options, utilities::process_gpx_file)
+                .map(|(activity, result)| (activity.into_iter().collect(), result))
+        };
+
+        // Or if it can't be processed
+        let (file_activities, result): (Vec<_>, _) = match processed {
+            Ok(processed) => processed,
+            Err(err) if keep_going => (
+                Vec::new(),
+                utilities::ProcessingResult::failed(filename, &err),
+            ),
+            Err(err) => return Err(err.into()),
+        };
+        log::debug!("main::run() -- {result:?}");
+        if cli_args.get_flag("stats") {
+            result.print_stats();
+        } else if cli_args.get_flag("show-warnings") {
+            result.print_warnings();
         }
+        results.push(result);
 
-        // Add the current activity to the list of activities and destroy the activity
-        activities.activities_list.push(activity);
+        // Add the activities to the list of activities
+        activities.activities_list.extend(file_activities);
     }
 
     // Export the summary list of activities
-    if cli_args.value_source("summary-file") == Some(ValueSource::CommandLine) {
+    if let Some(split) = split {
+        for summary_file in activities.export_csv_split(sessionfile, split)? {
+            log::info!("Summary information written to: {summary_file}");
+        }
+    } else if cli_args.value_source("summary-file") == Some(ValueSource::CommandLine) {
         log::info!("Summary information written to: {sessionfile}");
         activities.export_csv(sessionfile)?;
     }
 
-    // Everything is a-okay in the end
-    Ok(())
+    // Flag the activities that overlap in time, since adding up both would count the distance twice
+    for overlap in utilities::flag_overlaps(&mut results, &activities.time_windows()) {
+        if cli_args.get_flag("show-warnings") {
+            println!("Warning: {overlap}");
+        }
+    }
+
+    // What was written, to check nothing was dropped
+    utilities::log_written(&results);
+
+    if let Some(manifest) = cli_args.get_one::<String>("manifest") {
+        utilities::export_manifest(&results, manifest)?;
+        log::info!("Manifest written to: {manifest}");
+    }
+
+    utilities::save_summary_cache()?;
+    utilities::save_tag_store()?;
+
+    // Everything is a-okay in the end, unless some of the files couldn't be processed
+    Ok(utilities::report_failures(&results))
 }
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// The actual executable function that gets called when the program in invoked.
 fn main() {
     std::process::exit(match run() {
-        Ok(()) => 0, // everying is hunky dory
+        Ok(code) => code, // everying is hunky dory, or some of the files couldn't be processed
         Err(err) => {
             log::error!("{}", err.to_string().replace('\"', ""));
             1 // exit with a non-zero return code, indicating a problem
@@ -1,21 +1,26 @@
 use env_logger::Target;
 use std::error::Error;
-use std::fs::File;
-use std::io::BufReader;
 
-use clap::parser::ValueSource;
-use utilities::{TCXActivitiesList, TCXActivity, TCXTrackpointList};
+use utilities::{ProcessingOptions, ProcessingResult, SummarySplit, TCXActivitiesList};
 
 mod cli;
 
 /// This is where the actual processing takes place.
-fn run() -> Result<(), Box<dyn Error>> {
+/// Returns the exit code, which is `EXIT_PARTIAL_FAILURE` if any of the files couldn't be processed.
+fn run() -> Result<i32, Box<dyn Error>> {
     // Set up the command line. Ref https://docs.rs/clap for details.
-    let cli_args = cli::build().get_matches();
+    let cli_args = utilities::with_config(cli::build()).get_matches();
 
     // Initialize logging
     let mut logbuilder = utilities::build_log(&cli_args);
-    logbuilder.target(Target::Stdout).init();
+    // Keep stdout for the schema when it's printed
+    logbuilder
+        .target(if cli_args.get_flag("csv-schema") {
+            Target::Stderr
+        } else {
+            Target::Stdout
+        })
+        .init();
 
     for argument in cli_args
         .get_many::<String>("read")
@@ -32,80 +37,218 @@ fn run() -> Result<(), Box<dyn Error>> {
         .as_str();
     log::trace!("main::run() -- session output file: {summaryfile}");
 
+    // Work out which files to write. Without any of the detail flags, all the detail files are written.
+    let summary_only = cli_args.get_flag("summary-only");
+    let write_json = cli_args.get_flag("json");
+    let selected = write_json || cli_args.get_flag("csv") || cli_args.get_flag("trackpoints");
+    let tcx_detail = if selected {
+        utilities::TcxDetailFiles {
+            json: write_json,
+            laps: cli_args.get_flag("csv"),
+            trackpoints: cli_args.get_flag("trackpoints"),
+        }
+    } else {
+        utilities::TcxDetailFiles::default()
+    };
+
     // Let the user know if we're writing
-    if cli_args.value_source("detail-off") == Some(ValueSource::CommandLine) {
+    if summary_only {
         log::debug!("Writing summary file {} only.", &summaryfile);
     } else {
-        log::debug!("Writing summary and detail files.");
+        log::debug!("Writing summary and detail files: {tcx_detail:?}");
+    }
+
+    let mut settings = utilities::Settings::default();
+
+    // Round the numbers in the exports if requested
+    settings.set_float_precision_from_args(&cli_args)?;
+    settings.set_output_naming_from_args(&cli_args)?;
+    settings.set_csv_format_from_args(&cli_args)?;
+    settings.set_sync_outputs(cli_args.get_flag("fsync"));
+
+    // Keep the CSV columns of an earlier version if requested
+    if let Some(version) = cli_args.get_one::<String>("columns-version") {
+        settings.set_columns_version(version.parse::<utilities::ColumnsVersion>()?);
+    }
+
+    // Write the summary JSON in the layout requested
+    if let Some(schema) = cli_args.get_one::<String>("schema") {
+        settings.set_summary_schema(schema.parse::<utilities::SummarySchema>()?);
+    }
+
+    // Work out the time in the heart rate and pace zones with the zones requested
+    if let Some(zones) = cli_args.get_one::<String>("hr-zones") {
+        settings.set_hr_zones(zones.parse::<utilities::HrZoneLimits>()?);
+    }
+    if let Some(zones) = cli_args.get_one::<String>("pace-zones") {
+        settings.set_pace_zones(zones.parse::<utilities::PaceZoneLimits>()?);
+    }
+
+    // Reuse the summaries from earlier runs if requested
+    if let Some(cache) = cli_args.get_one::<String>("cache") {
+        utilities::open_summary_cache(cache)?;
+    }
+
+    // Read the tags of the activities, adding the tags given to each activity processed
+    let tags: Vec<String> = cli_args
+        .get_many::<String>("tag")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    if let Some(tags_file) = cli_args.get_one::<String>("tags-file") {
+        utilities::open_tag_store(tags_file, &tags)?;
+    }
+
+    // Hide the device serial numbers in the exports if requested
+    settings.set_hash_serials(cli_args.get_flag("hash-serials"));
+
+    // Show the distances and speeds in another unit system if requested
+    settings.set_unit_system(
+        cli_args
+            .get_one::<String>("units")
+            .map(|units| units.parse::<utilities::UnitSystem>())
+            .transpose()?,
+    );
+
+    // Use the same text for all the values that aren't known if requested
+    settings.set_placeholder(
+        cli_args
+            .get_one::<String>("placeholder")
+            .map(String::as_str),
+    );
+
+    // Use the settings for the files processed
+    let _settings = settings.apply();
+
+    // Print the columns of the CSV files for downstream tooling if requested
+    if cli_args.get_flag("csv-schema") {
+        utilities::write_csv_schema(
+            utilities::CsvFile::of_format(utilities::ActivityFormat::Tcx),
+            std::io::stdout().lock(),
+        )?;
+        return Ok(0);
     }
 
     /////////////////////////////////////////////////////////////////////////////////////////////////////////
     // Working section
     // Do the parsing
 
+    // How to process each file
+    let options = ProcessingOptions {
+        export_detail: !summary_only,
+        bundle: cli_args.get_flag("bundle"),
+        parse_stats: cli_args.get_flag("stats"),
+        timeout: cli_args
+            .get_one::<u64>("timeout")
+            .map(|secs| std::time::Duration::from_secs(*secs)),
+        min_activity: utilities::min_activity_from_args(&cli_args)?,
+        splits: cli_args
+            .get_one::<String>("splits")
+            .map(|unit| unit.parse::<utilities::SplitUnit>())
+            .transpose()?,
+        mean_max: cli_args.get_flag("mean-max"),
+        hr_scatter: cli_args.get_flag("hr-scatter"),
+        profile_svg: cli_args.get_flag("export-profile-svg"),
+        format: cli_args
+            .get_one::<String>("format")
+            .map(|format| format.parse::<utilities::ExportFormat>())
+            .transpose()?
+            .unwrap_or_default(),
+        tcx_detail,
+        ..ProcessingOptions::default()
+    };
+
+    // Split the summary into several files if requested
+    let split = cli_args
+        .get_one::<String>("split-summary-by")
+        .map(|s| s.parse::<SummarySplit>())
+        .transpose()?;
+
     let mut act_list = TCXActivitiesList::default();
 
-    for filename in cli_args
-        .get_many::<String>("read")
-        .unwrap_or_default()
-        .map(std::string::String::as_str)
-    {
+    // What was done for each file, for the manifest
+    let mut results = Vec::new();
+
+    // Zip archives are read as the activity files inside them
+    let filenames = utilities::expand_archives(
+        cli_args
+            .get_many::<String>("read")
+            .unwrap_or_default()
+            .map(std::string::String::as_str),
+    );
+
+    // Carry on with the rest of the files when one can't be processed, unless asked to stop
+    let keep_going = cli_args.get_one::<bool>("keep-going") != Some(&false);
+
+    for filename in filenames.iter().map(std::string::String::as_str) {
         log::info!("Processing file: {filename}");
 
-        let mut tcdb = tcx::read(&mut BufReader::new(File::open(filename)?))?;
-        tcdb.calc_heartrates();
-
-        // If -d then export the activity to JSON
-        if cli_args.value_source("debug") == Some(ValueSource::CommandLine)
-            || cli_args.value_source("debug") == Some(ValueSource::EnvVariable)
-        {
-            let outfile = utilities::set_extension(filename, "json")
-                .as_str()
-                .to_owned();
-            log::trace!("main::run() -- Exporting {filename} to {outfile} for debugging purposes.");
-            tcdb.export_json(&outfile)?;
+        // Parse the file and export the details if requested, skipping it if it takes too long or can't be processed
+        let (activity, result) = match utilities::process_with_timeout(
+            filename,
+            &options,
+            utilities::process_tcx_file,
+        ) {
+            Ok(processed) => processed,
+            Err(err) if keep_going => (None, ProcessingResult::failed(filename, &err)),
+            Err(err) => return Err(err.into()),
+        };
+        log::debug!("main::run() -- {result:?}");
+        if cli_args.get_flag("stats") {
+            result.print_stats();
+        } else if cli_args.get_flag("show-warnings") {
+            result.print_warnings();
         }
+        results.push(result);
 
-        log::trace!("main::run() -- tcxfile = {tcdb:?}");
-        if let Some(activities) = tcdb.activities {
-            let mut curr_activities = TCXActivity::from_activities(&activities);
-            let file_name = filename.to_string();
-            curr_activities.filename = Some(file_name.clone());
+        if let Some(curr_activities) = activity.flatten() {
+            act_list.activities.push(curr_activities);
+        }
+    }
 
-            log::trace!("main::run() -- activities summary: {curr_activities:?}");
-            if cli_args.value_source("detail-off") != Some(ValueSource::CommandLine) {
-                // Export the activity summary to JSON
-                log::debug!("main::run() -- Writing activity summary for {file_name}");
-                curr_activities.export_json()?;
+    // Export the summary in JSON format as well if asked to
+    if write_json {
+        let json_file = utilities::set_extension(summaryfile, "json");
+        log::info!("Exporting summary JSON file: {json_file}");
+        act_list.export_json(&json_file)?;
+    }
 
-                // Export the Trackpoints to CSV
-                log::debug!("Parsing and exporting Trackpoint list.");
-                let tp_list = TCXTrackpointList::from_activities(&activities);
-                tp_list.export_csv(&utilities::set_extension(filename, "trackpoints.csv"))?;
-            }
+    if let Some(split) = split {
+        for summary_file in act_list.export_csv_split(summaryfile, split)? {
+            log::info!("Exporting summary CSV file: {summary_file}");
+        }
+    } else {
+        log::info!("Exporting summary CSV file: {summaryfile}");
+        act_list.export_csv(summaryfile)?;
+    }
 
-            act_list.activities.push(curr_activities);
+    // Flag the activities that overlap in time, since adding up both would count the distance twice
+    for overlap in utilities::flag_overlaps(&mut results, &act_list.time_windows()) {
+        if cli_args.get_flag("show-warnings") {
+            println!("Warning: {overlap}");
         }
     }
 
-    // If we're tracing, export the summary in JSON format
-    if cli_args.get_count("debug") > 1 {
-        log::trace!("main::run() -- Exporting summary JSON file.");
-        act_list.export_json(&utilities::set_extension(summaryfile, "json"))?;
+    // What was written, to check nothing was dropped
+    utilities::log_written(&results);
+
+    if let Some(manifest) = cli_args.get_one::<String>("manifest") {
+        utilities::export_manifest(&results, manifest)?;
+        log::info!("Manifest written to: {manifest}");
     }
 
-    log::info!("Exporting summary CSV file: {summaryfile}");
-    act_list.export_csv(summaryfile)?;
+    utilities::save_summary_cache()?;
+    utilities::save_tag_store()?;
 
-    // Everything is a-okay in the end
-    Ok(())
+    // Everything is a-okay in the end, unless some of the files couldn't be processed
+    Ok(utilities::report_failures(&results))
 }
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// The actual executable function that gets called when the program in invoked.
 fn main() {
     std::process::exit(match run() {
-        Ok(()) => 0, // everying is hunky dory
+        Ok(code) => code, // everying is hunky dory, or some of the files couldn't be processed
         Err(err) => {
             log::error!("{}", err.to_string().replace('\"', ""));
             1 // exit with a non-zero return code, indicating a problem
@@ -0,0 +1,140 @@
+//! Contains a single function to build the CLI
+use clap::{Arg, ArgAction, Command};
+
+/// Builds the CLI so the main file doesn't get cluttered.
+pub fn build() -> Command {
+    Command::new(clap::crate_name!())
+        .about(clap::crate_description!())
+        .version(clap::crate_version!())
+        // .author(clap::crate_authors!("\n"))
+        .long_about("This program will convert one or more .tcx files to .gpx files with the same name. Each activity becomes a track and each lap a track segment, unless --lap-mapping tracks is given. The heart rate and cadence are kept as Garmin TrackPointExtensions. Trackpoints without a position are left out, since GPX needs one. The tracks are colored by sport for viewers such as GPXSee.")
+        .arg(
+            Arg::new("read")
+                .value_name("FILE(S)")
+                .help("One or more .tcx file(s) to convert. Wildcards and multiple_occurrences files (e.g. 2019*.tcx 2020*.tcx) are supported.")
+                .num_args(1..)
+                .required(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Hidden debug parameter
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Output debug information as we go. Supply it twice for trace-level logs.")
+                .env("FIT_DEBUG")
+                .num_args(0)
+                .action(ArgAction::Count)
+                .hide(true),
+        )
+        .arg( // Don't print any information
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Don't produce any output except errors while working.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Print summary information
+            Arg::new("print-summary")
+                .short('s')
+                .long("print-summary")
+                .help("Print a summary of the number of files converted and skipped.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Overwrite existing files
+            Arg::new("force")
+                .short('f')
+                .long("force")
+                .help("Overwrite .gpx files that already exist. Without this, the files that would be overwritten are skipped.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Track colors
+            Arg::new("track-color")
+                .short('c')
+                .long("track-color")
+                .value_name("SPORT=RRGGBB")
+                .help("The color of the tracks of a sport, e.g. running=ff0000. Use SPORT=none to leave the color out. Can be given several times. Running, biking, walking, hiking, swimming and rowing have colors by default.")
+                .num_args(1)
+                .action(ArgAction::Append)
+        )
+        .arg( // Laps as segments or tracks
+            Arg::new("lap-mapping")
+                .short('l')
+                .long("lap-mapping")
+                .value_name("segments|tracks")
+                .help("Write each lap as a track segment of the activity's track, or as a track of its own. Some applications only tell tracks apart.")
+                .num_args(1)
+                .default_value("segments")
+                .value_parser(utilities::LapMapping::VALUES)
+                .action(ArgAction::Set)
+        )
+}
+
+#[cfg(test)]
+/// Tests for the CLI module
+mod tests {
+    use super::*;
+
+    /// Test the CLI build function
+    #[test]
+    fn test_cli_build() {
+        // Long form
+        let args = build().get_matches_from(vec![
+            "--read",
+            "test.tcx",
+            "--debug",
+            "--debug",
+            "--quiet",
+            "--print-summary",
+            "--force",
+            "--track-color",
+            "running=ff0000",
+            "--track-color",
+            "biking=none",
+            "--lap-mapping",
+            "tracks",
+        ]);
+
+        assert!(args.contains_id("read"));
+        assert_eq!(args.get_count("debug"), 2);
+        assert!(args.get_flag("quiet"));
+        assert!(args.get_flag("print-summary"));
+        assert!(args.get_flag("force"));
+        assert_eq!(
+            args.get_many::<String>("track-color")
+                .unwrap_or_default()
+                .collect::<Vec<_>>(),
+            vec!["running=ff0000", "biking=none"]
+        );
+        assert_eq!(
+            args.get_one::<String>("lap-mapping").map(String::as_str),
+            Some("tracks")
+        );
+
+        // Short form
+        let args2 = build().get_matches_from(vec![
+            "--read",
+            "test.tcx",
+            "-d",
+            "-q",
+            "-s",
+            "-f",
+            "-c",
+            "running=ff0000",
+            "-l",
+            "segments",
+        ]);
+
+        assert_eq!(args2.get_count("debug"), 1);
+        assert!(args2.get_flag("quiet"));
+        assert!(args2.get_flag("print-summary"));
+        assert!(args2.get_flag("force"));
+        assert!(args2.contains_id("track-color"));
+        assert_eq!(
+            args2.get_one::<String>("lap-mapping").map(String::as_str),
+            Some("segments")
+        );
+    }
+}
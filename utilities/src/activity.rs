@@ -0,0 +1,509 @@
+//! Defines the `Activity` enum, which holds an activity read from a FIT, GPX or TCX file, so callers can work with
+//! any of the formats without dispatching on the file extension themselves.
+
+use serde_json::Value;
+use std::error::Error;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::io::{Read, Write};
+
+use crate::analysis::compare::SessionTotals;
+use crate::analysis::derived::mean;
+use crate::analysis::hr_scatter::HrScatter;
+use crate::analysis::laps::LapSummary;
+use crate::analysis::mean_max::MeanMax;
+use crate::analysis::race::{race_report, RaceDistance, RaceMark};
+use crate::analysis::series::{speeds, RecordSeries, SeriesPoint};
+use crate::analysis::splits::{splits, Split, SplitPoint, SplitUnit};
+use crate::fit::activity::split_points;
+#[cfg(feature = "fs")]
+use crate::input::open_input;
+use crate::{get_extension, FITActivity, GPXActivity, TCXActivity, TCXTrackpointList, TrackPoint};
+
+/// The kinds of activity files that can be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum ActivityFormat {
+    /// A Garmin FIT file.
+    Fit,
+    /// A GPS Exchange Format file.
+    Gpx,
+    /// A Training Center XML file.
+    Tcx,
+}
+
+impl ActivityFormat {
+    /// Works out the format from the file extension.
+    ///
+    /// # Arguments
+    ///
+    /// `filename: &str` -- The name of the file, e.g. `running.gpx`.
+    ///
+    /// # Returns
+    ///
+    /// `Option<Self>` -- The format, or `None` if the extension isn't `fit`, `gpx` or `tcx`.
+    #[must_use]
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        match get_extension(filename).as_ref() {
+            "fit" => Some(Self::Fit),
+            "gpx" => Some(Self::Gpx),
+            "tcx" => Some(Self::Tcx),
+            _ => None,
+        }
+    }
+
+    /// The name used for the format, which is also the file extension.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Fit => "fit",
+            Self::Gpx => "gpx",
+            Self::Tcx => "tcx",
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// An activity read from a FIT, GPX or TCX file.
+///
+/// | Format | Summary        | Laps            | Records                |
+/// |:-------|:---------------|:----------------|:-----------------------|
+/// | FIT    | `FITSession`   | `FITLap`s       | `FITRecord`s           |
+/// | GPX    | `GPXMetadata`  | `GPXTrack`s     | The track waypoints    |
+/// | TCX    | `TCXActivity`  | `TCXLap`s       | `TCXTrackpoint`s       |
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)] // Activities are read one at a time, so the size doesn't matter
+pub enum Activity {
+    /// An activity read from a FIT file.
+    Fit(FITActivity),
+    /// An activity read from a GPX file.
+    Gpx(GPXActivity),
+    /// An activity read from a TCX file, along with its trackpoints.
+    Tcx(TCXActivity, TCXTrackpointList),
+}
+
+impl Activity {
+    /// Reads the activity from the file. The format is worked out from the file extension.
+    ///
+    /// # Arguments
+    ///
+    /// `filename: &str` -- The FIT, GPX or TCX file to read.
+    ///
+    /// # Errors
+    ///
+    /// The file may not be a FIT, GPX or TCX file. Reading or parsing the file may fail.
+    #[cfg(feature = "fs")]
+    pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
+        let format = ActivityFormat::from_filename(filename)
+            .ok_or_else(|| format!("{filename}: Unknown file type."))?;
+        Self::from_reader(BufReader::new(open_input(filename)?), filename, format)
+    }
+
+    /// Parses the activity from a reader, e.g. the contents of a file dropped into a browser. Use this where there is
+    /// no file system.
+    ///
+    /// # Arguments
+    ///
+    /// - `reader: R` -- Where the data is read from.
+    /// - `filename: &str` -- The name the activity is known by. It's used to name the exports.
+    /// - `format: ActivityFormat` -- The format of the data.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the data may fail.
+    pub fn from_reader<R: Read>(
+        mut reader: R,
+        filename: &str,
+        format: ActivityFormat,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(match format {
+            ActivityFormat::Fit => Self::Fit(FITActivity::from_reader(&mut reader, filename)?),
+            ActivityFormat::Gpx => Self::Gpx(GPXActivity::from_reader(reader, filename)?),
+            ActivityFormat::Tcx => {
+                // The summary and the trackpoints are parsed separately, so the data is read up front
+                let mut buffer = Vec::new();
+                reader.read_to_end(&mut buffer)?;
+                let activity = TCXActivity::from_reader(buffer.as_slice(), filename)?;
+                let mut trackpoints = TCXTrackpointList::from_reader(buffer.as_slice())?;
+                trackpoints.set_activity_uuid(activity.uuid);
+                Self::Tcx(activity, trackpoints)
+            }
+        })
+    }
+
+    /// The format the activity was read from.
+    #[must_use]
+    pub const fn format(&self) -> ActivityFormat {
+        match self {
+            Self::Fit(_) => ActivityFormat::Fit,
+            Self::Gpx(_) => ActivityFormat::Gpx,
+            Self::Tcx(..) => ActivityFormat::Tcx,
+        }
+    }
+
+    /// The warnings found while parsing the file.
+    #[must_use]
+    pub fn warnings(&self) -> &[String] {
+        match self {
+            Self::Fit(act) => &act.warnings,
+            Self::Gpx(act) => &act.warnings,
+            Self::Tcx(act, _) => &act.warnings,
+        }
+    }
+
+    /// The session summary as JSON -- the `FITSession`, `GPXMetadata` or `TCXActivity`.
+    ///
+    /// # Errors
+    ///
+    /// Serializing to JSON may fail.
+    pub fn summary_json(&self) -> Result<Value, Box<dyn Error>> {
+        Ok(match self {
+            Self::Fit(act) => serde_json::to_value(&act.session)?,
+            Self::Gpx(act) => serde_json::to_value(&act.metadata)?,
+            Self::Tcx(act, _) => serde_json::to_value(act)?,
+        })
+    }
+
+    /// The laps as a JSON array -- the FIT laps, the GPX tracks without their waypoints or the TCX laps.
+    ///
+    /// # Errors
+    ///
+    /// Serializing to JSON may fail.
+    pub fn laps_json(&self) -> Result<Value, Box<dyn Error>> {
+        Ok(match self {
+            Self::Fit(act) => serde_json::to_value(&act.laps)?,
+            Self::Gpx(act) => serde_json::to_value(&act.tracks)?,
+            Self::Tcx(act, _) => serde_json::to_value(&act.laps)?,
+        })
+    }
+
+    /// The records as a JSON array -- the FIT records, the GPX track waypoints or the TCX trackpoints.
+    ///
+    /// # Errors
+    ///
+    /// Serializing to JSON may fail.
+    pub fn records_json(&self) -> Result<Value, Box<dyn Error>> {
+        Ok(match self {
+            Self::Fit(act) => serde_json::to_value(&act.records)?,
+            Self::Gpx(act) => serde_json::to_value(
+                act.tracks
+                    .iter()
+                    .flat_map(|track| &track.waypoints)
+                    .collect::<Vec<_>>(),
+            )?,
+            Self::Tcx(_, trackpoints) => serde_json::to_value(&trackpoints.trackpoints)?,
+        })
+    }
+
+    /// The summary, laps and records together as a single JSON document -- the same document the `bundle.json`
+    /// export writes.
+    ///
+    /// # Errors
+    ///
+    /// Serializing to JSON may fail.
+    pub fn bundle_json(&self) -> Result<String, Box<dyn Error>> {
+        match self {
+            Self::Fit(act) => act.bundle_json(),
+            Self::Gpx(act) => act.bundle_json(),
+            Self::Tcx(act, trackpoints) => act.bundle_json(trackpoints),
+        }
+    }
+
+    /// Writes the records as CSV to any writer -- the same CSV the `records.csv`, `waypoints.csv` or
+    /// `trackpoints.csv` export writes.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// GPX files without tracks can't be written. Serializing or writing may fail.
+    pub fn write_records_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Fit(act) => act.write_records_csv(writer),
+            Self::Gpx(act) => act.write_waypoints_csv(writer),
+            Self::Tcx(_, trackpoints) => trackpoints.write_csv(writer),
+        }
+    }
+
+    /// Splits the activity into kilometers or miles from the records, independent of the laps recorded by the device.
+    ///
+    /// # Arguments
+    ///
+    /// `unit: SplitUnit` -- Whether to split by kilometer or mile.
+    #[must_use]
+    pub fn splits(&self, unit: SplitUnit) -> Vec<Split> {
+        splits(&self.split_points(), unit)
+    }
+
+    /// Works out the mean-maximal power and heart rate curve, i.e. the best averages held over every duration from
+    /// one second up to the length of the activity. TCX trackpoints don't hold the power, so TCX files only get the
+    /// heart rate.
+    #[must_use]
+    pub fn mean_max(&self) -> Vec<MeanMax> {
+        match self {
+            Self::Fit(act) => act.mean_max(),
+            Self::Gpx(act) => act.mean_max(),
+            Self::Tcx(_, trackpoints) => trackpoints.mean_max(),
+        }
+    }
+
+    /// Works out the heart rate against the speed and power over each half minute, for seeing how the heart rate
+    /// drifts at the same effort. TCX trackpoints don't hold the power, so TCX files only get the speed.
+    #[must_use]
+    pub fn hr_scatter(&self) -> Vec<HrScatter> {
+        match self {
+            Self::Fit(act) => act.hr_scatter(),
+            Self::Gpx(act) => act.hr_scatter(),
+            Self::Tcx(_, trackpoints) => trackpoints.hr_scatter(),
+        }
+    }
+
+    /// The numbers printed for each lap recorded by the device. GPX files have no laps.
+    #[must_use]
+    pub fn lap_summaries(&self) -> Vec<LapSummary> {
+        match self {
+            Self::Fit(act) => act
+                .laps
+                .iter()
+                .enumerate()
+                .map(|(num, lap)| LapSummary {
+                    lap_num: lap
+                        .lap_num
+                        .and_then(|lap_num| usize::try_from(lap_num).ok())
+                        .unwrap_or(num + 1),
+                    duration: lap.duration,
+                    distance_m: lap.distance.map(|distance| distance.value),
+                    heartrate_avg_bpm: lap.heartrate_avg.map(f64::from),
+                    power_avg_w: lap.power_avg.map(f64::from),
+                })
+                .collect(),
+            Self::Gpx(_) => Vec::new(),
+            Self::Tcx(act, _) => act
+                .laps
+                .iter()
+                .map(|lap| LapSummary {
+                    lap_num: lap.lap_num,
+                    duration: lap.duration,
+                    distance_m: lap.distance_meters,
+                    heartrate_avg_bpm: lap.average_heart_rate,
+                    power_avg_w: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// The totals compared between activities. GPX files have no totals for the heart rate and power, so they are
+    /// averaged over the track waypoints.
+    #[must_use]
+    pub fn totals(&self) -> SessionTotals {
+        match self {
+            Self::Fit(act) => SessionTotals {
+                duration: act.session.duration,
+                distance_m: act.session.distance.map(|distance| distance.value),
+                heartrate_avg_bpm: act.session.heartrate_avg.map(f64::from),
+                power_avg_w: act.session.power_avg.map(f64::from),
+                ascent_m: act.session.ascent.map(|ascent| f64::from(ascent.value)),
+            },
+            Self::Gpx(act) => {
+                let average = |series| {
+                    let values: Vec<f64> = self
+                        .series(series)
+                        .iter()
+                        .map(|point| point.value)
+                        .collect();
+                    mean(&values)
+                };
+                SessionTotals {
+                    duration: act.metadata.duration,
+                    distance_m: act.metadata.distance_m,
+                    heartrate_avg_bpm: average(RecordSeries::HeartRate),
+                    power_avg_w: average(RecordSeries::Power),
+                    ascent_m: act.metadata.ascent_m,
+                }
+            }
+            Self::Tcx(act, _) => SessionTotals {
+                duration: act.duration,
+                distance_m: act.distance_meters,
+                heartrate_avg_bpm: act.average_heart_rate,
+                power_avg_w: None,
+                ascent_m: act.ascent_meters,
+            },
+        }
+    }
+
+    /// Fills in the gaps between the records of FIT files made with smart recording with a synthetic record for every
+    /// second. GPX and TCX files are left as they are.
+    ///
+    /// # Returns
+    ///
+    /// `usize` -- The number of records added.
+    pub fn interpolate_records(&mut self) -> usize {
+        match self {
+            Self::Fit(act) => act.interpolate_records(),
+            Self::Gpx(_) | Self::Tcx(..) => 0,
+        }
+    }
+
+    /// Finds the time at each of the race distances covered, and predicts the finish time for each of them. See
+    /// `race_report()` for how.
+    ///
+    /// # Arguments
+    ///
+    /// `targets: &[RaceDistance]` -- The race distances to report on.
+    #[must_use]
+    pub fn race_report(&self, targets: &[RaceDistance]) -> Vec<RaceMark> {
+        race_report(&self.split_points(), targets)
+    }
+
+    /// Picks a single series out of the records, with the time of each value. Records without a time or without the
+    /// value are left out. TCX files have no power, and the speed of files without a recorded speed is worked out from
+    /// the distance covered.
+    ///
+    /// # Arguments
+    ///
+    /// `series: RecordSeries` -- The series to pick out.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<SeriesPoint>` -- The values in the units recorded in the file, in order.
+    #[must_use]
+    pub fn series(&self, series: RecordSeries) -> Vec<SeriesPoint> {
+        let recorded: Vec<SeriesPoint> = self
+            .track_points()
+            .filter_map(|point| {
+                let value = match series {
+                    RecordSeries::Elevation => point.elevation,
+                    RecordSeries::HeartRate => point.heart_rate,
+                    RecordSeries::Power => point.power,
+                    RecordSeries::Speed => point.speed,
+                };
+                Some(SeriesPoint {
+                    time: point.time?,
+                    value: value?,
+                })
+            })
+            .collect();
+
+        if recorded.is_empty() && series == RecordSeries::Speed {
+            speeds(&self.split_points())
+        } else {
+            recorded
+        }
+    }
+
+    /// The points of the activity in the view shared by all the formats: the FIT records, the GPX track waypoints or
+    /// the TCX trackpoints, in order.
+    ///
+    /// # Returns
+    ///
+    /// `Box<dyn Iterator<Item = TrackPoint> + '_>` -- The points, with the values the format doesn't have left empty.
+    #[must_use]
+    pub fn track_points(&self) -> Box<dyn Iterator<Item = TrackPoint> + '_> {
+        match self {
+            Self::Fit(act) => Box::new(act.track_points()),
+            Self::Gpx(act) => Box::new(act.track_points()),
+            Self::Tcx(_, trackpoints) => Box::new(trackpoints.track_points()),
+        }
+    }
+
+    /// The points of the activity with the time and the distance covered, for the split and race analysis. FIT
+    /// records and TCX trackpoints without a distance are left out. GPX files don't record the distance, so it is
+    /// worked out from the positions of the track waypoints.
+    #[must_use]
+    pub fn split_points(&self) -> Vec<SplitPoint> {
+        match self {
+            Self::Fit(act) => split_points(&act.records),
+            Self::Gpx(act) => act.split_points(),
+            Self::Tcx(_, trackpoints) => trackpoints.split_points(),
+        }
+    }
+
+    /// Prints the activity to stdout.
+    ///
+    /// # Arguments
+    ///
+    /// `detailed: bool` -- Print more of the details, where the format has them.
+    pub fn print(&self, detailed: bool) {
+        match self {
+            Self::Fit(act) => act.print(detailed),
+            Self::Gpx(act) => act.print(detailed),
+            Self::Tcx(act, _) => act.print(detailed),
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests for the activity module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test working out the format from the file name
+    fn test_format() {
+        assert_eq!(
+            ActivityFormat::from_filename("data/running.GPX"),
+            Some(ActivityFormat::Gpx)
+        );
+        assert_eq!(
+            ActivityFormat::from_filename("rowing.fit").map(ActivityFormat::name),
+            Some("fit")
+        );
+        assert!(ActivityFormat::from_filename("readme.md").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test reading the same kind of information from each of the formats
+    fn test_from_file() {
+        for filename in [
+            "../data/rowing.fit",
+            "../data/running.gpx",
+            "../data/running.tcx",
+        ] {
+            let activity = Activity::from_file(filename).unwrap();
+            assert_eq!(
+                Some(activity.format()),
+                ActivityFormat::from_filename(filename)
+            );
+            assert!(activity.summary_json().unwrap().is_object());
+            assert!(activity.laps_json().unwrap().is_array());
+            assert!(activity
+                .records_json()
+                .unwrap()
+                .as_array()
+                .is_some_and(|records| !records.is_empty()));
+
+            // The indoor rowing has no distance to split
+            assert_eq!(
+                activity.splits(SplitUnit::Kilometer).is_empty(),
+                activity.format() == ActivityFormat::Fit
+            );
+
+            assert!(!activity.mean_max().is_empty());
+
+            // Every file has a heart rate to chart
+            assert!(!activity.series(RecordSeries::HeartRate).is_empty());
+
+            // GPX files have no laps, and the laps keep their numbers
+            let laps = activity.lap_summaries();
+            assert_eq!(laps.is_empty(), activity.format() == ActivityFormat::Gpx);
+            assert!(laps.iter().zip(1..).all(|(lap, num)| lap.lap_num == num));
+
+            // The runs are shorter than 5k, but long enough to predict from
+            let race = activity.race_report(&[RaceDistance::FiveK]);
+            assert!(race[0].split.is_none());
+            assert_eq!(
+                race[0].predicted.is_some(),
+                activity.format() != ActivityFormat::Fit
+            );
+
+            let mut csv = Vec::new();
+            activity.write_records_csv(&mut csv).unwrap();
+            assert!(String::from_utf8(csv).unwrap().lines().count() > 1);
+        }
+
+        assert!(Activity::from_file("../data/readme.md").is_err());
+    }
+}
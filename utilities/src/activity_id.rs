@@ -0,0 +1,78 @@
+//! Generates a stable identifier for each activity, so the files exported from it can be joined together.
+
+use chrono::{DateTime, TimeZone};
+use uuid::Uuid;
+
+/// The namespace for the name-based activity UUIDs. Changing this changes every activity UUID.
+const ACTIVITY_NAMESPACE: Uuid = Uuid::from_u128(0xfcd1_c1ae_3182_4751_9b94_6e5f_673a_9faf);
+
+/// Generates a UUID for an activity from its start time, the serial number of the device and the sport.
+///
+/// The same activity always gets the same UUID, so re-running an export gives the same identifiers. The start time is
+/// used in whole seconds, and the sport is not case sensitive, so small differences between file formats don't matter.
+///
+/// # Arguments
+///
+/// - `start_time: Option<&DateTime<Tz>>` -- When the activity started.
+/// - `serial_number: Option<&str>` -- The serial number (or unit ID) of the device that recorded the activity.
+/// - `sport: Option<&str>` -- The sport, e.g. "Running".
+///
+/// # Returns
+///
+/// `Uuid` -- A version 5 (name-based) UUID.
+///
+/// # Example
+///
+/// ```text
+/// let uuid = activity_uuid(session.start_time.as_ref(), session.serial_number.as_deref(), Some("Running"));
+/// ```
+#[must_use]
+pub fn activity_uuid<Tz: TimeZone>(
+    start_time: Option<&DateTime<Tz>>,
+    serial_number: Option<&str>,
+    sport: Option<&str>,
+) -> Uuid {
+    let name = format!(
+        "{}|{}|{}",
+        start_time.map(DateTime::timestamp).unwrap_or_default(),
+        serial_number.unwrap_or_default().trim(),
+        sport.unwrap_or_default().trim().to_lowercase()
+    );
+
+    Uuid::new_v5(&ACTIVITY_NAMESPACE, name.as_bytes())
+}
+
+#[cfg(test)]
+/// Tests for the activity_id module
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, Local, Utc};
+
+    #[test]
+    /// Test that the UUID is stable and depends on all the inputs
+    fn test_activity_uuid() {
+        let start = Utc.with_ymd_and_hms(2021, 9, 7, 13, 42, 12).unwrap();
+        let uuid = activity_uuid(Some(&start), Some("3990000000"), Some("Rowing"));
+
+        // Same instant in a different time zone, sport in a different case
+        let start_pst = start.with_timezone(&FixedOffset::west_opt(7 * 3600).unwrap());
+        assert_eq!(
+            uuid,
+            activity_uuid(Some(&start_pst), Some("3990000000"), Some("rowing"))
+        );
+
+        assert_ne!(
+            uuid,
+            activity_uuid(Some(&start), Some("3990000001"), Some("Rowing"))
+        );
+        assert_ne!(
+            uuid,
+            activity_uuid(Some(&start), Some("3990000000"), Some("Running"))
+        );
+        assert_ne!(
+            uuid,
+            activity_uuid(None::<&DateTime<Local>>, Some("3990000000"), Some("Rowing"))
+        );
+        assert_eq!(uuid.get_version_num(), 5);
+    }
+}
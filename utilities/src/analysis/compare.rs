@@ -0,0 +1,180 @@
+//! Compares the totals of two activities side by side, e.g. two efforts on the same route, with the change from the
+//! first to the second.
+
+use crate::table::Table;
+use crate::units::{converted, unit_system, Measure, UnitSystem};
+use crate::Duration;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The totals compared for each activity. Anything not recorded in the file is `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SessionTotals {
+    /// The elapsed time of the activity.
+    pub duration: Option<Duration>,
+
+    /// The distance covered in meters.
+    pub distance_m: Option<f64>,
+
+    /// The average heart rate in beats per minute.
+    pub heartrate_avg_bpm: Option<f64>,
+
+    /// The average power in watts. TCX files don't record the power.
+    pub power_avg_w: Option<f64>,
+
+    /// The total ascent in meters.
+    pub ascent_m: Option<f64>,
+}
+
+impl SessionTotals {
+    /// The average pace of the activity, i.e. the time taken per kilometer or mile.
+    ///
+    /// # Arguments
+    ///
+    /// `units: UnitSystem` -- Whether the pace is per kilometer or mile.
+    ///
+    /// # Returns
+    ///
+    /// `Option<Duration>` -- The pace, or `None` if the activity has no time or distance.
+    #[must_use]
+    pub fn pace(&self, units: UnitSystem) -> Option<Duration> {
+        let secs = self.duration?.0.as_secs_f64();
+        let distance = self.distance_m?;
+        (secs > 0.0).then(|| units.pace(distance / secs)).flatten()
+    }
+}
+
+/// The change from the first value to the second, in percent of the first.
+///
+/// # Returns
+///
+/// `Option<f64>` -- The change, or `None` if either value is missing or the first one is zero.
+fn change_pct(first: Option<f64>, second: Option<f64>) -> Option<f64> {
+    let (first, second) = (first?, second?);
+    (first != 0.0).then(|| (second - first) / first * 100.0)
+}
+
+/// Formats a time, or a change in time with its sign, as `hh:mm:ss`.
+fn time(secs: f64, signed: bool) -> String {
+    let sign = match (signed, secs < 0.0) {
+        (false, _) => "",
+        (true, true) => "-",
+        (true, false) => "+",
+    };
+    format!("{sign}{}", Duration::from_secs_f64(secs.abs()))
+}
+
+/// Adds a row with the value of each activity, the change and the change in percent. The change is left blank unless
+/// both activities have the value.
+fn add_row(
+    table: &mut Table,
+    name: &str,
+    values: [Option<f64>; 2],
+    format: impl Fn(f64, bool) -> String,
+) {
+    let [first, second] = values;
+    table.add_row(vec![
+        name.to_string(),
+        first.map_or_else(String::new, |value| format(value, false)),
+        second.map_or_else(String::new, |value| format(value, false)),
+        first
+            .zip(second)
+            .map_or_else(String::new, |(first, second)| format(second - first, true)),
+        change_pct(first, second).map_or_else(String::new, |pct| format!("{pct:+.1}%")),
+    ]);
+}
+
+/// Prints the totals of two activities side by side to stdout, with the change from the first to the second. The
+/// distance and ascent are in the unit system of the run, and the pace is per kilometer unless the run is in miles.
+///
+/// # Arguments
+///
+/// - `names: [&str; 2]` -- The names of the activities, e.g. the file names.
+/// - `totals: [SessionTotals; 2]` -- The totals of each activity.
+pub fn print_comparison(names: [&str; 2], totals: [SessionTotals; 2]) {
+    let units = unit_system();
+    let pace_units = units.unwrap_or(UnitSystem::Metric);
+    let distance_decimals = if units.is_some() { 2 } else { 0 };
+    let pick = |value: fn(&SessionTotals) -> Option<f64>| totals.map(|totals| value(&totals));
+
+    let mut table = Table::new(&["", names[0], names[1], "Change", "%"]);
+    add_row(
+        &mut table,
+        &format!("Distance ({})", Measure::Distance.unit(units)),
+        pick(|totals| {
+            totals
+                .distance_m
+                .map(|meters| converted(Measure::Distance, meters))
+        }),
+        |value, signed| {
+            if signed {
+                format!("{value:+.distance_decimals$}")
+            } else {
+                format!("{value:.distance_decimals$}")
+            }
+        },
+    );
+    add_row(
+        &mut table,
+        "Time",
+        pick(|totals| totals.duration.map(|duration| duration.0.as_secs_f64())),
+        time,
+    );
+    add_row(
+        &mut table,
+        &format!("Pace ({})", pace_units.pace_unit()),
+        totals.map(|totals| totals.pace(pace_units).map(|pace| pace.0.as_secs_f64())),
+        time,
+    );
+    for (name, values) in [
+        ("HR (bpm)", pick(|totals| totals.heartrate_avg_bpm)),
+        ("Power (W)", pick(|totals| totals.power_avg_w)),
+        (
+            &*format!("Ascent ({})", Measure::Length.unit(units)),
+            pick(|totals| {
+                totals
+                    .ascent_m
+                    .map(|meters| converted(Measure::Length, meters))
+            }),
+        ),
+    ] {
+        add_row(&mut table, name, values, |value, signed| {
+            if signed {
+                format!("{value:+.0}")
+            } else {
+                format!("{value:.0}")
+            }
+        });
+    }
+
+    println!("\nComparison:\n");
+    print!("{table}");
+}
+
+#[cfg(test)]
+/// Tests for the compare module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the change in percent, and the signed times
+    fn test_change() {
+        assert_eq!(change_pct(Some(200.0), Some(210.0)), Some(5.0));
+        assert_eq!(change_pct(Some(200.0), Some(190.0)), Some(-5.0));
+        assert!(change_pct(Some(0.0), Some(10.0)).is_none());
+        assert!(change_pct(None, Some(10.0)).is_none());
+
+        assert_eq!(time(3_012.0, false), "00:50:12");
+        assert_eq!(time(-42.0, true), "-00:00:42");
+        assert_eq!(time(42.0, true), "+00:00:42");
+
+        let totals = SessionTotals {
+            duration: Some(Duration::from_secs_f64(3_000.0)),
+            distance_m: Some(10_000.0),
+            ..SessionTotals::default()
+        };
+        assert_eq!(
+            totals.pace(UnitSystem::Metric),
+            Some(Duration::from_secs_f64(300.0))
+        );
+    }
+}
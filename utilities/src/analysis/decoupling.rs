@@ -0,0 +1,193 @@
+//! Works out the aerobic decoupling of an activity, i.e. how far the heart rate drifts up against the output over the
+//! course of it. The ratio of the power (Pw:HR) or the speed (Pa:HR) to the heart rate over the first half of the
+//! elapsed time is compared with the ratio over the second half. A decoupling below 5% on a long, steady session is
+//! generally taken as a sign of a good aerobic base.
+//!
+//! Each sample holds its values until the next one, so the averages don't depend on how often the device records.
+//! Gaps longer than `MAX_GAP_SEC` (e.g. where the recording was paused) only count for `MAX_GAP_SEC`.
+
+use chrono::{DateTime, Local};
+
+use crate::analysis::mean_max::MeanMaxSample;
+use crate::analysis::splits::SplitPoint;
+
+/// Activities shorter than this (in seconds) aren't analysed, since the heart rate has barely settled.
+pub const MIN_DECOUPLING_SEC: f64 = 600.0;
+
+/// Samples further apart than this (in seconds) only hold their values for this long.
+pub const MAX_GAP_SEC: f64 = 5.0;
+
+/// The aerobic decoupling of an activity.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Decoupling {
+    /// How much the power to heart rate ratio dropped from the first half to the second, in percent (Pw:HR).
+    pub power_pct: Option<f64>,
+
+    /// How much the speed to heart rate ratio dropped from the first half to the second, in percent (Pa:HR).
+    pub pace_pct: Option<f64>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out both the power and the pace decoupling of the activity.
+///
+/// # Arguments
+///
+/// - `samples: &[MeanMaxSample]` -- The power and heart rate samples in the order they were recorded.
+/// - `points: &[SplitPoint]` -- The distance and heart rate points in the order they were recorded.
+///
+/// # Returns
+///
+/// `Decoupling` -- The decoupling, with either value left out if there isn't enough data to work it out.
+#[must_use]
+pub fn decoupling(samples: &[MeanMaxSample], points: &[SplitPoint]) -> Decoupling {
+    Decoupling {
+        power_pct: power_decoupling(samples),
+        pace_pct: pace_decoupling(points),
+    }
+}
+
+/// Works out the power to heart rate decoupling (Pw:HR) of the activity.
+///
+/// # Arguments
+///
+/// `samples: &[MeanMaxSample]` -- The samples of the activity in the order they were recorded.
+///
+/// # Returns
+///
+/// `Option<f64>` -- How much the ratio dropped in the second half, in percent of the first half. Negative if the heart
+/// rate dropped against the power. `None` if the activity is too short, or either half has no power or heart rate.
+#[must_use]
+pub fn power_decoupling(samples: &[MeanMaxSample]) -> Option<f64> {
+    drift(
+        &samples
+            .iter()
+            .map(|sample| (sample.time, sample.power, sample.heart_rate))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Works out the speed to heart rate decoupling (Pa:HR) of the activity. The speed between two points is worked out
+/// from the distance covered between them.
+///
+/// # Arguments
+///
+/// `points: &[SplitPoint]` -- The points of the activity in the order they were recorded.
+///
+/// # Returns
+///
+/// `Option<f64>` -- How much the ratio dropped in the second half, in percent of the first half. Negative if the heart
+/// rate dropped against the speed. `None` if the activity is too short, or either half has no heart rate.
+#[must_use]
+pub fn pace_decoupling(points: &[SplitPoint]) -> Option<f64> {
+    let mut samples: Vec<(DateTime<Local>, Option<f64>, Option<f64>)> = points
+        .iter()
+        .zip(points.iter().skip(1))
+        .map(|(prev, curr)| {
+            let seconds = seconds_between(prev.time, curr.time);
+            let speed = (seconds > 0.0).then(|| (curr.distance - prev.distance).max(0.0) / seconds);
+            (prev.time, speed, prev.heart_rate)
+        })
+        .collect();
+    if let Some(last) = points.last() {
+        samples.push((last.time, None, last.heart_rate));
+    }
+
+    drift(&samples)
+}
+
+/// Compares the ratio of the output to the heart rate between the two halves of the elapsed time.
+///
+/// # Arguments
+///
+/// `samples: &[(DateTime<Local>, Option<f64>, Option<f64>)]` -- The time, output and heart rate of each sample.
+fn drift(samples: &[(DateTime<Local>, Option<f64>, Option<f64>)]) -> Option<f64> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let halfway = seconds_between(first.0, last.0) / 2.0;
+    if halfway * 2.0 < MIN_DECOUPLING_SEC {
+        return None;
+    }
+
+    // The output and heart rate added up over the seconds they were held, for each half
+    let mut sums = [(0.0, 0.0); 2];
+    for (prev, curr) in samples.iter().zip(samples.iter().skip(1)) {
+        let (Some(output), Some(heart_rate)) = (prev.1, prev.2) else {
+            continue;
+        };
+        let seconds = seconds_between(prev.0, curr.0).min(MAX_GAP_SEC);
+        if seconds <= 0.0 {
+            continue;
+        }
+        let half = usize::from(seconds_between(first.0, prev.0) >= halfway);
+        sums[half].0 += output * seconds;
+        sums[half].1 += heart_rate * seconds;
+    }
+
+    let [first_half, second_half] =
+        sums.map(|(output, heart_rate)| (heart_rate > 0.0).then(|| output / heart_rate));
+    let (first_half, second_half) = (first_half.filter(|ratio| *ratio > 0.0)?, second_half?);
+
+    Some((first_half - second_half) / first_half * 100.0)
+}
+
+/// The number of seconds from one time to the next.
+#[allow(clippy::cast_precision_loss)]
+fn seconds_between(from: DateTime<Local>, to: DateTime<Local>) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+#[cfg(test)]
+/// Tests for the decoupling module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// The time `secs` seconds into the activity.
+    fn time(secs: i64) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap() + chrono::Duration::seconds(secs)
+    }
+
+    #[test]
+    /// Test the power decoupling with the heart rate drifting up in the second half
+    fn test_power_decoupling() {
+        let samples: Vec<MeanMaxSample> = (0..=3_600)
+            .step_by(10)
+            .map(|secs| MeanMaxSample {
+                time: time(secs),
+                power: Some(200.0),
+                heart_rate: Some(if secs < 1_800 { 140.0 } else { 154.0 }),
+            })
+            .collect();
+        let pct = power_decoupling(&samples).unwrap();
+        assert!((pct - (1.0 - 140.0 / 154.0) * 100.0).abs() < 0.01);
+
+        // Too short, or no heart rate
+        assert!(power_decoupling(&samples[..30]).is_none());
+        let no_hr: Vec<MeanMaxSample> = samples
+            .iter()
+            .map(|sample| MeanMaxSample {
+                heart_rate: None,
+                ..*sample
+            })
+            .collect();
+        assert!(power_decoupling(&no_hr).is_none());
+    }
+
+    #[test]
+    /// Test the pace decoupling at an even speed and heart rate
+    fn test_pace_decoupling() {
+        let points: Vec<SplitPoint> = (0..=1_200)
+            .step_by(5)
+            .map(|secs| SplitPoint {
+                time: time(secs),
+                distance: f64::from(u16::try_from(secs).unwrap()) * 3.0,
+                heart_rate: Some(150.0),
+                altitude: None,
+            })
+            .collect();
+        assert!(pace_decoupling(&points).unwrap().abs() < 0.01);
+
+        let result = decoupling(&[], &points);
+        assert!(result.power_pct.is_none());
+        assert!(result.pace_pct.is_some());
+    }
+}
@@ -0,0 +1,287 @@
+//! Per-record computations (distance, smoothing, percentiles) that are run in parallel for large activities, and the
+//! averages and ratios used by the summaries.
+//!
+//! The per-record work is spread across threads with `rayon`, but the results are always collected in the original
+//! record order, and anything that accumulates (sums, running totals) is done sequentially afterwards. This keeps the
+//! output identical from run to run regardless of the number of threads.
+
+use rayon::prelude::*;
+
+/// The mean radius of the Earth in meters, used in the haversine formula.
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Calculates the great-circle distance in meters between two points using the haversine formula.
+///
+/// # Arguments
+///
+/// - `from: (f64, f64)` -- Latitude and longitude of the first point in degrees.
+/// - `to: (f64, f64)` -- Latitude and longitude of the second point in degrees.
+///
+/// # Returns
+///
+/// `f64` -- The distance between the points in meters.
+///
+/// # Example
+///
+/// ```text
+/// let dist = haversine_distance((59.9139, 10.7522), (60.3913, 5.3221));
+/// ```
+#[must_use]
+pub fn haversine_distance(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Calculates the distance between each pair of consecutive points.
+///
+/// # Arguments
+///
+/// `points: &[(f64, f64)]` -- Latitude and longitude pairs in degrees, in record order.
+///
+/// # Returns
+///
+/// `Vec<f64>` -- One distance (in meters) per point. The first point is always `0.0`.
+#[must_use]
+pub fn segment_distances(points: &[(f64, f64)]) -> Vec<f64> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+
+    let mut distances = Vec::with_capacity(points.len());
+    distances.push(0.0);
+    distances.par_extend(
+        points
+            .par_windows(2)
+            .map(|pair| haversine_distance(pair[0], pair[1])),
+    );
+
+    distances
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Calculates the cumulative distance from the start of the activity at each point.
+///
+/// The segment distances are calculated in parallel, while the running total is summed sequentially so the result
+/// does not depend on how the work was split up.
+///
+/// # Arguments
+///
+/// `points: &[(f64, f64)]` -- Latitude and longitude pairs in degrees, in record order.
+///
+/// # Returns
+///
+/// `Vec<f64>` -- The distance (in meters) covered from the start up to and including each point.
+#[must_use]
+pub fn cumulative_distance(points: &[(f64, f64)]) -> Vec<f64> {
+    let mut total = 0.0;
+    segment_distances(points)
+        .into_iter()
+        .map(|dist| {
+            total += dist;
+            total
+        })
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Smooths a series of values using a centered moving average.
+///
+/// # Arguments
+///
+/// - `values: &[f64]` -- The values to be smoothed, in record order.
+/// - `window: usize` -- The width of the moving average window. A window of `0` or `1` returns the values unchanged.
+///
+/// # Returns
+///
+/// `Vec<f64>` -- The smoothed values. Near the start and end of the series the window is shortened to fit.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn smooth(values: &[f64], window: usize) -> Vec<f64> {
+    if window <= 1 {
+        return values.to_vec();
+    }
+
+    let half = window / 2;
+    (0..values.len())
+        .into_par_iter()
+        .map(|i| {
+            let start = i.saturating_sub(half);
+            let end = (i + window - half).min(values.len());
+            let slice = &values[start..end];
+            slice.iter().sum::<f64>() / slice.len() as f64
+        })
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Calculates the given percentiles of a series of values using linear interpolation between the closest ranks.
+///
+/// # Arguments
+///
+/// - `values: &[f64]` -- The values. `NaN` values are ignored.
+/// - `percentiles: &[f64]` -- The percentiles to calculate, in the range `0.0..=100.0`.
+///
+/// # Returns
+///
+/// `Vec<Option<f64>>` -- One result per requested percentile, or `None` if there are no values.
+///
+/// # Example
+///
+/// ```text
+/// let p = percentiles(&heart_rates, &[50.0, 90.0]);
+/// ```
+#[must_use]
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn percentiles(values: &[f64], percentiles: &[f64]) -> Vec<Option<f64>> {
+    let mut sorted: Vec<f64> = values.par_iter().copied().filter(|v| !v.is_nan()).collect();
+    sorted.par_sort_unstable_by(f64::total_cmp);
+
+    percentiles
+        .iter()
+        .map(|p| {
+            if sorted.is_empty() {
+                return None;
+            }
+            let rank = (p.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f64;
+            let lower = rank.floor() as usize;
+            let upper = rank.ceil() as usize;
+            Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+        })
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Calculates the mean of a series of values, such as the heart rates of a lap.
+///
+/// # Arguments
+///
+/// `values: &[f64]` -- The values.
+///
+/// # Returns
+///
+/// `Option<f64>` -- The mean, or `None` if there are no values or the mean isn't a finite number.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn mean(values: &[f64]) -> Option<f64> {
+    ratio(values.iter().sum(), values.len() as f64)
+}
+
+/// Divides one value by another, such as a distance by a duration to get the average speed.
+///
+/// # Arguments
+///
+/// - `numerator: f64` -- The value to divide.
+/// - `denominator: f64` -- The value to divide by.
+///
+/// # Returns
+///
+/// `Option<f64>` -- The ratio, or `None` if the denominator is zero or the ratio isn't a finite number. Summaries of
+/// files without records would otherwise get `NaN` or infinite averages.
+#[must_use]
+pub fn ratio(numerator: f64, denominator: f64) -> Option<f64> {
+    if denominator == 0.0 {
+        return None;
+    }
+    Some(numerator / denominator).filter(|ratio| ratio.is_finite())
+}
+
+#[cfg(test)]
+/// Tests for the derived computations
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the haversine distance between two known points
+    fn test_haversine_distance() {
+        assert!(haversine_distance((0.0, 0.0), (0.0, 0.0)).abs() < f64::EPSILON);
+
+        // One degree of latitude is roughly 111.2 km
+        let dist = haversine_distance((0.0, 0.0), (1.0, 0.0));
+        assert!((dist - 111_195.0).abs() < 10.0);
+    }
+
+    #[test]
+    /// Test that the segment and cumulative distances line up
+    fn test_cumulative_distance() {
+        assert!(cumulative_distance(&[]).is_empty());
+
+        let points = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)];
+        let segments = segment_distances(&points);
+        let cumulative = cumulative_distance(&points);
+
+        assert_eq!(segments.len(), 3);
+        assert!(segments[0].abs() < f64::EPSILON);
+        assert!((cumulative[2] - segments[1] - segments[2]).abs() < 1e-9);
+    }
+
+    #[test]
+    /// Test that the parallel computation gives the same result as a sequential one, every time
+    #[allow(clippy::cast_precision_loss)]
+    fn test_cumulative_distance_deterministic() {
+        let points: Vec<(f64, f64)> = (0..100_000)
+            .map(|i| {
+                (
+                    59.0 + i as f64 * 1e-5,
+                    10.0 + (i as f64 * 0.01).sin() * 1e-3,
+                )
+            })
+            .collect();
+
+        let mut sequential = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        sequential.push(0.0);
+        for pair in points.windows(2) {
+            total += haversine_distance(pair[0], pair[1]);
+            sequential.push(total);
+        }
+
+        let first = cumulative_distance(&points);
+        assert_eq!(first, cumulative_distance(&points));
+        assert_eq!(first, sequential);
+    }
+
+    #[test]
+    /// Test the moving average
+    fn test_smooth() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+
+        assert_eq!(smooth(&values, 1), values.to_vec());
+        assert_eq!(smooth(&values, 3), vec![1.5, 2.0, 3.0, 4.0, 4.5]);
+        assert!(smooth(&[], 3).is_empty());
+    }
+
+    #[test]
+    /// Test the percentile calculation
+    fn test_percentiles() {
+        let values = [5.0, 1.0, f64::NAN, 3.0, 2.0, 4.0];
+
+        assert_eq!(
+            percentiles(&values, &[0.0, 50.0, 100.0, 25.0]),
+            vec![Some(1.0), Some(3.0), Some(5.0), Some(2.0)]
+        );
+        assert_eq!(percentiles(&[], &[50.0]), vec![None]);
+    }
+
+    #[test]
+    /// Test the mean and ratio of values that may be missing
+    fn test_mean_ratio() {
+        assert_eq!(mean(&[120.0, 130.0, 140.0]), Some(130.0));
+        assert_eq!(mean(&[]), None);
+        assert_eq!(mean(&[1.0, f64::NAN]), None);
+        assert_eq!(ratio(1_000.0, 250.0), Some(4.0));
+        assert_eq!(ratio(1_000.0, 0.0), None);
+        assert_eq!(ratio(0.0, 0.0), None);
+        assert_eq!(ratio(f64::INFINITY, 2.0), None);
+    }
+}
@@ -0,0 +1,148 @@
+//! Compares the distance recorded by the device with the distance worked out from the GPS track. When a wheel sensor
+//! or foot pod is paired, the device records the distance from the sensor, so a large difference usually means the
+//! sensor (wheel size, foot pod factor) is miscalibrated.
+
+use crate::analysis::derived::haversine_distance;
+
+/// Differences larger than this (in percent) are flagged as a possible sensor miscalibration.
+pub const DISCREPANCY_THRESHOLD_PCT: f64 = 5.0;
+
+/// GPS tracks shorter than this (in meters) aren't compared, since GPS drift while standing still or indoors would
+/// give meaningless percentages.
+pub const MIN_GPS_DISTANCE_M: f64 = 100.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Adds up the distance of a GPS track one point at a time, so tracks can be measured without holding all the points
+/// in memory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GpsDistance {
+    /// The previous point, as latitude and longitude in degrees.
+    last: Option<(f64, f64)>,
+
+    /// The distance (in meters) covered so far.
+    total: f64,
+
+    /// The number of points seen.
+    num_points: usize,
+
+    /// The south-west and north-east corners of the area covered by the track, in degrees.
+    bounds: Option<((f64, f64), (f64, f64))>,
+}
+
+impl GpsDistance {
+    /// Adds the next point of the track. Missing positions are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// - `lat: Option<f64>` -- The latitude of the point in degrees.
+    /// - `lon: Option<f64>` -- The longitude of the point in degrees.
+    pub fn add(&mut self, lat: Option<f64>, lon: Option<f64>) {
+        if let (Some(lat), Some(lon)) = (lat, lon) {
+            if let Some(last) = self.last {
+                self.total += haversine_distance(last, (lat, lon));
+            }
+            self.last = Some((lat, lon));
+            self.num_points += 1;
+            self.bounds = Some(self.bounds.map_or(
+                ((lat, lon), (lat, lon)),
+                |((south, west), (north, east))| {
+                    (
+                        (south.min(lat), west.min(lon)),
+                        (north.max(lat), east.max(lon)),
+                    )
+                },
+            ));
+        }
+    }
+
+    /// The distance of the track in meters, or `None` if there were fewer than two points.
+    #[must_use]
+    pub fn total(&self) -> Option<f64> {
+        (self.num_points > 1).then_some(self.total)
+    }
+
+    /// The length of the diagonal across the area covered by the track in meters, or `None` if there were no points.
+    /// A track that wanders around within a few meters has a small extent, however long its distance.
+    #[must_use]
+    pub fn extent(&self) -> Option<f64> {
+        self.bounds
+            .map(|(south_west, north_east)| haversine_distance(south_west, north_east))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Calculates how much the recorded distance differs from the GPS distance.
+///
+/// # Arguments
+///
+/// - `recorded: f64` -- The distance recorded by the device in meters.
+/// - `gps: f64` -- The distance of the GPS track in meters.
+///
+/// # Returns
+///
+/// `Option<f64>` -- The difference in percent of the GPS distance. Positive if the recorded distance is longer.
+/// `None` if the GPS track is too short to compare with.
+///
+/// # Example
+///
+/// ```text
+/// let pct = distance_discrepancy(10_500.0, 10_000.0); // Some(5.0)
+/// ```
+#[must_use]
+pub fn distance_discrepancy(recorded: f64, gps: f64) -> Option<f64> {
+    (gps >= MIN_GPS_DISTANCE_M && recorded > 0.0).then(|| (recorded - gps) / gps * 100.0)
+}
+
+/// Whether the difference is large enough to suggest a miscalibrated sensor.
+#[must_use]
+pub fn is_miscalibrated(discrepancy_pct: f64) -> bool {
+    discrepancy_pct.abs() > DISCREPANCY_THRESHOLD_PCT
+}
+
+/// A warning if the difference between the recorded and GPS distance suggests a miscalibrated sensor.
+pub(crate) fn miscalibration_warning(discrepancy_pct: Option<f64>) -> Option<String> {
+    discrepancy_pct.filter(|pct| is_miscalibrated(*pct)).map(|pct| {
+        format!("The recorded distance differs from the GPS distance by {pct:.1}%. A speed sensor or foot pod may be miscalibrated.")
+    })
+}
+
+#[cfg(test)]
+/// Tests for the distance check
+mod tests {
+    use super::*;
+    use crate::analysis::derived::cumulative_distance;
+
+    #[test]
+    /// Test that the running total matches the cumulative distance and skips missing points
+    fn test_gps_distance() {
+        let points = [(59.0, 10.0), (59.001, 10.0), (59.002, 10.001)];
+        let mut gps = GpsDistance::default();
+        assert!(gps.total().is_none());
+
+        gps.add(Some(points[0].0), Some(points[0].1));
+        gps.add(None, None);
+        assert!(gps.total().is_none());
+        gps.add(Some(points[1].0), Some(points[1].1));
+        gps.add(Some(points[2].0), None);
+        gps.add(Some(points[2].0), Some(points[2].1));
+
+        assert_eq!(gps.total(), cumulative_distance(&points).last().copied());
+        assert_eq!(gps.extent(), Some(haversine_distance(points[0], points[2])));
+    }
+
+    #[test]
+    /// Test the discrepancy and the flagging
+    fn test_distance_discrepancy() {
+        assert_eq!(distance_discrepancy(10_500.0, 10_000.0), Some(5.0));
+        assert_eq!(distance_discrepancy(9_000.0, 10_000.0), Some(-10.0));
+        assert!(distance_discrepancy(1_000.0, 50.0).is_none());
+        assert!(distance_discrepancy(0.0, 10_000.0).is_none());
+
+        assert!(is_miscalibrated(-10.0));
+        assert!(!is_miscalibrated(5.0));
+
+        assert!(miscalibration_warning(Some(-10.0)).is_some_and(|w| w.contains("-10.0%")));
+        assert!(miscalibration_warning(Some(5.0)).is_none());
+        assert!(miscalibration_warning(None).is_none());
+    }
+}
@@ -0,0 +1,182 @@
+//! Finds GPS spikes: points where the position jumps away from the track faster than anything could move, and comes
+//! back to it a few points later. These usually come from a poor fix under trees or between buildings, and add
+//! distance and speed that were never there.
+//!
+//! The spikes are always counted for the summaries. They are only dropped when set for the whole run using
+//! `Settings::set_gps_cleaning()`, along with the speed above which a jump is implausible. FIT records dropped keep
+//! their other values, e.g. the heart rate and power, and only lose their position. GPX waypoints are dropped
+//! altogether.
+
+use chrono::{DateTime, Local};
+
+use crate::analysis::derived::haversine_distance;
+use crate::analysis::track_stats::StatsPoint;
+use crate::Settings;
+
+/// The speed (in meters per second, about 180 km/h) above which a jump between two points is a spike, unless set with
+/// `Settings::set_gps_cleaning()`.
+pub const DEFAULT_MAX_GPS_SPEED_MS: f64 = 50.0;
+
+/// The track has to come back within this many points for the points in between to count as a spike. Longer jumps are
+/// kept, since the track may really have moved on, e.g. after a train ride or a lost fix.
+pub const MAX_SPIKE_POINTS: usize = 5;
+
+impl Settings {
+    /// Sets whether the GPS spikes are dropped.
+    ///
+    /// # Arguments
+    ///
+    /// `max_speed_ms: Option<f64>` -- Drop the points reached faster than this, in meters per second, or `None` to
+    /// keep them.
+    pub fn set_gps_cleaning(&mut self, max_speed_ms: Option<f64>) {
+        self.gps_cleaning = max_speed_ms;
+    }
+}
+
+/// The speed set with `Settings::set_gps_cleaning()`, if the spikes are to be dropped.
+pub(crate) fn gps_cleaning() -> Option<f64> {
+    Settings::current().gps_cleaning
+}
+
+/// The speed above which a jump is a spike for this run.
+pub(crate) fn max_gps_speed() -> f64 {
+    gps_cleaning().unwrap_or(DEFAULT_MAX_GPS_SPEED_MS)
+}
+
+/// Finds the GPS spikes in a track. A point is a spike if it's reached from the last good point faster than the
+/// maximum speed, and the track is back within reach of that point in at most `MAX_SPIKE_POINTS` points. Points
+/// without a position or a time are skipped.
+///
+/// # Arguments
+///
+/// - `points: &[StatsPoint]` -- The points of the track, in order.
+/// - `max_speed_ms: f64` -- The speed in meters per second above which a jump is implausible.
+///
+/// # Returns
+///
+/// `Vec<usize>` -- The indexes of the spikes in `points`, in order.
+#[must_use]
+pub fn gps_outliers(points: &[StatsPoint], max_speed_ms: f64) -> Vec<usize> {
+    let positioned: Vec<usize> = (0..points.len())
+        .filter(|&num| points[num].position.is_some() && points[num].time.is_some())
+        .collect();
+
+    let mut outliers = Vec::new();
+    let mut last_good: Option<usize> = None;
+    let mut next = 0;
+    while next < positioned.len() {
+        let current = positioned[next];
+        let Some(good) = last_good else {
+            last_good = Some(current);
+            next += 1;
+            continue;
+        };
+        if !too_fast(&points[good], &points[current], max_speed_ms) {
+            last_good = Some(current);
+            next += 1;
+            continue;
+        }
+
+        let back = positioned
+            .iter()
+            .skip(next + 1)
+            .take(MAX_SPIKE_POINTS)
+            .position(|&num| !too_fast(&points[good], &points[num], max_speed_ms));
+        match back {
+            Some(offset) => {
+                outliers.extend_from_slice(&positioned[next..=next + offset]);
+                next += offset + 1;
+            }
+            // The track never comes back, so it has moved on
+            None => {
+                last_good = Some(current);
+                next += 1;
+            }
+        }
+    }
+
+    outliers
+}
+
+/// Whether the second point is reached from the first faster than the maximum speed. Points recorded in the same
+/// second count as a second apart.
+fn too_fast(from: &StatsPoint, to: &StatsPoint, max_speed_ms: f64) -> bool {
+    let (Some(start), Some(end), Some(from_pos), Some(to_pos)) =
+        (from.time, to.time, from.position, to.position)
+    else {
+        return false;
+    };
+    let secs = seconds_between(start, end).max(1.0);
+    haversine_distance(from_pos, to_pos) / secs > max_speed_ms
+}
+
+/// The number of seconds from one time to another.
+#[allow(clippy::cast_precision_loss)]
+fn seconds_between(start: DateTime<Local>, end: DateTime<Local>) -> f64 {
+    (end - start).num_milliseconds().abs() as f64 / 1_000.0
+}
+
+/// The warning for the GPS spikes found in an activity, if any.
+///
+/// # Arguments
+///
+/// `num_outliers: Option<usize>` -- The number of spikes found.
+///
+/// # Returns
+///
+/// `Option<String>` -- What was found, and whether the spikes were dropped, or `None` if there were none.
+pub(crate) fn gps_outlier_warning(num_outliers: Option<usize>) -> Option<String> {
+    let num_outliers = num_outliers.filter(|&num| num > 0)?;
+    Some(match gps_cleaning() {
+        Some(speed) => format!("{num_outliers} GPS spikes faster than {speed} m/s dropped."),
+        None => format!(
+            "{num_outliers} GPS points look like spikes faster than {DEFAULT_MAX_GPS_SPEED_MS} m/s. The distance and speeds may be too high."
+        ),
+    })
+}
+
+#[cfg(test)]
+/// Tests for the gps_outliers module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A point `secs` into the track, `north_m` meters north of the start.
+    fn point(secs: i64, north_m: f64) -> StatsPoint {
+        StatsPoint {
+            time: Local.timestamp_opt(1_600_000_000 + secs, 0).single(),
+            // A degree of latitude is about 111 km
+            position: Some((59.0 + north_m / 111_195.0, 10.0)),
+            elevation: None,
+        }
+    }
+
+    #[test]
+    /// Test finding single and double spikes, and keeping a jump the track doesn't come back from
+    fn test_gps_outliers() {
+        let track = [
+            point(0, 0.0),
+            point(1, 3.0),
+            point(2, 900.0),
+            point(3, 9.0),
+            point(4, 12.0),
+            point(5, 2_000.0),
+            point(6, -1_500.0),
+            point(7, 21.0),
+            point(8, 24.0),
+        ];
+        assert_eq!(gps_outliers(&track, DEFAULT_MAX_GPS_SPEED_MS), [2, 5, 6]);
+        assert!(gps_outliers(&track, 10_000.0).is_empty());
+
+        let moved_on: Vec<StatsPoint> = (0..10)
+            .map(|secs| point(secs, if secs < 3 { 0.0 } else { 5_000.0 }))
+            .collect();
+        assert!(gps_outliers(&moved_on, DEFAULT_MAX_GPS_SPEED_MS).is_empty());
+
+        let untimed = [StatsPoint {
+            time: None,
+            ..point(1, 900.0)
+        }];
+        assert!(gps_outliers(&untimed, DEFAULT_MAX_GPS_SPEED_MS).is_empty());
+    }
+}
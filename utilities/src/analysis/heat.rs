@@ -0,0 +1,97 @@
+//! Works out how much the heat held back an activity, so efforts on hot summer days can be compared fairly with the
+//! rest of the year. The same effort gets slower as it gets warmer, by about `HEAT_SLOWDOWN_PCT_PER_C` for each degree
+//! above `NEUTRAL_TEMPERATURE_C`. The heat-adjusted speed is the speed the effort would have given at the neutral
+//! temperature.
+//!
+//! The temperature comes from the device. None of the formats record the humidity, so it is left out.
+
+use serde::{Deserialize, Serialize};
+
+/// The temperature (in degrees Celsius) where the heat doesn't slow the effort down.
+pub const NEUTRAL_TEMPERATURE_C: f64 = 15.0;
+
+/// How much slower the same effort gets for each degree above `NEUTRAL_TEMPERATURE_C`, in percent.
+pub const HEAT_SLOWDOWN_PCT_PER_C: f64 = 0.4;
+
+/// Activities with an average temperature at or above this (in degrees Celsius) are flagged as hot.
+pub const HOT_TEMPERATURE_C: f64 = 25.0;
+
+/// Whether an activity took place in hot weather.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatFlag {
+    /// Below `HOT_TEMPERATURE_C`.
+    Normal,
+    /// At or above `HOT_TEMPERATURE_C`.
+    Hot,
+}
+
+impl std::fmt::Display for HeatFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Normal => write!(f, "normal"),
+            Self::Hot => write!(f, "hot"),
+        }
+    }
+}
+
+/// How much the heat held back an activity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeatEffort {
+    /// How much slower the effort was because of the heat, in percent. Zero at or below the neutral temperature.
+    pub adjustment_pct: f64,
+
+    /// The average speed the effort would have given at the neutral temperature, in meters per second.
+    pub adjusted_speed: Option<f64>,
+
+    /// Whether the activity took place in hot weather.
+    pub flag: HeatFlag,
+}
+
+/// Works out how much the heat held back an activity.
+///
+/// # Arguments
+///
+/// - `temperature_avg_c: Option<f64>` -- The average temperature in degrees Celsius.
+/// - `speed_avg: Option<f64>` -- The average speed in meters per second.
+///
+/// # Returns
+///
+/// `Option<HeatEffort>` -- The effect of the heat, or `None` if the temperature isn't known.
+#[must_use]
+pub fn heat_effort(temperature_avg_c: Option<f64>, speed_avg: Option<f64>) -> Option<HeatEffort> {
+    let temperature = temperature_avg_c.filter(|temperature| temperature.is_finite())?;
+    let adjustment_pct = (temperature - NEUTRAL_TEMPERATURE_C).max(0.0) * HEAT_SLOWDOWN_PCT_PER_C;
+
+    Some(HeatEffort {
+        adjustment_pct,
+        adjusted_speed: speed_avg.map(|speed| speed * (1.0 + adjustment_pct / 100.0)),
+        flag: if temperature >= HOT_TEMPERATURE_C {
+            HeatFlag::Hot
+        } else {
+            HeatFlag::Normal
+        },
+    })
+}
+
+#[cfg(test)]
+/// Tests for the heat module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the adjustment above and below the neutral temperature
+    fn test_heat_effort() {
+        let hot = heat_effort(Some(30.0), Some(3.0)).unwrap();
+        assert!((hot.adjustment_pct - 6.0).abs() < 1e-9);
+        assert!((hot.adjusted_speed.unwrap() - 3.18).abs() < 1e-9);
+        assert_eq!(hot.flag, HeatFlag::Hot);
+
+        let cold = heat_effort(Some(5.0), None).unwrap();
+        assert!(cold.adjustment_pct.abs() < f64::EPSILON);
+        assert!(cold.adjusted_speed.is_none());
+        assert_eq!(cold.flag, HeatFlag::Normal);
+
+        assert!(heat_effort(None, Some(3.0)).is_none());
+    }
+}
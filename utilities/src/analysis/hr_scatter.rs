@@ -0,0 +1,322 @@
+//! Pairs the heart rate with the output it was held at -- the speed and pace, and the power -- for regression analysis
+//! of the fitness across activities, e.g. the heart rate at 12 km/h dropping over a season. The activity is cut into
+//! windows of `SCATTER_WINDOW_SEC`, and each window gives one sample with the averages over it, which smooths out the
+//! noise of the single records and the lag of the heart rate behind the output.
+//!
+//! Windows are left out where the data can't be trusted: with gaps in the recording, while standing still, with a
+//! heart rate outside `HEART_RATE_RANGE_BPM`, and in the first `SCATTER_WARM_UP_SEC` while the heart rate is still
+//! rising. Of the rest, the outputs where the heart rate per unit of output is more than `OUTLIER_MADS` median
+//! absolute deviations from the median of the activity are left out too, e.g. where a heart rate strap lost contact.
+
+use chrono::{DateTime, Local, TimeDelta};
+use serde::Serialize;
+use std::error::Error;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use crate::analysis::derived::percentiles;
+use crate::analysis::mean_max::{per_second_held, MeanMaxSample};
+use crate::analysis::splits::SplitPoint;
+use crate::analysis::track_stats::MOVING_SPEED_MIN_MS;
+use crate::columns::{ColumnWriter, CsvFile};
+use crate::precision::Rounded;
+use crate::units::unit_system;
+use crate::UnitSystem;
+
+/// The length of the windows averaged into each sample, in seconds.
+pub const SCATTER_WINDOW_SEC: usize = 30;
+
+/// The start of the activity left out while the heart rate settles, in seconds.
+pub const SCATTER_WARM_UP_SEC: usize = 300;
+
+/// The values are held over shorter gaps between the samples, in seconds, so that files made with smart recording
+/// keep their windows. Longer gaps are pauses.
+const SCATTER_MAX_GAP_SEC: usize = 10;
+
+/// The heart rates taken as real, in beats per minute.
+const HEART_RATE_RANGE_BPM: RangeInclusive<f64> = 40.0..=230.0;
+
+/// Outputs further than this many median absolute deviations from the median are taken for outliers.
+const OUTLIER_MADS: f64 = 3.0;
+
+/// Scales the median absolute deviation to the standard deviation of normally distributed values.
+const MAD_SCALE: f64 = 1.4826;
+
+/// The heart rate over a window of the activity, along with the output it was held at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct HrScatter {
+    /// When the window started.
+    #[serde(rename = "time")]
+    pub time: Option<DateTime<Local>>,
+
+    /// The seconds from the start of the activity to the start of the window.
+    #[serde(rename = "elapsed_sec")]
+    pub elapsed_sec: usize,
+
+    /// The average speed over the window in meters per second, if moving and not an outlier.
+    #[serde(rename = "speed_ms")]
+    pub speed_ms: Option<f64>,
+
+    /// The time per kilometer at the average speed in seconds, or per mile with imperial units.
+    #[serde(rename = "pace_sec")]
+    pub pace_sec: Option<f64>,
+
+    /// The average power over the window in watts, if recorded and not an outlier.
+    #[serde(rename = "power_w")]
+    pub power_w: Option<f64>,
+
+    /// The average heart rate over the window in beats per minute.
+    #[serde(rename = "heartrate_bpm")]
+    pub heartrate_bpm: Option<f64>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the heart rate against the speed and power over each window of the activity.
+///
+/// # Arguments
+///
+/// - `samples: &[MeanMaxSample]` -- The power and heart rate samples in the order they were recorded.
+/// - `points: &[SplitPoint]` -- The distance points in the order they were recorded. The speed is worked out from them.
+///
+/// # Returns
+///
+/// `Vec<HrScatter>` -- One sample per window with a heart rate and a speed or power, in the order they came.
+#[must_use]
+pub fn hr_scatter(samples: &[MeanMaxSample], points: &[SplitPoint]) -> Vec<HrScatter> {
+    let Some(first) = samples.first() else {
+        return Vec::new();
+    };
+    let heart_rate = per_second_held(samples, SCATTER_MAX_GAP_SEC, |sample| sample.heart_rate);
+    let power = per_second_held(samples, SCATTER_MAX_GAP_SEC, |sample| sample.power);
+
+    // The windows are lined up with the start of the activity, and only taken within a stretch without pauses
+    let mut scatter: Vec<HrScatter> = heart_rate
+        .iter()
+        .zip(&power)
+        .flat_map(|(heart_rate, power)| {
+            let windows = heart_rate
+                .start_sec
+                .div_ceil(SCATTER_WINDOW_SEC)
+                .max(SCATTER_WARM_UP_SEC / SCATTER_WINDOW_SEC)
+                ..(heart_rate.start_sec + heart_rate.values.len()) / SCATTER_WINDOW_SEC;
+            windows.filter_map(move |window| {
+                let start = window * SCATTER_WINDOW_SEC;
+                let offset = start - heart_rate.start_sec;
+                let range = offset..offset + SCATTER_WINDOW_SEC;
+                let heartrate = window_mean(&heart_rate.values[range.clone()])
+                    .filter(|heartrate| HEART_RATE_RANGE_BPM.contains(heartrate))?;
+                let time = first.time + TimeDelta::seconds(i64::try_from(start).ok()?);
+                let speed =
+                    window_speed(points, time).filter(|speed| *speed >= MOVING_SPEED_MIN_MS);
+                let power = window_mean(&power.values[range]).filter(|power| *power > 0.0);
+
+                (speed.is_some() || power.is_some()).then_some(HrScatter {
+                    time: Some(time),
+                    elapsed_sec: start,
+                    speed_ms: speed,
+                    pace_sec: None,
+                    power_w: power,
+                    heartrate_bpm: Some(heartrate),
+                })
+            })
+        })
+        .collect();
+
+    drop_outliers(&mut scatter, |sample| &mut sample.speed_ms);
+    drop_outliers(&mut scatter, |sample| &mut sample.power_w);
+    scatter.retain(|sample| sample.speed_ms.is_some() || sample.power_w.is_some());
+
+    let units = unit_system().unwrap_or(UnitSystem::Metric);
+    for sample in &mut scatter {
+        sample.pace_sec = sample
+            .speed_ms
+            .and_then(|speed| units.pace(speed))
+            .map(|pace| pace.0.as_secs_f64());
+    }
+    scatter
+}
+
+/// The average of the values, or `None` if any second of the window is empty.
+#[allow(clippy::cast_precision_loss)]
+fn window_mean(values: &[Option<f64>]) -> Option<f64> {
+    let sum = values.iter().copied().sum::<Option<f64>>()?;
+    Some(sum / values.len() as f64)
+}
+
+/// The average speed over the window starting at the time given, from the distance covered over it.
+#[allow(clippy::cast_precision_loss)]
+fn window_speed(points: &[SplitPoint], start: DateTime<Local>) -> Option<f64> {
+    let end = start + TimeDelta::seconds(i64::try_from(SCATTER_WINDOW_SEC).ok()?);
+    Some((distance_at(points, end)? - distance_at(points, start)?) / SCATTER_WINDOW_SEC as f64)
+}
+
+/// The distance covered at the time given, interpolated between the points either side of it.
+#[allow(clippy::cast_precision_loss)]
+fn distance_at(points: &[SplitPoint], time: DateTime<Local>) -> Option<f64> {
+    let next = points.partition_point(|point| point.time < time);
+    let after = points.get(next)?;
+    if after.time == time {
+        return Some(after.distance);
+    }
+    let before = points.get(next.checked_sub(1)?)?;
+
+    let span = (after.time - before.time).num_milliseconds() as f64;
+    let elapsed = (time - before.time).num_milliseconds() as f64;
+    Some(before.distance + (after.distance - before.distance) * elapsed / span)
+}
+
+/// Clears the outputs where the heart rate per unit of output is further than `OUTLIER_MADS` scaled median absolute
+/// deviations from the median.
+fn drop_outliers<F>(scatter: &mut [HrScatter], output: F)
+where
+    F: Fn(&mut HrScatter) -> &mut Option<f64>,
+{
+    let ratio = |sample: &mut HrScatter| {
+        let heartrate = sample.heartrate_bpm?;
+        output(sample).map(|output| heartrate / output)
+    };
+    let ratios: Vec<f64> = scatter.iter_mut().filter_map(&ratio).collect();
+    let Some(median) = percentiles(&ratios, &[50.0])[0] else {
+        return;
+    };
+    let deviations: Vec<f64> = ratios.iter().map(|r| (r - median).abs()).collect();
+    let Some(mad) = percentiles(&deviations, &[50.0])[0].filter(|mad| *mad > 0.0) else {
+        return;
+    };
+
+    for sample in scatter.iter_mut() {
+        if ratio(sample).is_some_and(|r| (r - median).abs() > OUTLIER_MADS * MAD_SCALE * mad) {
+            *output(sample) = None;
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes the samples as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+///
+/// # Arguments
+///
+/// - `scatter: &[HrScatter]` -- The samples to write.
+/// - `writer: W` -- Where the CSV is written.
+///
+/// # Errors
+///
+/// Serializing or writing the samples may fail.
+pub fn write_hr_scatter_csv<W: Write>(
+    scatter: &[HrScatter],
+    writer: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = ColumnWriter::new(writer, CsvFile::HrScatter);
+    writer.write_header()?;
+    for sample in scatter {
+        writer.serialize(Rounded(sample))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the samples to a CSV file.
+///
+/// # Arguments
+///
+/// - `scatter: &[HrScatter]` -- The samples to write.
+/// - `filename: &str` -- The CSV file to write, usually named after the activity file with `.hr_scatter.csv` added.
+///
+/// # Errors
+///
+/// Creating or writing the file may fail.
+#[cfg(feature = "fs")]
+pub fn export_hr_scatter_csv(scatter: &[HrScatter], filename: &str) -> Result<(), Box<dyn Error>> {
+    log::trace!(
+        "hr_scatter::export_hr_scatter_csv() -- Writing {} samples to {filename}",
+        scatter.len()
+    );
+    crate::output_naming::write_output(std::path::Path::new(filename), |file| {
+        write_hr_scatter_csv(scatter, file)
+    })
+}
+
+#[cfg(test)]
+/// Tests for the hr_scatter module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// The time `secs` seconds into the activity.
+    fn time(secs: i64) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap() + TimeDelta::seconds(secs)
+    }
+
+    #[test]
+    /// Test pairing the heart rate with the speed and power, leaving out the warm-up, the stops and the outliers
+    fn test_hr_scatter() {
+        let heart_rate = |secs: i64| match secs {
+            1_200..1_230 => 220.0, // The strap lost contact
+            _ => 140.0 + f64::from(u8::try_from(secs % 7).unwrap()),
+        };
+        let samples: Vec<MeanMaxSample> = (0..1_800)
+            .map(|secs| MeanMaxSample {
+                time: time(secs),
+                power: Some(200.0),
+                heart_rate: Some(heart_rate(secs)),
+            })
+            .collect();
+        let points: Vec<SplitPoint> = (0..1_800)
+            .map(|secs| SplitPoint {
+                time: time(secs),
+                // Standing still from 15 to 16 minutes
+                distance: 3.0
+                    * f64::from(u16::try_from(secs.clamp(0, 900) + (secs - 960).max(0)).unwrap()),
+                heart_rate: Some(heart_rate(secs)),
+                altitude: None,
+            })
+            .collect();
+
+        let scatter = hr_scatter(&samples, &points);
+        assert_eq!(scatter[0].elapsed_sec, SCATTER_WARM_UP_SEC);
+        assert_eq!(scatter[0].time, Some(time(300)));
+        assert_eq!(scatter[0].speed_ms, Some(3.0));
+        assert!((scatter[0].pace_sec.unwrap() - 1_000.0 / 3.0).abs() < 0.01);
+        assert_eq!(scatter[0].power_w, Some(200.0));
+        assert_eq!(scatter[0].heartrate_bpm, Some(143.0));
+
+        // The stop has the power but no speed, and the strap losing contact is left out altogether
+        let at = |secs: usize| scatter.iter().find(|sample| sample.elapsed_sec == secs);
+        assert_eq!(at(930).map(|sample| sample.speed_ms), Some(None));
+        assert!(at(1_200).is_none());
+        assert_eq!(scatter.len(), (1_800 - 300) / 30 - 1);
+
+        // Smart recording every 6 seconds keeps the windows, but the last one, which runs past the last sample
+        let sparse: Vec<MeanMaxSample> = samples.iter().step_by(6).cloned().collect();
+        let sparse_scatter = hr_scatter(&sparse, &points);
+        assert_eq!(sparse_scatter.len(), scatter.len() - 1);
+
+        assert!(hr_scatter(&[], &points).is_empty());
+    }
+
+    #[test]
+    /// Test writing the samples as CSV
+    fn test_write_hr_scatter_csv() {
+        let scatter = [HrScatter {
+            time: Some(time(300)),
+            elapsed_sec: 300,
+            speed_ms: Some(3.0),
+            pace_sec: Some(333.333_333),
+            power_w: None,
+            heartrate_bpm: Some(141.5),
+        }];
+        let mut csv = Vec::new();
+        write_hr_scatter_csv(&scatter, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("time,elapsed_sec,speed_ms,pace_sec,power_w,heartrate_bpm")
+        );
+        assert!(lines
+            .next()
+            .unwrap()
+            .ends_with(",300,3.0,333.333333,,141.5"));
+    }
+}
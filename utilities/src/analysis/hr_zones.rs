@@ -0,0 +1,254 @@
+//! Works out the time spent in each heart rate zone from the heart rates of the trackpoints, for files that don't
+//! record it themselves, such as GPX and TCX files, and for FIT files when the zones are set.
+//!
+//! The zones are set once for the whole run using `Settings::set_hr_zones()`, either from the maximum heart rate, the
+//! lactate threshold heart rate or as the heart rates where each zone starts. Without it, the zones are based on a
+//! maximum heart rate of `DEFAULT_MAX_HR`, and FIT files keep the time in zone recorded by the device.
+
+use chrono::{DateTime, Local};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Duration, FITHrZones, Settings};
+
+/// The maximum heart rate assumed if the zones aren't set, i.e. 220 less an age of 30.
+pub const DEFAULT_MAX_HR: f64 = 190.0;
+
+/// Where zones 1 to 4 start, in percent of the maximum heart rate.
+pub const ZONE_START_PCT: [f64; 4] = [60.0, 70.0, 80.0, 90.0];
+
+/// Where zones 1 to 4 start, in percent of the lactate threshold heart rate.
+pub const LTHR_ZONE_START_PCT: [f64; 4] = [85.0, 90.0, 95.0, 100.0];
+
+/// Longer gaps between two trackpoints (in seconds) are pauses, and don't count towards any zone.
+pub const MAX_ZONE_INTERVAL_SEC: f64 = 30.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The heart rates where each zone starts. Anything below the start of zone 1 is in zone 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HrZoneLimits {
+    /// The heart rates in beats per minute where zones 1 to 4 start, lowest first.
+    pub zone_start_bpm: [f64; 4],
+}
+
+impl HrZoneLimits {
+    /// The zones for the maximum heart rate given, starting at `ZONE_START_PCT` of it.
+    ///
+    /// # Arguments
+    ///
+    /// `max_hr: f64` -- The maximum heart rate in beats per minute.
+    #[must_use]
+    pub fn from_max_hr(max_hr: f64) -> Self {
+        Self {
+            zone_start_bpm: ZONE_START_PCT.map(|pct| max_hr * pct / 100.0),
+        }
+    }
+
+    /// The zones for the lactate threshold heart rate given, starting at `LTHR_ZONE_START_PCT` of it.
+    ///
+    /// # Arguments
+    ///
+    /// `lthr: f64` -- The lactate threshold heart rate in beats per minute.
+    #[must_use]
+    pub fn from_lthr(lthr: f64) -> Self {
+        Self {
+            zone_start_bpm: LTHR_ZONE_START_PCT.map(|pct| lthr * pct / 100.0),
+        }
+    }
+
+    /// The zone of the heart rate, from 0 to 4.
+    #[must_use]
+    pub fn zone(&self, heart_rate: f64) -> usize {
+        self.zone_start_bpm
+            .iter()
+            .take_while(|start| heart_rate >= **start)
+            .count()
+    }
+}
+
+impl Default for HrZoneLimits {
+    fn default() -> Self {
+        Self::from_max_hr(DEFAULT_MAX_HR)
+    }
+}
+
+impl FromStr for HrZoneLimits {
+    type Err = String;
+
+    /// Reads the zones as either the maximum heart rate, e.g. `185`, the lactate threshold heart rate, e.g.
+    /// `lthr:165`, or the heart rates where zones 1 to 4 start, e.g. `111,130,148,167`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = || {
+            format!("Unknown heart rate zones {s}. Use the maximum heart rate, the lactate threshold heart rate, e.g. lthr:165, or the four heart rates where zones 1 to 4 start, e.g. 111,130,148,167.")
+        };
+        if let Some(lthr) = s.trim().strip_prefix("lthr:") {
+            return match lthr.trim().parse::<f64>() {
+                Ok(lthr) if lthr.is_finite() && lthr > 0.0 => Ok(Self::from_lthr(lthr)),
+                _ => Err(usage()),
+            };
+        }
+
+        let bpm = s
+            .split(',')
+            .map(|bpm| bpm.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| usage())?;
+        if bpm.iter().any(|bpm| !bpm.is_finite() || *bpm <= 0.0) {
+            return Err(usage());
+        }
+
+        match bpm.as_slice() {
+            [max_hr] => Ok(Self::from_max_hr(*max_hr)),
+            [one, two, three, four] if one < two && two < three && three < four => Ok(Self {
+                zone_start_bpm: [*one, *two, *three, *four],
+            }),
+            _ => Err(usage()),
+        }
+    }
+}
+
+impl fmt::Display for HrZoneLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [one, two, three, four] = self.zone_start_bpm;
+        write!(f, "{one:.0},{two:.0},{three:.0},{four:.0}")
+    }
+}
+
+impl Settings {
+    /// Sets the heart rate zones.
+    ///
+    /// # Arguments
+    ///
+    /// `limits: HrZoneLimits` -- The heart rates where the zones start.
+    pub fn set_hr_zones(&mut self, limits: HrZoneLimits) {
+        self.hr_zones = Some(limits);
+    }
+}
+
+/// The heart rate zones for this run.
+pub(crate) fn hr_zones() -> HrZoneLimits {
+    custom_hr_zones().unwrap_or_default()
+}
+
+/// The heart rate zones set with `Settings::set_hr_zones()`, if any.
+pub(crate) fn custom_hr_zones() -> Option<HrZoneLimits> {
+    Settings::current().hr_zones
+}
+
+/// The zones to work out the time in each zone with for a file that may have recorded it already: the zones set for
+/// the run, or the default zones if nothing was recorded. `None` means the recorded time in zone is kept.
+///
+/// # Arguments
+///
+/// `recorded: &FITHrZones` -- The time in each zone recorded by the device, if any.
+pub(crate) fn recalculated_hr_zones(recorded: &FITHrZones) -> Option<HrZoneLimits> {
+    custom_hr_zones().or_else(|| (*recorded == FITHrZones::default()).then(HrZoneLimits::default))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Adds up the time spent in each heart rate zone. The time between two trackpoints counts towards the zone of the
+/// first one, unless the first one has no heart rate or the gap is a pause.
+///
+/// # Arguments
+///
+/// - `samples: &[(DateTime<Local>, Option<f64>)]` -- The time and heart rate of each trackpoint, in order.
+/// - `limits: &HrZoneLimits` -- The heart rates where the zones start.
+///
+/// # Returns
+///
+/// `FITHrZones` -- The time in each zone, or all `None` if there are no heart rates.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn time_in_hr_zones(
+    samples: &[(DateTime<Local>, Option<f64>)],
+    limits: &HrZoneLimits,
+) -> FITHrZones {
+    if samples.iter().all(|(_, heart_rate)| heart_rate.is_none()) {
+        return FITHrZones::default();
+    }
+
+    let mut secs = [0.0; 5];
+    for pair in samples.windows(2) {
+        let ((start, heart_rate), (end, _)) = (pair[0], pair[1]);
+        let interval = (end - start).num_milliseconds() as f64 / 1_000.0;
+        if let Some(heart_rate) = heart_rate {
+            if interval > 0.0 && interval <= MAX_ZONE_INTERVAL_SEC {
+                secs[limits.zone(heart_rate)] += interval;
+            }
+        }
+    }
+
+    let [zone_0, zone_1, zone_2, zone_3, zone_4] = secs.map(|s| Some(Duration::from_secs_f64(s)));
+    FITHrZones {
+        hr_zone_0: zone_0,
+        hr_zone_1: zone_1,
+        hr_zone_2: zone_2,
+        hr_zone_3: zone_3,
+        hr_zone_4: zone_4,
+    }
+}
+
+#[cfg(test)]
+/// Tests for the hr_zones module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    /// Test the zones of the heart rates, and reading the zones from the command line
+    fn test_zones() {
+        let limits = HrZoneLimits::from_max_hr(200.0);
+        assert_eq!(limits.zone_start_bpm, [120.0, 140.0, 160.0, 180.0]);
+        assert_eq!(limits.zone(90.0), 0);
+        assert_eq!(limits.zone(120.0), 1);
+        assert_eq!(limits.zone(159.9), 2);
+        assert_eq!(limits.zone(200.0), 4);
+
+        assert_eq!("200".parse(), Ok(limits));
+        assert_eq!(
+            "lthr:160".parse::<HrZoneLimits>().unwrap().zone_start_bpm,
+            [136.0, 144.0, 152.0, 160.0]
+        );
+        assert_eq!(
+            "111, 130,148,167"
+                .parse::<HrZoneLimits>()
+                .unwrap()
+                .to_string(),
+            "111,130,148,167"
+        );
+        for bad in [
+            "",
+            "fast",
+            "0",
+            "120,140,160",
+            "140,120,160,180",
+            "lthr:",
+            "lthr:-1",
+        ] {
+            assert!(bad.parse::<HrZoneLimits>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    /// Test adding up the time in each zone, leaving out the pauses and the trackpoints without a heart rate
+    fn test_time_in_hr_zones() {
+        let at = |secs: i64| Local.timestamp_opt(1_600_000_000 + secs, 0).unwrap();
+        let samples = [
+            (at(0), Some(100.0)),
+            (at(10), Some(125.0)),
+            (at(20), None),
+            (at(25), Some(185.0)),
+            (at(30), Some(185.0)),
+            (at(330), Some(100.0)),
+        ];
+
+        let zones = time_in_hr_zones(&samples, &HrZoneLimits::from_max_hr(200.0));
+        assert_eq!(zones.hr_zone_0, Some(Duration::from_secs_f64(10.0)));
+        assert_eq!(zones.hr_zone_1, Some(Duration::from_secs_f64(10.0)));
+        assert_eq!(zones.hr_zone_2, Some(Duration::from_secs_f64(0.0)));
+        assert_eq!(zones.hr_zone_4, Some(Duration::from_secs_f64(5.0)));
+
+        let no_hr = time_in_hr_zones(&[(at(0), None), (at(1), None)], &HrZoneLimits::default());
+        assert!(no_hr.hr_zone_0.is_none());
+    }
+}
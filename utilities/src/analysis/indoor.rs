@@ -0,0 +1,86 @@
+//! Tells treadmill runs and other indoor activities from outdoor ones by the GPS track, for files where the sport
+//! doesn't say. Indoors the distance comes from a foot pod, a trainer or the machine, while the GPS either has no fix
+//! at all or wanders around within a few meters of where the device is.
+
+use crate::analysis::distance_check::GpsDistance;
+use crate::FITEnvironment;
+
+/// Activities shorter than this (in meters) are left as they are, since there is too little movement to tell.
+pub const MIN_DISTANCE_M: f64 = 500.0;
+
+/// GPS tracks covering an area with a diagonal shorter than this (in meters) are taken as drift rather than movement.
+pub const MAX_INDOOR_EXTENT_M: f64 = 100.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out where the activity took place from what the file says and from the shape of the GPS track.
+///
+/// # Arguments
+///
+/// - `declared: FITEnvironment` -- The environment given by the file, e.g. from the FIT `sub_sport`.
+/// - `distance_m: Option<f64>` -- The distance covered in meters, from the device or the GPS track.
+/// - `gps: &GpsDistance` -- The GPS track of the activity.
+///
+/// # Returns
+///
+/// `FITEnvironment` -- `Indoor` if the activity covered a distance with no GPS positions, or with positions that never
+/// got further apart than `MAX_INDOOR_EXTENT_M`. Otherwise the environment given by the file.
+///
+/// # Example
+///
+/// ```text
+/// // A 5 km treadmill run where the watch recorded no positions
+/// let environment = detect_environment(FITEnvironment::Outdoor, Some(5_000.0), &GpsDistance::default()); // Indoor
+/// ```
+#[must_use]
+pub fn detect_environment(
+    declared: FITEnvironment,
+    distance_m: Option<f64>,
+    gps: &GpsDistance,
+) -> FITEnvironment {
+    if declared != FITEnvironment::Outdoor
+        || distance_m.is_none_or(|distance| distance < MIN_DISTANCE_M)
+    {
+        return declared;
+    }
+
+    match gps.extent() {
+        Some(extent) if extent >= MAX_INDOOR_EXTENT_M => declared,
+        _ => FITEnvironment::Indoor,
+    }
+}
+
+#[cfg(test)]
+/// Tests for the indoor module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test telling indoor from outdoor by the GPS track
+    fn test_detect_environment() {
+        let outdoor = FITEnvironment::Outdoor;
+        let mut drift = GpsDistance::default();
+        drift.add(Some(59.0), Some(10.0));
+        drift.add(Some(59.0002), Some(10.0003));
+        let mut track = drift;
+        track.add(Some(59.01), Some(10.0));
+
+        // No GPS, or GPS drifting in place, while covering 5 km
+        assert_eq!(
+            detect_environment(outdoor, Some(5_000.0), &GpsDistance::default()),
+            FITEnvironment::Indoor
+        );
+        assert_eq!(
+            detect_environment(outdoor, Some(5_000.0), &drift),
+            FITEnvironment::Indoor
+        );
+        assert_eq!(detect_environment(outdoor, Some(5_000.0), &track), outdoor);
+
+        // Too short to tell, or already given by the file
+        assert_eq!(detect_environment(outdoor, Some(100.0), &drift), outdoor);
+        assert_eq!(detect_environment(outdoor, None, &drift), outdoor);
+        assert_eq!(
+            detect_environment(FITEnvironment::Virtual, Some(5_000.0), &track),
+            FITEnvironment::Virtual
+        );
+    }
+}
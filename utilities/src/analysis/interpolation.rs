@@ -0,0 +1,172 @@
+//! Fills in the gaps between the records of files made with smart recording, where the device only records every few
+//! seconds, with a record for every second in between. The values are interpolated in a straight line between the two
+//! recorded records, which gives smoother charts and a better estimate of the moving time.
+//!
+//! The records added are marked as `synthetic`, so they can be told apart from the ones recorded by the device.
+
+use chrono::TimeDelta;
+use uom::si::f64::{Length, Velocity};
+use uom::si::{length::meter, velocity::meter_per_second};
+
+use crate::analysis::track_stats::MOVING_SPEED_MIN_MS;
+use crate::{Duration, FITRecord};
+
+/// Longer gaps between two records (in seconds) are pauses, and are left as they are.
+pub const MAX_INTERPOLATION_GAP_SEC: i64 = 10;
+
+/// The value a share of the way from one value to the next, or `None` unless both are known.
+fn lerp(from: Option<f64>, to: Option<f64>, share: f64) -> Option<f64> {
+    Some(from? + (to? - from?) * share)
+}
+
+/// The record `offset_ms` into the gap between two records, with the values that change smoothly interpolated. The
+/// power, cadence and the other values measured at a point in time are left out.
+#[allow(clippy::cast_precision_loss)]
+fn synthetic_record(from: &FITRecord, to: &FITRecord, offset_ms: i64, gap_ms: i64) -> FITRecord {
+    let share = offset_ms as f64 / gap_ms as f64;
+    let meters = |length: Option<Length>| length.map(|length| length.value);
+
+    FITRecord {
+        timestamp: from
+            .timestamp
+            .map(|time| time + TimeDelta::milliseconds(offset_ms)),
+        duration: lerp(
+            from.duration.map(|duration| duration.0.as_secs_f64()),
+            to.duration.map(|duration| duration.0.as_secs_f64()),
+            share,
+        )
+        .map(Duration::from_secs_f64),
+        distance: lerp(meters(from.distance), meters(to.distance), share).map(Length::new::<meter>),
+        altitude: lerp(meters(from.altitude), meters(to.altitude), share).map(Length::new::<meter>),
+        altitude_source: from.altitude_source,
+        speed: lerp(
+            from.speed.map(|speed| speed.value),
+            to.speed.map(|speed| speed.value),
+            share,
+        )
+        .map(Velocity::new::<meter_per_second>),
+        heartrate: lerp(
+            from.heartrate.map(f64::from),
+            to.heartrate.map(f64::from),
+            share,
+        )
+        .map(|heartrate| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let heartrate = heartrate.round() as u8;
+            heartrate
+        }),
+        lat: lerp(from.lat, to.lat, share),
+        lon: lerp(from.lon, to.lon, share),
+        activity_uuid: from.activity_uuid,
+        session_num: from.session_num,
+        synthetic: Some(true),
+        ..FITRecord::default()
+    }
+}
+
+/// Adds a record for every second in the gaps between the records. Gaps of a second or less, gaps longer than
+/// `MAX_INTERPOLATION_GAP_SEC` and gaps between sessions are left as they are.
+///
+/// # Arguments
+///
+/// `records: &[FITRecord]` -- The recorded records, in order.
+///
+/// # Returns
+///
+/// `Vec<FITRecord>` -- The records with the synthetic records added between them.
+#[must_use]
+pub fn interpolate_records(records: &[FITRecord]) -> Vec<FITRecord> {
+    let mut filled = Vec::with_capacity(records.len());
+    for pair in records.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        filled.push(from.clone());
+
+        let (Some(start), Some(end)) = (from.timestamp, to.timestamp) else {
+            continue;
+        };
+        let gap_ms = (end - start).num_milliseconds();
+        if gap_ms > MAX_INTERPOLATION_GAP_SEC * 1_000 || from.session_num != to.session_num {
+            continue;
+        }
+        filled.extend(
+            (1..)
+                .map(|second| second * 1_000)
+                .take_while(|offset_ms| *offset_ms < gap_ms)
+                .map(|offset_ms| synthetic_record(from, to, offset_ms, gap_ms)),
+        );
+    }
+    filled.extend(records.last().cloned());
+    filled
+}
+
+/// Adds up the time spent moving, i.e. the gaps between the records that end at a speed of at least
+/// `MOVING_SPEED_MIN_MS`. Pauses longer than `MAX_INTERPOLATION_GAP_SEC` don't count.
+///
+/// # Arguments
+///
+/// `records: &[FITRecord]` -- The records, in order.
+///
+/// # Returns
+///
+/// `Option<Duration>` -- The moving time, or `None` if no records have both a time and a speed.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn moving_time(records: &[FITRecord]) -> Option<Duration> {
+    let mut moving_ms = None;
+    for pair in records.windows(2) {
+        let (Some(start), Some(end), Some(speed)) =
+            (pair[0].timestamp, pair[1].timestamp, pair[1].speed)
+        else {
+            continue;
+        };
+        let gap_ms = (end - start).num_milliseconds();
+        let total = moving_ms.get_or_insert(0);
+        if gap_ms <= MAX_INTERPOLATION_GAP_SEC * 1_000 && speed.value >= MOVING_SPEED_MIN_MS {
+            *total += gap_ms;
+        }
+    }
+    moving_ms.map(|ms| Duration::from_secs_f64(ms as f64 / 1_000.0))
+}
+
+#[cfg(test)]
+/// Tests for the interpolation module
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    /// A record `secs` into the activity, at the distance and speed given.
+    fn record(secs: i64, distance: f64, speed: f64) -> FITRecord {
+        FITRecord {
+            timestamp: Local.timestamp_opt(1_600_000_000 + secs, 0).single(),
+            distance: Some(Length::new::<meter>(distance)),
+            speed: Some(Velocity::new::<meter_per_second>(speed)),
+            heartrate: Some(120),
+            power: Some(200),
+            ..FITRecord::default()
+        }
+    }
+
+    #[test]
+    /// Test filling in the gaps, leaving out the pauses
+    fn test_interpolate_records() {
+        let records = [
+            record(0, 0.0, 3.0),
+            record(4, 12.0, 3.0),
+            record(5, 15.0, 3.0),
+            record(65, 15.0, 0.0),
+        ];
+        let filled = interpolate_records(&records);
+
+        assert_eq!(filled.len(), 7);
+        assert!(filled[0].synthetic.is_none());
+        assert_eq!(filled[1].synthetic, Some(true));
+        assert!((filled[2].distance.unwrap().value - 6.0).abs() < f64::EPSILON);
+        assert_eq!(filled[2].heartrate, Some(120));
+        assert!(filled[2].power.is_none());
+        assert!(filled[4].synthetic.is_none());
+        assert!(filled[6].synthetic.is_none());
+
+        assert_eq!(moving_time(&filled), Some(Duration::from_secs_f64(5.0)));
+        assert!(moving_time(&[]).is_none());
+    }
+}
@@ -0,0 +1,118 @@
+//! Summarizes the laps recorded by the device, so FIT and TCX laps can be printed side by side in the same table.
+
+use crate::table::Table;
+use crate::units::{converted, unit_system, Measure, UnitSystem};
+use crate::Duration;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The numbers printed for each lap. Anything not recorded for the lap is `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LapSummary {
+    /// The number of the lap, starting at 1.
+    pub lap_num: usize,
+
+    /// The elapsed time of the lap.
+    pub duration: Option<Duration>,
+
+    /// The distance covered in meters.
+    pub distance_m: Option<f64>,
+
+    /// The average heart rate in beats per minute.
+    pub heartrate_avg_bpm: Option<f64>,
+
+    /// The average power in watts. TCX laps don't record the power.
+    pub power_avg_w: Option<f64>,
+}
+
+impl LapSummary {
+    /// The average pace of the lap, i.e. the time taken per kilometer or mile.
+    ///
+    /// # Arguments
+    ///
+    /// `units: UnitSystem` -- Whether the pace is per kilometer or mile.
+    ///
+    /// # Returns
+    ///
+    /// `Option<Duration>` -- The pace, or `None` if the lap has no time or distance.
+    #[must_use]
+    pub fn pace(&self, units: UnitSystem) -> Option<Duration> {
+        let secs = self.duration?.0.as_secs_f64();
+        let distance = self.distance_m?;
+        (secs > 0.0).then(|| units.pace(distance / secs)).flatten()
+    }
+}
+
+/// Prints the laps as a table to stdout. The distance is in the unit system of the run, and the pace is per kilometer
+/// unless the run is in miles.
+pub fn print_laps(laps: &[LapSummary]) {
+    if laps.is_empty() {
+        println!("\nNo laps. The activity has no laps recorded.");
+        return;
+    }
+
+    let units = unit_system();
+    let pace_units = units.unwrap_or(UnitSystem::Metric);
+    let distance_header = format!("Distance ({})", Measure::Distance.unit(units));
+    let pace_header = format!("Pace ({})", pace_units.pace_unit());
+    let mut table = Table::new(&[
+        "Lap",
+        "Time",
+        &distance_header,
+        &pace_header,
+        "HR (bpm)",
+        "Power (W)",
+    ]);
+
+    for lap in laps {
+        table.add_row(vec![
+            lap.lap_num.to_string(),
+            lap.duration
+                .map_or_else(String::new, |time| time.to_string()),
+            lap.distance_m.map_or_else(String::new, |meters| {
+                let decimals = if units.is_some() { 2 } else { 0 };
+                format!("{:.decimals$}", converted(Measure::Distance, meters))
+            }),
+            lap.pace(pace_units)
+                .map_or_else(String::new, |pace| pace.to_string()),
+            lap.heartrate_avg_bpm
+                .map_or_else(String::new, |hr| format!("{hr:.0}")),
+            lap.power_avg_w
+                .map_or_else(String::new, |power| format!("{power:.0}")),
+        ]);
+    }
+
+    println!("\nLaps:\n");
+    print!("{table}");
+}
+
+#[cfg(test)]
+/// Tests for the laps module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the pace of a lap, with and without the time and distance
+    fn test_pace() {
+        let lap = LapSummary {
+            lap_num: 1,
+            duration: Some(Duration::from_secs_f64(600.0)),
+            distance_m: Some(2_000.0),
+            ..LapSummary::default()
+        };
+        assert_eq!(
+            lap.pace(UnitSystem::Metric),
+            Some(Duration::from_secs_f64(300.0))
+        );
+        assert_eq!(
+            lap.pace(UnitSystem::Imperial).map(|pace| pace.0.as_secs()),
+            Some(482)
+        );
+
+        let standing = LapSummary {
+            distance_m: Some(0.0),
+            ..lap
+        };
+        assert!(standing.pace(UnitSystem::Metric).is_none());
+        assert!(LapSummary::default().pace(UnitSystem::Metric).is_none());
+    }
+}
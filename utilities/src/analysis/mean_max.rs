@@ -0,0 +1,427 @@
+//! Works out the mean-maximal curve of an activity: the best average power and heart rate held over durations from one
+//! second up to the length of the activity. The curves of many activities can be combined into a single curve holding
+//! the best of each, to follow the form over a season.
+//!
+//! The samples are spread out to one per second first, holding each value until the next sample, so the curve doesn't
+//! depend on how often the device records. The series is split at gaps longer than `MAX_GAP_SEC` (e.g. where the
+//! recording was paused, or a corrupt timestamp), and no average is taken across them.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::Write;
+
+use crate::columns::{ColumnWriter, CsvFile};
+use crate::precision::Rounded;
+use crate::Duration;
+
+/// Gaps between samples longer than this (in seconds) are left empty rather than filled with the previous value.
+pub const MAX_GAP_SEC: usize = 5;
+
+/// The durations shown when printing a curve, in seconds. Each is on the grid of `durations()`.
+const PRINTED_DURATIONS: [usize; 10] = [5, 15, 30, 60, 300, 600, 1_200, 1_800, 3_600, 7_200];
+
+/// A sample of the activity with what is needed for the curve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeanMaxSample {
+    /// When the sample was recorded.
+    pub time: DateTime<Local>,
+
+    /// The power in watts, if recorded.
+    pub power: Option<f64>,
+
+    /// The heart rate in beats per minute, if recorded.
+    pub heart_rate: Option<f64>,
+}
+
+/// A stretch of the activity without long gaps, with one value per second.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SecondsRun {
+    /// When the stretch starts, in seconds from the first sample.
+    pub start_sec: usize,
+
+    /// The value for each second of the stretch.
+    pub values: Vec<Option<f64>>,
+}
+
+impl SecondsRun {
+    /// The second after the end of the stretch, from the first sample.
+    fn end_sec(&self) -> usize {
+        self.start_sec + self.values.len()
+    }
+}
+
+/// The best averages held over a single duration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct MeanMax {
+    /// The length of the period in seconds.
+    #[serde(rename = "duration_sec")]
+    pub duration_sec: usize,
+
+    /// The best average power over the period in watts.
+    #[serde(rename = "power_avg_w")]
+    pub power_avg_w: Option<f64>,
+
+    /// The best average heart rate over the period in beats per minute.
+    #[serde(rename = "heartrate_avg_bpm")]
+    pub heartrate_avg_bpm: Option<f64>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the mean-maximal curve of the activity for the power and heart rate.
+///
+/// # Arguments
+///
+/// `samples: &[MeanMaxSample]` -- The samples of the activity in the order they were recorded.
+///
+/// # Returns
+///
+/// `Vec<MeanMax>` -- The best averages for each duration on the grid of `durations()`, up to the longest stretch
+/// without a long gap. Durations with neither a power nor a heart rate average are left out, so the curve is empty if
+/// neither was recorded.
+#[must_use]
+pub fn mean_max(samples: &[MeanMaxSample]) -> Vec<MeanMax> {
+    let power = per_second(samples, |sample| sample.power);
+    let heart_rate = per_second(samples, |sample| sample.heart_rate);
+    let longest = power
+        .iter()
+        .map(|run| run.values.len())
+        .max()
+        .unwrap_or_default();
+
+    durations(longest)
+        .zip(curve(&power, longest))
+        .zip(curve(&heart_rate, longest))
+        .filter(|((_, power), heart_rate)| power.is_some() || heart_rate.is_some())
+        .map(|((duration_sec, power_avg_w), heartrate_avg_bpm)| MeanMax {
+            duration_sec,
+            power_avg_w,
+            heartrate_avg_bpm,
+        })
+        .collect()
+}
+
+/// The durations the curve is worked out for, in seconds, up to the length given: every second up to a minute, every
+/// 5 seconds up to 10 minutes, every 30 seconds up to an hour and every 5 minutes after that. This keeps the work for
+/// long activities in check, while the durations are the same for every activity so the curves can be combined.
+fn durations(max_sec: usize) -> impl Iterator<Item = usize> {
+    std::iter::successors(Some(1_usize), |&duration| {
+        let step = if duration < 60 {
+            1
+        } else if duration < 600 {
+            5
+        } else if duration < 3_600 {
+            30
+        } else {
+            300
+        };
+        Some(duration + step)
+    })
+    .take_while(move |&duration| duration <= max_sec)
+}
+
+/// Combines the curves of several activities into one with the best power and heart rate for each duration, even if
+/// they come from different activities.
+///
+/// # Arguments
+///
+/// `curves: &[Vec<MeanMax>]` -- The curves to combine.
+///
+/// # Returns
+///
+/// `Vec<MeanMax>` -- The combined curve, ordered by duration.
+#[must_use]
+pub fn best_mean_max(curves: &[Vec<MeanMax>]) -> Vec<MeanMax> {
+    let mut best: BTreeMap<usize, MeanMax> = BTreeMap::new();
+    for point in curves.iter().flatten() {
+        best.entry(point.duration_sec)
+            .and_modify(|current| {
+                current.power_avg_w = max(current.power_avg_w, point.power_avg_w);
+                current.heartrate_avg_bpm = max(current.heartrate_avg_bpm, point.heartrate_avg_bpm);
+            })
+            .or_insert(*point);
+    }
+
+    best.into_values().collect()
+}
+
+/// The larger of two values, where either may be unknown.
+fn max(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        _ => a.or(b),
+    }
+}
+
+/// Spreads the values out to one per second, holding each value until the next sample, and splits them into stretches
+/// at gaps of `MAX_GAP_SEC` or longer. Samples recorded in the same second as the one before, or earlier, are skipped.
+pub(crate) fn per_second<F>(samples: &[MeanMaxSample], value: F) -> Vec<SecondsRun>
+where
+    F: Fn(&MeanMaxSample) -> Option<f64>,
+{
+    per_second_held(samples, MAX_GAP_SEC, value)
+}
+
+/// Like `per_second()`, holding the values over gaps shorter than `max_gap_sec` and splitting at longer ones. No
+/// seconds are stored for the gaps, so a corrupt timestamp far from the rest only starts a new stretch.
+pub(crate) fn per_second_held<F>(
+    samples: &[MeanMaxSample],
+    max_gap_sec: usize,
+    value: F,
+) -> Vec<SecondsRun>
+where
+    F: Fn(&MeanMaxSample) -> Option<f64>,
+{
+    let mut runs: Vec<SecondsRun> = Vec::new();
+    let Some(first) = samples.first() else {
+        return runs;
+    };
+
+    for sample in samples {
+        let Ok(second) = usize::try_from((sample.time - first.time).num_seconds()) else {
+            continue;
+        };
+        match runs.last_mut() {
+            Some(run) if second < run.end_sec() => continue,
+            // Hold the previous value over short gaps
+            Some(run) if second - run.end_sec() < max_gap_sec => {
+                let held = run.values.last().copied().flatten();
+                run.values
+                    .extend(std::iter::repeat_n(held, second - run.end_sec()));
+                run.values.push(value(sample));
+            }
+            _ => runs.push(SecondsRun {
+                start_sec: second,
+                values: vec![value(sample)],
+            }),
+        }
+    }
+
+    runs
+}
+
+/// The best average of the values over each duration of `durations()` up to `longest` seconds, in any of the
+/// stretches. Averages are only taken over periods without empty seconds.
+#[allow(clippy::cast_precision_loss)]
+fn curve(runs: &[SecondsRun], longest: usize) -> Vec<Option<f64>> {
+    let mut best = vec![None; durations(longest).count()];
+    for run in runs {
+        // The running totals of the values and of the empty seconds, so each average is a subtraction
+        let series = &run.values;
+        let mut sums = Vec::with_capacity(series.len() + 1);
+        let mut empty = Vec::with_capacity(series.len() + 1);
+        sums.push(0.0);
+        empty.push(0_usize);
+        for value in series {
+            sums.push(sums[sums.len() - 1] + value.unwrap_or_default());
+            empty.push(empty[empty.len() - 1] + usize::from(value.is_none()));
+        }
+
+        for (best, duration) in best.iter_mut().zip(durations(series.len())) {
+            let run_best = (duration..=series.len())
+                .filter(|&end| empty[end] == empty[end - duration])
+                .map(|end| (sums[end] - sums[end - duration]) / duration as f64)
+                .reduce(f64::max);
+            *best = max(*best, run_best);
+        }
+    }
+
+    best
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes the curve as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+///
+/// # Arguments
+///
+/// - `curve: &[MeanMax]` -- The curve to write.
+/// - `writer: W` -- Where the CSV is written.
+///
+/// # Errors
+///
+/// Serializing or writing the curve may fail.
+pub fn write_mean_max_csv<W: Write>(curve: &[MeanMax], writer: W) -> Result<(), Box<dyn Error>> {
+    let mut writer = ColumnWriter::new(writer, CsvFile::MeanMax);
+    writer.write_header()?;
+    for point in curve {
+        writer.serialize(Rounded(point))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the curve to a CSV file.
+///
+/// # Arguments
+///
+/// - `curve: &[MeanMax]` -- The curve to write.
+/// - `filename: &str` -- The CSV file to write, usually named after the activity file with `.mean_max.csv` added.
+///
+/// # Errors
+///
+/// Creating or writing the file may fail.
+#[cfg(feature = "fs")]
+pub fn export_mean_max_csv(curve: &[MeanMax], filename: &str) -> Result<(), Box<dyn Error>> {
+    log::trace!(
+        "mean_max::export_mean_max_csv() -- Writing {} durations to {filename}",
+        curve.len()
+    );
+    crate::output_naming::write_output(std::path::Path::new(filename), |file| {
+        write_mean_max_csv(curve, file)
+    })
+}
+
+/// Prints the curve at a few common durations as a table to stdout.
+#[allow(clippy::cast_possible_truncation)]
+pub fn print_mean_max(curve: &[MeanMax]) {
+    if curve.is_empty() {
+        println!("\nNo mean-maximal curve. The activity has no power or heart rate.");
+        return;
+    }
+
+    println!("\nMean-maximal power and heart rate:\n");
+    println!("Duration   Power     HR");
+    for point in curve
+        .iter()
+        .filter(|point| PRINTED_DURATIONS.contains(&point.duration_sec))
+    {
+        println!(
+            "{}  {:>6}  {:>5}",
+            Duration::from_millis_u64(point.duration_sec as u64 * 1_000),
+            point
+                .power_avg_w
+                .map_or_else(String::new, |power| format!("{power:.0} W")),
+            point
+                .heartrate_avg_bpm
+                .map_or_else(String::new, |hr| format!("{hr:.0}")),
+        );
+    }
+}
+
+#[cfg(test)]
+/// Tests for the mean_max module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A sample `secs` seconds into the activity.
+    fn sample(secs: i64, power: Option<f64>, heart_rate: Option<f64>) -> MeanMaxSample {
+        MeanMaxSample {
+            time: Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap()
+                + chrono::Duration::seconds(secs),
+            power,
+            heart_rate,
+        }
+    }
+
+    #[test]
+    /// Test the curve with samples every other second, held in between
+    fn test_mean_max() {
+        let samples = vec![
+            sample(0, Some(100.0), Some(120.0)),
+            sample(2, Some(300.0), Some(150.0)),
+            sample(4, Some(200.0), None),
+            sample(5, Some(100.0), Some(130.0)),
+        ];
+        let curve = mean_max(&samples);
+
+        // The power is 100, 100, 300, 300, 200, 100 once held
+        assert_eq!(curve.len(), 6);
+        assert_eq!(curve[0].duration_sec, 1);
+        assert_eq!(curve[0].power_avg_w, Some(300.0));
+        assert_eq!(curve[1].power_avg_w, Some(300.0));
+        assert_eq!(curve[2].power_avg_w, Some(800.0 / 3.0));
+        assert_eq!(curve[5].power_avg_w, Some(1_100.0 / 6.0));
+
+        // The heart rate is missing at 4 seconds
+        assert_eq!(curve[0].heartrate_avg_bpm, Some(150.0));
+        assert_eq!(curve[3].heartrate_avg_bpm, Some(135.0));
+        assert!(curve[4].heartrate_avg_bpm.is_none());
+
+        assert!(mean_max(&[]).is_empty());
+        assert!(mean_max(&[sample(0, None, None)]).is_empty());
+    }
+
+    #[test]
+    /// Test that no average is taken across a long gap
+    fn test_gap() {
+        let samples = vec![
+            sample(0, Some(200.0), None),
+            sample(1, Some(200.0), None),
+            sample(60, Some(100.0), None),
+        ];
+        let curve = mean_max(&samples);
+        assert_eq!(curve.len(), 2);
+        assert_eq!(curve[1].power_avg_w, Some(200.0));
+    }
+
+    #[test]
+    /// Test that a corrupt timestamp far from the rest only starts a new stretch, rather than a second for each
+    /// second in between
+    fn test_large_gap() {
+        let samples = vec![
+            sample(0, Some(200.0), Some(140.0)),
+            sample(1, Some(200.0), Some(140.0)),
+            sample(400_000_000, Some(900.0), Some(200.0)),
+            sample(400_000_001, Some(900.0), None),
+            sample(400_000_002, Some(900.0), None),
+        ];
+        let runs = per_second(&samples, |sample| sample.power);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].start_sec, 400_000_000);
+        assert_eq!(runs[1].values.len(), 3);
+
+        let curve = mean_max(&samples);
+        assert_eq!(curve.len(), 3);
+        assert_eq!(curve[2].power_avg_w, Some(900.0));
+        assert_eq!(curve[1].heartrate_avg_bpm, Some(140.0));
+    }
+
+    #[test]
+    /// Test the durations of the curve of a long ride
+    fn test_durations() {
+        let samples: Vec<MeanMaxSample> = (0..4 * 3_600)
+            .map(|sec| sample(sec, Some(200.0), None))
+            .collect();
+        let curve = mean_max(&samples);
+        let durations: Vec<usize> = curve.iter().map(|point| point.duration_sec).collect();
+
+        assert_eq!(durations.len(), 60 + 108 + 100 + 36);
+        assert_eq!(durations[..3], [1, 2, 3]);
+        assert!(durations.contains(&7_200));
+        assert_eq!(durations.last(), Some(&14_400));
+        assert!(PRINTED_DURATIONS
+            .iter()
+            .all(|duration| durations.contains(duration)));
+        assert!(curve.iter().all(|point| point.power_avg_w == Some(200.0)));
+    }
+
+    #[test]
+    /// Test combining the curves of two activities
+    fn test_best_mean_max() {
+        let first = mean_max(&[sample(0, Some(300.0), Some(120.0))]);
+        let second = mean_max(&[
+            sample(0, Some(200.0), Some(160.0)),
+            sample(1, Some(200.0), Some(160.0)),
+        ]);
+        let best = best_mean_max(&[first, second]);
+
+        assert_eq!(best.len(), 2);
+        assert_eq!(best[0].power_avg_w, Some(300.0));
+        assert_eq!(best[0].heartrate_avg_bpm, Some(160.0));
+        assert_eq!(best[1].power_avg_w, Some(200.0));
+    }
+
+    #[test]
+    /// Test writing the curve to CSV
+    fn test_write_mean_max_csv() {
+        let mut csv = Vec::new();
+        write_mean_max_csv(&mean_max(&[sample(0, Some(250.0), None)]), &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        assert!(csv.starts_with("duration_sec,power_avg_w,heartrate_avg_bpm"));
+        assert_eq!(csv.lines().count(), 2);
+    }
+}
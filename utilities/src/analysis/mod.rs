@@ -0,0 +1,24 @@
+//! Contains computations that derive additional information from the records/waypoints/trackpoints of an activity.
+
+pub mod compare;
+pub mod decoupling;
+pub mod derived;
+pub mod distance_check;
+pub mod gps_outliers;
+pub mod heat;
+pub mod hr_scatter;
+pub mod hr_zones;
+pub mod indoor;
+pub mod interpolation;
+pub mod laps;
+pub mod mean_max;
+pub mod overlap;
+pub mod pacing;
+pub mod power;
+pub mod race;
+pub mod recording;
+pub mod running;
+pub mod series;
+pub mod splits;
+pub mod track_stats;
+pub mod validate;
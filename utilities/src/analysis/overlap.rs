@@ -0,0 +1,194 @@
+//! Finds activities that overlap in time, e.g. the same ride recorded on both a watch and a bike computer. Adding up
+//! the totals of both would count the distance and time twice.
+
+use chrono::{DateTime, Local};
+use std::path::PathBuf;
+
+use crate::Duration;
+
+/// When an activity took place, and what recorded it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivityWindow {
+    /// The file the activity was read from.
+    pub filename: PathBuf,
+
+    /// The device that recorded the activity, e.g. its serial number, if known.
+    pub device: Option<String>,
+
+    /// When the activity started.
+    pub start: DateTime<Local>,
+
+    /// How long the activity lasted.
+    pub duration: Duration,
+}
+
+impl ActivityWindow {
+    /// Creates a window if both the start time and the duration are known.
+    ///
+    /// # Arguments
+    ///
+    /// - `filename: impl Into<PathBuf>` -- The file the activity was read from.
+    /// - `device: Option<&str>` -- The device that recorded the activity, if known.
+    /// - `start: Option<DateTime<Local>>` -- When the activity started.
+    /// - `duration: Option<Duration>` -- How long the activity lasted.
+    #[must_use]
+    pub fn new(
+        filename: impl Into<PathBuf>,
+        device: Option<&str>,
+        start: Option<DateTime<Local>>,
+        duration: Option<Duration>,
+    ) -> Option<Self> {
+        Some(Self {
+            filename: filename.into(),
+            device: device.map(ToString::to_string),
+            start: start?,
+            duration: duration?,
+        })
+    }
+
+    /// When the activity ended. Durations too long to add to the start time are treated as zero.
+    #[must_use]
+    pub fn end(&self) -> DateTime<Local> {
+        chrono::Duration::from_std(self.duration.0)
+            .ok()
+            .and_then(|duration| self.start.checked_add_signed(duration))
+            .unwrap_or(self.start)
+    }
+}
+
+/// Two activities that took place at the same time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    /// The activity that started first.
+    pub first: PathBuf,
+
+    /// The activity that started second.
+    pub second: PathBuf,
+
+    /// How long the activities overlap.
+    pub duration: Duration,
+
+    /// Whether the activities were recorded on the same device. `None` if either device is unknown.
+    pub same_device: Option<bool>,
+}
+
+impl Overlap {
+    /// Whether the file is one of the two overlapping activities.
+    #[must_use]
+    pub fn involves(&self, filename: &std::path::Path) -> bool {
+        self.first == filename || self.second == filename
+    }
+}
+
+impl std::fmt::Display for Overlap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} and {} overlap by {}",
+            self.first.display(),
+            self.second.display(),
+            self.duration
+        )?;
+        match self.same_device {
+            Some(true) => write!(f, " on the same device, and may be duplicates.")?,
+            Some(false) => write!(f, " on different devices.")?,
+            None => write!(f, ".")?,
+        }
+        write!(f, " Adding up both would count the distance twice.")
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Finds the activities that overlap in time. Activities that only touch, i.e. one starts when the other ends, don't
+/// overlap.
+///
+/// # Arguments
+///
+/// `windows: &[ActivityWindow]` -- When each activity took place, in any order.
+///
+/// # Returns
+///
+/// `Vec<Overlap>` -- Each pair of activities that overlap, ordered by the start of the first activity.
+#[must_use]
+pub fn find_overlaps(windows: &[ActivityWindow]) -> Vec<Overlap> {
+    let mut sorted: Vec<&ActivityWindow> = windows.iter().collect();
+    sorted.sort_by_key(|window| window.start);
+
+    let mut overlaps = Vec::new();
+    for (i, first) in sorted.iter().enumerate() {
+        let first_end = first.end();
+
+        // Only the activities starting before this one ends can overlap it
+        for second in sorted[i + 1..]
+            .iter()
+            .take_while(|second| second.start < first_end)
+        {
+            let end = first_end.min(second.end());
+            if end <= second.start {
+                continue;
+            }
+            overlaps.push(Overlap {
+                first: first.filename.clone(),
+                second: second.filename.clone(),
+                duration: Duration::between(&second.start, &end),
+                same_device: first
+                    .device
+                    .as_ref()
+                    .zip(second.device.as_ref())
+                    .map(|(a, b)| a == b),
+            });
+        }
+    }
+
+    overlaps
+}
+
+#[cfg(test)]
+/// Tests for the overlap module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A window starting `start` minutes past midnight and lasting `minutes`.
+    fn window(name: &str, device: Option<&str>, start: i64, minutes: u64) -> ActivityWindow {
+        let midnight = Local.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        ActivityWindow::new(
+            name,
+            device,
+            Some(midnight + chrono::Duration::minutes(start)),
+            Some(Duration::from_millis_u64(minutes * 60_000)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    /// Test finding the overlapping activities
+    fn test_find_overlaps() {
+        let windows = vec![
+            window("bike.fit", Some("edge"), 60, 90),
+            window("watch.fit", Some("fenix"), 50, 120),
+            window("evening.fit", Some("fenix"), 600, 30),
+            window("touching.fit", Some("fenix"), 630, 10),
+            window("copy.fit", Some("fenix"), 610, 5),
+            window("unknown.gpx", None, 0, 55),
+        ];
+        let overlaps = find_overlaps(&windows);
+
+        assert_eq!(overlaps.len(), 3);
+        assert_eq!(overlaps[0].first, PathBuf::from("unknown.gpx"));
+        assert_eq!(overlaps[0].duration, Duration::from_millis_u64(5 * 60_000));
+        assert!(overlaps[0].same_device.is_none());
+        assert_eq!(overlaps[1].second, PathBuf::from("bike.fit"));
+        assert_eq!(overlaps[1].duration, Duration::from_millis_u64(90 * 60_000));
+        assert_eq!(overlaps[1].same_device, Some(false));
+        assert_eq!(overlaps[2].same_device, Some(true));
+        assert!(overlaps[2].involves(std::path::Path::new("copy.fit")));
+        assert!(overlaps[1]
+            .to_string()
+            .starts_with("watch.fit and bike.fit overlap by 01:30:00 on different devices."));
+
+        assert!(
+            ActivityWindow::new("no_time.gpx", None, None, Some(Duration::default())).is_none()
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Compares the speed in the first and second half of an activity, to find out whether it was run as a negative split
+//! (faster second half), an even split or a positive split (slower second half). Handy for race files.
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::splits::SplitPoint;
+
+/// Halves whose times differ by no more than this (in percent) are considered an even split.
+pub const EVEN_SPLIT_PCT: f64 = 1.0;
+
+/// Activities shorter than this (in meters) aren't analysed, since there is no meaningful pacing to speak of.
+pub const MIN_PACING_DISTANCE_M: f64 = 200.0;
+
+/// How the second half of an activity compares with the first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PacingSplit {
+    /// The second half was faster.
+    Negative,
+    /// Both halves took about the same time.
+    Even,
+    /// The second half was slower.
+    Positive,
+}
+
+impl std::fmt::Display for PacingSplit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Negative => write!(f, "negative"),
+            Self::Even => write!(f, "even"),
+            Self::Positive => write!(f, "positive"),
+        }
+    }
+}
+
+/// The pacing of an activity, split at half the distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pacing {
+    /// The average speed over the first half of the distance in meters per second.
+    pub first_half_speed: f64,
+
+    /// The average speed over the second half of the distance in meters per second.
+    pub second_half_speed: f64,
+
+    /// How much longer the second half took than the first, in percent of the first half. Negative for a negative
+    /// split.
+    pub index_pct: f64,
+
+    /// Whether the activity was a negative, even or positive split.
+    pub split: PacingSplit,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the pacing of the activity by comparing the time taken to cover each half of the distance. The time at
+/// the halfway point is interpolated between the points either side.
+///
+/// # Arguments
+///
+/// `points: &[SplitPoint]` -- The points of the activity in the order they were recorded.
+///
+/// # Returns
+///
+/// `Option<Pacing>` -- The pacing, or `None` if the activity is too short or either half took no time.
+#[must_use]
+pub fn pacing(points: &[SplitPoint]) -> Option<Pacing> {
+    let (first, last) = (points.first()?, points.last()?);
+    let total = points
+        .iter()
+        .map(|point| point.distance)
+        .fold(f64::NEG_INFINITY, f64::max)
+        - first.distance;
+    if total < MIN_PACING_DISTANCE_M {
+        return None;
+    }
+
+    // Find the points either side of the halfway mark. Distances that go backwards are glitches, so the furthest
+    // distance so far is used.
+    let halfway = first.distance + total / 2.0;
+    let mut furthest = first.distance;
+    let mut halfway_time = None;
+    for (prev, curr) in points.iter().zip(points.iter().skip(1)) {
+        let prev_distance = furthest;
+        furthest = furthest.max(curr.distance);
+        if furthest >= halfway {
+            let fraction = (halfway - prev_distance) / (furthest - prev_distance);
+            halfway_time =
+                Some(seconds_between(first, prev) + seconds_between(prev, curr) * fraction);
+            break;
+        }
+    }
+
+    let first_half_sec = halfway_time?;
+    let second_half_sec = seconds_between(first, last) - first_half_sec;
+    if first_half_sec <= 0.0 || second_half_sec <= 0.0 {
+        return None;
+    }
+
+    let index_pct = (second_half_sec - first_half_sec) / first_half_sec * 100.0;
+    let split = if index_pct < -EVEN_SPLIT_PCT {
+        PacingSplit::Negative
+    } else if index_pct > EVEN_SPLIT_PCT {
+        PacingSplit::Positive
+    } else {
+        PacingSplit::Even
+    };
+
+    Some(Pacing {
+        first_half_speed: total / 2.0 / first_half_sec,
+        second_half_speed: total / 2.0 / second_half_sec,
+        index_pct,
+        split,
+    })
+}
+
+/// The number of seconds from one point to the next.
+#[allow(clippy::cast_precision_loss)]
+fn seconds_between(from: &SplitPoint, to: &SplitPoint) -> f64 {
+    (to.time - from.time).num_milliseconds() as f64 / 1000.0
+}
+
+#[cfg(test)]
+/// Tests for the pacing module
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    /// A point `secs` seconds into the activity.
+    fn point(secs: i64, distance: f64) -> SplitPoint {
+        SplitPoint {
+            time: Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap()
+                + chrono::Duration::seconds(secs),
+            distance,
+            heart_rate: None,
+            altitude: None,
+        }
+    }
+
+    #[test]
+    /// Test telling negative, even and positive splits apart
+    fn test_pacing() {
+        // 10 km, with the halfway mark between two points
+        let negative = pacing(&[
+            point(0, 0.0),
+            point(1_000, 4_000.0),
+            point(2_000, 6_000.0),
+            point(2_400, 10_000.0),
+        ])
+        .unwrap();
+        assert_eq!(negative.split, PacingSplit::Negative);
+        assert!((negative.first_half_speed - 10.0 / 3.0).abs() < 0.01);
+        assert!((negative.index_pct + 40.0).abs() < 0.01);
+
+        let even = pacing(&[point(0, 0.0), point(1_000, 5_000.0), point(2_005, 10_000.0)]).unwrap();
+        assert_eq!(even.split, PacingSplit::Even);
+
+        let positive = pacing(&[
+            point(0, 0.0),
+            point(1_000, 5_000.0),
+            point(1_100, 4_900.0),
+            point(2_500, 10_000.0),
+        ])
+        .unwrap();
+        assert_eq!(positive.split, PacingSplit::Positive);
+        assert!((positive.second_half_speed - 10.0 / 3.0).abs() < 0.01);
+        assert_eq!(positive.split.to_string(), "positive");
+
+        assert!(pacing(&[point(0, 0.0), point(60, 150.0)]).is_none());
+        assert!(pacing(&[]).is_none());
+    }
+}
@@ -0,0 +1,165 @@
+//! Works out the training load of an activity from the power: the Normalized Power (NP), the Intensity Factor (IF)
+//! and the Training Stress Score (TSS).
+//!
+//! The Normalized Power is the fourth root of the average of the 30 second rolling average power raised to the fourth
+//! power, which weighs the hard efforts of a varied ride more than the plain average does. The Intensity Factor is
+//! the Normalized Power in parts of the Functional Threshold Power (FTP), and an hour at FTP gives a TSS of 100.
+//!
+//! The FTP is set for the run using `Settings::set_ftp()`. Without it, the threshold power recorded in the file is
+//! used, if any.
+
+use crate::analysis::mean_max::{per_second, MeanMaxSample, SecondsRun};
+use crate::Settings;
+
+/// The length of the rolling average (in seconds) the Normalized Power is worked out from.
+pub const NP_WINDOW_SEC: usize = 30;
+
+/// The training load of an activity.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PowerMetrics {
+    /// The Normalized Power in watts.
+    pub normalized_power: Option<f64>,
+
+    /// The Normalized Power in parts of the FTP.
+    pub intensity_factor: Option<f64>,
+
+    /// The Training Stress Score, where an hour at FTP is 100.
+    pub training_stress_score: Option<f64>,
+}
+
+impl Settings {
+    /// Sets the Functional Threshold Power.
+    ///
+    /// # Arguments
+    ///
+    /// `ftp: Option<f64>` -- The FTP in watts, or `None` to use the threshold power recorded in each file.
+    pub fn set_ftp(&mut self, ftp: Option<f64>) {
+        self.ftp = ftp.filter(|ftp| ftp.is_finite() && *ftp > 0.0);
+    }
+}
+
+/// The FTP set for this run, if any.
+pub(crate) fn ftp() -> Option<f64> {
+    Settings::current().ftp
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the Normalized Power, Intensity Factor and Training Stress Score of the activity.
+///
+/// # Arguments
+///
+/// - `samples: &[MeanMaxSample]` -- The power samples in the order they were recorded.
+/// - `ftp: Option<f64>` -- The Functional Threshold Power in watts.
+///
+/// # Returns
+///
+/// `PowerMetrics` -- The training load. The Intensity Factor and TSS are left out without an FTP, and all three are
+/// left out if the activity has no power, or less than `NP_WINDOW_SEC` of it between pauses.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn power_metrics(samples: &[MeanMaxSample], ftp: Option<f64>) -> PowerMetrics {
+    if samples.iter().all(|sample| sample.power.is_none()) {
+        return PowerMetrics::default();
+    }
+
+    // The power is held over short gaps only, and the rolling averages start over after longer ones
+    let runs = per_second(samples, |sample| sample.power);
+    let Some(normalized_power) = normalized_power(&runs) else {
+        return PowerMetrics::default();
+    };
+    let ftp = ftp.filter(|ftp| *ftp > 0.0);
+    let intensity_factor = ftp.map(|ftp| normalized_power / ftp);
+    let secs = runs
+        .iter()
+        .flat_map(|run| run.values.iter().flatten())
+        .count() as f64;
+
+    PowerMetrics {
+        normalized_power: Some(normalized_power),
+        intensity_factor,
+        training_stress_score: intensity_factor
+            .map(|intensity| secs / 3_600.0 * intensity * intensity * 100.0),
+    }
+}
+
+/// The Normalized Power of the stretches of power with one value per second. Rolling averages that reach into a pause
+/// are left out.
+#[allow(clippy::cast_precision_loss)]
+fn normalized_power(runs: &[SecondsRun]) -> Option<f64> {
+    let rolling: Vec<f64> = runs
+        .iter()
+        .flat_map(|run| run.values.windows(NP_WINDOW_SEC))
+        .filter_map(|window| window.iter().copied().sum::<Option<f64>>())
+        .map(|sum| sum / NP_WINDOW_SEC as f64)
+        .collect();
+    if rolling.is_empty() {
+        return None;
+    }
+
+    let mean = rolling.iter().map(|power| power.powi(4)).sum::<f64>() / rolling.len() as f64;
+    Some(mean.powf(0.25))
+}
+
+#[cfg(test)]
+/// Tests for the power module
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    /// Samples every second for `secs` seconds at the power given, starting `start` seconds into the activity.
+    fn samples(start: i64, secs: i64, power: f64) -> Vec<MeanMaxSample> {
+        (start..start + secs)
+            .map(|sec| MeanMaxSample {
+                time: Local.timestamp_opt(1_600_000_000 + sec, 0).unwrap(),
+                power: Some(power),
+                heart_rate: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Test an hour at FTP, and a ride switching between hard and easy
+    fn test_power_metrics() {
+        let steady = power_metrics(&samples(0, 3_600, 250.0), Some(250.0));
+        assert!((steady.normalized_power.unwrap() - 250.0).abs() < 1e-9);
+        assert!((steady.intensity_factor.unwrap() - 1.0).abs() < 1e-9);
+        assert!((steady.training_stress_score.unwrap() - 100.0).abs() < 1e-9);
+
+        // Ten minutes hard and ten easy weigh the hard part more than the average of 200 W
+        let mut varied = samples(0, 600, 300.0);
+        varied.extend(samples(600, 600, 100.0));
+        let varied = power_metrics(&varied, None);
+        assert!(varied.normalized_power.unwrap() > 240.0);
+        assert!(varied.intensity_factor.is_none());
+        assert!(varied.training_stress_score.is_none());
+
+        assert_eq!(
+            power_metrics(&samples(0, 20, 250.0), Some(250.0)),
+            PowerMetrics::default()
+        );
+    }
+
+    #[test]
+    /// Test that a corrupt timestamp far from the rest doesn't count as time at the power, and that records without
+    /// power give no training load
+    fn test_power_metrics_gap() {
+        let mut gap = samples(0, 3_600, 250.0);
+        gap.extend(samples(400_000_000, 60, 250.0));
+        let metrics = power_metrics(&gap, Some(250.0));
+        assert!((metrics.normalized_power.unwrap() - 250.0).abs() < 1e-9);
+        assert!((metrics.training_stress_score.unwrap() - 3_660.0 / 36.0).abs() < 1e-9);
+
+        let no_power: Vec<MeanMaxSample> = samples(0, 60, 0.0)
+            .into_iter()
+            .map(|sample| MeanMaxSample {
+                power: None,
+                heart_rate: Some(120.0),
+                ..sample
+            })
+            .collect();
+        assert_eq!(
+            power_metrics(&no_power, Some(250.0)),
+            PowerMetrics::default()
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Reports the time at the common race distances (5k, 10k, half and full marathon) and predicts the finish time for
+//! each of them from the activity, using Riegel's formula.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::analysis::splits::SplitPoint;
+use crate::Duration;
+
+/// The exponent in Riegel's formula, `T2 = T1 * (D2 / D1) ^ 1.06`.
+pub const RIEGEL_EXPONENT: f64 = 1.06;
+
+/// Activities shorter than this (in meters) are too short to predict race times from.
+pub const MIN_PREDICTION_DISTANCE_M: f64 = 1_000.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The race distances that can be reported on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaceDistance {
+    /// 5 kilometers.
+    FiveK,
+    /// 10 kilometers.
+    TenK,
+    /// Half marathon, 21.0975 kilometers.
+    HalfMarathon,
+    /// Marathon, 42.195 kilometers.
+    Marathon,
+}
+
+impl RaceDistance {
+    /// All the race distances, shortest first.
+    pub const ALL: [Self; 4] = [Self::FiveK, Self::TenK, Self::HalfMarathon, Self::Marathon];
+
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 4] = ["5k", "10k", "half", "marathon"];
+
+    /// The length of the race in meters.
+    #[must_use]
+    pub const fn meters(self) -> f64 {
+        match self {
+            Self::FiveK => 5_000.0,
+            Self::TenK => 10_000.0,
+            Self::HalfMarathon => 21_097.5,
+            Self::Marathon => 42_195.0,
+        }
+    }
+}
+
+impl FromStr for RaceDistance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "5k" => Ok(Self::FiveK),
+            "10k" => Ok(Self::TenK),
+            "half" | "half-marathon" => Ok(Self::HalfMarathon),
+            "marathon" | "full" => Ok(Self::Marathon),
+            _ => Err(format!(
+                "Unknown race distance {s}. Use 5k, 10k, half or marathon."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RaceDistance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FiveK => write!(f, "5k"),
+            Self::TenK => write!(f, "10k"),
+            Self::HalfMarathon => write!(f, "Half marathon"),
+            Self::Marathon => write!(f, "Marathon"),
+        }
+    }
+}
+
+/// How the activity measures up against a race distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaceMark {
+    /// The race distance.
+    pub distance: RaceDistance,
+
+    /// The time from the start of the activity until the race distance was reached, if it was.
+    pub split: Option<Duration>,
+
+    /// The predicted finish time for the race distance, from the whole activity.
+    pub predicted: Option<Duration>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Finds the time at each of the race distances, and predicts the finish time for each of them with Riegel's formula
+/// using the distance and time of the whole activity. The time where each distance is reached is interpolated between
+/// the points either side.
+///
+/// # Arguments
+///
+/// - `points: &[SplitPoint]` -- The points of the activity in the order they were recorded.
+/// - `targets: &[RaceDistance]` -- The race distances to report on.
+///
+/// # Returns
+///
+/// `Vec<RaceMark>` -- One mark per race distance, in the order given. The prediction is `None` if the activity is
+/// shorter than `MIN_PREDICTION_DISTANCE_M`.
+#[must_use]
+pub fn race_report(points: &[SplitPoint], targets: &[RaceDistance]) -> Vec<RaceMark> {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return targets
+            .iter()
+            .map(|&distance| RaceMark {
+                distance,
+                split: None,
+                predicted: None,
+            })
+            .collect();
+    };
+
+    // Distances that go backwards are glitches, so the furthest distance so far is used
+    let mut furthest = Vec::with_capacity(points.len());
+    let mut max_distance = first.distance;
+    for point in points {
+        max_distance = max_distance.max(point.distance);
+        furthest.push(max_distance - first.distance);
+    }
+    let total_distance = max_distance - first.distance;
+    let total_sec = seconds_between(first, last);
+
+    targets
+        .iter()
+        .map(|&distance| {
+            let target = distance.meters();
+            let split = furthest.iter().position(|&d| d >= target).map(|i| {
+                let prev = i.saturating_sub(1);
+                let covered = furthest[i] - furthest[prev];
+                let fraction = if covered > 0.0 {
+                    (target - furthest[prev]) / covered
+                } else {
+                    0.0
+                };
+                let sec = seconds_between(&points[prev], &points[i])
+                    .mul_add(fraction, seconds_between(first, &points[prev]));
+                Duration::from_secs_f64(sec)
+            });
+            let predicted =
+                (total_distance >= MIN_PREDICTION_DISTANCE_M && total_sec > 0.0).then(|| {
+                    Duration::from_secs_f64(
+                        total_sec * (target / total_distance).powf(RIEGEL_EXPONENT),
+                    )
+                });
+
+            RaceMark {
+                distance,
+                split,
+                predicted,
+            }
+        })
+        .collect()
+}
+
+/// The number of seconds from one point to the next.
+#[allow(clippy::cast_precision_loss)]
+fn seconds_between(from: &SplitPoint, to: &SplitPoint) -> f64 {
+    (to.time - from.time).num_milliseconds() as f64 / 1000.0
+}
+
+/// Prints the race report as a table to stdout.
+pub fn print_race_report(marks: &[RaceMark]) {
+    println!("\nRace distances:\n");
+    println!("Distance          Reached at   Predicted");
+    for mark in marks {
+        println!(
+            "{:16}  {:>10}  {:>10}",
+            mark.distance.to_string(),
+            mark.split
+                .map_or_else(|| "-".to_string(), |d| d.to_string()),
+            mark.predicted
+                .map_or_else(|| "-".to_string(), |d| d.to_string()),
+        );
+    }
+}
+
+#[cfg(test)]
+/// Tests for the race module
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    /// A point `secs` seconds into the activity.
+    fn point(secs: i64, distance: f64) -> SplitPoint {
+        SplitPoint {
+            time: Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap()
+                + chrono::Duration::seconds(secs),
+            distance,
+            heart_rate: None,
+            altitude: None,
+        }
+    }
+
+    #[test]
+    /// Test the times at the race distances and the predictions
+    fn test_race_report() {
+        // 10 km in 50 minutes, evenly paced
+        let points: Vec<SplitPoint> = (0..=100)
+            .map(|i| point(i * 30, f64::from(i32::try_from(i).unwrap()) * 100.0))
+            .collect();
+        let marks = race_report(&points, &RaceDistance::ALL);
+
+        assert_eq!(marks.len(), 4);
+        assert_eq!(marks[0].split, Some(Duration::from_secs_f64(1_500.0)));
+        assert_eq!(marks[1].split, Some(Duration::from_secs_f64(3_000.0)));
+        assert!(marks[2].split.is_none());
+        assert_eq!(marks[1].predicted, Some(Duration::from_secs_f64(3_000.0)));
+
+        // Riegel: 3000 s * 4.2195 ^ 1.06
+        let marathon = marks[3].predicted.unwrap().0.as_secs_f64();
+        assert!((marathon - 3_000.0 * 4.2195_f64.powf(1.06)).abs() < 1.0);
+
+        assert!(race_report(&points[..3], &[RaceDistance::FiveK])[0]
+            .predicted
+            .is_none());
+        assert!(race_report(&[], &[RaceDistance::TenK])[0].split.is_none());
+    }
+
+    #[test]
+    /// Test reading the race distances
+    fn test_race_distance() {
+        assert_eq!(
+            "HALF".parse::<RaceDistance>(),
+            Ok(RaceDistance::HalfMarathon)
+        );
+        assert_eq!("5k".parse::<RaceDistance>(), Ok(RaceDistance::FiveK));
+        assert!("15k".parse::<RaceDistance>().is_err());
+        assert_eq!(RaceDistance::Marathon.to_string(), "Marathon");
+    }
+}
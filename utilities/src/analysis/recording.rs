@@ -0,0 +1,163 @@
+//! Works out how often the device recorded, i.e. every second or "smart recording" where the device only records when
+//! something changes. This affects every statistic derived from the records, so it is worth knowing.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Activities where at least this share (in percent) of the intervals are one second are considered to be recorded
+/// every second.
+pub const EVERY_SECOND_MIN_PCT: f64 = 90.0;
+
+/// How the device recorded the activity.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingMode {
+    /// A record every second.
+    EverySecond,
+    /// Records only when something changes, typically every few seconds.
+    Smart,
+}
+
+impl std::fmt::Display for RecordingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EverySecond => write!(f, "every second"),
+            Self::Smart => write!(f, "smart"),
+        }
+    }
+}
+
+/// The recording interval of an activity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordingInterval {
+    /// The median time between two records in seconds.
+    pub median_sec: f64,
+
+    /// The share of the intervals that are one second, in percent.
+    pub every_second_pct: f64,
+
+    /// Whether the device recorded every second or used smart recording.
+    pub mode: RecordingMode,
+}
+
+/// Collects the time between the records one record at a time, so the recording interval can be found without holding
+/// all the records in memory. The intervals are rounded to whole seconds and counted.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingIntervals {
+    /// The time of the previous record.
+    last: Option<DateTime<Local>>,
+
+    /// The number of intervals of each length in whole seconds.
+    counts: BTreeMap<i64, usize>,
+}
+
+impl RecordingIntervals {
+    /// Adds the time of the next record. Records without a time, and records at the same time as the previous one,
+    /// are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// `time: Option<DateTime<Local>>` -- The time of the record.
+    pub fn add(&mut self, time: Option<DateTime<Local>>) {
+        let Some(time) = time else {
+            return;
+        };
+        if let Some(last) = self.last {
+            let seconds = ((time - last).num_milliseconds() + 500) / 1000;
+            if seconds > 0 {
+                *self.counts.entry(seconds).or_default() += 1;
+            }
+        }
+        self.last = Some(time);
+    }
+
+    /// The recording interval, or `None` if there were fewer than two records with a time.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn summary(&self) -> Option<RecordingInterval> {
+        let total: usize = self.counts.values().sum();
+        if total == 0 {
+            return None;
+        }
+
+        // The median is the first interval length where half the intervals have been counted
+        let mut counted = 0;
+        let median = self.counts.iter().find_map(|(&seconds, &count)| {
+            counted += count;
+            (counted * 2 >= total).then_some(seconds)
+        })?;
+
+        let every_second_pct =
+            self.counts.get(&1).copied().unwrap_or_default() as f64 / total as f64 * 100.0;
+        let mode = if every_second_pct >= EVERY_SECOND_MIN_PCT {
+            RecordingMode::EverySecond
+        } else {
+            RecordingMode::Smart
+        };
+
+        Some(RecordingInterval {
+            median_sec: median as f64,
+            every_second_pct,
+            mode,
+        })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Finds the recording interval from the times of the records.
+///
+/// # Arguments
+///
+/// `times: I` -- The times of the records in the order they were recorded. Missing times are skipped.
+///
+/// # Returns
+///
+/// `Option<RecordingInterval>` -- The recording interval, or `None` if there were fewer than two records with a time.
+#[must_use]
+pub fn recording_interval<I>(times: I) -> Option<RecordingInterval>
+where
+    I: IntoIterator<Item = Option<DateTime<Local>>>,
+{
+    let mut intervals = RecordingIntervals::default();
+    for time in times {
+        intervals.add(time);
+    }
+    intervals.summary()
+}
+
+#[cfg(test)]
+/// Tests for the recording module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// The times `secs` seconds into the activity.
+    fn times(secs: &[i64]) -> Vec<Option<DateTime<Local>>> {
+        let start = Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap();
+        secs.iter()
+            .map(|s| Some(start + chrono::Duration::seconds(*s)))
+            .collect()
+    }
+
+    #[test]
+    /// Test telling every second and smart recording apart
+    fn test_recording_interval() {
+        // Every second, with a pause and a duplicate
+        let mut secs: Vec<i64> = (0..100).collect();
+        secs.extend([99, 160, 161]);
+        let every_second = recording_interval(times(&secs)).unwrap();
+        assert_eq!(every_second.mode, RecordingMode::EverySecond);
+        assert!((every_second.median_sec - 1.0).abs() < f64::EPSILON);
+        assert!((every_second.every_second_pct - 100.0 * 100.0 / 101.0).abs() < 0.01);
+
+        let smart = recording_interval(times(&[0, 1, 4, 8, 13, 16, 17, 23])).unwrap();
+        assert_eq!(smart.mode, RecordingMode::Smart);
+        assert!((smart.median_sec - 3.0).abs() < f64::EPSILON);
+        assert_eq!(smart.mode.to_string(), "smart");
+
+        let mut untimed = times(&[0]);
+        untimed.push(None);
+        assert!(recording_interval(untimed).is_none());
+    }
+}
@@ -0,0 +1,392 @@
+//! Works out the grade-adjusted pace and the time spent in each pace zone of runs, walks and hikes from the points of
+//! the activity.
+//!
+//! The grade-adjusted pace is the pace the same effort would have given on the flat. Running uphill costs more energy
+//! per meter and running gently downhill less, as measured by Minetti et al. (2002). The grade is worked out over
+//! `GRADE_WINDOW_M` so the noise in the altitude doesn't count as hills.
+//!
+//! The pace zones are set once for the whole run using `Settings::set_pace_zones()`, either from the threshold pace or
+//! as the paces where each zone starts. Without it, there are no pace zones.
+
+use chrono::{DateTime, Local};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::analysis::splits::SplitPoint;
+use crate::table::Table;
+use crate::units::unit_system;
+use crate::{Duration, Settings, UnitSystem};
+
+/// The grade between two points is worked out over at least this distance (in meters) around them. The altitude from
+/// GPS is too noisy to use over shorter distances.
+pub const GRADE_WINDOW_M: f64 = 100.0;
+
+/// Steeper grades (as a fraction, up or down) are counted as this steep, since the energy cost isn't known beyond it.
+pub const MAX_GRADE: f64 = 0.45;
+
+/// The energy cost of running on the flat in J/kg/m.
+const FLAT_COST: f64 = 3.6;
+
+/// Where zones 1 to 4 start, in percent of the threshold pace. Slower paces are higher percentages.
+pub const PACE_ZONE_START_PCT: [f64; 4] = [129.0, 114.0, 106.0, 99.0];
+
+/// Longer gaps between two points (in seconds) are pauses, and don't count towards any zone.
+pub const MAX_PACE_ZONE_INTERVAL_SEC: f64 = 30.0;
+
+/// The length of a kilometer in meters, which the pace zones are given per.
+const METERS_PER_KM: f64 = 1_000.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The paces where each zone starts. Anything slower than the start of zone 1 is in zone 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaceZoneLimits {
+    /// The paces in seconds per kilometer where zones 1 to 4 start, slowest first.
+    pub zone_start_sec: [f64; 4],
+}
+
+impl PaceZoneLimits {
+    /// The zones for the threshold pace given, starting at `PACE_ZONE_START_PCT` of it.
+    ///
+    /// # Arguments
+    ///
+    /// `threshold_sec: f64` -- The threshold pace in seconds per kilometer.
+    #[must_use]
+    pub fn from_threshold(threshold_sec: f64) -> Self {
+        Self {
+            zone_start_sec: PACE_ZONE_START_PCT.map(|pct| threshold_sec * pct / 100.0),
+        }
+    }
+
+    /// The zone of the pace, from 0 to 4.
+    ///
+    /// # Arguments
+    ///
+    /// `pace_sec: f64` -- The pace in seconds per kilometer.
+    #[must_use]
+    pub fn zone(&self, pace_sec: f64) -> usize {
+        self.zone_start_sec
+            .iter()
+            .take_while(|start| pace_sec <= **start)
+            .count()
+    }
+}
+
+impl FromStr for PaceZoneLimits {
+    type Err = String;
+
+    /// Reads the zones as either the threshold pace per kilometer, e.g. `4:30`, or the paces per kilometer where
+    /// zones 1 to 4 start, e.g. `6:00,5:15,4:45,4:25`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = || {
+            format!("Unknown pace zones {s}. Use the threshold pace, or the four paces where zones 1 to 4 start, in min/km, e.g. 6:00,5:15,4:45,4:25.")
+        };
+        let paces = s
+            .split(',')
+            .map(|pace| pace.trim().parse::<Duration>())
+            .collect::<Result<Vec<Duration>, _>>()
+            .map_err(|_| usage())?
+            .iter()
+            .map(|pace| pace.0.as_secs_f64())
+            .collect::<Vec<f64>>();
+        if paces.iter().any(|pace| *pace <= 0.0) {
+            return Err(usage());
+        }
+
+        match paces.as_slice() {
+            [threshold] => Ok(Self::from_threshold(*threshold)),
+            [one, two, three, four] if one > two && two > three && three > four => Ok(Self {
+                zone_start_sec: [*one, *two, *three, *four],
+            }),
+            _ => Err(usage()),
+        }
+    }
+}
+
+impl fmt::Display for PaceZoneLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paces: Vec<String> = self
+            .zone_start_sec
+            .iter()
+            .map(|sec| min_sec(*sec))
+            .collect();
+        write!(f, "{}", paces.join(","))
+    }
+}
+
+impl Settings {
+    /// Sets the pace zones.
+    ///
+    /// # Arguments
+    ///
+    /// `limits: PaceZoneLimits` -- The paces where the zones start.
+    pub fn set_pace_zones(&mut self, limits: PaceZoneLimits) {
+        self.pace_zones = Some(limits);
+    }
+}
+
+/// The pace zones for this run, if set.
+pub(crate) fn pace_zones() -> Option<PaceZoneLimits> {
+    Settings::current().pace_zones
+}
+
+/// The pace as `m:ss`, e.g. `4:05`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn min_sec(sec: f64) -> String {
+    let sec = sec.round() as u64;
+    format!("{}:{:02}", sec / 60, sec % 60)
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// How much more energy running at the grade takes than running on the flat, from the energy cost measured by Minetti
+/// et al. Gentle downhills take the least, at about half the energy of the flat.
+///
+/// # Arguments
+///
+/// `grade: f64` -- The grade as a fraction, e.g. `0.05` for 5% uphill. Limited to `MAX_GRADE` either way.
+///
+/// # Returns
+///
+/// `f64` -- The energy cost relative to the flat, e.g. `1.3` for 30% more.
+#[must_use]
+pub fn grade_cost_factor(grade: f64) -> f64 {
+    let i = grade.clamp(-MAX_GRADE, MAX_GRADE);
+    let cost = 155.4_f64.mul_add(i.powi(5), -30.4 * i.powi(4))
+        + (-43.3_f64).mul_add(i.powi(3), 46.3 * i.powi(2))
+        + 19.5_f64.mul_add(i, FLAT_COST);
+    cost / FLAT_COST
+}
+
+/// The energy cost factor of each pair of points, from the grade worked out over at least `GRADE_WINDOW_M` around
+/// them. Pairs without the altitude either side have no factor.
+///
+/// # Arguments
+///
+/// `points: &[SplitPoint]` -- The points of the activity in the order they were recorded.
+///
+/// # Returns
+///
+/// `Vec<Option<f64>>` -- The factor of each pair of points, one fewer than the points.
+pub(crate) fn grade_factors(points: &[SplitPoint]) -> Vec<Option<f64>> {
+    // Distances that go backwards are GPS or sensor glitches
+    let distances: Vec<f64> = points
+        .iter()
+        .scan(f64::MIN, |max, point| {
+            *max = max.max(point.distance);
+            Some(*max)
+        })
+        .collect();
+
+    let (mut from, mut to) = (0, 0);
+    (0..points.len().saturating_sub(1))
+        .map(|pair| {
+            let middle = (distances[pair] + distances[pair + 1]) / 2.0;
+            while from < pair && distances[from + 1] <= middle - GRADE_WINDOW_M / 2.0 {
+                from += 1;
+            }
+            to = to.max(pair + 1);
+            while to + 1 < points.len() && distances[to] < middle + GRADE_WINDOW_M / 2.0 {
+                to += 1;
+            }
+
+            let run = distances[to] - distances[from];
+            let rise = points[to].altitude? - points[from].altitude?;
+            Some(if run > 0.0 {
+                grade_cost_factor(rise / run)
+            } else {
+                1.0
+            })
+        })
+        .collect()
+}
+
+/// Works out the grade-adjusted pace of the whole activity.
+///
+/// # Arguments
+///
+/// `points: &[SplitPoint]` -- The points of the activity in the order they were recorded.
+///
+/// # Returns
+///
+/// `Option<f64>` -- The grade-adjusted pace in seconds per kilometer, or `None` if there is no altitude or distance.
+#[must_use]
+pub fn grade_adjusted_pace(points: &[SplitPoint]) -> Option<f64> {
+    let (first, last) = (points.first()?, points.last()?);
+    let mut flat_distance = 0.0;
+    let mut distance = first.distance;
+    let mut graded = false;
+    for (pair, factor) in points.windows(2).zip(grade_factors(points)) {
+        let covered = pair[1].distance - distance;
+        if covered > 0.0 {
+            flat_distance += covered * factor.unwrap_or(1.0);
+            distance = pair[1].distance;
+        }
+        graded |= factor.is_some();
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let secs = (last.time - first.time).num_milliseconds() as f64 / 1_000.0;
+    (graded && flat_distance > 0.0).then(|| secs / flat_distance * METERS_PER_KM)
+}
+
+/// The pace zone of the stretch between two points, if it isn't a pause. Standing still is in zone 0.
+///
+/// # Arguments
+///
+/// - `from: &SplitPoint` -- The point at the start of the stretch.
+/// - `to_time: DateTime<Local>` -- When the stretch ended.
+/// - `covered: f64` -- The distance covered in the stretch in meters.
+/// - `limits: &PaceZoneLimits` -- The paces where the zones start.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn stretch_zone(
+    from: &SplitPoint,
+    to_time: DateTime<Local>,
+    covered: f64,
+    limits: &PaceZoneLimits,
+) -> Option<usize> {
+    let secs = (to_time - from.time).num_milliseconds() as f64 / 1_000.0;
+    if secs <= 0.0 || secs > MAX_PACE_ZONE_INTERVAL_SEC {
+        return None;
+    }
+    Some(if covered > 0.0 {
+        limits.zone(secs / covered * METERS_PER_KM)
+    } else {
+        0
+    })
+}
+
+/// Adds up the time spent in each pace zone.
+///
+/// # Arguments
+///
+/// - `points: &[SplitPoint]` -- The points of the activity in the order they were recorded.
+/// - `limits: &PaceZoneLimits` -- The paces where the zones start.
+///
+/// # Returns
+///
+/// `[f64; 5]` -- The time in seconds in zones 0 to 4.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn time_in_pace_zones(points: &[SplitPoint], limits: &PaceZoneLimits) -> [f64; 5] {
+    let mut secs = [0.0; 5];
+    for pair in points.windows(2) {
+        let covered = (pair[1].distance - pair[0].distance).max(0.0);
+        if let Some(zone) = stretch_zone(&pair[0], pair[1].time, covered, limits) {
+            secs[zone] += (pair[1].time - pair[0].time).num_milliseconds() as f64 / 1_000.0;
+        }
+    }
+    secs
+}
+
+/// Prints the grade-adjusted pace and, if the pace zones are set, the time in each zone to stdout. The pace is per
+/// mile in the imperial unit system, and per kilometer otherwise.
+///
+/// # Arguments
+///
+/// `points: &[SplitPoint]` -- The points of the activity in the order they were recorded.
+pub fn print_pace_analysis(points: &[SplitPoint]) {
+    let units = unit_system().unwrap_or(UnitSystem::Metric);
+    if let Some(pace) = grade_adjusted_pace(points) {
+        if let Some(pace) = units.pace(METERS_PER_KM / pace) {
+            println!(
+                "\n{:<27}{:>9}",
+                format!("Grade-adj. pace ({}):", units.pace_unit()),
+                pace.to_string()
+            );
+        }
+    }
+
+    let Some(limits) = pace_zones() else {
+        return;
+    };
+    let secs = time_in_pace_zones(points, &limits);
+    let mut table = Table::new(&["Zone", "From (min/km)", "Time"]);
+    for (zone, secs) in secs.iter().enumerate() {
+        table.add_row(vec![
+            zone.to_string(),
+            zone.checked_sub(1)
+                .map_or_else(String::new, |start| min_sec(limits.zone_start_sec[start])),
+            Duration::from_secs_f64(*secs).to_string(),
+        ]);
+    }
+
+    println!("\nTime in pace zones:\n");
+    print!("{table}");
+}
+
+#[cfg(test)]
+/// Tests for the running module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A point `secs` seconds into the activity.
+    fn point(secs: i64, distance: f64, altitude: Option<f64>) -> SplitPoint {
+        SplitPoint {
+            time: Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap()
+                + chrono::Duration::seconds(secs),
+            distance,
+            heart_rate: None,
+            altitude,
+        }
+    }
+
+    #[test]
+    /// Test the energy cost of the grades
+    fn test_grade_cost_factor() {
+        assert!((grade_cost_factor(0.0) - 1.0).abs() < f64::EPSILON);
+        assert!(grade_cost_factor(0.1) > 1.4);
+        assert!(grade_cost_factor(-0.1) < 0.7);
+        assert!((grade_cost_factor(0.9) - grade_cost_factor(MAX_GRADE)).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    /// Test the grade-adjusted pace uphill, on the flat and without the altitude
+    fn test_grade_adjusted_pace() {
+        // 5% uphill at 5:00/km
+        let uphill: Vec<SplitPoint> = (0..=100)
+            .map(|n| point(n * 3, n as f64 * 10.0, Some(n as f64 * 0.5)))
+            .collect();
+        let pace = grade_adjusted_pace(&uphill).unwrap();
+        assert!(pace < 300.0, "{pace}");
+
+        let flat: Vec<SplitPoint> = (0..=100)
+            .map(|n| point(n * 3, n as f64 * 10.0, Some(10.0)))
+            .collect();
+        assert!((grade_adjusted_pace(&flat).unwrap() - 300.0).abs() < 0.01);
+
+        let no_altitude = [point(0, 0.0, None), point(300, 1_000.0, None)];
+        assert!(grade_adjusted_pace(&no_altitude).is_none());
+    }
+
+    #[test]
+    /// Test the zones of the paces, reading them from the command line and adding up the time in each
+    fn test_pace_zones() {
+        let limits = PaceZoneLimits::from_threshold(300.0);
+        assert_eq!(limits.zone(400.0), 0);
+        assert_eq!(limits.zone(330.0), 2);
+        assert_eq!(limits.zone(290.0), 4);
+
+        assert_eq!("5:00".parse(), Ok(limits));
+        assert_eq!(
+            "6:00, 5:15,4:45,4:25"
+                .parse::<PaceZoneLimits>()
+                .unwrap()
+                .to_string(),
+            "6:00,5:15,4:45,4:25"
+        );
+        for bad in ["", "fast", "0", "6:00,5:00", "4:00,5:00,6:00,7:00"] {
+            assert!(bad.parse::<PaceZoneLimits>().is_err(), "{bad}");
+        }
+
+        let points = [
+            point(0, 0.0, None),
+            point(10, 20.0, None),  // 8:20/km
+            point(20, 52.0, None),  // 5:13/km
+            point(30, 55.0, None),  // standing still
+            point(330, 60.0, None), // pause
+        ];
+        let secs = time_in_pace_zones(&points, &limits);
+        assert_eq!(secs, [20.0, 0.0, 0.0, 10.0, 0.0]);
+    }
+}
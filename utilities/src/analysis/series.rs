@@ -0,0 +1,240 @@
+//! Picks a single series, e.g. the heart rate, out of the records of an activity along with the time of each value,
+//! and draws it as a sparkline chart for the console output.
+
+use chrono::{DateTime, Local};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::analysis::splits::SplitPoint;
+use crate::units::{converted, unit_system, Measure};
+use crate::Duration;
+
+/// The bars of the sparkline, lowest first.
+const SPARK_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The series that can be picked out of the records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordSeries {
+    /// The altitude or elevation in meters.
+    Elevation,
+    /// The heart rate in beats per minute.
+    HeartRate,
+    /// The power in watts. TCX files don't record it.
+    Power,
+    /// The speed in meters per second. Worked out from the distance where the speed isn't recorded.
+    Speed,
+}
+
+impl RecordSeries {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 4] = ["elevation", "hr", "power", "speed"];
+
+    /// What the values are measured in, if they change with the unit system.
+    const fn measure(self) -> Option<Measure> {
+        match self {
+            Self::Elevation => Some(Measure::Length),
+            Self::Speed => Some(Measure::Speed),
+            Self::HeartRate | Self::Power => None,
+        }
+    }
+
+    /// The unit shown in the console output, in the unit system of the run.
+    fn unit(self) -> &'static str {
+        match (self, self.measure()) {
+            (_, Some(measure)) => measure.unit(unit_system()),
+            (Self::HeartRate, None) => "bpm",
+            (_, None) => "W",
+        }
+    }
+}
+
+impl FromStr for RecordSeries {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "elevation" | "altitude" => Ok(Self::Elevation),
+            "hr" | "heart-rate" | "heartrate" => Ok(Self::HeartRate),
+            "power" => Ok(Self::Power),
+            "speed" => Ok(Self::Speed),
+            _ => Err(format!(
+                "Unknown series {s}. Use elevation, hr, power or speed."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RecordSeries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Elevation => write!(f, "Elevation"),
+            Self::HeartRate => write!(f, "Heart rate"),
+            Self::Power => write!(f, "Power"),
+            Self::Speed => write!(f, "Speed"),
+        }
+    }
+}
+
+/// A single value of a series, in the units recorded in the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeriesPoint {
+    /// When the value was recorded.
+    pub time: DateTime<Local>,
+
+    /// The value.
+    pub value: f64,
+}
+
+/// Works out the speed between each pair of points from the distance covered, for files that don't record the speed.
+///
+/// # Arguments
+///
+/// `points: &[SplitPoint]` -- The points with the time and the distance covered, in order.
+///
+/// # Returns
+///
+/// `Vec<SeriesPoint>` -- The speed in meters per second, at the time of the second point of each pair. Pairs
+/// recorded at the same time are left out.
+pub(crate) fn speeds(points: &[SplitPoint]) -> Vec<SeriesPoint> {
+    points
+        .windows(2)
+        .filter_map(|pair| {
+            let secs = (pair[1].time - pair[0].time).num_milliseconds();
+            (secs > 0).then(|| SeriesPoint {
+                time: pair[1].time,
+                #[allow(clippy::cast_precision_loss)]
+                value: (pair[1].distance - pair[0].distance) / (secs as f64 / 1_000.0),
+            })
+        })
+        .collect()
+}
+
+/// Draws the values as a sparkline of block characters, from `▁` for the lowest value to `█` for the highest. Series
+/// longer than the width are downsampled by averaging the values that fall on each character.
+///
+/// # Arguments
+///
+/// - `values: &[f64]` -- The values, in order.
+/// - `width: usize` -- The most characters to use.
+///
+/// # Returns
+///
+/// `String` -- The sparkline, or an empty string if there are no values.
+#[must_use]
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    let width = width.max(1);
+    let buckets: Vec<f64> = if values.len() <= width {
+        values.to_vec()
+    } else {
+        (0..width)
+            .map(|bucket| {
+                let bucket =
+                    &values[bucket * values.len() / width..(bucket + 1) * values.len() / width];
+                bucket.iter().sum::<f64>() / bucket.len() as f64
+            })
+            .collect()
+    };
+
+    let min = buckets.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = buckets.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let top = (SPARK_BARS.len() - 1) as f64;
+    buckets
+        .iter()
+        .map(|value| {
+            let level = if max > min {
+                ((value - min) / (max - min) * top).round() as usize
+            } else {
+                0
+            };
+            SPARK_BARS[level.min(SPARK_BARS.len() - 1)]
+        })
+        .collect()
+}
+
+/// Prints the series as a sparkline chart to stdout, with the lowest and highest values above it and the time along
+/// the bottom. Elevations and speeds are in the unit system of the run.
+///
+/// # Arguments
+///
+/// - `series: RecordSeries` -- What the values are.
+/// - `points: &[SeriesPoint]` -- The values, in order.
+/// - `width: usize` -- The most characters the chart can use, usually the width of the terminal.
+#[allow(clippy::cast_precision_loss)]
+pub fn print_chart(series: RecordSeries, points: &[SeriesPoint], width: usize) {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        let name = series.to_string().to_lowercase();
+        println!("\nNo {name} chart. The activity has no {name} values.");
+        return;
+    };
+
+    let values: Vec<f64> = points
+        .iter()
+        .map(|point| {
+            series
+                .measure()
+                .map_or(point.value, |measure| converted(measure, point.value))
+        })
+        .collect();
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let chart = sparkline(&values, width);
+
+    let start = Duration::default().to_string();
+    let finish =
+        Duration::from_secs_f64((last.time - first.time).num_milliseconds() as f64 / 1_000.0)
+            .to_string();
+    let gap = chart
+        .chars()
+        .count()
+        .saturating_sub(start.len() + finish.len())
+        .max(1);
+
+    println!("\n{series} ({}), {min:.1} to {max:.1}:\n", series.unit());
+    println!("{chart}");
+    println!("{start}{}{finish}", " ".repeat(gap));
+}
+
+#[cfg(test)]
+/// Tests for the series module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    /// Test drawing the sparkline, with and without downsampling
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0.0, 7.0, 3.5, 1.0], 10), "▁█▅▂");
+        assert_eq!(sparkline(&[0.0, 0.0, 7.0, 7.0, 14.0, 14.0], 3), "▁▅█");
+        assert_eq!(sparkline(&[5.0, 5.0], 10), "▁▁");
+        assert_eq!(sparkline(&[], 10), "");
+        assert_eq!("HR".parse(), Ok(RecordSeries::HeartRate));
+        assert!("cadence".parse::<RecordSeries>().is_err());
+    }
+
+    #[test]
+    /// Test working out the speed from the distance
+    fn test_speeds() {
+        let point = |secs: i64, distance: f64| SplitPoint {
+            time: Local.timestamp_opt(1_600_000_000 + secs, 0).unwrap(),
+            distance,
+            heart_rate: None,
+            altitude: None,
+        };
+        let speeds = speeds(&[
+            point(0, 0.0),
+            point(10, 30.0),
+            point(10, 31.0),
+            point(20, 81.0),
+        ]);
+
+        assert_eq!(speeds.len(), 2);
+        assert!((speeds[0].value - 3.0).abs() < f64::EPSILON);
+        assert!((speeds[1].value - 5.0).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,533 @@
+//! Splits an activity into kilometers or miles, with the time, pace, grade-adjusted pace, heart rate and elevation
+//! change for each split, worked out from the records rather than the laps recorded by the device. If the pace zones
+//! are set, the time in each zone is added too.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::analysis::running::{grade_factors, pace_zones, stretch_zone, PaceZoneLimits};
+use crate::columns::{ColumnWriter, CsvFile};
+use crate::precision::Rounded;
+use crate::table::Table;
+use crate::Duration;
+
+/// The length of a mile in meters.
+const METERS_PER_MILE: f64 = 1_609.344;
+
+/// Partial splits at the end shorter than this (in meters) are left out.
+const MIN_PARTIAL_SPLIT_M: f64 = 1.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The distance each split covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum SplitUnit {
+    /// One split per kilometer.
+    #[default]
+    #[serde(rename = "km")]
+    Kilometer,
+    /// One split per mile.
+    #[serde(rename = "mi")]
+    Mile,
+}
+
+impl SplitUnit {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 2] = ["km", "mi"];
+
+    /// The length of the split in meters.
+    #[must_use]
+    pub const fn meters(self) -> f64 {
+        match self {
+            Self::Kilometer => 1_000.0,
+            Self::Mile => METERS_PER_MILE,
+        }
+    }
+}
+
+impl FromStr for SplitUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "km" | "kilometer" | "kilometre" => Ok(Self::Kilometer),
+            "mi" | "mile" => Ok(Self::Mile),
+            _ => Err(format!("Unable to split by {s}. Use km or mi.")),
+        }
+    }
+}
+
+impl fmt::Display for SplitUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kilometer => write!(f, "km"),
+            Self::Mile => write!(f, "mi"),
+        }
+    }
+}
+
+/// A point in the activity, with the distance covered since the start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitPoint {
+    /// When the point was recorded.
+    pub time: DateTime<Local>,
+
+    /// The distance covered since the start of the activity in meters.
+    pub distance: f64,
+
+    /// The heart rate in beats per minute, if recorded.
+    pub heart_rate: Option<f64>,
+
+    /// The altitude in meters, if recorded.
+    pub altitude: Option<f64>,
+}
+
+/// A single kilometer or mile of the activity. The last split is usually shorter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Split {
+    /// Whether the split is a kilometer or a mile.
+    #[serde(rename = "unit")]
+    pub unit: SplitUnit,
+
+    /// The number of the split, starting at 1.
+    #[serde(rename = "split")]
+    pub split: usize,
+
+    /// The distance covered in the split in meters.
+    #[serde(rename = "distance_m")]
+    pub distance_m: f64,
+
+    /// The time taken to cover the split in seconds.
+    #[serde(rename = "duration_sec")]
+    pub duration_sec: f64,
+
+    /// The time per kilometer or mile in seconds.
+    #[serde(rename = "pace_sec")]
+    pub pace_sec: f64,
+
+    /// The average heart rate in the split.
+    #[serde(rename = "heartrate_avg_bpm")]
+    pub heartrate_avg_bpm: Option<f64>,
+
+    /// The altitude at the end of the split less the altitude at the start in meters.
+    #[serde(rename = "elevation_change_m")]
+    pub elevation_change_m: Option<f64>,
+
+    /// The pace the same effort would have given on the flat, in seconds per kilometer or mile.
+    #[serde(rename = "grade_adjusted_pace_sec")]
+    pub grade_adjusted_pace_sec: Option<f64>,
+
+    /// The time spent in pace zone 0 in seconds, if the pace zones are set.
+    #[serde(rename = "pace_zone0_sec")]
+    pub pace_zone0_sec: Option<f64>,
+
+    /// The time spent in pace zone 1 in seconds, if the pace zones are set.
+    #[serde(rename = "pace_zone1_sec")]
+    pub pace_zone1_sec: Option<f64>,
+
+    /// The time spent in pace zone 2 in seconds, if the pace zones are set.
+    #[serde(rename = "pace_zone2_sec")]
+    pub pace_zone2_sec: Option<f64>,
+
+    /// The time spent in pace zone 3 in seconds, if the pace zones are set.
+    #[serde(rename = "pace_zone3_sec")]
+    pub pace_zone3_sec: Option<f64>,
+
+    /// The time spent in pace zone 4 in seconds, if the pace zones are set.
+    #[serde(rename = "pace_zone4_sec")]
+    pub pace_zone4_sec: Option<f64>,
+}
+
+/// What is added up while going through the points of a split.
+#[derive(Debug, Clone, Copy, Default)]
+struct SplitTotals {
+    /// The distance covered, weighted by the energy cost of the grade.
+    flat_distance: f64,
+    /// Whether any of the split has a grade.
+    graded: bool,
+    /// The time in each pace zone in seconds, if the pace zones are set.
+    zone_secs: Option<[f64; 5]>,
+}
+
+impl SplitTotals {
+    /// Nothing added up yet.
+    fn new(zones: Option<&PaceZoneLimits>) -> Self {
+        Self {
+            zone_secs: zones.map(|_| [0.0; 5]),
+            ..Self::default()
+        }
+    }
+
+    /// Adds a stretch of the split.
+    fn add(&mut self, distance: f64, factor: Option<f64>, zone: Option<usize>, secs: f64) {
+        self.flat_distance += distance * factor.unwrap_or(1.0);
+        self.graded |= factor.is_some();
+        if let (Some(zone_secs), Some(zone)) = (self.zone_secs.as_mut(), zone) {
+            zone_secs[zone] += secs;
+        }
+    }
+
+    /// The grade-adjusted pace of the split, if any of it has a grade.
+    fn grade_adjusted_pace(&self, duration_sec: f64, length: f64) -> Option<f64> {
+        (self.graded && self.flat_distance > 0.0)
+            .then(|| duration_sec / self.flat_distance * length)
+    }
+
+    /// The time in each pace zone, or all `None` if the pace zones aren't set.
+    fn pace_zones(&self) -> [Option<f64>; 5] {
+        self.zone_secs.map_or([None; 5], |secs| secs.map(Some))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Splits the activity into kilometers or miles. The time and altitude where each split ends are interpolated between
+/// the points either side, so the splits don't depend on how often the device records. The time in the pace zones is
+/// added if they are set with `Settings::set_pace_zones()`.
+///
+/// # Arguments
+///
+/// - `points: &[SplitPoint]` -- The points of the activity in the order they were recorded.
+/// - `unit: SplitUnit` -- Whether to split by kilometer or mile.
+///
+/// # Returns
+///
+/// `Vec<Split>` -- The splits, followed by the remaining part split if there is one. Empty if there are fewer than two
+/// points.
+#[must_use]
+pub fn splits(points: &[SplitPoint], unit: SplitUnit) -> Vec<Split> {
+    zoned_splits(points, unit, pace_zones().as_ref())
+}
+
+/// Splits the activity into kilometers or miles, with the time in the pace zones given, if any.
+#[allow(clippy::cast_precision_loss)]
+fn zoned_splits(
+    points: &[SplitPoint],
+    unit: SplitUnit,
+    zones: Option<&PaceZoneLimits>,
+) -> Vec<Split> {
+    let mut splits = Vec::new();
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return splits;
+    };
+    if points.len() < 2 {
+        return splits;
+    }
+
+    let length = unit.meters();
+    let mut next_boundary = first.distance + length;
+    let mut split_start = first.time;
+    let mut start_altitude = first.altitude;
+    let mut last_altitude = first.altitude;
+    let (mut hr_sum, mut hr_count) = first.heart_rate.map_or((0.0, 0_u32), |hr| (hr, 1));
+    let mut distance = first.distance;
+    let mut totals = SplitTotals::new(zones);
+
+    for ((prev, curr), factor) in points
+        .iter()
+        .zip(points.iter().skip(1))
+        .zip(grade_factors(points))
+    {
+        // Distances that go backwards are GPS or sensor glitches
+        let prev_distance = distance;
+        distance = distance.max(curr.distance);
+        let secs = seconds_between(prev.time, curr.time);
+        let zone =
+            zones.and_then(|zones| stretch_zone(prev, curr.time, distance - prev_distance, zones));
+        let (mut covered, mut covered_fraction) = (prev_distance, 0.0);
+
+        while distance >= next_boundary {
+            let fraction = (next_boundary - prev_distance) / (distance - prev_distance);
+            let boundary_time = interpolate_time(prev.time, curr.time, fraction);
+            let boundary_altitude = match (prev.altitude, curr.altitude) {
+                (Some(a), Some(b)) => Some((b - a).mul_add(fraction, a)),
+                _ => last_altitude,
+            };
+
+            totals.add(
+                next_boundary - covered,
+                factor,
+                zone,
+                (fraction - covered_fraction) * secs,
+            );
+            (covered, covered_fraction) = (next_boundary, fraction);
+
+            // A full split, so the pace is the same as the time
+            let duration_sec = seconds_between(split_start, boundary_time);
+            let [zone0, zone1, zone2, zone3, zone4] = totals.pace_zones();
+            splits.push(Split {
+                unit,
+                split: splits.len() + 1,
+                distance_m: length,
+                duration_sec,
+                pace_sec: duration_sec,
+                heartrate_avg_bpm: (hr_count > 0).then(|| hr_sum / f64::from(hr_count)),
+                elevation_change_m: boundary_altitude
+                    .zip(start_altitude)
+                    .map(|(end, start)| end - start),
+                grade_adjusted_pace_sec: totals.grade_adjusted_pace(duration_sec, length),
+                pace_zone0_sec: zone0,
+                pace_zone1_sec: zone1,
+                pace_zone2_sec: zone2,
+                pace_zone3_sec: zone3,
+                pace_zone4_sec: zone4,
+            });
+
+            totals = SplitTotals::new(zones);
+            split_start = boundary_time;
+            start_altitude = boundary_altitude;
+            (hr_sum, hr_count) = (0.0, 0);
+            next_boundary += length;
+        }
+        totals.add(
+            distance - covered,
+            factor,
+            zone,
+            (1.0 - covered_fraction) * secs,
+        );
+
+        if let Some(hr) = curr.heart_rate {
+            hr_sum += hr;
+            hr_count += 1;
+        }
+        if curr.altitude.is_some() {
+            last_altitude = curr.altitude;
+            start_altitude = start_altitude.or(curr.altitude);
+        }
+    }
+
+    // What's left after the last full split
+    let remaining = distance - (next_boundary - length);
+    if remaining >= MIN_PARTIAL_SPLIT_M {
+        let duration_sec = seconds_between(split_start, last.time);
+        let [zone0, zone1, zone2, zone3, zone4] = totals.pace_zones();
+        splits.push(Split {
+            unit,
+            split: splits.len() + 1,
+            distance_m: remaining,
+            duration_sec,
+            pace_sec: duration_sec / remaining * length,
+            heartrate_avg_bpm: (hr_count > 0).then(|| hr_sum / f64::from(hr_count)),
+            elevation_change_m: last_altitude
+                .zip(start_altitude)
+                .map(|(end, start)| end - start),
+            grade_adjusted_pace_sec: totals.grade_adjusted_pace(duration_sec, length),
+            pace_zone0_sec: zone0,
+            pace_zone1_sec: zone1,
+            pace_zone2_sec: zone2,
+            pace_zone3_sec: zone3,
+            pace_zone4_sec: zone4,
+        });
+    }
+
+    splits
+}
+
+/// The time a fraction of the way from `from` to `to`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn interpolate_time(from: DateTime<Local>, to: DateTime<Local>, fraction: f64) -> DateTime<Local> {
+    let span_ms = (to - from).num_milliseconds() as f64;
+    from + chrono::Duration::milliseconds((span_ms * fraction).round() as i64)
+}
+
+/// The number of seconds from `from` to `to`.
+#[allow(clippy::cast_precision_loss)]
+fn seconds_between(from: DateTime<Local>, to: DateTime<Local>) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes the splits as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+///
+/// # Arguments
+///
+/// - `splits: &[Split]` -- The splits to write.
+/// - `writer: W` -- Where the CSV is written.
+///
+/// # Errors
+///
+/// Serializing or writing the splits may fail.
+pub fn write_splits_csv<W: Write>(splits: &[Split], writer: W) -> Result<(), Box<dyn Error>> {
+    let mut writer = ColumnWriter::new(writer, CsvFile::Splits);
+    for split in splits {
+        writer.serialize(Rounded(split))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the splits to a CSV file.
+///
+/// # Arguments
+///
+/// - `splits: &[Split]` -- The splits to write.
+/// - `filename: &str` -- The CSV file to write, usually named after the activity file with `.splits.csv` added.
+///
+/// # Errors
+///
+/// Creating or writing the file may fail.
+#[cfg(feature = "fs")]
+pub fn export_splits_csv(splits: &[Split], filename: &str) -> Result<(), Box<dyn Error>> {
+    log::trace!(
+        "splits::export_splits_csv() -- Writing {} splits to {filename}",
+        splits.len()
+    );
+    crate::output_naming::write_output(std::path::Path::new(filename), |file| {
+        write_splits_csv(splits, file)
+    })
+}
+
+/// Prints the splits as a table to stdout.
+pub fn print_splits(splits: &[Split]) {
+    let Some(first) = splits.first() else {
+        println!("\nNo splits. The activity has no distance.");
+        return;
+    };
+
+    let mut table = Table::new(&[
+        "Split",
+        "Distance",
+        "Time",
+        "Pace",
+        "GAP",
+        "HR",
+        "Elevation",
+    ]);
+    for split in splits {
+        table.add_row(vec![
+            split.split.to_string(),
+            format!("{:.0} m", split.distance_m),
+            Duration::from_secs_f64(split.duration_sec).to_string(),
+            Duration::from_secs_f64(split.pace_sec).to_string(),
+            split
+                .grade_adjusted_pace_sec
+                .map_or_else(String::new, |gap| Duration::from_secs_f64(gap).to_string()),
+            split
+                .heartrate_avg_bpm
+                .map_or_else(String::new, |hr| format!("{hr:.0}")),
+            split
+                .elevation_change_m
+                .map_or_else(String::new, |elev| format!("{elev:+.0} m")),
+        ]);
+    }
+
+    println!("\nSplits per {}:\n", first.unit);
+    print!("{table}");
+}
+
+#[cfg(test)]
+/// Tests for the splits module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A point `secs` seconds into the activity.
+    fn point(
+        secs: i64,
+        distance: f64,
+        heart_rate: Option<f64>,
+        altitude: Option<f64>,
+    ) -> SplitPoint {
+        SplitPoint {
+            time: Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap()
+                + chrono::Duration::seconds(secs),
+            distance,
+            heart_rate,
+            altitude,
+        }
+    }
+
+    #[test]
+    /// Test splitting by kilometer, with the boundaries interpolated
+    fn test_splits() {
+        let points = vec![
+            point(0, 0.0, Some(120.0), Some(10.0)),
+            point(200, 800.0, Some(140.0), Some(20.0)),
+            point(400, 1_200.0, Some(150.0), Some(30.0)),
+            point(410, 1_100.0, None, None), // glitch
+            point(700, 2_500.0, Some(160.0), Some(0.0)),
+        ];
+        let splits = splits(&points, SplitUnit::Kilometer);
+
+        assert_eq!(splits.len(), 3);
+        assert_eq!(splits[0].split, 1);
+        assert!((splits[0].duration_sec - 300.0).abs() < 0.01);
+        assert_eq!(splits[0].heartrate_avg_bpm, Some(130.0));
+        assert!(splits[0]
+            .elevation_change_m
+            .is_some_and(|e| (e - 15.0).abs() < 0.01));
+
+        // The last kilometer is only half done
+        assert!((splits[2].distance_m - 500.0).abs() < 0.01);
+        assert!((splits[2].pace_sec - 2.0 * splits[2].duration_sec).abs() < 0.01);
+
+        assert!(super::splits(&points[..1], SplitUnit::Mile).is_empty());
+    }
+
+    #[test]
+    /// Test the grade-adjusted pace and the time in the pace zones of the splits
+    fn test_splits_pace_zones() {
+        // Flat at 5:00/km, then 5% uphill at 6:00/km
+        let points = vec![
+            point(0, 0.0, None, Some(10.0)),
+            point(300, 1_000.0, None, Some(10.0)),
+            point(660, 2_000.0, None, Some(60.0)),
+        ];
+        let zones = PaceZoneLimits::from_threshold(300.0);
+        let splits = zoned_splits(&points, SplitUnit::Kilometer, Some(&zones));
+
+        assert_eq!(splits.len(), 2);
+        assert!(splits[0]
+            .grade_adjusted_pace_sec
+            .is_some_and(|gap| (gap - 300.0).abs() < 0.01));
+        assert!(splits[1]
+            .grade_adjusted_pace_sec
+            .is_some_and(|gap| gap < 360.0));
+        // The stretches are longer than a pause, so they don't count towards the zones
+        assert_eq!(splits[0].pace_zone4_sec, Some(0.0));
+
+        let steady: Vec<SplitPoint> = (0..=100)
+            .map(|secs| {
+                point(
+                    secs * 3,
+                    f64::from(u8::try_from(secs).unwrap()) * 10.0,
+                    None,
+                    None,
+                )
+            })
+            .collect();
+        let splits = zoned_splits(&steady, SplitUnit::Kilometer, Some(&zones));
+        assert!(splits[0].grade_adjusted_pace_sec.is_none());
+        assert!(splits[0]
+            .pace_zone3_sec
+            .is_some_and(|secs| (secs - 300.0).abs() < 0.01));
+        assert!(zoned_splits(&steady, SplitUnit::Kilometer, None)[0]
+            .pace_zone3_sec
+            .is_none());
+    }
+
+    #[test]
+    /// Test the units
+    fn test_split_unit() {
+        assert_eq!("KM".parse::<SplitUnit>(), Ok(SplitUnit::Kilometer));
+        assert_eq!("mile".parse::<SplitUnit>(), Ok(SplitUnit::Mile));
+        assert!("lap".parse::<SplitUnit>().is_err());
+        assert_eq!(SplitUnit::Mile.to_string(), "mi");
+        assert!((SplitUnit::Mile.meters() - 1_609.344).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    /// Test writing the splits to CSV
+    fn test_write_splits_csv() {
+        let points = vec![point(0, 0.0, None, None), point(600, 2_000.0, None, None)];
+        let mut csv = Vec::new();
+        write_splits_csv(&splits(&points, SplitUnit::Kilometer), &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        assert!(csv.starts_with("unit,split,distance_m,duration_sec,pace_sec,"));
+        assert_eq!(csv.lines().count(), 3);
+    }
+}
@@ -0,0 +1,269 @@
+//! Works out the summary numbers of a track from its points: the distance, moving time, average and maximum speed, and
+//! the ascent and descent. Used for GPX files, which carry no summary numbers of their own, so their summaries can be
+//! compared with FIT sessions.
+
+use chrono::{DateTime, Local};
+
+use crate::analysis::derived::{haversine_distance, smooth};
+
+/// Speeds below this (in meters per second, about 1.8 km/h) count as standing still, and don't add to the moving time.
+pub const MOVING_SPEED_MIN_MS: f64 = 0.5;
+
+/// The number of intervals in the moving average used on the speeds before finding the maximum, so a single GPS jump
+/// doesn't give an impossible maximum speed.
+pub const SPEED_SMOOTHING_WINDOW: usize = 5;
+
+/// The number of points in the moving average used on the elevations before adding up the ascent and descent.
+pub const ELEVATION_SMOOTHING_WINDOW: usize = 5;
+
+/// Climbs and drops smaller than this (in meters) are treated as GPS noise and left out of the ascent and descent.
+pub const ELEVATION_THRESHOLD_M: f64 = 2.0;
+
+/// A point of a track with what is needed for the summary numbers.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StatsPoint {
+    /// When the point was recorded.
+    pub time: Option<DateTime<Local>>,
+
+    /// The latitude and longitude in degrees.
+    pub position: Option<(f64, f64)>,
+
+    /// The elevation in meters.
+    pub elevation: Option<f64>,
+}
+
+/// The summary numbers of a track, or of several tracks added together.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrackStats {
+    /// The distance in meters, or `None` if fewer than two points have a position.
+    pub distance_m: Option<f64>,
+
+    /// The time spent moving in seconds, or `None` if no two points have both a time and a position.
+    pub moving_time_sec: Option<f64>,
+
+    /// The maximum speed in meters per second, smoothed over a few points.
+    pub max_speed_ms: Option<f64>,
+
+    /// The total climb in meters, or `None` if fewer than two points have an elevation.
+    pub ascent_m: Option<f64>,
+
+    /// The total drop in meters, or `None` if fewer than two points have an elevation.
+    pub descent_m: Option<f64>,
+}
+
+impl TrackStats {
+    /// The average speed while moving in meters per second, or `None` if the moving time isn't known or is zero.
+    #[must_use]
+    pub fn avg_speed_ms(&self) -> Option<f64> {
+        let (distance, moving) = (self.distance_m?, self.moving_time_sec?);
+        (moving > 0.0).then(|| distance / moving)
+    }
+
+    /// Adds the numbers of another track, e.g. to get the totals of an activity with several tracks.
+    #[must_use]
+    pub fn combine(&self, other: &Self) -> Self {
+        Self {
+            distance_m: add(self.distance_m, other.distance_m),
+            moving_time_sec: add(self.moving_time_sec, other.moving_time_sec),
+            max_speed_ms: max(self.max_speed_ms, other.max_speed_ms),
+            ascent_m: add(self.ascent_m, other.ascent_m),
+            descent_m: add(self.descent_m, other.descent_m),
+        }
+    }
+}
+
+/// The sum of two values, where either may be unknown.
+fn add(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        _ => a.or(b),
+    }
+}
+
+/// The larger of two values, where either may be unknown.
+fn max(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        _ => a.or(b),
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the summary numbers of a track. The segments are treated separately, so the gap between two segments
+/// (e.g. where the recording was paused) adds neither distance nor moving time.
+///
+/// # Arguments
+///
+/// `segments: &[Vec<StatsPoint>]` -- The points of each segment of the track, in the order they were recorded.
+///
+/// # Returns
+///
+/// `TrackStats` -- The summary numbers. Numbers that can't be worked out from the points are `None`.
+#[must_use]
+pub fn track_stats(segments: &[Vec<StatsPoint>]) -> TrackStats {
+    segments
+        .iter()
+        .map(|segment| segment_stats(segment))
+        .fold(TrackStats::default(), |total, stats| total.combine(&stats))
+}
+
+/// Works out the summary numbers of a single segment.
+fn segment_stats(points: &[StatsPoint]) -> TrackStats {
+    let positioned: Vec<&StatsPoint> = points
+        .iter()
+        .filter(|point| point.position.is_some())
+        .collect();
+
+    let mut distance = None;
+    let mut moving_time = None;
+    let mut speeds = Vec::new();
+    for pair in positioned.windows(2) {
+        let (Some(from), Some(to)) = (pair[0].position, pair[1].position) else {
+            continue;
+        };
+        let meters = haversine_distance(from, to);
+        distance = add(distance, Some(meters));
+
+        let Some(seconds) = pair[0]
+            .time
+            .zip(pair[1].time)
+            .map(|(from, to)| (to - from).num_milliseconds() as f64 / 1_000.0)
+            .filter(|seconds| *seconds > 0.0)
+        else {
+            continue;
+        };
+        let speed = meters / seconds;
+        moving_time = add(
+            moving_time,
+            Some(if speed >= MOVING_SPEED_MIN_MS {
+                seconds
+            } else {
+                0.0
+            }),
+        );
+        speeds.push(speed);
+    }
+
+    let (ascent, descent) = climb(
+        &points
+            .iter()
+            .filter_map(|point| point.elevation)
+            .collect::<Vec<f64>>(),
+    );
+
+    TrackStats {
+        distance_m: distance,
+        moving_time_sec: moving_time,
+        max_speed_ms: smooth(&speeds, SPEED_SMOOTHING_WINDOW)
+            .into_iter()
+            .reduce(f64::max),
+        ascent_m: ascent,
+        descent_m: descent,
+    }
+}
+
+/// Adds up the climbs and drops of the smoothed elevations, leaving out those smaller than `ELEVATION_THRESHOLD_M`.
+///
+/// # Returns
+///
+/// `(Option<f64>, Option<f64>)` -- The ascent and descent in meters, or `None` if there are fewer than two elevations.
+pub(crate) fn climb(elevations: &[f64]) -> (Option<f64>, Option<f64>) {
+    if elevations.len() < 2 {
+        return (None, None);
+    }
+
+    let smoothed = smooth(elevations, ELEVATION_SMOOTHING_WINDOW);
+    let mut reference = smoothed[0];
+    let (mut ascent, mut descent) = (0.0, 0.0);
+    for elevation in smoothed.into_iter().skip(1) {
+        if elevation - reference >= ELEVATION_THRESHOLD_M {
+            ascent += elevation - reference;
+            reference = elevation;
+        } else if reference - elevation >= ELEVATION_THRESHOLD_M {
+            descent += reference - elevation;
+            reference = elevation;
+        }
+    }
+
+    (Some(ascent), Some(descent))
+}
+
+#[cfg(test)]
+/// Tests for the track_stats module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A point `secs` seconds into the activity, `north_m` meters north of the start.
+    fn point(secs: i64, north_m: f64, elevation: f64) -> StatsPoint {
+        let start = Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap();
+        let lat = 45.0 + north_m / EARTH_DEGREE_M;
+        StatsPoint {
+            time: Some(start + chrono::Duration::seconds(secs)),
+            position: Some((lat, 7.0)),
+            elevation: Some(elevation),
+        }
+    }
+
+    /// The length of one degree of latitude in meters.
+    const EARTH_DEGREE_M: f64 =
+        crate::analysis::derived::EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+
+    #[test]
+    /// Test the distance, moving time and speeds, with a stop and a gap between two segments
+    fn test_track_stats() {
+        // 100 m in 20 s, standing still for 60 s, then 100 m in 20 s
+        let first = vec![
+            point(0, 0.0, 100.0),
+            point(10, 50.0, 100.0),
+            point(20, 100.0, 100.0),
+            point(80, 100.0, 100.0),
+            point(90, 150.0, 100.0),
+            point(100, 200.0, 100.0),
+        ];
+        // Starts 1 km further on after a pause, and climbs 10 m over 100 m
+        let second: Vec<StatsPoint> = (0..=10_i32)
+            .map(|i| {
+                point(
+                    200 + i64::from(i) * 10,
+                    1_200.0 + f64::from(i) * 10.0,
+                    100.0 + f64::from(i),
+                )
+            })
+            .collect();
+
+        let stats = track_stats(&[first, second]);
+        assert!((stats.distance_m.unwrap() - 300.0).abs() < 0.1);
+        assert!((stats.moving_time_sec.unwrap() - 140.0).abs() < f64::EPSILON);
+        assert!((stats.avg_speed_ms().unwrap() - 300.0 / 140.0).abs() < 0.01);
+        // The stop is averaged into the speeds around it
+        assert!((stats.max_speed_ms.unwrap() - 4.0).abs() < 0.01);
+        assert!(stats.ascent_m.unwrap() > 5.0 && stats.ascent_m.unwrap() <= 10.0);
+        assert!(stats.descent_m.unwrap().abs() < f64::EPSILON);
+    }
+
+    #[test]
+    /// Test that small ups and downs are treated as noise while real climbs are counted
+    fn test_climb() {
+        let noisy: Vec<f64> = (0..50)
+            .map(|i| if i % 2 == 0 { 100.0 } else { 101.0 })
+            .collect();
+        assert_eq!(climb(&noisy), (Some(0.0), Some(0.0)));
+
+        let mut hill: Vec<f64> = (0..=50).map(f64::from).collect();
+        hill.extend((0..=50).rev().map(f64::from));
+        let (ascent, descent) = climb(&hill);
+        assert!((ascent.unwrap() - 50.0).abs() < 3.0);
+        assert!((descent.unwrap() - 50.0).abs() < 3.0);
+
+        assert_eq!(climb(&[100.0]), (None, None));
+    }
+
+    #[test]
+    /// Test that tracks without positions or times give no numbers rather than zeros
+    fn test_unknown() {
+        let untimed = vec![StatsPoint::default(); 3];
+        assert_eq!(track_stats(&[untimed]), TrackStats::default());
+        assert!(TrackStats::default().avg_speed_ms().is_none());
+    }
+}
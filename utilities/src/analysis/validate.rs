@@ -0,0 +1,338 @@
+//! Checks the totals of the Session messages against the totals worked out again from the records. The device works
+//! out both as it records, so they should agree closely. A large difference means records are missing, or the file
+//! was corrupted or edited after it was recorded, e.g. to change the distance.
+//!
+//! The ascent is worked out from smoothed altitudes, which devices do in their own ways, so it differs by a few
+//! percent more than the distance and the time even in files that are whole.
+
+use std::fmt;
+
+use crate::extract::sliced_session;
+use crate::table::Table;
+use crate::units::{converted, unit_system, Measure};
+use crate::{Duration, FITActivity, FITRecord, FITSession};
+
+/// Differences larger than this (in percent) are reported unless another tolerance is given.
+pub const DEFAULT_VALIDATION_TOLERANCE_PCT: f64 = 5.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The totals that are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidatedTotal {
+    /// The distance in meters.
+    Distance,
+
+    /// The elapsed time in seconds.
+    Duration,
+
+    /// The total ascent in meters.
+    Ascent,
+}
+
+impl ValidatedTotal {
+    /// The smallest difference reported, in meters or seconds, so the percentages of small totals, e.g. a few meters
+    /// of ascent, aren't reported.
+    #[must_use]
+    pub const fn min_difference(self) -> f64 {
+        match self {
+            Self::Distance => 50.0,
+            Self::Duration => 30.0,
+            Self::Ascent => 10.0,
+        }
+    }
+}
+
+impl fmt::Display for ValidatedTotal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Distance => "distance",
+            Self::Duration => "duration",
+            Self::Ascent => "ascent",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A total from the Session message along with the same total worked out from the records.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TotalCheck {
+    /// The total checked.
+    pub total: ValidatedTotal,
+
+    /// The total in the Session message, in meters or seconds.
+    pub session: Option<f64>,
+
+    /// The total worked out from the records, in meters or seconds.
+    pub records: Option<f64>,
+}
+
+impl TotalCheck {
+    /// How much the total from the records differs from the one in the Session message, in percent of the latter.
+    ///
+    /// # Returns
+    ///
+    /// `Option<f64>` -- The difference, or `None` if either total is missing or the session total is zero while the
+    /// records have some.
+    #[must_use]
+    pub fn deviation_pct(&self) -> Option<f64> {
+        let (session, records) = (self.session?, self.records?);
+        if session == 0.0 {
+            return (records == 0.0).then_some(0.0);
+        }
+        Some((records - session) / session * 100.0)
+    }
+
+    /// Whether the totals differ by more than the tolerance, and by more than the smallest difference reported.
+    ///
+    /// # Arguments
+    ///
+    /// `tolerance_pct: f64` -- The largest difference allowed, in percent.
+    #[must_use]
+    pub fn exceeds(&self, tolerance_pct: f64) -> bool {
+        let difference = self
+            .session
+            .zip(self.records)
+            .map_or(0.0, |(session, records)| (records - session).abs());
+        difference > self.total.min_difference()
+            && self
+                .deviation_pct()
+                .is_some_and(|pct| pct.abs() > tolerance_pct)
+    }
+
+    /// The total formatted for the console, as a time or in the unit system of the run.
+    fn format(&self, value: f64) -> String {
+        match self.total {
+            ValidatedTotal::Duration => Duration::from_secs_f64(value).to_string(),
+            ValidatedTotal::Distance => {
+                let decimals = if unit_system().is_some() { 2 } else { 0 };
+                format!("{:.decimals$}", converted(Measure::Distance, value))
+            }
+            ValidatedTotal::Ascent => format!("{:.0}", converted(Measure::Length, value)),
+        }
+    }
+
+    /// The name of the total for the console, with its unit.
+    fn label(&self) -> String {
+        match self.total {
+            ValidatedTotal::Duration => String::from("Time"),
+            ValidatedTotal::Distance => {
+                format!("Distance ({})", Measure::Distance.unit(unit_system()))
+            }
+            ValidatedTotal::Ascent => format!("Ascent ({})", Measure::Length.unit(unit_system())),
+        }
+    }
+}
+
+/// The totals of a session checked against its records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validation {
+    /// The file the session is from.
+    pub filename: String,
+
+    /// The number of the session in the file, starting at 1.
+    pub session_num: Option<u16>,
+
+    /// The number of records the totals were worked out from.
+    pub num_records: usize,
+
+    /// The totals checked.
+    pub checks: Vec<TotalCheck>,
+}
+
+impl Validation {
+    /// Checks the totals of a session against its records.
+    ///
+    /// # Arguments
+    ///
+    /// - `session: &FITSession` -- The session, with the totals from the Session message.
+    /// - `records: &[FITRecord]` -- The records of the session.
+    #[must_use]
+    pub fn new(session: &FITSession, records: &[FITRecord]) -> Self {
+        let worked_out = sliced_session(session, records);
+        let check = |total, value: fn(&FITSession) -> Option<f64>| TotalCheck {
+            total,
+            session: value(session),
+            records: if records.is_empty() {
+                None
+            } else {
+                value(&worked_out)
+            },
+        };
+
+        Self {
+            filename: session.filename.clone().unwrap_or_default(),
+            session_num: session.session_num,
+            num_records: records.len(),
+            checks: vec![
+                check(ValidatedTotal::Distance, |session| {
+                    session.distance.map(|distance| distance.value)
+                }),
+                check(ValidatedTotal::Duration, |session| {
+                    session.duration.map(|duration| duration.0.as_secs_f64())
+                }),
+                check(ValidatedTotal::Ascent, |session| {
+                    session.ascent.map(|ascent| f64::from(ascent.value))
+                }),
+            ],
+        }
+    }
+
+    /// The totals that differ by more than the tolerance, described for the warnings of the file. Differences smaller
+    /// than `ValidatedTotal::min_difference()` are left out, however large in percent.
+    ///
+    /// # Arguments
+    ///
+    /// `tolerance_pct: f64` -- The largest difference allowed, in percent.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<String>` -- A description of each difference, e.g. `The distance differs from the records by -12.3%.`
+    #[must_use]
+    pub fn deviations(&self, tolerance_pct: f64) -> Vec<String> {
+        let session = self
+            .session_num
+            .map_or_else(String::new, |num| format!(" of session {num}"));
+        self.checks
+            .iter()
+            .filter(|check| check.exceeds(tolerance_pct))
+            .filter_map(|check| {
+                let pct = check.deviation_pct()?;
+                Some(format!(
+                    "The {}{session} differs from the records by {pct:+.1}%.",
+                    check.total
+                ))
+            })
+            .collect()
+    }
+
+    /// Prints the totals of the session and the records side by side to stdout, with the difference in percent. The
+    /// differences reported by `deviations()` are marked with `!`.
+    ///
+    /// # Arguments
+    ///
+    /// `tolerance_pct: f64` -- The largest difference allowed, in percent.
+    pub fn print(&self, tolerance_pct: f64) {
+        let mut table = Table::new(&["", "Session", "Records", "%", ""]);
+        for check in &self.checks {
+            let pct = check.deviation_pct();
+            table.add_row(vec![
+                check.label(),
+                check
+                    .session
+                    .map_or_else(String::new, |value| check.format(value)),
+                check
+                    .records
+                    .map_or_else(String::new, |value| check.format(value)),
+                pct.map_or_else(String::new, |pct| format!("{pct:+.1}%")),
+                if check.exceeds(tolerance_pct) {
+                    String::from("!")
+                } else {
+                    String::new()
+                },
+            ]);
+        }
+
+        let session = self
+            .session_num
+            .map_or_else(String::new, |num| format!(", session {num}"));
+        println!(
+            "\nValidation of {}{session} ({} records):\n",
+            self.filename, self.num_records
+        );
+        print!("{table}");
+    }
+}
+
+impl FITActivity {
+    /// Checks the totals of each session against the records of the session. Multisport files have one session per
+    /// sport, and the records are matched to their session by their session number.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<Validation>` -- The checks of each session. Files read in chunks have no records to check against.
+    #[must_use]
+    pub fn validate(&self) -> Vec<Validation> {
+        let sessions = self.summary_sessions();
+        if sessions.len() == 1 {
+            return vec![Validation::new(sessions[0], &self.records)];
+        }
+
+        sessions
+            .into_iter()
+            .map(|session| {
+                let records: Vec<FITRecord> = self
+                    .records
+                    .iter()
+                    .filter(|record| record.session_num == session.session_num)
+                    .cloned()
+                    .collect();
+                Validation::new(session, &records)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+/// Tests for the validate module
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+    use uom::si::f64::Length as Length_f64;
+    use uom::si::length::meter;
+    use uom::si::u16::Length as Length_u16;
+
+    /// A record `secs` into the activity, `meters` from the start.
+    fn record(secs: i64, meters: f64) -> FITRecord {
+        FITRecord {
+            timestamp: Local.timestamp_opt(1_600_000_000 + secs, 0).single(),
+            distance: Some(Length_f64::new::<meter>(meters)),
+            ..FITRecord::default()
+        }
+    }
+
+    #[test]
+    /// Test checking a session with a distance that doesn't match the records
+    fn test_validation() {
+        let session = FITSession {
+            filename: Some("ride.fit".to_string()),
+            distance: Some(Length_f64::new::<meter>(1_200.0)),
+            duration: Some(Duration::from_secs_f64(100.0)),
+            ascent: Some(Length_u16::new::<meter>(0)),
+            ..FITSession::default()
+        };
+        let records: Vec<FITRecord> = (0..=10)
+            .map(|i| record(i * 10, f64::from(i32::try_from(i).unwrap()) * 100.0))
+            .collect();
+
+        let validation = Validation::new(&session, &records);
+        assert_eq!(validation.num_records, 11);
+        let pcts: Vec<Option<f64>> = validation
+            .checks
+            .iter()
+            .map(TotalCheck::deviation_pct)
+            .collect();
+        assert!((pcts[0].unwrap() + 16.667).abs() < 0.01);
+        assert_eq!(pcts[1], Some(0.0));
+        assert_eq!(pcts[2], None);
+
+        let deviations = validation.deviations(DEFAULT_VALIDATION_TOLERANCE_PCT);
+        assert_eq!(
+            deviations,
+            ["The distance differs from the records by -16.7%."]
+        );
+        assert!(validation.deviations(20.0).is_empty());
+
+        // Twelve meters is a lot in percent, but too little to report
+        let short = FITSession {
+            distance: Some(Length_f64::new::<meter>(12.0)),
+            ..session.clone()
+        };
+        let short_records = [record(0, 0.0), record(100, 24.0)];
+        assert!(Validation::new(&short, &short_records)
+            .deviations(DEFAULT_VALIDATION_TOLERANCE_PCT)
+            .is_empty());
+
+        let empty = Validation::new(&session, &[]);
+        assert!(empty.checks.iter().all(|check| check.records.is_none()));
+    }
+}
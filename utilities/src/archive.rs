@@ -0,0 +1,215 @@
+//! Reads the activity files inside zip archives, e.g. the `.zip` Garmin Connect exports each activity as, so they
+//! don't have to be unzipped first. The FIT, GPX and TCX files in an archive are given the names they would have if
+//! the archive had been unzipped where it is, e.g. `downloads/1234_ACTIVITY.fit` for `downloads/1234.zip`, and are
+//! read straight from the archive by `open_input()`. The outputs are written next to the archive.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zip::ZipArchive;
+
+use crate::{get_extension, ActivityFormat};
+
+/// The extension of zip archives.
+const ZIP_EXTENSION: &str = "zip";
+
+/// Where each activity file found in an archive is read from, by the name it was given.
+static ARCHIVE_MEMBERS: Mutex<BTreeMap<String, ArchiveMember>> = Mutex::new(BTreeMap::new());
+
+/// An activity file inside a zip archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ArchiveMember {
+    /// The archive holding the file.
+    archive: PathBuf,
+
+    /// The name of the file in the archive, which may include directories.
+    name: String,
+}
+
+/// Checks whether the file is a zip archive, i.e. has the `.zip` extension, whatever the case.
+///
+/// # Arguments
+///
+/// `filename: &str` -- The file name to check.
+///
+/// # Returns
+///
+/// `bool` -- `true` if the file is a zip archive.
+#[must_use]
+pub fn is_archive(filename: &str) -> bool {
+    get_extension(filename) == ZIP_EXTENSION
+}
+
+/// Checks whether the file was found inside a zip archive by `expand_archives()`, rather than being on disk.
+///
+/// # Arguments
+///
+/// `filename: &str` -- The file name to check.
+///
+/// # Returns
+///
+/// `bool` -- `true` if the file is read from an archive.
+#[must_use]
+pub fn is_archive_member(filename: &str) -> bool {
+    archive_member(filename).is_some()
+}
+
+/// Replaces the zip archives among the files with the FIT, GPX and TCX files inside them, in the order they are
+/// stored, so they can be read like any other file. Other files are kept as they are. Archives that can't be read
+/// are left out with a warning, so one broken download doesn't stop the rest.
+///
+/// # Arguments
+///
+/// `filenames: I` -- The files given, e.g. on the command line.
+///
+/// # Returns
+///
+/// `Vec<String>` -- The files to read.
+pub fn expand_archives<'a, I>(filenames: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut expanded = Vec::new();
+    for filename in filenames {
+        if !is_archive(filename) {
+            expanded.push(filename.to_string());
+            continue;
+        }
+
+        match archive_files(filename) {
+            Ok(files) => {
+                if files.is_empty() {
+                    log::warn!("{filename}: No FIT, GPX or TCX files in the archive.");
+                }
+                expanded.extend(files);
+            }
+            Err(err) => log::warn!("{filename}: Unable to read the archive. Skipping. {err}"),
+        }
+    }
+
+    expanded
+}
+
+/// Lists the activity files in the archive, and remembers where to read each of them from.
+fn archive_files(filename: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(filename)?))?;
+    let mut files = Vec::new();
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        let name = entry.name().to_string();
+        if !entry.is_file() || ActivityFormat::from_filename(&name).is_none() {
+            continue;
+        }
+
+        let Some(file_name) = Path::new(&name).file_name() else {
+            continue;
+        };
+        let path = Path::new(filename)
+            .with_file_name(file_name)
+            .to_string_lossy()
+            .to_string();
+        log::trace!("archive::archive_files() -- {name} in {filename} read as {path}");
+        if let Ok(mut members) = ARCHIVE_MEMBERS.lock() {
+            members.insert(
+                path.clone(),
+                ArchiveMember {
+                    archive: PathBuf::from(filename),
+                    name,
+                },
+            );
+        }
+        files.push(path);
+    }
+
+    Ok(files)
+}
+
+/// Where to read the file from, if it is inside an archive.
+fn archive_member(filename: &str) -> Option<ArchiveMember> {
+    ARCHIVE_MEMBERS
+        .lock()
+        .ok()
+        .and_then(|members| members.get(filename).cloned())
+}
+
+/// Reads the file out of the archive it was found in.
+///
+/// # Arguments
+///
+/// `filename: &str` -- The name the file was given by `expand_archives()`.
+///
+/// # Returns
+///
+/// `Result<Option<Vec<u8>>, Box<dyn Error>>` -- The contents of the file as stored, or `None` if the file isn't in
+/// an archive.
+///
+/// # Errors
+///
+/// Reading the archive may fail.
+pub(crate) fn read_archive_member(filename: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let Some(member) = archive_member(filename) else {
+        return Ok(None);
+    };
+
+    let mut archive = ZipArchive::new(BufReader::new(File::open(&member.archive)?))?;
+    let mut entry = archive.by_name(&member.name)?;
+    let mut contents = Vec::with_capacity(usize::try_from(entry.size()).unwrap_or_default());
+    entry.read_to_end(&mut contents)?;
+    Ok(Some(contents))
+}
+
+#[cfg(test)]
+/// Tests for the archive module
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    /// Test finding and reading the activity files in an archive
+    fn test_expand_archives() {
+        let dir = std::env::temp_dir().join("fitutils_test_expand_archives");
+        std::fs::create_dir_all(&dir).unwrap();
+        let zip = dir.join("1234.ZIP");
+        let fit = std::fs::read("../data/rowing.fit").unwrap();
+
+        let mut writer = ZipWriter::new(File::create(&zip).unwrap());
+        writer
+            .start_file("1234_ACTIVITY.fit", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&fit).unwrap();
+        writer
+            .start_file("notes/readme.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"Not an activity").unwrap();
+        writer
+            .start_file("notes/walk.gpx", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"<gpx/>").unwrap();
+        writer.finish().unwrap();
+
+        let zip_name = zip.to_string_lossy().to_string();
+        let files = expand_archives(["../data/running.gpx", zip_name.as_str(), "missing.zip"]);
+        let member = dir.join("1234_ACTIVITY.fit").to_string_lossy().to_string();
+        assert_eq!(
+            files,
+            vec![
+                "../data/running.gpx".to_string(),
+                member.clone(),
+                dir.join("walk.gpx").to_string_lossy().to_string()
+            ]
+        );
+        assert!(is_archive(&zip_name));
+        assert!(is_archive_member(&member));
+        assert!(!is_archive_member("../data/running.gpx"));
+        assert_eq!(read_archive_member(&member).unwrap(), Some(fit));
+        assert_eq!(read_archive_member("../data/running.gpx").unwrap(), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+//! Writes files so they are either complete or not there at all. The data goes to a temporary file next to the file
+//! asked for, which is renamed to it once everything has been written. A crash or an error half way leaves the old
+//! file, if any, as it was, and other programs, e.g. a sync client, never see a half-written file.
+//!
+//! The temporary files are named after the file asked for, the process and a counter, so several runs can write to
+//! the same directory at the same time.
+//!
+//! Files written while processing a file that can time out are held back until the file has finished, see the `cancel`
+//! module, and writing them fails once the file has timed out.
+//!
+//! The files can also be flushed to disk before they are renamed using `Settings::set_sync_outputs()`, so they survive
+//! a power failure. This is slower, so it's off by default.
+
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::cancel::{current_flag, hold_back, CancelFlag};
+use crate::Settings;
+
+/// Tells the temporary files of this process apart.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+impl Settings {
+    /// Sets whether the files are flushed to disk before they replace the old ones.
+    ///
+    /// # Arguments
+    ///
+    /// `sync: bool` -- Flush the files to disk.
+    pub fn set_sync_outputs(&mut self, sync: bool) {
+        self.sync_outputs = sync;
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A file that only appears under its name once `commit()` is called. Until then the data goes to a temporary file,
+/// which is removed if the `AtomicFile` is dropped without being committed.
+#[derive(Debug)]
+pub struct AtomicFile {
+    /// The temporary file being written.
+    file: File,
+
+    /// The name of the temporary file.
+    temp_path: PathBuf,
+
+    /// The name the file gets when it's committed.
+    path: PathBuf,
+
+    /// Whether the file has been renamed to its name, or handed over to be renamed once the processing finishes.
+    committed: bool,
+
+    /// Set if the file being processed times out.
+    cancel: Option<Arc<CancelFlag>>,
+}
+
+impl AtomicFile {
+    /// Starts writing a file. The directory it goes in must exist.
+    ///
+    /// # Arguments
+    ///
+    /// `path: &Path` -- The name the file gets when it's committed.
+    ///
+    /// # Errors
+    ///
+    /// Creating the temporary file may fail.
+    pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| format!("{}: Not a file name.", path.display()))?;
+        let temp_path = path.with_file_name(format!(
+            ".{}.{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+
+        Ok(Self {
+            file,
+            temp_path,
+            path: path.to_path_buf(),
+            committed: false,
+            cancel: current_flag(),
+        })
+    }
+
+    /// Renames the temporary file to its name, replacing the file that was there, if any. The file is flushed to
+    /// disk first if set with `Settings::set_sync_outputs()`. If the file is being processed so it can time out, the
+    /// renaming waits until the processing has finished.
+    ///
+    /// # Errors
+    ///
+    /// Flushing or renaming the file may fail. The temporary file is removed if so.
+    pub fn commit(mut self) -> Result<(), Box<dyn Error>> {
+        self.file.flush()?;
+        if Settings::current().sync_outputs {
+            self.file.sync_all()?;
+        }
+        if let Some((temp_path, path)) = hold_back(self.temp_path.clone(), self.path.clone()) {
+            fs::rename(temp_path, path)?;
+        }
+        self.committed = true;
+
+        Ok(())
+    }
+
+    /// The name the file gets when it's committed.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+        {
+            return Err(io::Error::other("Cancelled."));
+        }
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            if let Err(err) = fs::remove_file(&self.temp_path) {
+                log::debug!(
+                    "AtomicFile::drop() -- Unable to remove {}: {err}",
+                    self.temp_path.display()
+                );
+            }
+        }
+    }
+}
+
+/// Writes a file in one go, so it's either complete or not there at all.
+///
+/// # Arguments
+///
+/// - `path: &Path` -- The file to write. The directory it goes in must exist.
+/// - `write: F` -- Writes the contents to the file.
+///
+/// # Returns
+///
+/// `Result<T, Box<dyn Error>>` -- What `write` returned.
+///
+/// # Errors
+///
+/// Creating, writing or renaming the file may fail. Nothing is written under the name if so.
+pub fn write_atomically<T, F>(path: &Path, write: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(&mut AtomicFile) -> Result<T, Box<dyn Error>>,
+{
+    let mut file = AtomicFile::create(path)?;
+    let result = write(&mut file)?;
+    file.commit()?;
+
+    Ok(result)
+}
+
+#[cfg(test)]
+/// Tests for the atomic_file module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that the file only appears when committed, and that nothing is left behind otherwise
+    fn test_atomic_file() {
+        let dir = std::env::temp_dir().join("fitutils_test_atomic_file");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("summary.csv");
+        fs::write(&path, "old").unwrap();
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        file.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+
+        let failed: Result<(), _> = write_atomically(&path, |file| {
+            file.write_all(b"half")?;
+            Err("failed half way".into())
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,16 +2,18 @@ use clap::parser::ValueSource;
 use env_logger::Builder;
 use log::LevelFilter;
 
+use crate::config::config_log_level;
+
 #[must_use]
 pub fn build_log(cli_args: &clap::ArgMatches) -> Builder {
     let mut logbuilder = Builder::new();
 
-    // Figure out what log level to use.
+    // Figure out what log level to use. The one from the config file is used unless -d or -q is given.
     if cli_args.value_source("quiet") == Some(ValueSource::CommandLine) {
         logbuilder.filter_level(LevelFilter::Off);
     } else {
         match cli_args.get_count("debug") {
-            0 => logbuilder.filter_level(LevelFilter::Info),
+            0 => logbuilder.filter_level(config_log_level().unwrap_or(LevelFilter::Info)),
             1 => logbuilder.filter_level(LevelFilter::Debug),
             _ => logbuilder.filter_level(LevelFilter::Trace),
         };
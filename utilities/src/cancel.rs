@@ -0,0 +1,250 @@
+//! Stops processing a file that has timed out. `process_with_timeout()` processes each file in a thread of its own
+//! inside `run_cancellable()`, and cancels it when the timeout is up. The parsing and exporting loops check for this
+//! with `check_cancelled()` and stop with an error, and the detail files written in the thread are held back until the
+//! file has finished, so a file that timed out leaves no detail files behind. Without a timeout nothing is held back.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use crate::processing_error::ProcessingError;
+
+/// The file is still being processed.
+const RUNNING: u8 = 0;
+
+/// The file was processed in time, and its detail files have been kept.
+const FINISHED: u8 = 1;
+
+/// The file timed out, and its detail files have been thrown away.
+const CANCELLED: u8 = 2;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Shared between the thread processing a file and the thread waiting for it. Whichever of `finish()` and `cancel()`
+/// is called first wins, so a file is either processed in time with all its detail files, or timed out with none.
+#[derive(Debug, Default)]
+pub(crate) struct CancelFlag {
+    /// `RUNNING`, `FINISHED` or `CANCELLED`.
+    state: AtomicU8,
+}
+
+impl CancelFlag {
+    /// Cancels the processing, unless it has finished already.
+    ///
+    /// # Returns
+    ///
+    /// `bool` -- `true` if the processing was cancelled, `false` if it finished first.
+    pub(crate) fn cancel(&self) -> bool {
+        self.state
+            .compare_exchange(RUNNING, CANCELLED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Marks the processing as finished, unless it has been cancelled already.
+    ///
+    /// # Returns
+    ///
+    /// `bool` -- `true` if the processing finished, `false` if it was cancelled first.
+    fn finish(&self) -> bool {
+        self.state
+            .compare_exchange(RUNNING, FINISHED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Whether the processing has been cancelled.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::Acquire) == CANCELLED
+    }
+}
+
+/// The error returned by `check_cancelled()`.
+#[derive(Debug)]
+pub(crate) struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cancelled.")
+    }
+}
+
+impl Error for Cancelled {}
+
+/// The file being processed on this thread.
+struct Cancellable {
+    /// Set when the file times out.
+    flag: Arc<CancelFlag>,
+
+    /// The detail files written so far, as the temporary file and the name it gets once the file has finished.
+    pending: Vec<(PathBuf, PathBuf)>,
+}
+
+thread_local! {
+    /// The file being processed on this thread, if it can be cancelled.
+    static CURRENT: RefCell<Option<Cancellable>> = const { RefCell::new(None) };
+}
+
+/// Throws away the held back detail files if the thread stops without finishing, e.g. if the processing panics.
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if let Some(current) = CURRENT.with(|current| current.borrow_mut().take()) {
+            discard(&current.pending);
+        }
+    }
+}
+
+/// Removes the temporary files.
+fn discard(pending: &[(PathBuf, PathBuf)]) {
+    for (temp_path, _) in pending {
+        if let Err(err) = std::fs::remove_file(temp_path) {
+            log::debug!(
+                "cancel::discard() -- Unable to remove {}: {err}",
+                temp_path.display()
+            );
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Processes a file on the current thread so it can be cancelled with the flag. The detail files written are held back
+/// until `process` returns, and are then renamed to their names, or removed if the flag was cancelled first.
+///
+/// # Arguments
+///
+/// - `flag: Arc<CancelFlag>` -- Cancelled by the thread waiting for the file when the timeout is up.
+/// - `process: F` -- Processes the file.
+///
+/// # Returns
+///
+/// `Result<T, ProcessingError>` -- What `process` returned.
+///
+/// # Errors
+///
+/// The processing may fail, and renaming the detail files may fail. Returns `ProcessingError::Stopped` if the processing
+/// was cancelled.
+pub(crate) fn run_cancellable<T, F>(flag: Arc<CancelFlag>, process: F) -> Result<T, ProcessingError>
+where
+    F: FnOnce() -> Result<T, ProcessingError>,
+{
+    CURRENT.with(|current| {
+        *current.borrow_mut() = Some(Cancellable {
+            flag: Arc::clone(&flag),
+            pending: Vec::new(),
+        });
+    });
+    let guard = Guard;
+    let outcome = process();
+
+    let pending = CURRENT
+        .with(|current| current.borrow_mut().take())
+        .map(|current| current.pending)
+        .unwrap_or_default();
+    drop(guard);
+
+    if !flag.finish() {
+        discard(&pending);
+        return Err(ProcessingError::Stopped);
+    }
+    for (temp_path, path) in &pending {
+        std::fs::rename(temp_path, path)?;
+    }
+
+    outcome
+}
+
+/// Checks whether the file being processed on this thread has been cancelled.
+///
+/// # Errors
+///
+/// Returns `Cancelled` if the file has timed out, so the processing stops.
+pub(crate) fn check_cancelled() -> Result<(), Box<dyn Error>> {
+    let cancelled = CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .is_some_and(|current| current.flag.is_cancelled())
+    });
+    if cancelled {
+        Err(Box::new(Cancelled))
+    } else {
+        Ok(())
+    }
+}
+
+/// The flag of the file being processed on this thread, if it can be cancelled.
+pub(crate) fn current_flag() -> Option<Arc<CancelFlag>> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|current| Arc::clone(&current.flag))
+    })
+}
+
+/// Holds back a detail file until the file being processed on this thread has finished.
+///
+/// # Arguments
+///
+/// - `temp_path: PathBuf` -- The temporary file written.
+/// - `path: PathBuf` -- The name it gets once the file has finished.
+///
+/// # Returns
+///
+/// `Option<(PathBuf, PathBuf)>` -- The paths given back if nothing is being held back on this thread, so the file can
+/// be renamed straight away.
+pub(crate) fn hold_back(temp_path: PathBuf, path: PathBuf) -> Option<(PathBuf, PathBuf)> {
+    CURRENT.with(|current| match current.borrow_mut().as_mut() {
+        Some(current) => {
+            current.pending.push((temp_path, path));
+            None
+        }
+        None => Some((temp_path, path)),
+    })
+}
+
+#[cfg(test)]
+/// Tests for the cancel module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that the detail files are kept when the file finishes, and thrown away when it's cancelled first
+    fn test_run_cancellable() {
+        let dir = std::env::temp_dir().join("fitutils_test_cancel");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str| {
+            let temp_path = dir.join(format!(".{name}.tmp"));
+            std::fs::write(&temp_path, name).unwrap();
+            assert!(hold_back(temp_path, dir.join(name)).is_none());
+        };
+
+        let flag = Arc::new(CancelFlag::default());
+        let outcome = run_cancellable(Arc::clone(&flag), || {
+            write("kept.csv");
+            Ok(check_cancelled()?)
+        });
+        assert!(outcome.is_ok());
+        assert!(!flag.cancel());
+        assert!(dir.join("kept.csv").exists());
+
+        let flag = Arc::new(CancelFlag::default());
+        let outcome = run_cancellable(Arc::clone(&flag), || {
+            write("discarded.csv");
+            assert!(flag.cancel());
+            Ok(check_cancelled()?)
+        });
+        assert!(outcome.is_err());
+        assert!(!dir.join("discarded.csv").exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        // Nothing is held back outside run_cancellable()
+        assert!(check_cancelled().is_ok());
+        assert!(hold_back(dir.join(".a.tmp"), dir.join("a.csv")).is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
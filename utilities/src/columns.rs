@@ -0,0 +1,1352 @@
+//! Keeps the names and order of the CSV columns stable across versions, so pipelines reading the files don't break
+//! when fields are added to the structs or moved around. New columns are only ever added to the latest version.
+//!
+//! The column version is set once for the whole run using `Settings::set_columns_version()`. Older versions leave out
+//! the columns added since, and put the rest back in the order they had in that version.
+//!
+//! The header of the latest version is worked out from the fields serde writes, so it always matches the values. The
+//! FIT structs name their fields without the unit, so their names are normalized to the column names first, e.g.
+//! `distance` to `distance_m`. The columns of each version are locked in below, and the tests check that the structs
+//! still serialize to them.
+
+use csv::{ReaderBuilder, StringRecord, Writer, WriterBuilder};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::csv_format::{csv_format, csv_writer, CsvFormat};
+use crate::csv_schema::{serde_fields, SerdeField};
+use crate::units::UnitColumns;
+use crate::{
+    ActivityFormat, FITDeviceInfo, FITEvent, FITLap, FITRecord, FITSession, FITSplit, GPXMetadata,
+    GPXTrack, GPXWaypoint, HrScatter, MeanMax, Settings, Split, TCXActivity, TCXLap, TCXTrackpoint,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The versions of the CSV columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ColumnsVersion {
+    /// The columns written before the UUIDs, GPS checks, pacing and recording interval were added.
+    V1,
+    /// The current columns.
+    #[default]
+    V2,
+}
+
+impl ColumnsVersion {
+    /// The latest version, which has all the columns.
+    pub const LATEST: Self = Self::V2;
+
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 2] = ["1", "2"];
+}
+
+impl FromStr for ColumnsVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().trim_start_matches(['v', 'V']) {
+            "1" => Ok(Self::V1),
+            "2" => Ok(Self::V2),
+            _ => Err(format!("Unknown columns version {s}. Use 1 or 2.")),
+        }
+    }
+}
+
+impl fmt::Display for ColumnsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V1 => write!(f, "1"),
+            Self::V2 => write!(f, "2"),
+        }
+    }
+}
+
+impl Settings {
+    /// Sets the column version used by all the CSV exports.
+    ///
+    /// # Arguments
+    ///
+    /// `version: ColumnsVersion` -- The version of the columns to write.
+    pub fn set_columns_version(&mut self, version: ColumnsVersion) {
+        self.columns_version = version;
+    }
+}
+
+/// The column version used by the CSV exports.
+pub(crate) fn columns_version() -> ColumnsVersion {
+    Settings::current().columns_version
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The CSV files written, each with its own set of columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvFile {
+    /// The FIT summary, one session per row.
+    FitSummary,
+    /// The laps of a FIT file.
+    FitLaps,
+    /// The records of a FIT file.
+    FitRecords,
+    /// The events of a FIT file.
+    FitEvents,
+    /// The devices of a FIT file.
+    FitDevices,
+    /// The splits and split summaries recorded by the device in a FIT file.
+    FitDeviceSplits,
+    /// The GPX summary, one file per row.
+    GpxSummary,
+    /// The tracks of a GPX file.
+    GpxTracks,
+    /// The waypoints of a GPX file.
+    GpxWaypoints,
+    /// The TCX summary, one file per row.
+    TcxSummary,
+    /// The laps of a TCX file.
+    TcxLaps,
+    /// The trackpoints of a TCX file.
+    TcxTrackpoints,
+    /// The kilometer or mile splits of an activity.
+    Splits,
+    /// The mean-maximal power and heart rate curve of an activity, or of many activities combined.
+    MeanMax,
+    /// The heart rate against the speed and power over each half minute of an activity.
+    HrScatter,
+}
+
+impl CsvFile {
+    /// All the CSV files.
+    pub const ALL: [Self; 15] = [
+        Self::FitSummary,
+        Self::FitLaps,
+        Self::FitRecords,
+        Self::FitEvents,
+        Self::FitDevices,
+        Self::FitDeviceSplits,
+        Self::GpxSummary,
+        Self::GpxTracks,
+        Self::GpxWaypoints,
+        Self::TcxSummary,
+        Self::TcxLaps,
+        Self::TcxTrackpoints,
+        Self::Splits,
+        Self::MeanMax,
+        Self::HrScatter,
+    ];
+
+    /// The CSV files written for the activity files of the format given.
+    ///
+    /// # Arguments
+    ///
+    /// `format: ActivityFormat` -- The format of the activity files.
+    #[must_use]
+    pub const fn of_format(format: ActivityFormat) -> &'static [Self] {
+        match format {
+            ActivityFormat::Fit => &[
+                Self::FitSummary,
+                Self::FitLaps,
+                Self::FitRecords,
+                Self::FitEvents,
+                Self::FitDevices,
+                Self::FitDeviceSplits,
+                Self::Splits,
+                Self::MeanMax,
+                Self::HrScatter,
+            ],
+            ActivityFormat::Gpx => &[
+                Self::GpxSummary,
+                Self::GpxTracks,
+                Self::GpxWaypoints,
+                Self::Splits,
+                Self::MeanMax,
+                Self::HrScatter,
+            ],
+            ActivityFormat::Tcx => &[
+                Self::TcxSummary,
+                Self::TcxLaps,
+                Self::TcxTrackpoints,
+                Self::Splits,
+                Self::MeanMax,
+                Self::HrScatter,
+            ],
+        }
+    }
+
+    /// What the names of the detail files end with, e.g. `records.csv`. The summaries are named on the command line.
+    #[must_use]
+    pub const fn suffix(self) -> Option<&'static str> {
+        match self {
+            Self::FitSummary | Self::GpxSummary | Self::TcxSummary => None,
+            Self::FitLaps | Self::TcxLaps => Some("laps.csv"),
+            Self::FitRecords => Some("records.csv"),
+            Self::FitEvents => Some("events.csv"),
+            Self::FitDevices => Some("devices.csv"),
+            Self::FitDeviceSplits => Some("device_splits.csv"),
+            Self::GpxTracks => Some("tracks.csv"),
+            Self::GpxWaypoints => Some("waypoints.csv"),
+            Self::TcxTrackpoints => Some("trackpoints.csv"),
+            Self::Splits => Some("splits.csv"),
+            Self::MeanMax => Some("mean_max.csv"),
+            Self::HrScatter => Some("hr_scatter.csv"),
+        }
+    }
+
+    /// The names of the columns locked in for the file in the version given. Files that didn't exist in the version
+    /// have the latest columns.
+    ///
+    /// # Arguments
+    ///
+    /// `version: ColumnsVersion` -- The version of the columns.
+    #[must_use]
+    pub const fn columns(self, version: ColumnsVersion) -> &'static [&'static str] {
+        match (self, version) {
+            (Self::FitSummary, ColumnsVersion::V1) => FIT_SUMMARY_V1,
+            (Self::FitSummary, ColumnsVersion::V2) => FIT_SUMMARY_V2,
+            (Self::FitLaps, ColumnsVersion::V1) => FIT_LAPS_V1,
+            (Self::FitLaps, ColumnsVersion::V2) => FIT_LAPS_V2,
+            (Self::FitRecords, ColumnsVersion::V1) => FIT_RECORDS_V1,
+            (Self::FitRecords, ColumnsVersion::V2) => FIT_RECORDS_V2,
+            (Self::FitEvents, _) => FIT_EVENTS_V2,
+            (Self::FitDevices, _) => FIT_DEVICES_V2,
+            (Self::FitDeviceSplits, _) => FIT_DEVICE_SPLITS_V2,
+            (Self::GpxSummary, ColumnsVersion::V1) => GPX_SUMMARY_V1,
+            (Self::GpxSummary, ColumnsVersion::V2) => GPX_SUMMARY_V2,
+            (Self::GpxTracks, ColumnsVersion::V1) => GPX_TRACKS_V1,
+            (Self::GpxTracks, ColumnsVersion::V2) => GPX_TRACKS_V2,
+            (Self::GpxWaypoints, ColumnsVersion::V1) => GPX_WAYPOINTS_V1,
+            (Self::GpxWaypoints, ColumnsVersion::V2) => GPX_WAYPOINTS_V2,
+            (Self::TcxSummary, ColumnsVersion::V1) => TCX_SUMMARY_V1,
+            (Self::TcxSummary, ColumnsVersion::V2) => TCX_SUMMARY_V2,
+            (Self::TcxLaps, _) => TCX_LAPS_V2,
+            (Self::TcxTrackpoints, ColumnsVersion::V1) => TCX_TRACKPOINTS_V1,
+            (Self::TcxTrackpoints, ColumnsVersion::V2) => TCX_TRACKPOINTS_V2,
+            (Self::Splits, _) => SPLITS_V2,
+            (Self::MeanMax, _) => MEAN_MAX_V2,
+            (Self::HrScatter, _) => HR_SCATTER_V2,
+        }
+    }
+
+    /// The header written for the file in the version given. The latest version has the columns serde writes, and
+    /// the older ones have the columns locked in for them.
+    ///
+    /// # Arguments
+    ///
+    /// `version: ColumnsVersion` -- The version of the columns.
+    pub(crate) fn header(self, version: ColumnsVersion) -> Vec<String> {
+        if version == ColumnsVersion::LATEST {
+            self.latest_columns()
+                .into_iter()
+                .map(|column| column.name)
+                .collect()
+        } else {
+            self.columns(version)
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        }
+    }
+
+    /// The columns of the latest version, with what they hold, in the order serde writes them. The names are
+    /// normalized to the column names.
+    pub(crate) fn latest_columns(self) -> Vec<SerdeField> {
+        let renamed = self.renamed();
+        let mut fields = match self {
+            Self::FitSummary => serde_fields(&FITSession::default()),
+            Self::FitLaps => serde_fields(&FITLap::default()),
+            Self::FitRecords => serde_fields(&FITRecord::default()),
+            Self::FitEvents => serde_fields(&FITEvent::default()),
+            Self::FitDevices => serde_fields(&FITDeviceInfo::default()),
+            Self::FitDeviceSplits => serde_fields(&FITSplit::default()),
+            Self::GpxSummary => serde_fields(&GPXMetadata::default()),
+            Self::GpxTracks => serde_fields(&GPXTrack::default()),
+            Self::GpxWaypoints => serde_fields(&GPXWaypoint::default()),
+            Self::TcxSummary => serde_fields(&TCXActivity::default()),
+            Self::TcxLaps => serde_fields(&TCXLap::default()),
+            Self::TcxTrackpoints => serde_fields(&TCXTrackpoint::default()),
+            Self::Splits => serde_fields(&Split::default()),
+            Self::MeanMax => serde_fields(&MeanMax::default()),
+            Self::HrScatter => serde_fields(&HrScatter::default()),
+        };
+        for field in &mut fields {
+            if let Some((_, column)) = renamed.iter().find(|(name, _)| *name == field.name) {
+                field.name = (*column).to_string();
+            }
+        }
+        fields
+    }
+
+    /// The fields whose columns are named differently, along with the names of the columns.
+    const fn renamed(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::FitSummary => FIT_SUMMARY_RENAMED,
+            Self::FitLaps => FIT_LAPS_RENAMED,
+            Self::FitRecords => FIT_RECORDS_RENAMED,
+            Self::TcxLaps => TCX_LAPS_RENAMED,
+            _ => &[],
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes the rows of a CSV file with the columns of the version set by `Settings::set_columns_version()`. The rows are
+/// serialized with the latest columns, and the columns not in the version are left out. The distances, lengths and
+/// speeds are converted to the unit system set by `Settings::set_unit_system()`, if any, and the values are separated
+/// and the decimals written as set by `Settings::set_csv_format()`.
+#[derive(Debug)]
+pub(crate) struct ColumnWriter<W: Write> {
+    /// The CSV writer, without headers since they are written from the list of columns.
+    writer: Writer<W>,
+
+    /// The columns written, with their names and values in the unit system of the run.
+    columns: UnitColumns,
+
+    /// The positions of the latest columns to keep, or `None` to keep them all.
+    keep: Option<Vec<usize>>,
+
+    /// Whether the header has been written.
+    header_written: bool,
+
+    /// The delimiter and decimal separator of the run.
+    format: CsvFormat,
+}
+
+impl<W: Write> ColumnWriter<W> {
+    /// Creates a writer for the file given. The header is written with the first row, or by `write_header()`.
+    ///
+    /// # Arguments
+    ///
+    /// - `writer: W` -- Where the CSV is written.
+    /// - `file: CsvFile` -- The file being written, which decides the columns.
+    pub(crate) fn new(writer: W, file: CsvFile) -> Self {
+        Self::with_version(writer, file, columns_version())
+    }
+
+    /// Creates a writer for the file given, with the columns of the version given.
+    ///
+    /// # Arguments
+    ///
+    /// - `writer: W` -- Where the CSV is written.
+    /// - `file: CsvFile` -- The file being written.
+    /// - `version: ColumnsVersion` -- The version of the columns to write.
+    pub(crate) fn with_version(writer: W, file: CsvFile, version: ColumnsVersion) -> Self {
+        let latest = file.header(ColumnsVersion::LATEST);
+        let columns = file.header(version);
+        let keep = (columns != latest).then(|| {
+            columns
+                .iter()
+                .filter_map(|column| latest.iter().position(|c| c == column))
+                .collect()
+        });
+
+        Self {
+            writer: csv_writer(writer),
+            columns: UnitColumns::new(&columns),
+            keep,
+            header_written: false,
+            format: csv_format(),
+        }
+    }
+
+    /// Writes the header, if it hasn't been written already. Use this for files that have a header even if there
+    /// are no rows.
+    ///
+    /// # Errors
+    ///
+    /// Writing may fail.
+    pub(crate) fn write_header(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.header_written {
+            self.writer.write_record(&self.columns.names)?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    /// Leaves out the header, for adding rows to a file that has one already.
+    #[cfg(feature = "fs")]
+    pub(crate) fn skip_header(&mut self) {
+        self.header_written = true;
+    }
+
+    /// Writes a row, leaving out the columns that aren't in the version and converting the units.
+    ///
+    /// # Arguments
+    ///
+    /// `row: T` -- The row to write, serialized with the latest columns.
+    ///
+    /// # Errors
+    ///
+    /// Serializing or writing the row may fail.
+    pub(crate) fn serialize<T: Serialize>(&mut self, row: T) -> Result<(), Box<dyn Error>> {
+        self.write_header()?;
+
+        if self.keep.is_none() && !self.columns.converts() && !self.format.decimal_comma {
+            self.writer.serialize(row)?;
+            return Ok(());
+        }
+
+        // Serialize the row on its own, read the fields back, and write the ones to keep
+        let mut buffer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        buffer.serialize(row)?;
+        let bytes = buffer.into_inner().map_err(|e| e.to_string())?;
+        let mut record = StringRecord::new();
+        ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(bytes.as_slice())
+            .read_record(&mut record)?;
+
+        let cells: Vec<&str> = match &self.keep {
+            Some(keep) => keep
+                .iter()
+                .map(|&i| record.get(i).unwrap_or_default())
+                .collect(),
+            None => record.iter().collect(),
+        };
+        self.writer
+            .write_record(cells.into_iter().enumerate().map(|(i, cell)| {
+                self.format
+                    .localize(self.columns.convert(i, cell))
+                    .into_owned()
+            }))?;
+        Ok(())
+    }
+
+    /// Flushes the rows written so far.
+    ///
+    /// # Errors
+    ///
+    /// Writing may fail.
+    pub(crate) fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// The header line of the file, as `ColumnWriter::write_header()` writes it, for checking that rows added to a file
+/// that has a header already go in the same columns.
+///
+/// # Errors
+///
+/// Writing the header may fail.
+#[cfg(feature = "fs")]
+pub(crate) fn header_line(file: CsvFile) -> Result<String, Box<dyn Error>> {
+    let mut header = ColumnWriter::new(Vec::new(), file);
+    header.write_header()?;
+    let bytes = header.writer.into_inner().map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(bytes)?.trim_end().to_string())
+}
+
+/// The fields of `FITSession` written to the FIT summary columns of other names.
+const FIT_SUMMARY_RENAMED: &[(&str, &str)] = &[
+    ("cadence_avg", "cadence_avg_bpm"),
+    ("cadence_max", "cadence_max_bpm"),
+    ("heartrate_avg", "heartrate_avg_bpm"),
+    ("heartrate_max", "heartrate_max_bpm"),
+    ("heartrate_min", "heartrate_min_bpm"),
+    ("speed_avg", "speed_avg_ms"),
+    ("speed_max", "speed_max_ms"),
+    ("power_avg", "power_avg_w"),
+    ("power_max", "power_max_w"),
+    ("power_threshold", "power_threshold_w"),
+    ("nec_lat", "nec_lat_deg"),
+    ("nec_lon", "nec_lon_deg"),
+    ("swc_lat", "swc_lat_deg"),
+    ("swc_lon", "swc_lon_deg"),
+    ("ascent", "ascent_m"),
+    ("descent", "descent_m"),
+    ("distance", "distance_m"),
+    ("gps_distance", "gps_distance_m"),
+    ("duration", "duration_sec"),
+    ("duration_active", "duration_active_sec"),
+    ("duration_moving", "duration_moving_sec"),
+    ("hr_zone_0_secs", "time_in_hr_zone_0_sec"),
+    ("hr_zone_1_secs", "time_in_hr_zone_1_sec"),
+    ("hr_zone_2_secs", "time_in_hr_zone_2_sec"),
+    ("hr_zone_3_secs", "time_in_hr_zone_3_sec"),
+    ("hr_zone_4_secs", "time_in_hr_zone_4_sec"),
+    ("utc_offset", "utc_offset_sec"),
+    ("total_timer_time", "total_timer_time_sec"),
+    ("speed_first_half", "speed_first_half_ms"),
+    ("speed_second_half", "speed_second_half_ms"),
+    ("speed_heat_adjusted", "speed_heat_adjusted_ms"),
+    ("power_normalized", "power_normalized_w"),
+];
+
+/// The fields of `FITLap` written to the FIT laps columns of other names.
+const FIT_LAPS_RENAMED: &[(&str, &str)] = &[
+    ("cadence_avg", "cadence_avg_bpm"),
+    ("cadence_max", "cadence_max_bpm"),
+    ("heartrate_min", "heartrate_min_bpm"),
+    ("heartrate_avg", "heartrate_avg_bpm"),
+    ("heartrate_max", "heartrate_max_bpm"),
+    ("speed_avg", "speed_avg_ms"),
+    ("speed_max", "speed_max_ms"),
+    ("power_avg", "power_avg_w"),
+    ("power_max", "power_max_w"),
+    ("stance_time_avg", "stance_time_avg_sec"),
+    ("ascent", "ascent_m"),
+    ("descent", "descent_m"),
+    ("distance", "distance_m"),
+    ("duration", "duration_secs"),
+    ("duration_active", "duration_active_sec"),
+    ("duration_moving", "duration_moving_sec"),
+    ("hr_zone_0_secs", "heart_rate_zone0_sec"),
+    ("hr_zone_1_secs", "heart_rate_zone1_sec"),
+    ("hr_zone_2_secs", "heart_rate_zone2_sec"),
+    ("hr_zone_3_secs", "heart_rate_zone3_sec"),
+    ("hr_zone_4_secs", "heart_rate_zone4_sec"),
+];
+
+/// The fields of `FITRecord` written to the FIT records columns of other names.
+const FIT_RECORDS_RENAMED: &[(&str, &str)] = &[
+    ("duration", "duration_sec"),
+    ("distance", "distance_m"),
+    ("altitude", "altitude_m"),
+    ("stance_time", "stance_time_sec"),
+    ("cadence", "cadence_bpm"),
+    ("speed", "speed_ms"),
+    ("power", "power_w"),
+    ("heartrate", "heartrate_bpm"),
+    ("lat", "lat_deg"),
+    ("lon", "lon_deg"),
+];
+
+/// The fields of `TCXLap` written to the TCX laps columns of other names.
+const TCX_LAPS_RENAMED: &[(&str, &str)] = &[
+    ("hr_zone_0_secs", "heart_rate_zone0_sec"),
+    ("hr_zone_1_secs", "heart_rate_zone1_sec"),
+    ("hr_zone_2_secs", "heart_rate_zone2_sec"),
+    ("hr_zone_3_secs", "heart_rate_zone3_sec"),
+    ("hr_zone_4_secs", "heart_rate_zone4_sec"),
+];
+
+/// The FIT summary columns in version 1.
+const FIT_SUMMARY_V1: &[&str] = &[
+    "filename",
+    "manufacturer",
+    "product",
+    "serial_number",
+    "time_created",
+    "activity_type",
+    "activity_detailed",
+    "num_sessions",
+    "num_laps",
+    "num_records",
+    "cadence_avg_bpm",
+    "cadence_max_bpm",
+    "heartrate_avg_bpm",
+    "heartrate_max_bpm",
+    "heartrate_min_bpm",
+    "speed_avg_ms",
+    "speed_max_ms",
+    "power_avg_w",
+    "power_max_w",
+    "power_threshold_w",
+    "nec_lat_deg",
+    "nec_lon_deg",
+    "swc_lat_deg",
+    "swc_lon_deg",
+    "stance_time_avg",
+    "vertical_oscillation_avg",
+    "ascent_m",
+    "descent_m",
+    "calories",
+    "distance_m",
+    "duration_sec",
+    "duration_active_sec",
+    "duration_moving_sec",
+    "start_time",
+    "finish_time",
+    "time_in_hr_zone_0_sec",
+    "time_in_hr_zone_1_sec",
+    "time_in_hr_zone_2_sec",
+    "time_in_hr_zone_3_sec",
+    "time_in_hr_zone_4_sec",
+];
+
+/// The FIT summary columns in version 2.
+const FIT_SUMMARY_V2: &[&str] = &[
+    "filename",
+    "manufacturer",
+    "product",
+    "serial_number",
+    "time_created",
+    "activity_type",
+    "activity_detailed",
+    "environment",
+    "num_sessions",
+    "num_laps",
+    "num_records",
+    "cadence_avg_bpm",
+    "cadence_max_bpm",
+    "heartrate_avg_bpm",
+    "heartrate_max_bpm",
+    "heartrate_min_bpm",
+    "speed_avg_ms",
+    "speed_max_ms",
+    "power_avg_w",
+    "power_max_w",
+    "power_threshold_w",
+    "nec_lat_deg",
+    "nec_lon_deg",
+    "swc_lat_deg",
+    "swc_lon_deg",
+    "stance_time_avg",
+    "vertical_oscillation_avg",
+    "ascent_m",
+    "descent_m",
+    "calories",
+    "distance_m",
+    "gps_distance_m",
+    "distance_discrepancy_pct",
+    "duration_sec",
+    "duration_active_sec",
+    "duration_moving_sec",
+    "start_time",
+    "finish_time",
+    "time_in_hr_zone_0_sec",
+    "time_in_hr_zone_1_sec",
+    "time_in_hr_zone_2_sec",
+    "time_in_hr_zone_3_sec",
+    "time_in_hr_zone_4_sec",
+    "local_timestamp",
+    "utc_offset_sec",
+    "total_timer_time_sec",
+    "uuid",
+    "speed_first_half_ms",
+    "speed_second_half_ms",
+    "pacing_index_pct",
+    "pacing_split",
+    "recording_interval_sec",
+    "recording_every_second_pct",
+    "recording_mode",
+    "session_num",
+    "decoupling_power_pct",
+    "decoupling_pace_pct",
+    "temperature_avg_c",
+    "heat_adjustment_pct",
+    "speed_heat_adjusted_ms",
+    "heat",
+    "power_normalized_w",
+    "intensity_factor",
+    "training_stress_score",
+    "tags",
+    "gps_outliers",
+];
+
+/// The FIT laps columns in version 1.
+const FIT_LAPS_V1: &[&str] = &[
+    "filename",
+    "lap_num",
+    "cadence_avg_bpm",
+    "cadence_max_bpm",
+    "heartrate_min_bpm",
+    "heartrate_avg_bpm",
+    "heartrate_max_bpm",
+    "speed_avg_ms",
+    "speed_max_ms",
+    "power_avg_w",
+    "power_max_w",
+    "lat_start",
+    "lon_start",
+    "lat_end",
+    "lon_end",
+    "stance_time_avg_sec",
+    "vertical_oscillation_avg",
+    "ascent_m",
+    "descent_m",
+    "calories",
+    "distance_m",
+    "duration_secs",
+    "duration_active_sec",
+    "duration_moving_sec",
+    "start_time",
+    "finish_time",
+    "heart_rate_zone0_sec",
+    "heart_rate_zone1_sec",
+    "heart_rate_zone2_sec",
+    "heart_rate_zone3_sec",
+    "heart_rate_zone4_sec",
+];
+
+/// The FIT laps columns in version 2.
+const FIT_LAPS_V2: &[&str] = &[
+    "filename",
+    "lap_num",
+    "cadence_avg_bpm",
+    "cadence_max_bpm",
+    "heartrate_min_bpm",
+    "heartrate_avg_bpm",
+    "heartrate_max_bpm",
+    "speed_avg_ms",
+    "speed_max_ms",
+    "power_avg_w",
+    "power_max_w",
+    "lat_start",
+    "lon_start",
+    "lat_end",
+    "lon_end",
+    "stance_time_avg_sec",
+    "vertical_oscillation_avg",
+    "ascent_m",
+    "descent_m",
+    "calories",
+    "distance_m",
+    "duration_secs",
+    "duration_active_sec",
+    "duration_moving_sec",
+    "start_time",
+    "finish_time",
+    "heart_rate_zone0_sec",
+    "heart_rate_zone1_sec",
+    "heart_rate_zone2_sec",
+    "heart_rate_zone3_sec",
+    "heart_rate_zone4_sec",
+    "activity_uuid",
+    "session_num",
+];
+
+/// The FIT records columns in version 1.
+const FIT_RECORDS_V1: &[&str] = &[
+    "timestamp",
+    "duration_sec",
+    "distance_m",
+    "altitude_m",
+    "stance_time_sec",
+    "vertical_oscillation",
+    "cadence_bpm",
+    "speed_ms",
+    "power_w",
+    "heartrate_bpm",
+    "calories",
+    "lat_deg",
+    "lon_deg",
+];
+
+/// The FIT records columns in version 2.
+const FIT_RECORDS_V2: &[&str] = &[
+    "timestamp",
+    "duration_sec",
+    "distance_m",
+    "altitude_m",
+    "altitude_source",
+    "stance_time_sec",
+    "vertical_oscillation",
+    "cadence_bpm",
+    "speed_ms",
+    "power_w",
+    "heartrate_bpm",
+    "calories",
+    "lat_deg",
+    "lon_deg",
+    "activity_uuid",
+    "session_num",
+    "synthetic",
+];
+
+/// The FIT events columns. The file was added in version 2.
+const FIT_EVENTS_V2: &[&str] = &[
+    "filename",
+    "timestamp",
+    "event",
+    "event_type",
+    "timer_trigger",
+    "data",
+    "event_group",
+    "front_gear_num",
+    "front_gear",
+    "rear_gear_num",
+    "rear_gear",
+    "activity_uuid",
+];
+
+/// The FIT devices columns. The file was added in version 2.
+const FIT_DEVICES_V2: &[&str] = &[
+    "filename",
+    "timestamp",
+    "device_index",
+    "device_type",
+    "manufacturer",
+    "product",
+    "serial_number",
+    "software_version",
+    "hardware_version",
+    "battery_voltage_v",
+    "battery_status",
+    "operating_time_sec",
+    "source_type",
+    "descriptor",
+    "ant_device_number",
+    "activity_uuid",
+];
+
+/// The FIT device splits columns. The file was added in version 2.
+const FIT_DEVICE_SPLITS_V2: &[&str] = &[
+    "filename",
+    "message",
+    "split_index",
+    "split_type",
+    "num_splits",
+    "start_time",
+    "end_time",
+    "elapsed_time_sec",
+    "timer_time_sec",
+    "moving_time_sec",
+    "distance_m",
+    "speed_avg_ms",
+    "speed_max_ms",
+    "vertical_speed_avg_ms",
+    "ascent_m",
+    "descent_m",
+    "start_elevation_m",
+    "heartrate_avg_bpm",
+    "heartrate_max_bpm",
+    "calories",
+    "lat_start_deg",
+    "lon_start_deg",
+    "lat_end_deg",
+    "lon_end_deg",
+    "activity_uuid",
+];
+
+/// The GPX summary columns in version 1.
+const GPX_SUMMARY_V1: &[&str] = &[
+    "filename",
+    "version",
+    "creator",
+    "activity",
+    "description",
+    "author_name",
+    "author_email",
+    "links_href",
+    "links_text",
+    "keywords",
+    "time",
+    "duration",
+    "copyright_author",
+    "copyright_year",
+    "copyright_license",
+    "num_waypoints",
+    "num_tracks",
+    "num_routes",
+];
+
+/// The GPX summary columns in version 2.
+const GPX_SUMMARY_V2: &[&str] = &[
+    "filename",
+    "version",
+    "creator",
+    "activity",
+    "description",
+    "author_name",
+    "author_email",
+    "links_href",
+    "links_text",
+    "keywords",
+    "time",
+    "duration",
+    "copyright_author",
+    "copyright_year",
+    "copyright_license",
+    "num_waypoints",
+    "num_tracks",
+    "num_routes",
+    "uuid",
+    "pct_3d_fix",
+    "avg_satellites",
+    "avg_hdop",
+    "speed_first_half_ms",
+    "speed_second_half_ms",
+    "pacing_index_pct",
+    "pacing_split",
+    "recording_interval_sec",
+    "recording_every_second_pct",
+    "recording_mode",
+    "distance_m",
+    "duration_moving",
+    "speed_avg_ms",
+    "speed_max_ms",
+    "ascent_m",
+    "descent_m",
+    "environment",
+    "decoupling_power_pct",
+    "decoupling_pace_pct",
+    "temperature_avg_c",
+    "heat_adjustment_pct",
+    "speed_heat_adjusted_ms",
+    "heat",
+    "heart_rate_zone0_sec",
+    "heart_rate_zone1_sec",
+    "heart_rate_zone2_sec",
+    "heart_rate_zone3_sec",
+    "heart_rate_zone4_sec",
+    "tags",
+    "gps_outliers",
+];
+
+/// The GPX tracks columns in version 1.
+const GPX_TRACKS_V1: &[&str] = &[
+    "filename",
+    "track_num",
+    "name",
+    "start_time",
+    "duration",
+    "comment",
+    "description",
+    "source",
+    "links_href",
+    "links_text",
+    "t_type",
+    "num_segments",
+    "num_waypoints",
+];
+
+/// The GPX tracks columns in version 2.
+const GPX_TRACKS_V2: &[&str] = &[
+    "filename",
+    "track_num",
+    "name",
+    "start_time",
+    "duration",
+    "comment",
+    "description",
+    "source",
+    "links_href",
+    "links_text",
+    "t_type",
+    "num_segments",
+    "num_waypoints",
+    "activity_uuid",
+    "distance_m",
+    "duration_moving",
+    "speed_avg_ms",
+    "speed_max_ms",
+    "ascent_m",
+    "descent_m",
+];
+
+/// The GPX waypoints columns in version 1.
+const GPX_WAYPOINTS_V1: &[&str] = &[
+    "track_num",
+    "route_num",
+    "segment_num",
+    "waypoint_mum",
+    "longitude",
+    "latitude",
+    "elevation",
+    "speed",
+    "time",
+    "name",
+    "comment",
+    "description",
+    "source",
+    "num_links",
+    "links_href",
+    "links_text",
+    "symbol",
+    "w_type",
+    "geoidheight",
+    "fix",
+    "sat",
+    "hdop",
+    "vdop",
+    "pdop",
+    "age",
+    "dgpsid",
+    "heart_rate",
+    "cadence",
+];
+
+/// The GPX waypoints columns in version 2.
+const GPX_WAYPOINTS_V2: &[&str] = &[
+    "track_num",
+    "route_num",
+    "segment_num",
+    "waypoint_mum",
+    "longitude",
+    "latitude",
+    "elevation",
+    "speed",
+    "time",
+    "name",
+    "comment",
+    "description",
+    "source",
+    "num_links",
+    "links_href",
+    "links_text",
+    "symbol",
+    "w_type",
+    "geoidheight",
+    "fix",
+    "sat",
+    "hdop",
+    "vdop",
+    "pdop",
+    "age",
+    "dgpsid",
+    "heart_rate",
+    "cadence",
+    "activity_uuid",
+    "temperature",
+    "power",
+];
+
+/// The TCX summary columns in version 1.
+const TCX_SUMMARY_V1: &[&str] = &[
+    "filename",
+    "num_activities",
+    "sport",
+    "start_time",
+    "duration",
+    "notes",
+    "num_laps",
+    "num_tracks",
+    "num_trackpoints",
+    "distance_meters",
+    "start_altitude",
+    "max_altitude",
+    "ascent_meters",
+    "average_speed",
+    "maximum_speed",
+    "calories",
+    "average_heart_rate",
+    "maximum_heart_rate",
+    "average_cadence",
+    "maximum_cadence",
+];
+
+/// The TCX summary columns in version 2.
+const TCX_SUMMARY_V2: &[&str] = &[
+    "filename",
+    "manufacturer",
+    "product",
+    "serial_number",
+    "num_activities",
+    "sport",
+    "start_time",
+    "duration",
+    "notes",
+    "num_laps",
+    "num_tracks",
+    "num_trackpoints",
+    "distance_meters",
+    "gps_distance_meters",
+    "distance_discrepancy_pct",
+    "start_altitude",
+    "max_altitude",
+    "ascent_meters",
+    "average_speed",
+    "maximum_speed",
+    "calories",
+    "average_heart_rate",
+    "maximum_heart_rate",
+    "average_cadence",
+    "maximum_cadence",
+    "uuid",
+    "speed_first_half_ms",
+    "speed_second_half_ms",
+    "pacing_index_pct",
+    "pacing_split",
+    "recording_interval_sec",
+    "recording_every_second_pct",
+    "recording_mode",
+    "lap_notes",
+    "software_version",
+    "author",
+    "environment",
+    "decoupling_pace_pct",
+    "heart_rate_zone0_sec",
+    "heart_rate_zone1_sec",
+    "heart_rate_zone2_sec",
+    "heart_rate_zone3_sec",
+    "heart_rate_zone4_sec",
+    "tags",
+];
+
+/// The TCX trackpoints columns in version 1.
+const TCX_TRACKPOINTS_V1: &[&str] = &[
+    "sport",
+    "start_time",
+    "time",
+    "duration",
+    "activity_num",
+    "lap_num",
+    "track_num",
+    "trackpoint_num",
+    "latitude",
+    "longitude",
+    "altitude_meters",
+    "distance_meters",
+    "heart_rate",
+    "cadence",
+];
+
+/// The TCX trackpoints columns in version 2.
+const TCX_TRACKPOINTS_V2: &[&str] = &[
+    "sport",
+    "start_time",
+    "time",
+    "duration",
+    "activity_num",
+    "lap_num",
+    "track_num",
+    "trackpoint_num",
+    "latitude",
+    "longitude",
+    "altitude_meters",
+    "distance_meters",
+    "heart_rate",
+    "cadence",
+    "activity_uuid",
+];
+
+/// The TCX laps columns. The file was added in version 2.
+const TCX_LAPS_V2: &[&str] = &[
+    "activity_num",
+    "lap_num",
+    "sport",
+    "start_time",
+    "duration",
+    "distance_meters",
+    "average_speed",
+    "maximum_speed",
+    "calories",
+    "average_heart_rate",
+    "maximum_heart_rate",
+    "average_cadence",
+    "maximum_cadence",
+    "intensity",
+    "trigger_method",
+    "num_trackpoints",
+    "notes",
+    "activity_uuid",
+    "heart_rate_zone0_sec",
+    "heart_rate_zone1_sec",
+    "heart_rate_zone2_sec",
+    "heart_rate_zone3_sec",
+    "heart_rate_zone4_sec",
+];
+
+/// The splits columns. The file was added in version 2.
+const SPLITS_V2: &[&str] = &[
+    "unit",
+    "split",
+    "distance_m",
+    "duration_sec",
+    "pace_sec",
+    "heartrate_avg_bpm",
+    "elevation_change_m",
+    "grade_adjusted_pace_sec",
+    "pace_zone0_sec",
+    "pace_zone1_sec",
+    "pace_zone2_sec",
+    "pace_zone3_sec",
+    "pace_zone4_sec",
+];
+
+/// The mean-maximal curve columns. The file was added in version 2.
+const MEAN_MAX_V2: &[&str] = &["duration_sec", "power_avg_w", "heartrate_avg_bpm"];
+
+/// The heart rate scatter columns. The file was added in version 2.
+const HR_SCATTER_V2: &[&str] = &[
+    "time",
+    "elapsed_sec",
+    "speed_ms",
+    "pace_sec",
+    "power_w",
+    "heartrate_bpm",
+];
+
+#[cfg(test)]
+/// Tests for the columns module
+mod tests {
+    use super::*;
+    use crate::gpx::gpxmetadata::GPXMetadata;
+    use crate::gpx::waypoint::GPXWaypoint;
+    use crate::{
+        FITDeviceInfo, FITEvent, FITLap, FITRecord, FITSession, FITSplit, GPXTrack, MeanMax, Split,
+        SplitUnit, TCXActivity, TCXLap, TCXTrackpoint,
+    };
+
+    /// The header serde writes for a row.
+    fn serde_header<T: Serialize>(row: &T) -> Vec<String> {
+        let mut writer = WriterBuilder::new()
+            .has_headers(true)
+            .from_writer(Vec::new());
+        writer.serialize(row).unwrap();
+        let bytes = writer.into_inner().unwrap();
+        let mut reader = ReaderBuilder::new().from_reader(bytes.as_slice());
+        reader.headers().unwrap().iter().map(String::from).collect()
+    }
+
+    /// The number of fields serde writes for a row.
+    fn num_fields<T: Serialize>(row: &T) -> usize {
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        writer.serialize(row).unwrap();
+        let bytes = writer.into_inner().unwrap();
+        let mut record = StringRecord::new();
+        ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(bytes.as_slice())
+            .read_record(&mut record)
+            .unwrap();
+        record.len()
+    }
+
+    #[test]
+    /// Test that the structs still serialize to the columns that are locked in. If this fails, a field was added,
+    /// renamed or moved. Add new fields at the end of the struct and of the latest columns, and keep the old names.
+    fn test_columns_locked() {
+        let split = Split {
+            unit: SplitUnit::Kilometer,
+            split: 1,
+            distance_m: 1_000.0,
+            duration_sec: 300.0,
+            pace_sec: 300.0,
+            heartrate_avg_bpm: None,
+            elevation_change_m: None,
+            grade_adjusted_pace_sec: None,
+            pace_zone0_sec: None,
+            pace_zone1_sec: None,
+            pace_zone2_sec: None,
+            pace_zone3_sec: None,
+            pace_zone4_sec: None,
+        };
+        let latest = |file: CsvFile| file.columns(ColumnsVersion::LATEST);
+
+        // The structs with serde headers
+        assert_eq!(
+            serde_header(&GPXMetadata::default()),
+            latest(CsvFile::GpxSummary)
+        );
+        assert_eq!(
+            serde_header(&GPXTrack::default()),
+            latest(CsvFile::GpxTracks)
+        );
+        assert_eq!(
+            serde_header(&GPXWaypoint::default()),
+            latest(CsvFile::GpxWaypoints)
+        );
+        assert_eq!(
+            serde_header(&TCXActivity::default()),
+            latest(CsvFile::TcxSummary)
+        );
+        assert_eq!(serde_header(&split), latest(CsvFile::Splits));
+        let mean_max = MeanMax {
+            duration_sec: 1,
+            power_avg_w: None,
+            heartrate_avg_bpm: None,
+        };
+        assert_eq!(serde_header(&mean_max), latest(CsvFile::MeanMax));
+        assert_eq!(
+            serde_header(&FITEvent::default()),
+            latest(CsvFile::FitEvents)
+        );
+        assert_eq!(
+            serde_header(&FITDeviceInfo::default()),
+            latest(CsvFile::FitDevices)
+        );
+        assert_eq!(
+            serde_header(&FITSplit::default()),
+            latest(CsvFile::FitDeviceSplits)
+        );
+
+        // The structs with the header written separately must at least have a field for each column
+        assert_eq!(
+            num_fields(&FITSession::default()),
+            latest(CsvFile::FitSummary).len()
+        );
+        assert_eq!(
+            num_fields(&FITLap::default()),
+            latest(CsvFile::FitLaps).len()
+        );
+        assert_eq!(
+            num_fields(&FITRecord::default()),
+            latest(CsvFile::FitRecords).len()
+        );
+        assert_eq!(
+            num_fields(&TCXLap::default()),
+            latest(CsvFile::TcxLaps).len()
+        );
+        assert_eq!(
+            num_fields(&TCXTrackpoint::default()),
+            latest(CsvFile::TcxTrackpoints).len()
+        );
+
+        // The headers worked out from the structs, with the FIT names normalized
+        for file in CsvFile::ALL {
+            assert_eq!(
+                file.header(ColumnsVersion::LATEST),
+                latest(file),
+                "{file:?}"
+            );
+        }
+    }
+
+    #[test]
+    /// Test that the older versions only have columns that are still written, in the same order
+    fn test_older_versions() {
+        for file in CsvFile::ALL {
+            let latest = file.columns(ColumnsVersion::LATEST);
+            let positions: Vec<usize> = file
+                .columns(ColumnsVersion::V1)
+                .iter()
+                .map(|column| latest.iter().position(|c| c == column).unwrap())
+                .collect();
+            assert!(positions.is_sorted(), "{file:?}");
+        }
+    }
+
+    #[test]
+    /// Test writing the version 1 columns
+    fn test_column_writer() {
+        let activity = TCXActivity {
+            filename: Some("running.tcx".to_string()),
+            sport: Some("Running".to_string()),
+            uuid: Some(uuid::Uuid::nil()),
+            ..TCXActivity::default()
+        };
+
+        let mut csv = Vec::new();
+        let mut writer =
+            ColumnWriter::with_version(&mut csv, CsvFile::TcxSummary, ColumnsVersion::V1);
+        writer.serialize(&activity).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let text = String::from_utf8(csv).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            CsvFile::TcxSummary.columns(ColumnsVersion::V1).join(",")
+        );
+        assert!(lines.next().unwrap().starts_with("running.tcx,,Running,"));
+        assert!(!text.contains("00000000-0000"));
+
+        // The latest version writes the header even without rows when asked to
+        let mut csv = Vec::new();
+        let mut writer = ColumnWriter::with_version(&mut csv, CsvFile::FitLaps, ColumnsVersion::V2);
+        writer.write_header().unwrap();
+        writer.write_header().unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    /// Test reading the columns version
+    fn test_columns_version() {
+        assert_eq!("1".parse::<ColumnsVersion>(), Ok(ColumnsVersion::V1));
+        assert_eq!("v2".parse::<ColumnsVersion>(), Ok(ColumnsVersion::V2));
+        assert!("3".parse::<ColumnsVersion>().is_err());
+        assert_eq!(ColumnsVersion::default(), ColumnsVersion::LATEST);
+        assert_eq!(ColumnsVersion::V1.to_string(), "1");
+    }
+}
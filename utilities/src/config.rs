@@ -0,0 +1,349 @@
+//! Reads the default settings of the tools from a TOML config file, so options used on every run, such as the units
+//! or the `fitrename` patterns, don't have to be given each time. Options given on the command line always win.
+//!
+//! The file is `fitutils.toml` in `$XDG_CONFIG_HOME`, or in `~/.config` if that isn't set. Set `FITUTILS_CONFIG` to
+//! use another file. The keys are the long names of the command line options. Keys at the top of the file apply to
+//! every tool that has the option, and keys in a table named after a tool apply to that tool only:
+//!
+//! ```toml
+//! units = "imperial"
+//! log-level = "warn"
+//!
+//! [fitrename]
+//! pattern = "%year-%month-%day %hour%minute %activity"
+//! move = "%year/%month"
+//!
+//! [fit2csv]
+//! format = "parquet"
+//! mean-max = true
+//! ```
+
+use clap::{error::ErrorKind, Arg, ArgAction, Command};
+use log::LevelFilter;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::RwLock;
+use toml::value::{Table, Value};
+
+/// The environment variable pointing to a config file other than the default.
+pub const CONFIG_ENV: &str = "FITUTILS_CONFIG";
+
+/// The key of the log level, which isn't a command line option.
+const LOG_LEVEL_KEY: &str = "log-level";
+
+/// The log level from the config file, used when neither `--debug` nor `--quiet` is given.
+static LOG_LEVEL: RwLock<Option<LevelFilter>> = RwLock::new(None);
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The settings read from the config file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// The settings for all the tools.
+    shared: Table,
+
+    /// The settings for each tool, by the name of the tool.
+    tools: Table,
+}
+
+impl FromStr for Config {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tools, shared) = toml::from_str::<Table>(s)?
+            .into_iter()
+            .partition(|(_, value)| value.is_table());
+
+        Ok(Self { shared, tools })
+    }
+}
+
+impl Config {
+    /// The config file used unless `FITUTILS_CONFIG` is set.
+    ///
+    /// # Returns
+    ///
+    /// `Option<PathBuf>` -- `fitutils.toml` in `$XDG_CONFIG_HOME` or `~/.config`, or `None` if neither is known.
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(dir.join("fitutils.toml"))
+    }
+
+    /// Reads the config file. A missing file gives an empty config, so the tools run without one.
+    ///
+    /// # Arguments
+    ///
+    /// `path: &Path` -- The config file.
+    ///
+    /// # Errors
+    ///
+    /// Reading the file may fail, or it may not be valid TOML.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        log::trace!("Config::load() -- Reading {}", path.display());
+        std::fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| format!("{}: {err}", path.display()).into())
+    }
+
+    /// The settings for the tool, with the ones in its own table taking the place of the shared ones.
+    fn settings(&self, tool: &str) -> Table {
+        let mut settings = self.shared.clone();
+        if let Some(Value::Table(own)) = self.tools.get(tool) {
+            settings.extend(own.clone());
+        }
+        settings
+    }
+
+    /// The log level set for the tool, if any.
+    ///
+    /// # Errors
+    ///
+    /// The log level may not be one of `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    pub fn log_level(&self, tool: &str) -> Result<Option<LevelFilter>, Box<dyn Error>> {
+        self.settings(tool)
+            .get(LOG_LEVEL_KEY)
+            .map(|level| {
+                level
+                    .as_str()
+                    .and_then(|level| level.parse::<LevelFilter>().ok())
+                    .ok_or_else(|| {
+                        format!("{LOG_LEVEL_KEY} = {level}: Use off, error, warn, info, debug or trace.")
+                            .into()
+                    })
+            })
+            .transpose()
+    }
+
+    /// Makes the settings for the tool the defaults of its command line options. Shared settings the tool doesn't
+    /// have are skipped, while settings in the tool's own table must match one of its options.
+    ///
+    /// # Arguments
+    ///
+    /// `command: Command` -- The command line of the tool. Its name is the name of the tool's table.
+    ///
+    /// # Errors
+    ///
+    /// A setting in the tool's own table may not be an option of the tool, or a value may not be allowed for the
+    /// option.
+    pub fn apply(&self, mut command: Command) -> Result<Command, Box<dyn Error>> {
+        let tool = command.get_name().to_string();
+        let own = match self.tools.get(&tool) {
+            Some(Value::Table(own)) => own.clone(),
+            _ => Table::new(),
+        };
+
+        for (key, value) in self.settings(&tool) {
+            if key == LOG_LEVEL_KEY {
+                continue;
+            }
+            let Some(arg) = command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(&key))
+            else {
+                if own.contains_key(&key) {
+                    return Err(format!("[{tool}] {key}: Not an option of {tool}.").into());
+                }
+                continue;
+            };
+
+            let values = default_values(arg, &value).map_err(|err| format!("{key}: {err}"))?;
+            let id = arg.get_id().to_string();
+            command = command.mut_arg(id, |arg| arg.default_values(values));
+        }
+
+        Ok(command)
+    }
+}
+
+/// The default values of the option from the value in the config file.
+fn default_values(arg: &Arg, value: &Value) -> Result<Vec<String>, Box<dyn Error>> {
+    match arg.get_action() {
+        ArgAction::SetTrue | ArgAction::SetFalse => {
+            return match value {
+                Value::Boolean(flag) => Ok(vec![flag.to_string()]),
+                _ => Err("Use true or false.".into()),
+            };
+        }
+        ArgAction::Set | ArgAction::Append => (),
+        _ => return Err("Can't be set in the config file.".into()),
+    }
+
+    let values = match value {
+        Value::Array(values) => values.iter().map(scalar).collect::<Result<Vec<_>, _>>()?,
+        value => vec![scalar(value)?],
+    };
+
+    // Check the values here, since clap only checks the defaults when they are used
+    let possible = arg.get_possible_values();
+    if let Some(value) = values
+        .iter()
+        .find(|value| !possible.is_empty() && !possible.iter().any(|p| p.matches(value, false)))
+    {
+        let names: Vec<&str> = possible.iter().map(|p| p.get_name()).collect();
+        return Err(format!("{value} isn't allowed. Use {}.", names.join(", ")).into());
+    }
+
+    Ok(values)
+}
+
+/// A single value from the config file as it would be given on the command line.
+fn scalar(value: &Value) -> Result<String, Box<dyn Error>> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => Ok(value.to_string()),
+        _ => Err(format!("{value}: Use a text, number or list.").into()),
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Reads the config file and makes its settings the defaults of the tool's command line options. The log level is
+/// kept for `build_log()`.
+///
+/// Since this runs before logging is set up, a config file that can't be used is reported the way clap reports a bad
+/// command line argument, and the tool exits.
+///
+/// # Arguments
+///
+/// `command: Command` -- The command line of the tool, named after the tool.
+///
+/// # Returns
+///
+/// `Command` -- The command line with the defaults from the config file.
+///
+/// # Example
+///
+/// ```ignore
+/// let cli_args = utilities::with_config(cli::build()).get_matches();
+/// ```
+#[must_use]
+pub fn with_config(mut command: Command) -> Command {
+    let path = std::env::var_os(CONFIG_ENV)
+        .map(PathBuf::from)
+        .or_else(Config::default_path);
+    let Some(path) = path else {
+        return command;
+    };
+
+    let configured = Config::load(&path).and_then(|config| {
+        let level = config.log_level(command.get_name())?;
+        if let Ok(mut current) = LOG_LEVEL.write() {
+            *current = level;
+        }
+        config.apply(command.clone())
+    });
+
+    match configured {
+        Ok(configured) => configured,
+        Err(err) => command
+            .error(
+                ErrorKind::InvalidValue,
+                format!("{}: {err}", path.display()),
+            )
+            .exit(),
+    }
+}
+
+/// The log level from the config file, if any.
+pub(crate) fn config_log_level() -> Option<LevelFilter> {
+    LOG_LEVEL.read().ok().and_then(|level| *level)
+}
+
+#[cfg(test)]
+/// Tests for the config module
+mod tests {
+    use super::*;
+
+    /// A command line with a few kinds of options.
+    fn command() -> Command {
+        Command::new("fit2csv")
+            .arg(Arg::new("read").num_args(1..).action(ArgAction::Append))
+            .arg(
+                Arg::new("units")
+                    .long("units")
+                    .value_parser(["metric", "imperial"])
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("mean-max")
+                    .long("mean-max")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("precision")
+                    .long("precision")
+                    .value_parser(clap::value_parser!(u32))
+                    .action(ArgAction::Set),
+            )
+    }
+
+    #[test]
+    /// Test taking the defaults from the config, with the tool's own table and the command line winning
+    fn test_apply() {
+        let config: Config = r#"
+            units = "imperial"
+            pattern = "%year"
+            log-level = "warn"
+
+            [fit2csv]
+            units = "metric"
+            mean-max = true
+            precision = 2
+        "#
+        .parse()
+        .unwrap();
+
+        let args = config
+            .apply(command())
+            .unwrap()
+            .get_matches_from(["fit2csv", "a.fit"]);
+        assert_eq!(
+            args.get_one::<String>("units").map(String::as_str),
+            Some("metric")
+        );
+        assert!(args.get_flag("mean-max"));
+        assert_eq!(args.get_one::<u32>("precision"), Some(&2));
+        assert_eq!(
+            config.log_level("fit2csv").unwrap(),
+            Some(LevelFilter::Warn)
+        );
+
+        let args = config
+            .apply(command())
+            .unwrap()
+            .get_matches_from(["fit2csv", "--units", "imperial", "a.fit"]);
+        assert_eq!(
+            args.get_one::<String>("units").map(String::as_str),
+            Some("imperial")
+        );
+    }
+
+    #[test]
+    /// Test the settings that don't fit the tool
+    fn test_apply_errors() {
+        for toml in [
+            "[fit2csv]\nunits = \"nautical\"",
+            "[fit2csv]\nunit = \"metric\"",
+            "[fit2csv]\nmean-max = \"yes\"",
+            "log-level = \"loud\"",
+        ] {
+            let config: Config = toml.parse().unwrap();
+            assert!(
+                config.apply(command()).is_err() || config.log_level("fit2csv").is_err(),
+                "{toml}"
+            );
+        }
+
+        assert!("units = ".parse::<Config>().is_err());
+        assert_eq!(
+            Config::load(Path::new("../data/missing.toml")).unwrap(),
+            Config::default()
+        );
+    }
+}
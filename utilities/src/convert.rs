@@ -0,0 +1,115 @@
+//! Single-call conversions of an activity file, for callers that only have a filename to work with -- e.g. the C
+//! interface in `fitffi`. The file type is worked out from the extension.
+
+use std::error::Error;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::atomic_file::write_atomically;
+use crate::{get_extension, Activity};
+
+/// Reads the activity file and returns it as a single JSON document -- the same document the `bundle.json` export
+/// writes.
+///
+/// # Arguments
+///
+/// `filename: &str` -- The FIT, GPX or TCX file to read.
+///
+/// # Errors
+///
+/// The file may not be a FIT, GPX or TCX file. Reading, parsing or serializing the activity may fail.
+pub fn activity_json(filename: &str) -> Result<String, Box<dyn Error>> {
+    Activity::from_file(filename)?.bundle_json()
+}
+
+/// Converts the activity file to the output file. The output type is worked out from its extension:
+///
+/// | Output  | Contents                                                                       |
+/// |:--------|:-------------------------------------------------------------------------------|
+/// | `.json` | The activity as a single JSON document. See `activity_json()`.                 |
+/// | `.csv`  | The FIT records, the GPX track waypoints or the TCX trackpoints.               |
+/// | `.gpx`  | The FIT sessions or TCX activities as tracks, with a segment or track per lap. |
+/// | `.tcx`  | The GPX tracks as activities, with a lap per track segment.                    |
+///
+/// # Arguments
+///
+/// - `input: &str` -- The FIT, GPX or TCX file to read.
+/// - `output: &str` -- The file to write.
+///
+/// # Errors
+///
+/// The input may not be a FIT, GPX or TCX file, or the output may not be a JSON or CSV file. Only FIT and TCX files
+/// can be converted to GPX, and only GPX files to TCX. Reading, parsing or writing may fail.
+pub fn convert_file(input: &str, output: &str) -> Result<(), Box<dyn Error>> {
+    match get_extension(output).as_str() {
+        "json" => {
+            let json = activity_json(input)?;
+            write_atomically(Path::new(output), |file| {
+                Ok(file.write_all(json.as_bytes())?)
+            })?;
+        }
+        "csv" => {
+            let activity = Activity::from_file(input)?;
+            write_atomically(Path::new(output), |file| activity.write_records_csv(file))?;
+        }
+        "gpx" => match Activity::from_file(input)? {
+            Activity::Fit(activity) => {
+                write_atomically(Path::new(output), |file| {
+                    let mut writer = BufWriter::new(file);
+                    activity.write_gpx(&mut writer)?;
+                    Ok(writer.flush()?)
+                })?;
+            }
+            Activity::Tcx(_, trackpoints) => {
+                write_atomically(Path::new(output), |file| {
+                    let mut writer = BufWriter::new(file);
+                    trackpoints.write_gpx(&mut writer)?;
+                    Ok(writer.flush()?)
+                })?;
+            }
+            Activity::Gpx(_) => {
+                return Err(
+                    format!("{input}: Only FIT and TCX files can be converted to GPX.").into(),
+                )
+            }
+        },
+        "tcx" => match Activity::from_file(input)? {
+            Activity::Gpx(activity) => {
+                write_atomically(Path::new(output), |file| {
+                    let mut writer = BufWriter::new(file);
+                    activity.write_tcx(&mut writer)?;
+                    Ok(writer.flush()?)
+                })?;
+            }
+            _ => return Err(format!("{input}: Only GPX files can be converted to TCX.").into()),
+        },
+        _ => return Err(format!("{output}: Unable to convert to this file type.").into()),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+/// Tests for the convert module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test converting to JSON and CSV, and rejecting the file types that can't be converted
+    fn test_convert_file() {
+        let json: serde_json::Value =
+            serde_json::from_str(&activity_json("../data/running.gpx").unwrap()).unwrap();
+        assert!(json["tracks"].as_array().is_some_and(|t| !t.is_empty()));
+
+        let output = std::env::temp_dir().join("fitutils-convert-test.csv");
+        let output = output.to_str().unwrap_or_default();
+        convert_file("../data/running.tcx", output).unwrap();
+        assert!(std::fs::read_to_string(output).unwrap().lines().count() > 1);
+        let _ = std::fs::remove_file(output);
+
+        assert!(activity_json("../data/readme.md").is_err());
+        assert!(convert_file("../data/running.gpx", "running.xlsx").is_err());
+        assert!(convert_file("../data/running.gpx", "running.gpx").is_err());
+        assert!(convert_file("../data/rowing.fit", "rowing.tcx").is_err());
+    }
+}
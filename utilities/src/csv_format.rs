@@ -0,0 +1,186 @@
+//! The delimiter and decimal separator of the CSV files, for spreadsheets set up for locales that write decimals with a
+//! comma, e.g. `12,5;3,75` instead of `12.5,3.75`.
+//!
+//! The format is set once for the whole run using `Settings::set_csv_format()`, and applies to all the CSV files
+//! written. The other export formats are left as they are, since they have types of their own for the numbers.
+
+use csv::{Writer, WriterBuilder};
+use std::borrow::Cow;
+use std::error::Error;
+use std::io::Write;
+
+use crate::Settings;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// How the values in the CSV files are separated and how the decimals are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvFormat {
+    /// The character between the values, e.g. `b','` or `b';'`.
+    pub delimiter: u8,
+
+    /// Write the decimal numbers with a comma, e.g. `12,5`.
+    pub decimal_comma: bool,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl CsvFormat {
+    /// Values separated by commas, with the decimals written with a point.
+    pub const DEFAULT: Self = Self {
+        delimiter: b',',
+        decimal_comma: false,
+    };
+
+    /// Values separated by semicolons, with the decimals written with a comma, as most European spreadsheets expect.
+    pub const DECIMAL_COMMA: Self = Self {
+        delimiter: b';',
+        decimal_comma: true,
+    };
+
+    /// Reads a delimiter from the command line: a single character, or `tab`.
+    ///
+    /// # Arguments
+    ///
+    /// `s: &str` -- The delimiter, e.g. `;`, `|` or `tab`.
+    ///
+    /// # Returns
+    ///
+    /// `Result<u8, String>` -- The delimiter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delimiter isn't a single ASCII character, or is a quote or a line break.
+    pub fn parse_delimiter(s: &str) -> Result<u8, String> {
+        if s.eq_ignore_ascii_case("tab") || s == "\\t" {
+            return Ok(b'\t');
+        }
+        match s.as_bytes() {
+            [delimiter] if delimiter.is_ascii() && !b"\"\r\n".contains(delimiter) => Ok(*delimiter),
+            _ => Err(format!(
+                "Unknown delimiter {s}. Use a single character, e.g. ; or |, or tab."
+            )),
+        }
+    }
+
+    /// The cell with the decimal point replaced by a comma, if the decimals are written with a comma and the cell is
+    /// a number. Other cells, e.g. times and names, are left as they are.
+    pub(crate) fn localize(self, cell: Cow<'_, str>) -> Cow<'_, str> {
+        if self.decimal_comma && cell.contains('.') && cell.parse::<f64>().is_ok() {
+            Cow::Owned(cell.replace('.', ","))
+        } else {
+            cell
+        }
+    }
+}
+
+impl Settings {
+    /// Sets the format used by all the CSV files.
+    ///
+    /// # Arguments
+    ///
+    /// `format: CsvFormat` -- The delimiter and decimal separator.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delimiter is a comma and the decimals are written with a comma, since the numbers
+    /// couldn't be told apart.
+    pub fn set_csv_format(&mut self, format: CsvFormat) -> Result<(), Box<dyn Error>> {
+        if format.decimal_comma && format.delimiter == b',' {
+            return Err(
+                "The delimiter can't be a comma when the decimals are written with a comma.".into(),
+            );
+        }
+        self.csv_format = format;
+        Ok(())
+    }
+
+    /// Sets the format from the `delimiter` and `decimal-comma` command line arguments. With `decimal-comma` the
+    /// values are separated by semicolons unless a delimiter is given.
+    ///
+    /// # Arguments
+    ///
+    /// `cli_args: &clap::ArgMatches` -- The command line arguments.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delimiter isn't a single character, or is a comma along with `decimal-comma`.
+    #[cfg(feature = "cli")]
+    pub fn set_csv_format_from_args(
+        &mut self,
+        cli_args: &clap::ArgMatches,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut format = if cli_args.get_flag("decimal-comma") {
+            CsvFormat::DECIMAL_COMMA
+        } else {
+            CsvFormat::DEFAULT
+        };
+        if let Some(delimiter) = cli_args.get_one::<String>("delimiter") {
+            format.delimiter = CsvFormat::parse_delimiter(delimiter)?;
+        }
+
+        if format != CsvFormat::DEFAULT {
+            log::debug!("csv_format::set_csv_format_from_args() -- {format:?}");
+        }
+        self.set_csv_format(format)
+    }
+}
+
+/// The format set with `Settings::set_csv_format()`.
+pub(crate) fn csv_format() -> CsvFormat {
+    Settings::current().csv_format
+}
+
+/// Creates a CSV writer with the delimiter of the run. The header isn't written automatically, so it has to be
+/// written as the first record.
+///
+/// # Arguments
+///
+/// `writer: W` -- Where the CSV is written.
+pub(crate) fn csv_writer<W: Write>(writer: W) -> Writer<W> {
+    WriterBuilder::new()
+        .has_headers(false)
+        .delimiter(csv_format().delimiter)
+        .from_writer(writer)
+}
+
+#[cfg(test)]
+/// Tests for the csv_format module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test reading the delimiters and writing the decimals with a comma
+    fn test_csv_format() {
+        assert_eq!(CsvFormat::parse_delimiter(";"), Ok(b';'));
+        assert_eq!(CsvFormat::parse_delimiter("TAB"), Ok(b'\t'));
+        assert!(CsvFormat::parse_delimiter(";;").is_err());
+        assert!(CsvFormat::parse_delimiter("\"").is_err());
+        assert!(CsvFormat::parse_delimiter("").is_err());
+
+        let format = CsvFormat::DECIMAL_COMMA;
+        assert_eq!(format.localize(Cow::Borrowed("12.5")), "12,5");
+        assert_eq!(format.localize(Cow::Borrowed("-0.25")), "-0,25");
+        assert_eq!(format.localize(Cow::Borrowed("42")), "42");
+        assert_eq!(
+            format.localize(Cow::Borrowed("2023-06-03T08:00:00.500+02:00")),
+            "2023-06-03T08:00:00.500+02:00"
+        );
+        assert_eq!(format.localize(Cow::Borrowed("Edge 1.5")), "Edge 1.5");
+        assert_eq!(CsvFormat::DEFAULT.localize(Cow::Borrowed("12.5")), "12.5");
+
+        let mut writer = WriterBuilder::new()
+            .delimiter(format.delimiter)
+            .from_writer(Vec::new());
+        writer
+            .write_record(["a", &format.localize(Cow::Borrowed("1.5"))])
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            "a;1,5\n"
+        );
+    }
+}
@@ -0,0 +1,537 @@
+//! Works out the columns of the CSV files from the structs written to them, so the header always matches the order in
+//! which serde writes the values. The names serde gives the fields are normalized to the CSV column names, which carry
+//! their unit, e.g. `distance` becomes `distance_m`.
+//!
+//! The same columns, with their types and units, make up the schema printed with `--csv-schema`, for tools reading the
+//! files.
+
+use serde::de::value::Error;
+use serde::ser::{self, Impossible, Serializer};
+use serde::Serialize;
+use std::any::type_name;
+use std::fmt;
+use std::io::Write;
+
+use crate::columns::{columns_version, CsvFile};
+use crate::units::{measured_unit, unit_system, UnitColumns};
+
+/// The units of the columns whose names don't end with their unit.
+const COLUMN_UNITS: [(&str, &str); 22] = [
+    ("calories", "kcal"),
+    ("duration", "s"),
+    ("duration_moving", "s"),
+    ("age", "s"),
+    ("stance_time_avg", "ms"),
+    ("vertical_oscillation", "mm"),
+    ("vertical_oscillation_avg", "mm"),
+    ("heart_rate", "bpm"),
+    ("average_heart_rate", "bpm"),
+    ("maximum_heart_rate", "bpm"),
+    ("cadence", "rpm"),
+    ("average_cadence", "rpm"),
+    ("maximum_cadence", "rpm"),
+    ("latitude", "deg"),
+    ("longitude", "deg"),
+    ("lat_start", "deg"),
+    ("lon_start", "deg"),
+    ("lat_end", "deg"),
+    ("lon_end", "deg"),
+    ("temperature", "°C"),
+    ("power", "W"),
+    ("pct_3d_fix", "%"),
+];
+
+/// The units of the columns by the end of their names. Distances, lengths and speeds are found by `measured_unit()`.
+const SUFFIX_UNITS: [(&str, &str); 8] = [
+    ("_sec", "s"),
+    ("_secs", "s"),
+    ("_bpm", "bpm"),
+    ("_w", "W"),
+    ("_v", "V"),
+    ("_deg", "deg"),
+    ("_pct", "%"),
+    ("_c", "°C"),
+];
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// What a column holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// Whole numbers.
+    Integer,
+    /// Decimal numbers.
+    Float,
+    /// `true` or `false`.
+    Boolean,
+    /// Text, including the names of enumerations such as the sport.
+    String,
+    /// A date and time in RFC 3339 format, with the UTC offset.
+    Timestamp,
+    /// A UUID.
+    Uuid,
+}
+
+impl ColumnType {
+    /// What a field of the Rust type given holds, from the name of the type. `Option`s hold what their contents hold.
+    /// Types that aren't recognized give `None`.
+    fn of_type(name: &str) -> Option<Self> {
+        let mut name = name;
+        while let Some(inner) = name
+            .strip_prefix("core::option::Option<")
+            .and_then(|inner| inner.strip_suffix('>'))
+        {
+            name = inner;
+        }
+
+        match name {
+            "f32" | "f64" => Some(Self::Float),
+            "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" | "usize" => {
+                Some(Self::Integer)
+            }
+            "bool" => Some(Self::Boolean),
+            _ if name.starts_with("chrono::") => Some(Self::Timestamp),
+            _ if name.starts_with("uuid::") => Some(Self::Uuid),
+            // Lengths and speeds, and durations in seconds
+            _ if name.starts_with("uom::") || name.ends_with("::Duration") => Some(Self::Float),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Integer => "integer",
+            Self::Float => "float",
+            Self::Boolean => "boolean",
+            Self::String => "string",
+            Self::Timestamp => "timestamp",
+            Self::Uuid => "uuid",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A column of a CSV file, as described by the schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColumnSchema {
+    /// The name of the column, as written in the header.
+    pub name: String,
+
+    /// What the column holds.
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+
+    /// The unit of the values, if they have one, e.g. `m` or `bpm`.
+    pub unit: Option<&'static str>,
+}
+
+/// The columns of a CSV file, as described by the schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileSchema {
+    /// The file.
+    pub file: CsvFile,
+
+    /// What the names of the files end with, e.g. `records.csv`, or `None` for the summaries.
+    pub suffix: Option<&'static str>,
+
+    /// The columns, in the order written.
+    pub columns: Vec<ColumnSchema>,
+}
+
+impl CsvFile {
+    /// The columns of the file as written in this run, i.e. in the column version set with
+    /// `Settings::set_columns_version()` and named after the unit system set with `Settings::set_unit_system()`, with
+    /// their types and units.
+    #[must_use]
+    pub fn schema(self) -> FileSchema {
+        let latest = self.latest_columns();
+        let header = self.header(columns_version());
+        let converted = UnitColumns::new(&header);
+        let columns = header
+            .iter()
+            .zip(converted.names)
+            .map(|(column, name)| ColumnSchema {
+                column_type: latest
+                    .iter()
+                    .find(|field| field.name == *column)
+                    .map_or(ColumnType::String, |field| field.column_type),
+                unit: column_unit(column),
+                name,
+            })
+            .collect();
+
+        FileSchema {
+            file: self,
+            suffix: self.suffix(),
+            columns,
+        }
+    }
+}
+
+/// The unit of the column, in the unit system of the run.
+///
+/// # Arguments
+///
+/// `column: &str` -- The name of the column as recorded, e.g. `distance_m`.
+fn column_unit(column: &str) -> Option<&'static str> {
+    measured_unit(column, unit_system())
+        .or_else(|| {
+            COLUMN_UNITS
+                .iter()
+                .find(|(name, _)| *name == column)
+                .map(|(_, unit)| *unit)
+        })
+        .or_else(|| {
+            SUFFIX_UNITS
+                .iter()
+                .find(|(suffix, _)| column.ends_with(suffix))
+                .map(|(_, unit)| *unit)
+        })
+}
+
+/// Writes the schema of the CSV files given as JSON: the column version and unit system of the run, and the name,
+/// type and unit of each column of each file.
+///
+/// # Arguments
+///
+/// - `files: &[CsvFile]` -- The files to describe, e.g. `CsvFile::of_format(ActivityFormat::Fit)`.
+/// - `writer: W` -- Where the JSON is written.
+///
+/// # Errors
+///
+/// Serializing or writing may fail.
+pub fn write_csv_schema<W: Write>(
+    files: &[CsvFile],
+    mut writer: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let schema = serde_json::json!({
+        "columns_version": columns_version().to_string(),
+        "units": unit_system().map(|units| units.to_string()),
+        "files": files.iter().map(|file| file.schema()).collect::<Vec<FileSchema>>(),
+    });
+    serde_json::to_writer_pretty(&mut writer, &schema)?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// A field of a row as serde writes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SerdeField {
+    /// The name serde gives the field.
+    pub(crate) name: String,
+
+    /// What the field holds.
+    pub(crate) column_type: ColumnType,
+}
+
+/// The fields serde writes for the row, in the order they are written. Fields holding a struct with several fields,
+/// e.g. the heart rate zones, are written as one field each, as the CSV writer does. Structs with a single field, e.g.
+/// the durations, keep the name of the field holding them.
+///
+/// # Arguments
+///
+/// `row: &T` -- A row of the file, usually the default value of the struct.
+pub(crate) fn serde_fields<T: Serialize>(row: &T) -> Vec<SerdeField> {
+    let mut collector = FieldCollector::default();
+    if let Err(e) = row.serialize(&mut collector) {
+        log::error!("csv_schema::serde_fields() -- {e}");
+    }
+    collector.fields
+}
+
+/// A serializer which writes nothing, but notes the name and type of each field written.
+#[derive(Debug, Default)]
+struct FieldCollector {
+    /// The fields found so far.
+    fields: Vec<SerdeField>,
+
+    /// The name and Rust type of the field being serialized.
+    current: Option<(String, &'static str)>,
+
+    /// The name to give the only field of the struct being serialized, if it is held by a field of its own.
+    single: Option<String>,
+}
+
+impl FieldCollector {
+    /// Notes the field being serialized, now that its value turned out not to be a struct.
+    fn leaf(&mut self, written: ColumnType) {
+        if let Some((name, type_name)) = self.current.take() {
+            self.fields.push(SerdeField {
+                name,
+                column_type: ColumnType::of_type(type_name).unwrap_or(written),
+            });
+        }
+    }
+}
+
+/// Generates the `Serializer` functions for values written as a single cell of the type given.
+macro_rules! leaf {
+    ($($function:ident($type:ty) => $column_type:ident),* $(,)?) => {
+        $(
+            fn $function(self, _v: $type) -> Result<(), Error> {
+                self.leaf(ColumnType::$column_type);
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl Serializer for &mut FieldCollector {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    leaf!(
+        serialize_bool(bool) => Boolean,
+        serialize_i8(i8) => Integer,
+        serialize_i16(i16) => Integer,
+        serialize_i32(i32) => Integer,
+        serialize_i64(i64) => Integer,
+        serialize_u8(u8) => Integer,
+        serialize_u16(u16) => Integer,
+        serialize_u32(u32) => Integer,
+        serialize_u64(u64) => Integer,
+        serialize_f32(f32) => Float,
+        serialize_f64(f64) => Float,
+        serialize_char(char) => String,
+        serialize_str(&str) => String,
+        serialize_bytes(&[u8]) => String,
+    );
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.leaf(ColumnType::String);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.leaf(ColumnType::String);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.leaf(ColumnType::String);
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Error> {
+        self.leaf(ColumnType::String);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        self.leaf(ColumnType::String);
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(ser::Error::custom(
+            "sequences can't be written to a CSV column",
+        ))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(ser::Error::custom(
+            "tuples can't be written to a CSV column",
+        ))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(ser::Error::custom(
+            "tuples can't be written to a CSV column",
+        ))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(ser::Error::custom(
+            "tuples can't be written to a CSV column",
+        ))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(ser::Error::custom("maps can't be written to a CSV column"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self, Error> {
+        // A struct held by a field is written in place of the field
+        if let Some((name, _)) = self.current.take() {
+            if len == 1 {
+                self.single = Some(name);
+            }
+        }
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(ser::Error::custom(
+            "enums with fields can't be written to a CSV column",
+        ))
+    }
+}
+
+impl ser::SerializeStruct for &mut FieldCollector {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let name = self.single.take().unwrap_or_else(|| key.to_string());
+        self.current = Some((name, type_name::<T>()));
+        value.serialize(&mut **self)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+/// Tests for the csv_schema module
+mod tests {
+    use super::*;
+    use crate::Duration;
+    use serde::Serialize;
+
+    /// The heart rate zones, written as one field each.
+    #[derive(Serialize)]
+    struct Zones {
+        zone_0: f64,
+        zone_1: f64,
+    }
+
+    /// A row with a field of each kind.
+    #[derive(Serialize)]
+    struct Row {
+        name: String,
+        laps: Option<u16>,
+        distance: Option<f64>,
+        moving: bool,
+        duration: Option<Duration>,
+        zones: Zones,
+        start: Option<chrono::DateTime<chrono::Local>>,
+        uuid: Option<uuid::Uuid>,
+    }
+
+    #[test]
+    /// Test finding the fields serde writes, with the structs of several fields written as one field each
+    fn test_serde_fields() {
+        let row = Row {
+            name: String::new(),
+            laps: None,
+            distance: None,
+            moving: false,
+            duration: Some(Duration::from_secs_f64(1.0)),
+            zones: Zones {
+                zone_0: 0.0,
+                zone_1: 0.0,
+            },
+            start: None,
+            uuid: None,
+        };
+        let fields: Vec<(String, ColumnType)> = serde_fields(&row)
+            .into_iter()
+            .map(|field| (field.name, field.column_type))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("name".to_string(), ColumnType::String),
+                ("laps".to_string(), ColumnType::Integer),
+                ("distance".to_string(), ColumnType::Float),
+                ("moving".to_string(), ColumnType::Boolean),
+                ("duration".to_string(), ColumnType::Float),
+                ("zone_0".to_string(), ColumnType::Float),
+                ("zone_1".to_string(), ColumnType::Float),
+                ("start".to_string(), ColumnType::Timestamp),
+                ("uuid".to_string(), ColumnType::Uuid),
+            ]
+        );
+    }
+
+    #[test]
+    /// Test the schema of the FIT records, with the units of the columns
+    fn test_schema() {
+        let schema = CsvFile::FitRecords.schema();
+        assert_eq!(schema.suffix, Some("records.csv"));
+        let column = |name: &str| {
+            schema
+                .columns
+                .iter()
+                .find(|column| column.name == name)
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(column("timestamp").column_type, ColumnType::Timestamp);
+        assert_eq!(column("timestamp").unit, None);
+        assert_eq!(column("distance_m").column_type, ColumnType::Float);
+        assert_eq!(column("distance_m").unit, Some("m"));
+        assert_eq!(column("heartrate_bpm").column_type, ColumnType::Integer);
+        assert_eq!(column("heartrate_bpm").unit, Some("bpm"));
+        assert_eq!(column("speed_ms").unit, Some("m/s"));
+        assert_eq!(column("synthetic").column_type, ColumnType::Boolean);
+
+        let mut json = Vec::new();
+        write_csv_schema(&[CsvFile::MeanMax], &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["files"][0]["file"], "mean_max");
+        assert_eq!(
+            json["files"][0]["columns"][0],
+            serde_json::json!({"name": "duration_sec", "type": "integer", "unit": "s"})
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Defines the `DateSource` enum used to choose which time the date and time rename tokens (`%year`, `%month`, etc.)
+//! are taken from, along with the function that fills in those tokens.
+//!
+//! The source is set once for the whole run using `Settings::set_date_source()`, and applies to all the file formats.
+
+use crate::placeholder::placeholder;
+use crate::Settings;
+use chrono::{DateTime, Datelike, FixedOffset, Local, Timelike};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Which time the date and time rename tokens are taken from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum DateSource {
+    /// The time the activity started. Falls back to the time the file was created if the start isn't known.
+    #[default]
+    Start,
+    /// The time the file was created according to the file itself -- the FIT `time_created` or the GPX metadata time.
+    /// Falls back to the start of the activity if the file doesn't say.
+    Created,
+    /// The time the file was last modified on disk.
+    FileMtime,
+}
+
+impl DateSource {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 3] = ["start", "created", "file-mtime"];
+
+    /// Picks the time to use for the date and time tokens.
+    ///
+    /// # Arguments
+    ///
+    /// - `start: Option<DateTime<FixedOffset>>` -- The time the activity started.
+    /// - `created: Option<DateTime<FixedOffset>>` -- The time the file was created, according to the file.
+    /// - `filename: &str` -- The file, used to find the modification time.
+    ///
+    /// # Returns
+    ///
+    /// `Option<DateTime<FixedOffset>>` -- The time, or `None` if neither the chosen time nor its fallback is known.
+    #[must_use]
+    pub fn pick(
+        self,
+        start: Option<DateTime<FixedOffset>>,
+        created: Option<DateTime<FixedOffset>>,
+        filename: &str,
+    ) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Self::Start => start.or(created),
+            Self::Created => created.or(start),
+            Self::FileMtime => file_mtime(filename),
+        }
+    }
+}
+
+impl FromStr for DateSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "start" => Ok(Self::Start),
+            "created" => Ok(Self::Created),
+            "file-mtime" | "mtime" => Ok(Self::FileMtime),
+            _ => Err(format!("Unknown date source {s}.")),
+        }
+    }
+}
+
+impl fmt::Display for DateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Start => write!(f, "start"),
+            Self::Created => write!(f, "created"),
+            Self::FileMtime => write!(f, "file-mtime"),
+        }
+    }
+}
+
+impl Settings {
+    /// Sets which time the date and time rename tokens are taken from.
+    ///
+    /// # Arguments
+    ///
+    /// `source: DateSource` -- The time to use.
+    pub fn set_date_source(&mut self, source: DateSource) {
+        self.date_source = source;
+    }
+}
+
+/// The time the date and time rename tokens are currently taken from.
+pub(crate) fn date_source() -> DateSource {
+    Settings::current().date_source
+}
+
+/// The time the file was last modified, in the local time zone.
+fn file_mtime(filename: &str) -> Option<DateTime<FixedOffset>> {
+    let modified = std::fs::metadata(filename).ok()?.modified().ok()?;
+    Some(DateTime::<Local>::from(modified).fixed_offset())
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Inserts the date and time rename tokens (`%year`, `%yr`, `%month`, etc.) into the values, along with `%start_time`
+/// as a compact ISO 8601 time that is safe in file names, e.g. `20220210T065010`. If the time isn't known,
+/// the tokens are filled with zeros, or the placeholder if one has been set.
+///
+/// # Arguments
+///
+/// - `values: &mut HashMap<String, String>` -- The rename tokens and their values.
+/// - `time: Option<DateTime<FixedOffset>>` -- The time to take the values from.
+pub(crate) fn insert_date_tokens(
+    values: &mut HashMap<String, String>,
+    time: Option<DateTime<FixedOffset>>,
+) {
+    let mut insert = |long: &str, short: &str, value: String| {
+        values.insert(long.to_string(), value.clone());
+        values.insert(short.to_string(), value);
+    };
+
+    if let Some(tc) = time {
+        let (pm, hour12) = tc.hour12();
+
+        insert("%year", "%yr", format!("{:04}", tc.year()));
+        insert("%month", "%mn", format!("{:02}", tc.month()));
+        insert("%day", "%dy", format!("{:02}", tc.day()));
+        insert("%hour", "%hr", format!("{:02}", tc.hour()));
+        insert("%24hour", "%24", format!("{:02}", tc.hour()));
+        insert("%12hour", "%12", format!("{hour12:02}"));
+        insert("%ampm", "%ap", if pm { "pm" } else { "am" }.to_string());
+        insert("%minute", "%mt", format!("{:02}", tc.minute()));
+        insert("%second", "%sc", format!("{:02}", tc.second()));
+        insert("%weekday", "%wd", tc.weekday().to_string());
+        insert("%start_time", "%st", tc.format("%Y%m%dT%H%M%S").to_string());
+    } else {
+        insert("%year", "%yr", placeholder("0000"));
+        for (long, short) in [
+            ("%month", "%mn"),
+            ("%day", "%dy"),
+            ("%hour", "%hr"),
+            ("%24hour", "%24"),
+            ("%12hour", "%12"),
+            ("%minute", "%mt"),
+            ("%second", "%sc"),
+            ("%weekday", "%wd"),
+        ] {
+            insert(long, short, placeholder("00"));
+        }
+        insert("%ampm", "%ap", placeholder("ampm"));
+        insert("%start_time", "%st", placeholder("00000000T000000"));
+    }
+}
+
+#[cfg(test)]
+/// Tests for the date_source module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test picking the time with fallbacks
+    fn test_pick() {
+        let start = DateTime::parse_from_rfc3339("2022-02-10T06:50:10-08:00").ok();
+        let created = DateTime::parse_from_rfc3339("2022-02-10T06:49:58-08:00").ok();
+
+        assert_eq!(DateSource::Start.pick(start, created, "none"), start);
+        assert_eq!(DateSource::Created.pick(start, created, "none"), created);
+        assert_eq!(DateSource::Created.pick(start, None, "none"), start);
+        assert_eq!(DateSource::Start.pick(None, None, "none"), None);
+        assert!(DateSource::FileMtime
+            .pick(start, created, "../data/rowing.fit")
+            .is_some());
+        assert!(DateSource::FileMtime.pick(start, created, "none").is_none());
+        assert_eq!("file-mtime".parse(), Ok(DateSource::FileMtime));
+        assert!("modified".parse::<DateSource>().is_err());
+    }
+
+    #[test]
+    /// Test the date and time tokens
+    fn test_insert_date_tokens() {
+        let mut values = HashMap::new();
+        insert_date_tokens(
+            &mut values,
+            DateTime::parse_from_rfc3339("2022-02-10T18:05:09-08:00").ok(),
+        );
+
+        assert_eq!(values["%year"], "2022");
+        assert_eq!(values["%mn"], "02");
+        assert_eq!(values["%24"], "18");
+        assert_eq!(values["%12hour"], "06");
+        assert_eq!(values["%ap"], "pm");
+        assert_eq!(values["%second"], "09");
+        assert_eq!(values["%wd"], "Thu");
+        assert_eq!(values["%start_time"], "20220210T180509");
+
+        let mut values = HashMap::new();
+        insert_date_tokens(&mut values, None);
+        assert_eq!(values["%yr"], "0000");
+        assert_eq!(values["%ampm"], "ampm");
+        assert_eq!(values["%st"], "00000000T000000");
+        assert_eq!(values.len(), 22);
+    }
+}
@@ -0,0 +1,242 @@
+//! Finds the same activity saved more than once, e.g. exported as both FIT and TCX from a head unit. Two activities are
+//! duplicates if they start at about the same time and have about the same duration and distance. The formats don't
+//! have to match, and the distances often differ a little between the formats.
+//!
+//! Of each set of duplicates, the copy with the most detail is kept: FIT before TCX, and TCX before GPX.
+
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{ActivityFormat, QueryFields};
+
+/// How close two activities have to be to count as duplicates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DedupTolerance {
+    /// The most the start times may differ by (Seconds).
+    pub start_sec: f64,
+
+    /// The most the durations may differ by (Seconds).
+    pub duration_sec: f64,
+
+    /// The most the distances may differ by, in percent of the longer distance.
+    pub distance_pct: f64,
+}
+
+impl Default for DedupTolerance {
+    fn default() -> Self {
+        Self {
+            start_sec: 60.0,
+            duration_sec: 60.0,
+            distance_pct: 2.0,
+        }
+    }
+}
+
+impl DedupTolerance {
+    /// Whether the two activities are the same, within the tolerance. The durations and the distances are only
+    /// compared when both activities have them.
+    fn matches(&self, first: &QueryFields, second: &QueryFields) -> bool {
+        let (Some(first_start), Some(second_start)) = (first.start_time, second.start_time) else {
+            return false;
+        };
+        let start_ok = seconds_between(first_start, second_start).abs() <= self.start_sec;
+
+        let duration_ok = match (first.duration_sec, second.duration_sec) {
+            (Some(first), Some(second)) => (first - second).abs() <= self.duration_sec,
+            _ => true,
+        };
+        let distance_ok = match (first.distance_m, second.distance_m) {
+            (Some(first), Some(second)) if first.max(second) > 0.0 => {
+                (first - second).abs() / first.max(second) * 100.0 <= self.distance_pct
+            }
+            _ => true,
+        };
+
+        start_ok && duration_ok && distance_ok
+    }
+}
+
+/// The copies of an activity found in more than one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateSet {
+    /// When the activity started, as found in the copy kept.
+    pub start_time: DateTime<Local>,
+
+    /// The file to keep.
+    pub keep: String,
+
+    /// The other copies, which can be removed.
+    pub redundant: Vec<String>,
+}
+
+impl fmt::Display for DuplicateSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Activity starting {}:", self.start_time)?;
+        write!(f, "  keep       {}", self.keep)?;
+        for filename in &self.redundant {
+            write!(f, "\n  redundant  {filename}")?;
+        }
+        Ok(())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Finds the sets of activities that are copies of each other. Activities without a start time can't be matched, and
+/// files holding more than one activity, e.g. multisport or chained FIT files, are left out so a file is never marked
+/// redundant for only one of its activities.
+///
+/// # Arguments
+///
+/// - `activities: &[QueryFields]` -- The summaries of the activities, with their file names, in any order.
+/// - `tolerance: &DedupTolerance` -- How close the activities have to be.
+///
+/// # Returns
+///
+/// `Vec<DuplicateSet>` -- Each set of copies, ordered by start time, with the copy to keep picked out.
+#[must_use]
+pub fn duplicate_sets(activities: &[QueryFields], tolerance: &DedupTolerance) -> Vec<DuplicateSet> {
+    let mut per_file: HashMap<&str, usize> = HashMap::new();
+    for activity in activities {
+        *per_file
+            .entry(activity.filename.as_deref().unwrap_or_default())
+            .or_default() += 1;
+    }
+
+    let mut candidates: Vec<&QueryFields> = activities
+        .iter()
+        .filter(|activity| {
+            let filename = activity.filename.as_deref().unwrap_or_default();
+            if per_file.get(filename).copied().unwrap_or_default() > 1 {
+                log::debug!(
+                    "dedup::duplicate_sets() -- {filename} holds several activities. Skipped."
+                );
+                return false;
+            }
+            !filename.is_empty() && activity.start_time.is_some()
+        })
+        .collect();
+    candidates.sort_by_key(|activity| activity.start_time);
+
+    // Each activity joins the first set it matches an activity of, so the copies end up together
+    let mut sets: Vec<Vec<&QueryFields>> = Vec::new();
+    let mut open = 0;
+    for activity in candidates {
+        // Sets whose last activity started too long before this one can't take any more
+        while open < sets.len()
+            && sets[open]
+                .last()
+                .is_some_and(|last| !started_within(last, activity, tolerance.start_sec))
+        {
+            open += 1;
+        }
+        match sets[open..]
+            .iter_mut()
+            .find(|set| set.iter().any(|other| tolerance.matches(other, activity)))
+        {
+            Some(set) => set.push(activity),
+            None => sets.push(vec![activity]),
+        }
+    }
+
+    sets.into_iter()
+        .filter(|set| set.len() > 1)
+        .filter_map(|mut set| {
+            set.sort_by_key(|activity| keep_order(activity));
+            let keep = set.first()?;
+            Some(DuplicateSet {
+                start_time: keep.start_time?,
+                keep: keep.filename.clone()?,
+                redundant: set[1..]
+                    .iter()
+                    .filter_map(|activity| activity.filename.clone())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Whether the second activity started at most this many seconds after the first.
+fn started_within(first: &QueryFields, second: &QueryFields, secs: f64) -> bool {
+    match (first.start_time, second.start_time) {
+        (Some(first), Some(second)) => seconds_between(first, second) <= secs,
+        _ => false,
+    }
+}
+
+/// The number of seconds from one time to another, negative if the second time is the earlier.
+#[allow(clippy::cast_precision_loss)]
+fn seconds_between(start: DateTime<Local>, end: DateTime<Local>) -> f64 {
+    (end - start).num_milliseconds() as f64 / 1_000.0
+}
+
+/// The order in which the copies are kept: FIT before TCX before GPX, then by file name.
+fn keep_order(activity: &QueryFields) -> (u8, String) {
+    let filename = activity.filename.clone().unwrap_or_default();
+    let rank = match ActivityFormat::from_filename(&filename) {
+        Some(ActivityFormat::Fit) => 0,
+        Some(ActivityFormat::Tcx) => 1,
+        Some(ActivityFormat::Gpx) => 2,
+        None => 3,
+    };
+    (rank, filename)
+}
+
+#[cfg(test)]
+/// Tests for the dedup module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// An activity in the file, starting `secs` into the day, lasting an hour and covering the distance in meters.
+    fn activity(filename: &str, secs: i64, distance_m: f64) -> QueryFields {
+        QueryFields {
+            filename: Some(filename.to_string()),
+            start_time: Local.timestamp_opt(1_600_000_000 + secs, 0).single(),
+            duration_sec: Some(3_600.0),
+            distance_m: Some(distance_m),
+            ..QueryFields::default()
+        }
+    }
+
+    #[test]
+    /// Test finding the copies across formats, keeping the FIT file, and telling different activities apart
+    fn test_duplicate_sets() {
+        let activities = [
+            activity("ride.gpx", 5, 40_100.0),
+            activity("ride.tcx", 2, 40_050.0),
+            activity("ride.fit", 0, 40_000.0),
+            // Same start, but much shorter
+            activity("short.fit", 0, 10_000.0),
+            // The next day
+            activity("other.tcx", 86_400, 40_000.0),
+            activity("other.gpx", 86_430, 39_800.0),
+            activity("alone.fit", 200_000, 5_000.0),
+        ];
+
+        let sets = duplicate_sets(&activities, &DedupTolerance::default());
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].keep, "ride.fit");
+        assert_eq!(sets[0].redundant, ["ride.tcx", "ride.gpx"]);
+        assert_eq!(sets[1].keep, "other.tcx");
+        assert_eq!(sets[1].redundant, ["other.gpx"]);
+        assert!(sets[0].to_string().contains("redundant  ride.gpx"));
+
+        let strict = DedupTolerance {
+            distance_pct: 0.2,
+            ..DedupTolerance::default()
+        };
+        assert_eq!(duplicate_sets(&activities, &strict).len(), 1);
+    }
+
+    #[test]
+    /// Test that files with several activities are never marked redundant
+    fn test_several_activities_skipped() {
+        let activities = [
+            activity("triathlon.fit", 0, 40_000.0),
+            activity("triathlon.fit", 4_000, 10_000.0),
+            activity("bike.tcx", 0, 40_000.0),
+        ];
+        assert!(duplicate_sets(&activities, &DedupTolerance::default()).is_empty());
+    }
+}
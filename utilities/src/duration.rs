@@ -2,21 +2,30 @@
 
 use chrono::{DateTime, Local};
 use serde::{
+    de::{Deserializer, Error},
     ser::{SerializeStruct, Serializer},
     Deserialize, Serialize,
 };
 use std::ops::{Add, AddAssign, Sub};
+use std::str::FromStr;
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-/// Wrapper for `std::time::Duration` so we can derive Serialize and Deserialize traits
-#[derive(Deserialize, PartialEq, Eq, PartialOrd, Clone, Copy, Default, Debug)]
+/// Wrapper for `std::time::Duration` so we can implement the Serialize and Deserialize traits
+#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Default, Debug)]
 pub struct Duration(pub std::time::Duration);
 
 impl Duration {
-    /// Get duration from seconds.
+    /// Get duration from seconds. Negative and NaN values give a zero duration, and values too large to hold give the
+    /// longest possible duration, so odd values in a file can't cause a panic.
     #[must_use]
     pub fn from_secs_f64(secs: f64) -> Self {
-        Self(std::time::Duration::from_secs_f64(secs))
+        Self(
+            std::time::Duration::try_from_secs_f64(secs).unwrap_or(if secs > 0.0 {
+                std::time::Duration::MAX
+            } else {
+                std::time::Duration::ZERO
+            }),
+        )
     }
 
     #[allow(dead_code)]
@@ -44,10 +53,6 @@ impl Duration {
     ///
     /// * `Self` -- The duration between the two timestamps.
     ///
-    /// # Panics
-    ///
-    /// * If the duration is out of bounds. This should never happen.
-    ///
     /// # Example
     ///
     /// ```
@@ -64,14 +69,13 @@ impl Duration {
     /// ```
     #[must_use]
     pub fn between(ts1: &DateTime<Local>, ts2: &DateTime<Local>) -> Self {
-        Self(if ts2 > ts1 {
-            // ts2 is after ts1
-            chrono::Duration::to_std(&ts2.signed_duration_since(ts1.with_timezone(&Local)))
-                .expect("types::Duration::between() -- ts2 > ts1: Duration out of bounds.")
-        } else {
-            chrono::Duration::to_std(&ts1.signed_duration_since(ts2.with_timezone(&Local)))
-                .expect("types::Duration::between() -- ts1 >= ts2: Duration out of bounds.")
-        })
+        // The difference is never negative, so it always fits
+        Self(
+            ts2.signed_duration_since(ts1)
+                .abs()
+                .to_std()
+                .unwrap_or(std::time::Duration::MAX),
+        )
     }
 
     /// Get the number of seconds in the duration.
@@ -79,36 +83,95 @@ impl Duration {
     pub const fn as_secs(&self) -> u64 {
         self.0.as_secs()
     }
+
+    /// Adds two durations.
+    ///
+    /// # Returns
+    ///
+    /// `Option<Self>` -- The sum, or `None` if it is too large to hold.
+    #[must_use]
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_add(rhs.0) {
+            Some(sum) => Some(Self(sum)),
+            None => None,
+        }
+    }
+
+    /// Subtracts a duration from this one.
+    ///
+    /// # Returns
+    ///
+    /// `Option<Self>` -- The difference, or `None` if `rhs` is longer than this duration.
+    #[must_use]
+    pub const fn checked_sub(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_sub(rhs.0) {
+            Some(difference) => Some(Self(difference)),
+            None => None,
+        }
+    }
+
+    /// Adds two durations, giving the longest possible duration if the sum is too large to hold.
+    #[must_use]
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+
+    /// Subtracts a duration from this one, giving a zero duration if `rhs` is longer than this duration.
+    #[must_use]
+    pub const fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Adds up the durations.
+    ///
+    /// # Arguments
+    ///
+    /// `durations: I` -- The durations to add up.
+    ///
+    /// # Returns
+    ///
+    /// `Option<Self>` -- The total, or `None` if it is too large to hold.
+    pub fn checked_sum<I: IntoIterator<Item = Self>>(durations: I) -> Option<Self> {
+        durations
+            .into_iter()
+            .try_fold(Self::default(), Self::checked_add)
+    }
 }
 
 impl Add for Duration {
     type Output = Self;
     /// Implements the `+` operation for Duration.
+    ///
+    /// # Panics
+    ///
+    /// If the sum is too large to hold. Use `checked_add()` or `saturating_add()` for durations read from a file.
     fn add(self, rhs: Self) -> Self::Output {
-        Self(
-            self.0
-                .checked_add(rhs.0)
-                .expect("overflow when adding durations."),
-        )
+        self.checked_add(rhs)
+            .expect("overflow when adding durations.")
     }
 }
 
 impl AddAssign for Duration {
     /// Implements the `+=` operation for Duration.
+    ///
+    /// # Panics
+    ///
+    /// If the sum is too large to hold, as for `+`.
     fn add_assign(&mut self, rhs: Self) {
-        self.0 = self.0 + rhs.0;
+        *self = *self + rhs;
     }
 }
 
 impl Sub for Duration {
     type Output = Self;
     /// Implements the `-` operation for Duration.
+    ///
+    /// # Panics
+    ///
+    /// If `rhs` is longer than `self`. Use `checked_sub()` or `saturating_sub()` for durations read from a file.
     fn sub(self, rhs: Self) -> Self {
-        Self(
-            self.0
-                .checked_sub(rhs.0)
-                .expect("overflow when subtracting durations"),
-        )
+        self.checked_sub(rhs)
+            .expect("overflow when subtracting durations")
     }
 }
 
@@ -122,6 +185,29 @@ impl std::fmt::Display for Duration {
     }
 }
 
+impl FromStr for Duration {
+    type Err = String;
+
+    /// Reads a duration as `hh:mm:ss`, `mm:ss` or seconds, e.g. `1:05:00`, `25:00` or `90`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage =
+            || format!("Unknown duration {s}. Use hh:mm:ss, mm:ss or seconds, e.g. 00:25:00.");
+        let parts = s
+            .trim()
+            .split(':')
+            .map(|part| part.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| usage())?;
+        if parts.len() > 3 || parts.iter().any(|part| !part.is_finite() || *part < 0.0) {
+            return Err(usage());
+        }
+
+        Ok(Self::from_secs_f64(
+            parts.iter().fold(0.0, |total, part| total * 60.0 + part),
+        ))
+    }
+}
+
 impl Serialize for Duration {
     /// Serializes the Duration for output into various types of files.
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -134,8 +220,26 @@ impl Serialize for Duration {
     }
 }
 
+impl<'de> Deserialize<'de> for Duration {
+    /// Reads back a Duration written by `serialize`, i.e. `{"secs": 604.9}`.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Secs {
+            secs: f64,
+        }
+
+        let Secs { secs } = Secs::deserialize(deserializer)?;
+        std::time::Duration::try_from_secs_f64(secs)
+            .map(Self)
+            .map_err(D::Error::custom)
+    }
+}
+
 #[cfg(test)]
-///
+/// Tests for the duration module
 mod tests {
     use super::*;
 
@@ -145,6 +249,13 @@ mod tests {
 
         assert_eq!(dur.0.as_secs(), 120);
         assert_eq!(dur.0.as_millis(), 120_100);
+
+        assert_eq!(Duration::from_secs_f64(-1.0), Duration::default());
+        assert_eq!(Duration::from_secs_f64(f64::NAN), Duration::default());
+        assert_eq!(
+            Duration::from_secs_f64(f64::INFINITY).0,
+            std::time::Duration::MAX
+        );
     }
 
     #[test]
@@ -165,6 +276,28 @@ mod tests {
         assert_eq!(dur.0.as_nanos(), 123_123_000_000);
     }
 
+    #[test]
+    fn test_from_str() {
+        assert_eq!("00:25:00".parse(), Ok(Duration::from_secs_f64(1_500.0)));
+        assert_eq!("1:05:30".parse(), Ok(Duration::from_secs_f64(3_930.0)));
+        assert_eq!("2:30".parse(), Ok(Duration::from_secs_f64(150.0)));
+        assert_eq!("90".parse(), Ok(Duration::from_secs_f64(90.0)));
+        assert!("10 minutes".parse::<Duration>().is_err());
+        assert!("1:2:3:4".parse::<Duration>().is_err());
+        assert!("-5".parse::<Duration>().is_err());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let dur = Duration::from_millis_u64(604_500);
+        let json = serde_json::to_string(&dur).unwrap();
+        let back: Duration = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(json, r#"{"secs":604.5}"#);
+        assert_eq!(back, dur);
+        assert!(serde_json::from_str::<Duration>(r#"{"secs":-1.0}"#).is_err());
+    }
+
     #[test]
     fn test_between() {
         let t1 = Local::now();
@@ -313,7 +446,32 @@ mod tests {
     }
 
     #[test]
-    ///
+    /// Tests the checked and saturating arithmetic, which must not panic
+    fn test_checked_ops() {
+        let (one, two) = (
+            Duration::from_millis_u64(1_000),
+            Duration::from_millis_u64(2_000),
+        );
+        let max = Duration(std::time::Duration::MAX);
+
+        assert_eq!(one.checked_add(two), Some(Duration::from_millis_u64(3_000)));
+        assert!(max.checked_add(one).is_none());
+        assert_eq!(max.saturating_add(one), max);
+
+        assert_eq!(two.checked_sub(one), Some(one));
+        assert!(one.checked_sub(two).is_none());
+        assert_eq!(one.saturating_sub(two), Duration::default());
+
+        assert_eq!(
+            Duration::checked_sum([one, two, one]),
+            Some(Duration::from_millis_u64(4_000))
+        );
+        assert!(Duration::checked_sum([one, max]).is_none());
+        assert_eq!(Duration::checked_sum([]), Some(Duration::default()));
+    }
+
+    #[test]
+    /// Test the Display implementation
     fn test_display() {
         let d1 = Duration::from_secs_f64(3750.2);
         println!("d1 = {d1}");
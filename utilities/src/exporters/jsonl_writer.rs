@@ -0,0 +1,120 @@
+//! Writes rows as JSON Lines (also known as NDJSON): one JSON object per line, with the same columns and values as the
+//! CSV file. Numbers and true/false become JSON numbers and booleans, empty cells become `null`, and everything else,
+//! including the timestamps, is written as text.
+
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+use serde_json::Value;
+use std::error::Error;
+use std::io::Write;
+
+use super::RowCells;
+use crate::columns::CsvFile;
+
+/// A row as a JSON object, with the keys in the order of the columns.
+struct JsonRow<'a> {
+    /// The names of the columns.
+    names: &'a [String],
+
+    /// The values of the columns.
+    values: Vec<Value>,
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.names.len()))?;
+        for (name, value) in self.names.iter().zip(&self.values) {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// The JSON value of a CSV cell.
+fn json_value(cell: &str) -> Value {
+    if cell.is_empty() {
+        Value::Null
+    } else if let Ok(int) = cell.parse::<i64>() {
+        Value::from(int)
+    } else if let Some(number) = cell
+        .parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+    {
+        Value::Number(number)
+    } else if let Ok(boolean) = cell.parse::<bool>() {
+        Value::Bool(boolean)
+    } else {
+        Value::String(cell.to_string())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes the rows as JSON Lines to any writer, with the columns of the CSV file given in the version set by
+/// `Settings::set_columns_version()`.
+///
+/// # Arguments
+///
+/// - `rows: I` -- The rows to write.
+/// - `file: CsvFile` -- The CSV file whose columns are written.
+/// - `writer: W` -- Where the JSON Lines are written.
+///
+/// # Errors
+///
+/// Serializing or writing the rows may fail.
+pub(crate) fn write_jsonl<T, I, W>(
+    rows: I,
+    file: CsvFile,
+    mut writer: W,
+) -> Result<(), Box<dyn Error>>
+where
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+    W: Write,
+{
+    let mut cells = RowCells::new(file);
+    for row in rows {
+        let values = cells
+            .cells(row)?
+            .iter()
+            .map(|cell| json_value(cell))
+            .collect();
+        let names = cells.names();
+        serde_json::to_writer(&mut writer, &JsonRow { names, values })?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+/// Tests for the jsonl_writer module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the JSON values of the cells
+    fn test_json_value() {
+        assert_eq!(json_value(""), Value::Null);
+        assert_eq!(json_value("42"), Value::from(42));
+        assert_eq!(json_value("2.5"), Value::from(2.5));
+        assert_eq!(json_value("true"), Value::Bool(true));
+        assert_eq!(json_value("NaN"), Value::from("NaN"));
+        assert_eq!(
+            json_value("2024-05-01T07:00:00Z"),
+            Value::from("2024-05-01T07:00:00Z")
+        );
+    }
+
+    #[test]
+    /// Test that the keys are written in the order of the columns
+    fn test_write_jsonl() {
+        let mut jsonl = Vec::new();
+        write_jsonl([("1", "", "x")], CsvFile::MeanMax, &mut jsonl).unwrap();
+        assert_eq!(
+            String::from_utf8(jsonl).unwrap(),
+            "{\"duration_sec\":1,\"power_avg_w\":null,\"heartrate_avg_bpm\":\"x\"}\n"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Writes the records, waypoints and trackpoints in the format chosen for the export: CSV, Parquet for loading large
+//! files straight into pandas, polars or DuckDB, or JSON Lines for jq, Elasticsearch and log pipelines. All the formats
+//! have the same columns, as listed in `columns`.
+//!
+//! Parquet needs the `parquet` feature. Without it, asking for Parquet gives an error rather than a CSV file.
+
+mod jsonl_writer;
+#[cfg(feature = "parquet")]
+mod parquet_writer;
+
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::columns::{columns_version, ColumnWriter, ColumnsVersion, CsvFile};
+use crate::units::UnitColumns;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The formats the records, waypoints and trackpoints can be exported in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum ExportFormat {
+    /// Comma separated values.
+    #[default]
+    Csv,
+    /// Apache Parquet, a compressed column format.
+    Parquet,
+    /// JSON Lines, one JSON object per row.
+    Jsonl,
+}
+
+impl ExportFormat {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 3] = ["csv", "parquet", "jsonl"];
+
+    /// The file extension of the format, e.g. `csv` for `records.csv`.
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "parquet" | "pq" => Ok(Self::Parquet),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            _ => Err(format!(
+                "Unknown export format {s}. Use csv, parquet or jsonl."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// Writes the rows in the format given, with the columns of the CSV file given.
+///
+/// # Arguments
+///
+/// - `rows: I` -- The rows to write, already wrapped in `Rounded` if the precision should apply.
+/// - `file: CsvFile` -- The file being written, which decides the columns.
+/// - `format: ExportFormat` -- The format to write.
+/// - `writer: W` -- Where the rows are written.
+///
+/// # Errors
+///
+/// Parquet may not be available in this build. Serializing or writing the rows may fail.
+pub(crate) fn write_rows<T, I, W>(
+    rows: I,
+    file: CsvFile,
+    format: ExportFormat,
+    writer: W,
+) -> Result<(), Box<dyn Error>>
+where
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+    W: Write + Send,
+{
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = ColumnWriter::new(writer, file);
+            writer.write_header()?;
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => parquet_writer::write_parquet(rows, file, writer),
+        #[cfg(not(feature = "parquet"))]
+        ExportFormat::Parquet => {
+            Err("Parquet export isn't available. Build with the parquet feature to use it.".into())
+        }
+        ExportFormat::Jsonl => jsonl_writer::write_jsonl(rows, file, writer),
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Serializes rows the same way as the CSV file and picks out the cells of the columns in the version set by
+/// `Settings::set_columns_version()`, so the other formats get the same columns and values as the CSV file.
+struct RowCells {
+    /// The names and units of the columns written.
+    columns: UnitColumns,
+
+    /// Where each column is found in a row serialized with the latest columns, if it is there at all.
+    positions: Vec<Option<usize>>,
+
+    /// The last row serialized.
+    record: StringRecord,
+}
+
+impl RowCells {
+    /// Prepares to serialize the rows of the file given.
+    fn new(file: CsvFile) -> Self {
+        // The rows are serialized with the latest columns, so pick out the ones in the version
+        let names = file.header(columns_version());
+        let latest = file.header(ColumnsVersion::LATEST);
+        let positions = names
+            .iter()
+            .map(|name| latest.iter().position(|column| column == name))
+            .collect();
+
+        Self {
+            columns: UnitColumns::new(&names),
+            positions,
+            record: StringRecord::new(),
+        }
+    }
+
+    /// The names of the columns written, in the unit system of the run.
+    fn names(&self) -> &[String] {
+        &self.columns.names
+    }
+
+    /// Serializes the row and returns its cells in the order of `names()`, converted to the unit system of the run.
+    /// Missing and empty values are empty strings.
+    ///
+    /// # Errors
+    ///
+    /// Serializing the row may fail.
+    fn cells<T: Serialize>(&mut self, row: T) -> Result<Vec<Cow<'_, str>>, Box<dyn Error>> {
+        let mut buffer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        buffer.serialize(row)?;
+        let bytes = buffer.into_inner().map_err(|e| e.to_string())?;
+        ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(bytes.as_slice())
+            .read_record(&mut self.record)?;
+
+        Ok(self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| {
+                let cell = position
+                    .and_then(|position| self.record.get(position))
+                    .unwrap_or_default();
+                self.columns.convert(i, cell)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+/// Tests for the exporters module
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::{Rounded, TCXTrackpointList};
+
+    #[test]
+    /// Test the format names
+    fn test_export_format() {
+        for value in ExportFormat::VALUES {
+            assert_eq!(value.parse::<ExportFormat>().unwrap().to_string(), value);
+        }
+        assert!("xlsx".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test writing the same trackpoints as CSV, Parquet and JSON Lines
+    fn test_write_rows() {
+        let trackpoints = TCXTrackpointList::from_file("../data/running.tcx").unwrap();
+        let rows = || trackpoints.trackpoints.iter().map(Rounded);
+
+        let mut csv = Vec::new();
+        write_rows(rows(), CsvFile::TcxTrackpoints, ExportFormat::Csv, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap().lines().count(),
+            trackpoints.trackpoints.len() + 1
+        );
+
+        let mut parquet = Vec::new();
+        let written = write_rows(
+            rows(),
+            CsvFile::TcxTrackpoints,
+            ExportFormat::Parquet,
+            &mut parquet,
+        );
+        if cfg!(feature = "parquet") {
+            written.unwrap();
+            assert!(parquet.starts_with(b"PAR1"));
+        } else {
+            assert!(written.is_err());
+        }
+
+        let mut jsonl = Vec::new();
+        write_rows(
+            rows(),
+            CsvFile::TcxTrackpoints,
+            ExportFormat::Jsonl,
+            &mut jsonl,
+        )
+        .unwrap();
+        let jsonl = String::from_utf8(jsonl).unwrap();
+        assert_eq!(jsonl.lines().count(), trackpoints.trackpoints.len());
+        assert!(jsonl.lines().all(
+            |line| serde_json::from_str::<serde_json::Value>(line).is_ok_and(|v| v.is_object())
+        ));
+    }
+}
@@ -0,0 +1,231 @@
+//! Writes rows to a Parquet file with the same columns and values as the CSV file. The type of each column is worked
+//! out from the values: whole numbers, decimals, true/false, timestamps or text.
+//! Columns holding a mix of types are written as text.
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray,
+    TimestampMillisecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::DateTime;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use std::error::Error;
+use std::io::Write;
+use std::sync::Arc;
+
+use super::RowCells;
+use crate::columns::CsvFile;
+
+/// The values of a column, collected until the rows are written.
+#[derive(Debug, Clone, PartialEq)]
+enum Column {
+    /// No values yet, only empty cells.
+    Empty,
+    /// Whole numbers.
+    Int(Vec<Option<i64>>),
+    /// Decimal numbers.
+    Float(Vec<Option<f64>>),
+    /// True or false.
+    Bool(Vec<Option<bool>>),
+    /// Anything else, including timestamps until the column is written.
+    Text(Vec<Option<String>>),
+}
+
+impl Column {
+    /// Adds a value to the column, changing the type of the column if the value doesn't fit.
+    ///
+    /// # Arguments
+    ///
+    /// - `value: &str` -- The value as written to the CSV. Empty values are missing.
+    /// - `rows: usize` -- The number of rows before this one, to fill in the empty cells when the first value arrives.
+    #[allow(clippy::cast_precision_loss)]
+    fn push(&mut self, value: &str, rows: usize) {
+        if value.is_empty() {
+            match self {
+                Self::Empty => (),
+                Self::Int(values) => values.push(None),
+                Self::Float(values) => values.push(None),
+                Self::Bool(values) => values.push(None),
+                Self::Text(values) => values.push(None),
+            }
+            return;
+        }
+
+        let (int, float, boolean) = (
+            value.parse::<i64>().ok(),
+            value.parse::<f64>().ok(),
+            value.parse::<bool>().ok(),
+        );
+
+        // Pick up the type from the first value
+        if *self == Self::Empty {
+            *self = if int.is_some() {
+                Self::Int(vec![None; rows])
+            } else if float.is_some() {
+                Self::Float(vec![None; rows])
+            } else if boolean.is_some() {
+                Self::Bool(vec![None; rows])
+            } else {
+                Self::Text(vec![None; rows])
+            };
+        }
+
+        match self {
+            Self::Int(values) if int.is_some() => values.push(int),
+            Self::Int(values) if float.is_some() => {
+                let mut floats: Vec<Option<f64>> =
+                    values.iter().map(|value| value.map(|v| v as f64)).collect();
+                floats.push(float);
+                *self = Self::Float(floats);
+            }
+            Self::Float(values) if float.is_some() => values.push(float),
+            Self::Bool(values) if boolean.is_some() => values.push(boolean),
+            Self::Text(values) => values.push(Some(value.to_string())),
+            _ => {
+                let mut texts = self.texts();
+                texts.push(Some(value.to_string()));
+                *self = Self::Text(texts);
+            }
+        }
+    }
+
+    /// The values written as text.
+    fn texts(&self) -> Vec<Option<String>> {
+        match self {
+            Self::Empty => Vec::new(),
+            Self::Int(values) => to_texts(values),
+            Self::Float(values) => to_texts(values),
+            Self::Bool(values) => to_texts(values),
+            Self::Text(values) => values.clone(),
+        }
+    }
+
+    /// Turns the values into an Arrow array. Text columns where every value is a timestamp become timestamps.
+    fn into_array(self, rows: usize) -> (DataType, ArrayRef) {
+        match self {
+            Self::Empty => (
+                DataType::Utf8,
+                Arc::new(StringArray::from(vec![None::<String>; rows])),
+            ),
+            Self::Int(values) => (DataType::Int64, Arc::new(Int64Array::from(values))),
+            Self::Float(values) => (DataType::Float64, Arc::new(Float64Array::from(values))),
+            Self::Bool(values) => (DataType::Boolean, Arc::new(BooleanArray::from(values))),
+            Self::Text(values) => {
+                let timestamps: Option<Vec<Option<i64>>> = values
+                    .iter()
+                    .map(|value| match value {
+                        Some(value) => DateTime::parse_from_rfc3339(value)
+                            .ok()
+                            .map(|time| Some(time.timestamp_millis())),
+                        None => Some(None),
+                    })
+                    .collect();
+                match timestamps {
+                    Some(timestamps) => (
+                        DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                        Arc::new(TimestampMillisecondArray::from(timestamps).with_timezone("UTC")),
+                    ),
+                    None => (DataType::Utf8, Arc::new(StringArray::from(values))),
+                }
+            }
+        }
+    }
+}
+
+/// The values as text.
+fn to_texts<T: ToString>(values: &[Option<T>]) -> Vec<Option<String>> {
+    values
+        .iter()
+        .map(|value| value.as_ref().map(ToString::to_string))
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes the rows as Parquet to any writer, with the columns of the CSV file given in the version set by
+/// `Settings::set_columns_version()`.
+///
+/// # Arguments
+///
+/// - `rows: I` -- The rows to write.
+/// - `file: CsvFile` -- The CSV file whose columns are written.
+/// - `writer: W` -- Where the Parquet is written.
+///
+/// # Errors
+///
+/// Serializing or writing the rows may fail.
+pub(crate) fn write_parquet<T, I, W>(
+    rows: I,
+    file: CsvFile,
+    writer: W,
+) -> Result<(), Box<dyn Error>>
+where
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+    W: Write + Send,
+{
+    let mut cells = RowCells::new(file);
+    let mut columns = vec![Column::Empty; cells.names().len()];
+    let mut num_rows = 0;
+    for row in rows {
+        for (column, cell) in columns.iter_mut().zip(cells.cells(row)?) {
+            column.push(&cell, num_rows);
+        }
+        num_rows += 1;
+    }
+
+    let (fields, arrays): (Vec<Field>, Vec<ArrayRef>) = columns
+        .into_iter()
+        .zip(cells.names())
+        .map(|(column, name)| {
+            let (data_type, array) = column.into_array(num_rows);
+            (Field::new(name, data_type, true), array)
+        })
+        .unzip();
+    let schema = Arc::new(Schema::new(fields));
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, schema.clone(), Some(properties))?;
+    writer.write(&RecordBatch::try_new(schema, arrays)?)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+/// Tests for the parquet_writer module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test working out the column types, and changing them when a value doesn't fit
+    fn test_column() {
+        let mut column = Column::Empty;
+        column.push("", 0);
+        column.push("1", 1);
+        assert_eq!(column, Column::Int(vec![None, Some(1)]));
+        column.push("2.5", 2);
+        assert_eq!(column, Column::Float(vec![None, Some(1.0), Some(2.5)]));
+        column.push("fast", 3);
+        assert_eq!(
+            column,
+            Column::Text(vec![
+                None,
+                Some("1".to_string()),
+                Some("2.5".to_string()),
+                Some("fast".to_string())
+            ])
+        );
+
+        let mut times = Column::Empty;
+        times.push("2024-05-01T07:00:00+02:00", 0);
+        times.push("", 1);
+        let (data_type, array) = times.into_array(2);
+        assert!(matches!(data_type, DataType::Timestamp(..)));
+        assert_eq!(array.null_count(), 1);
+    }
+}
@@ -1,14 +1,67 @@
-use std::path::PathBuf;
+//! Gets and changes the extensions of file names. The file names given on the command line are passed on as they are,
+//! so both `/` and `\` are taken as separators wherever the tools run, and a dot in a directory name is never taken
+//! for the start of an extension.
+//!
+//! Gzipped files, e.g. `running.fit.gz` from a Strava bulk export, are taken for the file inside, so the extension of
+//! `running.fit.gz` is `fit`. Use `is_gzipped()` to tell them apart.
 
-/// Get the extension part of the filename and return it as a string
+use std::path::Path;
+
+/// The separators between the directories and the file name, on any platform.
+const SEPARATORS: [char; 2] = ['/', '\\'];
+
+/// The extension added to gzipped files.
+const GZIP_EXTENSION: &str = "gz";
+
+/// Splits the path into the directories, including the last separator, and the file name.
+fn split_file_name(filename: &str) -> (&str, &str) {
+    filename
+        .rfind(SEPARATORS)
+        .map_or(("", filename), |pos| filename.split_at(pos + 1))
+}
+
+/// The name of the file inside a gzipped file, i.e. without the `.gz`, or the name as it is otherwise.
+pub(crate) fn without_gzip(name: &str) -> &str {
+    if is_gzipped(name) {
+        &name[..name.len() - GZIP_EXTENSION.len() - 1]
+    } else {
+        name
+    }
+}
+
+/// Checks whether the file is gzipped, i.e. has the `.gz` extension, whatever the case.
+///
+/// # Parameters
+///
+/// `filename: &str` -- The filename to check.
+///
+/// # Returns
+///
+/// `bool` -- `true` if the file is gzipped.
+///
+/// # Example
+///
+/// ```
+/// # use utilities::is_gzipped;
+/// assert!(is_gzipped("activities/1234.fit.GZ"));
+/// ```
+#[must_use]
+pub fn is_gzipped(filename: &str) -> bool {
+    let (_, name) = split_file_name(filename);
+    Path::new(name)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(GZIP_EXTENSION))
+}
+
+/// Get the extension part of the filename and return it as a string. The `.gz` of gzipped files is skipped.
 ///
 /// # Parameters
 ///
-/// `filename: &str` -- The filename to get the extension from.
+/// `filename: &str` -- The filename to get the extension from, e.g. `C:\Data\Running.GPX`.
 ///
 /// # Returns
 ///
-/// `String` -- The extension part of the filename.
+/// `String` -- The extension part of the filename in lowercase, or `unknown` if there is none.
 ///
 /// # Example
 ///
@@ -18,7 +71,8 @@ use std::path::PathBuf;
 /// ```
 #[must_use]
 pub fn get_extension(filename: &str) -> String {
-    std::path::Path::new(&filename)
+    let (_, name) = split_file_name(filename);
+    Path::new(without_gzip(name))
         .extension()
         .unwrap_or_else(|| std::ffi::OsStr::new("unknown"))
         .to_ascii_lowercase()
@@ -27,7 +81,34 @@ pub fn get_extension(filename: &str) -> String {
         .to_string()
 }
 
-/// Change the file extension
+/// Checks whether the file has the extension, whatever the case of either. The `.gz` of gzipped files is skipped.
+///
+/// # Parameters
+///
+/// `filename: &str` -- The filename to check.
+/// `extension: &str` -- The extension to look for, with or without the leading dot.
+///
+/// # Returns
+///
+/// `bool` -- `true` if the file has the extension.
+///
+/// # Example
+///
+/// ```
+/// # use utilities::has_extension;
+/// assert!(has_extension("Running.FIT", "fit"));
+/// ```
+#[must_use]
+pub fn has_extension(filename: &str, extension: &str) -> bool {
+    let (_, name) = split_file_name(filename);
+    Path::new(without_gzip(name))
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension.trim_start_matches('.')))
+}
+
+/// Change the file extension. The directories are kept as they are, separators and all. The `.gz` of gzipped files is
+/// dropped along with the extension, so the files written from `running.fit.gz` are named like those from
+/// `running.fit`.
 ///
 /// # Parameters
 ///
@@ -46,10 +127,10 @@ pub fn get_extension(filename: &str) -> String {
 /// ```
 #[must_use]
 pub fn set_extension(filename: &str, extension: &str) -> String {
-    let mut filename = PathBuf::from(&filename);
-    filename.set_extension(extension);
+    let (dirs, name) = split_file_name(filename);
+    let name = Path::new(without_gzip(name)).with_extension(extension);
 
-    String::from(filename.as_os_str().to_str().unwrap_or("unknown"))
+    format!("{dirs}{}", name.to_str().unwrap_or("unknown"))
 }
 
 #[cfg(test)]
@@ -67,6 +148,53 @@ mod tests {
         assert_eq!(get_extension("filename.TXT"), "txt".to_string());
     }
 
+    #[test]
+    /// Test the extensions of Windows-style paths and paths with mixed separators
+    fn test_windows_paths() {
+        assert_eq!(get_extension(r"C:\Data\Running.GPX"), "gpx");
+        assert_eq!(get_extension(r"C:\Data.2022\Running"), "unknown");
+        assert_eq!(get_extension(r"C:\Data.2022/Sub\Rowing.Fit"), "fit");
+        assert_eq!(get_extension("data.d/running"), "unknown");
+
+        assert_eq!(
+            set_extension(r"C:\Data\Running.GPX", "tcx"),
+            r"C:\Data\Running.tcx"
+        );
+        assert_eq!(
+            set_extension(r"C:\Data.2022\Running", "gpx"),
+            r"C:\Data.2022\Running.gpx"
+        );
+        assert_eq!(
+            set_extension(r"\\server\share.v2/runs\Rowing.FIT", "session.json"),
+            r"\\server\share.v2/runs\Rowing.session.json"
+        );
+
+        assert!(has_extension(r"C:\Data\Rowing.FIT", "fit"));
+        assert!(has_extension("rowing.fit", ".FIT"));
+        assert!(!has_extension(r"C:\Data.fit\Rowing", "fit"));
+        assert!(!has_extension("rowing.fit.bak", "fit"));
+    }
+
+    #[test]
+    /// Test taking gzipped files for the file inside
+    fn test_gzipped() {
+        assert!(is_gzipped("activities/1234.fit.gz"));
+        assert!(is_gzipped(r"C:\Export\1234.GPX.GZ"));
+        assert!(!is_gzipped("activities.gz/1234.fit"));
+
+        assert_eq!(get_extension("activities/1234.fit.gz"), "fit");
+        assert_eq!(get_extension(r"C:\Export\1234.TCX.GZ"), "tcx");
+        assert_eq!(get_extension("archive.gz"), "unknown");
+        assert!(has_extension("1234.gpx.gz", "gpx"));
+        assert!(!has_extension("1234.gpx.gz", "gz"));
+
+        assert_eq!(
+            set_extension("activities/1234.fit.gz", "records.csv"),
+            "activities/1234.records.csv"
+        );
+        assert_eq!(set_extension("1234.gpx.GZ", "tcx"), "1234.tcx");
+    }
+
     /// Test the `set_extension` function
     #[test]
     fn test_set_extension() {
@@ -0,0 +1,709 @@
+//! Cuts a single lap or a time range out of an activity, e.g. to share an interval or the effort on a segment. The
+//! summary of the part cut out is worked out again from its records or track points.
+//!
+//! FIT files are cut into a new FIT file. GPX and TCX files can be cut into either GPX or TCX. TCX files are read
+//! through GPX, so their laps become track segments and their trackpoints without a position are left out.
+
+use chrono::{DateTime, Local};
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::{
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::analysis::derived::{haversine_distance, smooth};
+use crate::analysis::interpolation::moving_time;
+use crate::analysis::track_stats::{track_stats, StatsPoint, SPEED_SMOOTHING_WINDOW};
+#[cfg(feature = "fs")]
+use crate::atomic_file::write_atomically;
+use crate::fit::activity::set_record_stats;
+use crate::query::{with_unit, DISTANCE_UNITS};
+#[cfg(feature = "fs")]
+use crate::{get_extension, ActivityFormat};
+use crate::{
+    Activity, Duration, FITActivity, FITLap, FITRecord, FITSession, GPXActivity, GPXWaypoint,
+};
+
+use uom::si::f64::{Length as Length_f64, Velocity};
+use uom::si::length::meter;
+use uom::si::u16::Length as Length_u16;
+use uom::si::velocity::meter_per_second;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The part of an activity to cut out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExtractRange {
+    /// A single lap, numbered from 1. The laps of GPX files are their track segments.
+    Lap(usize),
+
+    /// The time from `from` to `to` into the activity. Leaving out either end cuts from the start or to the finish.
+    Time {
+        from: Option<Duration>,
+        to: Option<Duration>,
+    },
+
+    /// The distance from `from` to `to` into the activity. Leaving out either end cuts from the start or to the finish.
+    Distance {
+        from: Option<Length_f64>,
+        to: Option<Length_f64>,
+    },
+}
+
+impl ExtractRange {
+    /// The part of the output file name that tells what was cut out, e.g. `lap3`.
+    #[must_use]
+    pub fn suffix(&self) -> String {
+        match self {
+            Self::Lap(num) => format!("lap{num}"),
+            Self::Time { .. } | Self::Distance { .. } => String::from("extract"),
+        }
+    }
+
+    /// The times the range covers.
+    ///
+    /// # Arguments
+    ///
+    /// - `points: &[RangePoint]` -- The records or track points of the activity, in order.
+    /// - `laps: &[TimeWindow]` -- The times of each lap, in order.
+    ///
+    /// # Errors
+    ///
+    /// No points may have a time, there may be no such lap, or the range may end before it starts. The activity may
+    /// not be long enough to reach a distance.
+    pub(crate) fn window(
+        &self,
+        points: &[RangePoint],
+        laps: &[TimeWindow],
+    ) -> Result<TimeWindow, Box<dyn Error>> {
+        let start = points
+            .iter()
+            .find_map(|point| point.time)
+            .ok_or("The activity has nothing recorded with a time.")?;
+        match *self {
+            Self::Lap(num) => laps.get(num.wrapping_sub(1)).copied().ok_or_else(|| {
+                format!(
+                    "There is no lap {num}. The activity has {} laps.",
+                    laps.len()
+                )
+                .into()
+            }),
+            Self::Time { from, to } => {
+                if let (Some(from), Some(to)) = (from, to) {
+                    if from >= to {
+                        return Err(
+                            format!("The range ends at {to}, before it starts at {from}.").into(),
+                        );
+                    }
+                }
+                let at = |offset: Duration| {
+                    chrono::Duration::from_std(offset.0)
+                        .ok()
+                        .and_then(|offset| start.checked_add_signed(offset))
+                };
+                Ok(TimeWindow {
+                    from: from.and_then(at),
+                    to: to.and_then(at),
+                })
+            }
+            Self::Distance { from, to } => {
+                if let (Some(from), Some(to)) = (from, to) {
+                    if from >= to {
+                        return Err(format!(
+                            "The range ends at {}, before it starts at {}.",
+                            km(to),
+                            km(from)
+                        )
+                        .into());
+                    }
+                }
+                let nothing = || format!("There is nothing recorded in {self}.");
+                let distances = || {
+                    points
+                        .iter()
+                        .filter_map(|point| point.time.zip(point.distance_m))
+                };
+                Ok(TimeWindow {
+                    from: from
+                        .map(|from| {
+                            distances()
+                                .find(|(_, distance)| *distance >= from.value)
+                                .map(|(time, _)| time)
+                                .ok_or_else(nothing)
+                        })
+                        .transpose()?,
+                    to: to
+                        .map(|to| {
+                            distances()
+                                .rev()
+                                .find(|(_, distance)| *distance <= to.value)
+                                .map(|(time, _)| time)
+                                .ok_or_else(nothing)
+                        })
+                        .transpose()?,
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExtractRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lap(num) => write!(f, "lap {num}"),
+            Self::Time { from, to } => write!(
+                f,
+                "{} to {}",
+                from.unwrap_or_default(),
+                to.map_or_else(|| String::from("the finish"), |to| to.to_string())
+            ),
+            Self::Distance { from, to } => write!(
+                f,
+                "{} to {}",
+                from.map_or_else(|| String::from("the start"), km),
+                to.map_or_else(|| String::from("the finish"), km)
+            ),
+        }
+    }
+}
+
+/// The distance in kilometers, for the messages.
+fn km(distance: Length_f64) -> String {
+    format!("{:.2} km", distance.value / 1_000.0)
+}
+
+/// Parses a distance with `m`, `km` or `mi`, e.g. `1.5km` or `800m`. Kilometers if no unit is given.
+///
+/// # Arguments
+///
+/// `value: &str` -- The distance.
+///
+/// # Errors
+///
+/// The distance may not be a number, or the unit may not be known.
+pub fn parse_distance(value: &str) -> Result<Length_f64, Box<dyn Error>> {
+    with_unit(value, &DISTANCE_UNITS)
+        .map(Length_f64::new::<meter>)
+        .ok_or_else(|| format!("Invalid distance {value}. Use e.g. 1.5km, 800m or 2mi.").into())
+}
+
+/// A record or track point, with what is needed to find where a part of the activity starts and ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct RangePoint {
+    /// When the point was recorded.
+    pub time: Option<DateTime<Local>>,
+    /// The distance from the start in meters.
+    pub distance_m: Option<f64>,
+    /// The speed in meters per second.
+    pub speed_ms: Option<f64>,
+}
+
+/// The times a part of an activity covers. Either end may be open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct TimeWindow {
+    /// The earliest time in the part, if any.
+    pub from: Option<DateTime<Local>>,
+    /// The latest time in the part, if any.
+    pub to: Option<DateTime<Local>>,
+}
+
+impl TimeWindow {
+    /// Whether the time is in the window. Points without a time aren't.
+    pub fn contains(&self, time: Option<DateTime<Local>>) -> bool {
+        time.is_some_and(|time| {
+            self.from.is_none_or(|from| from <= time) && self.to.is_none_or(|to| time <= to)
+        })
+    }
+}
+
+impl Activity {
+    /// Cuts a lap or a time range out of the activity. TCX activities are read through GPX, so the part cut out is a GPX
+    /// activity.
+    ///
+    /// # Arguments
+    ///
+    /// `range: &ExtractRange` -- The part to cut out.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Activity, Box<dyn Error>>` -- The part cut out, with its summary worked out again.
+    ///
+    /// # Errors
+    ///
+    /// There may be no such lap, or nothing recorded in the range.
+    pub fn extract(&self, range: &ExtractRange) -> Result<Self, Box<dyn Error>> {
+        match self {
+            Self::Fit(activity) => Ok(Self::Fit(activity.extract(range)?)),
+            Self::Gpx(activity) => Ok(Self::Gpx(activity.extract(range)?)),
+            Self::Tcx(..) => Ok(Self::Gpx(self.tcx_as_gpx()?.extract(range)?)),
+        }
+    }
+
+    /// A TCX activity read through GPX, so it can be cut like a GPX activity. Other activities give an error.
+    ///
+    /// # Errors
+    ///
+    /// The activity may not be a TCX activity, or converting it may fail.
+    pub(crate) fn tcx_as_gpx(&self) -> Result<GPXActivity, Box<dyn Error>> {
+        let Self::Tcx(activity, trackpoints) = self else {
+            return Err("Not a TCX activity.".into());
+        };
+        let mut gpx = Vec::new();
+        trackpoints.write_gpx(&mut gpx)?;
+        let filename = activity.filename.as_deref().unwrap_or_default();
+        GPXActivity::from_reader(gpx.as_slice(), filename)
+    }
+}
+
+impl FITActivity {
+    /// Cuts a lap or a time range out of the activity. The part cut out has a single session and lap, worked out again
+    /// from its records. The events and device splits are left out.
+    ///
+    /// # Arguments
+    ///
+    /// `range: &ExtractRange` -- The part to cut out.
+    ///
+    /// # Errors
+    ///
+    /// There may be no such lap, or no records in the range.
+    pub fn extract(&self, range: &ExtractRange) -> Result<Self, Box<dyn Error>> {
+        let window = range.window(&self.range_points(), &self.lap_windows())?;
+
+        let mut records: Vec<FITRecord> = self
+            .records
+            .iter()
+            .filter(|record| window.contains(record.timestamp))
+            .cloned()
+            .collect();
+        if records.is_empty() {
+            return Err(format!("There are no records in {range}.").into());
+        }
+
+        let mut session = sliced_session(&self.session, &records);
+        for record in &mut records {
+            record.activity_uuid = session.uuid;
+        }
+        let lap = sliced_lap(&session);
+        session.session_num = Some(1);
+
+        Ok(Self {
+            session: session.clone(),
+            sessions: vec![session],
+            laps: vec![lap],
+            records,
+            events: Vec::new(),
+            device_splits: Vec::new(),
+            devices: self.devices.clone(),
+            warnings: Vec::new(),
+        })
+    }
+}
+
+impl FITActivity {
+    /// The times of each lap, in order.
+    pub(crate) fn lap_windows(&self) -> Vec<TimeWindow> {
+        self.laps
+            .iter()
+            .map(|lap| TimeWindow {
+                from: lap.start_time,
+                to: lap.finish_time,
+            })
+            .collect()
+    }
+
+    /// The records with the distance and speed recorded.
+    pub(crate) fn range_points(&self) -> Vec<RangePoint> {
+        self.records
+            .iter()
+            .map(|record| RangePoint {
+                time: record.timestamp,
+                distance_m: record.distance.map(|distance| distance.value),
+                speed_ms: record.speed.map(|speed| speed.value),
+            })
+            .collect()
+    }
+}
+
+/// The session of the records cut out, with the device and sport of the original session and the totals worked out
+/// from the records.
+pub(crate) fn sliced_session(original: &FITSession, records: &[FITRecord]) -> FITSession {
+    let start = records.iter().find_map(|record| record.timestamp);
+    let finish = records.iter().rev().find_map(|record| record.timestamp);
+    let duration = start
+        .zip(finish)
+        .map(|(start, finish)| Duration::between(&start, &finish));
+    let distance = records
+        .iter()
+        .find_map(|record| record.distance)
+        .zip(records.iter().rev().find_map(|record| record.distance))
+        .map(|(first, last)| (last.value - first.value).max(0.0));
+    let secs = duration.map(|duration| duration.0.as_secs_f64());
+
+    let heart_rates: Vec<f64> = records
+        .iter()
+        .filter_map(|record| record.heartrate.map(f64::from))
+        .collect();
+    let cadences: Vec<f64> = records
+        .iter()
+        .filter_map(|record| record.cadence.map(f64::from))
+        .collect();
+    let powers: Vec<f64> = records
+        .iter()
+        .filter_map(|record| record.power.map(f64::from))
+        .collect();
+    let climb = track_stats(&[records
+        .iter()
+        .map(|record| StatsPoint {
+            time: record.timestamp,
+            position: None,
+            elevation: record.altitude.map(|altitude| altitude.value),
+        })
+        .collect()]);
+    let lats = records.iter().filter_map(|record| record.lat);
+    let lons = records.iter().filter_map(|record| record.lon);
+
+    let mut session = FITSession {
+        filename: original.filename.clone(),
+        manufacturer: original.manufacturer.clone(),
+        product: original.product.clone(),
+        serial_number: original.serial_number.clone(),
+        time_created: original.time_created,
+        activity_type: original.activity_type.clone(),
+        activity_detailed: original.activity_detailed.clone(),
+        environment: original.environment,
+        utc_offset: original.utc_offset,
+        power_threshold: original.power_threshold,
+        num_sessions: Some(1),
+        num_laps: Some(1),
+        start_time: start,
+        finish_time: finish,
+        duration,
+        duration_active: duration,
+        duration_moving: moving_time(records),
+        total_timer_time: duration,
+        distance: distance.map(Length_f64::new::<meter>),
+        speed_avg: distance
+            .zip(secs)
+            .filter(|(_, secs)| *secs > 0.0)
+            .map(|(distance, secs)| Velocity::new::<meter_per_second>(distance / secs)),
+        speed_max: records
+            .iter()
+            .filter_map(|record| record.speed)
+            .reduce(|a, b| if b > a { b } else { a }),
+        heartrate_avg: average(&heart_rates).and_then(to_u8),
+        heartrate_max: records.iter().filter_map(|record| record.heartrate).max(),
+        heartrate_min: records.iter().filter_map(|record| record.heartrate).min(),
+        cadence_avg: average(&cadences).and_then(to_u8),
+        cadence_max: records.iter().filter_map(|record| record.cadence).max(),
+        power_avg: average(&powers).and_then(|power| u16::try_from(power.round() as i64).ok()),
+        power_max: records.iter().filter_map(|record| record.power).max(),
+        ascent: climb.ascent_m.and_then(to_length_u16),
+        descent: climb.descent_m.and_then(to_length_u16),
+        nec_lat: lats.clone().reduce(f64::max),
+        nec_lon: lons.clone().reduce(f64::max),
+        swc_lat: lats.reduce(f64::min),
+        swc_lon: lons.reduce(f64::min),
+        ..FITSession::default()
+    };
+    set_record_stats(&mut session, &records.iter().collect::<Vec<_>>());
+    session.set_uuid();
+    session
+}
+
+/// The single lap of the records cut out, with the totals of the session.
+pub(crate) fn sliced_lap(session: &FITSession) -> FITLap {
+    FITLap {
+        filename: session.filename.clone(),
+        lap_num: Some(1),
+        cadence_avg: session.cadence_avg,
+        cadence_max: session.cadence_max,
+        heartrate_min: session.heartrate_min,
+        heartrate_avg: session.heartrate_avg,
+        heartrate_max: session.heartrate_max,
+        speed_avg: session.speed_avg,
+        speed_max: session.speed_max,
+        power_avg: session.power_avg,
+        power_max: session.power_max,
+        ascent: session.ascent,
+        descent: session.descent,
+        distance: session.distance,
+        duration: session.duration,
+        duration_active: session.duration_active,
+        duration_moving: session.duration_moving,
+        start_time: session.start_time,
+        finish_time: session.finish_time,
+        time_in_hr_zones: session.time_in_hr_zones,
+        activity_uuid: session.uuid,
+        session_num: Some(1),
+        ..FITLap::default()
+    }
+}
+
+/// The average of the values, or `None` if there are none.
+#[allow(clippy::cast_precision_loss)]
+fn average(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// The value rounded to a whole number, if it fits in a `u8`.
+#[allow(clippy::cast_possible_truncation)]
+fn to_u8(value: f64) -> Option<u8> {
+    u8::try_from(value.round() as i64).ok()
+}
+
+/// The length in meters rounded to whole meters, if it fits in a `u16`.
+#[allow(clippy::cast_possible_truncation)]
+fn to_length_u16(meters: f64) -> Option<Length_u16> {
+    u16::try_from(meters.round() as i64)
+        .ok()
+        .map(Length_u16::new::<meter>)
+}
+
+impl GPXActivity {
+    /// Cuts a lap, i.e. a track segment, or a time range out of the activity. The part cut out only has the track
+    /// points in the range, and its summary is worked out again from them.
+    ///
+    /// # Arguments
+    ///
+    /// `range: &ExtractRange` -- The part to cut out.
+    ///
+    /// # Errors
+    ///
+    /// There may be no such lap, or no track points in the range.
+    pub fn extract(&self, range: &ExtractRange) -> Result<Self, Box<dyn Error>> {
+        let window = range.window(&self.range_points(), &self.lap_windows())?;
+        self.between(&window)?
+            .ok_or_else(|| format!("There are no track points in {range}.").into())
+    }
+
+    /// The part of the activity recorded within the times given, with its summary worked out again.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Option<Self>, Box<dyn Error>>` -- The part, or `None` if there are no track points within the times.
+    ///
+    /// # Errors
+    ///
+    /// Writing or reading the part back may fail.
+    pub(crate) fn between(&self, window: &TimeWindow) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut gpx = Vec::new();
+        self.write_gpx_between(&mut gpx, Some(window))?;
+        let filename = self
+            .metadata
+            .filename
+            .as_deref()
+            .and_then(|filename| filename.to_str())
+            .unwrap_or_default();
+        let part = Self::from_reader(gpx.as_slice(), filename)?;
+        Ok((!part.tracks.is_empty()).then_some(part))
+    }
+
+    /// The times of each track segment, in order.
+    pub(crate) fn lap_windows(&self) -> Vec<TimeWindow> {
+        self.tracks
+            .iter()
+            .flat_map(|track| {
+                track
+                    .waypoints
+                    .chunk_by(|a, b| a.segment_num == b.segment_num)
+            })
+            .map(|segment| TimeWindow {
+                from: segment.iter().find_map(|wpt| wpt.time),
+                to: segment.iter().rev().find_map(|wpt| wpt.time),
+            })
+            .collect()
+    }
+
+    /// The track points with the distance from the start and the speed, worked out from the positions where the
+    /// speed isn't recorded. The speeds are smoothed over a few points, so GPS jitter doesn't look like movement.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn range_points(&self) -> Vec<RangePoint> {
+        let waypoints: Vec<&GPXWaypoint> = self
+            .tracks
+            .iter()
+            .flat_map(|track| &track.waypoints)
+            .collect();
+
+        let mut distance = 0.0;
+        let mut previous: Option<&GPXWaypoint> = None;
+        let mut distances = Vec::with_capacity(waypoints.len());
+        let mut speeds = Vec::with_capacity(waypoints.len());
+        for wpt in &waypoints {
+            let position = wpt.latitude.zip(wpt.longitude);
+            let step = previous
+                .and_then(|prev| prev.latitude.zip(prev.longitude))
+                .zip(position)
+                .map(|(from, to)| haversine_distance(from, to));
+            distance += step.unwrap_or_default();
+            distances.push(position.map(|_| distance));
+
+            let secs = previous
+                .and_then(|prev| prev.time)
+                .zip(wpt.time)
+                .map(|(from, to)| (to - from).num_milliseconds() as f64 / 1_000.0);
+            let speed = wpt.speed.or_else(|| {
+                step.zip(secs)
+                    .filter(|(_, secs)| *secs > 0.0)
+                    .map(|(step, secs)| step / secs)
+            });
+            speeds.push(speed.unwrap_or_default());
+
+            if position.is_some() {
+                previous = Some(wpt);
+            }
+        }
+
+        waypoints
+            .iter()
+            .zip(distances)
+            .zip(smooth(&speeds, SPEED_SMOOTHING_WINDOW))
+            .map(|((wpt, distance_m), speed)| RangePoint {
+                time: wpt.time,
+                distance_m,
+                speed_ms: Some(speed),
+            })
+            .collect()
+    }
+}
+
+/// Cuts a lap or a time range out of an activity file and writes it to a new file. The format of the new file is
+/// worked out from its extension: FIT files can only be cut into FIT files, and GPX and TCX files into GPX or TCX.
+///
+/// # Arguments
+///
+/// - `input: &str` -- The FIT, GPX or TCX file to read.
+/// - `output: &str` -- The file to write.
+/// - `range: &ExtractRange` -- The part to cut out.
+///
+/// # Returns
+///
+/// `Result<Activity, Box<dyn Error>>` -- The part cut out, e.g. for printing its summary.
+///
+/// # Errors
+///
+/// The input may not be a FIT, GPX or TCX file, or the output may not be a format the input can be written as. There
+/// may be no such lap, or nothing recorded in the range. Reading, parsing or writing may fail.
+#[cfg(feature = "fs")]
+pub fn extract_file(
+    input: &str,
+    output: &str,
+    range: &ExtractRange,
+) -> Result<Activity, Box<dyn Error>> {
+    let extracted = Activity::from_file(input)?.extract(range)?;
+    write_part(&extracted, output)?;
+
+    Ok(extracted)
+}
+
+/// Writes a part of an activity to a file in the format given by its extension. FIT activities can only be written as
+/// FIT, and GPX activities as GPX or TCX.
+///
+/// # Errors
+///
+/// The activity can't be written in the format of the extension, or writing may fail.
+#[cfg(feature = "fs")]
+pub(crate) fn write_part(part: &Activity, output: &str) -> Result<(), Box<dyn Error>> {
+    match (part, ActivityFormat::from_filename(output)) {
+        (Activity::Fit(activity), Some(ActivityFormat::Fit)) => {
+            write_atomically(Path::new(output), |file| {
+                let mut writer = BufWriter::new(file);
+                activity.write_fit(&mut writer)?;
+                Ok(writer.flush()?)
+            })?;
+        }
+        (Activity::Gpx(activity), Some(ActivityFormat::Gpx)) => {
+            write_atomically(Path::new(output), |file| {
+                let mut writer = BufWriter::new(file);
+                activity.write_gpx(&mut writer)?;
+                Ok(writer.flush()?)
+            })?;
+        }
+        (Activity::Gpx(activity), Some(ActivityFormat::Tcx)) => {
+            write_atomically(Path::new(output), |file| {
+                let mut writer = BufWriter::new(file);
+                activity.write_tcx(&mut writer)?;
+                Ok(writer.flush()?)
+            })?;
+        }
+        (Activity::Fit(_), _) => {
+            return Err(format!("{output}: FIT files can only be written as FIT.").into());
+        }
+        _ => {
+            return Err(format!(
+                "{output}: Unable to write .{} files. Use .gpx or .tcx.",
+                get_extension(output)
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "fs"))]
+/// Tests for the extract module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test cutting out a time range and a lap of a FIT file, and writing it back out
+    fn test_extract_fit() {
+        let activity = FITActivity::from_file("../data/test.fit").unwrap();
+        let range = ExtractRange::Time {
+            from: Some(Duration::from_secs_f64(60.0)),
+            to: Some(Duration::from_secs_f64(300.0)),
+        };
+        let extracted = activity.extract(&range).unwrap();
+
+        assert!(extracted.records.len() < activity.records.len());
+        assert_eq!(extracted.laps.len(), 1);
+        assert_eq!(
+            extracted.session.duration,
+            Some(Duration::from_secs_f64(240.0))
+        );
+        assert_eq!(
+            extracted.session.num_records,
+            Some(extracted.records.len() as u64)
+        );
+        assert_ne!(extracted.session.uuid, activity.session.uuid);
+
+        let mut fit = Vec::new();
+        extracted.write_fit(&mut fit).unwrap();
+        let written = FITActivity::from_reader(&mut fit.as_slice(), "test.extract.fit").unwrap();
+        assert_eq!(written.records.len(), extracted.records.len());
+        assert_eq!(
+            written.session.distance,
+            extracted
+                .session
+                .distance
+                .map(|d| { Length_f64::new::<meter>((d.value * 100.0).round() / 100.0) })
+        );
+
+        assert!(activity.extract(&ExtractRange::Lap(1)).is_ok());
+        assert!(activity.extract(&ExtractRange::Lap(99)).is_err());
+        assert!(activity
+            .extract(&ExtractRange::Time {
+                from: Some(Duration::from_secs_f64(300.0)),
+                to: Some(Duration::from_secs_f64(60.0)),
+            })
+            .is_err());
+    }
+
+    #[test]
+    /// Test cutting a time range out of a GPX file
+    fn test_extract_gpx() {
+        let activity = GPXActivity::from_file("../data/walking2.gpx").unwrap();
+        let range = ExtractRange::Time {
+            from: Some(Duration::from_secs_f64(120.0)),
+            to: None,
+        };
+        let extracted = activity.extract(&range).unwrap();
+
+        let start = activity.tracks[0].waypoints[0].time.unwrap();
+        let extracted_start = extracted.tracks[0].waypoints[0].time.unwrap();
+        assert!(extracted_start >= start + chrono::Duration::seconds(120));
+        assert!(extracted.metadata.distance_m < activity.metadata.distance_m);
+        assert_eq!(ExtractRange::Lap(3).suffix(), "lap3");
+    }
+}
@@ -1,10 +1,30 @@
 //! Defines the `Activities` struct which is used to hold a list of all activities, and associated functions.
 
-use csv::WriterBuilder;
+#[cfg(feature = "fs")]
 use std::error::Error;
-use std::path::PathBuf;
+#[cfg(feature = "fs")]
+use std::fs::{self, File, OpenOptions};
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader};
+#[cfg(feature = "fs")]
+use std::path::Path;
 
+use crate::analysis::overlap::ActivityWindow;
+#[cfg(feature = "fs")]
+use crate::atomic_file::write_atomically;
+#[cfg(feature = "fs")]
+use crate::columns::{header_line, ColumnWriter, CsvFile};
+#[cfg(feature = "fs")]
+use crate::precision::Rounded;
+#[cfg(feature = "fs")]
+use crate::summary_split::{group_by, split_filename};
 use crate::FITActivity;
+#[cfg(feature = "fs")]
+use crate::FITSession;
+#[cfg(feature = "fs")]
+use crate::SummarySplit;
+#[cfg(feature = "fs")]
+use chrono::Datelike;
 
 /// Holds a list of all activities. Used to export session totals.
 #[derive(Debug, Default)]
@@ -15,7 +35,29 @@ pub struct FITActivities {
 }
 
 impl FITActivities {
-    /// Export the summary list of session information to a CSV file.
+    /// When each activity took place and what recorded it, for finding the activities that overlap in time.
+    /// Activities without a start time or duration are left out.
+    #[must_use]
+    pub fn time_windows(&self) -> Vec<ActivityWindow> {
+        self.activities_list
+            .iter()
+            .filter_map(|act| {
+                let session = &act.session;
+                ActivityWindow::new(
+                    session.filename.as_deref().unwrap_or_default(),
+                    session
+                        .serial_number
+                        .as_deref()
+                        .or(session.product.as_deref()),
+                    session.start_time,
+                    session.duration,
+                )
+            })
+            .collect()
+    }
+
+    /// Export the summary list of session information to a CSV file, with a row for each session. Multisport files
+    /// get a row per sport.
     ///
     /// # Parameters
     ///
@@ -34,61 +76,136 @@ impl FITActivities {
     /// ```
     /// activities.export_summary_csv("session_summary.csv")?;
     /// ```
+    #[cfg(feature = "fs")]
     pub fn export_summary_csv(&self, sessionfile: &str) -> Result<(), Box<dyn Error>> {
-        // Create a buffer for the CSV
-        let outfile = PathBuf::from(sessionfile);
-        let mut writer = WriterBuilder::new()
-            .has_headers(false)
-            .from_path(&outfile)?;
-
-        // Write the header separately since types::Duration doesn't get serialized properly
-        writer.write_record([
-            "filename",
-            "manufacturer",
-            "product",
-            "serial_number",
-            "time_created",
-            "activity_type",
-            "activity_detailed",
-            "num_sessions",
-            "num_laps",
-            "num_records",
-            "cadence_avg_bpm",
-            "cadence_max_bpm",
-            "heartrate_avg_bpm",
-            "heartrate_max_bpm",
-            "heartrate_min_bpm",
-            "speed_avg_ms",
-            "speed_max_ms",
-            "power_avg_w",
-            "power_max_w",
-            "power_threshold_w",
-            "nec_lat_deg",
-            "nec_lon_deg",
-            "swc_lat_deg",
-            "swc_lon_deg",
-            "stance_time_avg",
-            "vertical_oscillation_avg",
-            "ascent_m",
-            "descent_m",
-            "calories",
-            "distance_m",
-            "duration_sec",
-            "duration_active_sec",
-            "duration_moving_sec",
-            "start_time",
-            "finish_time",
-            "time_in_hr_zone_0_sec",
-            "time_in_hr_zone_1_sec",
-            "time_in_hr_zone_2_sec",
-            "time_in_hr_zone_3_sec",
-            "time_in_hr_zone_4_sec",
-        ])?;
+        write_summary_csv(sessionfile, &self.summary_sessions())
+    }
+
+    /// Export the summary list of session information to one CSV file per sport, year or device. The files are named
+    /// after the summary file, e.g. `fit-sessions.running.csv` and `fit-sessions.cycling.csv`.
+    ///
+    /// # Parameters
+    ///
+    /// - `sessionfile: &str`: The name of the summary file the split file names are based on.
+    /// - `split: SummarySplit`: What to split the summary by.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Vec<String>, Box<dyn Error>>`: The names of the files written.
+    ///
+    /// # Errors
+    ///
+    /// Failure to open a new writer may result in an error.
+    #[cfg(feature = "fs")]
+    pub fn export_summary_csv_split(
+        &self,
+        sessionfile: &str,
+        split: SummarySplit,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut written = Vec::new();
+        for (filename, sessions) in self.split_sessions(sessionfile, split) {
+            write_summary_csv(&filename, &sessions)?;
+            written.push(filename);
+        }
+
+        Ok(written)
+    }
+
+    /// Adds the sessions to the end of a summary CSV file, e.g. as each file is exported while watching a directory.
+    /// The header is written first if the file is new or empty.
+    ///
+    /// # Parameters
+    ///
+    /// `sessionfile: &str`: The summary file to add the sessions to.
+    ///
+    /// # Errors
+    ///
+    /// Opening or writing the file may fail, or the file may have other columns, e.g. from another
+    /// `--columns-version` or `--units`.
+    #[cfg(feature = "fs")]
+    pub fn append_summary_csv(&self, sessionfile: &str) -> Result<(), Box<dyn Error>> {
+        append_summary_csv(sessionfile, &self.summary_sessions())
+    }
+
+    /// Adds the sessions to the end of the summary CSV files split by sport, year or device, the same way as
+    /// `append_summary_csv()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `sessionfile: &str`: The name of the summary file the split file names are based on.
+    /// - `split: SummarySplit`: What to split the summary by.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Vec<String>, Box<dyn Error>>`: The names of the files added to.
+    ///
+    /// # Errors
+    ///
+    /// Opening or writing the files may fail, or a file may have other columns.
+    #[cfg(feature = "fs")]
+    pub fn append_summary_csv_split(
+        &self,
+        sessionfile: &str,
+        split: SummarySplit,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut written = Vec::new();
+        for (filename, sessions) in self.split_sessions(sessionfile, split) {
+            append_summary_csv(&filename, &sessions)?;
+            written.push(filename);
+        }
+
+        Ok(written)
+    }
+
+    /// The sessions grouped by what the summary is split by, along with the file each group goes in.
+    #[cfg(feature = "fs")]
+    fn split_sessions(
+        &self,
+        sessionfile: &str,
+        split: SummarySplit,
+    ) -> Vec<(String, Vec<&FITSession>)> {
+        let sessions = self.summary_sessions();
+        group_by(&sessions, |session| {
+            split.key(
+                session.activity_type.as_deref(),
+                session
+                    .start_time
+                    .or(session.time_created)
+                    .map(|t| t.year()),
+                session.product.as_deref(),
+            )
+        })
+        .into_iter()
+        .map(|(key, sessions)| {
+            (
+                split_filename(sessionfile, &key),
+                sessions.into_iter().copied().collect(),
+            )
+        })
+        .collect()
+    }
+
+    /// The sessions of all the activities, in the order they go in the summary.
+    #[cfg(feature = "fs")]
+    fn summary_sessions(&self) -> Vec<&FITSession> {
+        self.activities_list
+            .iter()
+            .flat_map(FITActivity::summary_sessions)
+            .collect()
+    }
+}
+
+/// Writes the header and the sessions to a summary CSV file.
+#[cfg(feature = "fs")]
+fn write_summary_csv(sessionfile: &str, sessions: &[&FITSession]) -> Result<(), Box<dyn Error>> {
+    write_atomically(Path::new(sessionfile), |file| {
+        let mut writer = ColumnWriter::new(file, CsvFile::FitSummary);
+        writer.write_header()?;
 
         // Now write the actual laps
-        for activity in &self.activities_list {
-            log::trace!("activities::export_summary_csv() -- serializing: {activity:?}");
-            writer.serialize(&activity.session)?;
+        for session in sessions {
+            log::trace!("activities::export_summary_csv() -- serializing: {session:?}");
+            writer.serialize(Rounded(*session))?;
         }
 
         log::trace!(
@@ -100,5 +217,98 @@ impl FITActivities {
 
         // Return safely
         Ok(())
+    })
+}
+
+/// Adds the sessions to the end of a summary CSV file, writing the header first if the file is new or empty.
+#[cfg(feature = "fs")]
+fn append_summary_csv(sessionfile: &str, sessions: &[&FITSession]) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(sessionfile);
+    let header = header_line(CsvFile::FitSummary)?;
+    let existing = match File::open(path) {
+        Ok(file) => BufReader::new(file).lines().next().transpose()?,
+        Err(_) => None,
+    };
+    if existing
+        .as_ref()
+        .is_some_and(|line| line.trim_end() != header)
+    {
+        return Err(format!(
+            "{sessionfile}: The summary file has other columns. Use another file, or the same --columns-version and --units as when it was written."
+        )
+        .into());
+    }
+
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut writer = ColumnWriter::new(file, CsvFile::FitSummary);
+    if existing.is_some() {
+        writer.skip_header();
+    } else {
+        writer.write_header()?;
+    }
+    for session in sessions {
+        writer.serialize(Rounded(*session))?;
+    }
+    writer.flush()
+}
+
+#[cfg(all(test, feature = "fs"))]
+/// Tests for the activities module
+mod tests {
+    use super::*;
+    use crate::{FITActivity, FITSession};
+
+    /// An activity with a single session of the sport given.
+    fn activity(sport: &str) -> FITActivity {
+        FITActivity {
+            session: FITSession {
+                activity_type: Some(sport.to_string()),
+                ..FITSession::default()
+            },
+            ..FITActivity::default()
+        }
+    }
+
+    #[test]
+    /// Test adding the sessions to the end of the summary, writing the header only once
+    fn test_append_summary_csv() {
+        let dir = std::env::temp_dir().join("fitutils_test_append_summary");
+        let _ = fs::remove_dir_all(&dir);
+        let sessionfile = dir.join("fit-sessions.csv");
+        let sessionfile = sessionfile.to_str().unwrap_or_default();
+
+        for sport in ["running", "cycling"] {
+            let activities = FITActivities {
+                activities_list: vec![activity(sport)],
+            };
+            activities.append_summary_csv(sessionfile).unwrap();
+        }
+        let written = fs::read_to_string(sessionfile).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], header_line(CsvFile::FitSummary).unwrap());
+        assert!(lines[1].contains("running") && lines[2].contains("cycling"));
+
+        // The same as exporting the sessions together
+        let together = dir.join("together.csv");
+        let together = together.to_str().unwrap_or_default();
+        FITActivities {
+            activities_list: vec![activity("running"), activity("cycling")],
+        }
+        .export_summary_csv(together)
+        .unwrap();
+        assert_eq!(fs::read_to_string(together).unwrap(), written);
+
+        // A file with other columns is left alone
+        fs::write(sessionfile, "filename,sport\n").unwrap();
+        assert!(FITActivities::default()
+            .append_summary_csv(sessionfile)
+            .is_err());
+        assert_eq!(fs::read_to_string(sessionfile).unwrap(), "filename,sport\n");
+
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -1,14 +1,51 @@
 //! Defines the `Activity` struct which holds the information contained in a .FIT file, and associated functions.
 
-use crate::{FITLap, FITRecord, FITSession};
+use crate::analysis::decoupling::decoupling;
+use crate::analysis::distance_check::{miscalibration_warning, GpsDistance};
+use crate::analysis::gps_outliers::{
+    gps_cleaning, gps_outlier_warning, gps_outliers, max_gps_speed,
+};
+use crate::analysis::hr_scatter::{hr_scatter, HrScatter};
+use crate::analysis::hr_zones::{recalculated_hr_zones, time_in_hr_zones};
+use crate::analysis::interpolation::{interpolate_records, moving_time};
+use crate::analysis::mean_max::{mean_max, MeanMax, MeanMaxSample};
+use crate::analysis::pacing::pacing;
+use crate::analysis::power::{ftp, power_metrics};
+#[cfg(feature = "fs")]
+use crate::analysis::recording::RecordingIntervals;
+use crate::analysis::recording::{recording_interval, RecordingMode};
+use crate::analysis::splits::{splits, Split, SplitPoint, SplitUnit};
+use crate::analysis::track_stats::StatsPoint;
+use crate::columns::{ColumnWriter, CsvFile};
+use crate::exporters::{write_rows, ExportFormat};
+use crate::geojson::{feature_collection, GeoJsonProperties};
+#[cfg(feature = "fs")]
+use crate::input::{open_input, read_input};
+#[cfg(feature = "fs")]
+use crate::output_naming::{create_output, output_path, write_output};
+use crate::placeholder::placeholder;
+use crate::precision::Rounded;
+use crate::privacy::output_serial;
+use crate::profile_svg::{profile_points, ProfilePoint};
+use crate::units::{converted, label, pace_label, Measure};
+use crate::warnings::log_warnings;
+use crate::{
+    get_extension, set_extension, Duration, FITDeviceInfo, FITEvent, FITLap, FITRecord, FITSession,
+    FITSplit, Settings, TrackPoint,
+};
 
-use chrono::{Local, TimeZone};
-use csv::WriterBuilder;
+use chrono::{DateTime, Local, TimeZone};
+use fitparser::de::{DecodeOption, FitObject, FitStreamProcessor};
 use fitparser::profile::field_types::MesgNum;
+use fitparser::{FitDataField, FitDataRecord};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::error::Error;
-use std::fs::File;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// Holds the all the information about a FIT file and its contents
@@ -16,12 +53,23 @@ use std::path::PathBuf;
 #[serde(default)]
 #[allow(clippy::module_name_repetitions)]
 pub struct FITActivity {
-    /// High-level session information and summary.
+    /// High-level session information and summary. For multisport files, this is the last session.
     pub session: FITSession,
+    /// Each session in the file, e.g. the swim, bike and run of a triathlon, with its own sport and totals.
+    pub sessions: Vec<FITSession>,
     /// Lists all the `Lap`s.
     pub laps: Vec<FITLap>,
     /// Lists all the `Record`s.
     pub records: Vec<FITRecord>,
+    /// Lists all the `Event`s, e.g. the timer being started and stopped.
+    pub events: Vec<FITEvent>,
+    /// Lists the recording device and the sensors connected to it.
+    pub devices: Vec<FITDeviceInfo>,
+    /// Lists the splits and split summaries worked out by the device, e.g. for run/walk detection and auto-climb.
+    pub device_splits: Vec<FITSplit>,
+    /// Anything noteworthy found while parsing the file, e.g. missing or repeated messages.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
 }
 
 impl FITActivity {
@@ -47,18 +95,109 @@ impl FITActivity {
     ///
     ///    let my_activity = FITActivity::from_file("data/rowing.fit")?;
     ///   ```
+    #[cfg(feature = "fs")]
     pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
-        // open the file and deserialize it - return error if unable.
-        let mut fp = File::open(filename)?;
-        let file = fitparser::from_reader(&mut fp)?;
+        Self::from_reader(&mut open_input(filename)?, filename)
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Parses FIT data from a reader, e.g. the contents of a file dropped into a browser. Use this where there is no
+    /// file system.
+    ///
+    /// # Arguments
+    ///
+    /// - `reader: &mut R` -- The FIT data.
+    /// - `filename: &str` -- The name of the file the data came from. Used in the summary and the exports.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Activity, Box<dyn Error>>` -- `Ok(Activity)` if successful, otherwise an `Error`.
+    ///
+    /// # Errors
+    ///
+    /// Reading or decoding the data may fail.
+    pub fn from_reader<R: Read>(reader: &mut R, filename: &str) -> Result<Self, Box<dyn Error>> {
+        let file = fitparser::de::from_reader_with_options(reader, &decode_options())?;
+        let mut files = split_chained(file).into_iter();
+        let mut activity = Self::from_messages(files.next().unwrap_or_default(), filename);
+
+        let num_files = files.len() + 1;
+        if num_files > 1 {
+            let warning =
+                format!("{num_files} chained FIT files found. Only the first one was read.");
+            log_warnings(Some(filename), std::slice::from_ref(&warning));
+            activity.warnings.push(warning);
+        }
+
+        Ok(activity)
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Parses the input file into one activity per FIT file in it. Downloads from some devices chain several FIT
+    /// files together in one file, each starting with its own `FileId` message.
+    ///
+    /// # Arguments
+    ///
+    /// - `filename: &str` -- The filename for the FIT file to be parsed.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Vec<Activity>, Box<dyn Error>>` -- One activity per chained file, in the order they are in the file.
+    ///   See `chained_filename()` for how they are named.
+    ///
+    /// # Errors
+    ///
+    /// Reading or decoding the file may fail.
+    #[cfg(feature = "fs")]
+    pub fn from_file_chained(filename: &str) -> Result<Vec<Self>, Box<dyn Error>> {
+        Self::from_reader_chained(&mut open_input(filename)?, filename)
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Same as `from_file_chained()`, but parses the FIT data from a reader.
+    ///
+    /// # Arguments
+    ///
+    /// - `reader: &mut R` -- The FIT data.
+    /// - `filename: &str` -- The name of the file the data came from. Used in the summary and the exports.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Vec<Activity>, Box<dyn Error>>` -- One activity per chained file, in the order they are in the file.
+    ///
+    /// # Errors
+    ///
+    /// Reading or decoding the data may fail.
+    pub fn from_reader_chained<R: Read>(
+        reader: &mut R,
+        filename: &str,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
+        let file = fitparser::de::from_reader_with_options(reader, &decode_options())?;
+        let files = split_chained(file);
+        let num_files = files.len();
+
+        Ok(files
+            .into_iter()
+            .enumerate()
+            .map(|(num, messages)| {
+                Self::from_messages(messages, &chained_filename(filename, num + 1, num_files))
+            })
+            .collect())
+    }
 
+    /// Builds the activity from the decoded messages of a single FIT file.
+    fn from_messages(file: Vec<FitDataRecord>, filename: &str) -> Self {
         // Create a bunch of placeholder variables.
         let mut my_session = FITSession::with_filename(filename);
-        let mut num_records = 0;
         let mut num_sessions = 0;
+        let mut session_fields: Vec<Vec<FitDataField>> = Vec::new(); // Each Session message, for multisport files
         let mut lap_num = 0;
         let mut lap_vec: Vec<FITLap> = Vec::new(); // Lap information vector
+        let mut event_vec: Vec<FITEvent> = Vec::new();
+        let mut device_vec: Vec<FITDeviceInfo> = Vec::new();
+        let mut split_vec: Vec<FITSplit> = Vec::new();
         let mut records_vec: Vec<FITRecord> = Vec::new();
+        let mut pending_records: Vec<FitDataRecord> = Vec::new(); // Records waiting to be converted
 
         // This is where the actual parsing happens
         for data in file {
@@ -66,6 +205,10 @@ impl FITActivity {
             match data.kind() {
                 // Figure out what kind it is and parse accordingly
                 MesgNum::FileId => {
+                    // Records depend on the header, so convert the ones belonging to the previous header first
+                    records_vec.append(&mut convert_records(&pending_records, &my_session));
+                    pending_records.clear();
+
                     // File header
                     my_session.parse_header(data.fields());
                 }
@@ -73,30 +216,310 @@ impl FITActivity {
                     my_session.parse_session(data.fields());
                     num_sessions += 1;
                     my_session.num_sessions = Some(num_sessions);
+                    session_fields.push(data.fields().to_vec());
                 }
+                MesgNum::Activity => my_session.parse_activity(data.fields()),
                 MesgNum::Lap => {
                     let mut lap = FITLap::from_fit_lap(data.fields(), &my_session);
                     lap_num += 1;
                     lap.lap_num = Some(lap_num);
                     lap_vec.push(lap); // push the lap onto the vector
                 }
+                MesgNum::Event => {
+                    event_vec.push(FITEvent::from_fit_event(data.fields(), &my_session));
+                }
+                MesgNum::DeviceInfo => {
+                    device_vec.push(FITDeviceInfo::from_fit_device_info(
+                        data.fields(),
+                        &my_session,
+                    ));
+                }
+                MesgNum::Split => {
+                    split_vec.push(FITSplit::from_fit_split(
+                        data.fields(),
+                        "split",
+                        &my_session,
+                    ));
+                }
+                MesgNum::SplitSummary => {
+                    split_vec.push(FITSplit::from_fit_split(
+                        data.fields(),
+                        "split_summary",
+                        &my_session,
+                    ));
+                }
+                MesgNum::Record => pending_records.push(data),
+                _ => (),
+            } // match
+        } // for data
+
+        // Convert the remaining records
+        records_vec.append(&mut convert_records(&pending_records, &my_session));
+
+        // Find the GPS spikes, and drop their positions if asked to
+        let gps_outliers = clean_gps_records(&mut records_vec);
+        my_session.gps_outliers = gps_outliers.as_ref().map(Vec::len);
+
+        // Count the records, compare the recorded distance with the GPS track, etc.
+        set_record_stats(&mut my_session, &records_vec.iter().collect::<Vec<_>>());
+
+        // Now that the session is known, tag the laps, events, devices and records with the activity UUID
+        my_session.set_uuid();
+        tag_activity_uuid(
+            &my_session,
+            &mut lap_vec,
+            &mut event_vec,
+            &mut device_vec,
+            &mut split_vec,
+        );
+        for record in &mut records_vec {
+            record.activity_uuid = my_session.uuid;
+        }
+
+        // Attach the laps and records to their sessions, and work out the numbers for each session
+        let mut sessions = split_sessions(&my_session, &session_fields);
+        set_lap_sessions(&sessions, &mut lap_vec);
+        set_lap_hr_zones(&mut lap_vec, &records_vec);
+        let mut finder = SessionFinder::new(&sessions);
+        for record in &mut records_vec {
+            record.session_num = finder.session_num(record.timestamp);
+        }
+        if sessions.len() > 1 {
+            for session in &mut sessions {
+                let records: Vec<&FITRecord> = records_vec
+                    .iter()
+                    .filter(|record| record.session_num == session.session_num)
+                    .collect();
+                set_record_stats(session, &records);
+                session.gps_outliers = gps_outliers.as_ref().map(|outliers| {
+                    outliers
+                        .iter()
+                        .filter(|&&num| records_vec[num].session_num == session.session_num)
+                        .count()
+                });
+                session.set_uuid();
+            }
+        }
+
+        let untimed_records = records_vec.iter().filter(|r| r.timestamp.is_none()).count();
+        let warnings = parse_warnings(&my_session, num_sessions, untimed_records);
+
+        // Build and return the activity
+        Self {
+            session: my_session,
+            sessions,
+            laps: lap_vec,
+            records: records_vec,
+            events: event_vec,
+            devices: device_vec,
+            device_splits: split_vec,
+            warnings,
+        }
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Parses the input file one message at a time, writing the records to `fitfilename.records.csv` in chunks
+    /// instead of holding them in memory. Use this for files that are too large to be parsed with `from_file`.
+    /// The messages are decoded twice: once for the session and laps, and once to write the records.
+    ///
+    /// # Arguments
+    ///
+    /// - `filename: &str` -- The filename for the FIT file to be parsed.
+    /// - `export_records: bool` -- Write the records to CSV. If `false`, the records are counted and discarded.
+    /// - `chunk_size: usize` -- The number of records written between each flush of the CSV file.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Activity, Box<dyn Error>>` -- `Ok(Activity)` with the session and laps filled in, but no records. The
+    ///   pacing, decoupling and training load aren't worked out, since they need the records.
+    ///
+    /// # Errors
+    ///
+    /// Reading the file may fail, decoding a message may fail, writing the records may fail.
+    ///
+    /// # Example
+    ///
+    ///   ```text
+    ///    use utilities::FITActivity;
+    ///
+    ///    let my_activity = FITActivity::from_file_chunked("data/rowing.fit", true, 10_000)?;
+    ///    my_activity.session.export_json()?;
+    ///    my_activity.export_laps_csv()?;
+    ///   ```
+    #[cfg(feature = "fs")]
+    pub fn from_file_chunked(
+        filename: &str,
+        export_records: bool,
+        chunk_size: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let buffer = read_input(filename)?;
+
+        let mut my_session = FITSession::with_filename(filename);
+        let mut num_records: u64 = 0;
+        let mut num_sessions = 0;
+        let mut session_fields: Vec<Vec<FitDataField>> = Vec::new();
+        let mut lap_num = 0;
+        let mut lap_vec: Vec<FITLap> = Vec::new();
+        let mut event_vec: Vec<FITEvent> = Vec::new();
+        let mut device_vec: Vec<FITDeviceInfo> = Vec::new();
+        let mut split_vec: Vec<FITSplit> = Vec::new();
+        let mut gps_distance = GpsDistance::default();
+        let mut intervals = RecordingIntervals::default();
+        let mut untimed_records = 0;
+        let mut num_files = 0;
+
+        // First pass: the session comes at the end of the file, so read it (and the laps) before writing any records
+        for_each_message(&buffer, |data| {
+            match data.kind() {
+                MesgNum::FileId => {
+                    my_session.parse_header(data.fields());
+                    num_files += 1;
+                }
+                MesgNum::Session => {
+                    my_session.parse_session(data.fields());
+                    num_sessions += 1;
+                    my_session.num_sessions = Some(num_sessions);
+                    session_fields.push(data.fields().to_vec());
+                }
+                MesgNum::Activity => my_session.parse_activity(data.fields()),
+                MesgNum::Lap => {
+                    let mut lap = FITLap::from_fit_lap(data.fields(), &my_session);
+                    lap_num += 1;
+                    lap.lap_num = Some(lap_num);
+                    lap_vec.push(lap);
+                }
+                MesgNum::Event => {
+                    event_vec.push(FITEvent::from_fit_event(data.fields(), &my_session));
+                }
+                MesgNum::DeviceInfo => {
+                    device_vec.push(FITDeviceInfo::from_fit_device_info(
+                        data.fields(),
+                        &my_session,
+                    ));
+                }
+                MesgNum::Split => {
+                    split_vec.push(FITSplit::from_fit_split(
+                        data.fields(),
+                        "split",
+                        &my_session,
+                    ));
+                }
+                MesgNum::SplitSummary => {
+                    split_vec.push(FITSplit::from_fit_split(
+                        data.fields(),
+                        "split_summary",
+                        &my_session,
+                    ));
+                }
                 MesgNum::Record => {
                     let record = FITRecord::from_fit_record(data.fields(), &my_session);
-                    records_vec.push(record);
+                    gps_distance.add(record.lat, record.lon);
+                    intervals.add(record.timestamp);
                     num_records += 1;
+                    if record.timestamp.is_none() {
+                        untimed_records += 1;
+                    }
                 }
                 _ => (),
-            } // match
-        } // for data
+            }
+            Ok(())
+        })?;
 
-        // Set the total number of records for the session
         my_session.num_records = Some(num_records);
+        my_session.set_gps_distance(gps_distance.total());
+        my_session.set_environment(&gps_distance);
+        my_session.set_recording_interval(intervals.summary());
+        my_session.set_uuid();
+        tag_activity_uuid(
+            &my_session,
+            &mut lap_vec,
+            &mut event_vec,
+            &mut device_vec,
+            &mut split_vec,
+        );
+        let mut sessions = split_sessions(&my_session, &session_fields);
+        set_lap_sessions(&sessions, &mut lap_vec);
+
+        // Second pass: write the records, and work out the numbers for each session of a multisport file
+        let multisport = sessions.len() > 1;
+        if export_records || multisport {
+            let mut output = if export_records {
+                Some(create_output(&records_filename(
+                    Some(filename),
+                    ExportFormat::Csv,
+                ))?)
+            } else {
+                None
+            };
+            let mut writer = output.as_mut().map(records_writer).transpose()?;
+            let mut header = FITSession::with_filename(filename);
+            let mut finder = SessionFinder::new(&sessions);
+            let mut session_stats: Vec<SessionRecordStats> = sessions
+                .iter()
+                .map(|_| SessionRecordStats::default())
+                .collect();
+            let mut num_written: u64 = 0;
+
+            for_each_message(&buffer, |data| {
+                match data.kind() {
+                    MesgNum::FileId => header.parse_header(data.fields()),
+                    MesgNum::Record => {
+                        let mut record = FITRecord::from_fit_record(data.fields(), &header);
+                        record.activity_uuid = my_session.uuid;
+                        record.session_num = finder.session_num(record.timestamp);
+                        if let Some(stats) = record
+                            .session_num
+                            .and_then(|num| session_stats.get_mut(usize::from(num) - 1))
+                        {
+                            stats.add(&record);
+                        }
+
+                        if let Some(writer) = writer.as_mut() {
+                            writer.serialize(Rounded(&record))?;
+                            num_written += 1;
+                            if num_written.is_multiple_of(chunk_size.max(1) as u64) {
+                                log::trace!("FITActivity::from_file_chunked() -- {num_written} records written.");
+                                writer.flush()?;
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                Ok(())
+            })?;
+
+            if let Some(writer) = writer.as_mut() {
+                writer.flush()?;
+            }
+            drop(writer);
+            if let Some(output) = output {
+                output.commit()?;
+            }
+            if multisport {
+                for (session, stats) in sessions.iter_mut().zip(session_stats) {
+                    stats.apply(session);
+                }
+            }
+        }
+
+        let mut warnings = parse_warnings(&my_session, num_sessions, untimed_records);
+        if num_files > 1 {
+            let warning = format!(
+                "{num_files} chained FIT files found. Too large to be split, so they were read as one activity."
+            );
+            log_warnings(Some(filename), std::slice::from_ref(&warning));
+            warnings.push(warning);
+        }
 
-        // Build and return the activity
         Ok(Self {
             session: my_session,
+            sessions,
             laps: lap_vec,
-            records: records_vec,
+            records: Vec::new(),
+            events: event_vec,
+            devices: device_vec,
+            device_splits: split_vec,
+            warnings,
         })
     }
 
@@ -106,6 +529,9 @@ impl FITActivity {
     /// - _Session_ gets exported to `fitfilename.session.json`
     /// - _Laps_ get exported to `fitfilename.laps.csv`
     /// - _Records_ get exported to `fitfilename.records.csv`
+    /// - _Events_ get exported to `fitfilename.events.csv`
+    /// - _Devices_ get exported to `fitfilename.devices.csv`
+    /// - _Device splits_ get exported to `fitfilename.device_splits.csv`
     ///
     /// # Parameters
     ///
@@ -118,17 +544,89 @@ impl FITActivity {
     /// # Errors
     ///
     /// Writing various exports may result in errors.
+    #[cfg(feature = "fs")]
     pub fn export(&self) -> Result<(), Box<dyn Error>> {
+        self.export_as(ExportFormat::Csv)
+    }
+
+    /// Same as `export()`, but with the records in the format given, e.g. `fitfilename.records.parquet`.
+    ///
+    /// # Errors
+    ///
+    /// Writing various exports may result in errors.
+    #[cfg(feature = "fs")]
+    pub fn export_as(&self, format: ExportFormat) -> Result<(), Box<dyn Error>> {
         self.session.export_json()?;
         Self::export_laps_csv(self)?;
-        Self::export_records_csv(self)?;
+        Self::export_records(self, format)?;
+        Self::export_events_csv(self)?;
+        Self::export_devices_csv(self)?;
+        Self::export_device_splits_csv(self)?;
 
         // return safely
         Ok(())
     }
 
     ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-    /// Export the laps information to a CSV file named after the FIT file with the _.fit_ extension replaced by _.laps.csv_
+    /// Export the session, laps and records together as a single JSON document named after the FIT file with the
+    /// _.fit_ extension replaced by _.bundle.json_
+    ///
+    /// # Parameters
+    ///
+    /// `&self` -- The current activity.
+    ///
+    /// # Returns
+    ///
+    /// `Result<(), Box<dyn Error>>` -- `Ok(())` if successful, `Error` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Creating the file may fail. Serializing to JSON may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_bundle_json(&self) -> Result<(), Box<dyn Error>> {
+        let outfile = output_path(
+            self.session.filename.as_deref().unwrap_or_default(),
+            "bundle.json",
+        );
+        log::trace!(
+            "exporter::export_bundle_json() -- Writing JSON file {}",
+            &outfile.to_str().unwrap_or("<Unknown filename>")
+        );
+
+        write_output(&outfile, |file| self.write_bundle_json(file))?;
+
+        Ok(())
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Write the session, laps and records together as a single JSON document to any writer, e.g. an HTTP response.
+    ///
+    /// # Parameters
+    ///
+    /// `writer: W` -- Where the JSON is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing or writing the JSON may fail.
+    pub fn write_bundle_json<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(writer, &Rounded(self))?;
+        Ok(())
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// The session, laps and records together as a single JSON document -- the same document `export_bundle_json()`
+    /// writes to a file.
+    ///
+    /// # Errors
+    ///
+    /// Serializing to JSON may fail.
+    pub fn bundle_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(&Rounded(self))?)
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Export the laps information to a CSV file named after the FIT file with the _.fit_ extension replaced by
+    /// _.laps.csv_
     ///
     /// # Parameters
     ///
@@ -144,129 +642,449 @@ impl FITActivity {
     ///
     /// # Panics
     ///
-    /// If the outfile is blank, setting the extension may panic.
-    pub fn export_laps_csv(&self) -> Result<(), Box<dyn Error>> {
-        // Change the file extension
-        let mut outfile = PathBuf::from(&self.session.filename.as_ref().unwrap_or(&String::new()));
-        outfile.set_extension("laps.csv");
+    /// If the outfile is blank, setting the extension may panic.
+    #[cfg(feature = "fs")]
+    pub fn export_laps_csv(&self) -> Result<(), Box<dyn Error>> {
+        // Change the file extension
+        let outfile = output_path(
+            self.session.filename.as_deref().unwrap_or_default(),
+            "laps.csv",
+        );
+        log::trace!(
+            "exporter::export_laps_csv() -- Writing lap CSV file {}",
+            &outfile.to_str().unwrap_or("<Unknown filename>")
+        );
+
+        write_output(&outfile, |file| self.write_laps_csv(file))
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Write the laps information as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Parameters
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing may fail. Writing may fail.
+    pub fn write_laps_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        // Create a buffer for the CSV
+        let mut lap_writer = ColumnWriter::new(writer, CsvFile::FitLaps);
+        lap_writer.write_header()?;
+
+        // Now write the actual laps
+        for lap in &self.laps {
+            lap_writer.serialize(Rounded(lap))?;
+        }
+
+        // Write the file
+        lap_writer.flush()?;
+
+        Ok(())
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Export the events to a CSV file named after the FIT file with the _.fit_ extension replaced by _.events.csv_
+    ///
+    /// # Parameters
+    ///
+    /// `&self` -- The current activity.
+    ///
+    /// # Returns
+    ///
+    /// `Result<(), Box<dyn Error>>` -- `Ok(())` if successful, `Error` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Writing the CSV may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_events_csv(&self) -> Result<(), Box<dyn Error>> {
+        let outfile = output_path(
+            self.session.filename.as_deref().unwrap_or_default(),
+            "events.csv",
+        );
+        log::trace!(
+            "exporter::export_events_csv() -- Writing event CSV file {}",
+            &outfile.to_str().unwrap_or("<Unknown filename>")
+        );
+
+        write_output(&outfile, |file| self.write_events_csv(file))
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Write the events as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Parameters
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing may fail. Writing may fail.
+    pub fn write_events_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut event_writer = ColumnWriter::new(writer, CsvFile::FitEvents);
+        event_writer.write_header()?;
+        for event in &self.events {
+            event_writer.serialize(event)?;
+        }
+        event_writer.flush()?;
+
+        Ok(())
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Export the devices to a CSV file named after the FIT file with the _.fit_ extension replaced by _.devices.csv_
+    ///
+    /// # Parameters
+    ///
+    /// `&self` -- The current activity.
+    ///
+    /// # Returns
+    ///
+    /// `Result<(), Box<dyn Error>>` -- `Ok(())` if successful, `Error` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Writing the CSV may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_devices_csv(&self) -> Result<(), Box<dyn Error>> {
+        let outfile = output_path(
+            self.session.filename.as_deref().unwrap_or_default(),
+            "devices.csv",
+        );
+        log::trace!(
+            "exporter::export_devices_csv() -- Writing device CSV file {}",
+            &outfile.to_str().unwrap_or("<Unknown filename>")
+        );
+
+        write_output(&outfile, |file| self.write_devices_csv(file))
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Write the devices as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Parameters
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing may fail. Writing may fail.
+    pub fn write_devices_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut device_writer = ColumnWriter::new(writer, CsvFile::FitDevices);
+        device_writer.write_header()?;
+        for device in &self.devices {
+            device_writer.serialize(Rounded(device))?;
+        }
+        device_writer.flush()?;
+
+        Ok(())
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Export the splits recorded by the device to a CSV file named after the FIT file with the _.fit_ extension
+    /// replaced by _.device_splits.csv_
+    ///
+    /// # Parameters
+    ///
+    /// `&self` -- The current activity.
+    ///
+    /// # Returns
+    ///
+    /// `Result<(), Box<dyn Error>>` -- `Ok(())` if successful, `Error` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Writing the CSV may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_device_splits_csv(&self) -> Result<(), Box<dyn Error>> {
+        let outfile = output_path(
+            self.session.filename.as_deref().unwrap_or_default(),
+            "device_splits.csv",
+        );
+        log::trace!(
+            "exporter::export_device_splits_csv() -- Writing device split CSV file {}",
+            &outfile.to_str().unwrap_or("<Unknown filename>")
+        );
+
+        write_output(&outfile, |file| self.write_device_splits_csv(file))
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Write the splits recorded by the device as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Parameters
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing may fail. Writing may fail.
+    pub fn write_device_splits_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut split_writer = ColumnWriter::new(writer, CsvFile::FitDeviceSplits);
+        split_writer.write_header()?;
+        for split in &self.device_splits {
+            split_writer.serialize(Rounded(split))?;
+        }
+        split_writer.flush()?;
+
+        Ok(())
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Export the records information to a CSV file named after the FIT file with the _.fit_ extension replaced by
+    /// _.records.csv_
+    ///
+    /// # Parameters
+    ///
+    /// `&self` -- The current activity.
+    ///
+    /// # Returns
+    ///
+    /// `Result<(), Box<dyn Error>>` -- `Ok(())` if successful, `Error` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Creating a buffer for the CSV may fail. Serializing may fail. Flushing may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_records_csv(&self) -> Result<(), Box<dyn Error>> {
+        // Change the file extension
+        let outfile = records_filename(self.session.filename.as_deref(), ExportFormat::Csv);
+        self.export_records_csv_to(&outfile)
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Export the records to a file named after the FIT file with the _.fit_ extension replaced by _.records.csv_ or
+    /// _.records.parquet_, depending on the format.
+    ///
+    /// # Parameters
+    ///
+    /// `format: ExportFormat` -- The format to write.
+    ///
+    /// # Errors
+    ///
+    /// Parquet may not be available in this build. Serializing or writing may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_records(&self, format: ExportFormat) -> Result<(), Box<dyn Error>> {
+        let outfile = records_filename(self.session.filename.as_deref(), format);
+        log::trace!(
+            "exporter::export_records() -- Writing records file {}",
+            outfile.display()
+        );
+        write_output(&outfile, |file| self.write_records(file, format))
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Write the records in the format given to any writer.
+    ///
+    /// # Parameters
+    ///
+    /// - `writer: W` -- Where the records are written.
+    /// - `format: ExportFormat` -- The format to write.
+    ///
+    /// # Errors
+    ///
+    /// Parquet may not be available in this build. Serializing or writing may fail.
+    pub fn write_records<W: Write + Send>(
+        &self,
+        writer: W,
+        format: ExportFormat,
+    ) -> Result<(), Box<dyn Error>> {
+        write_rows(
+            self.records.iter().map(Rounded),
+            CsvFile::FitRecords,
+            format,
+            writer,
+        )
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Export the records information to the CSV file given.
+    ///
+    /// # Parameters
+    ///
+    /// `outfile: &Path` -- The CSV file to be written.
+    ///
+    /// # Errors
+    ///
+    /// Creating a buffer for the CSV may fail. Serializing may fail. Flushing may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_records_csv_to(&self, outfile: &Path) -> Result<(), Box<dyn Error>> {
         log::trace!(
-            "exporter::export_laps_csv() -- Writing lap CSV file {}",
+            "exporter::export_records_csv() -- Writing records CSV file {}",
             &outfile.to_str().unwrap_or("<Unknown filename>")
         );
 
+        write_output(outfile, |file| self.write_records_csv(file))
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Write the records information as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Parameters
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing may fail. Writing may fail.
+    pub fn write_records_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
         // Create a buffer for the CSV
-        let mut lap_writer = WriterBuilder::new().has_headers(false).from_path(outfile)?;
-
-        // Write the header separately since types::Duration doesn't get serialized properly
-        lap_writer.write_record([
-            "filename",
-            "lap_num",
-            "cadence_avg_bpm",
-            "cadence_max_bpm",
-            "heartrate_min_bpm",
-            "heartrate_avg_bpm",
-            "heartrate_max_bpm",
-            "speed_avg_ms",
-            "speed_max_ms",
-            "power_avg_w",
-            "power_max_w",
-            "lat_start",
-            "lon_start",
-            "lat_end",
-            "lon_end",
-            "stance_time_avg_sec",
-            "vertical_oscillation_avg",
-            "ascent_m",
-            "descent_m",
-            "calories",
-            "distance_m",
-            "duration_secs",
-            "duration_active_sec",
-            "duration_moving_sec",
-            "start_time",
-            "finish_time",
-            "heart_rate_zone0_sec",
-            "heart_rate_zone1_sec",
-            "heart_rate_zone2_sec",
-            "heart_rate_zone3_sec",
-            "heart_rate_zone4_sec",
-        ])?;
+        let mut rec_writer = records_writer(writer)?;
 
-        // Now write the actual laps
-        for lap in &self.laps {
-            lap_writer.serialize(lap)?;
+        // Now write the actual records
+        for rec in &self.records {
+            rec_writer.serialize(Rounded(rec))?;
         }
 
         // Write the file
-        lap_writer.flush()?;
+        rec_writer.flush()?;
 
         Ok(())
     }
 
-    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-    /// Export the records information to a CSV file named after the FIT file with the _.fit_ extension replaced by _.records.csv_
+    /// The sessions that go in the summary CSV: one per sport for multisport files, or the overall session if the file
+    /// has no Session message.
+    #[must_use]
+    pub fn summary_sessions(&self) -> Vec<&FITSession> {
+        if self.sessions.is_empty() {
+            vec![&self.session]
+        } else {
+            self.sessions.iter().collect()
+        }
+    }
+
+    /// Splits the activity into kilometers or miles, using the records rather than the laps.
     ///
-    /// # Parameters
+    /// # Arguments
     ///
-    /// `&self` -- The current activity.
+    /// `unit: SplitUnit` -- Whether to split by kilometer or mile.
     ///
     /// # Returns
     ///
-    /// `Result<(), Box<dyn Error>>` -- `Ok(())` if successful, `Error` otherwise.
+    /// `Vec<Split>` -- The splits. Records without a timestamp or distance are left out.
+    #[must_use]
+    pub fn splits(&self, unit: SplitUnit) -> Vec<Split> {
+        splits(&split_points(&self.records), unit)
+    }
+
+    /// The elevation against the distance, from the records, for drawing with `profile_svg()`.
     ///
-    /// # Errors
+    /// # Returns
     ///
-    /// Creating a buffer for the CSV may fail. Serializing may fail. Flushing may fail.
-    pub fn export_records_csv(&self) -> Result<(), Box<dyn Error>> {
-        // Change the file extension
-        let mut outfile = PathBuf::from(
-            &self
-                .session
-                .filename
-                .as_ref()
-                .unwrap_or(&String::from("export-records.csv")),
-        );
-        outfile.set_extension("records.csv");
-        log::trace!(
-            "exporter::export_records_csv() -- Writing records CSV file {}",
-            &outfile.to_str().unwrap_or("<Unknown filename>")
+    /// `Vec<ProfilePoint>` -- The profile. Records without a timestamp, distance or altitude are left out.
+    #[must_use]
+    pub fn elevation_profile(&self) -> Vec<ProfilePoint> {
+        profile_points(&split_points(&self.records))
+    }
+
+    /// Fills in the gaps between the records of files made with smart recording with a synthetic record for every
+    /// second, interpolated between the recorded ones. If the file has no moving time, it is estimated from the filled
+    /// in records. Files recorded every second are left as they are.
+    ///
+    /// # Returns
+    ///
+    /// `usize` -- The number of records added.
+    pub fn interpolate_records(&mut self) -> usize {
+        if self.session.recording_mode != Some(RecordingMode::Smart) {
+            return 0;
+        }
+
+        let recorded = self.records.len();
+        self.records = interpolate_records(&self.records);
+        if self.session.duration_moving.is_none() {
+            self.session.duration_moving = moving_time(&self.records);
+        }
+        log::debug!(
+            "FITActivity::interpolate_records() -- {} records added.",
+            self.records.len() - recorded
         );
+        self.records.len() - recorded
+    }
 
-        // Create a buffer for the CSV
-        let mut rec_writer = WriterBuilder::new().has_headers(false).from_path(outfile)?;
-
-        // Write the header separately since types::Duration doesn't get serialized properly
-        rec_writer.write_record([
-            "timestamp",
-            "duration_sec",
-            "distance_m",
-            "altitude_m",
-            "stance_time_sec",
-            "vertical_oscillation",
-            "cadence_bpm",
-            "speed_ms",
-            "power_w",
-            "heartrate_bpm",
-            "calories",
-            "lat_deg",
-            "lon_deg",
-        ])?;
+    /// Works out the mean-maximal power and heart rate curve from the records.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<MeanMax>` -- The best averages for each duration. Records without a timestamp are left out.
+    #[must_use]
+    pub fn mean_max(&self) -> Vec<MeanMax> {
+        mean_max(&mean_max_samples(&self.records))
+    }
 
-        // Now write the actual laps
-        for rec in &self.records {
-            rec_writer.serialize(rec)?;
+    /// Works out the heart rate against the speed and power over each half minute of the records.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<HrScatter>` -- One sample per half minute, without the warm-up, the stops and the outliers.
+    #[must_use]
+    pub fn hr_scatter(&self) -> Vec<HrScatter> {
+        hr_scatter(
+            &mean_max_samples(&self.records),
+            &split_points(&self.records),
+        )
+    }
+
+    /// The records as track points, the view shared with the other formats.
+    pub fn track_points(&self) -> impl Iterator<Item = TrackPoint> + '_ {
+        self.records.iter().map(TrackPoint::from)
+    }
+
+    /// Builds the track of the activity as a GeoJSON `FeatureCollection`, with a `LineString` for each lap. The records
+    /// are placed in the laps by their timestamps. Activities without laps get a single line for the whole activity.
+    ///
+    /// # Returns
+    ///
+    /// `serde_json::Value` -- The `FeatureCollection`. Records without a position are left out, and so are laps with
+    /// fewer than two positions. Indoor and virtual activities have no real-world track, so they get no features.
+    #[must_use]
+    pub fn geojson(&self) -> serde_json::Value {
+        if !self.session.environment.has_real_location() {
+            return feature_collection(std::iter::empty());
         }
 
-        // Write the file
-        rec_writer.flush()?;
+        if self.laps.is_empty() {
+            let properties = GeoJsonProperties {
+                name: self.session.filename.clone(),
+                start_time: self.session.start_time,
+                distance_m: self.session.distance.map(|distance| distance.value),
+                sport: self.session.activity_type.clone(),
+            };
+            return feature_collection([(properties, positions(&self.records))]);
+        }
 
-        Ok(())
+        feature_collection(self.laps.iter().map(|lap| {
+            let sport = self
+                .sessions
+                .iter()
+                .find(|session| {
+                    session.session_num.is_some() && session.session_num == lap.session_num
+                })
+                .unwrap_or(&self.session)
+                .activity_type
+                .clone();
+            let properties = GeoJsonProperties {
+                name: lap.lap_num.map(|num| format!("Lap {num}")),
+                start_time: lap.start_time,
+                distance_m: lap.distance.map(|distance| distance.value),
+                sport,
+            };
+            let coordinates = match (lap.start_time, lap.finish_time) {
+                (Some(start), Some(finish)) => positions(self.records.iter().filter(|rec| {
+                    rec.timestamp
+                        .is_some_and(|time| time >= start && time <= finish)
+                })),
+                _ => Vec::new(),
+            };
+            (properties, coordinates)
+        }))
     }
 
     /// Print the metadata header from the FIT file.
     #[allow(clippy::too_many_lines)]
     pub fn print(&self, detailed: bool) {
-        let unknown = String::new();
+        let unknown = placeholder("");
 
         println!(
             "\nFile:                     {}",
@@ -282,14 +1100,16 @@ impl FITActivity {
         );
         println!(
             "Serial number:            {}",
-            self.session.serial_number.as_ref().unwrap_or(&unknown)
+            self.session
+                .serial_number
+                .as_deref()
+                .map_or_else(|| unknown.clone(), output_serial)
         );
         println!(
             "Time created:             {}",
-            self.session
-                .time_created
-                .as_ref()
-                .unwrap_or(&Local.timestamp_opt(0, 0).unwrap())
+            self.session.time_created_local().unwrap_or_else(|| self
+                .session
+                .to_device_local(&Local.timestamp_opt(0, 0).unwrap()))
         );
         println!(
             "Activity type:            {}",
@@ -299,6 +1119,7 @@ impl FITActivity {
             "Activity detail:          {}",
             self.session.activity_detailed.as_ref().unwrap_or(&unknown)
         );
+        println!("Environment:              {}", self.session.environment);
         println!(
             "Sessions:                  {:>9}",
             self.session.num_sessions.unwrap_or_default()
@@ -341,12 +1162,25 @@ impl FITActivity {
             f64::from(self.session.heartrate_max.unwrap_or_default())
         );
 
-        println!("Speed Avg (m/s):           {:>9.2}", {
-            self.session.speed_avg.unwrap_or_default().value
-        });
-        println!("Speed Max (m/s):           {:>9.2}", {
-            self.session.speed_max.unwrap_or_default().value
-        });
+        let speed_avg = self.session.speed_avg.unwrap_or_default().value;
+        println!(
+            "{:<27}{:>9.2}",
+            label("Speed Avg", Measure::Speed),
+            converted(Measure::Speed, speed_avg)
+        );
+        println!(
+            "{:<27}{:>9.2}",
+            label("Speed Max", Measure::Speed),
+            converted(
+                Measure::Speed,
+                self.session.speed_max.unwrap_or_default().value
+            )
+        );
+        if let Some((pace_label, pace)) =
+            pace_label("Pace Avg", self.session.activity_type.as_deref(), speed_avg)
+        {
+            println!("{pace_label:<27}{:>9}", pace.to_string());
+        }
 
         println!(
             "Power Avg:                 {:>9.2}",
@@ -362,17 +1196,75 @@ impl FITActivity {
         );
 
         println!(
-            "Ascent (m):                {:>9.2}",
-            f64::from(self.session.ascent.unwrap_or_default().value)
+            "{:<27}{:>9.2}",
+            label("Ascent", Measure::Length),
+            converted(
+                Measure::Length,
+                f64::from(self.session.ascent.unwrap_or_default().value)
+            )
+        );
+        println!(
+            "{:<27}{:>9.2}",
+            label("Descent", Measure::Length),
+            converted(
+                Measure::Length,
+                f64::from(self.session.descent.unwrap_or_default().value)
+            )
+        );
+        println!(
+            "{:<27}{:>9.2}",
+            label("Distance", Measure::Distance),
+            converted(
+                Measure::Distance,
+                self.session.distance.unwrap_or_default().value
+            )
         );
         println!(
-            "Descent (m):               {:>9.2}",
-            f64::from(self.session.descent.unwrap_or_default().value)
+            "{:<27}{:>9.2}",
+            label("GPS Distance", Measure::Distance),
+            converted(
+                Measure::Distance,
+                self.session.gps_distance.unwrap_or_default().value
+            )
         );
         println!(
-            "Distance (m):              {:>9.2}",
-            self.session.distance.unwrap_or_default().value
+            "Distance Discrepancy (%):  {:>9.2}",
+            self.session.distance_discrepancy_pct.unwrap_or_default()
         );
+        if let (Some(index), Some(split)) =
+            (self.session.pacing_index_pct, self.session.pacing_split)
+        {
+            println!("Pacing Index (%):          {index:>9.2} ({split} split)");
+        }
+        if let Some(pct) = self.session.decoupling_power_pct {
+            println!("Decoupling Pw:HR (%):      {pct:>9.2}");
+        }
+        if let Some(pct) = self.session.decoupling_pace_pct {
+            println!("Decoupling Pa:HR (%):      {pct:>9.2}");
+        }
+        if let Some(power) = self.session.power_normalized {
+            println!("Normalized Power (W):      {power:>9.0}");
+        }
+        if let (Some(intensity), Some(tss)) = (
+            self.session.intensity_factor,
+            self.session.training_stress_score,
+        ) {
+            println!("Intensity Factor:          {intensity:>9.2}");
+            println!("Training Stress Score:     {tss:>9.0}");
+        }
+        if let (Some(temperature), Some(pct), Some(heat)) = (
+            self.session.temperature_avg_c,
+            self.session.heat_adjustment_pct,
+            self.session.heat,
+        ) {
+            println!("Temperature (°C):          {temperature:>9.1} ({heat}, {pct:.1}% slower)");
+        }
+        if let (Some(interval), Some(mode)) = (
+            self.session.recording_interval_sec,
+            self.session.recording_mode,
+        ) {
+            println!("Recording Interval (s):    {interval:>9.0} ({mode})");
+        }
         if detailed {
             println!(
                 "North East Latitude:       {:>9.3}",
@@ -441,18 +1333,458 @@ impl FITActivity {
                 "  Warmup:                   {}",
                 self.session.time_in_hr_zones.hr_zone_0.unwrap_or_default()
             );
+            if !self.device_splits.is_empty() {
+                println!("{}", label("Device Splits", Measure::Distance));
+                for split in &self.device_splits {
+                    print_device_split(split);
+                }
+            }
         }
     }
 
     // end impl Activity
 }
 
-#[cfg(test)]
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the name of the records file based on the FIT file name, e.g. _.records.csv_.
+#[cfg(feature = "fs")]
+fn records_filename(filename: Option<&str>, format: ExportFormat) -> PathBuf {
+    output_path(
+        filename.unwrap_or("export-records.csv"),
+        &format!("records.{}", format.extension()),
+    )
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Creates the records CSV writer and writes the header.
+fn records_writer<W: Write>(writer: W) -> Result<ColumnWriter<W>, Box<dyn Error>> {
+    let mut rec_writer = ColumnWriter::new(writer, CsvFile::FitRecords);
+    rec_writer.write_header()?;
+
+    Ok(rec_writer)
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The records with both a timestamp and a distance, as points for the split and pacing analysis.
+pub(crate) fn split_points<'a, I>(records: I) -> Vec<SplitPoint>
+where
+    I: IntoIterator<Item = &'a FITRecord>,
+{
+    records
+        .into_iter()
+        .filter_map(|rec| TrackPoint::from(rec).split_point())
+        .collect()
+}
+
+/// The GeoJSON positions of the records that have one.
+fn positions<'a, I>(records: I) -> Vec<Vec<f64>>
+where
+    I: IntoIterator<Item = &'a FITRecord>,
+{
+    records
+        .into_iter()
+        .filter_map(|rec| TrackPoint::from(rec).position())
+        .collect()
+}
+
+/// The records with a timestamp, as samples for the mean-maximal curve.
+pub(crate) fn mean_max_samples<'a, I>(records: I) -> Vec<MeanMaxSample>
+where
+    I: IntoIterator<Item = &'a FITRecord>,
+{
+    records
+        .into_iter()
+        .filter_map(|rec| TrackPoint::from(rec).mean_max_sample())
+        .collect()
+}
+
+/// The records with a timestamp, as samples for the time in each heart rate zone.
+pub(crate) fn hr_samples<'a, I>(records: I) -> Vec<(DateTime<Local>, Option<f64>)>
+where
+    I: IntoIterator<Item = &'a FITRecord>,
+{
+    records
+        .into_iter()
+        .filter_map(|rec| Some((rec.timestamp?, rec.heartrate.map(f64::from))))
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Prints a split recorded by the device on a line of its own: its number and kind, or the number of splits for a
+/// summary, followed by the timer time and the distance.
+fn print_device_split(split: &FITSplit) {
+    let name = if split.is_summary() {
+        format!(
+            "{} x {}",
+            split.split_type.as_deref().unwrap_or_default(),
+            split.num_splits.unwrap_or_default()
+        )
+    } else {
+        format!(
+            "{:>3} {}",
+            split.split_index.unwrap_or_default() + 1,
+            split.split_type.as_deref().unwrap_or_default()
+        )
+    };
+    println!(
+        "  {name:<24} {} {:>9.2}",
+        Duration::from_secs_f64(split.timer_time.unwrap_or_default()),
+        converted(Measure::Distance, split.distance.unwrap_or_default())
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Tags the laps, events, devices and device splits with the activity UUID, once the session is known.
+fn tag_activity_uuid(
+    session: &FITSession,
+    laps: &mut [FITLap],
+    events: &mut [FITEvent],
+    devices: &mut [FITDeviceInfo],
+    splits: &mut [FITSplit],
+) {
+    for lap in laps {
+        lap.activity_uuid = session.uuid;
+    }
+    for event in events {
+        event.activity_uuid = session.uuid;
+    }
+    for device in devices {
+        device.activity_uuid = session.uuid;
+    }
+    for split in splits {
+        split.activity_uuid = session.uuid;
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Splits the activity into its sessions. Files with a single session get a copy of the overall session, so the output
+/// is the same as before, while multisport files get a session per Session message with its own sport and totals.
+///
+/// # Arguments
+///
+/// - `session: &FITSession` -- The overall session, with the file header and activity information.
+/// - `session_fields: &[Vec<FitDataField>]` -- The fields of each Session message, in the order found in the file.
+///
+/// # Returns
+///
+/// `Vec<FITSession>` -- The sessions, numbered from 1. Empty if the file has no Session message.
+fn split_sessions(session: &FITSession, session_fields: &[Vec<FitDataField>]) -> Vec<FITSession> {
+    if session_fields.len() == 1 {
+        return vec![FITSession {
+            session_num: Some(1),
+            ..session.clone()
+        }];
+    }
+
+    session_fields
+        .iter()
+        .zip(1_u16..)
+        .map(|(fields, num)| {
+            let mut split = session.clone();
+            split.parse_session(fields);
+            split.session_num = Some(num);
+            split
+        })
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Finds the session a lap or record belongs to from its time: the last session starting at or before it. Laps and
+/// records come in time order, so messages without a time stay with the session of the previous one.
+struct SessionFinder {
+    /// The start time and number of each session.
+    starts: Vec<(Option<DateTime<Local>>, Option<u16>)>,
+    /// The session of the previous message.
+    current: Option<u16>,
+}
+
+impl SessionFinder {
+    /// A finder for the sessions given, starting with the first session.
+    fn new(sessions: &[FITSession]) -> Self {
+        Self {
+            starts: sessions
+                .iter()
+                .map(|session| (session.start_time, session.session_num))
+                .collect(),
+            current: sessions.first().and_then(|session| session.session_num),
+        }
+    }
+
+    /// The number of the session the message at `time` belongs to, or `None` if the file has no sessions.
+    fn session_num(&mut self, time: Option<DateTime<Local>>) -> Option<u16> {
+        if let Some(time) = time {
+            if let Some(num) = self
+                .starts
+                .iter()
+                .rev()
+                .find(|(start, _)| start.is_some_and(|start| start <= time))
+                .map(|(_, num)| *num)
+            {
+                self.current = num;
+            }
+        }
+        self.current
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Tags each lap with the number of the session it belongs to, going by the start time of the lap.
+fn set_lap_sessions(sessions: &[FITSession], laps: &mut [FITLap]) {
+    let mut finder = SessionFinder::new(sessions);
+    for lap in laps {
+        lap.session_num = finder.session_num(lap.start_time);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the time in each heart rate zone of the laps from their records, if the zones are set for the run or the
+/// device didn't record it. The records of a lap are the ones from its start time to its finish time.
+fn set_lap_hr_zones(laps: &mut [FITLap], records: &[FITRecord]) {
+    for lap in laps {
+        let Some(limits) = recalculated_hr_zones(&lap.time_in_hr_zones) else {
+            continue;
+        };
+        let (Some(start), Some(finish)) = (lap.start_time, lap.finish_time) else {
+            continue;
+        };
+        let samples = hr_samples(records.iter().filter(|rec| {
+            rec.timestamp
+                .is_some_and(|time| start <= time && time <= finish)
+        }));
+        lap.time_in_hr_zones = time_in_hr_zones(&samples, &limits);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Finds the GPS spikes in the records, and drops their positions if set with `Settings::set_gps_cleaning()`. The
+/// records themselves are kept, along with their other values.
+///
+/// # Arguments
+///
+/// `records: &mut [FITRecord]` -- The records of the activity, in order.
+///
+/// # Returns
+///
+/// `Option<Vec<usize>>` -- The indexes of the records with a spike, or `None` if none of the records have a position.
+fn clean_gps_records(records: &mut [FITRecord]) -> Option<Vec<usize>> {
+    if !records.iter().any(|record| record.lat.is_some()) {
+        return None;
+    }
+
+    let points: Vec<StatsPoint> = records
+        .iter()
+        .map(|record| StatsPoint {
+            time: record.timestamp,
+            position: record.lat.zip(record.lon),
+            elevation: None,
+        })
+        .collect();
+    let outliers = gps_outliers(&points, max_gps_speed());
+    if gps_cleaning().is_some() {
+        for &num in &outliers {
+            records[num].lat = None;
+            records[num].lon = None;
+        }
+    }
+
+    Some(outliers)
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Sets the numbers worked out from the records on a session: the number of records, the GPS distance, the environment,
+/// the pacing, the decoupling, the training load, the recording interval and the time in each heart rate zone. The
+/// training load uses the FTP set for the run, or else the threshold power recorded in the file. The time in zone is
+/// only worked out if the zones are set for the run or the device didn't record it.
+///
+/// # Arguments
+///
+/// - `session: &mut FITSession` -- The session to update.
+/// - `records: &[&FITRecord]` -- The records of the session.
+pub(crate) fn set_record_stats(session: &mut FITSession, records: &[&FITRecord]) {
+    session.num_records = u64::try_from(records.len()).ok();
+
+    // Compare the recorded distance with the GPS track
+    let mut gps_distance = GpsDistance::default();
+    for record in records {
+        gps_distance.add(record.lat, record.lon);
+    }
+    session.set_gps_distance(gps_distance.total());
+    session.set_environment(&gps_distance);
+    let points = split_points(records.iter().copied());
+    session.set_pacing(pacing(&points));
+    let samples = mean_max_samples(records.iter().copied());
+    session.set_decoupling(decoupling(&samples, &points));
+    session.set_power_metrics(power_metrics(
+        &samples,
+        ftp().or_else(|| session.power_threshold.map(f64::from)),
+    ));
+    session.set_recording_interval(recording_interval(
+        records.iter().map(|record| record.timestamp),
+    ));
+    if let Some(limits) = recalculated_hr_zones(&session.time_in_hr_zones) {
+        session.time_in_hr_zones = time_in_hr_zones(&hr_samples(records.iter().copied()), &limits);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The numbers for one session of a multisport file, collected one record at a time when reading in chunks.
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+struct SessionRecordStats {
+    /// The number of records in the session.
+    num_records: u64,
+    /// The length of the GPS track of the session.
+    gps_distance: GpsDistance,
+    /// The time between the records of the session.
+    intervals: RecordingIntervals,
+}
+
+#[cfg(feature = "fs")]
+impl SessionRecordStats {
+    /// Adds the next record of the session.
+    fn add(&mut self, record: &FITRecord) {
+        self.num_records += 1;
+        self.gps_distance.add(record.lat, record.lon);
+        self.intervals.add(record.timestamp);
+    }
+
+    /// Sets the numbers on the session, and the UUID now that the sport is known.
+    fn apply(self, session: &mut FITSession) {
+        session.num_records = Some(self.num_records);
+        session.set_gps_distance(self.gps_distance.total());
+        session.set_environment(&self.gps_distance);
+        session.set_recording_interval(self.intervals.summary());
+        session.set_uuid();
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out what is worth warning about once the file has been parsed, and logs it.
+///
+/// # Arguments
+///
+/// - `session: &FITSession` -- The session, with the GPS distance set.
+/// - `num_sessions: u16` -- The number of Session messages found.
+/// - `untimed_records: usize` -- The number of records without a timestamp.
+fn parse_warnings(session: &FITSession, num_sessions: u16, untimed_records: usize) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    match num_sessions {
+        0 => warnings.push("No Session message found. The summary is incomplete.".to_string()),
+        1 => (),
+        n => warnings.push(format!(
+            "{n} Session messages found. The session summary only covers the last one, the summary CSV has a row for each."
+        )),
+    }
+    if untimed_records > 0 {
+        warnings.push(format!(
+            "{untimed_records} records have no timestamp. Their duration is unknown."
+        ));
+    }
+    warnings.extend(miscalibration_warning(session.distance_discrepancy_pct));
+    warnings.extend(gps_outlier_warning(session.gps_outliers));
+
+    log_warnings(session.filename.as_deref(), &warnings);
+    warnings
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The options used when decoding the FIT data. The composite fields are kept so both the `altitude` and the
+/// `enhanced_altitude` record fields are available, rather than `altitude` being replaced by `enhanced_altitude`.
+fn decode_options() -> HashSet<DecodeOption> {
+    HashSet::from([DecodeOption::KeepCompositeFields])
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Splits the decoded messages into one list per chained FIT file. A `FileId` message after any other message starts
+/// a new file.
+fn split_chained(messages: Vec<FitDataRecord>) -> Vec<Vec<FitDataRecord>> {
+    let mut files: Vec<Vec<FitDataRecord>> = vec![Vec::new()];
+    for message in messages {
+        let new_file = message.kind() == MesgNum::FileId
+            && files
+                .last()
+                .is_some_and(|file| file.iter().any(|m| m.kind() != MesgNum::FileId));
+        if new_file {
+            files.push(Vec::new());
+        }
+        if let Some(file) = files.last_mut() {
+            file.push(message);
+        }
+    }
+
+    files
+}
+
+/// The name of a FIT file chained with others in the same file, with `.fileN` added, e.g. `download.file2.fit`, so the
+/// detail exports don't overwrite each other. Files that aren't chained keep their name.
 ///
+/// # Arguments
+///
+/// - `filename: &str` -- The name of the file holding the chained files.
+/// - `file_num: usize` -- The number of the chained file, starting at 1.
+/// - `num_files: usize` -- The number of chained files.
+pub(crate) fn chained_filename(filename: &str, file_num: usize, num_files: usize) -> String {
+    if num_files < 2 {
+        return filename.to_string();
+    }
+    set_extension(
+        filename,
+        &format!("file{file_num}.{}", get_extension(filename)),
+    )
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Decodes the FIT data one message at a time, calling `f` for each data message.
+pub(crate) fn for_each_message<F>(buffer: &[u8], mut f: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(FitDataRecord) -> Result<(), Box<dyn Error>>,
+{
+    let mut remaining = buffer;
+    let mut processor = FitStreamProcessor::new();
+    for option in decode_options() {
+        processor.add_option(option);
+    }
+
+    while !remaining.is_empty() {
+        #[cfg(feature = "fs")]
+        crate::cancel::check_cancelled()?;
+        let (rest, obj) = processor.deserialize_next(remaining)?;
+        remaining = rest;
+
+        match obj {
+            FitObject::Crc(..) => processor.reset(),
+            FitObject::DataMessage(msg) => f(processor.decode_message(msg)?)?,
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Converts the raw `Record` messages to `FITRecord`s in parallel, with the settings of the calling thread. The output
+/// keeps the order of the input.
+fn convert_records(records: &[FitDataRecord], session: &FITSession) -> Vec<FITRecord> {
+    let settings = Settings::current();
+    records
+        .par_iter()
+        .map(|data| {
+            let _settings = Settings::enter(Arc::clone(&settings));
+            FITRecord::from_fit_record(data.fields(), session)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+/// Tests for the activity module
 mod tests {
     use super::*;
+    use crate::{FITEnvironment, FITHrZones};
+    #[cfg(feature = "fs")]
     use assay::assay;
 
+    #[cfg(feature = "fs")]
     #[assay(include = ["/Users/evensolberg/Documents/Source/Rust/fitutils/data/rowing.fit"])]
     /// test FITActivity::from_file()
     fn test_from_file() {
@@ -463,4 +1795,244 @@ mod tests {
         assert!(!act.records.is_empty());
         assert_eq!(act.session.filename.unwrap(), filename.to_string());
     }
+
+    #[test]
+    /// Test reading FIT data from memory and writing the exports to memory
+    fn test_write_to_buffers() {
+        let bytes = std::fs::read("../data/rowing.fit").unwrap();
+        let act = FITActivity::from_reader(&mut bytes.as_slice(), "upload.fit").unwrap();
+
+        let mut records = Vec::new();
+        act.write_records_csv(&mut records).unwrap();
+        let records = String::from_utf8(records).unwrap();
+        assert!(records.starts_with("timestamp,duration_sec,"));
+        assert_eq!(records.lines().count(), act.records.len() + 1);
+
+        let mut laps = Vec::new();
+        act.write_laps_csv(&mut laps).unwrap();
+        assert_eq!(
+            String::from_utf8(laps).unwrap().lines().count(),
+            act.laps.len() + 1
+        );
+
+        let mut bundle = Vec::new();
+        act.write_bundle_json(&mut bundle).unwrap();
+        assert_eq!(
+            String::from_utf8(bundle).unwrap(),
+            act.bundle_json().unwrap()
+        );
+    }
+
+    #[test]
+    /// Test splitting chained FIT files into one activity each
+    fn test_chained() {
+        let single = std::fs::read("../data/rowing.fit").unwrap();
+        let chained = [single.clone(), single.clone()].concat();
+        let act = FITActivity::from_reader(&mut single.as_slice(), "upload.fit").unwrap();
+
+        let activities =
+            FITActivity::from_reader_chained(&mut chained.as_slice(), "upload.fit").unwrap();
+        assert_eq!(activities.len(), 2);
+        for (num, chained_act) in activities.iter().enumerate() {
+            assert_eq!(
+                chained_act.session.filename,
+                Some(format!("upload.file{}.fit", num + 1))
+            );
+            assert_eq!(chained_act.records.len(), act.records.len());
+            assert_eq!(chained_act.laps.len(), act.laps.len());
+            assert_eq!(chained_act.session.num_sessions, Some(1));
+        }
+
+        let first = FITActivity::from_reader(&mut chained.as_slice(), "upload.fit").unwrap();
+        assert_eq!(first.records.len(), act.records.len());
+        assert!(first.warnings.iter().any(|w| w.starts_with("2 chained")));
+
+        let single = FITActivity::from_reader_chained(&mut single.as_slice(), "upload.fit");
+        assert_eq!(
+            single.unwrap()[0].session.filename.as_deref(),
+            Some("upload.fit")
+        );
+    }
+
+    #[test]
+    /// Test placing the records in the laps by their timestamps for the GeoJSON
+    fn test_geojson() {
+        let start = Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap();
+        let records: Vec<FITRecord> = (0..6)
+            .map(|secs| FITRecord {
+                timestamp: Some(start + chrono::Duration::seconds(secs)),
+                lat: (secs != 1).then_some(59.0 + f64::from(u8::try_from(secs).unwrap()) / 1_000.0),
+                lon: Some(10.0),
+                ..FITRecord::default()
+            })
+            .collect();
+        let lap = |num: u64, from: i64, to: i64| FITLap {
+            lap_num: Some(num),
+            start_time: Some(start + chrono::Duration::seconds(from)),
+            finish_time: Some(start + chrono::Duration::seconds(to)),
+            ..FITLap::default()
+        };
+        let mut act = FITActivity {
+            records,
+            laps: vec![lap(1, 0, 2), lap(2, 3, 5)],
+            ..FITActivity::default()
+        };
+        act.session.activity_type = Some("running".to_string());
+
+        let geojson = act.geojson();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["properties"]["name"], "Lap 1");
+        assert_eq!(features[0]["properties"]["sport"], "running");
+        assert_eq!(
+            features[0]["geometry"]["coordinates"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            features[1]["geometry"]["coordinates"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        // A single line without laps
+        act.laps.clear();
+        assert_eq!(
+            act.geojson()["features"][0]["geometry"]["coordinates"]
+                .as_array()
+                .unwrap()
+                .len(),
+            5
+        );
+
+        // The coordinates of a virtual ride aren't on a real map
+        act.session.environment = FITEnvironment::Virtual;
+        assert!(act.geojson()["features"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    /// Test parsing the events and the devices, and writing them as CSV
+    fn test_events_and_devices() {
+        let bytes = std::fs::read("../data/faulty.fit").unwrap();
+        let act = FITActivity::from_reader(&mut bytes.as_slice(), "faulty.fit").unwrap();
+
+        assert_eq!(act.events.len(), 4);
+        assert_eq!(act.events[0].event.as_deref(), Some("timer"));
+        assert_eq!(act.events[0].event_type.as_deref(), Some("start"));
+        assert!(act
+            .events
+            .iter()
+            .all(|e| e.activity_uuid == act.session.uuid));
+
+        assert_eq!(act.devices.len(), 13);
+        assert!(act
+            .devices
+            .iter()
+            .any(|d| d.device_index.as_deref() == Some("creator")));
+
+        let mut events = Vec::new();
+        act.write_events_csv(&mut events).unwrap();
+        let events = String::from_utf8(events).unwrap();
+        assert!(events.starts_with("filename,timestamp,event,event_type,"));
+        assert_eq!(events.lines().count(), act.events.len() + 1);
+
+        let mut devices = Vec::new();
+        act.write_devices_csv(&mut devices).unwrap();
+        assert_eq!(
+            String::from_utf8(devices).unwrap().lines().count(),
+            act.devices.len() + 1
+        );
+    }
+
+    #[test]
+    /// Test that a single-session file gets one session with all the laps and records
+    fn test_single_session() {
+        let bytes = std::fs::read("../data/faulty.fit").unwrap();
+        let act = FITActivity::from_reader(&mut bytes.as_slice(), "faulty.fit").unwrap();
+
+        assert_eq!(act.sessions.len(), 1);
+        assert_eq!(act.sessions[0].session_num, Some(1));
+        assert_eq!(act.sessions[0].num_records, act.session.num_records);
+        assert_eq!(act.summary_sessions().len(), 1);
+        assert!(act.laps.iter().all(|lap| lap.session_num == Some(1)));
+        assert!(act.records.iter().all(|rec| rec.session_num == Some(1)));
+    }
+
+    #[test]
+    /// Test attaching laps and records to the sessions of a multisport file by time
+    fn test_session_finder() {
+        let start = Local.with_ymd_and_hms(2024, 6, 2, 8, 0, 0).unwrap();
+        let at = |mins: i64| Some(start + chrono::Duration::minutes(mins));
+        let sessions: Vec<FITSession> = [0, 30, 95]
+            .iter()
+            .zip(1_u16..)
+            .map(|(mins, num)| FITSession {
+                start_time: at(*mins),
+                session_num: Some(num),
+                ..FITSession::default()
+            })
+            .collect();
+
+        let mut finder = SessionFinder::new(&sessions);
+        assert_eq!(finder.session_num(at(0)), Some(1));
+        assert_eq!(finder.session_num(at(29)), Some(1));
+        assert_eq!(finder.session_num(at(30)), Some(2));
+        assert_eq!(finder.session_num(None), Some(2));
+        assert_eq!(finder.session_num(at(120)), Some(3));
+
+        // Records before the first session go with the first session, and files without sessions get none
+        assert_eq!(SessionFinder::new(&sessions).session_num(at(-1)), Some(1));
+        assert_eq!(SessionFinder::new(&[]).session_num(at(0)), None);
+    }
+
+    #[test]
+    /// Test working out the time in each heart rate zone for the laps without one recorded by the device
+    fn test_lap_hr_zones() {
+        let start = Local.with_ymd_and_hms(2024, 6, 2, 8, 0, 0).unwrap();
+        let at = |secs: i64| start + chrono::Duration::seconds(secs);
+        let records: Vec<FITRecord> = (0..=20)
+            .map(|secs| FITRecord {
+                timestamp: Some(at(secs)),
+                heartrate: Some(if secs < 10 { 100 } else { 180 }),
+                ..FITRecord::default()
+            })
+            .collect();
+        let recorded = FITHrZones {
+            hr_zone_0: Some(Duration::from_secs_f64(5.0)),
+            ..FITHrZones::default()
+        };
+        let mut laps = [
+            FITLap {
+                start_time: Some(at(0)),
+                finish_time: Some(at(20)),
+                ..FITLap::default()
+            },
+            FITLap {
+                start_time: Some(at(0)),
+                finish_time: Some(at(20)),
+                time_in_hr_zones: recorded,
+                ..FITLap::default()
+            },
+        ];
+
+        set_lap_hr_zones(&mut laps, &records);
+        assert_eq!(laps[0].time_in_hr_zones.secs()[0], Some(10.0));
+        assert_eq!(laps[0].time_in_hr_zones.secs()[4], Some(10.0));
+        assert_eq!(laps[1].time_in_hr_zones, recorded);
+    }
+
+    #[test]
+    /// Test that files cut short give an error or a partial activity rather than a panic
+    fn test_from_reader_truncated() {
+        let bytes = std::fs::read("../data/rowing.fit").unwrap();
+
+        for len in (0..bytes.len()).step_by(97) {
+            let _ = FITActivity::from_reader(&mut &bytes[..len], "truncated.fit");
+            let _ = crate::FITParseStats::from_bytes(&bytes[..len]);
+        }
+    }
 }
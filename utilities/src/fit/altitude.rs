@@ -0,0 +1,139 @@
+//! Defines the `AltitudeSource` enum used to choose between the `enhanced_altitude` and `altitude` fields in the FIT
+//! records. Many devices write both, and depending on the device one may come from the GPS and the other from the
+//! barometer, or one may be missing for parts of the activity.
+//!
+//! The preferred field is set once for the whole run using `Settings::set_altitude_source()`. The other field is used
+//! when the preferred one is missing from a record.
+
+use fitparser::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fit::constfunc::map_float64;
+use crate::Settings;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Which of the FIT altitude fields a record altitude was read from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AltitudeSource {
+    /// The `enhanced_altitude` field, which has a larger range than the `altitude` field.
+    #[default]
+    Enhanced,
+    /// The `altitude` field.
+    Standard,
+}
+
+impl AltitudeSource {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 2] = ["enhanced", "standard"];
+
+    /// The name of the field in the FIT record message.
+    #[must_use]
+    pub const fn field_name(self) -> &'static str {
+        match self {
+            Self::Enhanced => "enhanced_altitude",
+            Self::Standard => "altitude",
+        }
+    }
+
+    /// The other altitude field, used when this one is missing.
+    #[must_use]
+    pub const fn fallback(self) -> Self {
+        match self {
+            Self::Enhanced => Self::Standard,
+            Self::Standard => Self::Enhanced,
+        }
+    }
+
+    /// Reads the altitude from the record fields, trying this field first and then the other one.
+    ///
+    /// # Arguments
+    ///
+    /// `field_map: &HashMap<&str, &Value>` -- The fields of the record message.
+    ///
+    /// # Returns
+    ///
+    /// `Option<(f64, Self)>` -- The altitude in meters and the field it was read from, or `None` if the record has
+    /// neither.
+    #[must_use]
+    pub fn select(self, field_map: &HashMap<&str, &Value>) -> Option<(f64, Self)> {
+        [self, self.fallback()].into_iter().find_map(|source| {
+            field_map
+                .get(source.field_name())
+                .and_then(map_float64)
+                .map(|altitude| (altitude, source))
+        })
+    }
+}
+
+impl FromStr for AltitudeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "enhanced" | "enhanced_altitude" => Ok(Self::Enhanced),
+            "standard" | "altitude" => Ok(Self::Standard),
+            _ => Err(format!("Unknown altitude source {s}.")),
+        }
+    }
+}
+
+impl fmt::Display for AltitudeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Enhanced => write!(f, "enhanced"),
+            Self::Standard => write!(f, "standard"),
+        }
+    }
+}
+
+impl Settings {
+    /// Sets the altitude field preferred when reading the FIT records.
+    ///
+    /// # Arguments
+    ///
+    /// `source: AltitudeSource` -- The preferred field. The other field is used if a record doesn't have this one.
+    pub fn set_altitude_source(&mut self, source: AltitudeSource) {
+        self.altitude_source = source;
+    }
+}
+
+/// The altitude field currently preferred.
+pub(crate) fn altitude_source() -> AltitudeSource {
+    Settings::current().altitude_source
+}
+
+#[cfg(test)]
+/// Tests for the altitude module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test picking the altitude field with fallback
+    fn test_select() {
+        let enhanced = Value::Float64(152.4);
+        let standard = Value::Float64(150.0);
+        let both: HashMap<&str, &Value> =
+            HashMap::from([("enhanced_altitude", &enhanced), ("altitude", &standard)]);
+        let standard_only: HashMap<&str, &Value> = HashMap::from([("altitude", &standard)]);
+
+        assert_eq!(
+            AltitudeSource::Enhanced.select(&both),
+            Some((152.4, AltitudeSource::Enhanced))
+        );
+        assert_eq!(
+            AltitudeSource::Standard.select(&both),
+            Some((150.0, AltitudeSource::Standard))
+        );
+        assert_eq!(
+            AltitudeSource::Enhanced.select(&standard_only),
+            Some((150.0, AltitudeSource::Standard))
+        );
+        assert!(AltitudeSource::Enhanced.select(&HashMap::new()).is_none());
+        assert_eq!("altitude".parse(), Ok(AltitudeSource::Standard));
+        assert!("gps".parse::<AltitudeSource>().is_err());
+    }
+}
@@ -30,6 +30,85 @@ map_value!(map_sint32, i32, Value::SInt32(x) => *x);
 map_value!(map_float64, f64, Value::Float64(x) => *x);
 map_value!(map_string, String, Value::String(x) => x.to_string());
 
+/// Maps an enum value to its name. Values the FIT profile doesn't know are read as numbers, and are returned as such.
+pub fn map_text(v: &&Value) -> Option<String> {
+    match v {
+        Value::String(x) => Some(x.to_string()),
+        Value::Array(_) | Value::Timestamp(_) => None,
+        other => Some(other.to_string()),
+    }
+}
+
+/// Maps any integer value, whatever its size, to an `i64`.
+pub fn map_integer(v: &&Value) -> Option<i64> {
+    (*v).try_into().ok()
+}
+
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// Used in calculating latitudes and longitudes. You do not need to worry about this.
 pub const LATLON_MULTIPLIER: f64 = 180_f64 / (2_u32 << 30) as f64;
+
+/// Converts a latitude or longitude from semicircles, as FIT stores it, to degrees.
+///
+/// # Arguments
+///
+/// `semicircles: i32` -- The position in semicircles, where 2^31 semicircles make 180 degrees.
+///
+/// # Returns
+///
+/// `f64` -- The position in degrees. Converting it back with `degrees_to_semicircles()` gives the same semicircles.
+#[must_use]
+pub fn semicircles_to_degrees(semicircles: i32) -> f64 {
+    f64::from(semicircles) * LATLON_MULTIPLIER
+}
+
+/// Converts a latitude or longitude from degrees to semicircles, as FIT stores it, rounding to the nearest
+/// semicircle (about 9 mm at the equator). 180 degrees is the same meridian as -180, and is stored as such.
+///
+/// # Arguments
+///
+/// `degrees: f64` -- The position in degrees, from -180 to 180.
+///
+/// # Returns
+///
+/// `Option<i32>` -- The position in semicircles, or `None` if the degrees are out of range or not a number.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn degrees_to_semicircles(degrees: f64) -> Option<i32> {
+    if !degrees.is_finite() || degrees.abs() > 180.0 {
+        return None;
+    }
+    // Within the range checked, this is at most 2^31 and fits in an i64
+    let semicircles = (degrees / LATLON_MULTIPLIER).round() as i64;
+    Some(i32::try_from(semicircles).unwrap_or(i32::MIN))
+}
+
+#[cfg(test)]
+/// Tests for the constfunc module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test converting between semicircles and degrees, and that the semicircles survive the round trip
+    fn test_semicircles() {
+        assert_eq!(semicircles_to_degrees(1 << 30), 90.0);
+        assert_eq!(semicircles_to_degrees(i32::MIN), -180.0);
+        assert_eq!(degrees_to_semicircles(90.0), Some(1 << 30));
+        assert_eq!(degrees_to_semicircles(-180.0), Some(i32::MIN));
+        assert_eq!(degrees_to_semicircles(180.0), Some(i32::MIN));
+        assert_eq!(degrees_to_semicircles(180.1), None);
+        assert_eq!(degrees_to_semicircles(f64::NAN), None);
+
+        for semicircles in [0, 1, -1, 123_456_789, -987_654_321, i32::MAX, i32::MIN] {
+            assert_eq!(
+                degrees_to_semicircles(semicircles_to_degrees(semicircles)),
+                Some(semicircles)
+            );
+        }
+
+        // Degrees come back within half a semicircle
+        let oslo = 59.913_868_123_456;
+        let back = semicircles_to_degrees(degrees_to_semicircles(oslo).unwrap());
+        assert!((back - oslo).abs() <= LATLON_MULTIPLIER / 2.0);
+    }
+}
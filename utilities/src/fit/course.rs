@@ -0,0 +1,698 @@
+//! Defines the `FITCourse` struct, which holds a route to be navigated on a Garmin or Wahoo unit, and writes it as a
+//! FIT course file. A course file has the positions of the route as records, along with Course Point messages for the
+//! turns and climbs, which the units use for the turn-by-turn directions and the climb prompts.
+//!
+//! The turns are found where the direction changes, measured over `TURN_WINDOW_M` before and after each position so
+//! the GPS noise of a recorded track isn't taken for turns. The climbs are categorized by their length times their
+//! average grade in percent, as Strava does, so a fourth category climb gains at least 80 m.
+
+use chrono::{DateTime, Local, TimeDelta};
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+
+use crate::analysis::derived::{haversine_distance, smooth};
+use crate::analysis::track_stats::{climb, ELEVATION_SMOOTHING_WINDOW};
+use crate::fit::to_fit::{
+    fit_sport, fit_time, scaled, semicircles, BaseType, FitData, MessageType, FILE_ID,
+    MANUFACTURER_DEVELOPMENT,
+};
+
+/// The speed the times of the course are worked out from when the route has none, in meters per second (20 km/h).
+pub const DEFAULT_COURSE_SPEED_MS: f64 = 20.0 / 3.6;
+
+/// The distance before and after a position the direction is measured over, in meters.
+const TURN_WINDOW_M: f64 = 25.0;
+
+/// The smallest change of direction taken for a turn, in degrees.
+const MIN_TURN_DEG: f64 = 30.0;
+
+/// Changes of direction closer together than this are taken for the same turn, in meters.
+const MIN_TURN_SPACING_M: f64 = 50.0;
+
+/// A drop larger than this ends a climb, in meters.
+const CLIMB_DROP_M: f64 = 20.0;
+
+/// The smallest average grade of a climb, in percent.
+const MIN_CLIMB_GRADE_PCT: f64 = 3.0;
+
+/// The `file` type of a course file.
+const FILE_COURSE: i64 = 6;
+
+/// The `event` of the timer, and the `event_type` values starting and stopping it.
+const EVENT_TIMER: i64 = 0;
+const EVENT_TYPE_START: i64 = 0;
+const EVENT_TYPE_STOP_DISABLE_ALL: i64 = 9;
+
+/// Course: sport and name.
+const COURSE: MessageType = MessageType {
+    local: 1,
+    global: 31,
+    fields: &[(4, BaseType::Enum), (5, BaseType::String(32))],
+};
+
+/// Lap: timestamp, start time, start and end positions, elapsed and timer time, distance, ascent and descent.
+const COURSE_LAP: MessageType = MessageType {
+    local: 2,
+    global: 19,
+    fields: &[
+        (253, BaseType::Uint32),
+        (2, BaseType::Uint32),
+        (3, BaseType::Sint32),
+        (4, BaseType::Sint32),
+        (5, BaseType::Sint32),
+        (6, BaseType::Sint32),
+        (7, BaseType::Uint32),
+        (8, BaseType::Uint32),
+        (9, BaseType::Uint32),
+        (21, BaseType::Uint16),
+        (22, BaseType::Uint16),
+    ],
+};
+
+/// Event: timestamp, event, event type and event group.
+const EVENT: MessageType = MessageType {
+    local: 3,
+    global: 21,
+    fields: &[
+        (253, BaseType::Uint32),
+        (0, BaseType::Enum),
+        (1, BaseType::Enum),
+        (4, BaseType::Uint8),
+    ],
+};
+
+/// Record: timestamp, position, distance and altitude.
+const COURSE_RECORD: MessageType = MessageType {
+    local: 4,
+    global: 20,
+    fields: &[
+        (253, BaseType::Uint32),
+        (0, BaseType::Sint32),
+        (1, BaseType::Sint32),
+        (5, BaseType::Uint32),
+        (2, BaseType::Uint16),
+    ],
+};
+
+/// Course Point: message index, timestamp, position, distance, type and name.
+const COURSE_POINT: MessageType = MessageType {
+    local: 5,
+    global: 32,
+    fields: &[
+        (254, BaseType::Uint16),
+        (1, BaseType::Uint32),
+        (2, BaseType::Sint32),
+        (3, BaseType::Sint32),
+        (4, BaseType::Uint32),
+        (5, BaseType::Enum),
+        (6, BaseType::String(16)),
+    ],
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The kinds of course points written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoursePointType {
+    /// The top of a climb.
+    Summit,
+
+    /// The start of a climb gaining 80 m or more.
+    FourthCategory,
+
+    /// The start of a climb gaining 160 m or more.
+    ThirdCategory,
+
+    /// The start of a climb gaining 320 m or more.
+    SecondCategory,
+
+    /// The start of a climb gaining 640 m or more.
+    FirstCategory,
+
+    /// The start of a climb gaining 800 m or more.
+    HorsCategory,
+
+    /// A turn of 30 to 60 degrees to the left.
+    SlightLeft,
+
+    /// A turn of 60 to 120 degrees to the left.
+    Left,
+
+    /// A turn of 120 to 160 degrees to the left.
+    SharpLeft,
+
+    /// A turn of 30 to 60 degrees to the right.
+    SlightRight,
+
+    /// A turn of 60 to 120 degrees to the right.
+    Right,
+
+    /// A turn of 120 to 160 degrees to the right.
+    SharpRight,
+
+    /// A turn of more than 160 degrees either way.
+    UTurn,
+}
+
+impl CoursePointType {
+    /// The number of the type in the FIT profile.
+    #[must_use]
+    pub const fn fit_type(self) -> u8 {
+        match self {
+            Self::Summit => 1,
+            Self::Left => 6,
+            Self::Right => 7,
+            Self::FourthCategory => 10,
+            Self::ThirdCategory => 11,
+            Self::SecondCategory => 12,
+            Self::FirstCategory => 13,
+            Self::HorsCategory => 14,
+            Self::SlightLeft => 19,
+            Self::SharpLeft => 20,
+            Self::SlightRight => 21,
+            Self::SharpRight => 22,
+            Self::UTurn => 23,
+        }
+    }
+
+    /// Whether the course point is a turn rather than part of a climb.
+    #[must_use]
+    pub const fn is_turn(self) -> bool {
+        !matches!(
+            self,
+            Self::Summit
+                | Self::FourthCategory
+                | Self::ThirdCategory
+                | Self::SecondCategory
+                | Self::FirstCategory
+                | Self::HorsCategory
+        )
+    }
+
+    /// The turn for a change of direction, or `None` if it's too small to be a turn.
+    ///
+    /// # Arguments
+    ///
+    /// `change: f64` -- The change of direction in degrees, positive to the right.
+    fn of_turn(change: f64) -> Option<Self> {
+        let right = change > 0.0;
+        match change.abs() {
+            a if a < MIN_TURN_DEG => None,
+            a if a < 60.0 => Some(if right {
+                Self::SlightRight
+            } else {
+                Self::SlightLeft
+            }),
+            a if a < 120.0 => Some(if right { Self::Right } else { Self::Left }),
+            a if a < 160.0 => Some(if right {
+                Self::SharpRight
+            } else {
+                Self::SharpLeft
+            }),
+            _ => Some(Self::UTurn),
+        }
+    }
+
+    /// The category of a climb, or `None` if it's too small to be categorized.
+    ///
+    /// # Arguments
+    ///
+    /// `score: f64` -- The length of the climb in meters times its average grade in percent.
+    fn of_climb(score: f64) -> Option<Self> {
+        match score {
+            s if s >= 80_000.0 => Some(Self::HorsCategory),
+            s if s >= 64_000.0 => Some(Self::FirstCategory),
+            s if s >= 32_000.0 => Some(Self::SecondCategory),
+            s if s >= 16_000.0 => Some(Self::ThirdCategory),
+            s if s >= 8_000.0 => Some(Self::FourthCategory),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CoursePointType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Summit => "Summit",
+            Self::FourthCategory => "Cat 4",
+            Self::ThirdCategory => "Cat 3",
+            Self::SecondCategory => "Cat 2",
+            Self::FirstCategory => "Cat 1",
+            Self::HorsCategory => "HC",
+            Self::SlightLeft => "Slight left",
+            Self::Left => "Left",
+            Self::SharpLeft => "Sharp left",
+            Self::SlightRight => "Slight right",
+            Self::Right => "Right",
+            Self::SharpRight => "Sharp right",
+            Self::UTurn => "U-turn",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A position along the course.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoursePosition {
+    /// Latitude in degrees.
+    pub latitude: f64,
+
+    /// Longitude in degrees.
+    pub longitude: f64,
+
+    /// Elevation in meters.
+    pub elevation: Option<f64>,
+
+    /// When the position was passed, for courses made from a recorded track.
+    pub time: Option<DateTime<Local>>,
+
+    /// The name of the position, e.g. of a route point. Used as the name of a turn there.
+    pub name: Option<String>,
+
+    /// The distance from the start of the course in meters. Worked out by `FITCourse::new()`.
+    pub distance: f64,
+}
+
+/// A turn or climb along the course.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoursePoint {
+    /// The index of the position of the course point in `FITCourse::positions`.
+    pub position: usize,
+
+    /// The kind of course point.
+    pub point_type: CoursePointType,
+
+    /// The name shown by the unit, e.g. `Left` or `Cat 3 4.2km 6%`.
+    pub name: String,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A route to be navigated, with the turns and climbs along it.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct FITCourse {
+    /// The name of the course, as listed on the unit.
+    pub name: String,
+
+    /// The sport of the course, e.g. `cycling`.
+    pub sport: Option<String>,
+
+    /// The positions along the course, in order.
+    pub positions: Vec<CoursePosition>,
+
+    /// The turns and climbs, in the order they come along the course.
+    pub course_points: Vec<CoursePoint>,
+}
+
+impl FITCourse {
+    /// Creates a course from its positions, working out the distances and finding the turns and climbs.
+    ///
+    /// # Arguments
+    ///
+    /// - `name: &str` -- The name of the course.
+    /// - `sport: Option<&str>` -- The sport of the course, e.g. `cycling` or `running`.
+    /// - `positions: Vec<CoursePosition>` -- The positions along the course. Their distances are worked out here.
+    #[must_use]
+    pub fn new(name: &str, sport: Option<&str>, mut positions: Vec<CoursePosition>) -> Self {
+        let mut distance = 0.0;
+        for i in 0..positions.len() {
+            if i > 0 {
+                distance +=
+                    haversine_distance(position_of(&positions[i - 1]), position_of(&positions[i]));
+            }
+            positions[i].distance = distance;
+        }
+
+        let mut course_points = turns(&positions);
+        course_points.extend(climbs(&positions));
+        course_points.sort_by_key(|point| point.position);
+
+        Self {
+            name: name.to_string(),
+            sport: sport.map(ToString::to_string),
+            positions,
+            course_points,
+        }
+    }
+
+    /// The length of the course in meters.
+    #[must_use]
+    pub fn distance(&self) -> f64 {
+        self.positions
+            .last()
+            .map_or(0.0, |position| position.distance)
+    }
+
+    /// Writes the course as a FIT course file to any writer: the course, a single lap, the positions as records and
+    /// the turns and climbs as course points. Where the positions don't all have a time, e.g. for a planned route,
+    /// the times are worked out from the speed given, starting at the first time found or now.
+    ///
+    /// # Arguments
+    ///
+    /// - `writer: W` -- Where the FIT file is written.
+    /// - `speed: f64` -- The speed the times are worked out from, in meters per second. See `DEFAULT_COURSE_SPEED_MS`.
+    ///
+    /// # Errors
+    ///
+    /// The course may have fewer than two positions, or the speed may not be above zero. Writing may fail, or the
+    /// course may be too large for a FIT file.
+    pub fn write_fit<W: Write>(&self, mut writer: W, speed: f64) -> Result<(), Box<dyn Error>> {
+        if self.positions.len() < 2 {
+            return Err("The course needs at least two positions.".into());
+        }
+        if speed.is_nan() || speed <= 0.0 {
+            return Err(format!("The course speed must be above zero, not {speed}.").into());
+        }
+
+        let times = self.times(speed);
+        let (first, last) = (
+            &self.positions[0],
+            &self.positions[self.positions.len() - 1],
+        );
+        let (start, finish) = (times[0], times[times.len() - 1]);
+        let elapsed = (finish - start).num_milliseconds() as f64 / 1_000.0;
+        let elevations: Vec<f64> = self
+            .positions
+            .iter()
+            .filter_map(|position| position.elevation)
+            .collect();
+        let (ascent, descent) = climb(&elevations);
+
+        let mut data = FitData::default();
+        data.write(
+            &FILE_ID,
+            &[
+                Some(FILE_COURSE),
+                Some(MANUFACTURER_DEVELOPMENT),
+                Some(0),
+                fit_time(Some(start)),
+            ],
+        );
+        data.write_named(
+            &COURSE,
+            &[Some(fit_sport(self.sport.as_deref()))],
+            &self.name,
+        );
+        data.write(
+            &COURSE_LAP,
+            &[
+                fit_time(Some(finish)),
+                fit_time(Some(start)),
+                semicircles(Some(first.latitude)),
+                semicircles(Some(first.longitude)),
+                semicircles(Some(last.latitude)),
+                semicircles(Some(last.longitude)),
+                scaled(Some(elapsed), 1_000.0, 0.0),
+                scaled(Some(elapsed), 1_000.0, 0.0),
+                scaled(Some(last.distance), 100.0, 0.0),
+                scaled(ascent, 1.0, 0.0),
+                scaled(descent, 1.0, 0.0),
+            ],
+        );
+        data.write(
+            &EVENT,
+            &[
+                fit_time(Some(start)),
+                Some(EVENT_TIMER),
+                Some(EVENT_TYPE_START),
+                Some(0),
+            ],
+        );
+        for (position, time) in self.positions.iter().zip(&times) {
+            data.write(
+                &COURSE_RECORD,
+                &[
+                    fit_time(Some(*time)),
+                    semicircles(Some(position.latitude)),
+                    semicircles(Some(position.longitude)),
+                    scaled(Some(position.distance), 100.0, 0.0),
+                    scaled(position.elevation, 5.0, 500.0),
+                ],
+            );
+        }
+        data.write(
+            &EVENT,
+            &[
+                fit_time(Some(finish)),
+                Some(EVENT_TIMER),
+                Some(EVENT_TYPE_STOP_DISABLE_ALL),
+                Some(0),
+            ],
+        );
+        for (index, point) in self.course_points.iter().enumerate() {
+            let position = &self.positions[point.position];
+            data.write_named(
+                &COURSE_POINT,
+                &[
+                    i64::try_from(index).ok(),
+                    fit_time(Some(times[point.position])),
+                    semicircles(Some(position.latitude)),
+                    semicircles(Some(position.longitude)),
+                    scaled(Some(position.distance), 100.0, 0.0),
+                    Some(i64::from(point.point_type.fit_type())),
+                ],
+                &point.name,
+            );
+        }
+
+        writer.write_all(&data.into_file()?)?;
+        Ok(())
+    }
+
+    /// The time each position is passed: the times of the positions if they all have one, otherwise worked out from
+    /// the distance and the speed.
+    fn times(&self, speed: f64) -> Vec<DateTime<Local>> {
+        if let Some(times) = self
+            .positions
+            .iter()
+            .map(|position| position.time)
+            .collect::<Option<Vec<_>>>()
+        {
+            return times;
+        }
+
+        let start = self
+            .positions
+            .iter()
+            .find_map(|position| position.time)
+            .unwrap_or_else(Local::now);
+        #[allow(clippy::cast_possible_truncation)]
+        self.positions
+            .iter()
+            .map(|position| {
+                start + TimeDelta::milliseconds((position.distance / speed * 1_000.0) as i64)
+            })
+            .collect()
+    }
+}
+
+/// The latitude and longitude of a position.
+const fn position_of(position: &CoursePosition) -> (f64, f64) {
+    (position.latitude, position.longitude)
+}
+
+/// The direction from one position to another in degrees, clockwise from north.
+fn bearing(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let delta_lon = (to.1 - from.1).to_radians();
+    let y = delta_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * delta_lon.cos();
+    y.atan2(x).to_degrees()
+}
+
+/// Finds the turns along the course. The direction into each position is measured from `TURN_WINDOW_M` before it,
+/// and the direction out of it to `TURN_WINDOW_M` after it. Of the changes closer together than `MIN_TURN_SPACING_M`,
+/// only the largest is kept.
+fn turns(positions: &[CoursePosition]) -> Vec<CoursePoint> {
+    let mut changes: Vec<(usize, f64)> = Vec::new();
+    let (mut before, mut after) = (0, 0);
+    for i in 1..positions.len().saturating_sub(1) {
+        let distance = positions[i].distance;
+        while positions[before + 1].distance <= distance - TURN_WINDOW_M {
+            before += 1;
+        }
+        after = after.max(i + 1);
+        while after < positions.len() - 1 && positions[after].distance < distance + TURN_WINDOW_M {
+            after += 1;
+        }
+        if distance - positions[before].distance < 1.0 || positions[after].distance - distance < 1.0
+        {
+            continue;
+        }
+
+        let here = position_of(&positions[i]);
+        let change = bearing(here, position_of(&positions[after]))
+            - bearing(position_of(&positions[before]), here);
+        let change = (change + 540.0).rem_euclid(360.0) - 180.0;
+        if change.abs() < MIN_TURN_DEG {
+            continue;
+        }
+
+        match changes.last_mut() {
+            Some(last) if distance - positions[last.0].distance < MIN_TURN_SPACING_M => {
+                if change.abs() > last.1.abs() {
+                    *last = (i, change);
+                }
+            }
+            _ => changes.push((i, change)),
+        }
+    }
+
+    changes
+        .into_iter()
+        .filter_map(|(i, change)| {
+            let point_type = CoursePointType::of_turn(change)?;
+            Some(CoursePoint {
+                position: i,
+                point_type,
+                name: positions[i]
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| point_type.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Finds the categorized climbs along the course, with a course point at the start of each and one at the top. A
+/// climb runs from the lowest point before it to the highest, and ends where the elevation drops more than
+/// `CLIMB_DROP_M` below the highest.
+fn climbs(positions: &[CoursePosition]) -> Vec<CoursePoint> {
+    let (indexes, elevations): (Vec<usize>, Vec<f64>) = positions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, position)| Some((i, position.elevation?)))
+        .unzip();
+    if elevations.len() < 2 {
+        return Vec::new();
+    }
+    let elevations = smooth(&elevations, ELEVATION_SMOOTHING_WINDOW);
+
+    let mut found = Vec::new();
+    let (mut start, mut top) = (0, 0);
+    for i in 1..elevations.len() {
+        if elevations[i] > elevations[top] {
+            top = i;
+        } else if elevations[top] - elevations[i] > CLIMB_DROP_M {
+            found.push((start, top));
+            (start, top) = (i, i);
+        }
+        if elevations[i] < elevations[start] {
+            (start, top) = (i, i);
+        }
+    }
+    found.push((start, top));
+
+    let mut points = Vec::new();
+    for (start, top) in found {
+        let gain = elevations[top] - elevations[start];
+        let length = positions[indexes[top]].distance - positions[indexes[start]].distance;
+        if length <= 0.0 {
+            continue;
+        }
+        let grade = gain / length * 100.0;
+        let Some(category) = CoursePointType::of_climb(length * grade) else {
+            continue;
+        };
+        if grade < MIN_CLIMB_GRADE_PCT {
+            continue;
+        }
+
+        points.push(CoursePoint {
+            position: indexes[start],
+            point_type: category,
+            name: format!("{category} {:.1}km {grade:.0}%", length / 1_000.0),
+        });
+        points.push(CoursePoint {
+            position: indexes[top],
+            point_type: CoursePointType::Summit,
+            name: format!("Summit {:.0}m", elevations[top]),
+        });
+    }
+    points
+}
+
+#[cfg(test)]
+/// Tests for the course module
+mod tests {
+    use super::*;
+    use fitparser::profile::field_types::MesgNum;
+
+    /// The meters per degree of latitude.
+    const DEGREE_M: f64 = 111_195.0;
+
+    /// A position `north_m` and `east_m` meters from a point on the equator.
+    fn position(north_m: f64, east_m: f64, elevation: f64) -> CoursePosition {
+        CoursePosition {
+            latitude: north_m / DEGREE_M,
+            longitude: east_m / DEGREE_M,
+            elevation: Some(elevation),
+            ..CoursePosition::default()
+        }
+    }
+
+    /// A course going 1 km north, climbing 100 m, then turning right and going 1 km east on the flat.
+    fn course() -> FITCourse {
+        let north = (0..=100).map(|i| position(f64::from(i) * 10.0, 0.0, f64::from(i)));
+        let east = (1..=100).map(|i| position(1_000.0, f64::from(i) * 10.0, 100.0));
+        FITCourse::new("Test course", Some("cycling"), north.chain(east).collect())
+    }
+
+    #[test]
+    /// Test finding the turns and climbs
+    fn test_course_points() {
+        let course = course();
+        assert!((course.distance() - 2_000.0).abs() < 1.0);
+
+        let types: Vec<CoursePointType> = course
+            .course_points
+            .iter()
+            .map(|point| point.point_type)
+            .collect();
+        assert_eq!(
+            types,
+            [
+                CoursePointType::FourthCategory,
+                CoursePointType::Right,
+                CoursePointType::Summit
+            ]
+        );
+        assert_eq!(course.course_points[1].position, 100);
+        assert_eq!(course.course_points[0].name, "Cat 4 1.0km 10%");
+
+        assert_eq!(
+            CoursePointType::of_turn(-45.0),
+            Some(CoursePointType::SlightLeft)
+        );
+        assert_eq!(
+            CoursePointType::of_turn(170.0),
+            Some(CoursePointType::UTurn)
+        );
+        assert_eq!(CoursePointType::of_turn(10.0), None);
+        assert!((bearing((0.0, 0.0), (0.0, 1.0)) - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    /// Test that the course written can be read back with its records and course points
+    fn test_write_fit() {
+        let course = course();
+        let mut fit = Vec::new();
+        course.write_fit(&mut fit, DEFAULT_COURSE_SPEED_MS).unwrap();
+
+        let messages = fitparser::from_bytes(&fit).unwrap();
+        let count = |kind: MesgNum| messages.iter().filter(|m| m.kind() == kind).count();
+        assert_eq!(count(MesgNum::Course), 1);
+        assert_eq!(count(MesgNum::Record), course.positions.len());
+        assert_eq!(count(MesgNum::CoursePoint), 3);
+
+        let name = messages
+            .iter()
+            .find(|m| m.kind() == MesgNum::Course)
+            .and_then(|m| m.fields().iter().find(|f| f.name() == "name"))
+            .map(|f| f.value().to_string());
+        assert_eq!(name.as_deref(), Some("Test course"));
+
+        assert!(FITCourse::default().write_fit(Vec::new(), 5.0).is_err());
+        assert!(course.write_fit(Vec::new(), 0.0).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! Defines the `FITDeviceInfo` struct which holds information about the device that recorded the workout and the
+//! sensors connected to it, and associated functions.
+
+use crate::fit::constfunc::{map_float64, map_integer, map_string, map_text, map_uint8};
+use crate::fit::products::product_name;
+use crate::privacy::serialize_serial;
+use crate::FITSession;
+
+use chrono::{DateTime, Local};
+use fitparser::FitDataField;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A device taking part in the workout: the device recording it (`device_index` is `creator`), or one of its built-in
+/// or connected sensors.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct FITDeviceInfo {
+    /// The name of the .FIT file in which the device information is found.
+    #[serde(rename = "filename")]
+    pub filename: Option<String>,
+
+    /// When the device information was recorded.
+    #[serde(rename = "timestamp")]
+    pub timestamp: Option<DateTime<Local>>,
+
+    /// The device number within the file. `creator` is the device that recorded the workout.
+    #[serde(rename = "device_index")]
+    pub device_index: Option<String>,
+
+    /// The kind of device, e.g. `heart_rate`, `barometer` or `bike_power`.
+    #[serde(rename = "device_type")]
+    pub device_type: Option<String>,
+
+    /// The manufacturer of the device.
+    #[serde(rename = "manufacturer")]
+    pub manufacturer: Option<String>,
+
+    /// The product name of the device.
+    #[serde(rename = "product")]
+    pub product: Option<String>,
+
+    /// The serial number of the device.
+    #[serde(rename = "serial_number", serialize_with = "serialize_serial")]
+    pub serial_number: Option<String>,
+
+    /// The firmware version.
+    #[serde(rename = "software_version")]
+    pub software_version: Option<f64>,
+
+    /// The hardware version.
+    #[serde(rename = "hardware_version")]
+    pub hardware_version: Option<u8>,
+
+    /// The battery voltage (Volts).
+    #[serde(rename = "battery_voltage_v")]
+    pub battery_voltage: Option<f64>,
+
+    /// The battery status, e.g. `good` or `low`.
+    #[serde(rename = "battery_status")]
+    pub battery_status: Option<String>,
+
+    /// The total time the device has been in use (Seconds).
+    #[serde(rename = "operating_time_sec")]
+    pub operating_time: Option<i64>,
+
+    /// How the device is connected: `local` (built in), `antplus`, `bluetooth_low_energy`, etc.
+    #[serde(rename = "source_type")]
+    pub source_type: Option<String>,
+
+    /// A description of the device, if the device gives one.
+    #[serde(rename = "descriptor")]
+    pub descriptor: Option<String>,
+
+    /// The ANT device number of a connected sensor.
+    #[serde(rename = "ant_device_number")]
+    pub ant_device_number: Option<i64>,
+
+    /// The UUID of the activity the device took part in.
+    #[serde(rename = "activity_uuid")]
+    pub activity_uuid: Option<Uuid>,
+}
+
+impl FITDeviceInfo {
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Parses a device information message into a new `FITDeviceInfo`.
+    ///
+    /// # Parameters
+    ///
+    /// - `fields: &[FitDataField]` -- The fields of the device information message.
+    /// - `session: &FITSession` -- Session summary information. Only used to get the file name.
+    ///
+    /// # Returns
+    ///
+    /// `Self` -- The device information, with the fields that were found filled in.
+    pub fn from_fit_device_info(fields: &[FitDataField], session: &FITSession) -> Self {
+        let field_map: HashMap<&str, &fitparser::Value> =
+            fields.iter().map(|x| (x.name(), x.value())).collect();
+
+        let timestamp = match field_map.get("timestamp") {
+            Some(fitparser::Value::Timestamp(ts)) => Some(*ts),
+            _ => None,
+        };
+        let manufacturer = field_map.get("manufacturer").and_then(map_text);
+
+        // The device type is in a different field depending on how the device is connected
+        let device_type = [
+            "local_device_type",
+            "antplus_device_type",
+            "ble_device_type",
+            "device_type",
+        ]
+        .iter()
+        .find_map(|name| field_map.get(name).and_then(map_text));
+
+        let product = product_name(
+            manufacturer.as_deref(),
+            ["product", "garmin_product", "favero_product"]
+                .iter()
+                .find_map(|name| field_map.get(name))
+                .copied(),
+        )
+        .or_else(|| field_map.get("product_name").and_then(map_string));
+
+        Self {
+            filename: session.filename.clone(),
+            timestamp,
+            device_index: field_map.get("device_index").and_then(map_text),
+            device_type,
+            manufacturer,
+            product,
+            serial_number: field_map.get("serial_number").and_then(|value| {
+                map_string(value).or_else(|| Some(map_integer(value)?.to_string()))
+            }),
+            software_version: field_map.get("software_version").and_then(map_float64),
+            hardware_version: field_map.get("hardware_version").and_then(map_uint8),
+            battery_voltage: field_map.get("battery_voltage").and_then(map_float64),
+            battery_status: field_map.get("battery_status").and_then(map_text),
+            operating_time: field_map.get("cum_operating_time").and_then(map_integer),
+            source_type: field_map.get("source_type").and_then(map_text),
+            descriptor: field_map.get("descriptor").and_then(map_string),
+            ant_device_number: field_map.get("ant_device_number").and_then(map_integer),
+            activity_uuid: None,
+        }
+    }
+}
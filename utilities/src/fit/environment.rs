@@ -0,0 +1,116 @@
+//! Defines the `Environment` enum which tells where an activity took place, based on the FIT `sub_sport`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The `sub_sport` values for activities done on a trainer, treadmill or similar, or in a pool.
+const INDOOR_SUB_SPORTS: [&str; 5] = [
+    "treadmill",
+    "spin",
+    "elliptical",
+    "stair_climbing",
+    "lap_swimming",
+];
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Where the activity took place, based on the `sub_sport` of the session.
+///
+/// Indoor activities (e.g. trainer rides and treadmill runs) have no GPS track, while virtual activities
+/// (e.g. Zwift rides) have GPS coordinates from a virtual world which don't belong on a real map.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+#[allow(clippy::module_name_repetitions)]
+pub enum FITEnvironment {
+    /// Outdoors, or nothing in the file says otherwise.
+    #[default]
+    Outdoor,
+    /// On a trainer, treadmill, rowing machine, in a pool, etc.
+    Indoor,
+    /// In a virtual world, such as Zwift.
+    Virtual,
+}
+
+impl FITEnvironment {
+    /// Works out the environment from the FIT `sub_sport` value, e.g. `indoor_cycling` or `virtual_activity`.
+    ///
+    /// # Arguments
+    ///
+    /// `sub_sport: &str` -- The `sub_sport` value as read from the FIT file.
+    ///
+    /// # Returns
+    ///
+    /// `Self` -- `Outdoor` if the `sub_sport` doesn't say otherwise.
+    #[must_use]
+    pub fn from_sub_sport(sub_sport: &str) -> Self {
+        let sub_sport = sub_sport.to_lowercase().replace(' ', "_");
+
+        if sub_sport == "virtual_activity" {
+            Self::Virtual
+        } else if sub_sport.starts_with("indoor_")
+            || INDOOR_SUB_SPORTS.contains(&sub_sport.as_str())
+        {
+            Self::Indoor
+        } else {
+            Self::Outdoor
+        }
+    }
+
+    /// Whether the GPS coordinates of the activity, if any, are real-world locations that can be put on a map.
+    #[must_use]
+    pub const fn has_real_location(self) -> bool {
+        matches!(self, Self::Outdoor)
+    }
+}
+
+impl fmt::Display for FITEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Outdoor => write!(f, "Outdoor"),
+            Self::Indoor => write!(f, "Indoor"),
+            Self::Virtual => write!(f, "Virtual"),
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests for the environment module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the mapping from sub_sport
+    fn test_from_sub_sport() {
+        assert_eq!(
+            FITEnvironment::from_sub_sport("virtual_activity"),
+            FITEnvironment::Virtual
+        );
+        assert_eq!(
+            FITEnvironment::from_sub_sport("indoor_cycling"),
+            FITEnvironment::Indoor
+        );
+        assert_eq!(
+            FITEnvironment::from_sub_sport("Indoor Rowing"),
+            FITEnvironment::Indoor
+        );
+        assert_eq!(
+            FITEnvironment::from_sub_sport("treadmill"),
+            FITEnvironment::Indoor
+        );
+        assert_eq!(
+            FITEnvironment::from_sub_sport("road"),
+            FITEnvironment::Outdoor
+        );
+        assert_eq!(
+            FITEnvironment::from_sub_sport("unknown"),
+            FITEnvironment::Outdoor
+        );
+    }
+
+    #[test]
+    /// Test which environments have real-world locations
+    fn test_has_real_location() {
+        assert!(FITEnvironment::Outdoor.has_real_location());
+        assert!(!FITEnvironment::Indoor.has_real_location());
+        assert!(!FITEnvironment::Virtual.has_real_location());
+    }
+}
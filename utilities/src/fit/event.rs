@@ -0,0 +1,138 @@
+//! Defines the `FITEvent` struct which holds the events of the workout, such as the timer being started and stopped or
+//! gear shifts, and associated functions.
+
+use crate::fit::constfunc::{map_integer, map_text};
+use crate::FITSession;
+
+use chrono::{DateTime, Local};
+use fitparser::FitDataField;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Something that happened during the workout, e.g. the timer being stopped by auto-pause or a gear shift.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct FITEvent {
+    /// The name of the .FIT file in which the event is found.
+    #[serde(rename = "filename")]
+    pub filename: Option<String>,
+
+    /// When the event happened.
+    #[serde(rename = "timestamp")]
+    pub timestamp: Option<DateTime<Local>>,
+
+    /// What the event is about, e.g. `timer`, `session` or `rear_gear_change`.
+    #[serde(rename = "event")]
+    pub event: Option<String>,
+
+    /// The kind of event, e.g. `start`, `stop`, `stop_all` or `marker`.
+    #[serde(rename = "event_type")]
+    pub event_type: Option<String>,
+
+    /// What started or stopped the timer: `manual`, `auto` (auto-pause) or `fitness_equipment`.
+    #[serde(rename = "timer_trigger")]
+    pub timer_trigger: Option<String>,
+
+    /// The data of the event. Its meaning depends on the event.
+    #[serde(rename = "data")]
+    pub data: Option<i64>,
+
+    /// The group of the event.
+    #[serde(rename = "event_group")]
+    pub event_group: Option<i64>,
+
+    /// The number of the front gear after a gear shift, counting from the inside.
+    #[serde(rename = "front_gear_num")]
+    pub front_gear_num: Option<i64>,
+
+    /// The number of teeth on the front gear after a gear shift.
+    #[serde(rename = "front_gear")]
+    pub front_gear: Option<i64>,
+
+    /// The number of the rear gear after a gear shift, counting from the outside.
+    #[serde(rename = "rear_gear_num")]
+    pub rear_gear_num: Option<i64>,
+
+    /// The number of teeth on the rear gear after a gear shift.
+    #[serde(rename = "rear_gear")]
+    pub rear_gear: Option<i64>,
+
+    /// The UUID of the activity the event belongs to.
+    #[serde(rename = "activity_uuid")]
+    pub activity_uuid: Option<Uuid>,
+}
+
+impl FITEvent {
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Parses an event message into a new `FITEvent`.
+    ///
+    /// # Parameters
+    ///
+    /// - `fields: &[FitDataField]` -- The fields of the event message.
+    /// - `session: &FITSession` -- Session summary information. Only used to get the file name.
+    ///
+    /// # Returns
+    ///
+    /// `Self` -- The event, with the fields that were found filled in.
+    pub fn from_fit_event(fields: &[FitDataField], session: &FITSession) -> Self {
+        let field_map: HashMap<&str, &fitparser::Value> =
+            fields.iter().map(|x| (x.name(), x.value())).collect();
+
+        let timestamp = match field_map.get("timestamp") {
+            Some(fitparser::Value::Timestamp(ts)) => Some(*ts),
+            _ => None,
+        };
+
+        Self {
+            filename: session.filename.clone(),
+            timestamp,
+            event: field_map.get("event").and_then(map_text),
+            event_type: field_map.get("event_type").and_then(map_text),
+            timer_trigger: field_map.get("timer_trigger").and_then(map_text),
+            data: field_map.get("data").and_then(map_integer),
+            event_group: field_map.get("event_group").and_then(map_integer),
+            front_gear_num: field_map.get("front_gear_num").and_then(map_integer),
+            front_gear: field_map.get("front_gear").and_then(map_integer),
+            rear_gear_num: field_map.get("rear_gear_num").and_then(map_integer),
+            rear_gear: field_map.get("rear_gear").and_then(map_integer),
+            activity_uuid: None,
+        }
+    }
+
+    /// Whether the event is the timer being stopped by auto-pause.
+    #[must_use]
+    pub fn is_auto_pause(&self) -> bool {
+        self.event.as_deref() == Some("timer")
+            && self.timer_trigger.as_deref() == Some("auto")
+            && self
+                .event_type
+                .as_deref()
+                .is_some_and(|event_type| event_type.starts_with("stop"))
+    }
+}
+
+#[cfg(test)]
+/// Tests for the event module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test telling auto-pause apart from the other timer events
+    fn test_is_auto_pause() {
+        let event = |event: &str, event_type: &str, trigger: &str| FITEvent {
+            event: Some(event.to_string()),
+            event_type: Some(event_type.to_string()),
+            timer_trigger: Some(trigger.to_string()),
+            ..FITEvent::default()
+        };
+
+        assert!(event("timer", "stop_all", "auto").is_auto_pause());
+        assert!(event("timer", "stop", "auto").is_auto_pause());
+        assert!(!event("timer", "start", "auto").is_auto_pause());
+        assert!(!event("timer", "stop_all", "manual").is_auto_pause());
+        assert!(!event("session", "stop", "auto").is_auto_pause());
+    }
+}
@@ -7,6 +7,9 @@ use serde::{
 
 use crate::Duration;
 
+/// The names of the heart rate zones, from zone 0 up.
+pub const HR_ZONE_NAMES: [&str; 5] = ["Warmup", "Fat Burn", "Aerobic", "Anaerobic", "Speed/Power"];
+
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// Detailed information about how much time is spent in each heart rate zone.
 ///
@@ -24,7 +27,7 @@ use crate::Duration;
 /// # References
 ///
 /// <https://www.heart.org/en/healthy-living/fitness/fitness-basics/target-heart-rates><br>
-#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
 #[serde(default)]
 pub struct FITHrZones {
     /// Time spent in Heart Rate zone 0 (Warmup).
@@ -102,6 +105,19 @@ impl FITHrZones {
         // return it
         hr_zones
     }
+
+    /// The time spent in each zone in seconds, from zone 0 up.
+    #[must_use]
+    pub fn secs(&self) -> [Option<f64>; 5] {
+        [
+            self.hr_zone_0,
+            self.hr_zone_1,
+            self.hr_zone_2,
+            self.hr_zone_3,
+            self.hr_zone_4,
+        ]
+        .map(|zone| zone.map(|duration| duration.0.as_secs_f64()))
+    }
 }
 
 impl Serialize for FITHrZones {
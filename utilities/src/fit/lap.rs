@@ -1,6 +1,8 @@
 //! Defines the `Lap` struct which contains summary information per lap, and associated functions.
 
-use crate::fit::constfunc::{map_float64, map_sint32, map_uint16, map_uint8, LATLON_MULTIPLIER};
+use crate::fit::constfunc::{
+    map_float64, map_sint32, map_uint16, map_uint8, semicircles_to_degrees,
+};
 use crate::{Duration, FITHrZones, FITSession};
 
 use chrono::{DateTime, Local};
@@ -13,6 +15,7 @@ use uom::si::{
     u16::Length as Length_u16,
     velocity::meter_per_second,
 };
+use uuid::Uuid;
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// Summary information per lap
@@ -100,6 +103,12 @@ pub struct FITLap {
 
     /// Time spent in each heart rate zone.
     pub time_in_hr_zones: FITHrZones,
+
+    /// The UUID of the activity the lap belongs to.
+    pub activity_uuid: Option<Uuid>,
+
+    /// The number of the session the lap belongs to, starting at 1.
+    pub session_num: Option<u16>,
 }
 
 impl FITLap {
@@ -174,19 +183,19 @@ impl FITLap {
         lap.lat_start = field_map
             .get("start_position_lat")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
         lap.lon_start = field_map
             .get("start_position_long")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
         lap.lat_end = field_map
             .get("end_position_lat")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
         lap.lon_end = field_map
             .get("end_position_long")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
 
         lap.ascent = field_map
             .get("total_ascent")
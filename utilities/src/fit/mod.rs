@@ -3,9 +3,20 @@
 // This is where the types are defined
 pub mod activities;
 pub mod activity;
+pub mod altitude;
 pub mod constfunc;
+pub mod course;
+pub mod device_info;
+pub mod environment;
+pub mod event;
 pub mod hrzones;
 pub mod lap;
+pub mod parse_stats;
+pub mod products;
 pub mod record;
 pub mod session;
+pub mod split;
+pub mod to_fit;
+pub mod to_gpx;
+#[cfg(feature = "fs")]
 pub mod to_hashmap;
@@ -0,0 +1,153 @@
+//! Defines the `FITParseStats` struct which counts the messages and fields found in a FIT file, so it's possible to
+//! see what information in the file the tools don't use.
+
+use fitparser::profile::field_types::MesgNum;
+use fitparser::FitDataRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::fit::activity::for_each_message;
+#[cfg(feature = "fs")]
+use crate::input::read_input;
+
+/// The messages the tools read information from. Everything else is ignored.
+const USED_MESSAGES: [&str; 5] = ["file_id", "activity", "session", "lap", "record"];
+
+/// The prefix `fitparser` gives fields that aren't in the FIT profile.
+const UNKNOWN_FIELD_PREFIX: &str = "unknown_field_";
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Counts of the messages and fields found in a FIT file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct FITParseStats {
+    /// The number of messages of each kind, e.g. `record`. Messages not in the FIT profile are named
+    /// `unknown_message_<number>`.
+    pub messages: BTreeMap<String, usize>,
+
+    /// The number of fields not in the FIT profile, by message kind and field, e.g. `record.unknown_field_87`.
+    pub unknown_fields: BTreeMap<String, usize>,
+}
+
+impl FITParseStats {
+    /// Reads the FIT file and counts the messages and fields in it.
+    ///
+    /// # Arguments
+    ///
+    /// `filename: &str` -- The FIT file to be read.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Self, Box<dyn Error>>` -- The counts.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the file may fail.
+    #[cfg(feature = "fs")]
+    pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
+        let stats = Self::from_bytes(&read_input(filename)?)?;
+
+        log::trace!("parse_stats::from_file() -- {filename}: {stats:?}");
+        Ok(stats)
+    }
+
+    /// Counts the messages and fields in FIT data that has already been read into memory.
+    ///
+    /// # Arguments
+    ///
+    /// `buffer: &[u8]` -- The FIT data.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Self, Box<dyn Error>>` -- The counts.
+    ///
+    /// # Errors
+    ///
+    /// Decoding the data may fail.
+    pub fn from_bytes(buffer: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut stats = Self::default();
+
+        for_each_message(buffer, |data| {
+            stats.add(&data);
+            Ok(())
+        })?;
+
+        Ok(stats)
+    }
+
+    /// Counts a single message and its unknown fields.
+    pub(crate) fn add(&mut self, data: &FitDataRecord) {
+        let kind = match data.kind() {
+            MesgNum::Value(num) => format!("unknown_message_{num}"),
+            kind => kind.to_string(),
+        };
+
+        for field in data.fields() {
+            if field.name().starts_with(UNKNOWN_FIELD_PREFIX) {
+                *self
+                    .unknown_fields
+                    .entry(format!("{kind}.{}", field.name()))
+                    .or_default() += 1;
+            }
+        }
+
+        *self.messages.entry(kind).or_default() += 1;
+    }
+
+    /// The number of messages of each kind the tools don't read any information from.
+    #[must_use]
+    pub fn ignored_messages(&self) -> BTreeMap<&str, usize> {
+        self.messages
+            .iter()
+            .filter(|(kind, _)| !USED_MESSAGES.contains(&kind.as_str()))
+            .map(|(kind, count)| (kind.as_str(), *count))
+            .collect()
+    }
+
+    /// Prints the counts to stdout.
+    pub fn print(&self) {
+        println!("Messages:");
+        for (kind, count) in &self.messages {
+            let used = if USED_MESSAGES.contains(&kind.as_str()) {
+                ""
+            } else {
+                " (ignored)"
+            };
+            println!("  {kind:<40} {count:>8}{used}");
+        }
+
+        if self.unknown_fields.is_empty() {
+            println!("Unknown fields: None");
+        } else {
+            println!("Unknown fields:");
+            for (field, count) in &self.unknown_fields {
+                println!("  {field:<40} {count:>8}");
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+/// Tests for the parse_stats module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test counting the messages in a file
+    fn test_from_file() {
+        let stats = FITParseStats::from_file("../data/rowing.fit").unwrap();
+
+        assert_eq!(stats.messages.get("file_id"), Some(&1));
+        assert_eq!(stats.messages.get("session"), Some(&1));
+        assert_eq!(stats.messages.get("record"), Some(&605));
+        assert!(stats
+            .ignored_messages()
+            .keys()
+            .all(|kind| !USED_MESSAGES.contains(kind)));
+        assert!(stats
+            .unknown_fields
+            .keys()
+            .all(|field| field.contains(".unknown_field_")));
+    }
+}
@@ -0,0 +1,169 @@
+//! Translates the manufacturer and product IDs from the FIT file header into readable device names, e.g.
+//! "Garmin Forerunner 955".
+
+use fitparser::profile::field_types::{FaveroProduct, GarminProduct};
+use fitparser::Value;
+
+/// Manufacturers whose products are listed in the FIT profile's `garmin_product` table.
+const GARMIN_PRODUCT_MANUFACTURERS: [&str; 4] = ["garmin", "dynastream", "dynastream_oem", "tacx"];
+
+/// Suffixes of regional versions of the same device, and other suffixes which don't belong in the device name.
+const IGNORED_SUFFIXES: [&str; 10] = [
+    "_single_byte_product_id",
+    "_asia",
+    "_apac",
+    "_china",
+    "_japan",
+    "_korea",
+    "_taiwan",
+    "_sea",
+    "_india",
+    "_wechat",
+];
+
+/// Abbreviations used in the FIT profile product names, and what they should be shown as.
+const ABBREVIATIONS: [(&str, &str); 7] = [
+    ("fr", "Forerunner"),
+    ("hrm", "HRM"),
+    ("gps", "GPS"),
+    ("sdm", "SDM"),
+    ("ant", "ANT"),
+    ("ohr", "OHR"),
+    ("lte", "LTE"),
+];
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the device name from the manufacturer and the product found in the FIT file header.
+///
+/// The product is either a name from the FIT profile (e.g. `fr955`), or a number if the FIT profile doesn't know the
+/// product. Numbers are looked up in the manufacturer's product table, if the FIT profile has one.
+///
+/// # Arguments
+///
+/// - `manufacturer: Option<&str>` -- The manufacturer as found in the file, e.g. `garmin`.
+/// - `product: Option<&Value>` -- The `product`, `garmin_product` or `favero_product` field from the file.
+///
+/// # Returns
+///
+/// `Option<String>` -- The device name, e.g. "Garmin Forerunner 955", or the bare product number if the product
+/// can't be looked up. `None` if there is no product.
+///
+/// # Example
+///
+/// ```text
+/// let name = product_name(Some("garmin"), Some(&Value::UInt16(4024)));
+/// assert_eq!(name.as_deref(), Some("Garmin Forerunner 955"));
+/// ```
+#[must_use]
+pub fn product_name(manufacturer: Option<&str>, product: Option<&Value>) -> Option<String> {
+    let manufacturer = manufacturer.unwrap_or_default();
+
+    let code = match product? {
+        Value::String(name) => name.clone(),
+        value => {
+            let id: i64 = value.try_into().ok()?;
+            let id = u16::try_from(id).ok()?;
+            if GARMIN_PRODUCT_MANUFACTURERS.contains(&manufacturer) {
+                GarminProduct::from(id).to_string()
+            } else if manufacturer == "favero_electronics" {
+                FaveroProduct::from(id).to_string()
+            } else {
+                id.to_string()
+            }
+        }
+    };
+
+    // Not in any of the product tables
+    if code.chars().all(|c| c.is_ascii_digit()) {
+        return Some(code);
+    }
+
+    let device = readable_name(&code);
+    let manufacturer = readable_name(manufacturer);
+    if manufacturer.is_empty() || device.starts_with(&manufacturer) {
+        Some(device)
+    } else {
+        Some(format!("{manufacturer} {device}"))
+    }
+}
+
+/// Turns a FIT profile name such as `fenix7x_pro_solar` into a readable name such as "Fenix 7X Pro Solar".
+fn readable_name(code: &str) -> String {
+    let mut code = code.to_lowercase();
+    for suffix in IGNORED_SUFFIXES {
+        if let Some(stripped) = code.strip_suffix(suffix) {
+            code = stripped.to_string();
+        }
+    }
+
+    code.split('_')
+        .flat_map(split_letters_digits)
+        .map(|word| {
+            if let Some((_, name)) = ABBREVIATIONS.iter().find(|(abbr, _)| *abbr == word) {
+                (*name).to_string()
+            } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+                word.to_uppercase()
+            } else {
+                let mut chars = word.chars();
+                chars.next().map_or_else(String::new, |first| {
+                    first.to_uppercase().chain(chars).collect()
+                })
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Splits a word where the letters end and the digits start, e.g. `fr955` becomes `fr` and `955`.
+fn split_letters_digits(word: &str) -> Vec<String> {
+    match word.find(|c: char| c.is_ascii_digit()) {
+        Some(pos) if pos > 0 => vec![word[..pos].to_string(), word[pos..].to_string()],
+        _ if word.is_empty() => Vec::new(),
+        _ => vec![word.to_string()],
+    }
+}
+
+#[cfg(test)]
+/// Tests for the products module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test looking up the product names
+    fn test_product_name() {
+        assert_eq!(
+            product_name(Some("garmin"), Some(&Value::String("fr955".to_string()))).as_deref(),
+            Some("Garmin Forerunner 955")
+        );
+        assert_eq!(
+            product_name(Some("garmin"), Some(&Value::UInt16(4024))).as_deref(),
+            Some("Garmin Forerunner 955")
+        );
+        assert_eq!(
+            product_name(
+                Some("garmin"),
+                Some(&Value::String("fenix7x_apac".to_string()))
+            )
+            .as_deref(),
+            Some("Garmin Fenix 7X")
+        );
+        assert_eq!(
+            product_name(Some("favero_electronics"), Some(&Value::UInt16(10))).as_deref(),
+            Some("Favero Electronics Assioma Uno")
+        );
+        assert_eq!(
+            product_name(Some("wahoo_fitness"), Some(&Value::UInt16(31))).as_deref(),
+            Some("31")
+        );
+        assert!(product_name(Some("garmin"), None).is_none());
+    }
+
+    #[test]
+    /// Test making the profile names readable
+    fn test_readable_name() {
+        assert_eq!(readable_name("edge_1030_plus"), "Edge 1030 Plus");
+        assert_eq!(readable_name("epix_gen2_pro_47"), "Epix Gen 2 Pro 47");
+        assert_eq!(readable_name("hrm_run_single_byte_product_id"), "HRM Run");
+        assert_eq!(readable_name("wahoo_fitness"), "Wahoo Fitness");
+    }
+}
@@ -1,6 +1,9 @@
 //! Defines the `Record` struct which contains detailed information about each record/data point in the workout session.
 
-use crate::fit::constfunc::{map_float64, map_sint32, map_uint16, map_uint8, LATLON_MULTIPLIER};
+use crate::fit::altitude::{altitude_source, AltitudeSource};
+use crate::fit::constfunc::{
+    map_float64, map_sint32, map_uint16, map_uint8, semicircles_to_degrees,
+};
 use crate::fit::session::FITSession;
 use crate::Duration;
 
@@ -11,6 +14,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uom::si::f64::{Length as Length_f64, Velocity};
 use uom::si::{length::meter, velocity::meter_per_second};
+use uuid::Uuid;
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// Detailed information about each record/data point in the workout session.
@@ -30,6 +34,9 @@ pub struct FITRecord {
     /// Altiude (Meters).
     pub altitude: Option<Length_f64>,
 
+    /// The FIT field the altitude was read from.
+    pub altitude_source: Option<AltitudeSource>,
+
     /// Stance time (Seconds).
     pub stance_time: Option<Duration>,
 
@@ -56,6 +63,16 @@ pub struct FITRecord {
 
     /// Longitude (Degrees).
     pub lon: Option<f64>,
+
+    /// The UUID of the activity the record belongs to.
+    pub activity_uuid: Option<Uuid>,
+
+    /// The number of the session the record belongs to, starting at 1.
+    pub session_num: Option<u16>,
+
+    /// Whether the record was interpolated between two recorded ones rather than recorded by the device. Empty for
+    /// recorded records.
+    pub synthetic: Option<bool>,
 }
 
 impl FITRecord {
@@ -116,10 +133,10 @@ impl FITRecord {
             .get("distance")
             .and_then(map_float64)
             .map(Length_f64::new::<meter>);
-        record.altitude = field_map
-            .get("enhanced_altitude")
-            .and_then(map_float64)
-            .map(Length_f64::new::<meter>);
+        if let Some((altitude, source)) = altitude_source().select(&field_map) {
+            record.altitude = Some(Length_f64::new::<meter>(altitude));
+            record.altitude_source = Some(source);
+        }
 
         record.cadence = field_map.get("cadence").and_then(map_uint8);
         record.speed = field_map
@@ -139,12 +156,12 @@ impl FITRecord {
         record.lat = field_map
             .get("position_lat")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
 
         record.lon = field_map
             .get("position_long")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
 
         record
     }
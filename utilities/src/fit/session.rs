@@ -1,19 +1,33 @@
 //! Defines the `Session` struct which holds summary information about the workout session, and associated functions.
 
-use crate::Duration;
+use crate::analysis::decoupling::Decoupling;
+use crate::analysis::distance_check::{distance_discrepancy, is_miscalibrated, GpsDistance};
+use crate::analysis::heat::{heat_effort, HeatFlag};
+use crate::analysis::indoor::detect_environment;
+use crate::analysis::pacing::{Pacing, PacingSplit};
+use crate::analysis::power::PowerMetrics;
+use crate::analysis::recording::{RecordingInterval, RecordingMode};
+use crate::fit::products::product_name;
+#[cfg(feature = "fs")]
+use crate::output_naming::{output_path, write_output};
+use crate::placeholder::placeholder;
+use crate::privacy::serialize_serial;
+use crate::schema::write_summary_json;
+use crate::{activity_uuid, Duration};
 use crate::{
     fit::constfunc::{
-        map_float64, map_sint32, map_string, map_uint16, map_uint8, LATLON_MULTIPLIER,
+        map_float64, map_integer, map_sint32, map_string, map_uint16, map_uint8,
+        semicircles_to_degrees,
     },
-    FITHrZones,
+    FITEnvironment, FITHrZones,
 };
 
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
 
 use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
-use std::path::PathBuf;
+use std::io::Write;
+use uuid::Uuid;
 
 use convert_case::{Case, Casing};
 use fitparser::FitDataField;
@@ -35,10 +49,15 @@ pub struct FITSession {
     pub filename: Option<String>, // TODO: Switch to PathBuf
     pub manufacturer: Option<String>,
     pub product: Option<String>,
+    #[serde(serialize_with = "serialize_serial")]
     pub serial_number: Option<String>,
     pub time_created: Option<DateTime<Local>>,
     pub activity_type: Option<String>,
     pub activity_detailed: Option<String>,
+    /// Whether the activity took place outdoors, indoors or in a virtual world, based on the `sub_sport` and the GPS
+    /// track.
+    #[serde(default)]
+    pub environment: FITEnvironment,
     pub num_sessions: Option<u16>,
     pub num_laps: Option<u16>,
     pub num_records: Option<u64>,
@@ -62,12 +81,62 @@ pub struct FITSession {
     pub descent: Option<Length_u16>,
     pub calories: Option<u16>,
     pub distance: Option<Length_f64>,
+    /// The distance worked out from the GPS positions in the records.
+    pub gps_distance: Option<Length_f64>,
+    /// How much the recorded distance differs from the GPS distance, in percent of the GPS distance.
+    pub distance_discrepancy_pct: Option<f64>,
     pub duration: Option<Duration>,
     pub duration_active: Option<Duration>,
     pub duration_moving: Option<Duration>,
     pub start_time: Option<DateTime<Local>>,
     pub finish_time: Option<DateTime<Local>>,
     pub time_in_hr_zones: FITHrZones,
+    /// The device's local time when the activity was saved, from the Activity message.
+    pub local_timestamp: Option<NaiveDateTime>,
+    /// The device's offset from UTC in seconds, worked out from the Activity message.
+    pub utc_offset: Option<i32>,
+    /// Total timer time from the Activity message.
+    pub total_timer_time: Option<Duration>,
+    /// Identifies the activity across all the files exported from it.
+    pub uuid: Option<Uuid>,
+    /// The average speed over the first half of the distance.
+    pub speed_first_half: Option<Velocity>,
+    /// The average speed over the second half of the distance.
+    pub speed_second_half: Option<Velocity>,
+    /// How much longer the second half took than the first, in percent. Negative for a negative split.
+    pub pacing_index_pct: Option<f64>,
+    /// Whether the second half was faster (negative), about the same (even) or slower (positive) than the first.
+    pub pacing_split: Option<PacingSplit>,
+    /// The median time between two records in seconds.
+    pub recording_interval_sec: Option<f64>,
+    /// The share of the intervals between records that are one second, in percent.
+    pub recording_every_second_pct: Option<f64>,
+    /// Whether the device recorded every second or used smart recording.
+    pub recording_mode: Option<RecordingMode>,
+    /// The number of the session in the file, starting at 1. Multisport files have one session per sport.
+    pub session_num: Option<u16>,
+    /// How much the power to heart rate ratio dropped from the first half to the second, in percent (Pw:HR).
+    pub decoupling_power_pct: Option<f64>,
+    /// How much the speed to heart rate ratio dropped from the first half to the second, in percent (Pa:HR).
+    pub decoupling_pace_pct: Option<f64>,
+    /// The average temperature recorded by the device, in degrees Celsius.
+    pub temperature_avg_c: Option<f64>,
+    /// How much slower the effort was because of the heat, in percent.
+    pub heat_adjustment_pct: Option<f64>,
+    /// The average speed the effort would have given at a neutral temperature.
+    pub speed_heat_adjusted: Option<Velocity>,
+    /// Whether the activity took place in hot weather.
+    pub heat: Option<HeatFlag>,
+    /// The Normalized Power in watts, worked out from the 30 second rolling average of the record power.
+    pub power_normalized: Option<f64>,
+    /// The Normalized Power in parts of the Functional Threshold Power.
+    pub intensity_factor: Option<f64>,
+    /// The Training Stress Score, where an hour at the Functional Threshold Power is 100.
+    pub training_stress_score: Option<f64>,
+    /// The tags of the activity from the tag file, separated by `TAG_SEPARATOR`.
+    pub tags: Option<String>,
+    /// The number of GPS spikes found in the records, dropped if set with `Settings::set_gps_cleaning()`.
+    pub gps_outliers: Option<usize>,
 }
 
 impl FITSession {
@@ -94,7 +163,7 @@ impl FITSession {
 
     /// Output details about the session
     pub fn print_summary(&self) {
-        let unknown = String::from("Unknown");
+        let unknown = placeholder("Unknown");
 
         println!(
             "\n{} summary:\n",
@@ -103,9 +172,8 @@ impl FITSession {
         println!(
             "Manufacturer: {}    Time created: {}",
             self.manufacturer.as_ref().unwrap_or(&unknown),
-            self.time_created
-                .as_ref()
-                .unwrap_or(&Local.timestamp_opt(0, 0).unwrap())
+            self.time_created_local()
+                .unwrap_or_else(|| self.to_device_local(&Local.timestamp_opt(0, 0).unwrap()))
         );
         println!(
             "Sessions: {}      Laps: {:2}      Records: {}",
@@ -118,6 +186,18 @@ impl FITSession {
             self.duration.unwrap_or_default(),
             self.calories.unwrap_or_default()
         );
+        if let Some(pct) = self.distance_discrepancy_pct {
+            println!(
+                "Distance: {:.0} m      GPS distance: {:.0} m      Discrepancy: {pct:.1}%{}",
+                self.distance.unwrap_or_default().value,
+                self.gps_distance.unwrap_or_default().value,
+                if is_miscalibrated(pct) {
+                    " (check sensor calibration)"
+                } else {
+                    ""
+                }
+            );
+        }
         println!("\nTime in Zones:");
         println!(
             "  Speed/Power: {}",
@@ -160,8 +240,20 @@ impl FITSession {
             fields.iter().map(|x| (x.name(), x.value())).collect();
 
         self.manufacturer = field_map.get("manufacturer").and_then(map_string);
-        self.product = field_map.get("product").and_then(map_string);
-        self.serial_number = field_map.get("serial_number").and_then(map_string);
+        self.product = product_name(
+            self.manufacturer.as_deref(),
+            ["product", "garmin_product", "favero_product"]
+                .iter()
+                .find_map(|name| field_map.get(name))
+                .copied(),
+        );
+        // The serial number is stored as a number, but some devices write it as a string
+        self.serial_number = field_map.get("serial_number").and_then(|value| {
+            map_string(value).or_else(|| {
+                let serial: i64 = (*value).try_into().ok()?;
+                Some(serial.to_string())
+            })
+        });
 
         if let Some(fitparser::Value::Timestamp(ft)) = field_map.get("time_created") {
             self.time_created = Some(*ft);
@@ -190,15 +282,18 @@ impl FITSession {
         self.activity_type = Some(
             self.activity_type
                 .as_ref()
-                .unwrap_or(&"unknown".to_string())
-                .to_case(Case::Title),
+                .map_or_else(|| placeholder("Unknown"), |at| at.to_case(Case::Title)),
         );
         self.activity_detailed = field_map.get("sub_sport").and_then(map_string);
+        self.environment = self
+            .activity_detailed
+            .as_deref()
+            .map(FITEnvironment::from_sub_sport)
+            .unwrap_or_default();
         self.activity_detailed = Some(
             self.activity_detailed
                 .as_ref()
-                .unwrap_or(&"unknown".to_string())
-                .to_case(Case::Title),
+                .map_or_else(|| placeholder("Unknown"), |ad| ad.to_case(Case::Title)),
         );
 
         self.cadence_avg = field_map.get("avg_cadence").and_then(map_uint8);
@@ -224,25 +319,32 @@ impl FITSession {
 
         self.power_avg = field_map.get("avg_power").and_then(map_uint16);
         self.power_max = field_map.get("max_power").and_then(map_uint16);
+
+        self.temperature_avg_c = field_map
+            .get("avg_temperature")
+            .and_then(map_integer)
+            .and_then(|temperature| i32::try_from(temperature).ok())
+            .map(f64::from);
+        self.set_heat_effort();
         self.power_threshold = field_map.get("threshold_power").and_then(map_uint16);
 
         // GPS - NEC = North East Corner, SWC = South West Corner
         self.nec_lat = field_map
             .get("nec_lat")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
         self.nec_lon = field_map
             .get("nec_long")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
         self.swc_lat = field_map
             .get("swc_lat")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
         self.swc_lon = field_map
             .get("swc_long")
             .and_then(map_sint32)
-            .map(|x| f64::from(x) * LATLON_MULTIPLIER);
+            .map(semicircles_to_degrees);
 
         self.ascent = field_map
             .get("total_ascent")
@@ -266,7 +368,8 @@ impl FITSession {
         self.duration_active = field_map
             .get("total_timer_time")
             .and_then(map_float64)
-            .map(Duration::from_secs_f64);
+            .map(Duration::from_secs_f64)
+            .or(self.total_timer_time);
         self.duration_moving = field_map
             .get("total_moving_time")
             .and_then(map_float64)
@@ -281,17 +384,177 @@ impl FITSession {
         if let Some(fitparser::Value::Timestamp(ft)) = field_map.get("DateTime<Local>") {
             self.finish_time = Some(*ft);
         } else {
+            // A duration too long to add to the start time leaves the finish time unknown
             let dur = self.duration.unwrap_or_default();
-            let c_dur = chrono::Duration::seconds(dur.0.as_secs() as i64);
             let st = self.start_time.unwrap_or_default();
-            let ft = st + c_dur;
-            self.finish_time = Some(ft);
+            self.finish_time = i64::try_from(dur.0.as_secs())
+                .ok()
+                .and_then(chrono::Duration::try_seconds)
+                .and_then(|c_dur| st.checked_add_signed(c_dur));
         }
         self.num_laps = field_map.get("num_laps").and_then(map_uint16);
 
         self.time_in_hr_zones = FITHrZones::from(field_map.get("time_in_hr_zone"));
     }
 
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Parses the Activity message, which holds the total timer time and the device's local time when the activity
+    /// was saved. The local time is used to work out the device's offset from UTC.
+    ///
+    /// # Arguments
+    ///
+    /// `fields: &[FitDataField]` -- The fields of the Activity message.
+    pub fn parse_activity(&mut self, fields: &[FitDataField]) {
+        let field_map: HashMap<&str, &fitparser::Value> =
+            fields.iter().map(|x| (x.name(), x.value())).collect();
+        log::trace!("FITSession::parse_activity() -- activity field_map = {field_map:?}");
+
+        self.total_timer_time = field_map
+            .get("total_timer_time")
+            .and_then(map_float64)
+            .map(Duration::from_secs_f64);
+        if self.duration_active.is_none() {
+            self.duration_active = self.total_timer_time;
+        }
+
+        if let (Some(fitparser::Value::Timestamp(ts)), Some(fitparser::Value::Timestamp(lt))) =
+            (field_map.get("timestamp"), field_map.get("local_timestamp"))
+        {
+            let local = device_local_time(lt);
+            self.local_timestamp = Some(local);
+
+            // Time zones are in whole quarter hours, so round off any difference between the two timestamps
+            let offset = ((local - ts.naive_utc()).num_seconds() as f64 / 900.0).round() * 900.0;
+            self.utc_offset = Some(offset as i32).filter(|o| FixedOffset::east_opt(*o).is_some());
+        } else {
+            self.local_timestamp = None;
+            self.utc_offset = None;
+        }
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Converts a timestamp to the device's local time, if the device's UTC offset is known.
+    /// Otherwise, the local time zone of this computer is used.
+    ///
+    /// # Arguments
+    ///
+    /// `time: &DateTime<Local>` -- The timestamp to convert.
+    ///
+    /// # Returns
+    ///
+    /// `DateTime<FixedOffset>` -- The timestamp in the device's time zone.
+    #[must_use]
+    pub fn to_device_local(&self, time: &DateTime<Local>) -> DateTime<FixedOffset> {
+        self.utc_offset.and_then(FixedOffset::east_opt).map_or_else(
+            || time.with_timezone(time.offset()),
+            |tz| time.with_timezone(&tz),
+        )
+    }
+
+    /// Sets the activity UUID from the start time, serial number and sport. Call this once the session has been parsed.
+    pub fn set_uuid(&mut self) {
+        self.uuid = Some(activity_uuid(
+            self.start_time.as_ref().or(self.time_created.as_ref()),
+            self.serial_number.as_deref(),
+            self.activity_type.as_deref(),
+        ));
+    }
+
+    /// Sets the GPS distance and compares it with the recorded distance, to find out if the difference suggests a
+    /// miscalibrated wheel sensor or foot pod.
+    ///
+    /// # Arguments
+    ///
+    /// `gps_distance: Option<f64>` -- The distance of the GPS track in meters, if the records have positions.
+    pub fn set_gps_distance(&mut self, gps_distance: Option<f64>) {
+        self.gps_distance = gps_distance.map(Length_f64::new::<meter>);
+        self.distance_discrepancy_pct = self
+            .distance
+            .zip(gps_distance)
+            .and_then(|(recorded, gps)| distance_discrepancy(recorded.value, gps));
+    }
+
+    /// Checks the environment given by the `sub_sport` against the GPS track, so treadmill runs and trainer rides
+    /// recorded without an indoor `sub_sport` are still marked as indoor.
+    ///
+    /// # Arguments
+    ///
+    /// `gps: &GpsDistance` -- The GPS track of the records.
+    pub fn set_environment(&mut self, gps: &GpsDistance) {
+        self.environment = detect_environment(
+            self.environment,
+            self.distance.map(|distance| distance.value),
+            gps,
+        );
+    }
+
+    /// Sets the pacing, i.e. how the speed in the second half of the activity compares with the first.
+    ///
+    /// # Arguments
+    ///
+    /// `pacing: Option<Pacing>` -- The pacing worked out from the records, if there is enough distance to tell.
+    pub fn set_pacing(&mut self, pacing: Option<Pacing>) {
+        self.speed_first_half =
+            pacing.map(|p| Velocity::new::<meter_per_second>(p.first_half_speed));
+        self.speed_second_half =
+            pacing.map(|p| Velocity::new::<meter_per_second>(p.second_half_speed));
+        self.pacing_index_pct = pacing.map(|p| p.index_pct);
+        self.pacing_split = pacing.map(|p| p.split);
+    }
+
+    /// Sets the aerobic decoupling, i.e. how far the heart rate drifted up against the power and speed.
+    ///
+    /// # Arguments
+    ///
+    /// `decoupling: Decoupling` -- The decoupling worked out from the records.
+    pub fn set_decoupling(&mut self, decoupling: Decoupling) {
+        self.decoupling_power_pct = decoupling.power_pct;
+        self.decoupling_pace_pct = decoupling.pace_pct;
+    }
+
+    /// Sets the Normalized Power, Intensity Factor and Training Stress Score.
+    ///
+    /// # Arguments
+    ///
+    /// `metrics: PowerMetrics` -- The training load worked out from the records.
+    pub fn set_power_metrics(&mut self, metrics: PowerMetrics) {
+        self.power_normalized = metrics.normalized_power;
+        self.intensity_factor = metrics.intensity_factor;
+        self.training_stress_score = metrics.training_stress_score;
+    }
+
+    /// Sets how much the heat held back the effort, from the average temperature and speed.
+    pub fn set_heat_effort(&mut self) {
+        let heat = heat_effort(
+            self.temperature_avg_c,
+            self.speed_avg.map(|speed| speed.value),
+        );
+        self.heat_adjustment_pct = heat.map(|h| h.adjustment_pct);
+        self.speed_heat_adjusted = heat
+            .and_then(|h| h.adjusted_speed)
+            .map(Velocity::new::<meter_per_second>);
+        self.heat = heat.map(|h| h.flag);
+    }
+
+    /// Sets how often the device recorded, i.e. every second or smart recording.
+    ///
+    /// # Arguments
+    ///
+    /// `interval: Option<RecordingInterval>` -- The recording interval worked out from the record timestamps.
+    pub fn set_recording_interval(&mut self, interval: Option<RecordingInterval>) {
+        self.recording_interval_sec = interval.map(|i| i.median_sec);
+        self.recording_every_second_pct = interval.map(|i| i.every_second_pct);
+        self.recording_mode = interval.map(|i| i.mode);
+    }
+
+    /// The time the file was created, in the device's local time if known.
+    #[must_use]
+    pub fn time_created_local(&self) -> Option<DateTime<FixedOffset>> {
+        self.time_created
+            .as_ref()
+            .map(|tc| self.to_device_local(tc))
+    }
+
     ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
     /// Export the session information to a JSON file name based on the FIT file name.
     ///
@@ -302,26 +565,151 @@ impl FITSession {
     /// # Errors
     ///
     /// Writing the JSON could fail.
+    #[cfg(feature = "fs")]
     pub fn export_json(&self) -> Result<(), Box<dyn Error>> {
         // Change the file extension
-        let mut export_path = PathBuf::from(
-            &self
-                .filename
-                .as_ref()
-                .unwrap_or(&String::from("export-session.json")),
+        let export_path = output_path(
+            self.filename.as_deref().unwrap_or("export-session.json"),
+            "session.json",
         );
-        export_path.set_extension("session.json");
         log::trace!(
             "exporter::export_session_json() -- Writing JSON file {}",
             &export_path.to_str().unwrap_or("<Unknown filename>")
         );
 
         // Write the session data to JSON
-        serde_json::to_writer_pretty(&File::create(&export_path)?, &self)?;
+        write_output(&export_path, |file| self.write_json(file))?;
 
         // Everything is OK
         Ok(())
     }
 
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Write the session information as JSON to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the JSON is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing or writing the JSON could fail.
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        write_summary_json(self, writer)
+    }
+
     // end impl Session
 }
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Recovers the wall clock time from a FIT `local_date_time` value.
+///
+/// `fitparser` places these values in the time zone of the computer reading the file, measured from the FIT epoch in
+/// that time zone. Measuring from the same epoch gives back the time as it was shown on the device, regardless of any
+/// daylight saving changes on this computer.
+fn device_local_time(time: &DateTime<Local>) -> NaiveDateTime {
+    let fit_epoch = NaiveDate::from_ymd_opt(1989, 12, 31)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .unwrap_or_default();
+
+    Local
+        .from_local_datetime(&fit_epoch)
+        .earliest()
+        .map_or_else(|| time.naive_local(), |epoch| fit_epoch + (*time - epoch))
+}
+
+#[cfg(test)]
+/// Tests for the session module
+mod tests {
+    use super::*;
+    use fitparser::Value;
+
+    /// Builds an Activity message with the given UTC and device local times
+    fn activity_fields(timestamp: DateTime<Local>, local: NaiveDateTime) -> Vec<FitDataField> {
+        vec![
+            FitDataField::new(
+                "timestamp".to_string(),
+                253,
+                Value::Timestamp(timestamp),
+                String::new(),
+            ),
+            FitDataField::new(
+                "local_timestamp".to_string(),
+                5,
+                Value::Timestamp(Local.from_local_datetime(&local).unwrap()),
+                String::new(),
+            ),
+            FitDataField::new(
+                "total_timer_time".to_string(),
+                1,
+                Value::Float64(1937.243),
+                "s".to_string(),
+            ),
+        ]
+    }
+
+    #[test]
+    /// Test that the device's UTC offset is picked up from the Activity message
+    fn test_parse_activity() {
+        let utc = chrono::Utc
+            .with_ymd_and_hms(2020, 1, 1, 16, 41, 23)
+            .unwrap()
+            .with_timezone(&Local);
+        let local = NaiveDate::from_ymd_opt(2020, 1, 1)
+            .unwrap()
+            .and_hms_opt(8, 41, 23)
+            .unwrap();
+
+        let mut session = FITSession::default();
+        session.parse_activity(&activity_fields(utc, local));
+
+        assert_eq!(session.local_timestamp, Some(local));
+        assert_eq!(session.utc_offset, Some(-8 * 3600));
+        assert_eq!(session.duration_active, session.total_timer_time);
+        assert_eq!(session.to_device_local(&utc).naive_local(), local);
+
+        session.time_created = Some(utc);
+        assert_eq!(session.time_created_local().unwrap().naive_local(), local);
+    }
+
+    #[test]
+    /// Test that the local time of this computer is used if the device's offset is unknown
+    fn test_to_device_local_fallback() {
+        let session = FITSession::default();
+        let now = Local::now();
+
+        assert_eq!(
+            session.to_device_local(&now).naive_local(),
+            now.naive_local()
+        );
+        assert!(session.time_created_local().is_none());
+    }
+
+    #[test]
+    /// Test that odd durations in the Session message don't cause a panic
+    fn test_parse_session_out_of_range() {
+        let fields = vec![FitDataField::new(
+            "total_elapsed_time".to_string(),
+            7,
+            Value::Float64(1.0e300),
+            "s".to_string(),
+        )];
+
+        let mut session = FITSession::default();
+        session.parse_session(&fields);
+        assert_eq!(
+            session.duration.map(|d| d.0),
+            Some(std::time::Duration::MAX)
+        );
+        assert!(session.finish_time.is_none());
+
+        let fields = vec![FitDataField::new(
+            "total_elapsed_time".to_string(),
+            7,
+            Value::Float64(-5.0),
+            "s".to_string(),
+        )];
+        session.parse_session(&fields);
+        assert_eq!(session.duration, Some(Duration::default()));
+    }
+}
@@ -0,0 +1,242 @@
+//! Defines the `FITSplit` struct which holds the splits worked out by the device itself, e.g. the run and walk
+//! intervals of a run/walk or the climbs found by auto-climb, and associated functions.
+
+use crate::fit::constfunc::{
+    map_float64, map_integer, map_sint32, map_text, semicircles_to_degrees,
+};
+use crate::FITSession;
+
+use chrono::{DateTime, Local};
+use fitparser::FitDataField;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A split recorded by the device, from a Split message, or the totals of all the splits of a kind, from a Split
+/// Summary message. Newer Garmin devices write these for e.g. run/walk detection and auto-climb.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct FITSplit {
+    /// The name of the .FIT file in which the split is found.
+    #[serde(rename = "filename")]
+    pub filename: Option<String>,
+
+    /// Whether this is a single split (`split`) or the totals of the splits of a kind (`split_summary`).
+    #[serde(rename = "message")]
+    pub message: Option<String>,
+
+    /// The number of the split within its message kind, starting from zero.
+    #[serde(rename = "split_index")]
+    pub split_index: Option<i64>,
+
+    /// The kind of split, e.g. `run_active`, `walk_active` or `climb_active`.
+    #[serde(rename = "split_type")]
+    pub split_type: Option<String>,
+
+    /// The number of splits of the kind. Only in split summaries.
+    #[serde(rename = "num_splits")]
+    pub num_splits: Option<i64>,
+
+    /// When the split started. Only in splits.
+    #[serde(rename = "start_time")]
+    pub start_time: Option<DateTime<Local>>,
+
+    /// When the split ended. Only in splits.
+    #[serde(rename = "end_time")]
+    pub end_time: Option<DateTime<Local>>,
+
+    /// The time from the start to the end of the split, including pauses (Seconds). Only in splits.
+    #[serde(rename = "elapsed_time_sec")]
+    pub elapsed_time: Option<f64>,
+
+    /// The time the timer was running (Seconds).
+    #[serde(rename = "timer_time_sec")]
+    pub timer_time: Option<f64>,
+
+    /// The time spent moving (Seconds).
+    #[serde(rename = "moving_time_sec")]
+    pub moving_time: Option<f64>,
+
+    /// The distance covered (Meters).
+    #[serde(rename = "distance_m")]
+    pub distance: Option<f64>,
+
+    /// Average speed (Meters per Second).
+    #[serde(rename = "speed_avg_ms")]
+    pub speed_avg: Option<f64>,
+
+    /// Maximum speed (Meters per Second).
+    #[serde(rename = "speed_max_ms")]
+    pub speed_max: Option<f64>,
+
+    /// Average vertical speed (Meters per Second).
+    #[serde(rename = "vertical_speed_avg_ms")]
+    pub vertical_speed_avg: Option<f64>,
+
+    /// Total ascent (Meters).
+    #[serde(rename = "ascent_m")]
+    pub ascent: Option<i64>,
+
+    /// Total descent (Meters).
+    #[serde(rename = "descent_m")]
+    pub descent: Option<i64>,
+
+    /// The elevation at the start of the split (Meters). Only in splits.
+    #[serde(rename = "start_elevation_m")]
+    pub start_elevation: Option<f64>,
+
+    /// Average heart rate (Beats per Minute). Only in split summaries.
+    #[serde(rename = "heartrate_avg_bpm")]
+    pub heartrate_avg: Option<i64>,
+
+    /// Maximum heart rate (Beats per Minute). Only in split summaries.
+    #[serde(rename = "heartrate_max_bpm")]
+    pub heartrate_max: Option<i64>,
+
+    /// Calories burned.
+    #[serde(rename = "calories")]
+    pub calories: Option<i64>,
+
+    /// Latitude of the split start. Only in splits.
+    #[serde(rename = "lat_start_deg")]
+    pub lat_start: Option<f64>,
+
+    /// Longitude of the split start. Only in splits.
+    #[serde(rename = "lon_start_deg")]
+    pub lon_start: Option<f64>,
+
+    /// Latitude of the split end. Only in splits.
+    #[serde(rename = "lat_end_deg")]
+    pub lat_end: Option<f64>,
+
+    /// Longitude of the split end. Only in splits.
+    #[serde(rename = "lon_end_deg")]
+    pub lon_end: Option<f64>,
+
+    /// The UUID of the activity the split belongs to.
+    #[serde(rename = "activity_uuid")]
+    pub activity_uuid: Option<Uuid>,
+}
+
+impl FITSplit {
+    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Parses a Split or Split Summary message into a new `FITSplit`.
+    ///
+    /// # Parameters
+    ///
+    /// - `fields: &[FitDataField]` -- The fields of the message.
+    /// - `message: &str` -- The kind of message: `split` or `split_summary`.
+    /// - `session: &FITSession` -- Session summary information. Only used to get the file name.
+    ///
+    /// # Returns
+    ///
+    /// `Self` -- The split, with the fields that were found filled in.
+    pub fn from_fit_split(fields: &[FitDataField], message: &str, session: &FITSession) -> Self {
+        let field_map: HashMap<&str, &fitparser::Value> =
+            fields.iter().map(|x| (x.name(), x.value())).collect();
+
+        let time = |name: &str| match field_map.get(name) {
+            Some(fitparser::Value::Timestamp(ts)) => Some(*ts),
+            _ => None,
+        };
+        let number = |name: &str| field_map.get(name).and_then(map_number);
+        let position = |name: &str| {
+            field_map
+                .get(name)
+                .and_then(map_sint32)
+                .map(semicircles_to_degrees)
+        };
+
+        Self {
+            filename: session.filename.clone(),
+            message: Some(message.to_string()),
+            split_index: field_map.get("message_index").and_then(map_integer),
+            split_type: field_map.get("split_type").and_then(map_text),
+            num_splits: field_map.get("num_splits").and_then(map_integer),
+            start_time: time("start_time"),
+            end_time: time("end_time"),
+            elapsed_time: number("total_elapsed_time"),
+            timer_time: number("total_timer_time"),
+            moving_time: number("total_moving_time"),
+            distance: number("total_distance"),
+            speed_avg: number("avg_speed"),
+            speed_max: number("max_speed"),
+            vertical_speed_avg: number("avg_vert_speed"),
+            ascent: field_map.get("total_ascent").and_then(map_integer),
+            descent: field_map.get("total_descent").and_then(map_integer),
+            start_elevation: number("start_elevation"),
+            heartrate_avg: field_map.get("avg_heart_rate").and_then(map_integer),
+            heartrate_max: field_map.get("max_heart_rate").and_then(map_integer),
+            calories: field_map.get("total_calories").and_then(map_integer),
+            lat_start: position("start_position_lat"),
+            lon_start: position("start_position_long"),
+            lat_end: position("end_position_lat"),
+            lon_end: position("end_position_long"),
+            activity_uuid: None,
+        }
+    }
+
+    /// Whether this is the totals of the splits of a kind, rather than a single split.
+    #[must_use]
+    pub fn is_summary(&self) -> bool {
+        self.message.as_deref() == Some("split_summary")
+    }
+}
+
+/// Maps a value to a number. Scaled values are decoded as floats, and the others as integers of their size.
+fn map_number(v: &&fitparser::Value) -> Option<f64> {
+    map_float64(v).or_else(|| i32::try_from(map_integer(v)?).ok().map(f64::from))
+}
+
+#[cfg(test)]
+/// Tests for the split module
+mod tests {
+    use super::*;
+    use fitparser::Value;
+
+    #[test]
+    /// Test parsing a split and a split summary
+    fn test_from_fit_split() {
+        let session = FITSession::with_filename("run.fit");
+        let field =
+            |name: &str, value: Value| FitDataField::new(name.to_string(), 0, value, String::new());
+
+        let split = FITSplit::from_fit_split(
+            &[
+                field("message_index", Value::UInt16(3)),
+                field("split_type", Value::String("walk_active".to_string())),
+                field("total_timer_time", Value::Float64(62.5)),
+                field("total_distance", Value::Float64(95.2)),
+                field("total_ascent", Value::UInt16(2)),
+                field("start_position_lat", Value::SInt32(1 << 30)),
+            ],
+            "split",
+            &session,
+        );
+        assert!(!split.is_summary());
+        assert_eq!(split.filename.as_deref(), Some("run.fit"));
+        assert_eq!(split.split_index, Some(3));
+        assert_eq!(split.split_type.as_deref(), Some("walk_active"));
+        assert_eq!(split.timer_time, Some(62.5));
+        assert_eq!(split.distance, Some(95.2));
+        assert_eq!(split.ascent, Some(2));
+        assert_eq!(split.lat_start, Some(90.0));
+        assert_eq!(split.num_splits, None);
+
+        let summary = FITSplit::from_fit_split(
+            &[
+                field("num_splits", Value::UInt16(12)),
+                field("avg_heart_rate", Value::UInt8(141)),
+                field("total_calories", Value::UInt32(410)),
+            ],
+            "split_summary",
+            &session,
+        );
+        assert!(summary.is_summary());
+        assert_eq!(summary.num_splits, Some(12));
+        assert_eq!(summary.heartrate_avg, Some(141));
+        assert_eq!(summary.calories, Some(410));
+    }
+}
@@ -0,0 +1,514 @@
+//! Writes a FIT activity back out as a FIT file, with the records, laps, sessions and an Activity message. Only the
+//! fields the tools read are written, so the file holds the numbers of the activity rather than a copy of the original
+//! messages. The names of the manufacturer and product can't be turned back into the numbers FIT uses, so the file is
+//! marked as made by a development device.
+
+use chrono::{DateTime, Local};
+use std::error::Error;
+use std::io::Write;
+
+use crate::fit::constfunc::degrees_to_semicircles;
+use crate::{normalized_sport, Duration, FITActivity, FITLap, FITRecord, FITSession};
+
+/// The seconds from the Unix epoch to the FIT epoch, 1989-12-31 00:00:00 UTC.
+const FIT_EPOCH_OFFSET: i64 = 631_065_600;
+
+/// FIT protocol version 2.0.
+const PROTOCOL_VERSION: u8 = 0x20;
+
+/// The FIT profile version the messages follow, 21.32.
+const PROFILE_VERSION: u16 = 2132;
+
+/// The size of the file header in bytes.
+const HEADER_SIZE: u8 = 14;
+
+/// The manufacturer number of a development device.
+pub(crate) const MANUFACTURER_DEVELOPMENT: i64 = 255;
+
+/// The nibble lookup table of the FIT CRC.
+const CRC_TABLE: [u16; 16] = [
+    0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800, 0xB401,
+    0x5000, 0x9C01, 0x8801, 0x4400,
+];
+
+/// The FIT base types of the fields written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BaseType {
+    Enum,
+    Uint8,
+    Uint16,
+    Sint32,
+    Uint32,
+    /// Text of up to the number of bytes given, including the terminating zero.
+    String(u8),
+}
+
+impl BaseType {
+    /// The number of the base type in the definition messages.
+    const fn id(self) -> u8 {
+        match self {
+            Self::Enum => 0x00,
+            Self::Uint8 => 0x02,
+            Self::Uint16 => 0x84,
+            Self::Sint32 => 0x85,
+            Self::Uint32 => 0x86,
+            Self::String(_) => 0x07,
+        }
+    }
+
+    /// The size of a value in bytes.
+    const fn size(self) -> u8 {
+        match self {
+            Self::Enum | Self::Uint8 => 1,
+            Self::Uint16 => 2,
+            Self::Sint32 | Self::Uint32 => 4,
+            Self::String(size) => size,
+        }
+    }
+
+    /// Adds the value in little endian order. Missing values, and values that don't fit, are written as the invalid
+    /// value of the type.
+    fn encode(self, value: Option<i64>, bytes: &mut Vec<u8>) {
+        match self {
+            Self::Enum | Self::Uint8 => {
+                bytes.push(value.and_then(|v| u8::try_from(v).ok()).unwrap_or(u8::MAX));
+            }
+            Self::Uint16 => bytes.extend(
+                value
+                    .and_then(|v| u16::try_from(v).ok())
+                    .unwrap_or(u16::MAX)
+                    .to_le_bytes(),
+            ),
+            Self::Sint32 => bytes.extend(
+                value
+                    .and_then(|v| i32::try_from(v).ok())
+                    .unwrap_or(i32::MAX)
+                    .to_le_bytes(),
+            ),
+            Self::Uint32 => bytes.extend(
+                value
+                    .and_then(|v| u32::try_from(v).ok())
+                    .unwrap_or(u32::MAX)
+                    .to_le_bytes(),
+            ),
+            Self::String(size) => encode_text("", size, bytes),
+        }
+    }
+}
+
+/// Adds the text, cut short at a character boundary so it fits with the terminating zero, and padded with zeros.
+fn encode_text(text: &str, size: u8, bytes: &mut Vec<u8>) {
+    let size = usize::from(size);
+    let mut end = text.len().min(size.saturating_sub(1));
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    bytes.extend(&text.as_bytes()[..end]);
+    bytes.resize(bytes.len() + size - end, 0);
+}
+
+/// A message type: the global message number and the field numbers and types written.
+pub(crate) struct MessageType {
+    /// The local message number the type is defined as in the file.
+    pub(crate) local: u8,
+    /// The global message number from the FIT profile.
+    pub(crate) global: u16,
+    /// The field numbers and base types, in the order the values are given.
+    pub(crate) fields: &'static [(u8, BaseType)],
+}
+
+/// File ID: type, manufacturer, product and time created.
+pub(crate) const FILE_ID: MessageType = MessageType {
+    local: 0,
+    global: 0,
+    fields: &[
+        (0, BaseType::Enum),
+        (1, BaseType::Uint16),
+        (2, BaseType::Uint16),
+        (4, BaseType::Uint32),
+    ],
+};
+
+/// Record: timestamp, position, heart rate, cadence, distance, power, speed and altitude.
+const RECORD: MessageType = MessageType {
+    local: 1,
+    global: 20,
+    fields: &[
+        (253, BaseType::Uint32),
+        (0, BaseType::Sint32),
+        (1, BaseType::Sint32),
+        (3, BaseType::Uint8),
+        (4, BaseType::Uint8),
+        (5, BaseType::Uint32),
+        (7, BaseType::Uint16),
+        (73, BaseType::Uint32),
+        (78, BaseType::Uint32),
+    ],
+};
+
+/// Lap: timestamp, event, event type, start time, elapsed and timer time, distance, calories, heart rate, cadence,
+/// power, ascent, descent and speed.
+const LAP: MessageType = MessageType {
+    local: 2,
+    global: 19,
+    fields: &[
+        (253, BaseType::Uint32),
+        (0, BaseType::Enum),
+        (1, BaseType::Enum),
+        (2, BaseType::Uint32),
+        (7, BaseType::Uint32),
+        (8, BaseType::Uint32),
+        (9, BaseType::Uint32),
+        (11, BaseType::Uint16),
+        (15, BaseType::Uint8),
+        (16, BaseType::Uint8),
+        (17, BaseType::Uint8),
+        (18, BaseType::Uint8),
+        (19, BaseType::Uint16),
+        (20, BaseType::Uint16),
+        (21, BaseType::Uint16),
+        (22, BaseType::Uint16),
+        (110, BaseType::Uint32),
+        (111, BaseType::Uint32),
+    ],
+};
+
+/// Session: as the lap, along with the sport and the number of laps.
+const SESSION: MessageType = MessageType {
+    local: 3,
+    global: 18,
+    fields: &[
+        (253, BaseType::Uint32),
+        (0, BaseType::Enum),
+        (1, BaseType::Enum),
+        (2, BaseType::Uint32),
+        (5, BaseType::Enum),
+        (7, BaseType::Uint32),
+        (8, BaseType::Uint32),
+        (9, BaseType::Uint32),
+        (11, BaseType::Uint16),
+        (16, BaseType::Uint8),
+        (17, BaseType::Uint8),
+        (18, BaseType::Uint8),
+        (19, BaseType::Uint8),
+        (20, BaseType::Uint16),
+        (21, BaseType::Uint16),
+        (22, BaseType::Uint16),
+        (23, BaseType::Uint16),
+        (26, BaseType::Uint16),
+        (124, BaseType::Uint32),
+        (125, BaseType::Uint32),
+    ],
+};
+
+/// Activity: timestamp, timer time, number of sessions, type, event and event type.
+const ACTIVITY: MessageType = MessageType {
+    local: 4,
+    global: 34,
+    fields: &[
+        (253, BaseType::Uint32),
+        (0, BaseType::Uint32),
+        (1, BaseType::Uint16),
+        (2, BaseType::Enum),
+        (3, BaseType::Enum),
+        (4, BaseType::Enum),
+    ],
+};
+
+/// The `file` type of an activity file.
+const FILE_ACTIVITY: i64 = 4;
+
+/// The `event` values of the lap, session and activity messages.
+const EVENT_SESSION: i64 = 8;
+const EVENT_LAP: i64 = 9;
+const EVENT_ACTIVITY: i64 = 26;
+
+/// The `event_type` of the end of a lap, session or activity.
+pub(crate) const EVENT_TYPE_STOP: i64 = 1;
+
+/// The messages of a FIT file, without the header and CRC.
+#[derive(Debug, Default)]
+pub(crate) struct FitData {
+    /// The messages written so far.
+    bytes: Vec<u8>,
+    /// The local message numbers defined so far.
+    defined: Vec<u8>,
+}
+
+impl FitData {
+    /// Adds a message, with the definition of its type before the first one.
+    pub(crate) fn write(&mut self, message: &MessageType, values: &[Option<i64>]) {
+        self.write_named(message, values, "");
+    }
+
+    /// Adds a message with a text field, e.g. a name. The values are given for the other fields.
+    pub(crate) fn write_named(
+        &mut self,
+        message: &MessageType,
+        values: &[Option<i64>],
+        text: &str,
+    ) {
+        debug_assert_eq!(
+            message
+                .fields
+                .iter()
+                .filter(|(_, base_type)| !matches!(base_type, BaseType::String(_)))
+                .count(),
+            values.len()
+        );
+        if !self.defined.contains(&message.local) {
+            self.bytes.push(0x40 | message.local);
+            self.bytes.push(0); // Reserved
+            self.bytes.push(0); // Little endian
+            self.bytes.extend(message.global.to_le_bytes());
+            self.bytes
+                .push(u8::try_from(message.fields.len()).unwrap_or(u8::MAX));
+            for (number, base_type) in message.fields {
+                self.bytes
+                    .extend([*number, base_type.size(), base_type.id()]);
+            }
+            self.defined.push(message.local);
+        }
+
+        self.bytes.push(message.local);
+        let mut values = values.iter();
+        for (_, base_type) in message.fields {
+            match base_type {
+                BaseType::String(size) => encode_text(text, *size, &mut self.bytes),
+                _ => base_type.encode(values.next().copied().flatten(), &mut self.bytes),
+            }
+        }
+    }
+
+    /// The complete file: the header, the messages and the CRC.
+    pub(crate) fn into_file(self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut file = vec![HEADER_SIZE, PROTOCOL_VERSION];
+        file.extend(PROFILE_VERSION.to_le_bytes());
+        file.extend(u32::try_from(self.bytes.len())?.to_le_bytes());
+        file.extend(b".FIT");
+        file.extend(crc(&file).to_le_bytes());
+        file.extend(self.bytes);
+        file.extend(crc(&file).to_le_bytes());
+        Ok(file)
+    }
+}
+
+/// The FIT CRC of the bytes.
+fn crc(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, byte| {
+        let crc =
+            (crc >> 4) ^ CRC_TABLE[usize::from(crc & 0xF)] ^ CRC_TABLE[usize::from(byte & 0xF)];
+        (crc >> 4) ^ CRC_TABLE[usize::from(crc & 0xF)] ^ CRC_TABLE[usize::from(byte >> 4)]
+    })
+}
+
+/// A time as seconds since the FIT epoch.
+pub(crate) fn fit_time(time: Option<DateTime<Local>>) -> Option<i64> {
+    time.map(|time| time.timestamp() - FIT_EPOCH_OFFSET)
+}
+
+/// A value with the offset added and multiplied by the scale, as FIT stores it.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn scaled(value: Option<f64>, scale: f64, offset: f64) -> Option<i64> {
+    value
+        .filter(|value| value.is_finite())
+        .map(|value| ((value + offset) * scale).round() as i64)
+}
+
+/// A duration in milliseconds.
+fn millis(duration: Option<Duration>) -> Option<i64> {
+    duration.and_then(|duration| i64::try_from(duration.0.as_millis()).ok())
+}
+
+/// A latitude or longitude in semicircles.
+pub(crate) fn semicircles(degrees: Option<f64>) -> Option<i64> {
+    degrees.and_then(degrees_to_semicircles).map(i64::from)
+}
+
+/// The FIT `sport` of the activity type, or generic if there isn't one for it.
+pub(crate) fn fit_sport(activity_type: Option<&str>) -> i64 {
+    match normalized_sport(activity_type.unwrap_or_default()).as_str() {
+        "running" => 1,
+        "cycling" => 2,
+        "swimming" => 5,
+        "training" => 10,
+        "walking" => 11,
+        "cross_country_skiing" => 12,
+        "rowing" | "indoor_rowing" => 15,
+        "hiking" => 17,
+        "paddling" => 19,
+        _ => 0,
+    }
+}
+
+/// The values of a record, in the order of `RECORD`.
+fn record_values(record: &FITRecord) -> Vec<Option<i64>> {
+    vec![
+        fit_time(record.timestamp),
+        semicircles(record.lat),
+        semicircles(record.lon),
+        record.heartrate.map(i64::from),
+        record.cadence.map(i64::from),
+        scaled(record.distance.map(|d| d.value), 100.0, 0.0),
+        record.power.map(i64::from),
+        scaled(record.speed.map(|s| s.value), 1_000.0, 0.0),
+        scaled(record.altitude.map(|a| a.value), 5.0, 500.0),
+    ]
+}
+
+/// The values of a lap, in the order of `LAP`.
+fn lap_values(lap: &FITLap) -> Vec<Option<i64>> {
+    vec![
+        fit_time(lap.finish_time.or(lap.start_time)),
+        Some(EVENT_LAP),
+        Some(EVENT_TYPE_STOP),
+        fit_time(lap.start_time),
+        millis(lap.duration),
+        millis(lap.duration_active.or(lap.duration)),
+        scaled(lap.distance.map(|d| d.value), 100.0, 0.0),
+        lap.calories.map(i64::from),
+        lap.heartrate_avg.map(i64::from),
+        lap.heartrate_max.map(i64::from),
+        lap.cadence_avg.map(i64::from),
+        lap.cadence_max.map(i64::from),
+        lap.power_avg.map(i64::from),
+        lap.power_max.map(i64::from),
+        lap.ascent.map(|a| i64::from(a.value)),
+        lap.descent.map(|d| i64::from(d.value)),
+        scaled(lap.speed_avg.map(|s| s.value), 1_000.0, 0.0),
+        scaled(lap.speed_max.map(|s| s.value), 1_000.0, 0.0),
+    ]
+}
+
+/// The values of a session, in the order of `SESSION`.
+fn session_values(session: &FITSession) -> Vec<Option<i64>> {
+    vec![
+        fit_time(session.finish_time.or(session.start_time)),
+        Some(EVENT_SESSION),
+        Some(EVENT_TYPE_STOP),
+        fit_time(session.start_time),
+        Some(fit_sport(session.activity_type.as_deref())),
+        millis(session.duration),
+        millis(session.duration_active.or(session.duration)),
+        scaled(session.distance.map(|d| d.value), 100.0, 0.0),
+        session.calories.map(i64::from),
+        session.heartrate_avg.map(i64::from),
+        session.heartrate_max.map(i64::from),
+        session.cadence_avg.map(i64::from),
+        session.cadence_max.map(i64::from),
+        session.power_avg.map(i64::from),
+        session.power_max.map(i64::from),
+        session.ascent.map(|a| i64::from(a.value)),
+        session.descent.map(|d| i64::from(d.value)),
+        session.num_laps.map(i64::from),
+        scaled(session.speed_avg.map(|s| s.value), 1_000.0, 0.0),
+        scaled(session.speed_max.map(|s| s.value), 1_000.0, 0.0),
+    ]
+}
+
+impl FITActivity {
+    /// Writes the activity as a FIT file to any writer: the records, the laps, a session per sport and the Activity
+    /// message. The numbers worked out by the tools, such as the pacing, aren't written, since they are worked out
+    /// again when the file is read.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the FIT file is written.
+    ///
+    /// # Errors
+    ///
+    /// Writing may fail, or the activity may be too large for a FIT file.
+    pub fn write_fit<W: Write>(&self, mut writer: W) -> Result<(), Box<dyn Error>> {
+        let sessions = self.summary_sessions();
+        let start = self
+            .session
+            .start_time
+            .or_else(|| self.records.iter().find_map(|record| record.timestamp));
+        let finish = sessions
+            .iter()
+            .filter_map(|session| session.finish_time)
+            .chain(self.records.iter().filter_map(|record| record.timestamp))
+            .max();
+
+        let mut data = FitData::default();
+        data.write(
+            &FILE_ID,
+            &[
+                Some(FILE_ACTIVITY),
+                Some(MANUFACTURER_DEVELOPMENT),
+                Some(0),
+                fit_time(self.session.time_created.or(start)),
+            ],
+        );
+        for record in &self.records {
+            data.write(&RECORD, &record_values(record));
+        }
+        for lap in &self.laps {
+            data.write(&LAP, &lap_values(lap));
+        }
+        for session in &sessions {
+            data.write(&SESSION, &session_values(session));
+        }
+        data.write(
+            &ACTIVITY,
+            &[
+                fit_time(finish),
+                millis(Duration::checked_sum(sessions.iter().filter_map(
+                    |session| session.duration_active.or(session.duration),
+                ))),
+                i64::try_from(sessions.len()).ok(),
+                Some(0), // Manual
+                Some(EVENT_ACTIVITY),
+                Some(EVENT_TYPE_STOP),
+            ],
+        );
+
+        writer.write_all(&data.into_file()?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+/// Tests for the to_fit module
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test that the FIT written can be read back with the same records and session totals
+    fn test_write_fit() {
+        let activity = FITActivity::from_file("../data/test.fit").unwrap();
+        let mut fit = Vec::new();
+        activity.write_fit(&mut fit).unwrap();
+
+        let written = FITActivity::from_reader(&mut fit.as_slice(), "written.fit").unwrap();
+        assert_eq!(written.records.len(), activity.records.len());
+        assert_eq!(written.laps.len(), activity.laps.len());
+        assert_eq!(
+            written.session.activity_type,
+            activity.session.activity_type
+        );
+        assert_eq!(written.session.start_time, activity.session.start_time);
+        assert_eq!(
+            written.session.heartrate_avg,
+            activity.session.heartrate_avg
+        );
+        assert_eq!(written.session.manufacturer.as_deref(), Some("development"));
+
+        let (first, written_first) = (&activity.records[0], &written.records[0]);
+        assert_eq!(written_first.timestamp, first.timestamp);
+        assert_eq!(written_first.heartrate, first.heartrate);
+        assert!(
+            (written_first.distance.unwrap().value - first.distance.unwrap().value).abs() < 0.01
+        );
+    }
+
+    #[test]
+    /// Test the CRC against the header of a known file
+    fn test_crc() {
+        assert_eq!(crc(&[]), 0);
+        let header = std::fs::read("../data/test.fit").unwrap();
+        let stored = u16::from_le_bytes([header[12], header[13]]);
+        assert!(stored == 0 || stored == crc(&header[..12]));
+    }
+}
@@ -0,0 +1,140 @@
+//! Converts the records of a FIT activity to GPX. Each session becomes a track and each lap a track segment, or each lap
+//! a track of its own depending on the `LapMapping` set for the run, the same way as for TCX files. The records are
+//! placed in the laps by their timestamps.
+
+use chrono::{DateTime, Local};
+use std::error::Error;
+use std::io::Write;
+
+use crate::lap_mapping::lap_mapping;
+use crate::tcx::to_gpx::{write_gpx_tracks, GpxPoint};
+use crate::{FITActivity, LapMapping, TrackPoint};
+
+impl FITActivity {
+    /// Writes the records as GPX to any writer. Each session becomes a track named and colored after the sport, and
+    /// each lap becomes a track segment -- or, with `LapMapping::Tracks`, each lap becomes a track named after the
+    /// sport and the lap number. Records without a position can't be written to GPX, so they are left out.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the GPX is written.
+    ///
+    /// # Returns
+    ///
+    /// `Result<usize, Box<dyn Error>>` -- The number of records left out because they have no position.
+    ///
+    /// # Errors
+    ///
+    /// Writing may fail.
+    pub fn write_gpx<W: Write>(&self, writer: W) -> Result<usize, Box<dyn Error>> {
+        self.write_gpx_mapped(writer, lap_mapping())
+    }
+
+    /// Writes the records as GPX with the laps laid out as given, rather than as set for the run.
+    fn write_gpx_mapped<W: Write>(
+        &self,
+        writer: W,
+        mapping: LapMapping,
+    ) -> Result<usize, Box<dyn Error>> {
+        let points = self.records.iter().map(|rec| {
+            let sport = self
+                .sessions
+                .iter()
+                .find(|session| {
+                    session.session_num.is_some() && session.session_num == rec.session_num
+                })
+                .unwrap_or(&self.session)
+                .activity_type
+                .as_deref()
+                .unwrap_or("unknown");
+            GpxPoint {
+                activity: rec.session_num.map_or(0, usize::from),
+                lap: self.lap_of(rec.timestamp),
+                sport,
+                point: TrackPoint::from(rec),
+            }
+        });
+        write_gpx_tracks(writer, points, self.session.start_time, mapping)
+    }
+
+    /// The number of the lap recorded at the time given, which is the last lap started by then. Records before the
+    /// first lap, or without a timestamp, are placed in the first lap.
+    fn lap_of(&self, time: Option<DateTime<Local>>) -> usize {
+        let index = time.map_or(0, |time| {
+            self.laps
+                .iter()
+                .rposition(|lap| lap.start_time.is_some_and(|start| start <= time))
+                .unwrap_or(0)
+        });
+        self.laps
+            .get(index)
+            .and_then(|lap| lap.lap_num)
+            .and_then(|num| usize::try_from(num).ok())
+            .unwrap_or(index + 1)
+    }
+}
+
+#[cfg(test)]
+/// Tests for the to_gpx module
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use crate::{FITActivity, FITLap, FITRecord, LapMapping};
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test that the GPX written can be read back with the same track points
+    fn test_write_gpx() {
+        let activity = FITActivity::from_file("../data/faulty.fit").unwrap();
+        let mut gpx = Vec::new();
+        let skipped = activity.write_gpx(&mut gpx).unwrap();
+
+        let text = String::from_utf8(gpx.clone()).unwrap();
+        assert!(text.contains("<gpxtpx:hr>"));
+        assert_eq!(text.matches("<trkseg>").count(), activity.laps.len());
+
+        let read = crate::GPXActivity::from_reader(gpx.as_slice(), "faulty.gpx").unwrap();
+        let waypoints: usize = read.tracks.iter().map(|track| track.waypoints.len()).sum();
+        assert_eq!(waypoints + skipped, activity.records.len());
+    }
+
+    #[test]
+    /// Test writing each lap as a track segment, and as a track of its own
+    fn test_lap_mapping() {
+        let start = Local.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap();
+        let records: Vec<FITRecord> = (0..6)
+            .map(|secs| FITRecord {
+                timestamp: Some(start + chrono::Duration::seconds(secs)),
+                lat: Some(59.0),
+                lon: Some(10.0),
+                ..FITRecord::default()
+            })
+            .collect();
+        let lap = |num: u64, from: i64| FITLap {
+            lap_num: Some(num),
+            start_time: Some(start + chrono::Duration::seconds(from)),
+            ..FITLap::default()
+        };
+        let mut activity = FITActivity {
+            records,
+            laps: vec![lap(1, 0), lap(2, 3)],
+            ..FITActivity::default()
+        };
+        activity.session.activity_type = Some("running".to_string());
+
+        for (mapping, tracks, segments) in
+            [(LapMapping::Segments, 1, 2), (LapMapping::Tracks, 2, 2)]
+        {
+            let mut gpx = Vec::new();
+            activity.write_gpx_mapped(&mut gpx, mapping).unwrap();
+
+            let text = String::from_utf8(gpx).unwrap();
+            assert_eq!(text.matches("<trk>").count(), tracks);
+            assert_eq!(text.matches("<trkseg>").count(), segments);
+            assert_eq!(text.matches("<trkpt ").count(), 6);
+            if mapping == LapMapping::Tracks {
+                assert!(text.contains("<name>running lap 2</name>"));
+            }
+        }
+    }
+}
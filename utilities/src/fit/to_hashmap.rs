@@ -1,8 +1,12 @@
+use crate::date_source::{date_source, insert_date_tokens};
 use crate::fit::session::FITSession;
-use chrono::{Datelike, Timelike};
+use crate::input::open_input;
+use crate::placeholder::placeholder;
+use crate::privacy::output_serial;
+use crate::summary_tokens::SummaryTokens;
 use convert_case::{Case, Casing};
 use fitparser::profile::field_types::MesgNum;
-use std::{collections::HashMap, error::Error, fs::File};
+use std::{collections::HashMap, error::Error};
 
 /// Process a .FIT file and return the results
 ///
@@ -26,7 +30,7 @@ pub fn fit_to_hashmap(filename: &str) -> Result<HashMap<String, String>, Box<dyn
     let mut values = HashMap::<String, String>::new();
 
     // open the file and deserialize it - return error if unable.
-    let mut fp = File::open(filename)?;
+    let mut fp = open_input(filename)?;
     let file = fitparser::from_reader(&mut fp)?;
 
     // Create a bunch of placeholder variables.
@@ -47,107 +51,73 @@ pub fn fit_to_hashmap(filename: &str) -> Result<HashMap<String, String>, Box<dyn
                 num_sessions += 1;
                 my_session.num_sessions = Some(num_sessions);
             }
+            MesgNum::Activity => my_session.parse_activity(data.fields()),
             _ => (),
         } // match
     } // for data
 
+    // Use the device's local time if the file tells us what it is
+    let time_created = my_session.time_created_local();
+    let start = my_session
+        .start_time
+        .as_ref()
+        .map(|st| my_session.to_device_local(st));
+
     // Push the data into the HashMap for later use.
     let mf = my_session
         .manufacturer
-        .unwrap_or_else(|| "unknown".to_string())
-        .to_case(Case::Title);
+        .map_or_else(|| placeholder("Unknown"), |mf| mf.to_case(Case::Title));
     values.insert("%manufacturer".to_string(), mf.clone());
     values.insert("%mf".to_string(), mf);
 
     let pr = my_session
         .product
-        .unwrap_or_else(|| "unknown".to_string())
-        .to_case(Case::Title);
+        .map_or_else(|| placeholder("Unknown"), |pr| pr.to_case(Case::Title));
     values.insert("%product".to_string(), pr.clone());
     values.insert("%pr".to_string(), pr);
 
     let sn = my_session
         .serial_number
-        .unwrap_or_else(|| "unknown".to_string());
+        .as_deref()
+        .map_or_else(|| placeholder("unknown"), output_serial);
     values.insert("%serial_number".to_string(), sn.clone());
     values.insert("%sn".to_string(), sn);
 
     let ac = my_session
         .activity_type
-        .unwrap_or_else(|| "unknown".to_string())
-        .to_case(Case::Title);
+        .map_or_else(|| placeholder("Unknown"), |ac| ac.to_case(Case::Title));
     values.insert("%activity".to_string(), ac.clone());
     values.insert("%at".to_string(), ac);
 
     let ad = my_session
         .activity_detailed
-        .unwrap_or_else(|| "unknown".to_string())
-        .to_case(Case::Title);
+        .map_or_else(|| placeholder("Unknown"), |ad| ad.to_case(Case::Title));
     values.insert("%activity_detailed".to_string(), ad.clone());
     values.insert("%ad".to_string(), ad);
 
-    if let Some(tc) = my_session.time_created {
-        values.insert("%year".to_string(), format!("{:04}", tc.year()));
-        values.insert("%yr".to_string(), format!("{:04}", tc.year()));
-        values.insert("%month".to_string(), format!("{:02}", tc.month()));
-        values.insert("%mn".to_string(), format!("{:02}", tc.month()));
-        values.insert("%day".to_string(), format!("{:02}", tc.day()));
-        values.insert("%dy".to_string(), format!("{:02}", tc.day()));
-
-        values.insert("%hour".to_string(), format!("{:02}", tc.hour()));
-        values.insert("%hr".to_string(), format!("{:02}", tc.hour()));
-        values.insert("%24hour".to_string(), format!("{:02}", tc.hour()));
-        values.insert("%24".to_string(), format!("{:02}", tc.hour()));
-
-        let (am, hrs) = tc.hour12();
-        let hr = format!("{hrs:02}");
-        values.insert("%hour12".to_string(), hr.clone());
-        values.insert("%12".to_string(), hr);
-        if am {
-            values.insert("%ampm".to_string(), "pm".to_string());
-            values.insert("%ap".to_string(), "pm".to_string());
-        } else {
-            values.insert("%ampm".to_string(), "am".to_string());
-            values.insert("%ap".to_string(), "am".to_string());
-        }
-
-        values.insert("%minute".to_string(), format!("{:02}", tc.minute()));
-        values.insert("%mt".to_string(), format!("{:02}", tc.minute()));
-        values.insert("%second".to_string(), format!("{:02}", tc.second()));
-        values.insert("%sc".to_string(), format!("{:02}", tc.second()));
-        values.insert("%weekday".to_string(), tc.weekday().to_string());
-        values.insert("%wd".to_string(), tc.weekday().to_string());
-    } else {
-        values.insert("%year".to_string(), "0000".to_string());
-        values.insert("%yr".to_string(), "0000".to_string());
-        values.insert("%month".to_string(), "00".to_string());
-        values.insert("%mn".to_string(), "00".to_string());
-        values.insert("%day".to_string(), "00".to_string());
-        values.insert("%dy".to_string(), "00".to_string());
-        values.insert("%hour".to_string(), "00".to_string());
-        values.insert("%hr".to_string(), "00".to_string());
-        values.insert("%24hour".to_string(), "00".to_string());
-        values.insert("%24".to_string(), "00".to_string());
-        values.insert("%12hour".to_string(), "00".to_string());
-        values.insert("%12".to_string(), "00".to_string());
-        values.insert("%ampm".to_string(), "ampm".to_string());
-        values.insert("%ap".to_string(), "ampm".to_string());
-        values.insert("%minute".to_string(), "00".to_string());
-        values.insert("%mt".to_string(), "00".to_string());
-        values.insert("%second".to_string(), "00".to_string());
-        values.insert("%sc".to_string(), "00".to_string());
-        values.insert("%weekday".to_string(), "00".to_string());
-        values.insert("%wd".to_string(), "00".to_string());
-    }
+    insert_date_tokens(
+        &mut values,
+        date_source().pick(start, time_created, filename),
+    );
 
     if let Some(dur) = my_session.duration {
         values.insert("%duration".to_string(), dur.to_string());
         values.insert("%du".to_string(), dur.to_string());
     } else {
-        values.insert("%duration".to_string(), "0".to_string());
-        values.insert("%du".to_string(), "0".to_string());
+        values.insert("%duration".to_string(), placeholder("0"));
+        values.insert("%du".to_string(), placeholder("0"));
     }
 
+    SummaryTokens {
+        distance_m: my_session.distance.map(|distance| distance.value),
+        calories: my_session.calories,
+        avg_hr: my_session.heartrate_avg.map(f64::from),
+        max_hr: my_session.heartrate_max.map(f64::from),
+        elevation_gain_m: my_session.ascent.map(|ascent| f64::from(ascent.value)),
+        duration: my_session.duration,
+    }
+    .insert(&mut values);
+
     log::debug!("values = {values:?}");
 
     // return safely
@@ -155,12 +125,12 @@ pub fn fit_to_hashmap(filename: &str) -> Result<HashMap<String, String>, Box<dyn
 }
 
 #[cfg(test)]
-///
+/// Tests for the to_hashmap module
 mod tests {
     use super::*;
 
     #[test]
-    ///
+    /// Test fit_to_hashmap() against one of the data files
     fn test_process_fit() {
         let filename = "../data/test.fit";
         let fm = fit_to_hashmap(filename).unwrap();
@@ -168,5 +138,14 @@ mod tests {
         // File contents only get printed if run with cargo test -- --nocapture
         println!("tm = {fm:?}");
         println!("tm.len() = {}", fm.len());
+
+        // The time tokens use the device's local time (UTC-8), not the time zone of this computer
+        assert_eq!(fm["%year"], "2020");
+        assert_eq!(fm["%hour"], "08");
+        assert_eq!(fm["%minute"], "09");
+        assert_eq!(fm["%calories"], "540");
+        assert_eq!(fm["%avg_hr"], "151");
+        assert_eq!(fm["%max_hr"], "162");
+        assert_eq!(fm["%duration_hms"], "00h32m17s");
     }
 }
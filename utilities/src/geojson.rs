@@ -0,0 +1,167 @@
+//! Writes the geometry of an activity as a GeoJSON `FeatureCollection`, for maps such as Leaflet, Mapbox and QGIS.
+//! Each GPX track or FIT lap becomes a `Feature` with a `LineString` geometry, and its start time, distance and sport
+//! as properties.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::Write;
+
+/// The properties of a track or lap in the GeoJSON file.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct GeoJsonProperties {
+    /// The name of the track or lap, e.g. `Lap 2`.
+    pub name: Option<String>,
+
+    /// When the track or lap started.
+    pub start_time: Option<DateTime<Local>>,
+
+    /// The distance covered in meters.
+    pub distance_m: Option<f64>,
+
+    /// The sport, e.g. `running`.
+    pub sport: Option<String>,
+}
+
+/// A GeoJSON position, which is longitude first, with the altitude added if known.
+///
+/// # Arguments
+///
+/// - `lat: Option<f64>` -- The latitude in degrees.
+/// - `lon: Option<f64>` -- The longitude in degrees.
+/// - `altitude: Option<f64>` -- The altitude in meters.
+///
+/// # Returns
+///
+/// `Option<Vec<f64>>` -- The position, or `None` if the latitude or longitude is missing.
+#[must_use]
+pub fn geojson_position(
+    lat: Option<f64>,
+    lon: Option<f64>,
+    altitude: Option<f64>,
+) -> Option<Vec<f64>> {
+    let (lat, lon) = (lat?, lon?);
+    Some(altitude.map_or_else(|| vec![lon, lat], |alt| vec![lon, lat, alt]))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Builds a `FeatureCollection` with a `LineString` feature for each track or lap. Tracks or laps with fewer than two
+/// positions are left out, since a line needs at least two.
+///
+/// # Arguments
+///
+/// `features: I` -- The properties and positions of each track or lap, in the order they should be drawn.
+///
+/// # Returns
+///
+/// `Value` -- The `FeatureCollection` as JSON.
+pub fn feature_collection<I>(features: I) -> Value
+where
+    I: IntoIterator<Item = (GeoJsonProperties, Vec<Vec<f64>>)>,
+{
+    let features: Vec<Value> = features
+        .into_iter()
+        .filter(|(_, coordinates)| coordinates.len() > 1)
+        .map(|(properties, coordinates)| {
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": coordinates,
+                },
+                "properties": properties,
+            })
+        })
+        .collect();
+
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+/// Writes the `FeatureCollection` to any writer.
+///
+/// # Arguments
+///
+/// - `collection: &Value` -- The `FeatureCollection` from `feature_collection()`.
+/// - `writer: W` -- Where the GeoJSON is written.
+///
+/// # Errors
+///
+/// Serializing or writing the GeoJSON may fail.
+pub fn write_geojson<W: Write>(collection: &Value, mut writer: W) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(&mut writer, collection)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the `FeatureCollection` to a file.
+///
+/// # Arguments
+///
+/// - `collection: &Value` -- The `FeatureCollection` from `feature_collection()`.
+/// - `filename: &str` -- The file to write, usually named after the activity file with `.geojson` added.
+///
+/// # Errors
+///
+/// Creating or writing the file may fail.
+#[cfg(feature = "fs")]
+pub fn export_geojson(collection: &Value, filename: &str) -> Result<(), Box<dyn Error>> {
+    log::trace!("geojson::export_geojson() -- Writing {filename}");
+    crate::output_naming::write_output(std::path::Path::new(filename), |file| {
+        write_geojson(collection, std::io::BufWriter::new(file))
+    })
+}
+
+#[cfg(test)]
+/// Tests for the geojson module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the GeoJSON positions
+    fn test_geojson_position() {
+        assert_eq!(
+            geojson_position(Some(59.0), Some(10.0), None),
+            Some(vec![10.0, 59.0])
+        );
+        assert_eq!(
+            geojson_position(Some(59.0), Some(10.0), Some(12.5)),
+            Some(vec![10.0, 59.0, 12.5])
+        );
+        assert!(geojson_position(None, Some(10.0), Some(12.5)).is_none());
+    }
+
+    #[test]
+    /// Test building the collection, leaving out the tracks that aren't lines
+    fn test_feature_collection() {
+        let properties = GeoJsonProperties {
+            name: Some("Lap 1".to_string()),
+            distance_m: Some(1_000.0),
+            sport: Some("running".to_string()),
+            ..GeoJsonProperties::default()
+        };
+        let collection = feature_collection([
+            (
+                properties,
+                vec![vec![10.0, 59.0], vec![10.001, 59.001, 12.0]],
+            ),
+            (GeoJsonProperties::default(), vec![vec![10.0, 59.0]]),
+        ]);
+
+        assert_eq!(collection["type"], "FeatureCollection");
+        let features = collection["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0]["geometry"]["type"], "LineString");
+        assert_eq!(features[0]["properties"]["name"], "Lap 1");
+        assert_eq!(features[0]["properties"]["start_time"], Value::Null);
+
+        let mut geojson = Vec::new();
+        write_geojson(&collection, &mut geojson).unwrap();
+        assert!(String::from_utf8(geojson).unwrap().ends_with("}\n"));
+    }
+}
@@ -1,10 +1,24 @@
 //! A list of all the `Activity` structs (i.e.. GPX files) parsed, and associated functions.
 
-use csv::WriterBuilder;
+#[cfg(feature = "fs")]
 use std::error::Error;
-use std::path::PathBuf;
+#[cfg(feature = "fs")]
+use std::path::Path;
 
+use crate::analysis::overlap::ActivityWindow;
+#[cfg(feature = "fs")]
+use crate::atomic_file::write_atomically;
+#[cfg(feature = "fs")]
+use crate::columns::{ColumnWriter, CsvFile};
 use crate::gpx::activity::GPXActivity;
+#[cfg(feature = "fs")]
+use crate::precision::Rounded;
+#[cfg(feature = "fs")]
+use crate::summary_split::{group_by, split_filename};
+#[cfg(feature = "fs")]
+use crate::SummarySplit;
+#[cfg(feature = "fs")]
+use chrono::Datelike;
 
 /// Holds a list of all activities. Used to export session totals
 #[derive(Debug)]
@@ -21,6 +35,26 @@ impl GPXActivities {
         Self::default()
     }
 
+    /// When each activity took place and what recorded it, for finding the activities that overlap in time.
+    /// Activities without a start time or duration are left out.
+    #[must_use]
+    pub fn time_windows(&self) -> Vec<ActivityWindow> {
+        self.activities_list
+            .iter()
+            .filter_map(|act| {
+                let metadata = &act.metadata;
+                ActivityWindow::new(
+                    metadata.filename.clone().unwrap_or_default(),
+                    metadata.creator.as_deref(),
+                    metadata
+                        .time
+                        .or_else(|| act.tracks.first().and_then(|track| track.start_time)),
+                    metadata.duration,
+                )
+            })
+            .collect()
+    }
+
     /// Export the list of session information to a CSV file
     ///
     /// # Parameters
@@ -42,23 +76,70 @@ impl GPXActivities {
     /// ```
     /// activities.export_csv("gpx-summary.csv")?;
     /// ```
+    #[cfg(feature = "fs")]
     pub fn export_csv(&self, filename: &str) -> Result<(), Box<dyn Error>> {
-        // Create a buffer for the CSV. Assume that the filename is valid.
-        let mut writer = WriterBuilder::new()
-            .has_headers(true)
-            .from_path(PathBuf::from(filename))?;
+        write_csv(filename, &self.activities_list.iter().collect::<Vec<_>>())
+    }
+
+    /// Export the list of session information to one CSV file per sport, year or device. The files are named after
+    /// the summary file, e.g. `gpx-summary.running.csv`. GPX files have no device information, so the creator is used.
+    ///
+    /// # Parameters
+    ///
+    /// - `filename: &str` -- The name of the summary file the split file names are based on.
+    /// - `split: SummarySplit` -- What to split the summary by.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Vec<String>, Box<dyn Error>>` -- The names of the files written.
+    ///
+    /// # Errors
+    ///
+    /// Creating a new `WriterBuilder` may fail. Serializing the activity data may fail. Flushing the writer may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_csv_split(
+        &self,
+        filename: &str,
+        split: SummarySplit,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let groups = group_by(&self.activities_list, |activity| {
+            let metadata = &activity.metadata;
+            split.key(
+                metadata.activity.as_deref(),
+                metadata.time.map(|t| t.year()),
+                metadata.creator.as_deref(),
+            )
+        });
+
+        let mut written = Vec::with_capacity(groups.len());
+        for (key, activities) in groups {
+            let split_file = split_filename(filename, &key);
+            write_csv(&split_file, &activities)?;
+            written.push(split_file);
+        }
+
+        Ok(written)
+    }
+}
+
+/// Writes the metadata for each of the activities to a summary CSV file.
+#[cfg(feature = "fs")]
+fn write_csv(filename: &str, activities: &[&GPXActivity]) -> Result<(), Box<dyn Error>> {
+    // Create a buffer for the CSV. Assume that the filename is valid.
+    write_atomically(Path::new(filename), |file| {
+        let mut writer = ColumnWriter::new(file, CsvFile::GpxSummary);
 
         // Go through the activities list
-        for curr_activity in &self.activities_list {
+        for curr_activity in activities {
             // Write the metadata for each activity
-            writer.serialize(&curr_activity.metadata)?;
+            writer.serialize(Rounded(&curr_activity.metadata))?;
         }
 
         writer.flush()?;
 
         // Return safely
         Ok(())
-    }
+    })
 }
 
 impl Default for GPXActivities {
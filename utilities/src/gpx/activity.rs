@@ -1,19 +1,67 @@
 //! Defines the `Activity` struct which contains the parsed contents of a GPX file, and associated functions.
 use chrono::{Local, TimeZone};
-use csv::WriterBuilder;
 use gpx::Gpx;
+use serde::Serialize;
 use std::{
     error::Error,
-    fs::File,
-    io::BufReader,
-    path::{Path, PathBuf},
+    io::{Read, Write},
+    path::Path,
 };
+#[cfg(feature = "fs")]
+use std::{io::BufReader, path::PathBuf};
 
+use crate::analysis::decoupling::decoupling;
+use crate::analysis::derived::{haversine_distance, mean};
+use crate::analysis::distance_check::GpsDistance;
+use crate::analysis::gps_outliers::gps_outlier_warning;
+use crate::analysis::heat::heat_effort;
+use crate::analysis::hr_scatter::{hr_scatter, HrScatter};
+use crate::analysis::hr_zones::{hr_zones, time_in_hr_zones};
+use crate::analysis::indoor::detect_environment;
+use crate::analysis::mean_max::{mean_max, MeanMax, MeanMaxSample};
+use crate::analysis::pacing::pacing;
+use crate::analysis::recording::recording_interval;
+use crate::analysis::splits::{splits, Split, SplitPoint, SplitUnit};
+use crate::analysis::track_stats::TrackStats;
+use crate::columns::{ColumnWriter, CsvFile};
+use crate::exporters::{write_rows, ExportFormat};
+use crate::geojson::{feature_collection, GeoJsonProperties};
+use crate::gpx::extensions::GPXExtensions;
 use crate::gpx::gpxmetadata::GPXMetadata;
 use crate::gpx::route::GPXRoute;
 use crate::gpx::track::GPXTrack;
 use crate::gpx::waypoint::GPXWaypoint;
-use crate::Duration;
+#[cfg(feature = "fs")]
+use crate::input::open_input;
+#[cfg(feature = "fs")]
+use crate::output_naming::{output_path, write_output};
+use crate::placeholder::placeholder;
+use crate::precision::Rounded;
+use crate::profile_svg::{profile_points, ProfilePoint};
+use crate::units::{pace_label, unit_system, Measure, UnitSystem};
+use crate::warnings::log_warnings;
+use crate::{activity_uuid, set_extension, Duration, FITEnvironment, TrackPoint};
+
+/// The fix types that give a 3D position.
+const THREE_D_FIXES: [&str; 3] = ["ThreeDimensional", "DGPS", "PPS"];
+
+/// The shape of the single-document JSON export. The track waypoints aren't serialized with the track itself, so they
+/// are nested here.
+#[derive(Serialize)]
+struct GPXBundle<'a> {
+    session: &'a GPXMetadata,
+    tracks: Vec<GPXTrackBundle<'a>>,
+    waypoints: &'a [GPXWaypoint],
+    routes: &'a [GPXRoute],
+}
+
+/// A track along with its waypoints.
+#[derive(Serialize)]
+struct GPXTrackBundle<'a> {
+    #[serde(flatten)]
+    track: &'a GPXTrack,
+    waypoints: &'a [GPXWaypoint],
+}
 
 /// High-level construct that contains the entirety of the GPX file
 #[derive(Debug)]
@@ -30,6 +78,9 @@ pub struct GPXActivity {
 
     /// A list of tracks with waypoints indicating point-in-time position and other data.
     pub tracks: Vec<GPXTrack>,
+
+    /// Anything noteworthy found while parsing the file, e.g. tracks without waypoints.
+    pub warnings: Vec<String>,
 }
 
 impl GPXActivity {
@@ -60,10 +111,33 @@ impl GPXActivity {
     ///
     /// let my_activity = Activity::from_file("running.gpx")?;
     /// ```
+    #[cfg(feature = "fs")]
     pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
-        let gpx: Gpx = gpx::read(BufReader::new(File::open(filename)?))?;
-        log::debug!("activity::from_file() -- gpx.metadata = {:?}", gpx.metadata);
-        log::trace!("\nactivity::from_file() -- gpx = {gpx:?}");
+        Self::from_reader(BufReader::new(open_input(filename)?), filename)
+    }
+
+    /// Parses GPX data from a reader, e.g. the contents of a file dropped into a browser. Use this where there is no
+    /// file system.
+    ///
+    /// # Arguments
+    ///
+    /// - `reader: R` -- The GPX data.
+    /// - `filename: &str` -- The name of the file the data came from. Used in the metadata and the exports.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the data may fail.
+    pub fn from_reader<R: Read>(mut reader: R, filename: &str) -> Result<Self, Box<dyn Error>> {
+        // The XML is read twice, since the `gpx` crate skips the track point extensions
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        let gpx: Gpx = gpx::read(buffer.as_slice())?;
+        let extensions = GPXExtensions::from_reader(buffer.as_slice())?;
+        log::debug!(
+            "activity::from_reader() -- gpx.metadata = {:?}",
+            gpx.metadata
+        );
+        log::trace!("\nactivity::from_reader() -- gpx = {gpx:?}");
 
         let mut activity = Self::new();
 
@@ -74,8 +148,12 @@ impl GPXActivity {
             activity.metadata
         );
 
-        for curr_track in gpx.tracks {
-            let mut track = GPXTrack::from_gpx_track(&curr_track, filename);
+        for (track_index, curr_track) in gpx.tracks.iter().enumerate() {
+            let mut track = GPXTrack::from_gpx_track(curr_track, filename);
+            if let Some(track_extensions) = extensions.get(track_index) {
+                track.set_extensions(track_extensions);
+            }
+            track.clean_gps();
             track.track_num += 1;
             log::debug!(
                 "main::run() -- track::Number of segments: {} / waypoints: {}",
@@ -89,6 +167,26 @@ impl GPXActivity {
 
         // Set the total duration to be the sum of the track durations
         activity.set_duration();
+        activity.set_track_stats();
+        activity.set_environment();
+        activity.set_gps_quality();
+        activity.set_gps_outliers();
+        activity.set_pacing();
+        activity.set_decoupling();
+        activity.set_recording_interval();
+        activity.set_heat_effort();
+        activity.set_time_in_hr_zones();
+        activity.set_uuid();
+
+        if gpx.metadata.as_ref().is_some_and(|m| m.time.is_some())
+            && activity.metadata.time.is_none()
+        {
+            activity
+                .warnings
+                .push("Unable to parse the time from the header.".to_string());
+        }
+        activity.set_track_warnings();
+        log_warnings(Some(filename), &activity.warnings);
 
         Ok(activity)
     }
@@ -112,14 +210,246 @@ impl GPXActivity {
     /// # Panics
     ///
     /// None.
+    #[cfg(feature = "fs")]
     pub fn export(&self) -> Result<(), Box<dyn Error>> {
+        self.export_as(ExportFormat::Csv)
+    }
+
+    /// Same as `export()`, but with the waypoints in the format given, e.g. `gpxfilename.waypoints.parquet`.
+    ///
+    /// # Errors
+    ///
+    /// Various export functions may return errors which get propagated.
+    #[cfg(feature = "fs")]
+    pub fn export_as(&self, format: ExportFormat) -> Result<(), Box<dyn Error>> {
         self.metadata.export_json()?;
         self.export_tracks_csv()?;
-        self.export_waypoints_csv()?;
+        if format == ExportFormat::Csv {
+            self.export_waypoints_csv()?;
+        } else {
+            let outfile = self.output_path(&format!("waypoints.{}", format.extension()));
+            write_output(&outfile, |file| self.write_waypoints(file, format))?;
+        }
 
         Ok(())
     }
 
+    /// Exports the metadata, tracks with their waypoints, marked waypoints and routes together as a single JSON
+    /// document named after the GPX file with the extension replaced by `bundle.json`.
+    ///
+    /// # Arguments
+    ///
+    /// None.
+    ///
+    /// # Returns
+    ///
+    /// Nothing.
+    ///
+    /// # Errors
+    ///
+    /// Creating the file may fail. Serializing to JSON may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_bundle_json(&self) -> Result<(), Box<dyn Error>> {
+        let outfile = self.output_path("bundle.json");
+        write_output(&outfile, |file| self.write_bundle_json(file))?;
+
+        Ok(())
+    }
+
+    /// Writes the metadata, tracks with their waypoints, marked waypoints and routes together as a single JSON
+    /// document to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the JSON is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing or writing the JSON may fail.
+    pub fn write_bundle_json<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(writer, &Rounded(&self.bundle()))?;
+        Ok(())
+    }
+
+    /// The metadata, tracks with their waypoints, marked waypoints and routes together as a single JSON document --
+    /// the same document `export_bundle_json()` writes to a file.
+    ///
+    /// # Errors
+    ///
+    /// Serializing to JSON may fail.
+    pub fn bundle_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(&Rounded(&self.bundle()))?)
+    }
+
+    /// Collects the parts of the single-document JSON export.
+    fn bundle(&self) -> GPXBundle<'_> {
+        GPXBundle {
+            session: &self.metadata,
+            tracks: self
+                .tracks
+                .iter()
+                .map(|track| GPXTrackBundle {
+                    track,
+                    waypoints: &track.waypoints,
+                })
+                .collect(),
+            waypoints: &self.waypoints,
+            routes: &self.routes,
+        }
+    }
+
+    /// Sets the activity UUID on the metadata, tracks and waypoints. GPX files have no serial number,
+    /// so the creator is used to tell devices apart.
+    pub fn set_uuid(&mut self) {
+        let start_time = self
+            .metadata
+            .time
+            .or_else(|| self.tracks.first().and_then(|track| track.start_time));
+        let uuid = Some(activity_uuid(
+            start_time.as_ref(),
+            self.metadata.creator.as_deref(),
+            self.metadata.activity.as_deref(),
+        ));
+
+        self.metadata.uuid = uuid;
+        for track in &mut self.tracks {
+            track.activity_uuid = uuid;
+            for waypoint in &mut track.waypoints {
+                waypoint.activity_uuid = uuid;
+            }
+        }
+        for waypoint in &mut self.waypoints {
+            waypoint.activity_uuid = uuid;
+        }
+        for route in &mut self.routes {
+            for waypoint in &mut route.points {
+                waypoint.activity_uuid = uuid;
+            }
+        }
+    }
+
+    /// Summarizes the GPS quality of the track waypoints -- the share of 3D fixes, the average number of satellites
+    /// and the average HDOP -- to help find out why a track looks bad. Each value is only set if at least one
+    /// waypoint reports it.
+    pub fn set_gps_quality(&mut self) {
+        let waypoints = || self.tracks.iter().flat_map(|track| &track.waypoints);
+
+        let pct_3d_fix = average(waypoints().filter_map(|wpt| {
+            wpt.fix.as_deref().map(|fix| {
+                if THREE_D_FIXES.contains(&fix) {
+                    100.0
+                } else {
+                    0.0
+                }
+            })
+        }));
+        #[allow(clippy::cast_precision_loss)]
+        let avg_satellites = average(waypoints().filter_map(|wpt| wpt.sat.map(|sat| sat as f64)));
+        let avg_hdop = average(waypoints().filter_map(|wpt| wpt.hdop));
+
+        self.metadata.pct_3d_fix = pct_3d_fix;
+        self.metadata.avg_satellites = avg_satellites;
+        self.metadata.avg_hdop = avg_hdop;
+        log::trace!(
+            "activity::set_gps_quality() -- 3D fixes: {pct_3d_fix:?}%, satellites: {avg_satellites:?}, HDOP: {avg_hdop:?}"
+        );
+    }
+
+    /// Adds up the GPS spikes found in the tracks. Left unset if none of the tracks have a position.
+    pub fn set_gps_outliers(&mut self) {
+        self.metadata.gps_outliers = self
+            .tracks
+            .iter()
+            .filter_map(|track| track.gps_outliers)
+            .reduce(|total, num| total + num);
+    }
+
+    /// Splits the activity into one activity per track, for files where each track is a separate activity. Each
+    /// activity gets its own metadata, named after the GPX file with `.trackN` added (e.g. `running.track2.gpx`), so
+    /// the detail exports don't overwrite each other. The name and time are taken from the track where it has them.
+    ///
+    /// The marked waypoints, routes and warnings aren't tied to any track, so they stay with the first activity.
+    /// Files with a single track are returned as they are.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<Self>` -- One activity per track, in the order of the tracks in the file.
+    #[must_use]
+    pub fn split_tracks(self) -> Vec<Self> {
+        if self.tracks.len() < 2 {
+            return vec![self];
+        }
+
+        let filename = self
+            .metadata
+            .filename
+            .as_ref()
+            .and_then(|f| f.to_str())
+            .unwrap_or("export.gpx")
+            .to_string();
+        let mut waypoints = self.waypoints;
+        let mut routes = self.routes;
+        let mut warnings = self.warnings;
+
+        let mut activities = Vec::with_capacity(self.tracks.len());
+        for (num, track) in self.tracks.into_iter().enumerate() {
+            let mut metadata = self.metadata.clone();
+            metadata.set_filename(&set_extension(&filename, &format!("track{}.gpx", num + 1)));
+            metadata.activity = track.name.clone().or(metadata.activity);
+            metadata.time = track.start_time.or(metadata.time);
+            metadata.num_tracks = 1;
+
+            let mut activity = Self {
+                metadata,
+                waypoints: std::mem::take(&mut waypoints),
+                routes: std::mem::take(&mut routes),
+                tracks: vec![track],
+                warnings: std::mem::take(&mut warnings),
+            };
+            activity.metadata.num_waypoints = activity.waypoints.len();
+            activity.metadata.num_routes = activity.routes.len();
+            activity.set_duration();
+            activity.set_track_stats();
+            activity.set_environment();
+            activity.set_gps_quality();
+            activity.set_gps_outliers();
+            activity.set_pacing();
+            activity.set_decoupling();
+            activity.set_recording_interval();
+            activity.set_heat_effort();
+            activity.set_time_in_hr_zones();
+            activity.set_uuid();
+            activities.push(activity);
+        }
+
+        activities
+    }
+
+    /// Adds warnings for the tracks without waypoints and the waypoints without a time, since neither count towards
+    /// the duration.
+    fn set_track_warnings(&mut self) {
+        for (num, track) in self.tracks.iter().enumerate() {
+            if track.waypoints.is_empty() {
+                self.warnings
+                    .push(format!("Track {} has no waypoints.", num + 1));
+            }
+        }
+
+        let untimed = self
+            .tracks
+            .iter()
+            .flat_map(|track| &track.waypoints)
+            .filter(|wpt| wpt.time.is_none())
+            .count();
+        if untimed > 0 {
+            self.warnings.push(format!(
+                "{untimed} track waypoints have no time. Their duration is unknown."
+            ));
+        }
+        self.warnings
+            .extend(gps_outlier_warning(self.metadata.gps_outliers));
+    }
+
     /// Iterates through the tracks and calculates a total activity duration.
     /// Should only be used after the track data has been gathered.
     ///
@@ -133,30 +463,24 @@ impl GPXActivity {
     ///
     /// # Errors
     ///
-    /// Returns an error if there are no tracks from which to read total activity duration.
+    /// None. If the track durations add up to more than can be held, the duration is capped and a warning is added.
     ///
     /// # Panics
     ///
     /// None.
     ///
     fn set_duration(&mut self) {
-        let mut duration = Duration::from_secs_f64(0.0);
-
-        // If there are no tracks, protest!
-        if self.tracks.is_empty() {
-            self.metadata.duration = Some(Duration::default());
-        } else {
-            // Iterate through the tracks
-            for track in &self.tracks {
-                // If the track has duration data, add it to the total.
-                if let Some(trackduration) = track.duration {
-                    duration += trackduration;
-                }
-            }
+        // Add up the tracks that have a duration. Bad timestamps may give durations too large to add up.
+        let duration = Duration::checked_sum(self.tracks.iter().filter_map(|track| track.duration))
+            .unwrap_or_else(|| {
+                self.warnings.push(
+                    "The track durations add up to more than can be held. The duration is capped."
+                        .to_string(),
+                );
+                Duration(std::time::Duration::MAX)
+            });
 
-            // Set the total duration
-            self.metadata.duration = Some(duration);
-        }
+        self.metadata.duration = Some(duration);
     }
 
     /// Export the tracks to CSV
@@ -176,27 +500,34 @@ impl GPXActivity {
     /// # Panics
     ///
     /// None.
+    #[cfg(feature = "fs")]
     fn export_tracks_csv(&self) -> Result<(), Box<dyn Error>> {
+        let outfile = self.output_path("tracks.csv");
+
+        write_output(&outfile, |file| self.write_tracks_csv(file))
+    }
+
+    /// Write the tracks, without their waypoints, as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Errors if there are no tracks in the activity. Serializing the track data may fail. Flushing the writer may fail.
+    pub fn write_tracks_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
         let tracks = &self.tracks;
         if tracks.is_empty() {
             return Err("track::export_tracks_csv() -- No Tracks in the Activity.".into());
         }
 
-        // Change the file extension
-        let mut outfile = PathBuf::from(
-            self.metadata
-                .filename
-                .as_ref()
-                .unwrap_or(&PathBuf::from("export")),
-        );
-        outfile.set_extension("tracks.csv");
-
         // Create a buffer for the CSV
-        let mut writer = WriterBuilder::new().has_headers(true).from_path(outfile)?;
+        let mut writer = ColumnWriter::new(writer, CsvFile::GpxTracks);
 
         // Export the tracks sans the waypoints
         for curr_track in tracks {
-            writer.serialize(curr_track)?;
+            writer.serialize(Rounded(curr_track))?;
         }
 
         writer.flush()?;
@@ -205,6 +536,24 @@ impl GPXActivity {
         Ok(())
     }
 
+    /// The name of a detail file of the activity, named after the GPX file with the extension replaced by the suffix
+    /// given, or after `export` if there is no GPX file.
+    ///
+    /// # Arguments
+    ///
+    /// `suffix: &str` -- The default suffix of the export, e.g. `tracks.csv`.
+    #[cfg(feature = "fs")]
+    fn output_path(&self, suffix: &str) -> PathBuf {
+        output_path(
+            &self
+                .metadata
+                .filename
+                .as_ref()
+                .map_or_else(|| "export".into(), |filename| filename.to_string_lossy()),
+            suffix,
+        )
+    }
+
     /// Export all the waypoints for each track to CSV
     ///
     /// # Arguments
@@ -222,28 +571,80 @@ impl GPXActivity {
     /// # Panics
     ///
     /// None.
+    #[cfg(feature = "fs")]
     fn export_waypoints_csv(&self) -> Result<(), Box<dyn Error>> {
+        let outfile = self.output_path("waypoints.csv");
+
+        self.export_waypoints_csv_to(&outfile)
+    }
+
+    /// Export all the waypoints for each track to the CSV file given.
+    ///
+    /// # Arguments
+    ///
+    /// `outfile: &Path` -- The CSV file to be written.
+    ///
+    /// # Errors
+    ///
+    /// Errors if there are no tracks in the activity. The `WriterBuilder` may fail. Serialization may fail. Flushing the writer may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_waypoints_csv_to(&self, outfile: &Path) -> Result<(), Box<dyn Error>> {
+        write_output(outfile, |file| self.write_waypoints_csv(file))
+    }
+
+    /// Write all the waypoints for each track in the format given to any writer.
+    ///
+    /// # Arguments
+    ///
+    /// - `writer: W` -- Where the waypoints are written.
+    /// - `format: ExportFormat` -- The format to write.
+    ///
+    /// # Errors
+    ///
+    /// Errors if there are no tracks in the activity. Parquet may not be available in this build. Serialization or
+    /// writing may fail.
+    pub fn write_waypoints<W: Write + Send>(
+        &self,
+        writer: W,
+        format: ExportFormat,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.tracks.is_empty() {
+            return Err("track::write_waypoints() -- No Tracks in the Activity.".into());
+        }
+
+        write_rows(
+            self.tracks
+                .iter()
+                .flat_map(|track| &track.waypoints)
+                .map(Rounded),
+            CsvFile::GpxWaypoints,
+            format,
+            writer,
+        )
+    }
+
+    /// Write all the waypoints for each track as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Errors if there are no tracks in the activity. Serialization may fail. Flushing the writer may fail.
+    pub fn write_waypoints_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
         let tracks = &self.tracks;
         if tracks.is_empty() {
             return Err("track::export_waypoints_csv() -- No Tracks in the Activity.".into());
         }
 
-        // Change the file extension
-        let mut outfile = PathBuf::from(
-            self.metadata
-                .filename
-                .as_ref()
-                .unwrap_or(&PathBuf::from("export")),
-        );
-        outfile.set_extension("waypoints.csv");
-
         // Create a buffer for the CSV
-        let mut writer = WriterBuilder::new().has_headers(true).from_path(outfile)?;
+        let mut writer = ColumnWriter::new(writer, CsvFile::GpxWaypoints);
 
-        // Export the tracks sans the waypoints
+        // Export the waypoints for each track
         for curr_track in tracks {
             for curr_wpt in &curr_track.waypoints {
-                writer.serialize(curr_wpt)?;
+                writer.serialize(Rounded(curr_wpt))?;
             }
         }
 
@@ -253,16 +654,241 @@ impl GPXActivity {
         Ok(())
     }
 
+    /// Splits the activity into kilometers or miles. GPX files don't record the distance, so it is worked out from
+    /// the positions of the track waypoints.
+    ///
+    /// # Arguments
+    ///
+    /// `unit: SplitUnit` -- Whether to split by kilometer or mile.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<Split>` -- The splits. Waypoints without a time or position are left out.
+    #[must_use]
+    pub fn splits(&self, unit: SplitUnit) -> Vec<Split> {
+        splits(&self.split_points(), unit)
+    }
+
+    /// The elevation against the distance, from the track waypoints, for drawing with `profile_svg()`. The distance
+    /// is worked out from the positions, as for the splits.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<ProfilePoint>` -- The profile. Waypoints without a time, position or elevation are left out.
+    #[must_use]
+    pub fn elevation_profile(&self) -> Vec<ProfilePoint> {
+        profile_points(&self.split_points())
+    }
+
+    /// Works out the mean-maximal power and heart rate curve from the track waypoints, using the power and heart rate
+    /// read from their extensions.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<MeanMax>` -- The best averages for each duration. Waypoints without a time are left out.
+    #[must_use]
+    pub fn mean_max(&self) -> Vec<MeanMax> {
+        mean_max(&self.mean_max_samples())
+    }
+
+    /// Works out the heart rate against the speed and power over each half minute of the tracks.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<HrScatter>` -- One sample per half minute, without the warm-up, the stops and the outliers.
+    #[must_use]
+    pub fn hr_scatter(&self) -> Vec<HrScatter> {
+        hr_scatter(&self.mean_max_samples(), &self.split_points())
+    }
+
+    /// The waypoints of the tracks as track points, the view shared with the other formats. GPX files don't record
+    /// the distance, so it is left empty.
+    pub fn track_points(&self) -> impl Iterator<Item = TrackPoint> + '_ {
+        self.tracks
+            .iter()
+            .flat_map(|track| &track.waypoints)
+            .map(TrackPoint::from)
+    }
+
+    /// Builds the tracks of the activity as a GeoJSON `FeatureCollection`, with a `LineString` for each track. The
+    /// segments of a track are joined into a single line.
+    ///
+    /// # Returns
+    ///
+    /// `serde_json::Value` -- The `FeatureCollection`. Waypoints without a position are left out, and so are tracks
+    /// with fewer than two positions.
+    #[must_use]
+    pub fn geojson(&self) -> serde_json::Value {
+        feature_collection(self.tracks.iter().map(|track| {
+            let properties = GeoJsonProperties {
+                name: track.name.clone(),
+                start_time: track.start_time,
+                distance_m: track.distance_m,
+                sport: track
+                    .t_type
+                    .clone()
+                    .or_else(|| self.metadata.activity.clone()),
+            };
+            let coordinates = track
+                .waypoints
+                .iter()
+                .filter_map(|wpt| TrackPoint::from(wpt).position())
+                .collect();
+            (properties, coordinates)
+        }))
+    }
+
+    /// Works out the distance, moving time, speed, ascent and descent of each track from its waypoints, since GPX
+    /// files carry no summary numbers, and adds them up for the activity so the summary can be compared with FIT
+    /// sessions.
+    pub fn set_track_stats(&mut self) {
+        let total = self
+            .tracks
+            .iter_mut()
+            .map(GPXTrack::set_track_stats)
+            .fold(TrackStats::default(), |total, stats| total.combine(&stats));
+
+        self.metadata.distance_m = total.distance_m;
+        self.metadata.duration_moving = total.moving_time_sec.map(Duration::from_secs_f64);
+        self.metadata.speed_avg_ms = total.avg_speed_ms();
+        self.metadata.speed_max_ms = total.max_speed_ms;
+        self.metadata.ascent_m = total.ascent_m;
+        self.metadata.descent_m = total.descent_m;
+        log::trace!("activity::set_track_stats() -- {total:?}");
+    }
+
+    /// Sets where the activity took place from the type of the first track that has one, e.g. `treadmill`, and from
+    /// the shape of the track. Treadmill runs recorded with GPS on wander around in place, so they cover a distance
+    /// without getting anywhere.
+    pub fn set_environment(&mut self) {
+        let declared = self
+            .tracks
+            .iter()
+            .find_map(|track| track.t_type.as_deref())
+            .map(FITEnvironment::from_sub_sport)
+            .unwrap_or_default();
+
+        let mut gps = GpsDistance::default();
+        for waypoint in self.tracks.iter().flat_map(|track| &track.waypoints) {
+            gps.add(waypoint.latitude, waypoint.longitude);
+        }
+
+        self.metadata.environment = detect_environment(declared, self.metadata.distance_m, &gps);
+        log::trace!(
+            "activity::set_environment() -- {}",
+            self.metadata.environment
+        );
+    }
+
+    /// Sets the pacing, i.e. how the speed in the second half of the activity compares with the first, from the
+    /// distance worked out from the track waypoints.
+    pub fn set_pacing(&mut self) {
+        let pacing = pacing(&self.split_points());
+        self.metadata.speed_first_half_ms = pacing.map(|p| p.first_half_speed);
+        self.metadata.speed_second_half_ms = pacing.map(|p| p.second_half_speed);
+        self.metadata.pacing_index_pct = pacing.map(|p| p.index_pct);
+        self.metadata.pacing_split = pacing.map(|p| p.split);
+    }
+
+    /// Sets the aerobic decoupling, i.e. how far the heart rate drifted up against the power and speed, from the
+    /// track waypoints.
+    pub fn set_decoupling(&mut self) {
+        let decoupling = decoupling(&self.mean_max_samples(), &self.split_points());
+        self.metadata.decoupling_power_pct = decoupling.power_pct;
+        self.metadata.decoupling_pace_pct = decoupling.pace_pct;
+    }
+
+    /// Sets how often the device recorded, i.e. every second or smart recording, from the times of the track
+    /// waypoints.
+    pub fn set_recording_interval(&mut self) {
+        let interval = recording_interval(
+            self.tracks
+                .iter()
+                .flat_map(|track| &track.waypoints)
+                .map(|wpt| wpt.time),
+        );
+        self.metadata.recording_interval_sec = interval.map(|i| i.median_sec);
+        self.metadata.recording_every_second_pct = interval.map(|i| i.every_second_pct);
+        self.metadata.recording_mode = interval.map(|i| i.mode);
+    }
+
+    /// Sets the average temperature and how much the heat held back the effort, from the temperatures of the track
+    /// waypoints.
+    pub fn set_heat_effort(&mut self) {
+        let temperatures: Vec<f64> = self
+            .tracks
+            .iter()
+            .flat_map(|track| &track.waypoints)
+            .filter_map(|wpt| wpt.temperature)
+            .collect();
+        self.metadata.temperature_avg_c = mean(&temperatures);
+
+        let heat = heat_effort(self.metadata.temperature_avg_c, self.metadata.speed_avg_ms);
+        self.metadata.heat_adjustment_pct = heat.map(|h| h.adjustment_pct);
+        self.metadata.speed_heat_adjusted_ms = heat.and_then(|h| h.adjusted_speed);
+        self.metadata.heat = heat.map(|h| h.flag);
+    }
+
+    /// Sets the time spent in each heart rate zone from the heart rates of the track waypoints, with the zones set by
+    /// `Settings::set_hr_zones()`.
+    pub fn set_time_in_hr_zones(&mut self) {
+        let samples: Vec<_> = self
+            .mean_max_samples()
+            .iter()
+            .map(|sample| (sample.time, sample.heart_rate))
+            .collect();
+        [
+            self.metadata.heart_rate_zone0_sec,
+            self.metadata.heart_rate_zone1_sec,
+            self.metadata.heart_rate_zone2_sec,
+            self.metadata.heart_rate_zone3_sec,
+            self.metadata.heart_rate_zone4_sec,
+        ] = time_in_hr_zones(&samples, &hr_zones()).secs();
+    }
+
+    /// The track waypoints with a time, as samples for the mean-maximal curve.
+    pub(crate) fn mean_max_samples(&self) -> Vec<MeanMaxSample> {
+        self.track_points()
+            .filter_map(|point| point.mean_max_sample())
+            .collect()
+    }
+
+    /// The track waypoints with both a time and a position, with the distance covered since the first of them.
+    pub(crate) fn split_points(&self) -> Vec<SplitPoint> {
+        let mut points = Vec::new();
+        let mut last_position = None;
+        let mut distance = 0.0;
+
+        for wpt in self.tracks.iter().flat_map(|track| &track.waypoints) {
+            let (Some(time), Some(lat), Some(lon)) = (wpt.time, wpt.latitude, wpt.longitude) else {
+                continue;
+            };
+            if let Some(last) = last_position {
+                distance += haversine_distance(last, (lat, lon));
+            }
+            last_position = Some((lat, lon));
+
+            points.push(SplitPoint {
+                time,
+                distance,
+                heart_rate: wpt.heart_rate.map(f64::from),
+                altitude: wpt.elevation,
+            });
+        }
+
+        points
+    }
+
     /// Prints the metadata information about the activity
     pub fn print(&self, detailed: bool) {
-        let unknown = String::new();
+        let unknown = placeholder("");
 
         println!(
             "\nFile:              {}",
             self.metadata
                 .filename
                 .as_ref()
-                .unwrap_or(&Path::new("unknown").to_path_buf())
+                .unwrap_or(&Path::new(&placeholder("unknown")).to_path_buf())
                 .to_string_lossy()
         );
         println!(
@@ -298,6 +924,56 @@ impl GPXActivity {
         println!("Waypoints:         {}", self.metadata.num_waypoints);
         println!("Tracks:            {}", self.metadata.num_tracks);
         println!("Routes:            {}", self.metadata.num_routes);
+        println!("Environment:       {}", self.metadata.environment);
+        if let (Some(index), Some(split)) =
+            (self.metadata.pacing_index_pct, self.metadata.pacing_split)
+        {
+            println!("Pacing Index:      {index:.2}% ({split} split)");
+        }
+        if let Some(pct) = self.metadata.decoupling_power_pct {
+            println!("Decoupling Pw:HR:  {pct:.2}%");
+        }
+        if let Some(pct) = self.metadata.decoupling_pace_pct {
+            println!("Decoupling Pa:HR:  {pct:.2}%");
+        }
+        if let (Some(temperature), Some(pct), Some(heat)) = (
+            self.metadata.temperature_avg_c,
+            self.metadata.heat_adjustment_pct,
+            self.metadata.heat,
+        ) {
+            println!("Temperature:       {temperature:.1} °C ({heat}, {pct:.1}% slower)");
+        }
+        if let Some(distance) = self.metadata.distance_m {
+            // Kilometers unless another unit system is set for the run
+            let units = unit_system().unwrap_or(UnitSystem::Metric);
+            println!(
+                "Distance:          {:.2} {}      Ascent: {:.0} {}",
+                Measure::Distance.convert(distance, Some(units)),
+                Measure::Distance.unit(Some(units)),
+                Measure::Length.convert(self.metadata.ascent_m.unwrap_or_default(), Some(units)),
+                Measure::Length.unit(Some(units)),
+            );
+            if let Some((pace_label, pace)) = self
+                .metadata
+                .duration_moving
+                .filter(|moving| moving.0.as_secs_f64() > 0.0)
+                .and_then(|moving| {
+                    pace_label(
+                        "Pace",
+                        self.metadata.activity.as_deref(),
+                        distance / moving.0.as_secs_f64(),
+                    )
+                })
+            {
+                println!("{pace_label:<19}{pace}");
+            }
+        }
+        if let (Some(interval), Some(mode)) = (
+            self.metadata.recording_interval_sec,
+            self.metadata.recording_mode,
+        ) {
+            println!("Recording:         every {interval:.0} s ({mode})");
+        }
         if detailed {
             println!(
                 "Author Name:       {}",
@@ -331,10 +1007,33 @@ impl GPXActivity {
                 "Copyright License: {}",
                 self.metadata.copyright_license.as_ref().unwrap_or(&unknown)
             );
+            println!(
+                "GPS 3D Fixes:      {}",
+                self.metadata
+                    .pct_3d_fix
+                    .map_or_else(|| unknown.clone(), |pct| format!("{pct:.1}%"))
+            );
+            println!(
+                "Avg Satellites:    {}",
+                self.metadata
+                    .avg_satellites
+                    .map_or_else(|| unknown.clone(), |sat| format!("{sat:.1}"))
+            );
+            println!(
+                "Avg HDOP:          {}",
+                self.metadata
+                    .avg_hdop
+                    .map_or_else(|| unknown.clone(), |hdop| format!("{hdop:.2}"))
+            );
         }
     }
 }
 
+/// The average of the values, or `None` if there are no values.
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    mean(&values.collect::<Vec<_>>())
+}
+
 impl Default for GPXActivity {
     /// Sets up the Activity with empty data placeholders.
     fn default() -> Self {
@@ -343,6 +1042,208 @@ impl Default for GPXActivity {
             waypoints: Vec::new(),
             routes: Vec::new(),
             tracks: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests for the GPX activity module
+mod tests {
+    use super::*;
+
+    /// Creates a waypoint with the GPS quality fields given.
+    fn waypoint(fix: Option<&str>, sat: Option<u64>, hdop: Option<f64>) -> GPXWaypoint {
+        GPXWaypoint {
+            fix: fix.map(str::to_string),
+            sat,
+            hdop,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    /// Test the GPS quality summary
+    fn test_set_gps_quality() {
+        let mut activity = GPXActivity::new();
+        activity.tracks.push(GPXTrack {
+            waypoints: vec![
+                waypoint(Some("ThreeDimensional"), Some(8), Some(1.0)),
+                waypoint(Some("TwoDimensional"), Some(3), Some(4.0)),
+                waypoint(Some("DGPS"), None, None),
+                waypoint(Some("None"), Some(1), None),
+                waypoint(None, None, None),
+            ],
+            ..Default::default()
+        });
+        activity.set_gps_quality();
+
+        assert_eq!(activity.metadata.pct_3d_fix, Some(50.0));
+        assert_eq!(activity.metadata.avg_satellites, Some(4.0));
+        assert_eq!(activity.metadata.avg_hdop, Some(2.5));
+
+        // No GPS quality information at all
+        let mut activity = GPXActivity::new();
+        activity.tracks.push(GPXTrack {
+            waypoints: vec![waypoint(None, None, None)],
+            ..Default::default()
+        });
+        activity.set_gps_quality();
+
+        assert!(activity.metadata.pct_3d_fix.is_none());
+        assert!(activity.metadata.avg_satellites.is_none());
+        assert!(activity.metadata.avg_hdop.is_none());
+    }
+
+    #[test]
+    /// Test that track durations too large to add up give a warning rather than a panic
+    fn test_set_duration() {
+        let mut activity = GPXActivity::new();
+        for secs in [600.0, 1_200.0] {
+            activity.tracks.push(GPXTrack {
+                duration: Some(Duration::from_secs_f64(secs)),
+                ..Default::default()
+            });
+        }
+        activity.set_duration();
+        assert_eq!(
+            activity.metadata.duration,
+            Some(Duration::from_secs_f64(1_800.0))
+        );
+        assert!(activity.warnings.is_empty());
+
+        activity.tracks[0].duration = Some(Duration::from_secs_f64(f64::INFINITY));
+        activity.set_duration();
+        assert_eq!(
+            activity.metadata.duration.map(|duration| duration.0),
+            Some(std::time::Duration::MAX)
+        );
+        assert_eq!(activity.warnings.len(), 1);
+    }
+
+    #[test]
+    /// Test parsing GPX data that has already been read into memory
+    fn test_from_reader() {
+        let bytes = std::fs::read("../data/running.gpx").unwrap();
+        let activity = GPXActivity::from_reader(bytes.as_slice(), "dropped.gpx").unwrap();
+
+        assert_eq!(
+            activity.metadata.filename,
+            Some(Path::new("dropped.gpx").to_path_buf())
+        );
+        assert_eq!(activity.tracks.len(), 1);
+        assert!(activity.metadata.uuid.is_some());
+
+        // The heart rate comes from the Garmin track point extensions
+        let first = &activity.tracks[0].waypoints[0];
+        assert_eq!(first.heart_rate, Some(76));
+        assert!(first.temperature.is_none());
+
+        // The time in each heart rate zone is worked out from the heart rates
+        let zones = [
+            activity.metadata.heart_rate_zone0_sec,
+            activity.metadata.heart_rate_zone1_sec,
+            activity.metadata.heart_rate_zone2_sec,
+            activity.metadata.heart_rate_zone3_sec,
+            activity.metadata.heart_rate_zone4_sec,
+        ];
+        assert!(zones.iter().all(Option::is_some));
+        assert!(zones.iter().flatten().sum::<f64>() > 0.0);
+    }
+
+    #[test]
+    /// Test writing the exports to memory
+    fn test_write_to_buffers() {
+        let activity = GPXActivity::from_reader(
+            std::fs::read("../data/running.gpx").unwrap().as_slice(),
+            "upload.gpx",
+        )
+        .unwrap();
+
+        let mut waypoints = Vec::new();
+        activity.write_waypoints_csv(&mut waypoints).unwrap();
+        assert_eq!(
+            String::from_utf8(waypoints).unwrap().lines().count(),
+            activity.tracks[0].waypoints.len() + 1
+        );
+
+        let mut metadata = Vec::new();
+        activity.metadata.write_json(&mut metadata).unwrap();
+        assert!(String::from_utf8(metadata).unwrap().contains("upload.gpx"));
+
+        assert!(GPXActivity::new().write_tracks_csv(Vec::new()).is_err());
+    }
+
+    #[test]
+    /// Test splitting a file into one activity per track
+    fn test_split_tracks() {
+        let gpx = r#"<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+<metadata><name>Morning</name></metadata>
+<trk><name>Swim</name><trkseg>
+<trkpt lat="59.9" lon="10.7"><time>2024-05-01T06:00:00Z</time></trkpt>
+<trkpt lat="59.9" lon="10.8"><time>2024-05-01T06:10:00Z</time></trkpt>
+</trkseg></trk>
+<trk><trkseg>
+<trkpt lat="59.9" lon="10.8"><time>2024-05-01T07:00:00Z</time></trkpt>
+<trkpt lat="59.9" lon="10.9"><time>2024-05-01T07:30:00Z</time></trkpt>
+</trkseg></trk></gpx>"#;
+        let activity = GPXActivity::from_reader(gpx.as_bytes(), "data/tri.gpx").unwrap();
+        let split = activity.split_tracks();
+
+        assert_eq!(split.len(), 2);
+        assert_eq!(
+            split[1].metadata.filename,
+            Some(Path::new("data/tri.track2.gpx").to_path_buf())
+        );
+        assert_eq!(split[0].metadata.activity.as_deref(), Some("Swim"));
+        assert_eq!(split[1].metadata.activity.as_deref(), Some("Morning"));
+        assert_eq!(
+            split[1].metadata.duration,
+            Some(Duration::from_secs_f64(1800.0))
+        );
+        assert_eq!(split[1].metadata.time, split[1].tracks[0].start_time);
+        assert_ne!(split[0].metadata.uuid, split[1].metadata.uuid);
+
+        let single = GPXActivity::from_reader(
+            std::fs::read("../data/running.gpx").unwrap().as_slice(),
+            "running.gpx",
+        )
+        .unwrap();
+        assert_eq!(
+            single.split_tracks()[0].metadata.filename,
+            Some(Path::new("running.gpx").to_path_buf())
+        );
+    }
+
+    #[test]
+    /// Test counting the GPS spikes of each track and of the activity
+    fn test_set_gps_outliers() {
+        let gpx = r#"<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+<trk><trkseg>
+<trkpt lat="59.90000" lon="10.7"><time>2024-05-01T06:00:00Z</time></trkpt>
+<trkpt lat="59.90003" lon="10.7"><time>2024-05-01T06:00:01Z</time></trkpt>
+<trkpt lat="59.95000" lon="10.7"><time>2024-05-01T06:00:02Z</time></trkpt>
+<trkpt lat="59.90009" lon="10.7"><time>2024-05-01T06:00:03Z</time></trkpt>
+</trkseg></trk>
+<trk><trkseg></trkseg></trk></gpx>"#;
+        let activity = GPXActivity::from_reader(gpx.as_bytes(), "spikes.gpx").unwrap();
+
+        assert_eq!(activity.tracks[0].gps_outliers, Some(1));
+        assert_eq!(activity.tracks[1].gps_outliers, None);
+        assert_eq!(activity.metadata.gps_outliers, Some(1));
+        assert!(activity
+            .warnings
+            .iter()
+            .any(|warning| warning.starts_with("1 GPS points look like spikes")));
+    }
+
+    #[test]
+    /// Test that files cut short give an error rather than a panic
+    fn test_from_reader_truncated() {
+        let bytes = std::fs::read("../data/running.gpx").unwrap();
+
+        for len in (0..bytes.len()).step_by(211) {
+            assert!(GPXActivity::from_reader(&bytes[..len], "truncated.gpx").is_err());
         }
     }
 }
@@ -0,0 +1,174 @@
+//! Defines the `GPXExtensions` struct which holds the sensor data found in the `<extensions>` of the track points in a
+//! GPX file, such as heart rate and cadence.
+//!
+//! The `gpx` crate skips the extensions, so they are read separately here. Both the Garmin `TrackPointExtension`
+//! (`<gpxtpx:hr>`, `<gpxtpx:cad>`, `<gpxtpx:atemp>`) and the Cluetrust `gpxdata` elements (`<gpxdata:hr>`,
+//! `<gpxdata:cadence>`, `<gpxdata:temp>`) are understood, along with the `<power>` element written by Strava and the
+//! Garmin `<pwr:PowerInType>`.
+
+use std::error::Error;
+use std::io::Read;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::GPXWaypoint;
+
+/// The sensor data from the extensions of a single track point.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct GPXExtensions {
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<u16>,
+
+    /// Cadence in revolutions or steps per minute.
+    pub cadence: Option<u16>,
+
+    /// Air temperature in degrees Celsius.
+    pub temperature: Option<f64>,
+
+    /// Power in Watts.
+    pub power: Option<u16>,
+}
+
+impl GPXExtensions {
+    /// Reads the extensions of each track point from GPX XML.
+    ///
+    /// # Arguments
+    ///
+    /// `reader: R` -- Where to read the XML from.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Vec<Vec<Vec<Self>>>, Box<dyn Error>>` -- The extensions by track, segment and track point, in the order
+    /// they are found in the file. Track points without extensions get an empty `GPXExtensions`.
+    ///
+    /// # Errors
+    ///
+    /// Parsing the XML may fail.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Vec<Vec<Vec<Self>>>, Box<dyn Error>> {
+        let mut tracks: Vec<Vec<Vec<Self>>> = Vec::new();
+        let mut path: Vec<String> = Vec::new();
+
+        for event in EventReader::new(reader) {
+            match event? {
+                XmlEvent::StartElement { name, .. } => {
+                    match name.local_name.as_str() {
+                        "trk" => tracks.push(Vec::new()),
+                        "trkseg" => {
+                            if let Some(track) = tracks.last_mut() {
+                                track.push(Vec::new());
+                            }
+                        }
+                        "trkpt" => {
+                            if let Some(segment) = tracks.last_mut().and_then(|t| t.last_mut()) {
+                                segment.push(Self::default());
+                            }
+                        }
+                        _ => (),
+                    }
+                    path.push(name.local_name);
+                }
+                XmlEvent::EndElement { .. } => {
+                    path.pop();
+                }
+                XmlEvent::Characters(text) => {
+                    // Only the elements within the extensions of a track point are of interest
+                    let in_extensions = path
+                        .iter()
+                        .skip_while(|element| *element != "trkpt")
+                        .any(|element| element == "extensions");
+                    if let (true, Some(field)) = (in_extensions, path.last()) {
+                        if let Some(point) = tracks
+                            .last_mut()
+                            .and_then(|t| t.last_mut())
+                            .and_then(|s| s.last_mut())
+                        {
+                            point.set(field, text.trim());
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        log::trace!(
+            "GPXExtensions::from_reader() -- extensions found for {} tracks",
+            tracks.len()
+        );
+        Ok(tracks)
+    }
+
+    /// Sets the field matching the name of the extension element, if it is one of interest.
+    fn set(&mut self, element: &str, text: &str) {
+        match element.to_ascii_lowercase().as_str() {
+            "hr" | "heartrate" => self.heart_rate = parse_u16(text),
+            "cad" | "cadence" => self.cadence = parse_u16(text),
+            "atemp" | "temp" | "temperature" => self.temperature = text.parse().ok(),
+            "power" | "powerintype" | "watts" => self.power = parse_u16(text),
+            _ => (),
+        }
+    }
+
+    /// Copies the sensor data onto the waypoint, keeping what the waypoint already has.
+    ///
+    /// # Arguments
+    ///
+    /// `waypoint: &mut GPXWaypoint` -- The waypoint of the track point the extensions were read from.
+    pub fn apply(&self, waypoint: &mut GPXWaypoint) {
+        waypoint.heart_rate = waypoint.heart_rate.or(self.heart_rate);
+        waypoint.cadence = waypoint.cadence.or(self.cadence);
+        waypoint.temperature = waypoint.temperature.or(self.temperature);
+        waypoint.power = waypoint.power.or(self.power);
+    }
+}
+
+/// Parses a whole number, rounding decimals such as `142.0` written by some applications.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn parse_u16(text: &str) -> Option<u16> {
+    text.parse::<u16>().ok().or_else(|| {
+        text.parse::<f64>()
+            .ok()
+            .filter(|value| (0.0..=f64::from(u16::MAX)).contains(value))
+            .map(|value| value.round() as u16)
+    })
+}
+
+#[cfg(test)]
+/// Tests for the extensions module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test reading the Garmin, Cluetrust and Strava extensions, and skipping the points without any
+    fn test_from_reader() {
+        let xml = r#"<gpx xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1"
+                          xmlns:gpxdata="http://www.cluetrust.com/XML/GPXDATA/1/0">
+            <trk><trkseg>
+                <trkpt lat="1" lon="2"><extensions><gpxtpx:TrackPointExtension>
+                    <gpxtpx:atemp>20.5</gpxtpx:atemp><gpxtpx:hr>142</gpxtpx:hr><gpxtpx:cad>88</gpxtpx:cad>
+                </gpxtpx:TrackPointExtension></extensions></trkpt>
+                <trkpt lat="1" lon="2"><name>No extensions</name></trkpt>
+            </trkseg><trkseg>
+                <trkpt lat="1" lon="2"><extensions>
+                    <gpxdata:hr>150.0</gpxdata:hr><gpxdata:cadence>90</gpxdata:cadence><power>250</power>
+                </extensions></trkpt>
+            </trkseg></trk>
+        </gpx>"#;
+
+        let tracks = GPXExtensions::from_reader(xml.as_bytes()).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].len(), 2);
+        assert_eq!(
+            tracks[0][0][0],
+            GPXExtensions {
+                heart_rate: Some(142),
+                cadence: Some(88),
+                temperature: Some(20.5),
+                power: None,
+            }
+        );
+        assert_eq!(tracks[0][0][1], GPXExtensions::default());
+        assert_eq!(tracks[0][1][0].heart_rate, Some(150));
+        assert_eq!(tracks[0][1][0].cadence, Some(90));
+        assert_eq!(tracks[0][1][0].power, Some(250));
+    }
+}
@@ -2,69 +2,219 @@ use chrono::{DateTime, Datelike, Local};
 /// Defines the `GpxMetadata` struct whih holds the metadata information about the file and its contents, with associated functions.
 use gpx;
 use serde::{Deserialize, Serialize};
-use std::{error::Error, fs::File, path::PathBuf};
-
+use std::{error::Error, io::Write, path::PathBuf};
+use uuid::Uuid;
+
+use crate::analysis::heat::HeatFlag;
+use crate::analysis::pacing::PacingSplit;
+use crate::analysis::recording::RecordingMode;
+#[cfg(feature = "fs")]
+use crate::output_naming::{output_path, write_output};
+use crate::schema::write_summary_json;
 use crate::set_string_field; // From the macros crate.
-use crate::Duration;
+use crate::{Duration, FITEnvironment};
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// Holds the metadata information about the file and its contents
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(default)]
 pub struct GPXMetadata {
     /// THe name of the GPX file from which the information was read.
+    #[serde(rename = "filename")]
     pub filename: Option<PathBuf>,
 
     /// Gpx version used (`Gpx10`, `Gpx11`, or `Unknown`) in this file.
+    #[serde(rename = "version")]
     pub version: Option<String>,
 
     /// Creator name or URL of the software that created the GPX document.
+    #[serde(rename = "creator")]
     pub creator: Option<String>,
 
     /// The name of the GPX file -- this usually corresponds to an activity.
+    #[serde(rename = "activity")]
     pub activity: Option<String>,
 
     /// A description of the contents of the GPX file.
+    #[serde(rename = "description")]
     pub description: Option<String>,
 
     /// The name of the person or organization who created the GPX file.
+    #[serde(rename = "author_name")]
     pub author_name: Option<String>,
 
     /// The email address for the person or organization who created the GPX file.
+    #[serde(rename = "author_email")]
     pub author_email: Option<String>,
 
     /// The first URL associated with the location described in the file.
+    #[serde(rename = "links_href")]
     pub links_href: Option<String>,
 
     /// The descriptive text for the first URL associated with this file.
+    #[serde(rename = "links_text")]
     pub links_text: Option<String>,
 
     /// Keywords associated with the file. Search engines or databases can use this information to classify the data.
+    #[serde(rename = "keywords")]
     pub keywords: Option<String>,
 
     /// The creation date of the file.
+    #[serde(rename = "time")]
     pub time: Option<DateTime<Local>>,
 
     /// The total duration of the activities found in this file.
+    #[serde(rename = "duration")]
     pub duration: Option<Duration>,
 
     /// The name of the person or company the holds the copyright for this GPX file.
+    #[serde(rename = "copyright_author")]
     pub copyright_author: Option<String>,
 
     /// The year the copyright for this file was put in place.
+    #[serde(rename = "copyright_year")]
     pub copyright_year: Option<i32>,
 
     /// The license terms for the GPX file.
+    #[serde(rename = "copyright_license")]
     pub copyright_license: Option<String>,
 
     /// The total number of waypoints (in tracks) found in this GPX file.
+    #[serde(rename = "num_waypoints")]
     pub num_waypoints: usize,
 
     /// The number of tracks found in this file.
+    #[serde(rename = "num_tracks")]
     pub num_tracks: usize,
 
     /// The number of routes found in this file.
+    #[serde(rename = "num_routes")]
     pub num_routes: usize,
+
+    /// Identifies the activity across all the files exported from it.
+    #[serde(rename = "uuid")]
+    pub uuid: Option<Uuid>,
+
+    /// The percentage of the track waypoints with a fix type that have a 3D (or better) fix.
+    #[serde(rename = "pct_3d_fix")]
+    pub pct_3d_fix: Option<f64>,
+
+    /// The average number of satellites used for the track waypoints that report it.
+    #[serde(rename = "avg_satellites")]
+    pub avg_satellites: Option<f64>,
+
+    /// The average horizontal dilution of precision for the track waypoints that report it.
+    #[serde(rename = "avg_hdop")]
+    pub avg_hdop: Option<f64>,
+
+    /// The average speed over the first half of the distance in meters per second.
+    #[serde(rename = "speed_first_half_ms")]
+    pub speed_first_half_ms: Option<f64>,
+
+    /// The average speed over the second half of the distance in meters per second.
+    #[serde(rename = "speed_second_half_ms")]
+    pub speed_second_half_ms: Option<f64>,
+
+    /// How much longer the second half took than the first, in percent. Negative for a negative split.
+    #[serde(rename = "pacing_index_pct")]
+    pub pacing_index_pct: Option<f64>,
+
+    /// Whether the second half was faster (negative), about the same (even) or slower (positive) than the first.
+    #[serde(rename = "pacing_split")]
+    pub pacing_split: Option<PacingSplit>,
+
+    /// The median time between two records in seconds.
+    #[serde(rename = "recording_interval_sec")]
+    pub recording_interval_sec: Option<f64>,
+
+    /// The share of the intervals between records that are one second, in percent.
+    #[serde(rename = "recording_every_second_pct")]
+    pub recording_every_second_pct: Option<f64>,
+
+    /// Whether the device recorded every second or used smart recording.
+    #[serde(rename = "recording_mode")]
+    pub recording_mode: Option<RecordingMode>,
+
+    /// The distance worked out from the track waypoints in meters.
+    #[serde(rename = "distance_m")]
+    pub distance_m: Option<f64>,
+
+    /// The time spent moving, leaving out the stops.
+    #[serde(rename = "duration_moving")]
+    pub duration_moving: Option<Duration>,
+
+    /// The average speed while moving in meters per second.
+    #[serde(rename = "speed_avg_ms")]
+    pub speed_avg_ms: Option<f64>,
+
+    /// The maximum speed in meters per second, smoothed over a few waypoints.
+    #[serde(rename = "speed_max_ms")]
+    pub speed_max_ms: Option<f64>,
+
+    /// The total climb in meters, leaving out small ups and downs from GPS noise.
+    #[serde(rename = "ascent_m")]
+    pub ascent_m: Option<f64>,
+
+    /// The total drop in meters, leaving out small ups and downs from GPS noise.
+    #[serde(rename = "descent_m")]
+    pub descent_m: Option<f64>,
+
+    /// Whether the activity took place outdoors, indoors or in a virtual world, based on the track type and the shape
+    /// of the track.
+    #[serde(rename = "environment")]
+    pub environment: FITEnvironment,
+
+    /// How much the power to heart rate ratio dropped from the first half to the second, in percent (Pw:HR).
+    #[serde(rename = "decoupling_power_pct")]
+    pub decoupling_power_pct: Option<f64>,
+
+    /// How much the speed to heart rate ratio dropped from the first half to the second, in percent (Pa:HR).
+    #[serde(rename = "decoupling_pace_pct")]
+    pub decoupling_pace_pct: Option<f64>,
+
+    /// The average temperature of the track waypoints, in degrees Celsius.
+    #[serde(rename = "temperature_avg_c")]
+    pub temperature_avg_c: Option<f64>,
+
+    /// How much slower the effort was because of the heat, in percent.
+    #[serde(rename = "heat_adjustment_pct")]
+    pub heat_adjustment_pct: Option<f64>,
+
+    /// The average speed the effort would have given at a neutral temperature, in meters per second.
+    #[serde(rename = "speed_heat_adjusted_ms")]
+    pub speed_heat_adjusted_ms: Option<f64>,
+
+    /// Whether the activity took place in hot weather.
+    #[serde(rename = "heat")]
+    pub heat: Option<HeatFlag>,
+
+    /// The time spent in heart rate zone 0, in seconds.
+    #[serde(rename = "heart_rate_zone0_sec")]
+    pub heart_rate_zone0_sec: Option<f64>,
+
+    /// The time spent in heart rate zone 1, in seconds.
+    #[serde(rename = "heart_rate_zone1_sec")]
+    pub heart_rate_zone1_sec: Option<f64>,
+
+    /// The time spent in heart rate zone 2, in seconds.
+    #[serde(rename = "heart_rate_zone2_sec")]
+    pub heart_rate_zone2_sec: Option<f64>,
+
+    /// The time spent in heart rate zone 3, in seconds.
+    #[serde(rename = "heart_rate_zone3_sec")]
+    pub heart_rate_zone3_sec: Option<f64>,
+
+    /// The time spent in heart rate zone 4, in seconds.
+    #[serde(rename = "heart_rate_zone4_sec")]
+    pub heart_rate_zone4_sec: Option<f64>,
+
+    /// The tags of the activity from the tag file, separated by `TAG_SEPARATOR`.
+    #[serde(rename = "tags")]
+    pub tags: Option<String>,
+
+    /// The number of GPS spikes found in the tracks, dropped if set with `Settings::set_gps_cleaning()`.
+    #[serde(rename = "gps_outliers")]
+    pub gps_outliers: Option<usize>,
 }
 
 impl GPXMetadata {
@@ -184,23 +334,38 @@ impl GPXMetadata {
     /// # Errors
     ///
     /// Writing the session data may fail.
+    #[cfg(feature = "fs")]
     pub fn export_json(&self) -> Result<(), Box<dyn Error>> {
-        let mut filename = self
-            .filename
-            .as_ref()
-            .unwrap_or(&PathBuf::from("export"))
-            .clone();
-        filename.set_extension("session.json");
+        let filename = output_path(
+            &self
+                .filename
+                .as_ref()
+                .map_or_else(|| "export".into(), |filename| filename.to_string_lossy()),
+            "session.json",
+        );
         log::trace!(
             "exporter::export_session_json() -- Writing JSON file {:?}",
             &filename.to_str()
         );
 
         // Write the session data to JSON
-        serde_json::to_writer_pretty(&File::create(&filename)?, &self)?;
+        write_output(&filename, |file| self.write_json(file))?;
 
         Ok(())
     }
+
+    /// Write the metadata as JSON to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the JSON is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing or writing the JSON may fail.
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        write_summary_json(self, writer)
+    }
 }
 
 /// Sets the copyright year based on the timestamp found in the metadata. If the
@@ -232,7 +397,8 @@ fn set_link(src_meta: &gpx::Metadata, dest: &mut GPXMetadata) {
     }
 }
 
-/// Sets the time field in the `GPXMetadata` struct based on the time in the `gpx::Metadata` struct.
+/// Sets the time field in the `GPXMetadata` struct based on the time in the `gpx::Metadata` struct. A time that can't
+/// be parsed is left out, and picked up as a warning by `GPXActivity::from_reader()`.
 ///
 /// # Arguments
 ///
@@ -245,8 +411,6 @@ fn set_time(src_meta: &gpx::Metadata, dest: &mut GPXMetadata) {
         if let Ok(ltz_w) = DateTime::parse_from_rfc3339(t.as_str()) {
             let ltz = ltz_w.with_timezone(&Local);
             dest.time = Some(ltz);
-        } else {
-            log::warn!("Unable to parse the time from the header. Yikes.");
         }
     }
 }
@@ -2,11 +2,16 @@
 // Submodules
 pub mod activities;
 pub mod activity;
+pub mod extensions;
 // pub mod fix; // Currently not used.
 pub mod gpxmetadata;
 pub mod link;
 // pub mod person; // Currently not used.
 pub mod route;
+pub mod to_course;
+pub mod to_gpx;
+#[cfg(feature = "fs")]
 pub mod to_hashmap;
+pub mod to_tcx;
 pub mod track;
 pub mod waypoint;
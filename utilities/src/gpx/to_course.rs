@@ -0,0 +1,133 @@
+//! Turns a GPX route or track into a FIT course, for navigating it on a Garmin or Wahoo unit. Routes are planned, so
+//! they are used where the file has one, and the recorded tracks otherwise.
+
+use crate::gpx::waypoint::GPXWaypoint;
+use crate::{CoursePosition, FITCourse, GPXActivity};
+
+impl GPXActivity {
+    /// The course of the first route with at least two positions, or of the tracks joined together if there isn't
+    /// one. The route points are usually the turns, so their names are used for the turns found there. The course is
+    /// named after the route or track, the activity or the file, in that order.
+    ///
+    /// # Returns
+    ///
+    /// `Option<FITCourse>` -- The course, or `None` if neither the routes nor the tracks have two positions.
+    #[must_use]
+    pub fn to_course(&self) -> Option<FITCourse> {
+        if let Some(route) = self
+            .routes
+            .iter()
+            .find(|route| course_positions(&route.points).len() >= 2)
+        {
+            return Some(FITCourse::new(
+                &self.course_name(route.name.as_deref()),
+                route._type.as_deref(),
+                course_positions(&route.points),
+            ));
+        }
+
+        let waypoints: Vec<GPXWaypoint> = self
+            .tracks
+            .iter()
+            .flat_map(|track| track.waypoints.iter().cloned())
+            .collect();
+        let positions = course_positions(&waypoints);
+        if positions.len() < 2 {
+            return None;
+        }
+        let track = self.tracks.first();
+        Some(FITCourse::new(
+            &self.course_name(track.and_then(|track| track.name.as_deref())),
+            track.and_then(|track| track.t_type.as_deref()),
+            positions,
+        ))
+    }
+
+    /// The name of the course: the name given, or the name of the activity or the file.
+    fn course_name(&self, name: Option<&str>) -> String {
+        name.or(self.metadata.activity.as_deref())
+            .map(ToString::to_string)
+            .or_else(|| {
+                self.metadata
+                    .filename
+                    .as_ref()
+                    .and_then(|filename| filename.file_stem())
+                    .map(|stem| stem.to_string_lossy().to_string())
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The waypoints with a position, as course positions.
+fn course_positions(waypoints: &[GPXWaypoint]) -> Vec<CoursePosition> {
+    waypoints
+        .iter()
+        .filter_map(|waypoint| {
+            Some(CoursePosition {
+                latitude: waypoint.latitude?,
+                longitude: waypoint.longitude?,
+                elevation: waypoint.elevation,
+                time: waypoint.time,
+                name: waypoint.name.clone(),
+                distance: 0.0,
+            })
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "fs"))]
+/// Tests for the to_course module
+mod tests {
+    use super::*;
+    use crate::GPXRoute;
+
+    /// A route point `north_m` and `east_m` meters from a point on the equator.
+    fn route_point(north_m: f64, east_m: f64, name: &str) -> GPXWaypoint {
+        GPXWaypoint {
+            latitude: Some(north_m / 111_195.0),
+            longitude: Some(east_m / 111_195.0),
+            name: Some(name.to_string()),
+            ..GPXWaypoint::default()
+        }
+    }
+
+    #[test]
+    /// Test making a course from a route, and from the tracks of a file without one
+    fn test_to_course() {
+        let mut activity = GPXActivity::from_file("../data/running.gpx").unwrap();
+        let course = activity.to_course().unwrap();
+        assert_eq!(
+            course.positions.len(),
+            activity
+                .tracks
+                .iter()
+                .flat_map(|track| &track.waypoints)
+                .filter(|waypoint| waypoint.latitude.is_some())
+                .count()
+        );
+        assert!(course.distance() > 0.0);
+        assert!(!course.name.is_empty());
+
+        activity.routes.push(GPXRoute {
+            name: Some("Loop".to_string()),
+            _type: Some("cycling".to_string()),
+            points: vec![
+                route_point(0.0, 0.0, "Start"),
+                route_point(500.0, 0.0, "Bridge Rd"),
+                route_point(500.0, -500.0, "Finish"),
+            ],
+            ..GPXRoute::default()
+        });
+        let course = activity.to_course().unwrap();
+        assert_eq!(course.name, "Loop");
+        assert_eq!(course.sport.as_deref(), Some("cycling"));
+        assert_eq!(course.course_points.len(), 1);
+        assert_eq!(course.course_points[0].name, "Bridge Rd");
+        assert_eq!(
+            course.course_points[0].point_type,
+            crate::CoursePointType::Left
+        );
+
+        assert!(GPXActivity::new().to_course().is_none());
+    }
+}
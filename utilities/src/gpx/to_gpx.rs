@@ -0,0 +1,177 @@
+//! Writes a GPX activity back out as GPX, with a track per track and a track segment per segment. The heart rate,
+//! cadence and temperature are written as a Garmin `TrackPointExtension`, and the power as a `power` extension, which
+//! is how most applications write it.
+
+use std::error::Error;
+use std::io::Write;
+use xml::writer::{EventWriter, XmlEvent};
+
+use crate::extract::TimeWindow;
+use crate::tcx::to_gpx::{GPX_CREATOR, GPX_NAMESPACE, TRACKPOINT_EXTENSION_NAMESPACE};
+use crate::xml_writer::{write_element, xml_time, xml_writer};
+use crate::{GPXActivity, GPXWaypoint};
+
+impl GPXActivity {
+    /// Writes the tracks of the activity as GPX to any writer. The routes and the waypoints outside the tracks are left
+    /// out, as are the track points without a position.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the GPX is written.
+    ///
+    /// # Returns
+    ///
+    /// `Result<usize, Box<dyn Error>>` -- The number of track points left out because they have no position.
+    ///
+    /// # Errors
+    ///
+    /// Writing may fail.
+    pub fn write_gpx<W: Write>(&self, writer: W) -> Result<usize, Box<dyn Error>> {
+        self.write_gpx_between(writer, None)
+    }
+
+    /// Writes the tracks of the activity as GPX, keeping only the track points recorded within the times given, if any.
+    /// Segments and tracks left without track points are left out.
+    pub(crate) fn write_gpx_between<W: Write>(
+        &self,
+        writer: W,
+        window: Option<&TimeWindow>,
+    ) -> Result<usize, Box<dyn Error>> {
+        let in_window = |wpt: &&GPXWaypoint| window.is_none_or(|window| window.contains(wpt.time));
+
+        let mut writer = xml_writer(writer);
+        writer.write(
+            XmlEvent::start_element("gpx")
+                .attr("version", "1.1")
+                .attr("creator", GPX_CREATOR)
+                .default_ns(GPX_NAMESPACE)
+                .ns("gpxtpx", TRACKPOINT_EXTENSION_NAMESPACE),
+        )?;
+
+        let first_time = self
+            .tracks
+            .iter()
+            .flat_map(|track| track.waypoints.iter().filter(in_window))
+            .find_map(|wpt| wpt.time);
+        if let Some(time) = first_time {
+            writer.write(XmlEvent::start_element("metadata"))?;
+            write_element(&mut writer, "time", &xml_time(&time))?;
+            writer.write(XmlEvent::end_element())?;
+        }
+
+        let mut skipped = 0;
+        for track in &self.tracks {
+            let waypoints: Vec<&GPXWaypoint> = track.waypoints.iter().filter(in_window).collect();
+            skipped += waypoints
+                .iter()
+                .filter(|wpt| wpt.latitude.is_none() || wpt.longitude.is_none())
+                .count();
+            let waypoints: Vec<&GPXWaypoint> = waypoints
+                .into_iter()
+                .filter(|wpt| wpt.latitude.is_some() && wpt.longitude.is_some())
+                .collect();
+            if waypoints.is_empty() {
+                continue;
+            }
+
+            writer.write(XmlEvent::start_element("trk"))?;
+            if let Some(name) = &track.name {
+                write_element(&mut writer, "name", name)?;
+            }
+            if let Some(t_type) = &track.t_type {
+                write_element(&mut writer, "type", t_type)?;
+            }
+            for segment in waypoints.chunk_by(|a, b| a.segment_num == b.segment_num) {
+                writer.write(XmlEvent::start_element("trkseg"))?;
+                for wpt in segment {
+                    write_trkpt(&mut writer, wpt)?;
+                }
+                writer.write(XmlEvent::end_element())?; // trkseg
+            }
+            writer.write(XmlEvent::end_element())?; // trk
+        }
+
+        writer.write(XmlEvent::end_element())?; // gpx
+
+        if skipped > 0 {
+            log::warn!("{skipped} track points have no position and were left out of the GPX.");
+        }
+        Ok(skipped)
+    }
+}
+
+/// Writes a single waypoint as a GPX track point. The waypoint must have a position.
+fn write_trkpt<W: Write>(
+    writer: &mut EventWriter<W>,
+    wpt: &GPXWaypoint,
+) -> Result<(), Box<dyn Error>> {
+    writer.write(
+        XmlEvent::start_element("trkpt")
+            .attr("lat", &wpt.latitude.unwrap_or_default().to_string())
+            .attr("lon", &wpt.longitude.unwrap_or_default().to_string()),
+    )?;
+    if let Some(elevation) = wpt.elevation {
+        write_element(writer, "ele", &elevation.to_string())?;
+    }
+    if let Some(time) = &wpt.time {
+        write_element(writer, "time", &xml_time(time))?;
+    }
+
+    let has_trackpoint_extension =
+        wpt.temperature.is_some() || wpt.heart_rate.is_some() || wpt.cadence.is_some();
+    if has_trackpoint_extension || wpt.power.is_some() {
+        writer.write(XmlEvent::start_element("extensions"))?;
+        if let Some(power) = wpt.power {
+            write_element(writer, "power", &power.to_string())?;
+        }
+        if has_trackpoint_extension {
+            writer.write(XmlEvent::start_element("gpxtpx:TrackPointExtension"))?;
+            if let Some(temperature) = wpt.temperature {
+                write_element(writer, "gpxtpx:atemp", &temperature.to_string())?;
+            }
+            if let Some(hr) = wpt.heart_rate {
+                write_element(writer, "gpxtpx:hr", &hr.to_string())?;
+            }
+            if let Some(cadence) = wpt.cadence {
+                write_element(writer, "gpxtpx:cad", &cadence.to_string())?;
+            }
+            writer.write(XmlEvent::end_element())?; // TrackPointExtension
+        }
+        writer.write(XmlEvent::end_element())?; // extensions
+    }
+
+    writer.write(XmlEvent::end_element())?; // trkpt
+    Ok(())
+}
+
+#[cfg(all(test, feature = "fs"))]
+/// Tests for the to_gpx module
+mod tests {
+    use crate::GPXActivity;
+
+    #[test]
+    /// Test that the GPX written can be read back with the same track points and extensions
+    fn test_write_gpx() {
+        let activity = GPXActivity::from_file("../data/walking2.gpx").unwrap();
+        let mut gpx = Vec::new();
+        assert_eq!(activity.write_gpx(&mut gpx).unwrap(), 0);
+
+        let written = GPXActivity::from_reader(gpx.as_slice(), "written.gpx").unwrap();
+        assert_eq!(written.tracks.len(), activity.tracks.len());
+        assert_eq!(
+            written.tracks[0].waypoints.len(),
+            activity.tracks[0].waypoints.len()
+        );
+        let (first, written_first) = (
+            &activity.tracks[0].waypoints[0],
+            &written.tracks[0].waypoints[0],
+        );
+        assert_eq!(written_first.time, first.time);
+        assert_eq!(written_first.heart_rate, first.heart_rate);
+        assert_eq!(written_first.temperature, first.temperature);
+        assert_eq!(
+            written.metadata.temperature_avg_c,
+            activity.metadata.temperature_avg_c
+        );
+    }
+}
@@ -1,10 +1,12 @@
 use convert_case::{Case, Casing};
-use std::{collections::HashMap, error::Error, fs::File, io::BufReader};
+use std::{collections::HashMap, error::Error, io::BufReader};
 
-use chrono::{Datelike, Timelike};
-use gpx::Gpx;
-
-use crate::GPXMetadata;
+use crate::analysis::derived::mean;
+use crate::date_source::{date_source, insert_date_tokens};
+use crate::input::open_input;
+use crate::placeholder::placeholder;
+use crate::summary_tokens::SummaryTokens;
+use crate::GPXActivity;
 
 /// Parses a GPX file and returns the relevant metadata
 ///
@@ -30,103 +32,73 @@ use crate::GPXMetadata;
 ///
 #[allow(clippy::module_name_repetitions)]
 pub fn gpx_to_hashmap(filename: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
-    let gpx: Gpx = gpx::read(BufReader::new(File::open(filename)?))?;
-    log::debug!("process_gpx::gpx = {:?}", gpx);
-    let gpxmeta = GPXMetadata::from_header(&gpx, filename);
+    // The whole activity is read, since the distance, ascent and heart rates come from the track points
+    let activity = GPXActivity::from_reader(BufReader::new(open_input(filename)?), filename)?;
+    let gpxmeta = activity.metadata.clone();
     log::debug!("process_gpx::gpxmeta = {:?}", gpxmeta);
 
     let mut values = HashMap::<String, String>::new();
     let mf = gpxmeta
         .creator
-        .unwrap_or_else(|| "unknown".to_string())
-        .to_case(Case::Title);
+        .map_or_else(|| placeholder("Unknown"), |mf| mf.to_case(Case::Title));
     values.insert("%manufacturer".to_string(), mf.clone());
     values.insert("%mf".to_string(), mf.clone());
     values.insert("%product".to_string(), mf.clone());
     values.insert("%pr".to_string(), mf);
 
-    let sn = gpxmeta
-        .description
-        .unwrap_or_else(|| "unknown".to_string())
-        .replace("GPX File Created by ", "")
-        .trim()
-        .to_string()
-        .to_case(Case::Title);
+    let sn = gpxmeta.description.map_or_else(
+        || placeholder("Unknown"),
+        |sn| {
+            sn.replace("GPX File Created by ", "")
+                .trim()
+                .to_case(Case::Title)
+        },
+    );
     values.insert("%serial_number".to_string(), sn.clone());
     values.insert("%sn".to_string(), sn);
 
     let ac = gpxmeta
         .activity
-        .unwrap_or_else(|| "unknown".to_string())
-        .to_case(Case::Title);
+        .map_or_else(|| placeholder("Unknown"), |ac| ac.to_case(Case::Title));
     values.insert("%activity".to_string(), ac.clone());
     values.insert("%at".to_string(), ac);
 
-    let ad = "Unknown".to_string();
+    let ad = placeholder("Unknown");
     values.insert("%activity_detailed".to_string(), ad.clone());
     values.insert("%ad".to_string(), ad);
 
-    if let Some(tc) = gpxmeta.time {
-        values.insert("%year".to_string(), format!("{:04}", tc.year()));
-        values.insert("%yr".to_string(), format!("{:04}", tc.year()));
-        values.insert("%month".to_string(), format!("{:02}", tc.month()));
-        values.insert("%mo".to_string(), format!("{:02}", tc.month()));
-        values.insert("%day".to_string(), format!("{:02}", tc.day()));
-        values.insert("%dy".to_string(), format!("{:02}", tc.day()));
-
-        values.insert("%hour".to_string(), format!("{:02}", tc.hour()));
-        values.insert("%hr".to_string(), format!("{:02}", tc.hour()));
-        values.insert("%24hour".to_string(), format!("{:02}", tc.hour()));
-        values.insert("%24".to_string(), format!("{:02}", tc.hour()));
-
-        let (am, hrs) = tc.hour12();
-        let hr = format!("{hrs:02}");
-        values.insert("%12hour".to_string(), hr.clone());
-        values.insert("%12".to_string(), hr);
-        if am {
-            values.insert("%ampm".to_string(), "pm".to_string());
-            values.insert("%ap".to_string(), "pm".to_string());
-        } else {
-            values.insert("%ampm".to_string(), "am".to_string());
-            values.insert("%ap".to_string(), "am".to_string());
-        }
-
-        values.insert("%minute".to_string(), format!("{:02}", tc.minute()));
-        values.insert("%mt".to_string(), format!("{:02}", tc.minute()));
-        values.insert("%second".to_string(), format!("{:02}", tc.second()));
-        values.insert("%sc".to_string(), format!("{:02}", tc.second()));
-        values.insert("%weekday".to_string(), tc.weekday().to_string());
-        values.insert("%wd".to_string(), tc.weekday().to_string());
-    } else {
-        values.insert("%year".to_string(), "0000".to_string());
-        values.insert("%yr".to_string(), "0000".to_string());
-        values.insert("%month".to_string(), "00".to_string());
-        values.insert("%mn".to_string(), "00".to_string());
-        values.insert("%day".to_string(), "00".to_string());
-        values.insert("%dy".to_string(), "00".to_string());
-        values.insert("%hour".to_string(), "00".to_string());
-        values.insert("%hr".to_string(), "00".to_string());
-        values.insert("%hour24".to_string(), "00".to_string());
-        values.insert("%24".to_string(), "00".to_string());
-        values.insert("%hour12".to_string(), "00".to_string());
-        values.insert("%12".to_string(), "00".to_string());
-        values.insert("%ampm".to_string(), "ampm".to_string());
-        values.insert("%ap".to_string(), "ampm".to_string());
-        values.insert("%minute".to_string(), "00".to_string());
-        values.insert("%mn".to_string(), "00".to_string());
-        values.insert("%second".to_string(), "00".to_string());
-        values.insert("%sc".to_string(), "00".to_string());
-        values.insert("%weekday".to_string(), "00".to_string());
-        values.insert("%wd".to_string(), "00".to_string());
-    }
+    // The metadata time is when the file was created, while the first track tells when the activity started
+    let start = activity
+        .tracks
+        .first()
+        .and_then(|track| track.start_time)
+        .map(|st| st.fixed_offset());
+    let created = gpxmeta.time.map(|tc| tc.fixed_offset());
+    insert_date_tokens(&mut values, date_source().pick(start, created, filename));
 
     if let Some(dur) = gpxmeta.duration {
         values.insert("%duration".to_string(), dur.to_string());
         values.insert("%du".to_string(), dur.to_string());
     } else {
-        values.insert("%duration".to_string(), "0".to_string());
-        values.insert("%du".to_string(), "0".to_string());
+        values.insert("%duration".to_string(), placeholder("0"));
+        values.insert("%du".to_string(), placeholder("0"));
+    }
+
+    let heart_rates: Vec<f64> = activity
+        .tracks
+        .iter()
+        .flat_map(|track| &track.waypoints)
+        .filter_map(|wpt| wpt.heart_rate.map(f64::from))
+        .collect();
+    SummaryTokens {
+        distance_m: gpxmeta.distance_m,
+        calories: None,
+        avg_hr: mean(&heart_rates),
+        max_hr: heart_rates.iter().copied().reduce(f64::max),
+        elevation_gain_m: gpxmeta.ascent_m,
+        duration: gpxmeta.duration,
     }
+    .insert(&mut values);
 
     Ok(values)
 }
@@ -0,0 +1,232 @@
+//! Converts a GPX activity to TCX. Each track becomes an activity and each track segment a lap. GPX files don't record
+//! the distance, so it is worked out from the positions of the waypoints.
+
+use chrono::{DateTime, Local};
+use std::error::Error;
+use std::io::Write;
+use xml::writer::{EventWriter, XmlEvent};
+
+use crate::analysis::derived::{haversine_distance, mean};
+use crate::gpx::waypoint::GPXWaypoint;
+use crate::xml_writer::{write_element, xml_time, xml_writer};
+use crate::{normalized_sport, GPXActivity, GPXTrack};
+
+/// The Training Center Database v2 namespace.
+const TCX_NAMESPACE: &str = "http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2";
+
+/// A track waypoint with a time, along with the distance covered since the start of the track.
+struct TimedWaypoint<'a> {
+    waypoint: &'a GPXWaypoint,
+    time: DateTime<Local>,
+    distance: f64,
+}
+
+impl GPXActivity {
+    /// Writes the activity as TCX to any writer. Each track becomes an activity and each track segment becomes a lap.
+    /// TCX trackpoints must have a time, so waypoints without one are left out, as are tracks without any timed
+    /// waypoints. The heart rate and cadence are written where the waypoints have them.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the TCX is written.
+    ///
+    /// # Returns
+    ///
+    /// `Result<usize, Box<dyn Error>>` -- The number of waypoints left out because they have no time.
+    ///
+    /// # Errors
+    ///
+    /// Writing may fail.
+    pub fn write_tcx<W: Write>(&self, writer: W) -> Result<usize, Box<dyn Error>> {
+        let mut writer = xml_writer(writer);
+        writer
+            .write(XmlEvent::start_element("TrainingCenterDatabase").default_ns(TCX_NAMESPACE))?;
+        writer.write(XmlEvent::start_element("Activities"))?;
+
+        let mut skipped = 0;
+        for track in &self.tracks {
+            let waypoints = timed_waypoints(track);
+            skipped += track.waypoints.len() - waypoints.len();
+            if waypoints.is_empty() {
+                continue;
+            }
+
+            let sport = track
+                .t_type
+                .as_deref()
+                .or(self.metadata.activity.as_deref())
+                .unwrap_or_default();
+            writer.write(XmlEvent::start_element("Activity").attr("Sport", tcx_sport(sport)))?;
+            write_element(&mut writer, "Id", &xml_time(&waypoints[0].time))?;
+
+            let mut lap_start_distance = 0.0;
+            for lap in waypoints.chunk_by(|a, b| a.waypoint.segment_num == b.waypoint.segment_num) {
+                write_lap(&mut writer, lap, lap_start_distance)?;
+                lap_start_distance = lap.last().map_or(lap_start_distance, |wpt| wpt.distance);
+            }
+
+            writer.write(XmlEvent::end_element())?; // Activity
+        }
+
+        writer.write(XmlEvent::end_element())?; // Activities
+        writer.write(XmlEvent::end_element())?; // TrainingCenterDatabase
+
+        if skipped > 0 {
+            log::warn!("{skipped} track waypoints have no time and were left out of the TCX.");
+        }
+        Ok(skipped)
+    }
+}
+
+/// The waypoints of the track that have a time, with the distance covered since the first of them.
+fn timed_waypoints(track: &GPXTrack) -> Vec<TimedWaypoint<'_>> {
+    let mut waypoints = Vec::with_capacity(track.waypoints.len());
+    let mut last_position = None;
+    let mut distance = 0.0;
+
+    for waypoint in &track.waypoints {
+        let Some(time) = waypoint.time else {
+            continue;
+        };
+        if let (Some(lat), Some(lon)) = (waypoint.latitude, waypoint.longitude) {
+            if let Some(last) = last_position {
+                distance += haversine_distance(last, (lat, lon));
+            }
+            last_position = Some((lat, lon));
+        }
+        waypoints.push(TimedWaypoint {
+            waypoint,
+            time,
+            distance,
+        });
+    }
+
+    waypoints
+}
+
+/// Writes the waypoints of a track segment as a TCX lap.
+#[allow(clippy::cast_precision_loss)]
+fn write_lap<W: Write>(
+    writer: &mut EventWriter<W>,
+    lap: &[TimedWaypoint],
+    start_distance: f64,
+) -> Result<(), Box<dyn Error>> {
+    let (Some(first), Some(last)) = (lap.first(), lap.last()) else {
+        return Ok(());
+    };
+    let heart_rates: Vec<u16> = lap
+        .iter()
+        .filter_map(|wpt| wpt.waypoint.heart_rate)
+        .collect();
+
+    writer.write(XmlEvent::start_element("Lap").attr("StartTime", &xml_time(&first.time)))?;
+    let seconds = (last.time - first.time).num_milliseconds() as f64 / 1000.0;
+    write_element(writer, "TotalTimeSeconds", &seconds.to_string())?;
+    write_element(
+        writer,
+        "DistanceMeters",
+        &(last.distance - start_distance).to_string(),
+    )?;
+    write_element(writer, "Calories", "0")?;
+    let avg = mean(
+        &heart_rates
+            .iter()
+            .map(|hr| f64::from(*hr))
+            .collect::<Vec<_>>(),
+    );
+    if let (Some(avg), Some(max)) = (avg, heart_rates.iter().max()) {
+        write_heart_rate(writer, "AverageHeartRateBpm", &format!("{avg:.0}"))?;
+        write_heart_rate(writer, "MaximumHeartRateBpm", &max.to_string())?;
+    }
+    write_element(writer, "Intensity", "Active")?;
+    write_element(writer, "TriggerMethod", "Manual")?;
+
+    writer.write(XmlEvent::start_element("Track"))?;
+    for wpt in lap {
+        writer.write(XmlEvent::start_element("Trackpoint"))?;
+        write_element(writer, "Time", &xml_time(&wpt.time))?;
+        if let (Some(lat), Some(lon)) = (wpt.waypoint.latitude, wpt.waypoint.longitude) {
+            writer.write(XmlEvent::start_element("Position"))?;
+            write_element(writer, "LatitudeDegrees", &lat.to_string())?;
+            write_element(writer, "LongitudeDegrees", &lon.to_string())?;
+            writer.write(XmlEvent::end_element())?;
+        }
+        if let Some(elevation) = wpt.waypoint.elevation {
+            write_element(writer, "AltitudeMeters", &elevation.to_string())?;
+        }
+        write_element(writer, "DistanceMeters", &wpt.distance.to_string())?;
+        if let Some(hr) = wpt.waypoint.heart_rate {
+            write_heart_rate(writer, "HeartRateBpm", &hr.to_string())?;
+        }
+        // TCX cadence is a single byte
+        if let Some(cadence) = wpt.waypoint.cadence.and_then(|cad| u8::try_from(cad).ok()) {
+            write_element(writer, "Cadence", &cadence.to_string())?;
+        }
+        writer.write(XmlEvent::end_element())?; // Trackpoint
+    }
+    writer.write(XmlEvent::end_element())?; // Track
+
+    writer.write(XmlEvent::end_element())?; // Lap
+    Ok(())
+}
+
+/// Writes a heart rate element, which holds the value in a `<Value>` element.
+fn write_heart_rate<W: Write>(
+    writer: &mut EventWriter<W>,
+    name: &str,
+    value: &str,
+) -> Result<(), Box<dyn Error>> {
+    writer.write(XmlEvent::start_element(name))?;
+    write_element(writer, "Value", value)?;
+    writer.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// TCX only knows running and biking. Everything else is `Other`.
+fn tcx_sport(sport: &str) -> &'static str {
+    match normalized_sport(sport).as_str() {
+        "running" => "Running",
+        "cycling" => "Biking",
+        _ => "Other",
+    }
+}
+
+#[cfg(test)]
+/// Tests for the to_tcx module
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::{TCXActivity, TCXTrackpointList};
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test that the TCX written can be read back with the same trackpoints and distance
+    fn test_write_tcx() {
+        let activity = GPXActivity::from_file("../data/running.gpx").unwrap();
+        let mut tcx = Vec::new();
+        assert_eq!(activity.write_tcx(&mut tcx).unwrap(), 0);
+
+        let summary = TCXActivity::from_reader(tcx.as_slice(), "running.tcx").unwrap();
+        assert_eq!(summary.sport.as_deref(), Some("Running"));
+        assert_eq!(
+            usize::from(summary.num_trackpoints.unwrap_or_default()),
+            activity.tracks[0].waypoints.len()
+        );
+
+        let trackpoints = TCXTrackpointList::from_reader(tcx.as_slice()).unwrap();
+        let last = trackpoints.trackpoints.last().unwrap();
+        assert!(last.distance_meters.is_some_and(|d| d > 1_000.0));
+        assert_eq!(
+            last.time,
+            activity.tracks[0].waypoints.last().unwrap().time.unwrap()
+        );
+    }
+
+    #[test]
+    /// Test mapping the sports to the ones TCX knows
+    fn test_tcx_sport() {
+        assert_eq!(tcx_sport("Running"), "Running");
+        assert_eq!(tcx_sport("ride"), "Biking");
+        assert_eq!(tcx_sport("Indoor Rowing"), "Other");
+    }
+}
@@ -2,9 +2,13 @@
 
 use serde::Serialize;
 use std::path::PathBuf;
+use uuid::Uuid;
 
 use chrono::{DateTime, Local};
 
+use crate::analysis::gps_outliers::{gps_cleaning, gps_outliers, max_gps_speed};
+use crate::analysis::track_stats::{track_stats, StatsPoint, TrackStats};
+use crate::gpx::extensions::GPXExtensions;
 use crate::gpx::waypoint::GPXWaypoint;
 use crate::set_string_field; // from the macros crate
 use crate::Duration; // from the macros crate
@@ -16,44 +20,89 @@ use crate::Duration; // from the macros crate
 #[allow(clippy::module_name_repetitions)]
 pub struct GPXTrack {
     /// The original file name containing the track
+    #[serde(rename = "filename")]
     pub filename: Option<PathBuf>,
 
     /// The track number if the overall file. Often cordestponds to Lap Number.
+    #[serde(rename = "track_num")]
     pub track_num: usize,
 
     /// GPS name of track.
+    #[serde(rename = "name")]
     pub name: Option<String>,
 
     /// Start time for the track
+    #[serde(rename = "start_time")]
     pub start_time: Option<DateTime<Local>>,
 
     /// Duration for the track
+    #[serde(rename = "duration")]
     pub duration: Option<Duration>,
 
     /// GPS comment for track.
+    #[serde(rename = "comment")]
     pub comment: Option<String>,
 
     /// User description of track.
+    #[serde(rename = "description")]
     pub description: Option<String>,
 
     /// Source of data. Included to give user some idea of reliability and accuracy of data.
+    #[serde(rename = "source")]
     pub source: Option<String>,
 
     /// The URL of the first link to external information about the track.
+    #[serde(rename = "links_href")]
     pub links_href: Option<String>,
 
     /// The description of the first link to external information about the track.
+    #[serde(rename = "links_text")]
     pub links_text: Option<String>,
 
     /// Type (classification) of track.
+    #[serde(rename = "t_type")]
     pub t_type: Option<String>,
 
     /// Number of track segments within this track
+    #[serde(rename = "num_segments")]
     pub num_segments: usize,
 
     /// Total number of waypoints within this track
+    #[serde(rename = "num_waypoints")]
     pub num_waypoints: usize,
 
+    /// The UUID of the activity the track belongs to.
+    #[serde(rename = "activity_uuid")]
+    pub activity_uuid: Option<Uuid>,
+
+    /// The distance worked out from the track waypoints in meters.
+    #[serde(rename = "distance_m")]
+    pub distance_m: Option<f64>,
+
+    /// The time spent moving, leaving out the stops.
+    #[serde(rename = "duration_moving")]
+    pub duration_moving: Option<Duration>,
+
+    /// The average speed while moving in meters per second.
+    #[serde(rename = "speed_avg_ms")]
+    pub speed_avg_ms: Option<f64>,
+
+    /// The maximum speed in meters per second, smoothed over a few waypoints.
+    #[serde(rename = "speed_max_ms")]
+    pub speed_max_ms: Option<f64>,
+
+    /// The total climb in meters, leaving out small ups and downs from GPS noise.
+    #[serde(rename = "ascent_m")]
+    pub ascent_m: Option<f64>,
+
+    /// The total drop in meters, leaving out small ups and downs from GPS noise.
+    #[serde(rename = "descent_m")]
+    pub descent_m: Option<f64>,
+
+    /// The number of GPS spikes found in the waypoints, or `None` if none of them have a position (not serialized).
+    #[serde(skip)]
+    pub gps_outliers: Option<usize>,
+
     /// The list of waypoints in this track (not serialized)
     #[serde(skip)] // Do not serialize - we'll handle it in the export. Maybe.
     pub waypoints: Vec<GPXWaypoint>,
@@ -119,6 +168,85 @@ impl GPXTrack {
 
         dest
     }
+
+    /// Adds the heart rate, cadence, temperature and power from the track point extensions to the waypoints.
+    ///
+    /// # Arguments
+    ///
+    /// `extensions: &[Vec<GPXExtensions>]` -- The extensions of the track by segment and track point, as read by
+    /// `GPXExtensions::from_reader()`.
+    pub fn set_extensions(&mut self, extensions: &[Vec<GPXExtensions>]) {
+        for wpt in &mut self.waypoints {
+            if let Some(ext) = extensions
+                .get(wpt.segment_num.wrapping_sub(1))
+                .and_then(|segment| segment.get(wpt.waypoint_mum.wrapping_sub(1)))
+            {
+                ext.apply(wpt);
+            }
+        }
+    }
+
+    /// Finds the GPS spikes in the waypoints, and drops them if set with `Settings::set_gps_cleaning()`. The spikes are
+    /// never the first or last waypoint, so the start time and duration stay the same.
+    pub fn clean_gps(&mut self) {
+        if !self.waypoints.iter().any(|wpt| wpt.latitude.is_some()) {
+            self.gps_outliers = None;
+            return;
+        }
+
+        let points: Vec<StatsPoint> = self
+            .waypoints
+            .iter()
+            .map(|wpt| StatsPoint {
+                time: wpt.time,
+                position: wpt.latitude.zip(wpt.longitude),
+                elevation: wpt.elevation,
+            })
+            .collect();
+        let outliers = gps_outliers(&points, max_gps_speed());
+        self.gps_outliers = Some(outliers.len());
+        if gps_cleaning().is_some() && !outliers.is_empty() {
+            let mut num = 0;
+            self.waypoints.retain(|_| {
+                num += 1;
+                outliers.binary_search(&(num - 1)).is_err()
+            });
+            self.num_waypoints = self.waypoints.len();
+        }
+    }
+
+    /// Works out the distance, moving time, speed, ascent and descent from the waypoints of each segment, and sets
+    /// them on the track.
+    ///
+    /// # Returns
+    ///
+    /// `TrackStats` -- The numbers that were set, for adding up the totals of the activity.
+    pub fn set_track_stats(&mut self) -> TrackStats {
+        let mut segments: Vec<Vec<StatsPoint>> = Vec::new();
+        let mut current_segment = None;
+        for wpt in &self.waypoints {
+            if current_segment != Some(wpt.segment_num) {
+                segments.push(Vec::new());
+                current_segment = Some(wpt.segment_num);
+            }
+            if let Some(segment) = segments.last_mut() {
+                segment.push(StatsPoint {
+                    time: wpt.time,
+                    position: wpt.latitude.zip(wpt.longitude),
+                    elevation: wpt.elevation,
+                });
+            }
+        }
+
+        let stats = track_stats(&segments);
+        self.distance_m = stats.distance_m;
+        self.duration_moving = stats.moving_time_sec.map(Duration::from_secs_f64);
+        self.speed_avg_ms = stats.avg_speed_ms();
+        self.speed_max_ms = stats.max_speed_ms;
+        self.ascent_m = stats.ascent_m;
+        self.descent_m = stats.descent_m;
+        stats
+    }
 }
 
 /// Reads the waypoints from the original file and adds them to the `Track` struct.
@@ -148,11 +276,13 @@ fn get_waypoints(src: &gpx::Track, dest: &mut GPXTrack) {
     );
 }
 
-/// Sets the start time and duration for the track based on the waypoints in the track. Note that this needs the number of waypoints to be set.
+/// Sets the start time and duration for the track based on the waypoints in the track. Note that this needs the number
+/// of waypoints to be set.
 ///
 /// # Arguments
 ///
-/// - `dest: &mut Track` -- The `Track` struct to which the times are added. This also contains the waypoint information.
+/// - `dest: &mut Track` -- The `Track` struct to which the times are added. This also contains the waypoint
+///   information.
 fn set_times(dest: &mut GPXTrack) {
     if dest.num_waypoints > 0 {
         let t_now = Local::now();
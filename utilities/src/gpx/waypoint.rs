@@ -2,6 +2,7 @@
 
 use chrono::{DateTime, Local};
 use serde::Serialize;
+use uuid::Uuid;
 
 use crate::set_string_field;
 
@@ -11,102 +12,142 @@ use crate::set_string_field;
 #[allow(clippy::module_name_repetitions)]
 pub struct GPXWaypoint {
     /// Track number to which this waypoint belongs - `0` if part of Route or separate Waypoint.
+    #[serde(rename = "track_num")]
     pub track_num: usize,
 
     /// Route number - `0` if not relevant.
+    #[serde(rename = "route_num")]
     pub route_num: usize,
 
     /// Segment number - `0` if not relevant.
+    #[serde(rename = "segment_num")]
     pub segment_num: usize,
 
     /// Waypoint number - Typically incremeents in fixed time durations.
+    #[serde(rename = "waypoint_mum")]
     pub waypoint_mum: usize,
 
     /// The geographical point - longitude.
+    #[serde(rename = "longitude")]
     pub longitude: Option<f64>,
 
     /// The geographical point - latitude.
+    #[serde(rename = "latitude")]
     pub latitude: Option<f64>,
 
     /// Elevation (in meters) of the point.
+    #[serde(rename = "elevation")]
     pub elevation: Option<f64>,
 
     /// Speed (in meters per second) (only in GPX 1.0)
+    #[serde(rename = "speed")]
     pub speed: Option<f64>,
 
     /// Creation/modification DateTime<Utc> for element. Date and time in are in
     /// Universal Coordinated Time (UTC), not local time! Conforms to ISO 8601
     /// specification for date/time repdestentation. Fractional seconds are
     /// allowed for millisecond timing in tracklogs.
+    #[serde(rename = "time")]
     pub time: Option<DateTime<Local>>,
 
     /// The GPS name of the waypoint. This field will be transferred to and
     /// from the GPS. GPX does not place desttrictions on the length of this
     /// field or the characters contained in it. It is up to the receiving
     /// application to validate the field before sending it to the GPS.
+    #[serde(rename = "name")]
     pub name: Option<String>,
 
     /// GPS waypoint comment. Sent to GPS as comment.
+    #[serde(rename = "comment")]
     pub comment: Option<String>,
 
     /// A text description of the element. Holds additional information about
     /// the element intended for the user, not the GPS.
+    #[serde(rename = "description")]
     pub description: Option<String>,
 
     /// Source of data. Included to give user some idea of reliability and
     /// accuracy of data. "Garmin eTrex", "USGS quad Boston North", e.g.
+    #[serde(rename = "source")]
     pub source: Option<String>,
 
     /// Number of links to additional information about the waypoint.
+    #[serde(rename = "num_links")]
     pub num_links: usize,
 
     /// URL for the first link to additional information about the waypoint.
+    #[serde(rename = "links_href")]
     pub links_href: Option<String>,
 
     /// Descriptive text about the first link to additional information about the waypoint.
+    #[serde(rename = "links_text")]
     pub links_text: Option<String>,
 
     /// Text of GPS symbol name. For interchange with other programs, use the
     /// exact spelling of the symbol as displayed on the GPS. If the GPS
     /// abbreviates words, spell them out.
+    #[serde(rename = "symbol")]
     pub symbol: Option<String>,
 
     /// Type (classification) of the waypoint.
+    #[serde(rename = "w_type")]
     pub w_type: Option<String>,
 
     // <magvar> degreesType </magvar> [0..1] ?
     /// Height of geoid in meters above WGS 84. This cordestpond to the sea level.
+    #[serde(rename = "geoidheight")]
     pub geoidheight: Option<f64>,
 
     /// Type of GPS fix. `none` means GPS had no fix. To signify "the fix info
     /// is unknown", leave out `fix` entirely. Value comes from the list
     /// `{'none'|'2d'|'3d'|'dgps'|'pps'}`, where `pps` means that the military
     /// signal was used.
+    #[serde(rename = "fix")]
     pub fix: Option<String>,
 
     /// Number of satellites used to calculate the GPX fix.
+    #[serde(rename = "sat")]
     pub sat: Option<u64>,
 
     /// Horizontal dilution of precision.
+    #[serde(rename = "hdop")]
     pub hdop: Option<f64>,
 
     /// Vertical dilution of precision.
+    #[serde(rename = "vdop")]
     pub vdop: Option<f64>,
 
     /// Positional dilution of precision.
+    #[serde(rename = "pdop")]
     pub pdop: Option<f64>,
 
     /// Number of seconds since last DGPS update, from the <ageofdgpsdata> element.
+    #[serde(rename = "age")]
     pub age: Option<f64>,
 
     /// ID of DGPS station used in differential correction, in the range [0, 1023].
+    #[serde(rename = "dgpsid")]
     pub dgpsid: Option<u16>,
 
-    /// Placeholder: Heart Rate in Beats per Minute.
+    /// Heart Rate in Beats per Minute, from the track point extensions.
+    #[serde(rename = "heart_rate")]
     pub heart_rate: Option<u16>,
 
-    /// Placeholder: Cadence in Beats/Revolutions/Strokes per Minute
+    /// Cadence in Beats/Revolutions/Strokes per Minute, from the track point extensions.
+    #[serde(rename = "cadence")]
     pub cadence: Option<u16>,
+
+    /// The UUID of the activity the waypoint belongs to.
+    #[serde(rename = "activity_uuid")]
+    pub activity_uuid: Option<Uuid>,
+
+    /// Air temperature in degrees Celsius, from the track point extensions.
+    #[serde(rename = "temperature")]
+    pub temperature: Option<f64>,
+
+    /// Power in Watts, from the track point extensions.
+    #[serde(rename = "power")]
+    pub power: Option<u16>,
 }
 
 impl GPXWaypoint {
@@ -152,8 +193,8 @@ impl GPXWaypoint {
         dest.age = src.dgps_age;
         dest.dgpsid = src.dgpsid;
 
-        // We currently don't have any way of extracting heart rate and cadence from
-        // the GPX file using the Gpx struct, so we're just omitting those for now.
+        // The `gpx` crate skips the extensions, so the heart rate, cadence, temperature and power are added afterwards
+        // from `GPXExtensions`.
 
         // return it
         dest
@@ -0,0 +1,129 @@
+//! Opens the activity files for reading. Gzipped files, e.g. the `activities/*.fit.gz` of a Strava bulk export, are
+//! decompressed as they are read, so they can be given to the tools just like the files inside. Files found in zip
+//! archives by `expand_archives()` are read from the archive.
+
+use flate2::read::MultiGzDecoder;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+
+use crate::archive::{is_archive_member, read_archive_member};
+use crate::is_gzipped;
+
+/// Opens the file for reading, decompressing it on the way if it is gzipped. Files found in a zip archive are read
+/// from the archive.
+///
+/// # Arguments
+///
+/// `filename: &str` -- The file to read, e.g. `running.fit` or `running.fit.gz`.
+///
+/// # Returns
+///
+/// `Result<Box<dyn Read + Send>, Box<dyn Error>>` -- The contents of the file, decompressed if need be.
+///
+/// # Errors
+///
+/// Opening the file or the archive holding it may fail.
+pub fn open_input(filename: &str) -> Result<Box<dyn Read + Send>, Box<dyn Error>> {
+    if let Some(contents) = read_archive_member(filename)? {
+        log::trace!("input::open_input() -- Reading {filename} from an archive");
+        return if is_gzipped(filename) {
+            Ok(Box::new(MultiGzDecoder::new(Cursor::new(contents))))
+        } else {
+            Ok(Box::new(Cursor::new(contents)))
+        };
+    }
+
+    let file = File::open(filename)?;
+    if is_gzipped(filename) {
+        log::trace!("input::open_input() -- Decompressing {filename}");
+        Ok(Box::new(MultiGzDecoder::new(BufReader::new(file))))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Reads the whole file into memory, decompressing it if it is gzipped.
+///
+/// # Arguments
+///
+/// `filename: &str` -- The file to read.
+///
+/// # Returns
+///
+/// `Result<Vec<u8>, Box<dyn Error>>` -- The contents of the file, decompressed if need be.
+///
+/// # Errors
+///
+/// Reading the file may fail, or it may not be valid gzip.
+pub fn read_input(filename: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if !is_gzipped(filename) {
+        if let Some(contents) = read_archive_member(filename)? {
+            return Ok(contents);
+        }
+        return Ok(std::fs::read(filename)?);
+    }
+
+    let mut buffer = Vec::new();
+    open_input(filename)?.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// The size of the file once decompressed, in bytes. Gzip files end with the size of the data inside, modulo 2^32,
+/// which is taken as it is, since activity files are far smaller. Files in an archive are small enough to be read to
+/// find out.
+///
+/// # Arguments
+///
+/// `filename: &str` -- The file to check.
+///
+/// # Returns
+///
+/// `Result<u64, Box<dyn Error>>` -- The size of the contents.
+///
+/// # Errors
+///
+/// Reading the file metadata may fail, or the gzip file may be too short to hold the size.
+pub(crate) fn input_size(filename: &str) -> Result<u64, Box<dyn Error>> {
+    if is_archive_member(filename) {
+        return Ok(read_input(filename)?.len() as u64);
+    }
+    if !is_gzipped(filename) {
+        return Ok(std::fs::metadata(filename)?.len());
+    }
+
+    let mut file = File::open(filename)?;
+    file.seek(SeekFrom::End(-4))?;
+    let mut size = [0u8; 4];
+    file.read_exact(&mut size)?;
+    Ok(u64::from(u32::from_le_bytes(size)))
+}
+
+#[cfg(test)]
+/// Tests for the input module
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    /// Test reading a gzipped file and a plain one
+    fn test_read_input() {
+        let plain = std::fs::read("../data/running.gpx").unwrap();
+        let gz = std::env::temp_dir().join("fitutils_test_read_input.gpx.gz");
+        let mut encoder = GzEncoder::new(File::create(&gz).unwrap(), Compression::default());
+        encoder.write_all(&plain).unwrap();
+        encoder.finish().unwrap();
+
+        assert_eq!(read_input(gz.to_str().unwrap()).unwrap(), plain);
+        assert_eq!(read_input("../data/running.gpx").unwrap(), plain);
+        assert!(read_input("../data/running.gpx.gz").is_err());
+        assert_eq!(
+            input_size(gz.to_str().unwrap()).unwrap(),
+            plain.len() as u64
+        );
+
+        std::fs::remove_file(gz).unwrap();
+    }
+}
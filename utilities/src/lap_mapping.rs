@@ -0,0 +1,79 @@
+//! Defines the `LapMapping` enum used to choose how the laps are laid out when writing GPX. Some applications show each
+//! track segment of a track as one line, while others only tell tracks apart, so the laps can be written either way.
+//!
+//! The mapping is set once for the whole run using `Settings::set_lap_mapping()`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Settings;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// How the laps of an activity are laid out in GPX.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum LapMapping {
+    /// Each activity becomes a track, and each lap a track segment within it.
+    #[default]
+    Segments,
+    /// Each lap becomes a track of its own, named after the sport and the lap number.
+    Tracks,
+}
+
+impl LapMapping {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 2] = ["segments", "tracks"];
+}
+
+impl FromStr for LapMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "segments" | "segment" => Ok(Self::Segments),
+            "tracks" | "track" => Ok(Self::Tracks),
+            _ => Err(format!("Unknown lap mapping {s}.")),
+        }
+    }
+}
+
+impl fmt::Display for LapMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Segments => write!(f, "segments"),
+            Self::Tracks => write!(f, "tracks"),
+        }
+    }
+}
+
+impl Settings {
+    /// Sets how the laps are written to GPX.
+    ///
+    /// # Arguments
+    ///
+    /// `mapping: LapMapping` -- Whether the laps become track segments or tracks.
+    pub fn set_lap_mapping(&mut self, mapping: LapMapping) {
+        self.lap_mapping = mapping;
+    }
+}
+
+/// How the laps are currently written to GPX.
+pub(crate) fn lap_mapping() -> LapMapping {
+    Settings::current().lap_mapping
+}
+
+#[cfg(test)]
+/// Tests for the lap_mapping module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test reading the lap mappings from the command line values and back
+    fn test_from_str() {
+        for value in LapMapping::VALUES {
+            assert_eq!(value.parse::<LapMapping>().unwrap().to_string(), value);
+        }
+        assert_eq!("Track".parse::<LapMapping>(), Ok(LapMapping::Tracks));
+        assert!("laps".parse::<LapMapping>().is_err());
+    }
+}
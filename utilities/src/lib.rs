@@ -1,29 +1,189 @@
+mod activity;
+mod activity_id;
+pub mod analysis;
+#[cfg(feature = "fs")]
+mod archive;
+#[cfg(feature = "fs")]
+mod atomic_file;
+#[cfg(feature = "cli")]
 mod build_logs;
+#[cfg(feature = "fs")]
+mod cancel;
+mod columns;
+#[cfg(feature = "cli")]
+mod config;
+#[cfg(feature = "fs")]
+mod convert;
+mod csv_format;
+mod csv_schema;
+#[cfg(feature = "fs")]
+mod date_source;
+mod dedup;
 mod duration;
+mod exporters;
 mod extensions;
+mod extract;
 mod fit;
+mod geojson;
 mod gpx;
+#[cfg(feature = "fs")]
+mod input;
+mod lap_mapping;
 mod macros;
+#[cfg(feature = "fs")]
+mod memory;
+mod merge;
+mod min_activity;
+#[cfg(feature = "fs")]
+mod output_naming;
+mod placeholder;
+mod precision;
+mod privacy;
+#[cfg(feature = "fs")]
+mod processing;
+mod processing_error;
+mod profile_svg;
+mod query;
+mod schema;
+mod settings;
+mod stats;
+#[cfg(feature = "fs")]
+mod summary_cache;
+mod summary_field;
+mod summary_split;
+#[cfg(feature = "fs")]
+mod summary_tokens;
+mod table;
+mod tags;
 mod tcx;
+mod title;
+mod track_colors;
+mod track_point;
+mod trim;
+mod units;
+mod warnings;
+#[cfg(feature = "watch")]
+mod watch;
+mod xml_writer;
 
 pub use crate::fit::{
-    activities::FITActivities, activity::FITActivity, hrzones::FITHrZones, lap::FITLap,
-    record::FITRecord, session::FITSession, to_hashmap::fit_to_hashmap,
+    activities::FITActivities,
+    activity::FITActivity,
+    altitude::AltitudeSource,
+    constfunc::{degrees_to_semicircles, semicircles_to_degrees},
+    course::{CoursePoint, CoursePointType, CoursePosition, FITCourse, DEFAULT_COURSE_SPEED_MS},
+    device_info::FITDeviceInfo,
+    environment::FITEnvironment,
+    event::FITEvent,
+    hrzones::FITHrZones,
+    lap::FITLap,
+    parse_stats::FITParseStats,
+    products::product_name,
+    record::FITRecord,
+    session::FITSession,
+    split::FITSplit,
 };
 
 pub use crate::gpx::{
-    activities::GPXActivities, activity::GPXActivity, gpxmetadata::GPXMetadata, link::GPXLink,
-    route::GPXRoute, to_hashmap::gpx_to_hashmap, track::GPXTrack, waypoint::GPXWaypoint,
+    activities::GPXActivities, activity::GPXActivity, extensions::GPXExtensions,
+    gpxmetadata::GPXMetadata, link::GPXLink, route::GPXRoute, track::GPXTrack,
+    waypoint::GPXWaypoint,
 };
 
 pub use crate::tcx::{
     activity::{TCXActivitiesList, TCXActivity},
-    to_hashmap::tcx_to_hashmap,
+    creator::TCXCreator,
+    lap::TCXLap,
+    quirks::read_tcx,
     trackpoints::{TCXTrackpoint, TCXTrackpointList},
 };
 
 pub use crate::{
-    build_logs::build_log,
+    activity::{Activity, ActivityFormat},
+    activity_id::activity_uuid,
+    analysis::compare::{print_comparison, SessionTotals},
+    analysis::gps_outliers::DEFAULT_MAX_GPS_SPEED_MS,
+    analysis::hr_scatter::{HrScatter, SCATTER_WARM_UP_SEC, SCATTER_WINDOW_SEC},
+    analysis::hr_zones::HrZoneLimits,
+    analysis::laps::{print_laps, LapSummary},
+    analysis::mean_max::{best_mean_max, print_mean_max, MeanMax},
+    analysis::power::PowerMetrics,
+    analysis::race::{print_race_report, RaceDistance, RaceMark},
+    analysis::running::{print_pace_analysis, PaceZoneLimits},
+    analysis::series::{print_chart, sparkline, RecordSeries, SeriesPoint},
+    analysis::splits::{print_splits, Split, SplitUnit},
+    analysis::validate::{
+        TotalCheck, ValidatedTotal, Validation, DEFAULT_VALIDATION_TOLERANCE_PCT,
+    },
+    columns::{ColumnsVersion, CsvFile},
+    csv_format::CsvFormat,
+    csv_schema::{write_csv_schema, ColumnSchema, ColumnType, FileSchema},
+    dedup::{duplicate_sets, DedupTolerance, DuplicateSet},
     duration::Duration,
-    extensions::{get_extension, set_extension},
+    exporters::ExportFormat,
+    extensions::{get_extension, has_extension, is_gzipped, set_extension},
+    extract::{parse_distance, ExtractRange},
+    geojson::{feature_collection, geojson_position, write_geojson, GeoJsonProperties},
+    lap_mapping::LapMapping,
+    min_activity::MinActivity,
+    precision::{FloatPrecision, Rounded},
+    privacy::hash_serial,
+    processing_error::{ProcessingError, EXIT_PARTIAL_FAILURE},
+    profile_svg::{profile_points, profile_svg, write_profile_svg, ProfilePoint},
+    query::{Query, QueryFields},
+    schema::SummarySchema,
+    settings::{Settings, SettingsGuard},
+    stats::{
+        print_rollups, rollups, write_rollups_csv, write_rollups_json, Rollup, SportTotals,
+        StatsPeriod, WeekStart,
+    },
+    summary_field::{SummaryField, SummaryValue},
+    summary_split::{normalized_sport, SummarySplit},
+    table::Table,
+    tags::{DEFAULT_TAG_FILE, TAG_SEPARATOR},
+    title::{fill_template, DEFAULT_TITLE_TEMPLATE},
+    track_point::TrackPoint,
+    trim::TrimOptions,
+    units::{Measure, UnitSystem},
 };
+
+#[cfg(feature = "fs")]
+pub use crate::{
+    analysis::hr_scatter::export_hr_scatter_csv,
+    analysis::mean_max::export_mean_max_csv,
+    archive::{expand_archives, is_archive, is_archive_member},
+    atomic_file::{write_atomically, AtomicFile},
+    convert::{activity_json, convert_file},
+    date_source::DateSource,
+    extract::extract_file,
+    fit::to_hashmap::fit_to_hashmap,
+    geojson::export_geojson,
+    gpx::to_hashmap::gpx_to_hashmap,
+    input::{open_input, read_input},
+    memory::{estimate_memory, exceeds_memory_limit, DEFAULT_CHUNK_SIZE},
+    merge::merge_files,
+    output_naming::OutputNaming,
+    processing::{
+        activity_files, activity_summaries, export_manifest, flag_overlaps, log_written,
+        process_fit_file, process_fit_files, process_gpx_file, process_gpx_file_tracks,
+        process_gpx_files, process_tcx_file, process_tcx_files, process_with_timeout,
+        report_failures, written_totals, ProcessingOptions, ProcessingResult, ProcessingStats,
+        RowCounts, TcxDetailFiles,
+    },
+    profile_svg::export_profile_svg,
+    summary_cache::{open_summary_cache, save_summary_cache},
+    tags::{open_tag_store, save_tag_store},
+    tcx::to_hashmap::tcx_to_hashmap,
+    title::activity_title,
+    trim::trim_file,
+};
+
+#[cfg(feature = "cli")]
+pub use crate::{
+    build_logs::build_log,
+    config::{with_config, Config, CONFIG_ENV},
+    min_activity::min_activity_from_args,
+};
+
+#[cfg(feature = "watch")]
+pub use crate::watch::watch_directory;
@@ -0,0 +1,107 @@
+//! Estimates how much memory parsing a file will take, so very large inputs can be exported in chunks.
+
+use std::error::Error;
+
+use crate::get_extension;
+use crate::input::input_size;
+
+/// The number of rows written between each flush when exporting in chunks.
+pub const DEFAULT_CHUNK_SIZE: usize = 10_000;
+
+/// Number of bytes in a megabyte, used when converting the `--max-memory` value.
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Rough number of bytes of memory used per byte of input file once it has been parsed.
+///
+/// FIT is a compact binary format, so each byte expands a lot once decoded into records.
+/// GPX and TCX are verbose XML formats, so they expand less.
+fn expansion_factor(extension: &str) -> u64 {
+    match extension {
+        "fit" => 40,
+        "gpx" | "tcx" => 8,
+        _ => 10,
+    }
+}
+
+/// Estimates the amount of memory (in bytes) needed to fully parse the file. Gzipped files are estimated from the size
+/// of the data inside.
+///
+/// # Arguments
+///
+/// `filename: &str` -- The name of the file to be parsed.
+///
+/// # Returns
+///
+/// `Result<u64, Box<dyn Error>>` -- The estimated number of bytes needed.
+///
+/// # Errors
+///
+/// Reading the file metadata may fail.
+///
+/// # Example
+///
+/// ```text
+/// let bytes = estimate_memory("data/rowing.fit")?;
+/// ```
+pub fn estimate_memory(filename: &str) -> Result<u64, Box<dyn Error>> {
+    let file_size = input_size(filename)?;
+    Ok(file_size.saturating_mul(expansion_factor(&get_extension(filename))))
+}
+
+/// Checks if parsing the file is likely to use more than `max_memory_mb` megabytes of memory,
+/// and lets the user know if that is the case.
+///
+/// # Arguments
+///
+/// - `filename: &str` -- The name of the file to be parsed.
+/// - `max_memory_mb: u64` -- The memory limit in megabytes.
+///
+/// # Returns
+///
+/// `Result<bool, Box<dyn Error>>` -- `true` if the estimate exceeds the limit, `false` otherwise.
+///
+/// # Errors
+///
+/// Reading the file metadata may fail.
+pub fn exceeds_memory_limit(filename: &str, max_memory_mb: u64) -> Result<bool, Box<dyn Error>> {
+    let estimate = estimate_memory(filename)?;
+    let limit = max_memory_mb.saturating_mul(BYTES_PER_MB);
+    log::debug!("memory::exceeds_memory_limit() -- {filename}: estimate = {estimate} bytes, limit = {limit} bytes");
+
+    if estimate > limit {
+        log::warn!(
+            "{filename}: estimated memory use of {} MB exceeds the limit of {max_memory_mb} MB. Exporting in chunks.",
+            estimate / BYTES_PER_MB
+        );
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+/// Tests for the memory module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the expansion factors
+    fn test_expansion_factor() {
+        assert_eq!(expansion_factor("fit"), 40);
+        assert_eq!(expansion_factor("gpx"), 8);
+        assert_eq!(expansion_factor("tcx"), 8);
+        assert_eq!(expansion_factor("unknown"), 10);
+    }
+
+    #[test]
+    /// Test the memory limit check against one of the data files
+    fn test_exceeds_memory_limit() {
+        let filename = "../data/rowing.fit";
+        let estimate = estimate_memory(filename).unwrap();
+        assert!(estimate > 0);
+
+        assert!(exceeds_memory_limit(filename, 0).unwrap());
+        assert!(!exceeds_memory_limit(filename, u64::MAX).unwrap());
+        assert!(exceeds_memory_limit("../data/does_not_exist.fit", 1).is_err());
+    }
+}
@@ -0,0 +1,375 @@
+//! Merges several files from the same workout into a single activity, e.g. when the watch crashed half way and the
+//! rest of the ride ended up in a second file, or a ride was recorded on two devices one after the other.
+//!
+//! The parts are put in order by their start time. Where they overlap, the part that started first is kept and the
+//! records or track points of the later part from before its end are left out. The distances of FIT records that
+//! start again from zero carry on from the end of the part before, and the totals are worked out again from the
+//! records. GPX and TCX files are merged into GPX, and FIT files only with other FIT files.
+
+use chrono::{DateTime, Local};
+use std::error::Error;
+
+use crate::extract::sliced_session;
+#[cfg(feature = "fs")]
+use crate::extract::write_part;
+use crate::{Activity, FITActivity, FITLap, FITRecord, GPXActivity};
+
+impl Activity {
+    /// Merges the activities into one. TCX activities are read through GPX, so they are merged into a GPX activity.
+    ///
+    /// # Arguments
+    ///
+    /// `parts: Vec<Activity>` -- The activities to merge, in any order.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Activity, Box<dyn Error>>` -- The activity merged, with its summary worked out again.
+    ///
+    /// # Errors
+    ///
+    /// There may be no activities, FIT activities may be mixed with GPX or TCX activities, or there may be nothing
+    /// left after the overlaps are removed.
+    pub fn merge(parts: Vec<Self>) -> Result<Self, Box<dyn Error>> {
+        if parts.is_empty() {
+            return Err("There are no activities to merge.".into());
+        }
+
+        if parts.iter().all(|part| matches!(part, Self::Fit(..))) {
+            let fits = parts
+                .into_iter()
+                .filter_map(|part| match part {
+                    Self::Fit(activity) => Some(activity),
+                    _ => None,
+                })
+                .collect();
+            return Ok(Self::Fit(FITActivity::merge(fits)?));
+        }
+
+        let mut gpxs = Vec::with_capacity(parts.len());
+        for part in parts {
+            gpxs.push(match part {
+                Self::Fit(..) => {
+                    return Err("FIT files can only be merged with other FIT files.".into())
+                }
+                Self::Gpx(activity) => activity,
+                Self::Tcx(..) => part.tcx_as_gpx()?,
+            });
+        }
+        Ok(Self::Gpx(GPXActivity::merge(gpxs)?))
+    }
+}
+
+impl FITActivity {
+    /// Merges the activities into one, with a single session worked out again from the records. The laps of each part
+    /// are kept where they have records left, and the device information is taken from the first part. The events and
+    /// device splits are left out.
+    ///
+    /// # Arguments
+    ///
+    /// `parts: Vec<FITActivity>` -- The activities to merge, in any order.
+    ///
+    /// # Errors
+    ///
+    /// There may be no records left after the overlaps are removed.
+    pub fn merge(mut parts: Vec<Self>) -> Result<Self, Box<dyn Error>> {
+        parts.sort_by_key(|part| part.records.iter().find_map(|record| record.timestamp));
+        let Some(first) = parts.first() else {
+            return Err("There are no activities to merge.".into());
+        };
+        warn_mixed_sports(
+            parts
+                .iter()
+                .map(|part| part.session.activity_type.as_deref()),
+        );
+
+        let mut records: Vec<FITRecord> = Vec::new();
+        let mut part_records = Vec::with_capacity(parts.len());
+        for part in &parts {
+            let kept = after(
+                &part.records,
+                last_time(records.iter().map(|record| record.timestamp)),
+                |record| record.timestamp,
+            );
+            let mut kept: Vec<FITRecord> = kept.into_iter().cloned().collect();
+            carry_distance(&records, &mut kept);
+            part_records.push(kept.len());
+            records.extend(kept);
+        }
+        if records.is_empty() {
+            return Err("There are no records left after merging.".into());
+        }
+
+        let mut session = sliced_session(&first.session, &records);
+        for record in &mut records {
+            record.activity_uuid = session.uuid;
+            record.session_num = Some(1);
+        }
+
+        let mut laps: Vec<FITLap> = Vec::new();
+        let mut start = 0;
+        for (part, num_records) in parts.iter().zip(part_records) {
+            let part_laps = part.laps_of(&records[start..start + num_records], session.uuid);
+            start += num_records;
+            for lap in part_laps {
+                laps.push(FITLap {
+                    lap_num: u64::try_from(laps.len() + 1).ok(),
+                    session_num: Some(1),
+                    ..lap
+                });
+            }
+        }
+
+        session.session_num = Some(1);
+        session.num_laps = u16::try_from(laps.len()).ok();
+        session.num_sessions = Some(1);
+        log::debug!(
+            "merge::FITActivity::merge() -- {} parts merged into {} records and {} laps",
+            parts.len(),
+            records.len(),
+            laps.len()
+        );
+
+        Ok(Self {
+            sessions: vec![session.clone()],
+            session,
+            laps,
+            records,
+            events: Vec::new(),
+            device_splits: Vec::new(),
+            devices: first.devices.clone(),
+            warnings: Vec::new(),
+        })
+    }
+}
+
+impl GPXActivity {
+    /// Merges the activities into one, keeping the tracks of each part where they have track points left. The
+    /// summary is worked out again from the track points.
+    ///
+    /// # Arguments
+    ///
+    /// `parts: Vec<GPXActivity>` -- The activities to merge, in any order.
+    ///
+    /// # Errors
+    ///
+    /// There may be no track points left after the overlaps are removed, or writing and reading back the GPX may fail.
+    pub fn merge(mut parts: Vec<Self>) -> Result<Self, Box<dyn Error>> {
+        let first_time = |part: &Self| {
+            part.tracks
+                .iter()
+                .flat_map(|track| &track.waypoints)
+                .find_map(|wpt| wpt.time)
+        };
+        parts.sort_by_key(first_time);
+        warn_mixed_sports(parts.iter().map(|part| {
+            part.tracks
+                .first()
+                .and_then(|track| track.t_type.as_deref())
+        }));
+        let filename = parts
+            .first()
+            .and_then(|part| part.metadata.filename.as_ref())
+            .map(|filename| filename.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut merged = Self::new();
+        let mut last = None;
+        for part in parts {
+            for mut track in part.tracks {
+                let waypoints = std::mem::take(&mut track.waypoints);
+                track.waypoints = after(&waypoints, last, |wpt| wpt.time)
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                if track.waypoints.is_empty() {
+                    continue;
+                }
+                last = last_time(track.waypoints.iter().map(|wpt| wpt.time)).or(last);
+                merged.tracks.push(track);
+            }
+        }
+        if merged.tracks.is_empty() {
+            return Err("There are no track points left after merging.".into());
+        }
+
+        let mut gpx = Vec::new();
+        merged.write_gpx(&mut gpx)?;
+        Self::from_reader(gpx.as_slice(), &filename)
+    }
+}
+
+/// The items recorded after the time given, along with the ones without a time. Items without a time before the first
+/// item kept are left out with the ones before them.
+fn after<T, F>(items: &[T], last: Option<DateTime<Local>>, time: F) -> Vec<&T>
+where
+    F: Fn(&T) -> Option<DateTime<Local>>,
+{
+    let Some(last) = last else {
+        return items.iter().collect();
+    };
+    let first = items
+        .iter()
+        .position(|item| time(item).is_some_and(|time| time > last))
+        .unwrap_or(items.len());
+    items[first..]
+        .iter()
+        .filter(|item| time(item).is_none_or(|time| time > last))
+        .collect()
+}
+
+/// The last time of the items, if any have a time.
+fn last_time<I>(times: I) -> Option<DateTime<Local>>
+where
+    I: DoubleEndedIterator<Item = Option<DateTime<Local>>>,
+{
+    times.rev().flatten().next()
+}
+
+/// Makes the distances of the records of the next part carry on from the records merged so far, if they start again
+/// from a shorter distance, e.g. after the watch was restarted.
+fn carry_distance(merged: &[FITRecord], next: &mut [FITRecord]) {
+    let last = merged.iter().rev().find_map(|record| record.distance);
+    let first = next.iter().find_map(|record| record.distance);
+    let (Some(last), Some(first)) = (last, first) else {
+        return;
+    };
+    if first < last {
+        let offset = last - first;
+        for distance in next
+            .iter_mut()
+            .filter_map(|record| record.distance.as_mut())
+        {
+            *distance += offset;
+        }
+    }
+}
+
+/// Warns if the parts are of different sports, since they are probably not the same workout.
+fn warn_mixed_sports<'a, I>(sports: I)
+where
+    I: Iterator<Item = Option<&'a str>>,
+{
+    let mut sports: Vec<&str> = sports.flatten().collect();
+    sports.sort_unstable();
+    sports.dedup();
+    if sports.len() > 1 {
+        log::warn!(
+            "Merging activities of different sports: {}.",
+            sports.join(", ")
+        );
+    }
+}
+
+/// Merges several activity files into one and writes it to a new file. The format of the new file is worked out from
+/// its extension: FIT files can only be merged into FIT files, and GPX and TCX files into GPX or TCX.
+///
+/// # Arguments
+///
+/// - `inputs: &[&str]` -- The FIT, GPX or TCX files to read, in any order.
+/// - `output: &str` -- The file to write.
+///
+/// # Returns
+///
+/// `Result<Activity, Box<dyn Error>>` -- The activity merged, e.g. for printing its summary.
+///
+/// # Errors
+///
+/// The inputs may not be FIT, GPX or TCX files, FIT files may be mixed with GPX or TCX files, or the output may not be
+/// a format the inputs can be written as. Reading, parsing or writing may fail.
+#[cfg(feature = "fs")]
+pub fn merge_files(inputs: &[&str], output: &str) -> Result<Activity, Box<dyn Error>> {
+    let parts = inputs
+        .iter()
+        .map(|input| Activity::from_file(input))
+        .collect::<Result<Vec<_>, _>>()?;
+    let merged = Activity::merge(parts)?;
+    write_part(&merged, output)?;
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+/// Tests for the merge module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use uom::si::f64::Length;
+    use uom::si::length::meter;
+
+    /// A record `secs` into the activity, `meters` from the start.
+    fn record(secs: i64, meters: f64) -> FITRecord {
+        FITRecord {
+            timestamp: Local.timestamp_opt(1_600_000_000 + secs, 0).single(),
+            distance: Some(Length::new::<meter>(meters)),
+            ..FITRecord::default()
+        }
+    }
+
+    #[test]
+    /// Test leaving out the overlap and carrying the distance on from the part before
+    fn test_merge_records() {
+        let first = [record(0, 0.0), record(10, 50.0), record(20, 100.0)];
+        let last = Some(first[2].timestamp.unwrap());
+        let mut second: Vec<FITRecord> = after(
+            &[record(15, 0.0), record(20, 20.0), record(30, 40.0)],
+            last,
+            |record| record.timestamp,
+        )
+        .into_iter()
+        .cloned()
+        .collect();
+        assert_eq!(second.len(), 1);
+
+        carry_distance(&first, &mut second);
+        assert_eq!(second[0].distance.unwrap().value, 100.0);
+        assert_eq!(after(&first, None, |record| record.timestamp).len(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test merging a FIT file split in two, in either order
+    fn test_merge_fit() {
+        let activity = FITActivity::from_file("../data/test.fit").unwrap();
+        let half = activity.records.len() / 2;
+        let part = |records: &[FITRecord]| FITActivity {
+            session: activity.session.clone(),
+            laps: activity.laps.clone(),
+            records: records.to_vec(),
+            ..FITActivity::default()
+        };
+        // The second part overlaps the first by ten records
+        let parts = vec![
+            part(&activity.records[half - 10..]),
+            part(&activity.records[..half]),
+        ];
+
+        let merged = FITActivity::merge(parts).unwrap();
+        assert_eq!(merged.records.len(), activity.records.len());
+        assert_eq!(merged.session.start_time, activity.records[0].timestamp);
+        assert_eq!(
+            merged.session.num_laps,
+            u16::try_from(merged.laps.len()).ok()
+        );
+        assert!(merged.laps.len() >= activity.laps.len());
+
+        assert!(FITActivity::merge(Vec::new()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test merging a GPX file with itself, which leaves the second copy out, and that FIT files can't be mixed in
+    fn test_merge_gpx() {
+        let read = || GPXActivity::from_file("../data/walking2.gpx").unwrap();
+        let activity = read();
+        let merged = GPXActivity::merge(vec![read(), read()]).unwrap();
+
+        assert_eq!(merged.tracks.len(), activity.tracks.len());
+        assert_eq!(
+            merged.tracks[0].waypoints.len(),
+            activity.tracks[0].waypoints.len()
+        );
+
+        let fit = FITActivity::from_file("../data/test.fit").unwrap();
+        assert!(Activity::merge(vec![Activity::Fit(fit), Activity::Gpx(read())]).is_err());
+    }
+}
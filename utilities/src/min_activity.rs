@@ -0,0 +1,161 @@
+//! Skips the activities too short to be worth keeping, e.g. a 20 second recording started by accident, so they don't
+//! clutter the summaries, the detail files and the training log.
+
+#[cfg(feature = "cli")]
+use std::error::Error;
+
+use crate::{Duration, QueryFields};
+
+/// The shortest activity to keep, by duration, distance or both. Activities shorter than either are skipped.
+/// Activities without a duration or distance in their summary are kept, since there is no telling how long they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MinActivity {
+    /// The shortest duration to keep.
+    pub duration: Option<Duration>,
+
+    /// The shortest distance to keep, in meters.
+    pub distance_m: Option<f64>,
+}
+
+impl MinActivity {
+    /// Whether a minimum duration or distance is set.
+    #[must_use]
+    pub const fn is_set(&self) -> bool {
+        self.duration.is_some() || self.distance_m.is_some()
+    }
+
+    /// Checks the activity against the minimums.
+    ///
+    /// # Arguments
+    ///
+    /// `fields: &QueryFields` -- The summary of the activity.
+    ///
+    /// # Returns
+    ///
+    /// `Option<String>` -- Why the activity is to be skipped, or `None` if it is long enough.
+    #[must_use]
+    pub fn skip_reason(&self, fields: &QueryFields) -> Option<String> {
+        if let (Some(min), Some(secs)) = (self.duration, fields.duration_sec) {
+            let duration = Duration::from_secs_f64(secs);
+            if duration < min {
+                return Some(format!(
+                    "Shorter than the minimum duration of {min}, at {duration}."
+                ));
+            }
+        }
+        if let (Some(min), Some(distance)) = (self.distance_m, fields.distance_m) {
+            if distance < min {
+                return Some(format!(
+                    "Shorter than the minimum distance of {:.2} km, at {:.2} km.",
+                    min / 1_000.0,
+                    distance / 1_000.0
+                ));
+            }
+        }
+        None
+    }
+
+    /// Checks the activities in a file against the minimums. The file is only skipped if all of its activities are
+    /// too short, so a file with several activities is kept if any of them is long enough.
+    ///
+    /// # Arguments
+    ///
+    /// `summaries: &[QueryFields]` -- The summary of each activity in the file.
+    ///
+    /// # Returns
+    ///
+    /// `Option<String>` -- Why the file is to be skipped, or `None` if it has an activity long enough, or none at all.
+    #[must_use]
+    pub fn skip_reason_all(&self, summaries: &[QueryFields]) -> Option<String> {
+        let reasons = summaries
+            .iter()
+            .map(|fields| self.skip_reason(fields))
+            .collect::<Option<Vec<String>>>()?;
+        match reasons.as_slice() {
+            [] => None,
+            [reason] => Some(reason.clone()),
+            _ => Some(format!("All {} activities are too short.", reasons.len())),
+        }
+    }
+}
+
+/// Reads the minimums from the `min-duration` and `min-distance` command line arguments.
+///
+/// # Arguments
+///
+/// `cli_args: &clap::ArgMatches` -- The command line arguments.
+///
+/// # Returns
+///
+/// `Result<MinActivity, Box<dyn Error>>` -- The minimums, which are unset if the arguments weren't given.
+///
+/// # Errors
+///
+/// The duration or distance may not be valid.
+#[cfg(feature = "cli")]
+pub fn min_activity_from_args(cli_args: &clap::ArgMatches) -> Result<MinActivity, Box<dyn Error>> {
+    let min = MinActivity {
+        duration: cli_args
+            .get_one::<String>("min-duration")
+            .map(|value| value.parse::<Duration>())
+            .transpose()?,
+        distance_m: cli_args
+            .get_one::<String>("min-distance")
+            .map(|value| crate::parse_distance(value))
+            .transpose()?
+            .map(|distance| distance.value),
+    };
+    log::debug!("min_activity::min_activity_from_args() -- {min:?}");
+    Ok(min)
+}
+
+#[cfg(test)]
+/// Tests for the min_activity module
+mod tests {
+    use super::*;
+
+    /// The summary of an activity with the duration and distance given.
+    fn summary(duration_sec: Option<f64>, distance_m: Option<f64>) -> QueryFields {
+        QueryFields {
+            duration_sec,
+            distance_m,
+            ..QueryFields::default()
+        }
+    }
+
+    #[test]
+    /// Test skipping the activities shorter than the minimums
+    fn test_skip_reason() {
+        let min = MinActivity {
+            duration: Some(Duration::from_secs_f64(60.0)),
+            distance_m: Some(500.0),
+        };
+        assert!(min.is_set());
+        assert!(!MinActivity::default().is_set());
+
+        assert_eq!(
+            min.skip_reason(&summary(Some(20.0), Some(1_000.0))),
+            Some("Shorter than the minimum duration of 00:01:00, at 00:00:20.".to_string())
+        );
+        assert_eq!(
+            min.skip_reason(&summary(Some(600.0), Some(120.0))),
+            Some("Shorter than the minimum distance of 0.50 km, at 0.12 km.".to_string())
+        );
+        assert!(min
+            .skip_reason(&summary(Some(600.0), Some(1_000.0)))
+            .is_none());
+        assert!(min.skip_reason(&summary(None, None)).is_none());
+        assert!(MinActivity::default()
+            .skip_reason(&summary(Some(1.0), Some(1.0)))
+            .is_none());
+
+        let short = summary(Some(20.0), None);
+        let long = summary(Some(600.0), None);
+        assert!(min.skip_reason_all(&[short.clone(), long]).is_none());
+        assert!(min.skip_reason_all(&[]).is_none());
+        assert_eq!(
+            min.skip_reason_all(&[short.clone(), short]),
+            Some("All 2 activities are too short.".to_string())
+        );
+    }
+}
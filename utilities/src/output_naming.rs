@@ -0,0 +1,261 @@
+//! Names the detail files written for each activity. By default they are written next to the input file, named after
+//! it with the extension replaced by the suffix of the export, e.g. `running.fit` gives `running.laps.csv`. The
+//! naming can be changed once for the whole run using `Settings::set_output_naming()`:
+//!
+//! - An output directory, so the input directories are left as they are.
+//! - A folder per activity, named after the input file, e.g. `running/running.laps.csv`.
+//! - Other suffixes, e.g. `laps.csv=lap-table.csv` gives `running.lap-table.csv`.
+//!
+//! The summary files and other files named on the command line are written where they are asked to be.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic_file::AtomicFile;
+use crate::extensions::without_gzip;
+use crate::{set_extension, Settings};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// How the detail files are named and where they go.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputNaming {
+    /// The directory the files are written to. `None` writes them next to the input file.
+    pub output_dir: Option<PathBuf>,
+
+    /// Write the files of each activity to a folder named after the input file, without its extension.
+    pub subfolder_per_activity: bool,
+
+    /// The suffixes to use instead of the default ones, keyed by the default suffix, e.g. `laps.csv`.
+    pub suffixes: BTreeMap<String, String>,
+}
+
+impl OutputNaming {
+    /// Uses another suffix for one of the exports.
+    ///
+    /// # Arguments
+    ///
+    /// - `suffix: &str` -- The default suffix, e.g. `laps.csv`.
+    /// - `replacement: &str` -- The suffix to use instead, e.g. `lap-table.csv`.
+    #[must_use]
+    pub fn with_suffix(mut self, suffix: &str, replacement: &str) -> Self {
+        self.suffixes.insert(
+            suffix.trim_start_matches('.').to_string(),
+            replacement.trim_start_matches('.').to_string(),
+        );
+        self
+    }
+
+    /// Reads a suffix to replace from the command line, e.g. `laps.csv=lap-table.csv`.
+    ///
+    /// # Arguments
+    ///
+    /// `spec: &str` -- The default suffix and its replacement, separated by `=`.
+    ///
+    /// # Returns
+    ///
+    /// `(String, String)` -- The default suffix and its replacement.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no `=`, or either side is empty.
+    pub fn parse_suffix(spec: &str) -> Result<(String, String), Box<dyn Error>> {
+        match spec.split_once('=') {
+            Some((suffix, replacement))
+                if !suffix.trim().is_empty() && !replacement.trim().is_empty() =>
+            {
+                Ok((suffix.trim().to_string(), replacement.trim().to_string()))
+            }
+            _ => Err(format!(
+                "Unknown suffix {spec}. Use the default suffix and the new one, e.g. laps.csv=lap-table.csv."
+            )
+            .into()),
+        }
+    }
+
+    /// The name of a detail file.
+    ///
+    /// # Arguments
+    ///
+    /// - `input: &str` -- The file the activity was read from, or the name the activity is known by.
+    /// - `suffix: &str` -- The default suffix of the export, e.g. `laps.csv`.
+    ///
+    /// # Returns
+    ///
+    /// `PathBuf` -- Where the detail file goes.
+    #[must_use]
+    pub fn output_path(&self, input: &str, suffix: &str) -> PathBuf {
+        let suffix = self.suffixes.get(suffix).map_or(suffix, String::as_str);
+        let named = PathBuf::from(set_extension(input, suffix));
+        if self.output_dir.is_none() && !self.subfolder_per_activity {
+            return named;
+        }
+
+        let mut dir = self
+            .output_dir
+            .clone()
+            .unwrap_or_else(|| named.parent().map_or_else(PathBuf::new, Path::to_path_buf));
+        if self.subfolder_per_activity {
+            dir.push(
+                Path::new(without_gzip(input))
+                    .file_stem()
+                    .unwrap_or_default(),
+            );
+        }
+        dir.join(named.file_name().unwrap_or_default())
+    }
+}
+
+impl Settings {
+    /// Sets how the detail files are named.
+    ///
+    /// # Arguments
+    ///
+    /// `naming: OutputNaming` -- The naming to use.
+    pub fn set_output_naming(&mut self, naming: OutputNaming) {
+        self.output_naming = Some(naming);
+    }
+
+    /// Sets the naming from the `output-dir`, `subfolders` and `output-suffix` command line arguments.
+    ///
+    /// # Arguments
+    ///
+    /// `cli_args: &clap::ArgMatches` -- The command line arguments.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an `output-suffix` value isn't in the form `suffix=replacement`.
+    #[cfg(feature = "cli")]
+    pub fn set_output_naming_from_args(
+        &mut self,
+        cli_args: &clap::ArgMatches,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut naming = OutputNaming {
+            output_dir: cli_args.get_one::<String>("output-dir").map(PathBuf::from),
+            subfolder_per_activity: cli_args.get_flag("subfolders"),
+            ..OutputNaming::default()
+        };
+        for spec in cli_args
+            .get_many::<String>("output-suffix")
+            .unwrap_or_default()
+        {
+            let (suffix, replacement) = OutputNaming::parse_suffix(spec)?;
+            naming = naming.with_suffix(&suffix, &replacement);
+        }
+
+        if naming != OutputNaming::default() {
+            log::debug!("output_naming::set_output_naming_from_args() -- {naming:?}");
+            self.set_output_naming(naming);
+        }
+
+        Ok(())
+    }
+}
+
+/// The name of a detail file, using the naming set for the run.
+///
+/// # Arguments
+///
+/// - `input: &str` -- The file the activity was read from, or the name the activity is known by.
+/// - `suffix: &str` -- The default suffix of the export, e.g. `laps.csv`.
+pub(crate) fn output_path(input: &str, suffix: &str) -> PathBuf {
+    match &Settings::current().output_naming {
+        Some(naming) => naming.output_path(input, suffix),
+        None => PathBuf::from(set_extension(input, suffix)),
+    }
+}
+
+/// Creates a detail file, along with the directories it goes in. The file only appears once it's committed.
+///
+/// # Errors
+///
+/// Creating the directories or the file may fail.
+pub(crate) fn create_output(path: &Path) -> Result<AtomicFile, Box<dyn Error>> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    AtomicFile::create(path)
+}
+
+/// Writes a detail file in one go, along with the directories it goes in.
+///
+/// # Arguments
+///
+/// - `path: &Path` -- The file to write.
+/// - `write: F` -- Writes the contents to the file.
+///
+/// # Errors
+///
+/// Creating the directories, or creating, writing or renaming the file may fail. Nothing is written under the name if
+/// so.
+pub(crate) fn write_output<T, F>(path: &Path, write: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(&mut AtomicFile) -> Result<T, Box<dyn Error>>,
+{
+    let mut file = create_output(path)?;
+    let result = write(&mut file)?;
+    file.commit()?;
+
+    Ok(result)
+}
+
+#[cfg(test)]
+/// Tests for the output_naming module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test naming the files next to the input, in an output directory and in a folder per activity
+    fn test_output_path() {
+        let naming = OutputNaming::default();
+        assert_eq!(
+            naming.output_path("data/running.fit", "laps.csv"),
+            PathBuf::from("data/running.laps.csv")
+        );
+
+        let naming = naming.with_suffix("laps.csv", ".lap-table.csv");
+        assert_eq!(
+            naming.output_path("data/running.fit", "laps.csv"),
+            PathBuf::from("data/running.lap-table.csv")
+        );
+        assert_eq!(
+            naming.output_path("data/running.fit", "records.csv"),
+            PathBuf::from("data/running.records.csv")
+        );
+
+        let naming = OutputNaming {
+            output_dir: Some(PathBuf::from("out")),
+            ..naming
+        };
+        assert_eq!(
+            naming.output_path("data/running.fit", "session.json"),
+            PathBuf::from("out/running.session.json")
+        );
+
+        let naming = OutputNaming {
+            subfolder_per_activity: true,
+            ..naming
+        };
+        assert_eq!(
+            naming.output_path("data/running.track2.gpx", "tracks.csv"),
+            PathBuf::from("out/running.track2/running.track2.tracks.csv")
+        );
+        let naming = OutputNaming {
+            output_dir: None,
+            ..naming
+        };
+        assert_eq!(
+            naming.output_path("data/running.fit", "laps.csv"),
+            PathBuf::from("data/running/running.lap-table.csv")
+        );
+        assert_eq!(
+            naming.output_path("activities/1234.fit.gz", "laps.csv"),
+            PathBuf::from("activities/1234/1234.lap-table.csv")
+        );
+
+        assert!(OutputNaming::parse_suffix("laps.csv=lap-table.csv").is_ok());
+        assert!(OutputNaming::parse_suffix("laps.csv").is_err());
+        assert!(OutputNaming::parse_suffix("=lap-table.csv").is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! The text used in place of values that aren't known, e.g. a file without a manufacturer or a start time, in the
+//! rename tokens, the summaries and the split summary file names.
+//!
+//! The placeholder is set for the run using `Settings::set_placeholder()`, and applies to all the file formats.
+//! Without it each value keeps its own placeholder as before, e.g. `Unknown` for the manufacturer and `0000` for the
+//! year. An empty placeholder leaves the values that aren't known blank.
+
+use crate::Settings;
+
+impl Settings {
+    /// Sets the text used for all values that aren't known.
+    ///
+    /// # Arguments
+    ///
+    /// `placeholder: Option<&str>` -- The text to use, e.g. `n-a` or an empty string. `None` goes back to the
+    /// defaults.
+    pub fn set_placeholder(&mut self, placeholder: Option<&str>) {
+        self.placeholder = placeholder.map(String::from);
+    }
+}
+
+/// The text to use for a value that isn't known.
+///
+/// # Arguments
+///
+/// `default: &str` -- The placeholder used for the value if none has been set, e.g. `Unknown` or `0000`.
+///
+/// # Returns
+///
+/// `String` -- The placeholder set by `Settings::set_placeholder()`, or `default` if none has been set.
+pub(crate) fn placeholder(default: &str) -> String {
+    Settings::current()
+        .placeholder
+        .clone()
+        .unwrap_or_else(|| default.to_string())
+}
@@ -0,0 +1,635 @@
+//! Rounds the floating point numbers written to the CSV and JSON exports, so the files are compact and don't change
+//! with every tiny difference in the last few decimals.
+//!
+//! The precision is set once for the whole run using `Settings::set_float_precision()`, and applies to all the exports.
+//! Without it the numbers are written with full precision as before.
+//!
+//! Numbers that aren't finite, i.e. `NaN` or infinite, are never written. They are left empty in the CSV files and
+//! written as `null` in the JSON files, whatever the precision.
+
+use serde::ser::{self, Serialize, Serializer};
+use std::collections::HashMap;
+#[cfg(feature = "cli")]
+use std::error::Error;
+use std::fmt::Display;
+
+use crate::Settings;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// How many decimals to keep for the floating point numbers in the exports.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FloatPrecision {
+    /// The number of decimals for all fields not listed in `fields`. `None` keeps full precision.
+    pub decimals: Option<u32>,
+
+    /// The number of decimals for individual fields, by the name of the field, e.g. `latitude`.
+    pub fields: HashMap<String, u32>,
+}
+
+impl FloatPrecision {
+    /// Creates a precision setting with the same number of decimals for all fields.
+    #[must_use]
+    pub fn new(decimals: Option<u32>) -> Self {
+        Self {
+            decimals,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Sets the number of decimals for a single field, overriding the number of decimals for all fields.
+    #[must_use]
+    pub fn with_field(mut self, field: &str, decimals: u32) -> Self {
+        self.fields.insert(field.to_string(), decimals);
+        self
+    }
+
+    /// Parses a per-field setting in the form `field=decimals`, e.g. `latitude=6`.
+    ///
+    /// # Arguments
+    ///
+    /// `spec: &str` -- The setting to parse.
+    ///
+    /// # Returns
+    ///
+    /// `Result<(String, u32), String>` -- The field name and the number of decimals.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there's no `=`, the field name is blank, or the decimals aren't a number.
+    pub fn parse_field(spec: &str) -> Result<(String, u32), String> {
+        let (field, decimals) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("Expected field=decimals, got {spec}"))?;
+        let field = field.trim();
+        if field.is_empty() {
+            return Err(format!("Missing field name in {spec}"));
+        }
+        let decimals = decimals
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| format!("Invalid number of decimals in {spec}: {e}"))?;
+
+        Ok((field.to_string(), decimals))
+    }
+
+    /// Whether anything gets rounded.
+    #[cfg(feature = "cli")]
+    fn is_full_precision(&self) -> bool {
+        self.decimals.is_none() && self.fields.is_empty()
+    }
+
+    /// The number of decimals for a field, falling back to the setting of the enclosing value.
+    fn for_field(&self, field: &str, parent: Option<u32>) -> Option<u32> {
+        self.fields.get(field).copied().or(parent)
+    }
+}
+
+impl Settings {
+    /// Sets the precision used by all the exports.
+    ///
+    /// # Arguments
+    ///
+    /// `precision: FloatPrecision` -- The number of decimals to keep.
+    pub fn set_float_precision(&mut self, precision: FloatPrecision) {
+        self.float_precision = Some(precision);
+    }
+
+    /// Sets the precision from the `precision` and `field-precision` command line arguments, if either of them was
+    /// given.
+    ///
+    /// # Arguments
+    ///
+    /// `cli_args: &clap::ArgMatches` -- The command line arguments.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a `field-precision` value isn't in the form `field=decimals`.
+    #[cfg(feature = "cli")]
+    pub fn set_float_precision_from_args(
+        &mut self,
+        cli_args: &clap::ArgMatches,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut precision = FloatPrecision::new(cli_args.get_one::<u32>("precision").copied());
+        for spec in cli_args
+            .get_many::<String>("field-precision")
+            .unwrap_or_default()
+        {
+            let (field, decimals) = FloatPrecision::parse_field(spec)?;
+            precision = precision.with_field(&field, decimals);
+        }
+
+        if !precision.is_full_precision() {
+            log::debug!("precision::set_float_precision_from_args() -- {precision:?}");
+            self.set_float_precision(precision);
+        }
+
+        Ok(())
+    }
+}
+
+/// Rounds the value to the number of decimals given.
+#[allow(clippy::cast_possible_wrap)]
+fn round(value: f64, decimals: u32) -> f64 {
+    let factor = 10_f64.powi(decimals.min(15) as i32);
+    (value * factor).round() / factor
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Wraps a value being exported so its floating point numbers are rounded according to
+/// `Settings::set_float_precision()`, and the ones that aren't finite are left out.
+///
+/// # Example
+///
+/// ```text
+/// let json = serde_json::to_string(&Rounded(&activity.session))?;
+/// ```
+pub struct Rounded<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Serialize + ?Sized> Serialize for Rounded<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let settings = Settings::current();
+        let full_precision = FloatPrecision::default();
+        let precision = settings.float_precision.as_ref().unwrap_or(&full_precision);
+        self.0.serialize(RoundingSerializer {
+            inner: serializer,
+            precision,
+            decimals: precision.decimals,
+        })
+    }
+}
+
+/// A value within the exported value, along with the number of decimals that applies to it.
+struct Value<'a, 'p, T: ?Sized> {
+    value: &'a T,
+    precision: &'p FloatPrecision,
+    decimals: Option<u32>,
+}
+
+impl<T: Serialize + ?Sized> Serialize for Value<'_, '_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(RoundingSerializer {
+            inner: serializer,
+            precision: self.precision,
+            decimals: self.decimals,
+        })
+    }
+}
+
+/// Passes everything on to the actual serializer, rounding the floating point numbers on the way. Numbers that aren't
+/// finite are passed on as `None`.
+struct RoundingSerializer<'p, S> {
+    inner: S,
+    precision: &'p FloatPrecision,
+    decimals: Option<u32>,
+}
+
+impl<'p, S> RoundingSerializer<'p, S> {
+    /// Wraps a value within the current value, which gets the same number of decimals.
+    const fn value<'a, T: ?Sized>(&self, value: &'a T) -> Value<'a, 'p, T> {
+        Value {
+            value,
+            precision: self.precision,
+            decimals: self.decimals,
+        }
+    }
+}
+
+/// Generates the `Serializer` functions which are passed on as they are.
+macro_rules! forward {
+    ($($function:ident($type:ty)),* $(,)?) => {
+        $(
+            fn $function(self, v: $type) -> Result<Self::Ok, Self::Error> {
+                self.inner.$function(v)
+            }
+        )*
+    };
+}
+
+impl<'p, S: Serializer> Serializer for RoundingSerializer<'p, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Compound<'p, S::SerializeSeq>;
+    type SerializeTuple = Compound<'p, S::SerializeTuple>;
+    type SerializeTupleStruct = Compound<'p, S::SerializeTupleStruct>;
+    type SerializeTupleVariant = Compound<'p, S::SerializeTupleVariant>;
+    type SerializeMap = Compound<'p, S::SerializeMap>;
+    type SerializeStruct = Compound<'p, S::SerializeStruct>;
+    type SerializeStructVariant = Compound<'p, S::SerializeStructVariant>;
+
+    forward!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+    );
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        if !v.is_finite() {
+            return self.inner.serialize_none();
+        }
+        match self.decimals {
+            Some(decimals) => self
+                .inner
+                .serialize_f32(round(f64::from(v), decimals) as f32),
+            None => self.inner.serialize_f32(v),
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        if !v.is_finite() {
+            return self.inner.serialize_none();
+        }
+        match self.decimals {
+            Some(decimals) => self.inner.serialize_f64(round(v, decimals)),
+            None => self.inner.serialize_f64(v),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        let value = self.value(value);
+        self.inner.serialize_some(&value)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.inner
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let value = self.value(value);
+        self.inner.serialize_newtype_struct(name, &value)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let value = self.value(value);
+        self.inner
+            .serialize_newtype_variant(name, variant_index, variant, &value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(Compound::new(
+            self.inner.serialize_seq(len)?,
+            self.precision,
+            self.decimals,
+        ))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(Compound::new(
+            self.inner.serialize_tuple(len)?,
+            self.precision,
+            self.decimals,
+        ))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(Compound::new(
+            self.inner.serialize_tuple_struct(name, len)?,
+            self.precision,
+            self.decimals,
+        ))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(Compound::new(
+            self.inner
+                .serialize_tuple_variant(name, variant_index, variant, len)?,
+            self.precision,
+            self.decimals,
+        ))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(Compound::new(
+            self.inner.serialize_map(len)?,
+            self.precision,
+            self.decimals,
+        ))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(Compound::new(
+            self.inner.serialize_struct(name, len)?,
+            self.precision,
+            self.decimals,
+        ))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(Compound::new(
+            self.inner
+                .serialize_struct_variant(name, variant_index, variant, len)?,
+            self.precision,
+            self.decimals,
+        ))
+    }
+
+    fn collect_str<T: Display + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        self.inner.collect_str(value)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Wraps the serializers for sequences, maps and structs so the values within them get rounded.
+struct Compound<'p, C> {
+    inner: C,
+    precision: &'p FloatPrecision,
+    decimals: Option<u32>,
+}
+
+impl<'p, C> Compound<'p, C> {
+    /// Wraps the actual compound serializer.
+    const fn new(inner: C, precision: &'p FloatPrecision, decimals: Option<u32>) -> Self {
+        Self {
+            inner,
+            precision,
+            decimals,
+        }
+    }
+
+    /// Wraps an element, which gets the same number of decimals as the compound value.
+    const fn element<'a, T: ?Sized>(&self, value: &'a T) -> Value<'a, 'p, T> {
+        Value {
+            value,
+            precision: self.precision,
+            decimals: self.decimals,
+        }
+    }
+
+    /// Wraps a named field, which gets its own number of decimals if one has been set.
+    fn field<'a, T: ?Sized>(&self, key: &str, value: &'a T) -> Value<'a, 'p, T> {
+        Value {
+            value,
+            precision: self.precision,
+            decimals: self.precision.for_field(key, self.decimals),
+        }
+    }
+}
+
+impl<C: ser::SerializeSeq> ser::SerializeSeq for Compound<'_, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let value = self.element(value);
+        self.inner.serialize_element(&value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeTuple> ser::SerializeTuple for Compound<'_, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let value = self.element(value);
+        self.inner.serialize_element(&value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeTupleStruct> ser::SerializeTupleStruct for Compound<'_, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let value = self.element(value);
+        self.inner.serialize_field(&value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeTupleVariant> ser::SerializeTupleVariant for Compound<'_, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let value = self.element(value);
+        self.inner.serialize_field(&value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeMap> ser::SerializeMap for Compound<'_, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_key(key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let value = self.element(value);
+        self.inner.serialize_value(&value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeStruct> ser::SerializeStruct for Compound<'_, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        let value = self.field(key, value);
+        self.inner.serialize_field(key, &value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+        self.inner.skip_field(key)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeStructVariant> ser::SerializeStructVariant for Compound<'_, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        let value = self.field(key, value);
+        self.inner.serialize_field(key, &value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+        self.inner.skip_field(key)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+#[cfg(test)]
+/// Tests for the precision module
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    /// A struct with floats in all the places serde can put them
+    struct Sample {
+        distance: f64,
+        latitude: Option<f64>,
+        speeds: Vec<f32>,
+        duration: crate::Duration,
+    }
+
+    #[test]
+    /// Test rounding with an overall and a per-field precision
+    fn test_rounded() {
+        let sample = Sample {
+            distance: 2_963.318_848,
+            latitude: Some(59.912_345_678),
+            speeds: vec![3.256_78, 2.704_31],
+            duration: crate::Duration::from_secs_f64(604.894),
+        };
+
+        let precision = FloatPrecision::new(Some(2)).with_field("latitude", 6);
+        let mut json = Vec::new();
+        sample
+            .serialize(RoundingSerializer {
+                inner: &mut serde_json::Serializer::new(&mut json),
+                precision: &precision,
+                decimals: precision.decimals,
+            })
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            r#"{"distance":2963.32,"latitude":59.912346,"speeds":[3.26,2.7],"duration":{"secs":604.89}}"#
+        );
+    }
+
+    #[test]
+    /// Test leaving out the numbers that aren't finite, at full precision too
+    fn test_non_finite() {
+        let sample = Sample {
+            distance: f64::NAN,
+            latitude: Some(f64::INFINITY),
+            speeds: vec![f32::NAN, 2.5],
+            duration: crate::Duration::from_secs_f64(1.0),
+        };
+
+        let json = serde_json::to_string(&Rounded(&sample)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"distance":null,"latitude":null,"speeds":[null,2.5],"duration":{"secs":1.0}}"#
+        );
+
+        let mut csv = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        csv.serialize(Rounded(&(1.5_f64, f64::NAN, f64::NEG_INFINITY)))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(csv.into_inner().unwrap()).unwrap(),
+            "1.5,,\n"
+        );
+    }
+
+    #[test]
+    /// Test parsing the per-field settings
+    fn test_parse_field() {
+        assert_eq!(
+            FloatPrecision::parse_field("latitude=6"),
+            Ok(("latitude".to_string(), 6))
+        );
+        assert!(FloatPrecision::parse_field("latitude").is_err());
+        assert!(FloatPrecision::parse_field("=6").is_err());
+        assert!(FloatPrecision::parse_field("latitude=six").is_err());
+    }
+
+    #[test]
+    /// Test the rounding itself
+    fn test_round() {
+        assert!((round(2_963.318_848, 1) - 2_963.3).abs() < f64::EPSILON);
+        assert!((round(2_963.318_848, 0) - 2_963.0).abs() < f64::EPSILON);
+        assert!((round(-0.125, 2) - -0.13).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,88 @@
+//! Replaces device serial numbers with a short hash in the exports and rename tokens, so files from different devices
+//! can still be told apart without giving away the actual serial numbers.
+//!
+//! The hashing is switched on for the run using `Settings::set_hash_serials()`. Only the output is changed, so the
+//! activity UUIDs are the same whether the serial numbers are hashed or not.
+
+use serde::Serializer;
+use uuid::Uuid;
+
+use crate::Settings;
+
+/// The namespace for the serial number hashes. Changing this changes every hash.
+const SERIAL_NAMESPACE: Uuid = Uuid::from_u128(0x5e71_a1f0_8c0e_4b7d_a6a2_3f1d_9c4e_0b27);
+
+/// The number of hex digits kept from the hash.
+const HASH_LENGTH: usize = 10;
+
+impl Settings {
+    /// Switches the hashing of serial numbers in the exports and rename tokens on or off.
+    ///
+    /// # Arguments
+    ///
+    /// `hash: bool` -- Whether to hash the serial numbers.
+    pub fn set_hash_serials(&mut self, hash: bool) {
+        self.hash_serials = hash;
+    }
+}
+
+/// Creates a short hash of the serial number. The same serial number always gives the same hash.
+///
+/// # Arguments
+///
+/// `serial_number: &str` -- The serial number to be hashed.
+///
+/// # Returns
+///
+/// `String` -- The first 10 hex digits of a name-based UUID created from the serial number.
+#[must_use]
+pub fn hash_serial(serial_number: &str) -> String {
+    let mut hash = Uuid::new_v5(&SERIAL_NAMESPACE, serial_number.trim().as_bytes())
+        .simple()
+        .to_string();
+    hash.truncate(HASH_LENGTH);
+    hash
+}
+
+/// Whether the serial numbers are currently hashed in the output.
+pub(crate) fn hash_serials() -> bool {
+    Settings::current().hash_serials
+}
+
+/// The serial number as it should be shown in the output -- hashed if requested, otherwise as it is.
+pub(crate) fn output_serial(serial_number: &str) -> String {
+    if hash_serials() {
+        hash_serial(serial_number)
+    } else {
+        serial_number.to_string()
+    }
+}
+
+/// Serializes a serial number field, hashing it if requested. Used with `#[serde(serialize_with)]`.
+#[allow(clippy::ref_option)]
+pub(crate) fn serialize_serial<S: Serializer>(
+    serial_number: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match serial_number {
+        Some(serial_number) => serializer.serialize_some(&output_serial(serial_number)),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+/// Tests for the privacy module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that the hash is short, stable and different per serial number
+    fn test_hash_serial() {
+        let hash = hash_serial("3400586606");
+
+        assert_eq!(hash.len(), HASH_LENGTH);
+        assert_eq!(hash, hash_serial(" 3400586606 "));
+        assert_ne!(hash, hash_serial("3400586607"));
+        assert!(!hash.contains("3400586606"));
+    }
+}
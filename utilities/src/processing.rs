@@ -0,0 +1,1885 @@
+//! Library-level functions for processing FIT, GPX and TCX files, reporting what was done for each file in a
+//! `ProcessingResult` so frontends can show progress and results without knowing the details of each format.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::analysis::hr_scatter::{export_hr_scatter_csv, HrScatter};
+use crate::analysis::mean_max::{export_mean_max_csv, MeanMax};
+use crate::analysis::overlap::{find_overlaps, ActivityWindow, Overlap};
+use crate::analysis::splits::{export_splits_csv, Split, SplitUnit};
+use crate::archive::{expand_archives, is_archive};
+use crate::atomic_file::write_atomically;
+use crate::cancel::{check_cancelled, run_cancellable, CancelFlag};
+use crate::exporters::ExportFormat;
+use crate::fit::activity::chained_filename;
+use crate::geojson::export_geojson;
+use crate::input::open_input;
+use crate::min_activity::MinActivity;
+use crate::output_naming::output_path;
+use crate::processing_error::{ProcessingError, EXIT_PARTIAL_FAILURE};
+use crate::profile_svg::{export_profile_svg, profile_svg, ProfilePoint};
+use crate::summary_cache::{cache_key, cached_summary, store_summary};
+use crate::tags::activity_tags;
+use crate::tcx::quirks::read_tcx;
+use crate::{
+    exceeds_memory_limit, ActivityFormat, FITActivities, FITActivity, FITParseStats, FITSession,
+    GPXActivities, GPXActivity, GPXMetadata, QueryFields, Settings, TCXActivitiesList, TCXActivity,
+    TCXCreator, TCXTrackpointList, DEFAULT_CHUNK_SIZE,
+};
+
+/// Controls how the files are processed.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessingOptions {
+    /// Write the detail files (records, laps, waypoints, trackpoints, etc.) next to the input file.
+    pub export_detail: bool,
+
+    /// Write the details as a single `bundle.json` document per file instead of separate files. Files exported in
+    /// chunks still get separate files, since the bundle needs the whole file in memory.
+    pub bundle: bool,
+
+    /// Count the messages and unknown fields in FIT files. This reads each FIT file an extra time.
+    pub parse_stats: bool,
+
+    /// If set, FIT files estimated to need more than this many megabytes of memory are exported in chunks. GPX and TCX
+    /// files are always parsed as a whole, so they are left as they are.
+    pub max_memory: Option<u64>,
+
+    /// The number of rows written between each flush when exporting in chunks.
+    pub chunk_size: usize,
+
+    /// If set, a per-kilometer or per-mile split table is exported to `splits.csv` along with the details.
+    pub splits: Option<SplitUnit>,
+
+    /// The format of the records, waypoints and trackpoints files. The other detail files are always CSV.
+    pub format: ExportFormat,
+
+    /// Export the mean-maximal power and heart rate curve to `mean_max.csv` along with the details.
+    pub mean_max: bool,
+
+    /// Export the heart rate against the speed and power over each half minute to `hr_scatter.csv` along with the
+    /// details.
+    pub hr_scatter: bool,
+
+    /// Export the track as a GeoJSON `FeatureCollection` to `.geojson` along with the details. FIT and GPX files only.
+    pub geojson: bool,
+
+    /// Export the elevation against the distance as an SVG image to `profile.svg` along with the details.
+    pub profile_svg: bool,
+
+    /// Fill in the gaps between the records of FIT files made with smart recording with a synthetic record for every
+    /// second. Files exported in chunks are left as they are.
+    pub interpolate: bool,
+
+    /// If set, files taking longer than this to process are skipped, and the failure is recorded in their result.
+    /// Only used by `process_with_timeout()` and the `process_*_files()` functions.
+    pub timeout: Option<Duration>,
+
+    /// Which of the TCX detail files to write when exporting the details. Not used for bundles.
+    pub tcx_detail: TcxDetailFiles,
+
+    /// Skip the activities shorter than this, leaving them out of the summary and writing no details for them. Files
+    /// exported in chunks have their details written as they are read, so only their summary is left out.
+    pub min_activity: MinActivity,
+}
+
+impl Default for ProcessingOptions {
+    fn default() -> Self {
+        Self {
+            export_detail: true,
+            bundle: false,
+            parse_stats: false,
+            max_memory: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            splits: None,
+            mean_max: false,
+            hr_scatter: false,
+            geojson: false,
+            profile_svg: false,
+            interpolate: false,
+            format: ExportFormat::Csv,
+            timeout: None,
+            tcx_detail: TcxDetailFiles::default(),
+            min_activity: MinActivity::default(),
+        }
+    }
+}
+
+/// The detail files written for each TCX file. All of them by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcxDetailFiles {
+    /// Write the activity summary to `activity.json`.
+    pub json: bool,
+
+    /// Write the laps to `laps.csv`.
+    pub laps: bool,
+
+    /// Write the trackpoints to `trackpoints.csv`, or the format set in the options.
+    pub trackpoints: bool,
+}
+
+impl Default for TcxDetailFiles {
+    fn default() -> Self {
+        Self {
+            json: true,
+            laps: true,
+            trackpoints: true,
+        }
+    }
+}
+
+/// Counts of what was found in the input file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessingStats {
+    /// Number of data points -- FIT records, GPX track waypoints or TCX trackpoints.
+    pub records: usize,
+
+    /// Number of laps. Always 0 for GPX files.
+    pub laps: usize,
+
+    /// Number of tracks. Always 0 for FIT files.
+    pub tracks: usize,
+
+    /// Whether the file was exported in chunks because it was too large to be held in memory.
+    pub chunked: bool,
+}
+
+/// The number of sessions, laps, tracks and records written for a file, or for all the files of a run.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RowCounts {
+    /// Number of sessions in the summary -- one per sport for multisport FIT files, and one per activity otherwise.
+    pub sessions: usize,
+
+    /// Number of laps written to the detail files. Always 0 for GPX files.
+    pub laps: usize,
+
+    /// Number of tracks written to the detail files. Always 0 for FIT files.
+    pub tracks: usize,
+
+    /// Number of data points written to the detail files -- FIT records, GPX track waypoints or TCX trackpoints.
+    pub records: usize,
+}
+
+impl RowCounts {
+    /// Adds the counts of another file.
+    fn add(&mut self, other: &Self) {
+        self.sessions += other.sessions;
+        self.laps += other.laps;
+        self.tracks += other.tracks;
+        self.records += other.records;
+    }
+}
+
+impl fmt::Display for RowCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sessions: {}      Laps: {}      Tracks: {}      Records: {}",
+            self.sessions, self.laps, self.tracks, self.records
+        )
+    }
+}
+
+/// The outcome of processing a single file.
+#[derive(Serialize, Debug, Clone, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct ProcessingResult {
+    /// The file that was processed.
+    pub input: PathBuf,
+
+    /// The files that were written.
+    pub outputs: Vec<PathBuf>,
+
+    /// Anything noteworthy that didn't stop the file from being processed.
+    pub warnings: Vec<String>,
+
+    /// Counts of what was found in the file.
+    pub stats: ProcessingStats,
+
+    /// Counts of what was written for the file. The laps, tracks and records are 0 if the details weren't exported.
+    pub written: RowCounts,
+
+    /// How long the processing took.
+    pub duration: std::time::Duration,
+
+    /// Counts of the messages and unknown fields, if requested. FIT files only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fit_stats: Option<FITParseStats>,
+
+    /// Why the file couldn't be processed, e.g. it timed out. Such files are left out of the summary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Why the file was skipped, e.g. it was shorter than the minimum duration. Such files are left out of the summary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+}
+
+impl ProcessingResult {
+    /// Creates an empty result for the input file.
+    fn new(filename: &str) -> Self {
+        Self {
+            input: PathBuf::from(filename),
+            ..Self::default()
+        }
+    }
+
+    /// Creates the result for a file that couldn't be processed, so it can be reported along with the rest.
+    ///
+    /// # Arguments
+    ///
+    /// - `filename: &str` -- The file that couldn't be processed.
+    /// - `err: &ProcessingError` -- What went wrong.
+    ///
+    /// # Returns
+    ///
+    /// `Self` -- The result, with the error recorded.
+    #[must_use]
+    pub fn failed(filename: &str, err: &ProcessingError) -> Self {
+        let mut result = Self::new(filename);
+        result.set_error(err.to_string());
+        result
+    }
+
+    /// Adds an output file named after the input file with the extension replaced by `extension`.
+    fn add_output(&mut self, extension: &str) {
+        let input = self.input.clone();
+        self.add_output_for(&input, extension);
+    }
+
+    /// Adds an output file named after `filename` with the extension replaced by `extension`. Used where a file is
+    /// split into several activities with their own names.
+    fn add_output_for(&mut self, filename: &Path, extension: &str) {
+        self.outputs.push(output_path(
+            filename.to_str().unwrap_or_default(),
+            extension,
+        ));
+    }
+
+    /// Exports the splits to a `splits.csv` file named after `filename`.
+    fn export_splits(&mut self, filename: &Path, splits: &[Split]) -> Result<(), Box<dyn Error>> {
+        export_splits_csv(
+            splits,
+            &output_path(filename.to_str().unwrap_or_default(), "splits.csv").to_string_lossy(),
+        )?;
+        self.add_output_for(filename, "splits.csv");
+        Ok(())
+    }
+
+    /// Exports the mean-maximal curve to a `mean_max.csv` file named after `filename`.
+    fn export_mean_max(
+        &mut self,
+        filename: &Path,
+        curve: &[MeanMax],
+    ) -> Result<(), Box<dyn Error>> {
+        export_mean_max_csv(
+            curve,
+            &output_path(filename.to_str().unwrap_or_default(), "mean_max.csv").to_string_lossy(),
+        )?;
+        self.add_output_for(filename, "mean_max.csv");
+        Ok(())
+    }
+
+    /// Exports the heart rate scatter to a `hr_scatter.csv` file named after `filename`.
+    fn export_hr_scatter(
+        &mut self,
+        filename: &Path,
+        scatter: &[HrScatter],
+    ) -> Result<(), Box<dyn Error>> {
+        export_hr_scatter_csv(
+            scatter,
+            &output_path(filename.to_str().unwrap_or_default(), "hr_scatter.csv").to_string_lossy(),
+        )?;
+        self.add_output_for(filename, "hr_scatter.csv");
+        Ok(())
+    }
+
+    /// Exports the track to a `.geojson` file named after `filename`.
+    fn export_geojson(
+        &mut self,
+        filename: &Path,
+        collection: &serde_json::Value,
+    ) -> Result<(), Box<dyn Error>> {
+        export_geojson(
+            collection,
+            &output_path(filename.to_str().unwrap_or_default(), "geojson").to_string_lossy(),
+        )?;
+        self.add_output_for(filename, "geojson");
+        Ok(())
+    }
+
+    /// Exports the elevation profile to a `profile.svg` file named after `filename`, titled with its file name.
+    /// Activities without elevations get a warning instead.
+    fn export_profile_svg(
+        &mut self,
+        filename: &Path,
+        profile: &[ProfilePoint],
+    ) -> Result<(), Box<dyn Error>> {
+        let title = filename
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+        if profile_svg(profile, &title).is_none() {
+            self.add_warning("No elevations found. No elevation profile exported.".to_string());
+            return Ok(());
+        }
+        export_profile_svg(
+            profile,
+            &title,
+            &output_path(filename.to_str().unwrap_or_default(), "profile.svg").to_string_lossy(),
+        )?;
+        self.add_output_for(filename, "profile.svg");
+        Ok(())
+    }
+
+    /// Prints the counts of what was found in the file, and what was ignored, to stdout.
+    pub fn print_stats(&self) {
+        println!("\n{} statistics:\n", self.input.display());
+        if let Some(error) = &self.error {
+            println!("Error: {error}");
+        }
+        if let Some(skipped) = &self.skipped {
+            println!("Skipped: {skipped}");
+        }
+        println!(
+            "Records: {}      Laps: {}      Tracks: {}",
+            self.stats.records, self.stats.laps, self.stats.tracks
+        );
+        println!("Written -- {}", self.written);
+        for warning in &self.warnings {
+            println!("Warning: {warning}");
+        }
+        if let Some(fit_stats) = &self.fit_stats {
+            fit_stats.print();
+        }
+    }
+
+    /// Warns that the file was too large to be bundled, if a bundle was asked for.
+    fn warn_not_bundled(&mut self, options: &ProcessingOptions) {
+        if options.export_detail && options.bundle {
+            self.add_warning(
+                "Too large to be bundled. Exported to separate detail files instead.".to_string(),
+            );
+        }
+    }
+
+    /// The format the records, waypoints or trackpoints are written in. Files exported in chunks are always written as
+    /// CSV, since the chunked reader writes the records as it goes.
+    fn records_format(&mut self, options: &ProcessingOptions) -> ExportFormat {
+        if !self.stats.chunked {
+            return options.format;
+        }
+        if options.export_detail && options.format != ExportFormat::Csv {
+            self.add_warning(format!(
+                "Too large to be written as {}. Exported to CSV instead.",
+                options.format
+            ));
+        }
+        ExportFormat::Csv
+    }
+
+    /// Adds the warnings found while parsing the file. The parsers have already passed them on to the log.
+    fn add_parse_warnings(&mut self, warnings: &[String]) {
+        self.warnings.extend_from_slice(warnings);
+    }
+
+    /// Prints the warnings for the file to stdout, if there are any.
+    pub fn print_warnings(&self) {
+        if let Some(error) = &self.error {
+            println!("{}: Error: {error}", self.input.display());
+        }
+        if let Some(skipped) = &self.skipped {
+            println!("{}: Skipped: {skipped}", self.input.display());
+        }
+        for warning in &self.warnings {
+            println!("{}: Warning: {warning}", self.input.display());
+        }
+    }
+
+    /// Adds a warning and passes it on to the log.
+    fn add_warning(&mut self, warning: String) {
+        log::warn!("{}: {warning}", self.input.display());
+        self.warnings.push(warning);
+    }
+
+    /// Records why the file couldn't be processed and passes it on to the log.
+    fn set_error(&mut self, error: String) {
+        log::error!("{}: {error}", self.input.display());
+        self.error = Some(error);
+    }
+
+    /// Records why the file was skipped and passes it on to the log.
+    fn set_skipped(&mut self, reason: String) {
+        log::warn!("{}: Skipped. {reason}", self.input.display());
+        self.written = RowCounts::default();
+        self.skipped = Some(reason);
+    }
+
+    /// Leaves out the activities shorter than the minimums, with a warning for each. If all of them are, the file is
+    /// skipped instead.
+    fn skip_short<T>(
+        &mut self,
+        activities: &mut Vec<T>,
+        min_activity: &MinActivity,
+        fields: impl Fn(&T) -> QueryFields,
+    ) {
+        if !min_activity.is_set() {
+            return;
+        }
+
+        let summaries: Vec<QueryFields> = activities.iter().map(fields).collect();
+        if let Some(reason) = min_activity.skip_reason_all(&summaries) {
+            activities.clear();
+            self.set_skipped(reason);
+            return;
+        }
+        let mut summaries = summaries.iter();
+        activities.retain(|_| {
+            let Some(summary) = summaries.next() else {
+                return true;
+            };
+            let Some(reason) = min_activity.skip_reason(summary) else {
+                return true;
+            };
+            self.add_warning(format!(
+                "{}: Skipped. {reason}",
+                summary.filename.as_deref().unwrap_or_default()
+            ));
+            false
+        });
+    }
+}
+
+/// The signature shared by the `process_*_file()` functions.
+type ProcessFn<T> = fn(&str, &ProcessingOptions) -> Result<(T, ProcessingResult), ProcessingError>;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Processes a file in a separate thread, so a pathological file can't hang a batch run. If the file takes longer than
+/// the timeout in the options, it is skipped and the failure is recorded in its result. Without a timeout, the file is
+/// processed on the current thread.
+///
+/// A file that times out is cancelled. The thread stops at the next check while parsing or exporting, and the detail
+/// files written for the file are thrown away, so it leaves no detail files behind. A file finishing just as the
+/// timeout is up counts as processed in time.
+///
+/// # Arguments
+///
+/// - `filename: &str` -- The file to be processed.
+/// - `options: &ProcessingOptions` -- How to process the file, including the timeout.
+/// - `process: ProcessFn<T>` -- The function processing the file, e.g. `process_fit_file`.
+///
+/// # Returns
+///
+/// `Result<(Option<T>, ProcessingResult), ProcessingError>` -- The parsed activity along with what was done, or `None`
+/// and the timeout in the result if the file timed out. Files skipped for being too short also give `None`, with the
+/// reason in the result.
+///
+/// # Errors
+///
+/// Reading, parsing or exporting the file may fail, and so may starting the thread. Use `ProcessingResult::failed()`
+/// to record the failure and carry on with the next file.
+///
+/// # Example
+///
+/// ```ignore
+/// let (activity, result) = process_with_timeout(filename, &options, process_fit_file)?;
+/// ```
+pub fn process_with_timeout<T: Send + 'static>(
+    filename: &str,
+    options: &ProcessingOptions,
+    process: ProcessFn<T>,
+) -> Result<(Option<T>, ProcessingResult), ProcessingError> {
+    let Some(timeout) = options.timeout else {
+        let (activity, result) = process(filename, options)?;
+        return Ok((result.skipped.is_none().then_some(activity), result));
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let thread_filename = filename.to_string();
+    let thread_options = *options;
+    let flag = Arc::new(CancelFlag::default());
+    let thread_flag = Arc::clone(&flag);
+    let settings = Settings::current();
+    thread::Builder::new()
+        .name(format!("process {filename}"))
+        .spawn(move || {
+            let _settings = Settings::enter(settings);
+            // Nobody is listening if the file timed out
+            let outcome =
+                run_cancellable(thread_flag, || process(&thread_filename, &thread_options));
+            let _ = sender.send(outcome);
+        })?;
+
+    let outcome = match receiver.recv_timeout(timeout) {
+        Err(RecvTimeoutError::Timeout) if flag.cancel() => {
+            let mut result =
+                ProcessingResult::failed(filename, &ProcessingError::TimedOut(timeout));
+            result.duration = timeout;
+            return Ok((None, result));
+        }
+        // Finished just as the timeout was up, so the outcome is on its way
+        Err(RecvTimeoutError::Timeout) => receiver.recv().map_err(|_| ProcessingError::Stopped)?,
+        Err(RecvTimeoutError::Disconnected) => return Err(ProcessingError::Stopped),
+        Ok(outcome) => outcome,
+    };
+    let (activity, result) = outcome?;
+    Ok((result.skipped.is_none().then_some(activity), result))
+}
+
+/// Checks the file against the memory limit, if any.
+fn use_chunks(filename: &str, options: &ProcessingOptions) -> Result<bool, Box<dyn Error>> {
+    match options.max_memory {
+        Some(max_memory) => exceeds_memory_limit(filename, max_memory),
+        None => Ok(false),
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Parses a FIT file and exports the details if requested. If a summary cache is open and no details are requested,
+/// the summary is taken from the cache when the file hasn't changed since it was cached.
+///
+/// Files with several FIT files chained together give one activity per chained file, each with its own detail exports.
+/// See `FITActivity::from_file_chained()` for how they are named.
+///
+/// # Arguments
+///
+/// - `filename: &str` -- The FIT file to be processed.
+/// - `options: &ProcessingOptions` -- How to process the file.
+///
+/// # Returns
+///
+/// `Result<(Vec<FITActivity>, ProcessingResult), ProcessingError>` -- The parsed activities along with what was done
+/// for the file as a whole.
+///
+/// # Errors
+///
+/// Reading, parsing or exporting the file may fail.
+pub fn process_fit_file(
+    filename: &str,
+    options: &ProcessingOptions,
+) -> Result<(Vec<FITActivity>, ProcessingResult), ProcessingError> {
+    let start = Instant::now();
+    let mut result = ProcessingResult::new(filename);
+
+    let key = cache_key(filename)?;
+    if !options.export_detail {
+        if let Some(summaries) = cached_summary::<Vec<(FITSession, Vec<FITSession>)>>(
+            key.as_ref(),
+            &mut result,
+            options.parse_stats,
+        ) {
+            let num_files = summaries.len();
+            result.written.sessions = summaries
+                .iter()
+                .map(|(_, sessions)| sessions.len().max(1))
+                .sum();
+            let mut activities = summaries
+                .into_iter()
+                .enumerate()
+                .map(|(num, (mut session, mut sessions))| {
+                    let name = chained_filename(filename, num + 1, num_files);
+                    for session in sessions.iter_mut().chain([&mut session]) {
+                        session.filename = Some(name.clone());
+                        session.tags = activity_tags(session.uuid);
+                    }
+                    FITActivity {
+                        session,
+                        sessions,
+                        ..FITActivity::default()
+                    }
+                })
+                .collect();
+            result.skip_short(&mut activities, &options.min_activity, |activity| {
+                QueryFields::from_fit(&activity.session)
+            });
+            result.duration = start.elapsed();
+            return Ok((activities, result));
+        }
+    }
+
+    result.stats.chunked = use_chunks(filename, options)?;
+
+    let mut activities = if result.stats.chunked {
+        let mut activity =
+            FITActivity::from_file_chunked(filename, options.export_detail, options.chunk_size)?;
+        tag_fit(&mut activity);
+        if options.export_detail {
+            activity.session.export_json()?;
+            activity.export_laps_csv()?;
+            activity.export_events_csv()?;
+            activity.export_devices_csv()?;
+            activity.export_device_splits_csv()?;
+        }
+        result.warn_not_bundled(options);
+        if options.interpolate {
+            result.add_warning(
+                "Too large to keep the records in memory. Records not interpolated.".to_string(),
+            );
+        }
+        vec![activity]
+    } else {
+        let mut activities = FITActivity::from_file_chained(filename)?;
+        check_cancelled()?;
+        for activity in &mut activities {
+            tag_fit(activity);
+            if options.interpolate {
+                activity.interpolate_records();
+            }
+        }
+        activities
+    };
+
+    for activity in &activities {
+        result.add_parse_warnings(&activity.warnings);
+        let short = options
+            .min_activity
+            .skip_reason(&QueryFields::from_fit(&activity.session))
+            .is_some();
+        if !short {
+            export_fit(activity, options, &mut result)?;
+            result.written.sessions += activity.summary_sessions().len();
+        }
+        result.stats.records += usize::try_from(activity.session.num_records.unwrap_or_default())
+            .map_err(|err| ProcessingError::Invalid(err.to_string()))?;
+        result.stats.laps += activity.laps.len();
+    }
+
+    if options.parse_stats {
+        result.fit_stats = Some(FITParseStats::from_file(filename)?);
+    }
+
+    if result.stats.records == 0 {
+        result.add_warning("No records found.".to_string());
+    }
+    let summaries: Vec<_> = activities
+        .iter()
+        .map(|activity| (&activity.session, &activity.sessions))
+        .collect();
+    store_summary(key.as_ref(), &summaries, &result);
+    result.skip_short(&mut activities, &options.min_activity, |activity| {
+        QueryFields::from_fit(&activity.session)
+    });
+
+    result.duration = start.elapsed();
+    Ok((activities, result))
+}
+
+/// Sets the tags of the FIT activity and each of its sessions from the tag store, if one is open.
+fn tag_fit(activity: &mut FITActivity) {
+    for session in activity.sessions.iter_mut().chain([&mut activity.session]) {
+        session.tags = activity_tags(session.uuid);
+    }
+}
+
+/// Exports the details of the FIT activity if requested, naming the files after the activity. Files exported in chunks
+/// have already had their detail files written while they were parsed.
+fn export_fit(
+    activity: &FITActivity,
+    options: &ProcessingOptions,
+    result: &mut ProcessingResult,
+) -> Result<(), Box<dyn Error>> {
+    if !options.export_detail {
+        return Ok(());
+    }
+
+    let name = activity
+        .session
+        .filename
+        .as_ref()
+        .map_or_else(|| result.input.clone(), PathBuf::from);
+    if options.bundle && !result.stats.chunked {
+        activity.export_bundle_json()?;
+        result.add_output_for(&name, "bundle.json");
+    } else {
+        let format = result.records_format(options);
+        if !result.stats.chunked {
+            activity.export_as(format)?;
+        }
+        result.add_output_for(&name, "session.json");
+        result.add_output_for(&name, "laps.csv");
+        result.add_output_for(&name, &format!("records.{}", format.extension()));
+        result.add_output_for(&name, "events.csv");
+        result.add_output_for(&name, "devices.csv");
+        result.add_output_for(&name, "device_splits.csv");
+    }
+    result.written.laps += activity.laps.len();
+    result.written.records += if result.stats.chunked {
+        usize::try_from(activity.session.num_records.unwrap_or_default())?
+    } else {
+        activity.records.len()
+    };
+
+    if let Some(unit) = options.splits {
+        if result.stats.chunked {
+            result.add_warning(
+                "Too large to keep the records in memory. No splits exported.".to_string(),
+            );
+        } else {
+            result.export_splits(&name, &activity.splits(unit))?;
+        }
+    }
+
+    if options.mean_max {
+        if result.stats.chunked {
+            result.add_warning(
+                "Too large to keep the records in memory. No mean-maximal curve exported."
+                    .to_string(),
+            );
+        } else {
+            result.export_mean_max(&name, &activity.mean_max())?;
+        }
+    }
+
+    if options.hr_scatter {
+        if result.stats.chunked {
+            result.add_warning(
+                "Too large to keep the records in memory. No heart rate scatter exported."
+                    .to_string(),
+            );
+        } else {
+            result.export_hr_scatter(&name, &activity.hr_scatter())?;
+        }
+    }
+
+    let environment = activity.session.environment;
+    if options.geojson {
+        if result.stats.chunked {
+            result.add_warning(
+                "Too large to keep the records in memory. No GeoJSON exported.".to_string(),
+            );
+        } else if !environment.has_real_location() {
+            result.add_warning(format!(
+                "{environment} activity without a real-world track. No GeoJSON exported."
+            ));
+        } else {
+            result.export_geojson(&name, &activity.geojson())?;
+        }
+    }
+
+    if options.profile_svg {
+        if result.stats.chunked {
+            result.add_warning(
+                "Too large to keep the records in memory. No elevation profile exported."
+                    .to_string(),
+            );
+        } else if !environment.has_real_location() {
+            result.add_warning(format!(
+                "{environment} activity without a real-world track. No elevation profile exported."
+            ));
+        } else {
+            result.export_profile_svg(&name, &activity.elevation_profile())?;
+        }
+    }
+
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Processes a list of FIT files, stopping at the first file that fails.
+///
+/// # Arguments
+///
+/// - `filenames: &[&str]` -- The FIT files to be processed.
+/// - `options: &ProcessingOptions` -- How to process the files.
+///
+/// # Returns
+///
+/// `Result<(FITActivities, Vec<ProcessingResult>), ProcessingError>` -- The activities and one result per file.
+///
+/// # Errors
+///
+/// Reading, parsing or exporting any of the files may fail. Files that time out are skipped, with the timeout recorded
+/// in their result.
+pub fn process_fit_files(
+    filenames: &[&str],
+    options: &ProcessingOptions,
+) -> Result<(FITActivities, Vec<ProcessingResult>), ProcessingError> {
+    let mut activities = FITActivities::default();
+    let mut results = Vec::with_capacity(filenames.len());
+
+    for filename in filenames {
+        log::info!("Processing file: {filename}");
+        let (file_activities, result) = process_with_timeout(filename, options, process_fit_file)?;
+        activities
+            .activities_list
+            .extend(file_activities.unwrap_or_default());
+        results.push(result);
+    }
+
+    Ok((activities, results))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Parses a GPX file and exports the details if requested. If a summary cache is open and no details are requested,
+/// the summary is taken from the cache when the file hasn't changed since it was cached.
+///
+/// # Arguments
+///
+/// - `filename: &str` -- The GPX file to be processed.
+/// - `options: &ProcessingOptions` -- How to process the file.
+///
+/// # Returns
+///
+/// `Result<(GPXActivity, ProcessingResult), ProcessingError>` -- The parsed activity along with what was done.
+/// If the file was exported in chunks or the summary was cached, the activity only holds the metadata.
+///
+/// # Errors
+///
+/// Reading, parsing or exporting the file may fail.
+pub fn process_gpx_file(
+    filename: &str,
+    options: &ProcessingOptions,
+) -> Result<(GPXActivity, ProcessingResult), ProcessingError> {
+    let start = Instant::now();
+    let mut result = ProcessingResult::new(filename);
+
+    let key = cache_key(filename)?;
+    if !options.export_detail {
+        if let Some(mut metadata) = cached_summary::<GPXMetadata>(key.as_ref(), &mut result, false)
+        {
+            metadata.filename = Some(PathBuf::from(filename));
+            metadata.tags = activity_tags(metadata.uuid);
+            result.written.sessions = 1;
+            if let Some(reason) = options
+                .min_activity
+                .skip_reason(&QueryFields::from_gpx(&metadata))
+            {
+                result.set_skipped(reason);
+            }
+            result.duration = start.elapsed();
+            let activity = GPXActivity {
+                metadata,
+                ..GPXActivity::default()
+            };
+            return Ok((activity, result));
+        }
+    }
+
+    let mut activity = GPXActivity::from_file(filename)?;
+    check_cancelled()?;
+    activity.metadata.tags = activity_tags(activity.metadata.uuid);
+    result.add_parse_warnings(&activity.warnings);
+    let skipped = options
+        .min_activity
+        .skip_reason(&QueryFields::from_gpx(&activity.metadata));
+    if skipped.is_none() {
+        export_gpx(&activity, options, &mut result)?;
+        result.written.sessions = 1;
+    }
+    count_gpx(&activity, &mut result);
+    store_summary(key.as_ref(), &activity.metadata, &result);
+    if let Some(reason) = skipped {
+        result.set_skipped(reason);
+    }
+
+    result.duration = start.elapsed();
+    Ok((activity, result))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Same as `process_gpx_file()`, but treats each track in the file as its own activity, with its own summary and
+/// detail exports. See `GPXActivity::split_tracks()` for how the tracks are named. The summary cache isn't used, since
+/// it holds a single summary per file.
+///
+/// # Arguments
+///
+/// - `filename: &str` -- The GPX file to be processed.
+/// - `options: &ProcessingOptions` -- How to process the file.
+///
+/// # Returns
+///
+/// `Result<(Vec<GPXActivity>, ProcessingResult), ProcessingError>` -- One activity per track along with what was done
+/// for the file as a whole.
+///
+/// # Errors
+///
+/// Reading, parsing or exporting the file may fail.
+pub fn process_gpx_file_tracks(
+    filename: &str,
+    options: &ProcessingOptions,
+) -> Result<(Vec<GPXActivity>, ProcessingResult), ProcessingError> {
+    let start = Instant::now();
+    let mut result = ProcessingResult::new(filename);
+
+    let activity = GPXActivity::from_file(filename)?;
+    check_cancelled()?;
+    result.add_parse_warnings(&activity.warnings);
+    count_gpx(&activity, &mut result);
+
+    let mut activities = activity.split_tracks();
+    result.skip_short(&mut activities, &options.min_activity, |activity| {
+        QueryFields::from_gpx(&activity.metadata)
+    });
+    for activity in &mut activities {
+        activity.metadata.tags = activity_tags(activity.metadata.uuid);
+        export_gpx(activity, options, &mut result)?;
+        result.written.sessions += 1;
+    }
+
+    result.duration = start.elapsed();
+    Ok((activities, result))
+}
+
+/// Exports the details of the GPX activity if requested, naming the files after the activity.
+fn export_gpx(
+    activity: &GPXActivity,
+    options: &ProcessingOptions,
+    result: &mut ProcessingResult,
+) -> Result<(), Box<dyn Error>> {
+    if !options.export_detail {
+        return Ok(());
+    }
+
+    let name = activity
+        .metadata
+        .filename
+        .clone()
+        .unwrap_or_else(|| result.input.clone());
+    if options.bundle {
+        activity.export_bundle_json()?;
+        result.add_output_for(&name, "bundle.json");
+    } else {
+        let format = result.records_format(options);
+        activity.export_as(format)?; // metadata, tracks, waypoints
+        result.add_output_for(&name, "session.json");
+        result.add_output_for(&name, "tracks.csv");
+        result.add_output_for(&name, &format!("waypoints.{}", format.extension()));
+    }
+    result.written.tracks += activity.tracks.len();
+    result.written.records += activity
+        .tracks
+        .iter()
+        .map(|track| track.waypoints.len())
+        .sum::<usize>();
+
+    if let Some(unit) = options.splits {
+        result.export_splits(&name, &activity.splits(unit))?;
+    }
+    if options.mean_max {
+        result.export_mean_max(&name, &activity.mean_max())?;
+    }
+    if options.hr_scatter {
+        result.export_hr_scatter(&name, &activity.hr_scatter())?;
+    }
+    if options.geojson {
+        result.export_geojson(&name, &activity.geojson())?;
+    }
+    if options.profile_svg {
+        result.export_profile_svg(&name, &activity.elevation_profile())?;
+    }
+
+    Ok(())
+}
+
+/// Counts the tracks and waypoints in the GPX activity.
+fn count_gpx(activity: &GPXActivity, result: &mut ProcessingResult) {
+    result.stats.records = activity.tracks.iter().map(|t| t.waypoints.len()).sum();
+    result.stats.tracks = activity.tracks.len();
+    if result.stats.tracks == 0 {
+        result.add_warning("No tracks found.".to_string());
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Processes a list of GPX files, stopping at the first file that fails.
+///
+/// # Arguments
+///
+/// - `filenames: &[&str]` -- The GPX files to be processed.
+/// - `options: &ProcessingOptions` -- How to process the files.
+///
+/// # Returns
+///
+/// `Result<(GPXActivities, Vec<ProcessingResult>), ProcessingError>` -- The activities and one result per file.
+///
+/// # Errors
+///
+/// Reading, parsing or exporting any of the files may fail. Files that time out are skipped, with the timeout recorded
+/// in their result.
+pub fn process_gpx_files(
+    filenames: &[&str],
+    options: &ProcessingOptions,
+) -> Result<(GPXActivities, Vec<ProcessingResult>), ProcessingError> {
+    let mut activities = GPXActivities::new();
+    let mut results = Vec::with_capacity(filenames.len());
+
+    for filename in filenames {
+        log::info!("Processing file: {filename}");
+        let (activity, result) = process_with_timeout(filename, options, process_gpx_file)?;
+        activities.activities_list.extend(activity);
+        results.push(result);
+    }
+
+    Ok((activities, results))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Parses a TCX file and exports the details if requested. If a summary cache is open and no details are requested,
+/// the summary is taken from the cache when the file hasn't changed since it was cached.
+///
+/// # Arguments
+///
+/// - `filename: &str` -- The TCX file to be processed.
+/// - `options: &ProcessingOptions` -- How to process the file.
+///
+/// # Returns
+///
+/// `Result<(Option<TCXActivity>, ProcessingResult), ProcessingError>` -- The activity summary, or `None` if the file
+/// contains no activities or was skipped for being too short, along with what was done.
+///
+/// # Errors
+///
+/// Reading, parsing or exporting the file may fail.
+pub fn process_tcx_file(
+    filename: &str,
+    options: &ProcessingOptions,
+) -> Result<(Option<TCXActivity>, ProcessingResult), ProcessingError> {
+    let start = Instant::now();
+    let mut result = ProcessingResult::new(filename);
+
+    let key = cache_key(filename)?;
+    if !options.export_detail {
+        if let Some(mut activity) =
+            cached_summary::<Option<TCXActivity>>(key.as_ref(), &mut result, false)
+        {
+            if let Some(activity) = activity.as_mut() {
+                activity.filename = Some(filename.to_string());
+                activity.tags = activity_tags(activity.uuid);
+            }
+            result.written.sessions = usize::from(activity.is_some());
+            if let Some(reason) = activity.as_ref().and_then(|activity| {
+                options
+                    .min_activity
+                    .skip_reason(&QueryFields::from_tcx(activity))
+            }) {
+                result.set_skipped(reason);
+                activity = None;
+            }
+            result.duration = start.elapsed();
+            return Ok((activity, result));
+        }
+    }
+
+    let tcdb = read_tcx(BufReader::new(open_input(filename)?))?;
+    check_cancelled()?;
+    log::trace!("processing::process_tcx_file() -- tcxfile = {tcdb:?}");
+
+    let Some(activities) = tcdb.activities else {
+        result.add_warning("No activities found.".to_string());
+        store_summary(key.as_ref(), &None::<TCXActivity>, &result);
+        result.duration = start.elapsed();
+        return Ok((None, result));
+    };
+
+    let mut curr_activities = TCXActivity::from_activities(&activities);
+    curr_activities.filename = Some(filename.to_string());
+    curr_activities.set_creator(&TCXCreator::from_file(filename)?);
+    curr_activities.tags = activity_tags(curr_activities.uuid);
+    log::trace!("processing::process_tcx_file() -- activities summary: {curr_activities:?}");
+    for warning in &curr_activities.warnings {
+        result.add_warning(warning.clone());
+    }
+    let skipped = options
+        .min_activity
+        .skip_reason(&QueryFields::from_tcx(&curr_activities));
+    let export_detail = options.export_detail && skipped.is_none();
+
+    for activity in &activities.activities {
+        result.stats.laps += activity.laps.len();
+        for lap in &activity.laps {
+            result.stats.tracks += lap.tracks.len();
+            result.stats.records += lap
+                .tracks
+                .iter()
+                .map(|t| t.trackpoints.len())
+                .sum::<usize>();
+        }
+    }
+
+    if export_detail && options.bundle {
+        log::debug!("processing::process_tcx_file() -- Writing bundle for {filename}");
+        let mut trackpoints = TCXTrackpointList::from_activities(&activities);
+        trackpoints.set_activity_uuid(curr_activities.uuid);
+        curr_activities.export_bundle_json(&trackpoints)?;
+        result.add_output("bundle.json");
+    } else if export_detail {
+        let detail = options.tcx_detail;
+
+        // Export the activity summary to JSON
+        if detail.json {
+            log::debug!(
+                "processing::process_tcx_file() -- Writing activity summary for {filename}"
+            );
+            curr_activities.export_json()?;
+            result.add_output("activity.json");
+        }
+
+        // Export the Trackpoints to CSV
+        if detail.trackpoints {
+            log::debug!("Parsing and exporting Trackpoint list.");
+            export_tcx_trackpoints(
+                filename,
+                &activities,
+                curr_activities.uuid,
+                options,
+                &mut result,
+            )?;
+        }
+
+        // Export the laps to CSV
+        if detail.laps {
+            log::debug!("processing::process_tcx_file() -- Writing laps for {filename}");
+            curr_activities.export_laps_csv()?;
+            result.add_output("laps.csv");
+        }
+    }
+
+    result.written.sessions = 1;
+    if export_detail {
+        let detail = options.tcx_detail;
+        let bundled = options.bundle;
+        if bundled || detail.laps {
+            result.written.laps = result.stats.laps;
+        }
+        if bundled || detail.trackpoints {
+            result.written.tracks = result.stats.tracks;
+            result.written.records = result.stats.records;
+        }
+    }
+
+    if export_detail
+        && (options.splits.is_some()
+            || options.mean_max
+            || options.hr_scatter
+            || options.profile_svg)
+    {
+        let input = result.input.clone();
+        let trackpoints = TCXTrackpointList::from_activities(&activities);
+        if let Some(unit) = options.splits {
+            result.export_splits(&input, &trackpoints.splits(unit))?;
+        }
+        if options.mean_max {
+            result.export_mean_max(&input, &trackpoints.mean_max())?;
+        }
+        if options.hr_scatter {
+            result.export_hr_scatter(&input, &trackpoints.hr_scatter())?;
+        }
+        if options.profile_svg {
+            result.export_profile_svg(&input, &trackpoints.elevation_profile())?;
+        }
+    }
+
+    if result.stats.records == 0 {
+        result.add_warning("No trackpoints found.".to_string());
+    }
+    store_summary(key.as_ref(), &Some(&curr_activities), &result);
+    if let Some(reason) = skipped {
+        result.set_skipped(reason);
+        result.duration = start.elapsed();
+        return Ok((None, result));
+    }
+
+    result.duration = start.elapsed();
+    Ok((Some(curr_activities), result))
+}
+
+/// Writes the trackpoints of a TCX file to `trackpoints.csv`, or the format set in the options.
+fn export_tcx_trackpoints(
+    filename: &str,
+    activities: &tcx::Activities,
+    activity_uuid: Option<Uuid>,
+    options: &ProcessingOptions,
+    result: &mut ProcessingResult,
+) -> Result<(), Box<dyn Error>> {
+    let format = result.records_format(options);
+    let tp_extension = format!("trackpoints.{}", format.extension());
+    let tp_file = output_path(filename, &tp_extension)
+        .to_string_lossy()
+        .to_string();
+    let mut trackpoints = TCXTrackpointList::from_activities(activities);
+    trackpoints.set_activity_uuid(activity_uuid);
+    trackpoints.export(&tp_file, format)?;
+    result.add_output(&tp_extension);
+
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Processes a list of TCX files, stopping at the first file that fails.
+///
+/// # Arguments
+///
+/// - `filenames: &[&str]` -- The TCX files to be processed.
+/// - `options: &ProcessingOptions` -- How to process the files.
+///
+/// # Returns
+///
+/// `Result<(TCXActivitiesList, Vec<ProcessingResult>), ProcessingError>` -- The activities and one result per file.
+///
+/// # Errors
+///
+/// Reading, parsing or exporting any of the files may fail. Files that time out are skipped, with the timeout recorded
+/// in their result.
+pub fn process_tcx_files(
+    filenames: &[&str],
+    options: &ProcessingOptions,
+) -> Result<(TCXActivitiesList, Vec<ProcessingResult>), ProcessingError> {
+    let mut act_list = TCXActivitiesList::default();
+    let mut results = Vec::with_capacity(filenames.len());
+
+    for filename in filenames {
+        log::info!("Processing file: {filename}");
+        let (activity, result) = process_with_timeout(filename, options, process_tcx_file)?;
+        act_list.activities.extend(activity.flatten());
+        results.push(result);
+    }
+
+    Ok((act_list, results))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Reads the summary of each activity in a FIT, GPX or TCX file, e.g. for queries or statistics. Multisport FIT files
+/// have one per sport, and chained FIT files one per file. The summaries are taken from the summary cache if one is
+/// open. Activities shorter than the minimums in the options are left out.
+///
+/// # Arguments
+///
+/// - `filename: &str` -- The file to read.
+/// - `options: &ProcessingOptions` -- How to process the file. Usually without the details.
+///
+/// # Returns
+///
+/// `Result<Vec<QueryFields>, ProcessingError>` -- The summary of each activity in the file.
+///
+/// # Errors
+///
+/// The file may not be a FIT, GPX or TCX file. Reading or parsing the file may fail.
+pub fn activity_summaries(
+    filename: &str,
+    options: &ProcessingOptions,
+) -> Result<Vec<QueryFields>, ProcessingError> {
+    let format = ActivityFormat::from_filename(filename)
+        .ok_or_else(|| ProcessingError::Invalid("Unknown file type.".to_string()))?;
+
+    Ok(match format {
+        ActivityFormat::Fit => process_fit_file(filename, options)?
+            .0
+            .iter()
+            .flat_map(FITActivity::summary_sessions)
+            .map(QueryFields::from_fit)
+            .collect(),
+        ActivityFormat::Gpx => {
+            let (activity, result) = process_gpx_file(filename, options)?;
+            if result.skipped.is_some() {
+                Vec::new()
+            } else {
+                vec![QueryFields::from_gpx(&activity.metadata)]
+            }
+        }
+        ActivityFormat::Tcx => process_tcx_file(filename, options)?
+            .0
+            .iter()
+            .map(QueryFields::from_tcx)
+            .collect(),
+    })
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Finds the activity files to read: the files given, and the FIT, GPX and TCX files in the directories given, in
+/// order of their names. Files given directly are kept whatever their extension, so unknown files are still reported.
+/// Zip archives, given or in the directories, are replaced by the activity files inside them. See
+/// `expand_archives()`.
+///
+/// # Arguments
+///
+/// - `inputs: I` -- The files and directories given on the command line.
+/// - `recursive: bool` -- Also look in the directories below the directories given.
+///
+/// # Returns
+///
+/// `Result<Vec<String>, Box<dyn Error>>` -- The files to read.
+///
+/// # Errors
+///
+/// Reading a directory may fail.
+pub fn activity_files<'a, I>(inputs: I, recursive: bool) -> Result<Vec<String>, Box<dyn Error>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut filenames = Vec::new();
+    for input in inputs {
+        if Path::new(input).is_dir() {
+            let mut found = Vec::new();
+            directory_files(Path::new(input), recursive, &mut found)?;
+            found.sort();
+            filenames.extend(expand_archives(found.iter().map(String::as_str)));
+        } else {
+            filenames.extend(expand_archives([input]));
+        }
+    }
+
+    Ok(filenames)
+}
+
+/// Adds the FIT, GPX and TCX files in the directory, and in the directories below it if recursive, to the list.
+fn directory_files(
+    dir: &Path,
+    recursive: bool,
+    found: &mut Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                directory_files(&path, recursive, found)?;
+            }
+        } else {
+            let filename = path.to_string_lossy().to_string();
+            if ActivityFormat::from_filename(&filename).is_some() || is_archive(&filename) {
+                found.push(filename);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Finds the activities that overlap in time, and adds a warning to the results for both files of each pair, so the
+/// overlaps show up in the statistics and the manifest. See `find_overlaps()`.
+///
+/// # Arguments
+///
+/// - `results: &mut [ProcessingResult]` -- What was done for each file.
+/// - `windows: &[ActivityWindow]` -- When each activity took place, e.g. from `FITActivities::time_windows()`.
+///
+/// # Returns
+///
+/// `Vec<Overlap>` -- Each pair of activities that overlap.
+pub fn flag_overlaps(results: &mut [ProcessingResult], windows: &[ActivityWindow]) -> Vec<Overlap> {
+    let overlaps = find_overlaps(windows);
+
+    for overlap in &overlaps {
+        log::warn!("{overlap}");
+        for result in results.iter_mut().filter(|r| overlap.involves(&r.input)) {
+            result.warnings.push(overlap.to_string());
+        }
+    }
+
+    overlaps
+}
+
+/// The shape of the manifest export.
+#[derive(Serialize)]
+struct Manifest<'a> {
+    /// What was done for each file.
+    files: &'a [ProcessingResult],
+
+    /// What was written for all the files.
+    totals: RowCounts,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Adds up what was written for all the files.
+///
+/// # Arguments
+///
+/// `results: &[ProcessingResult]` -- What was done for each file.
+///
+/// # Returns
+///
+/// `RowCounts` -- The number of sessions, laps, tracks and records written in total.
+#[must_use]
+pub fn written_totals(results: &[ProcessingResult]) -> RowCounts {
+    let mut totals = RowCounts::default();
+    for result in results {
+        totals.add(&result.written);
+    }
+    totals
+}
+
+/// Logs what was written for each file and in total at the end of a run, so it's easy to check nothing was dropped.
+///
+/// # Arguments
+///
+/// `results: &[ProcessingResult]` -- What was done for each file.
+pub fn log_written(results: &[ProcessingResult]) {
+    for result in results {
+        log::info!("{}: {}", result.input.display(), result.written);
+    }
+    log::info!(
+        "Total for {} files: {}",
+        results.len(),
+        written_totals(results)
+    );
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Logs the files that couldn't be processed at the end of a run, and works out the exit code from them.
+///
+/// # Arguments
+///
+/// `results: &[ProcessingResult]` -- What was done for each file.
+///
+/// # Returns
+///
+/// `i32` -- 0 if all the files were processed, or `EXIT_PARTIAL_FAILURE` if any of them failed.
+#[must_use]
+pub fn report_failures(results: &[ProcessingResult]) -> i32 {
+    let failed: Vec<&ProcessingResult> = results.iter().filter(|r| r.error.is_some()).collect();
+    if failed.is_empty() {
+        return 0;
+    }
+
+    log::error!(
+        "{} of {} files couldn't be processed:",
+        failed.len(),
+        results.len()
+    );
+    for result in failed {
+        log::error!(
+            "  {}: {}",
+            result.input.display(),
+            result.error.as_deref().unwrap_or_default()
+        );
+    }
+    EXIT_PARTIAL_FAILURE
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes a JSON manifest listing each file processed, with the outputs written, the number of rows written and the
+/// warnings found, along with the number of rows written in total.
+///
+/// # Arguments
+///
+/// - `results: &[ProcessingResult]` -- What was done for each file.
+/// - `filename: &str` -- The manifest file to write.
+///
+/// # Errors
+///
+/// Creating or writing the file may fail.
+pub fn export_manifest(results: &[ProcessingResult], filename: &str) -> Result<(), Box<dyn Error>> {
+    log::debug!("Writing manifest for {} files to {filename}", results.len());
+    write_atomically(Path::new(filename), |file| {
+        Ok(serde_json::to_writer_pretty(
+            file,
+            &Manifest {
+                files: results,
+                totals: written_totals(results),
+            },
+        )?)
+    })
+}
+
+#[cfg(test)]
+/// Tests for the processing module
+mod tests {
+    use super::*;
+    use crate::FITEnvironment;
+    use std::fs::File;
+
+    #[test]
+    /// Test processing a FIT file without writing anything
+    fn test_process_fit_file() {
+        let options = ProcessingOptions {
+            export_detail: false,
+            ..ProcessingOptions::default()
+        };
+        let (activities, result) = process_fit_file("../data/rowing.fit", &options).unwrap();
+
+        assert_eq!(result.input, PathBuf::from("../data/rowing.fit"));
+        assert!(result.outputs.is_empty());
+        assert!(result.warnings.is_empty());
+        assert!(!result.stats.chunked);
+        assert_eq!(activities.len(), 1);
+        assert_eq!(result.stats.records, activities[0].records.len());
+        assert_eq!(result.stats.laps, activities[0].laps.len());
+        assert_eq!(
+            result.written,
+            RowCounts {
+                sessions: 1,
+                ..RowCounts::default()
+            }
+        );
+        assert!(result.fit_stats.is_none());
+    }
+
+    #[test]
+    /// Test that a missing file is reported as an error
+    fn test_process_missing_file() {
+        let options = ProcessingOptions::default();
+
+        assert!(process_fit_files(&["../data/does_not_exist.fit"], &options).is_err());
+        assert!(process_gpx_file("../data/does_not_exist.gpx", &options).is_err());
+        assert!(process_tcx_file("../data/does_not_exist.tcx", &options).is_err());
+    }
+
+    #[test]
+    /// Test that a file taking too long is skipped, while the errors of files finishing in time are passed on
+    fn test_process_with_timeout() {
+        fn slow(
+            filename: &str,
+            _: &ProcessingOptions,
+        ) -> Result<((), ProcessingResult), ProcessingError> {
+            crate::output_naming::write_output(&output_path(filename, "laps.csv"), |file| {
+                Ok(std::io::Write::write_all(file, b"lap_num\n")?)
+            })?;
+            thread::sleep(Duration::from_millis(200));
+            check_cancelled()?;
+            Ok(((), ProcessingResult::new(filename)))
+        }
+
+        let dir = std::env::temp_dir().join("fitutils_test_timeout");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let slow_file = dir.join("slow.fit");
+        let options = ProcessingOptions {
+            export_detail: false,
+            timeout: Some(Duration::from_millis(20)),
+            ..ProcessingOptions::default()
+        };
+        let (activity, result) =
+            process_with_timeout(&slow_file.to_string_lossy(), &options, slow).unwrap();
+        assert!(activity.is_none());
+        assert!(result.error.unwrap().starts_with("Timed out"));
+
+        // The file timed out, so the detail file written before then is thrown away
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let options = ProcessingOptions {
+            timeout: Some(Duration::from_secs(60)),
+            ..options
+        };
+        let (activity, result) =
+            process_with_timeout("../data/rowing.fit", &options, process_fit_file).unwrap();
+        assert!(activity.is_some());
+        assert!(result.error.is_none());
+        assert!(matches!(
+            process_with_timeout("../data/does_not_exist.fit", &options, process_fit_file),
+            Err(ProcessingError::Io(_))
+        ));
+    }
+
+    #[test]
+    /// Test that the files that couldn't be processed give the exit code for partial failures
+    fn test_report_failures() {
+        let ok = ProcessingResult::new("running.fit");
+        assert_eq!(report_failures(std::slice::from_ref(&ok)), 0);
+        assert_eq!(report_failures(&[]), 0);
+
+        let failed = ProcessingResult::failed(
+            "broken.fit",
+            &ProcessingError::Invalid("Unexpected end of file".to_string()),
+        );
+        assert_eq!(
+            failed.error.as_deref(),
+            Some("Unable to process the file. Unexpected end of file")
+        );
+        assert_eq!(report_failures(&[ok, failed]), EXIT_PARTIAL_FAILURE);
+    }
+
+    #[test]
+    /// Test that a bundle nests the waypoints within the tracks
+    fn test_process_gpx_bundle() {
+        let dir = std::env::temp_dir().join("fitutils_test_process_gpx_bundle");
+        std::fs::create_dir_all(&dir).unwrap();
+        let filename = dir.join("running.gpx");
+        std::fs::copy("../data/running.gpx", &filename).unwrap();
+
+        let options = ProcessingOptions {
+            bundle: true,
+            ..ProcessingOptions::default()
+        };
+        let (activity, result) = process_gpx_file(filename.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.outputs, vec![dir.join("running.bundle.json")]);
+
+        let bundle: serde_json::Value =
+            serde_json::from_reader(File::open(&result.outputs[0]).unwrap()).unwrap();
+        assert_eq!(
+            bundle["tracks"][0]["waypoints"].as_array().unwrap().len(),
+            activity.tracks[0].waypoints.len()
+        );
+        assert!(bundle["session"]["uuid"].is_string());
+        assert_eq!(result.written.records, activity.tracks[0].waypoints.len());
+        assert_eq!(result.written.tracks, 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    /// Test finding the activity files in a directory and the directories below it
+    fn test_activity_files() {
+        let dir = std::env::temp_dir().join("fitutils_test_activity_files");
+        let sub = dir.join("2023");
+        std::fs::create_dir_all(&sub).unwrap();
+        for name in ["b.fit", "a.gpx", "notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        std::fs::write(sub.join("c.tcx"), "").unwrap();
+        let path = |path: PathBuf| path.to_string_lossy().to_string();
+        let dir_name = path(dir.clone());
+
+        assert_eq!(
+            activity_files([dir_name.as_str(), "other.csv"], false).unwrap(),
+            [
+                path(dir.join("a.gpx")),
+                path(dir.join("b.fit")),
+                "other.csv".to_string()
+            ]
+        );
+        assert_eq!(
+            activity_files([dir_name.as_str()], true).unwrap(),
+            [
+                path(sub.join("c.tcx")),
+                path(dir.join("a.gpx")),
+                path(dir.join("b.fit"))
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    /// Test the output naming
+    fn test_add_output() {
+        let mut result = ProcessingResult::new("data/rowing.fit");
+        result.add_output("laps.csv");
+
+        assert_eq!(result.outputs, vec![PathBuf::from("data/rowing.laps.csv")]);
+    }
+
+    #[test]
+    /// Test that an overlap is flagged on both files
+    fn test_flag_overlaps() {
+        let mut results = vec![
+            ProcessingResult::new("watch.fit"),
+            ProcessingResult::new("bike.fit"),
+            ProcessingResult::new("later.fit"),
+        ];
+        let start = chrono::Local::now();
+        let hour = Some(crate::Duration::from_millis_u64(3_600_000));
+        let windows: Vec<ActivityWindow> = [
+            ("watch.fit", start),
+            ("bike.fit", start + chrono::Duration::minutes(30)),
+            ("later.fit", start + chrono::Duration::hours(3)),
+        ]
+        .into_iter()
+        .filter_map(|(name, st)| ActivityWindow::new(name, None, Some(st), hour))
+        .collect();
+
+        assert_eq!(flag_overlaps(&mut results, &windows).len(), 1);
+        assert_eq!(results[0].warnings, results[1].warnings);
+        assert_eq!(results[0].warnings.len(), 1);
+        assert!(results[2].warnings.is_empty());
+    }
+
+    #[test]
+    /// Test that the parse warnings end up in the result and the manifest
+    fn test_export_manifest() {
+        let options = ProcessingOptions {
+            export_detail: false,
+            ..ProcessingOptions::default()
+        };
+        let gpx = std::env::temp_dir().join("fitutils_test_manifest.gpx");
+        std::fs::write(
+            &gpx,
+            r#"<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+<trk><trkseg><trkpt lat="59.9" lon="10.7"/></trkseg></trk><trk/></gpx>"#,
+        )
+        .unwrap();
+        let (_, result) = process_gpx_file(gpx.to_str().unwrap(), &options).unwrap();
+        assert_eq!(
+            result.warnings,
+            vec![
+                "Track 2 has no waypoints.".to_string(),
+                "1 track waypoints have no time. Their duration is unknown.".to_string(),
+            ]
+        );
+
+        let filename = std::env::temp_dir().join("fitutils_test_manifest.json");
+        let filename = filename.to_str().unwrap();
+        export_manifest(std::slice::from_ref(&result), filename).unwrap();
+
+        let manifest: serde_json::Value =
+            serde_json::from_reader(File::open(filename).unwrap()).unwrap();
+        assert_eq!(
+            manifest["files"][0]["warnings"],
+            serde_json::json!(result.warnings)
+        );
+        assert_eq!(manifest["totals"]["sessions"], 1);
+        assert_eq!(manifest["totals"]["records"], 0);
+
+        std::fs::remove_file(filename).unwrap();
+        std::fs::remove_file(gpx).unwrap();
+    }
+    #[test]
+    /// Test exporting the splits, the mean-maximal curve and the heart rate scatter along with the details
+    fn test_export_splits() {
+        let tcx = std::env::temp_dir().join("fitutils_test_splits.tcx");
+        std::fs::copy("../data/running.tcx", &tcx).unwrap();
+        let options = ProcessingOptions {
+            splits: Some(SplitUnit::Kilometer),
+            mean_max: true,
+            hr_scatter: true,
+            ..ProcessingOptions::default()
+        };
+        let (_, result) = process_tcx_file(tcx.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.written.records, result.stats.records);
+        assert_eq!(
+            written_totals(&[result.clone(), result.clone()]).laps,
+            2 * result.stats.laps
+        );
+
+        let splits = tcx.with_extension("splits.csv");
+        assert!(result.outputs.contains(&splits));
+        let csv = std::fs::read_to_string(&splits).unwrap();
+        assert!(csv.lines().count() > 1);
+
+        let mean_max = tcx.with_extension("mean_max.csv");
+        assert!(result.outputs.contains(&mean_max));
+        let csv = std::fs::read_to_string(&mean_max).unwrap();
+        assert!(csv.starts_with("duration_sec,power_avg_w,heartrate_avg_bpm"));
+
+        let hr_scatter = tcx.with_extension("hr_scatter.csv");
+        assert!(result.outputs.contains(&hr_scatter));
+        let csv = std::fs::read_to_string(&hr_scatter).unwrap();
+        assert!(csv.starts_with("time,elapsed_sec,speed_ms,pace_sec,power_w,heartrate_bpm"));
+
+        for output in &result.outputs {
+            std::fs::remove_file(output).unwrap();
+        }
+        std::fs::remove_file(tcx).unwrap();
+    }
+
+    #[test]
+    /// Test writing only some of the TCX detail files
+    fn test_tcx_detail_files() {
+        let tcx = std::env::temp_dir().join("fitutils_test_detail.tcx");
+        std::fs::copy("../data/running.tcx", &tcx).unwrap();
+        let options = ProcessingOptions {
+            tcx_detail: TcxDetailFiles {
+                json: false,
+                laps: true,
+                trackpoints: false,
+            },
+            ..ProcessingOptions::default()
+        };
+        let (_, result) = process_tcx_file(tcx.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.outputs, [tcx.with_extension("laps.csv")]);
+        assert_eq!(result.written.laps, result.stats.laps);
+        assert_eq!(result.written.records, 0);
+        assert!(!tcx.with_extension("activity.json").exists());
+
+        for output in &result.outputs {
+            std::fs::remove_file(output).unwrap();
+        }
+        std::fs::remove_file(tcx).unwrap();
+    }
+
+    #[test]
+    /// Test exporting the GPX tracks as GeoJSON along with the details
+    fn test_export_geojson() {
+        let gpx = std::env::temp_dir().join("fitutils_test_geojson.gpx");
+        std::fs::copy("../data/running.gpx", &gpx).unwrap();
+        let options = ProcessingOptions {
+            geojson: true,
+            ..ProcessingOptions::default()
+        };
+        let (_, result) = process_gpx_file(gpx.to_str().unwrap(), &options).unwrap();
+
+        let geojson = gpx.with_extension("geojson");
+        assert!(result.outputs.contains(&geojson));
+        let collection: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&geojson).unwrap()).unwrap();
+        assert_eq!(collection["type"], "FeatureCollection");
+        assert_eq!(collection["features"][0]["geometry"]["type"], "LineString");
+
+        for output in &result.outputs {
+            std::fs::remove_file(output).unwrap();
+        }
+        std::fs::remove_file(gpx).unwrap();
+    }
+
+    #[test]
+    /// Test that a virtual ride gets no GeoJSON or elevation profile, as its track isn't on a real map
+    fn test_virtual_no_geojson() {
+        let fit = std::env::temp_dir().join("fitutils_test_virtual.fit");
+        let mut activity = FITActivity::from_reader(
+            &mut std::fs::read("../data/rowing.fit").unwrap().as_slice(),
+            &fit.to_string_lossy(),
+        )
+        .unwrap();
+        activity.session.environment = FITEnvironment::Virtual;
+        let options = ProcessingOptions {
+            geojson: true,
+            profile_svg: true,
+            ..ProcessingOptions::default()
+        };
+        let mut result = ProcessingResult::new(&fit.to_string_lossy());
+        export_fit(&activity, &options, &mut result).unwrap();
+
+        assert!(!fit.with_extension("geojson").exists());
+        assert!(!result.outputs.contains(&fit.with_extension("geojson")));
+        assert!(!result.outputs.contains(&fit.with_extension("profile.svg")));
+        assert!(result.warnings.contains(
+            &"Virtual activity without a real-world track. No GeoJSON exported.".to_string()
+        ));
+
+        for output in &result.outputs {
+            let _ = std::fs::remove_file(output);
+        }
+    }
+
+    #[test]
+    /// Test exporting the elevation profile of a TCX file, and the warning for a FIT file without elevations
+    fn test_export_profile_svg() {
+        let tcx = std::env::temp_dir().join("fitutils_test_profile.tcx");
+        std::fs::copy("../data/running.tcx", &tcx).unwrap();
+        let options = ProcessingOptions {
+            profile_svg: true,
+            tcx_detail: TcxDetailFiles {
+                json: false,
+                laps: false,
+                trackpoints: false,
+            },
+            ..ProcessingOptions::default()
+        };
+        let (_, result) = process_tcx_file(tcx.to_str().unwrap(), &options).unwrap();
+
+        let svg = tcx.with_extension("profile.svg");
+        assert_eq!(result.outputs, std::slice::from_ref(&svg));
+        let image = std::fs::read_to_string(&svg).unwrap();
+        assert!(image.contains("<title>fitutils_test_profile.tcx</title>"));
+        assert!(image.contains("<polyline points="));
+
+        std::fs::remove_file(svg).unwrap();
+        std::fs::remove_file(tcx).unwrap();
+
+        let fit = std::env::temp_dir().join("fitutils_test_profile.fit");
+        std::fs::copy("../data/rowing.fit", &fit).unwrap();
+        let options = ProcessingOptions {
+            profile_svg: true,
+            ..ProcessingOptions::default()
+        };
+        let (_, result) = process_fit_file(fit.to_str().unwrap(), &options).unwrap();
+        assert!(!result.outputs.contains(&fit.with_extension("profile.svg")));
+        assert!(result
+            .warnings
+            .contains(&"No elevations found. No elevation profile exported.".to_string()));
+
+        for output in &result.outputs {
+            std::fs::remove_file(output).unwrap();
+        }
+        std::fs::remove_file(fit).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! The ways processing a single file can fail, so the tools can report each file that failed and carry on with the
+//! rest of the batch, exiting with `EXIT_PARTIAL_FAILURE` at the end.
+
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// The exit code when some of the files couldn't be processed. The rest of the files were processed as usual.
+pub const EXIT_PARTIAL_FAILURE: i32 = 2;
+
+/// Why a file couldn't be processed.
+#[derive(Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub enum ProcessingError {
+    /// The file, or one of the files written for it, couldn't be read or written, e.g. it doesn't exist.
+    Io(std::io::Error),
+
+    /// The FIT data couldn't be decoded, e.g. the file is truncated or the CRC doesn't match.
+    Fit(fitparser::Error),
+
+    /// The GPX data couldn't be parsed.
+    Gpx(gpx::errors::GpxError),
+
+    /// The TCX data couldn't be parsed.
+    Tcx(serde_xml_rs::Error),
+
+    /// The XML couldn't be read, e.g. while reading the creator of a TCX file.
+    Xml(xml::reader::Error),
+
+    /// A CSV file couldn't be read or written.
+    Csv(csv::Error),
+
+    /// A JSON file couldn't be read or written.
+    Json(serde_json::Error),
+
+    /// The file couldn't be processed for some other reason, e.g. it isn't an activity file.
+    Invalid(String),
+
+    /// The file took longer than the timeout.
+    TimedOut(Duration),
+
+    /// The processing stopped without finishing, e.g. the thread processing the file panicked.
+    Stopped,
+}
+
+impl fmt::Display for ProcessingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "Unable to read or write the file. {err}"),
+            Self::Fit(err) => write!(f, "Unable to decode the FIT data. {err}"),
+            Self::Gpx(err) => write!(f, "Unable to parse the GPX data. {err}"),
+            Self::Tcx(err) => write!(f, "Unable to parse the TCX data. {err}"),
+            Self::Xml(err) => write!(f, "Unable to read the XML. {err}"),
+            Self::Csv(err) => write!(f, "Unable to read or write the CSV. {err}"),
+            Self::Json(err) => write!(f, "Unable to read or write the JSON. {err}"),
+            Self::Invalid(err) => write!(f, "Unable to process the file. {err}"),
+            Self::TimedOut(timeout) => write!(
+                f,
+                "Timed out after {} seconds. The file was skipped.",
+                timeout.as_secs_f64()
+            ),
+            Self::Stopped => write!(f, "Processing stopped unexpectedly."),
+        }
+    }
+}
+
+impl Error for ProcessingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Fit(err) => Some(err),
+            Self::Gpx(err) => Some(err),
+            Self::Tcx(err) => Some(err),
+            Self::Xml(err) => Some(err),
+            Self::Csv(err) => Some(err),
+            Self::Json(err) => Some(err),
+            Self::Invalid(_) | Self::TimedOut(_) | Self::Stopped => None,
+        }
+    }
+}
+
+/// Implements `From` for each of the errors carried as they are, and picks them out of the errors passed on as
+/// `Box<dyn Error>`.
+macro_rules! from_source {
+    ($($variant:ident($source:ty)),* $(,)?) => {
+        $(
+            impl From<$source> for ProcessingError {
+                fn from(err: $source) -> Self {
+                    Self::$variant(err)
+                }
+            }
+        )*
+
+        impl From<Box<dyn Error>> for ProcessingError {
+            /// Sorts the errors passed on by the readers and exporters by where they came from, keeping the original
+            /// error. Errors that are only a message end up as `Invalid`.
+            fn from(err: Box<dyn Error>) -> Self {
+                let mut err = match err.downcast::<Self>() {
+                    Ok(err) => return *err,
+                    Err(err) => err,
+                };
+                $(
+                    err = match err.downcast::<$source>() {
+                        Ok(err) => return Self::$variant(*err),
+                        Err(err) => err,
+                    };
+                )*
+                Self::Invalid(err.to_string())
+            }
+        }
+    };
+}
+
+from_source!(
+    Io(std::io::Error),
+    Fit(fitparser::Error),
+    Gpx(gpx::errors::GpxError),
+    Tcx(serde_xml_rs::Error),
+    Xml(xml::reader::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+);
+
+#[cfg(test)]
+/// Tests for the processing_error module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test sorting the errors passed on into the kinds of failure
+    fn test_from_error() {
+        let missing: Box<dyn Error> = Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "No such file",
+        ));
+        let err = ProcessingError::from(missing);
+        assert!(matches!(err, ProcessingError::Io(_)));
+        assert!(err.source().is_some());
+        assert_eq!(
+            err.to_string(),
+            "Unable to read or write the file. No such file"
+        );
+
+        let mut bytes = std::fs::read("../data/rowing.fit").unwrap();
+        if let Some(crc) = bytes.last_mut() {
+            *crc = !*crc;
+        }
+        let fit =
+            crate::FITActivity::from_reader(&mut bytes.as_slice(), "bad_crc.fit").unwrap_err();
+        let err = ProcessingError::from(fit);
+        assert!(matches!(err, ProcessingError::Fit(_)));
+        assert!(err.source().is_some());
+
+        let gpx = crate::GPXActivity::from_reader(&mut "<gpx".as_bytes(), "short.gpx").unwrap_err();
+        assert!(matches!(
+            ProcessingError::from(gpx),
+            ProcessingError::Gpx(_)
+        ));
+
+        let invalid: Box<dyn Error> = "Unexpected end of file".into();
+        let err = ProcessingError::from(invalid);
+        assert!(matches!(err, ProcessingError::Invalid(_)));
+        assert!(err.source().is_none());
+        assert_eq!(
+            err.to_string(),
+            "Unable to process the file. Unexpected end of file"
+        );
+
+        assert_eq!(
+            ProcessingError::TimedOut(Duration::from_secs(30)).to_string(),
+            "Timed out after 30 seconds. The file was skipped."
+        );
+    }
+}
@@ -0,0 +1,354 @@
+//! Draws the elevation of an activity against the distance as a standalone SVG image, with a grid and labelled axes.
+//! The image needs no scripts, stylesheets or fonts of its own, so it can be opened in a browser or put straight into
+//! an HTML or Markdown report.
+//!
+//! The distances and elevations are in the unit system of the run, or in kilometers and meters if none is set.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::analysis::splits::SplitPoint;
+use crate::units::{unit_system, Measure, UnitSystem};
+
+/// The width of the image in pixels.
+const WIDTH: f64 = 800.0;
+
+/// The height of the image in pixels.
+const HEIGHT: f64 = 250.0;
+
+/// The space left of the plot for the elevation labels, in pixels.
+const MARGIN_LEFT: f64 = 56.0;
+
+/// The space right of the plot, in pixels.
+const MARGIN_RIGHT: f64 = 16.0;
+
+/// The space above the plot for the title, in pixels.
+const MARGIN_TOP: f64 = 28.0;
+
+/// The space below the plot for the distance labels, in pixels.
+const MARGIN_BOTTOM: f64 = 40.0;
+
+/// The most points drawn. Longer profiles are thinned out evenly, which can't be seen at this size.
+const MAX_POINTS: usize = 1_000;
+
+/// The least the elevation axis covers, in meters or feet, so flat activities don't look hilly.
+const MIN_ELEVATION_RANGE: f64 = 20.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A point of the elevation profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProfilePoint {
+    /// The distance from the start of the profile in meters.
+    pub distance_m: f64,
+
+    /// The elevation in meters.
+    pub elevation_m: f64,
+}
+
+/// The points of the profile, from the points of an activity that have an elevation.
+///
+/// # Arguments
+///
+/// `points: &[SplitPoint]` -- The points of the activity, in order of distance.
+///
+/// # Returns
+///
+/// `Vec<ProfilePoint>` -- The points with an elevation, with the distance counted from the first of them.
+#[must_use]
+pub fn profile_points(points: &[SplitPoint]) -> Vec<ProfilePoint> {
+    let mut start = None;
+    points
+        .iter()
+        .filter_map(|point| {
+            let elevation_m = point.altitude?;
+            let start = *start.get_or_insert(point.distance);
+            Some(ProfilePoint {
+                distance_m: point.distance - start,
+                elevation_m,
+            })
+        })
+        .collect()
+}
+
+/// Draws the profile as an SVG image.
+///
+/// # Arguments
+///
+/// - `points: &[ProfilePoint]` -- The points of the profile, in order of distance.
+/// - `title: &str` -- The title shown above the profile, e.g. the file name.
+///
+/// # Returns
+///
+/// `Option<String>` -- The SVG image, or `None` if there are fewer than two points or they cover no distance.
+#[must_use]
+pub fn profile_svg(points: &[ProfilePoint], title: &str) -> Option<String> {
+    let units = unit_system().unwrap_or(UnitSystem::Metric);
+    let step = points.len().div_ceil(MAX_POINTS).max(1);
+    let mut drawn: Vec<(f64, f64)> = points
+        .iter()
+        .step_by(step)
+        .map(|point| {
+            (
+                Measure::Distance.convert(point.distance_m, Some(units)),
+                Measure::Length.convert(point.elevation_m, Some(units)),
+            )
+        })
+        .collect();
+    if let (Some(last), Some(drawn_last)) = (points.last(), drawn.last()) {
+        let last = (
+            Measure::Distance.convert(last.distance_m, Some(units)),
+            Measure::Length.convert(last.elevation_m, Some(units)),
+        );
+        if *drawn_last != last {
+            drawn.push(last);
+        }
+    }
+
+    let distance = drawn.last()?.0;
+    if drawn.len() < 2 || distance <= 0.0 {
+        return None;
+    }
+    let (low, high) = drawn
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(low, high), (_, elevation)| {
+            (low.min(*elevation), high.max(*elevation))
+        });
+    let padding = ((MIN_ELEVATION_RANGE - (high - low)) / 2.0).max(0.0);
+    let elevation_step = nice_step(high - low + 2.0 * padding, 5);
+    let bottom = ((low - padding) / elevation_step).floor() * elevation_step;
+    let top = ((high + padding) / elevation_step).ceil() * elevation_step;
+    let distance_step = nice_step(distance, 10);
+
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let x = |distance: f64| MARGIN_LEFT + distance / drawn.last().map_or(1.0, |p| p.0) * plot_width;
+    let y = |elevation: f64| MARGIN_TOP + (top - elevation) / (top - bottom) * plot_height;
+    let base = MARGIN_TOP + plot_height;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="11" fill="#444">"##
+    );
+    let title = escape(title);
+    let _ = writeln!(svg, "<title>{title}</title>");
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{WIDTH}" height="{HEIGHT}" fill="white"/>"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{MARGIN_LEFT}" y="18" font-size="13">{title}</text>"#
+    );
+
+    // The grid, with the elevations on the left and the distances below
+    let elevation_decimals = decimals(elevation_step);
+    let mut elevation = bottom;
+    while elevation <= top + elevation_step / 2.0 {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{MARGIN_LEFT}" y1="{y:.1}" x2="{:.1}" y2="{y:.1}" stroke="#ddd"/><text x="{:.1}" y="{:.1}" text-anchor="end">{elevation:.elevation_decimals$}</text>"##,
+            WIDTH - MARGIN_RIGHT,
+            MARGIN_LEFT - 6.0,
+            y(elevation) + 4.0,
+            y = y(elevation),
+        );
+        elevation += elevation_step;
+    }
+    let distance_decimals = decimals(distance_step);
+    let mut tick = 0.0;
+    while tick <= distance + distance_step / 1_000.0 {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{x:.1}" y1="{MARGIN_TOP}" x2="{x:.1}" y2="{base:.1}" stroke="#ddd"/><text x="{x:.1}" y="{:.1}" text-anchor="middle">{tick:.distance_decimals$}</text>"##,
+            base + 15.0,
+            x = x(tick),
+        );
+        tick += distance_step;
+    }
+
+    // The profile, filled down to the bottom of the plot
+    let line: Vec<String> = drawn
+        .iter()
+        .map(|(distance, elevation)| format!("{:.1},{:.1}", x(*distance), y(*elevation)))
+        .collect();
+    let _ = writeln!(
+        svg,
+        r##"<polygon points="{:.1},{base:.1} {} {:.1},{base:.1}" fill="#c6dbef"/>"##,
+        x(0.0),
+        line.join(" "),
+        x(distance),
+    );
+    let _ = writeln!(
+        svg,
+        r##"<polyline points="{}" fill="none" stroke="#3182bd" stroke-width="1.5"/>"##,
+        line.join(" ")
+    );
+
+    // The names of the axes
+    let _ = writeln!(
+        svg,
+        r#"<text x="{:.1}" y="{:.1}" text-anchor="middle">Distance ({})</text>"#,
+        MARGIN_LEFT + plot_width / 2.0,
+        HEIGHT - 6.0,
+        Measure::Distance.unit(Some(units)),
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="12" y="{:.1}" text-anchor="middle" transform="rotate(-90 12 {:.1})">Elevation ({})</text>"#,
+        MARGIN_TOP + plot_height / 2.0,
+        MARGIN_TOP + plot_height / 2.0,
+        Measure::Length.unit(Some(units)),
+    );
+    svg.push_str("</svg>\n");
+
+    Some(svg)
+}
+
+/// Writes the profile as an SVG image to any writer.
+///
+/// # Arguments
+///
+/// - `points: &[ProfilePoint]` -- The points of the profile, in order of distance.
+/// - `title: &str` -- The title shown above the profile.
+/// - `writer: W` -- Where the SVG is written.
+///
+/// # Errors
+///
+/// There may be nothing to draw. Writing may fail.
+pub fn write_profile_svg<W: Write>(
+    points: &[ProfilePoint],
+    title: &str,
+    mut writer: W,
+) -> Result<(), Box<dyn Error>> {
+    let svg = profile_svg(points, title).ok_or("There is no elevation profile to draw.")?;
+    writer.write_all(svg.as_bytes())?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the profile as an SVG image to a file.
+///
+/// # Arguments
+///
+/// - `points: &[ProfilePoint]` -- The points of the profile, in order of distance.
+/// - `title: &str` -- The title shown above the profile.
+/// - `filename: &str` -- The file to write, usually named after the activity file with `.profile.svg` added.
+///
+/// # Errors
+///
+/// There may be nothing to draw. Creating or writing the file may fail.
+#[cfg(feature = "fs")]
+pub fn export_profile_svg(
+    points: &[ProfilePoint],
+    title: &str,
+    filename: &str,
+) -> Result<(), Box<dyn Error>> {
+    log::trace!("profile_svg::export_profile_svg() -- Writing {filename}");
+    crate::output_naming::write_output(std::path::Path::new(filename), |file| {
+        write_profile_svg(points, title, std::io::BufWriter::new(file))
+    })
+}
+
+/// The distance between the grid lines: 1, 2 or 5 times a power of ten, giving at most about `max_lines` lines.
+fn nice_step(range: f64, max_lines: u32) -> f64 {
+    let raw = (range / f64::from(max_lines)).max(f64::EPSILON);
+    let magnitude = 10_f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// The number of decimals needed to tell the grid lines apart.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn decimals(step: f64) -> usize {
+    (-step.log10().floor()).max(0.0) as usize
+}
+
+/// The text with the characters that have a meaning in XML replaced.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+/// Tests for the profile_svg module
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    /// A point `meters` into the activity at the elevation given.
+    fn point(meters: f64, altitude: Option<f64>) -> SplitPoint {
+        SplitPoint {
+            time: Local.timestamp_opt(1_600_000_000, 0).unwrap(),
+            distance: meters,
+            heart_rate: None,
+            altitude,
+        }
+    }
+
+    #[test]
+    /// Test picking out the points with an elevation, with the distance from the first of them
+    fn test_profile_points() {
+        let profile = profile_points(&[
+            point(0.0, None),
+            point(100.0, Some(12.0)),
+            point(200.0, None),
+            point(300.0, Some(18.5)),
+        ]);
+        assert_eq!(
+            profile,
+            [
+                ProfilePoint {
+                    distance_m: 0.0,
+                    elevation_m: 12.0
+                },
+                ProfilePoint {
+                    distance_m: 200.0,
+                    elevation_m: 18.5
+                }
+            ]
+        );
+    }
+
+    #[test]
+    /// Test drawing a profile, and that there is nothing to draw without a distance
+    fn test_profile_svg() {
+        let points: Vec<ProfilePoint> = (0..=5_000)
+            .map(|i| ProfilePoint {
+                distance_m: f64::from(i) * 2.0,
+                elevation_m: 100.0 + (f64::from(i) / 500.0).sin() * 40.0,
+            })
+            .collect();
+        let svg = profile_svg(&points, "Hills & <dales>").unwrap();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<title>Hills &amp; &lt;dales&gt;</title>"));
+        assert!(svg.contains(">10</text>"));
+        assert!(svg.contains("Elevation (m)"));
+
+        // Thinned out, with the last point kept
+        let polyline = svg
+            .lines()
+            .find(|line| line.starts_with("<polyline"))
+            .unwrap();
+        assert!(polyline.split(' ').count() <= MAX_POINTS + 8);
+        assert!(polyline.contains(&format!("{:.1},", WIDTH - MARGIN_RIGHT)));
+
+        let flat = [ProfilePoint::default(), ProfilePoint::default()];
+        assert!(profile_svg(&flat, "").is_none());
+        assert!(write_profile_svg(&flat[..1], "", Vec::new()).is_err());
+
+        assert_eq!(nice_step(10.0, 10), 1.0);
+        assert_eq!(nice_step(73.0, 5), 20.0);
+        assert_eq!(nice_step(0.42, 10), 0.05);
+        assert_eq!(decimals(0.05), 2);
+        assert_eq!(decimals(20.0), 0);
+    }
+}
@@ -0,0 +1,533 @@
+//! A small query language for finding activities by their summaries, e.g. `sport=cycling AND distance>100km AND
+//! year=2023`.
+//!
+//! A query is one or more conditions joined by `AND` and `OR`, where `AND` binds tighter than `OR`. Each condition is
+//! a field, an operator and a value:
+//!
+//! | Field         | Value                                                | Example              |
+//! |:--------------|:-----------------------------------------------------|:---------------------|
+//! | `sport`       | The sport. `Biking` and `cycling` are the same sport | `sport=cycling`      |
+//! | `distance`    | A number with `m`, `km` or `mi`. Kilometers if none  | `distance>100km`     |
+//! | `duration`    | A number with `s`, `min` or `h`. Minutes if none     | `duration>=1.5h`     |
+//! | `year`        | The year the activity started                        | `year=2023`          |
+//! | `month`       | The month the activity started, 1-12                 | `month<=3`           |
+//! | `date`        | The date the activity started, as `YYYY-MM-DD`       | `date>=2023-06-01`   |
+//! | `device`      | The device that recorded the activity                | `device~fenix`       |
+//! | `environment` | `outdoor`, `indoor` (e.g. a treadmill) or `virtual`  | `environment=indoor` |
+//! | `file`        | The file name                                        | `file~commute`       |
+//! | `tag`         | One of the tags of the activity                      | `tag=race`           |
+//!
+//! The operators are `=`, `!=`, `<`, `<=`, `>`, `>=` and `~` (contains). Text is compared without regard to case, and
+//! only `=`, `!=` and `~` can be used with text. Activities where the value isn't known never match the condition.
+//! `tag!=race` matches the activities that don't have the tag, including those without tags.
+
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::tags::split_tags;
+use crate::{normalized_sport, FITEnvironment, FITSession, GPXMetadata, TCXActivity};
+
+/// Meters per mile.
+const METERS_PER_MILE: f64 = 1_609.344;
+
+/// The units a distance can be given in, and the meters in each. Kilometers if none.
+pub(crate) const DISTANCE_UNITS: [(&str, f64); 4] = [
+    ("", 1_000.0),
+    ("km", 1_000.0),
+    ("m", 1.0),
+    ("mi", METERS_PER_MILE),
+];
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The values of an activity that a query can test, taken from the summary of a FIT, GPX or TCX file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryFields {
+    /// The name of the file.
+    pub filename: Option<String>,
+
+    /// The sport, as found in the file.
+    pub sport: Option<String>,
+
+    /// The distance in meters.
+    pub distance_m: Option<f64>,
+
+    /// The duration in seconds.
+    pub duration_sec: Option<f64>,
+
+    /// When the activity started.
+    pub start_time: Option<DateTime<Local>>,
+
+    /// The device that recorded the activity.
+    pub device: Option<String>,
+
+    /// Where the activity took place.
+    pub environment: Option<FITEnvironment>,
+
+    /// The total ascent in meters.
+    pub ascent_m: Option<f64>,
+
+    /// The calories burned.
+    pub calories: Option<f64>,
+
+    /// The Training Stress Score, if the device worked it out. Only found in FIT files.
+    pub training_stress_score: Option<f64>,
+
+    /// The tags of the activity.
+    pub tags: Vec<String>,
+}
+
+impl QueryFields {
+    /// The values of a FIT session.
+    #[must_use]
+    pub fn from_fit(session: &FITSession) -> Self {
+        Self {
+            filename: session.filename.clone(),
+            sport: session.activity_type.clone(),
+            distance_m: session.distance.map(|distance| distance.value),
+            duration_sec: session.duration.map(|duration| duration.0.as_secs_f64()),
+            start_time: session.start_time.or(session.time_created),
+            device: session.product.clone(),
+            environment: Some(session.environment),
+            ascent_m: session.ascent.map(|ascent| f64::from(ascent.value)),
+            calories: session.calories.map(f64::from),
+            training_stress_score: session.training_stress_score,
+            tags: session.tags.as_deref().map(split_tags).unwrap_or_default(),
+        }
+    }
+
+    /// The values of a GPX file. The distance is the one worked out from the track waypoints.
+    #[must_use]
+    pub fn from_gpx(metadata: &GPXMetadata) -> Self {
+        Self {
+            filename: metadata
+                .filename
+                .as_ref()
+                .map(|filename| filename.to_string_lossy().to_string()),
+            sport: metadata.activity.clone(),
+            distance_m: metadata.distance_m,
+            duration_sec: metadata.duration.map(|duration| duration.0.as_secs_f64()),
+            start_time: metadata.time,
+            device: metadata.creator.clone(),
+            environment: Some(metadata.environment),
+            ascent_m: metadata.ascent_m,
+            calories: None,
+            training_stress_score: None,
+            tags: metadata.tags.as_deref().map(split_tags).unwrap_or_default(),
+        }
+    }
+
+    /// The values of a TCX activity.
+    #[must_use]
+    pub fn from_tcx(activity: &TCXActivity) -> Self {
+        Self {
+            filename: activity.filename.clone(),
+            sport: activity.sport.clone(),
+            distance_m: activity.distance_meters,
+            duration_sec: activity.duration.map(|duration| duration.0.as_secs_f64()),
+            start_time: activity
+                .start_time
+                .as_deref()
+                .and_then(|st| DateTime::parse_from_rfc3339(st).ok())
+                .map(|st| st.with_timezone(&Local)),
+            device: activity.product.clone(),
+            environment: Some(activity.environment),
+            ascent_m: activity.ascent_meters,
+            calories: activity.calories.map(f64::from),
+            training_stress_score: None,
+            tags: activity.tags.as_deref().map(split_tags).unwrap_or_default(),
+        }
+    }
+}
+
+/// The fields that can be queried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Sport,
+    Distance,
+    Duration,
+    Year,
+    Month,
+    Date,
+    Device,
+    Environment,
+    File,
+    Tag,
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sport" => Ok(Self::Sport),
+            "distance" => Ok(Self::Distance),
+            "duration" => Ok(Self::Duration),
+            "year" => Ok(Self::Year),
+            "month" => Ok(Self::Month),
+            "date" => Ok(Self::Date),
+            "device" => Ok(Self::Device),
+            "environment" => Ok(Self::Environment),
+            "file" => Ok(Self::File),
+            "tag" => Ok(Self::Tag),
+            _ => Err(format!(
+                "Unknown field {s}. Use sport, distance, duration, year, month, date, device, environment, file or tag."
+            )),
+        }
+    }
+}
+
+impl Field {
+    /// Whether the field holds text rather than a number.
+    const fn is_text(self) -> bool {
+        matches!(
+            self,
+            Self::Sport | Self::Device | Self::Environment | Self::File | Self::Tag
+        )
+    }
+
+    /// Parses the value given for the field in a condition.
+    fn parse_value(self, value: &str) -> Result<Value, String> {
+        let invalid = || format!("Invalid value {value} for {self}.");
+        match self {
+            Self::Sport => Ok(Value::Text(normalized_sport(value))),
+            Self::Device | Self::Environment | Self::File | Self::Tag => {
+                Ok(Value::Text(value.to_lowercase()))
+            }
+            Self::Distance => with_unit(value, &DISTANCE_UNITS)
+                .map(Value::Number)
+                .ok_or_else(invalid),
+            Self::Duration => with_unit(
+                value,
+                &[
+                    ("", 60.0),
+                    ("min", 60.0),
+                    ("s", 1.0),
+                    ("sec", 1.0),
+                    ("h", 3_600.0),
+                ],
+            )
+            .map(Value::Number)
+            .ok_or_else(invalid),
+            Self::Year | Self::Month => value
+                .parse::<i32>()
+                .map(|number| Value::Number(f64::from(number)))
+                .map_err(|_| invalid()),
+            Self::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| Value::Number(f64::from(date.num_days_from_ce())))
+                .map_err(|_| invalid()),
+        }
+    }
+
+    /// The value of the field for an activity, in the same form as `parse_value()` gives, or `None` if it isn't known.
+    fn value(self, fields: &QueryFields) -> Option<Value> {
+        let start = fields.start_time;
+        match self {
+            Self::Sport => fields
+                .sport
+                .as_deref()
+                .map(|s| Value::Text(normalized_sport(s))),
+            Self::Device => fields
+                .device
+                .as_deref()
+                .map(|d| Value::Text(d.to_lowercase())),
+            Self::Environment => fields
+                .environment
+                .map(|e| Value::Text(e.to_string().to_lowercase())),
+            Self::File => fields
+                .filename
+                .as_deref()
+                .map(|f| Value::Text(f.to_lowercase())),
+            Self::Tag => Some(Value::List(
+                fields.tags.iter().map(|tag| tag.to_lowercase()).collect(),
+            )),
+            Self::Distance => fields.distance_m.map(Value::Number),
+            Self::Duration => fields.duration_sec.map(Value::Number),
+            Self::Year => start.map(|st| Value::Number(f64::from(st.year()))),
+            Self::Month => start.map(|st| Value::Number(f64::from(st.month()))),
+            Self::Date => start.map(|st| Value::Number(f64::from(st.num_days_from_ce()))),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Sport => "sport",
+            Self::Distance => "distance",
+            Self::Duration => "duration",
+            Self::Year => "year",
+            Self::Month => "month",
+            Self::Date => "date",
+            Self::Device => "device",
+            Self::Environment => "environment",
+            Self::File => "file",
+            Self::Tag => "tag",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Parses a number followed by one of the units, e.g. `100km`, into the base unit.
+pub(crate) fn with_unit(value: &str, units: &[(&str, f64)]) -> Option<f64> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let unit = unit.trim().to_lowercase();
+
+    units
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, factor)| number * factor)
+}
+
+/// The value a field is compared with.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+    /// Several values, of which one must match, e.g. the tags.
+    List(Vec<String>),
+}
+
+/// How the value of the field is compared with the value in the condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Contains,
+}
+
+impl Operator {
+    /// Finds the operator in a condition.
+    ///
+    /// # Returns
+    ///
+    /// `Option<(Self, usize, usize)>` -- The operator along with where it starts and ends, or `None` if there is none.
+    fn find(condition: &str) -> Option<(Self, usize, usize)> {
+        let start = condition.find(['=', '!', '<', '>', '~'])?;
+        let rest = &condition[start..];
+        let (operator, len) = if rest.starts_with("!=") {
+            (Self::NotEqual, 2)
+        } else if rest.starts_with("<=") {
+            (Self::LessOrEqual, 2)
+        } else if rest.starts_with(">=") {
+            (Self::GreaterOrEqual, 2)
+        } else {
+            match rest.chars().next()? {
+                '=' => (Self::Equal, 1),
+                '<' => (Self::Less, 1),
+                '>' => (Self::Greater, 1),
+                '~' => (Self::Contains, 1),
+                _ => return None,
+            }
+        };
+        Some((operator, start, start + len))
+    }
+}
+
+/// A single condition, e.g. `distance>100km`.
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    field: Field,
+    operator: Operator,
+    value: Value,
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (operator, start, end) =
+            Operator::find(s).ok_or_else(|| format!("No operator found in {s}."))?;
+        let field = s[..start].trim().parse::<Field>()?;
+        let value = s[end..].trim().trim_matches(|c| c == '"' || c == '\'');
+        if value.is_empty() {
+            return Err(format!("No value given for {field}."));
+        }
+
+        let allowed = if field.is_text() {
+            matches!(
+                operator,
+                Operator::Equal | Operator::NotEqual | Operator::Contains
+            )
+        } else {
+            operator != Operator::Contains
+        };
+        if !allowed {
+            return Err(if field.is_text() {
+                format!("Only =, != and ~ can be used with {field}.")
+            } else {
+                format!("~ can only be used with sport, device, environment, file and tag, not {field}.")
+            });
+        }
+
+        Ok(Self {
+            field,
+            operator,
+            value: field.parse_value(value)?,
+        })
+    }
+}
+
+impl Condition {
+    /// Whether the activity meets the condition. Activities where the value isn't known never do.
+    fn matches(&self, fields: &QueryFields) -> bool {
+        match (self.field.value(fields), &self.value) {
+            (Some(Value::Number(actual)), Value::Number(expected)) => match self.operator {
+                Operator::Equal => (actual - expected).abs() < f64::EPSILON,
+                Operator::NotEqual => (actual - expected).abs() >= f64::EPSILON,
+                Operator::Less => actual < *expected,
+                Operator::LessOrEqual => actual <= *expected,
+                Operator::Greater => actual > *expected,
+                Operator::GreaterOrEqual => actual >= *expected,
+                Operator::Contains => false,
+            },
+            (Some(Value::Text(actual)), Value::Text(expected)) => match self.operator {
+                Operator::Equal => actual == *expected,
+                Operator::NotEqual => actual != *expected,
+                Operator::Contains => actual.contains(expected.as_str()),
+                _ => false,
+            },
+            (Some(Value::List(actual)), Value::Text(expected)) => match self.operator {
+                Operator::Equal => actual.contains(expected),
+                Operator::NotEqual => !actual.contains(expected),
+                Operator::Contains => actual.iter().any(|a| a.contains(expected.as_str())),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A query for finding activities, e.g. `sport=cycling AND distance>100km AND year=2023`. See the module
+/// documentation for the syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    /// The groups of conditions joined by `OR`. All the conditions in a group must match.
+    any_of: Vec<Vec<Condition>>,
+}
+
+impl Query {
+    /// Whether the activity matches the query.
+    ///
+    /// # Arguments
+    ///
+    /// `fields: &QueryFields` -- The values of the activity.
+    ///
+    /// # Returns
+    ///
+    /// `bool` -- `true` if all the conditions on either side of an `OR` match.
+    #[must_use]
+    pub fn matches(&self, fields: &QueryFields) -> bool {
+        self.any_of
+            .iter()
+            .any(|all_of| all_of.iter().all(|condition| condition.matches(fields)))
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Gather the words between each AND and OR, so values may contain spaces, e.g. `device=Edge 530`
+        let mut any_of = vec![Vec::new()];
+        let mut words: Vec<&str> = Vec::new();
+        for word in s.split_whitespace().chain(["OR"]) {
+            let keyword = word.to_uppercase();
+            if keyword != "AND" && keyword != "OR" {
+                words.push(word);
+                continue;
+            }
+
+            if words.is_empty() {
+                return Err(format!(
+                    "Missing condition before or after {keyword} in {s}."
+                ));
+            }
+            if let Some(all_of) = any_of.last_mut() {
+                all_of.push(words.join(" ").parse::<Condition>()?);
+            }
+            words.clear();
+            if keyword == "OR" {
+                any_of.push(Vec::new());
+            }
+        }
+        any_of.pop(); // The OR added at the end
+
+        Ok(Self { any_of })
+    }
+}
+
+#[cfg(test)]
+/// Tests for the query module
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A ride of 120 km taking 4 hours in June 2023.
+    fn ride() -> QueryFields {
+        QueryFields {
+            filename: Some("2023-06-03 Commute.fit".to_string()),
+            sport: Some("Cycling".to_string()),
+            distance_m: Some(120_000.0),
+            duration_sec: Some(4.0 * 3_600.0),
+            start_time: Some(Local.with_ymd_and_hms(2023, 6, 3, 8, 0, 0).unwrap()),
+            device: Some("Edge 530".to_string()),
+            environment: Some(FITEnvironment::Outdoor),
+            tags: vec!["Race".to_string(), "brevet".to_string()],
+            ..QueryFields::default()
+        }
+    }
+
+    /// Whether the query matches the ride.
+    fn matches(query: &str) -> bool {
+        query.parse::<Query>().unwrap().matches(&ride())
+    }
+
+    #[test]
+    /// Test matching the conditions against an activity
+    fn test_matches() {
+        assert!(matches("sport=cycling AND distance>100km AND year=2023"));
+        assert!(matches("sport=biking and distance > 74.5mi"));
+        assert!(!matches("sport=running"));
+        assert!(matches("sport=running OR duration>=4h"));
+        assert!(!matches("duration<240"));
+        assert!(matches("duration<=240min AND month=6"));
+        assert!(matches("date>=2023-06-01 AND date<2023-07-01"));
+        assert!(matches("device=edge 530 AND file~commute"));
+        assert!(matches("device!=fenix"));
+        assert!(matches("environment=outdoor AND environment!=indoor"));
+        assert!(matches("tag=race AND tag=brevet AND tag!=commute"));
+        assert!(matches("tag~rev"));
+        assert!(!matches("tag=rac"));
+
+        // Values that aren't known never match
+        let unknown = QueryFields::default();
+        assert!(!"distance<1km".parse::<Query>().unwrap().matches(&unknown));
+        assert!(!"sport!=cycling".parse::<Query>().unwrap().matches(&unknown));
+        assert!("tag!=race".parse::<Query>().unwrap().matches(&unknown));
+    }
+
+    #[test]
+    /// Test that invalid queries are rejected with an error
+    fn test_parse_errors() {
+        assert!("".parse::<Query>().is_err());
+        assert!("sport=cycling AND".parse::<Query>().is_err());
+        assert!("speed>10".parse::<Query>().is_err());
+        assert!("distance>far".parse::<Query>().is_err());
+        assert!("distance>10furlongs".parse::<Query>().is_err());
+        assert!("sport>cycling".parse::<Query>().is_err());
+        assert!("year~20".parse::<Query>().is_err());
+        assert!("date=June".parse::<Query>().is_err());
+        assert!("sport cycling".parse::<Query>().is_err());
+        assert!("sport=".parse::<Query>().is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Selects the layout of the summary JSON files (`session.json`, `activity.json` and the TCX summary list). `v1` is
+//! the summary as it has always been written. `v2` is easier to work with in other tools:
+//!
+//! - A `schema` field says which layout the file uses.
+//! - A `units` block gives the unit of each field, e.g. `"distance": "m"` or `"heartrate_avg": "bpm"`.
+//! - Durations are plain seconds instead of `{"secs": 12.0}` objects.
+//! - The time in each heart rate zone is a list of zone objects instead of the flat `hr_zone_N_secs` fields.
+//!
+//! The fields are sorted by name in `v2`. The values are in the units recorded in the files, whatever `--units` says.
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::fit::hrzones::HR_ZONE_NAMES;
+use crate::{Rounded, Settings};
+
+/// The units of the fields whose names don't say.
+const FIELD_UNITS: [(&str, &str); 7] = [
+    ("ascent", "m"),
+    ("descent", "m"),
+    ("calories", "kcal"),
+    ("stance_time_avg", "ms"),
+    ("vertical_oscillation_avg", "mm"),
+    ("utc_offset", "s"),
+    ("time_in_zone", "s"),
+];
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The layout of the summary JSON files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SummarySchema {
+    /// The summary as it has always been written.
+    #[default]
+    V1,
+
+    /// The summary with its units, plain seconds and nested heart rate zones.
+    V2,
+}
+
+impl SummarySchema {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 2] = ["v1", "v2"];
+}
+
+impl FromStr for SummarySchema {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "v1" | "1" => Ok(Self::V1),
+            "v2" | "2" => Ok(Self::V2),
+            _ => Err(format!("Unknown schema {s}. Use v1 or v2.")),
+        }
+    }
+}
+
+impl fmt::Display for SummarySchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V1 => write!(f, "v1"),
+            Self::V2 => write!(f, "v2"),
+        }
+    }
+}
+
+impl Settings {
+    /// Sets the layout of the summary JSON files.
+    ///
+    /// # Arguments
+    ///
+    /// `schema: SummarySchema` -- The layout to write.
+    pub fn set_summary_schema(&mut self, schema: SummarySchema) {
+        self.summary_schema = schema;
+    }
+}
+
+/// The layout of the summary JSON files for this run.
+pub(crate) fn summary_schema() -> SummarySchema {
+    Settings::current().summary_schema
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes the summary as pretty JSON in the layout set with `Settings::set_summary_schema()`.
+///
+/// # Arguments
+///
+/// - `summary: &T` -- The summary, or list of summaries, to write.
+/// - `writer: W` -- Where the JSON is written.
+///
+/// # Errors
+///
+/// Serializing or writing the JSON can fail.
+pub(crate) fn write_summary_json<T, W>(summary: &T, writer: W) -> Result<(), Box<dyn Error>>
+where
+    T: Serialize + ?Sized,
+    W: Write,
+{
+    match summary_schema() {
+        SummarySchema::V1 => serde_json::to_writer_pretty(writer, &Rounded(summary))?,
+        SummarySchema::V2 => {
+            serde_json::to_writer_pretty(
+                writer,
+                &summary_v2(serde_json::to_value(Rounded(summary))?),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the summary in the `v2` layout.
+///
+/// # Arguments
+///
+/// `summary: Value` -- The summary as written in `v1`.
+///
+/// # Returns
+///
+/// `Value` -- The summary with the `schema` and `units` fields added at the top, if it is an object.
+pub(crate) fn summary_v2(summary: Value) -> Value {
+    let mut units = BTreeMap::new();
+    let summary = restructure(summary, &mut units);
+
+    match summary {
+        Value::Object(mut fields) => {
+            fields.insert("schema".to_string(), json!(SummarySchema::V2.to_string()));
+            fields.insert("units".to_string(), json!(units));
+            Value::Object(fields)
+        }
+        other => other,
+    }
+}
+
+/// Flattens the durations and nests the heart rate zones in the value, noting the units of the fields found.
+fn restructure(value: Value, units: &mut BTreeMap<String, &'static str>) -> Value {
+    match value {
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| restructure(value, units))
+                .collect(),
+        ),
+        Value::Object(fields) => {
+            let mut restructured = Map::new();
+            for (name, value) in fields {
+                let value = if let Some(secs) = duration_secs(&value) {
+                    units.insert(name.clone(), "s");
+                    secs
+                } else if name == "time_in_hr_zones" {
+                    restructured.insert("hr_zones".to_string(), hr_zones(&value, units));
+                    continue;
+                } else {
+                    if let Some(unit) = field_unit(&name) {
+                        units.insert(name.clone(), unit);
+                    }
+                    restructure(value, units)
+                };
+                restructured.insert(name, value);
+            }
+            Value::Object(restructured)
+        }
+        other => other,
+    }
+}
+
+/// The seconds of a `Duration`, which is written as `{"secs": 12.0}`.
+fn duration_secs(value: &Value) -> Option<Value> {
+    match value.as_object()? {
+        fields if fields.len() == 1 => fields.get("secs").map(shortest_f32),
+        _ => None,
+    }
+}
+
+/// The seconds written as an `f32`, without the digits added when it was widened to an `f64` on the way.
+#[allow(clippy::cast_possible_truncation)]
+fn shortest_f32(secs: &Value) -> Value {
+    secs.as_f64()
+        .and_then(|secs| (secs as f32).to_string().parse::<f64>().ok())
+        .map_or_else(|| secs.clone(), |secs| json!(secs))
+}
+
+/// The time in each heart rate zone as a list of zone objects, from the flat `hr_zone_N_secs` fields.
+fn hr_zones(value: &Value, units: &mut BTreeMap<String, &'static str>) -> Value {
+    let zones: Vec<Value> = HR_ZONE_NAMES
+        .iter()
+        .enumerate()
+        .map(|(zone, name)| {
+            let time = value.get(format!("hr_zone_{zone}_secs")).map(shortest_f32);
+            json!({
+                "zone": zone,
+                "name": name,
+                "time_in_zone": time.unwrap_or(Value::Null),
+            })
+        })
+        .collect();
+    if let Some(unit) = field_unit("time_in_zone") {
+        units.insert("time_in_zone".to_string(), unit);
+    }
+
+    Value::Array(zones)
+}
+
+/// The unit of the field, from its name.
+fn field_unit(name: &str) -> Option<&'static str> {
+    if let Some((_, unit)) = FIELD_UNITS.iter().find(|(field, _)| *field == name) {
+        return Some(unit);
+    }
+
+    let unit = if name.ends_with("_pct") {
+        "%"
+    } else if name.ends_with("_sec") || name.ends_with("_secs") {
+        "s"
+    } else if name.ends_with("_c") {
+        "°C"
+    } else if name.ends_with("_ms") || name.contains("speed") {
+        "m/s"
+    } else if name.ends_with("_m")
+        || name.ends_with("_meters")
+        || name.ends_with("distance")
+        || name.contains("altitude")
+    {
+        "m"
+    } else if name.ends_with("_lat") || name.ends_with("_lon") {
+        "deg"
+    } else if name.contains("heartrate") || name.contains("heart_rate") {
+        "bpm"
+    } else if name.contains("cadence") {
+        "rpm"
+    } else if name.starts_with("power") {
+        "W"
+    } else {
+        return None;
+    };
+
+    Some(unit)
+}
+
+#[cfg(test)]
+/// Tests for the schema module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the units of the fields
+    fn test_field_unit() {
+        assert_eq!(field_unit("distance"), Some("m"));
+        assert_eq!(field_unit("gps_distance_meters"), Some("m"));
+        assert_eq!(field_unit("distance_discrepancy_pct"), Some("%"));
+        assert_eq!(field_unit("temperature_avg_c"), Some("°C"));
+        assert_eq!(field_unit("speed_first_half_ms"), Some("m/s"));
+        assert_eq!(field_unit("average_heart_rate"), Some("bpm"));
+        assert_eq!(field_unit("calories"), Some("kcal"));
+        assert_eq!(field_unit("filename"), None);
+        assert_eq!("V2".parse(), Ok(SummarySchema::V2));
+        assert!("v3".parse::<SummarySchema>().is_err());
+    }
+
+    #[test]
+    /// Test rewriting a summary in the v2 layout
+    fn test_summary_v2() {
+        let summary = summary_v2(json!({
+            "filename": "rowing.fit",
+            "distance": 1_000.0,
+            "heartrate_avg": 127,
+            "duration": { "secs": f64::from(1_383.442_f32) },
+            "time_in_hr_zones": { "hr_zone_0_secs": 1.0, "hr_zone_4_secs": 5.0 },
+        }));
+
+        assert_eq!(summary["schema"], "v2");
+        assert_eq!(summary["duration"], 1_383.442);
+        assert_eq!(summary["units"]["duration"], "s");
+        assert_eq!(summary["units"]["distance"], "m");
+        assert_eq!(summary["units"]["heartrate_avg"], "bpm");
+        assert_eq!(summary["units"]["time_in_zone"], "s");
+        assert!(summary["units"].get("filename").is_none());
+        assert!(summary.get("time_in_hr_zones").is_none());
+        assert_eq!(summary["hr_zones"][4]["name"], "Speed/Power");
+        assert_eq!(summary["hr_zones"][4]["time_in_zone"], 5.0);
+        assert_eq!(summary["hr_zones"][1]["time_in_zone"], Value::Null);
+
+        // Lists get a single units block for all the summaries
+        let list = summary_v2(json!({ "activities": [{ "calories": 214 }, { "ascent": 3.0 }] }));
+        assert_eq!(list["units"], json!({ "ascent": "m", "calories": "kcal" }));
+    }
+}
@@ -0,0 +1,165 @@
+//! The settings of a run: the precision, units, placeholder and layout of the exports, the zones and FTP the analysis
+//! works with, how the FIT altitude and the GPS spikes are read, and how the GPX tracks and the detail files are laid
+//! out. The tools fill in a `Settings` from the command line and apply it before processing the files.
+//!
+//! The settings apply to the thread they are applied on, and are carried over to the threads the processing starts, so
+//! several runs with different settings can share a process, e.g. the tests running in parallel. Without any settings
+//! applied, the defaults are used.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::{
+    AltitudeSource, ColumnsVersion, CsvFormat, FloatPrecision, HrZoneLimits, LapMapping,
+    PaceZoneLimits, SummarySchema, UnitSystem,
+};
+#[cfg(feature = "fs")]
+use crate::{DateSource, OutputNaming};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The settings of a run. Start from the defaults and change them with the `set_*()` functions, e.g.
+/// `Settings::set_unit_system()`, then `apply()` them.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// The number of decimals kept in the exports. `None` means full precision.
+    pub(crate) float_precision: Option<FloatPrecision>,
+
+    /// The units of the console output and the exports. `None` means the units recorded in the files.
+    pub(crate) unit_system: Option<UnitSystem>,
+
+    /// The text used for all values that aren't known. `None` means each value uses its own default.
+    pub(crate) placeholder: Option<String>,
+
+    /// The layout of the summary JSON files.
+    pub(crate) summary_schema: SummarySchema,
+
+    /// The version of the columns of the CSV files.
+    pub(crate) columns_version: ColumnsVersion,
+
+    /// The delimiter and decimal separator of the CSV files.
+    pub(crate) csv_format: CsvFormat,
+
+    /// Whether the serial numbers are hashed in the output.
+    pub(crate) hash_serials: bool,
+
+    /// The Functional Threshold Power in watts. `None` means the threshold power recorded in each file.
+    pub(crate) ftp: Option<f64>,
+
+    /// The heart rate zones. `None` means the default zones, or the time in zone recorded by FIT devices.
+    pub(crate) hr_zones: Option<HrZoneLimits>,
+
+    /// The pace zones. `None` means no pace zones.
+    pub(crate) pace_zones: Option<PaceZoneLimits>,
+
+    /// The altitude field preferred when reading the FIT records.
+    pub(crate) altitude_source: AltitudeSource,
+
+    /// The speed in meters per second above which the GPS spikes are dropped. `None` means they are kept.
+    pub(crate) gps_cleaning: Option<f64>,
+
+    /// How the laps are laid out in GPX.
+    pub(crate) lap_mapping: LapMapping,
+
+    /// The colors of the GPX tracks, by lowercase sport, used instead of the defaults. `None` means no color.
+    pub(crate) track_colors: HashMap<String, Option<String>>,
+
+    /// How the detail files are named and where they go. `None` writes them next to the input file.
+    #[cfg(feature = "fs")]
+    pub(crate) output_naming: Option<OutputNaming>,
+
+    /// The time the date and time rename tokens are taken from.
+    #[cfg(feature = "fs")]
+    pub(crate) date_source: DateSource,
+
+    /// Whether to flush the files written to disk before renaming them.
+    #[cfg(feature = "fs")]
+    pub(crate) sync_outputs: bool,
+}
+
+thread_local! {
+    /// The settings applied on this thread, if any.
+    static CURRENT: RefCell<Option<Arc<Settings>>> = const { RefCell::new(None) };
+
+    /// The settings used when none have been applied.
+    static DEFAULT: Arc<Settings> = Arc::new(Settings::default());
+}
+
+/// Goes back to the settings used before when dropped. Returned by `Settings::apply()`.
+#[must_use = "the settings only apply until the guard is dropped"]
+#[allow(clippy::module_name_repetitions)]
+pub struct SettingsGuard {
+    /// The settings applied before.
+    previous: Option<Arc<Settings>>,
+
+    /// Keeps the guard on the thread the settings were applied on.
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for SettingsGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+impl Settings {
+    /// Uses the settings on this thread, and the threads the processing starts from it, until the guard returned is
+    /// dropped.
+    ///
+    /// # Returns
+    ///
+    /// `SettingsGuard` -- Goes back to the settings used before when dropped.
+    pub fn apply(&self) -> SettingsGuard {
+        Self::enter(Arc::new(self.clone()))
+    }
+
+    /// Uses the settings given on this thread until the guard returned is dropped.
+    pub(crate) fn enter(settings: Arc<Self>) -> SettingsGuard {
+        SettingsGuard {
+            previous: CURRENT.with(|current| current.borrow_mut().replace(settings)),
+            _thread: PhantomData,
+        }
+    }
+
+    /// The settings used on this thread, so they can be carried over to another thread with `enter()`.
+    pub(crate) fn current() -> Arc<Self> {
+        CURRENT
+            .with(|current| current.borrow().clone())
+            .unwrap_or_else(|| DEFAULT.with(Arc::clone))
+    }
+}
+
+#[cfg(test)]
+/// Tests for the settings module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that the settings only apply on the thread and until the guard is dropped
+    fn test_apply() {
+        let mut settings = Settings::default();
+        settings.set_placeholder(Some("n-a"));
+        settings.set_ftp(Some(250.0));
+
+        assert!(Settings::current().placeholder.is_none());
+        {
+            let _settings = settings.apply();
+            assert_eq!(Settings::current().placeholder.as_deref(), Some("n-a"));
+            assert_eq!(Settings::current().ftp, Some(250.0));
+
+            // Other threads keep their own settings
+            std::thread::spawn(|| assert!(Settings::current().placeholder.is_none()))
+                .join()
+                .unwrap();
+
+            // Nested settings apply until they are dropped
+            let inner = Settings::default().apply();
+            assert!(Settings::current().placeholder.is_none());
+            drop(inner);
+            assert_eq!(Settings::current().placeholder.as_deref(), Some("n-a"));
+        }
+        assert!(Settings::current().placeholder.is_none());
+    }
+}
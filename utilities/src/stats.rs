@@ -0,0 +1,494 @@
+//! Adds up the activities by week, month or year, with a column of totals for each sport, like the totals table of a
+//! classic training log. The weeks are ISO weeks, starting on a Monday, unless set to start on a Sunday.
+//!
+//! The periods without any activities between the first and the last activity are kept, with zero totals, so the
+//! rest weeks show up too. The totals can be written as CSV or JSON, or printed as a table.
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::csv_format::{csv_format, csv_writer};
+use crate::table::Table;
+use crate::units::{unit_system, Measure, UnitSystem};
+use crate::{normalized_sport, Duration, QueryFields};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The periods the activities are added up by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatsPeriod {
+    /// A week, starting on the day given by `WeekStart`.
+    #[default]
+    Week,
+
+    /// A calendar month.
+    Month,
+
+    /// A calendar year.
+    Year,
+}
+
+impl StatsPeriod {
+    /// The first day of the period the date is in.
+    fn start(self, date: NaiveDate, week_start: WeekStart) -> NaiveDate {
+        match self {
+            Self::Week => {
+                let days = match week_start {
+                    WeekStart::Monday => date.weekday().num_days_from_monday(),
+                    WeekStart::Sunday => date.weekday().num_days_from_sunday(),
+                };
+                date - Days::new(u64::from(days))
+            }
+            Self::Month => date.with_day(1).unwrap_or(date),
+            Self::Year => date.with_ordinal(1).unwrap_or(date),
+        }
+    }
+
+    /// The first day of the period after the one starting on the date.
+    fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Week => start + Days::new(7),
+            Self::Month => start + Months::new(1),
+            Self::Year => start + Months::new(12),
+        }
+    }
+
+    /// The name of the period starting on the date, e.g. `2023-W05`, `2023-06` or `2023`. Weeks starting on a Sunday
+    /// get the number of the ISO week starting the day after.
+    fn label(self, start: NaiveDate) -> String {
+        match self {
+            Self::Week => {
+                let week = start
+                    .checked_add_days(Days::new(1))
+                    .unwrap_or(start)
+                    .iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Self::Month => start.format("%Y-%m").to_string(),
+            Self::Year => start.format("%Y").to_string(),
+        }
+    }
+}
+
+impl FromStr for StatsPeriod {
+    type Err = String;
+
+    /// Reads the period as `week`, `month` or `year`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "year" => Ok(Self::Year),
+            _ => Err(format!("Unknown period {s}. Use week, month or year.")),
+        }
+    }
+}
+
+impl fmt::Display for StatsPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+        };
+        write!(f, "{name}")
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The day the weeks start on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WeekStart {
+    /// Monday, as in ISO weeks.
+    #[default]
+    Monday,
+
+    /// Sunday, as in the US.
+    Sunday,
+}
+
+impl FromStr for WeekStart {
+    type Err = String;
+
+    /// Reads the day as `monday` or `sunday`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "monday" => Ok(Self::Monday),
+            "sunday" => Ok(Self::Sunday),
+            _ => Err(format!("Unknown week start {s}. Use monday or sunday.")),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The totals of the activities of a sport, or of all sports, in a period.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct SportTotals {
+    /// The number of activities.
+    pub activities: usize,
+
+    /// The distance in meters. Activities without a distance add nothing.
+    pub distance_m: f64,
+
+    /// The duration in seconds. Activities without a duration add nothing.
+    pub duration_sec: f64,
+
+    /// The total ascent in meters. Activities without an ascent add nothing.
+    pub ascent_m: f64,
+
+    /// The calories burned. Activities without calories add nothing.
+    pub calories: f64,
+
+    /// The sum of the Training Stress Scores. Only FIT files from devices that work it out have one.
+    pub tss: f64,
+}
+
+impl SportTotals {
+    /// Adds an activity to the totals.
+    fn add(&mut self, activity: &QueryFields) {
+        self.activities += 1;
+        self.distance_m += activity.distance_m.unwrap_or_default();
+        self.duration_sec += activity.duration_sec.unwrap_or_default();
+        self.ascent_m += activity.ascent_m.unwrap_or_default();
+        self.calories += activity.calories.unwrap_or_default();
+        self.tss += activity.training_stress_score.unwrap_or_default();
+    }
+
+    /// Adds the totals of another sport.
+    fn combine(&mut self, other: &Self) {
+        self.activities += other.activities;
+        self.distance_m += other.distance_m;
+        self.duration_sec += other.duration_sec;
+        self.ascent_m += other.ascent_m;
+        self.calories += other.calories;
+        self.tss += other.tss;
+    }
+
+    /// The values of the CSV columns: the number of activities, the distance in kilometers, the duration, the ascent
+    /// in meters, the calories and the TSS.
+    fn columns(&self) -> [String; 6] {
+        [
+            self.activities.to_string(),
+            format!("{:.2}", self.distance_m / 1_000.0),
+            Duration::from_secs_f64(self.duration_sec).to_string(),
+            format!("{:.0}", self.ascent_m),
+            format!("{:.0}", self.calories),
+            format!("{:.1}", self.tss),
+        ]
+    }
+
+    /// The cells of a row of the console table, with the distance and the ascent in the unit system of the run.
+    fn cells(&self, units: UnitSystem) -> [String; 6] {
+        [
+            self.activities.to_string(),
+            format!(
+                "{:.1}",
+                Measure::Distance.convert(self.distance_m, Some(units))
+            ),
+            Duration::from_secs_f64(self.duration_sec).to_string(),
+            format!("{:.0}", Measure::Length.convert(self.ascent_m, Some(units))),
+            format!("{:.0}", self.calories),
+            format!("{:.0}", self.tss),
+        ]
+    }
+}
+
+/// The totals of a week, month or year.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Rollup {
+    /// The name of the period, e.g. `2023-W05`, `2023-06` or `2023`.
+    pub period: String,
+
+    /// The first day of the period.
+    pub start: NaiveDate,
+
+    /// The totals of each sport, by the normalized name of the sport, e.g. `cycling`.
+    pub sports: BTreeMap<String, SportTotals>,
+}
+
+impl Rollup {
+    /// The totals of all the sports.
+    #[must_use]
+    pub fn total(&self) -> SportTotals {
+        let mut total = SportTotals::default();
+        for sport in self.sports.values() {
+            total.combine(sport);
+        }
+        total
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Adds up the activities by period, by the day each activity started.
+///
+/// # Arguments
+///
+/// - `activities: &[QueryFields]` -- The summaries of the activities. Activities without a start time are left out.
+/// - `period: StatsPeriod` -- Whether to add up by week, month or year.
+/// - `week_start: WeekStart` -- The day the weeks start on.
+///
+/// # Returns
+///
+/// `Vec<Rollup>` -- The totals of each period from the first to the last activity, in order.
+#[must_use]
+pub fn rollups(
+    activities: &[QueryFields],
+    period: StatsPeriod,
+    week_start: WeekStart,
+) -> Vec<Rollup> {
+    let mut periods: BTreeMap<NaiveDate, BTreeMap<String, SportTotals>> = BTreeMap::new();
+    for activity in activities {
+        let Some(start_time) = activity.start_time else {
+            log::debug!(
+                "stats::rollups() -- {} has no start time. Skipped.",
+                activity.filename.as_deref().unwrap_or_default()
+            );
+            continue;
+        };
+        let sport = normalized_sport(activity.sport.as_deref().unwrap_or_default());
+        periods
+            .entry(period.start(start_time.date_naive(), week_start))
+            .or_default()
+            .entry(sport)
+            .or_default()
+            .add(activity);
+    }
+
+    let (Some(first), Some(last)) = (
+        periods.keys().next().copied(),
+        periods.keys().next_back().copied(),
+    ) else {
+        return Vec::new();
+    };
+    let mut rollups = Vec::new();
+    let mut start = first;
+    while start <= last {
+        rollups.push(Rollup {
+            period: period.label(start),
+            start,
+            sports: periods.remove(&start).unwrap_or_default(),
+        });
+        start = period.next(start);
+    }
+
+    rollups
+}
+
+/// Writes the totals as CSV: the period and its first day, then the number of activities, the distance in kilometers,
+/// the duration, the ascent in meters, the calories and the TSS for all sports, followed by the same for each sport,
+/// e.g. `cycling_distance_km`. The values are separated and the decimals written as set by
+/// `Settings::set_csv_format()`.
+///
+/// # Arguments
+///
+/// - `writer: W` -- Where the CSV is written.
+/// - `rollups: &[Rollup]` -- The totals of each period.
+///
+/// # Errors
+///
+/// Writing may fail.
+pub fn write_rollups_csv<W: Write>(writer: W, rollups: &[Rollup]) -> Result<(), Box<dyn Error>> {
+    let sports: BTreeSet<&String> = rollups
+        .iter()
+        .flat_map(|rollup| rollup.sports.keys())
+        .collect();
+
+    let format = csv_format();
+    let mut writer = csv_writer(writer);
+    let mut header = vec![String::from("period"), String::from("start")];
+    for name in std::iter::once("total").chain(sports.iter().map(|sport| sport.as_str())) {
+        header.push(format!("{name}_activities"));
+        header.push(format!("{name}_distance_km"));
+        header.push(format!("{name}_duration"));
+        header.push(format!("{name}_ascent_m"));
+        header.push(format!("{name}_calories"));
+        header.push(format!("{name}_tss"));
+    }
+    writer.write_record(&header)?;
+
+    for rollup in rollups {
+        let mut row = vec![rollup.period.clone(), rollup.start.to_string()];
+        row.extend(rollup.total().columns());
+        for sport in &sports {
+            row.extend(
+                rollup
+                    .sports
+                    .get(*sport)
+                    .copied()
+                    .unwrap_or_default()
+                    .columns(),
+            );
+        }
+        writer.write_record(
+            row.into_iter()
+                .map(|cell| format.localize(Cow::Owned(cell)).into_owned()),
+        )?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the totals as JSON: a list of the periods, each with its name, its first day and the totals of each sport.
+/// The distances and the ascents are in meters, and the durations in seconds.
+///
+/// # Arguments
+///
+/// - `writer: W` -- Where the JSON is written.
+/// - `rollups: &[Rollup]` -- The totals of each period.
+///
+/// # Errors
+///
+/// Writing may fail.
+pub fn write_rollups_json<W: Write>(writer: W, rollups: &[Rollup]) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(writer, rollups)?;
+    Ok(())
+}
+
+/// Prints the totals as tables to stdout: the totals of all sports for each period, followed by the totals of each
+/// sport over all the periods. The distances and the ascents are in the unit system of the run, or in kilometers and
+/// meters if none is set.
+///
+/// # Arguments
+///
+/// `rollups: &[Rollup]` -- The totals of each period.
+pub fn print_rollups(rollups: &[Rollup]) {
+    if rollups.is_empty() {
+        println!("\nNo activities to add up.");
+        return;
+    }
+
+    let units = unit_system().unwrap_or(UnitSystem::Metric);
+    let distance_header = format!("Distance ({})", Measure::Distance.unit(Some(units)));
+    let ascent_header = format!("Ascent ({})", Measure::Length.unit(Some(units)));
+    let header = [
+        "Activities",
+        &distance_header,
+        "Time",
+        &ascent_header,
+        "Calories",
+        "TSS",
+    ];
+
+    let mut periods = Table::new(&[&["Period"], header.as_slice()].concat());
+    let mut sports: BTreeMap<&str, SportTotals> = BTreeMap::new();
+    for rollup in rollups {
+        let mut row = vec![rollup.period.clone()];
+        row.extend(rollup.total().cells(units));
+        periods.add_row(row);
+        for (sport, totals) in &rollup.sports {
+            sports.entry(sport).or_default().combine(totals);
+        }
+    }
+
+    let mut total = SportTotals::default();
+    let mut by_sport = Table::new(&[&["Sport"], header.as_slice()].concat());
+    for (sport, totals) in &sports {
+        total.combine(totals);
+        let mut row = vec![(*sport).to_string()];
+        row.extend(totals.cells(units));
+        by_sport.add_row(row);
+    }
+    let mut row = vec![String::from("total")];
+    row.extend(total.cells(units));
+    by_sport.add_row(row);
+
+    println!("\nTotals by period:\n");
+    print!("{periods}");
+    println!("\nTotals by sport:\n");
+    print!("{by_sport}");
+}
+
+#[cfg(test)]
+/// Tests for the stats module
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    /// An activity of the sport and distance in kilometers, starting at noon on the day given.
+    fn activity(sport: &str, year: i32, month: u32, day: u32, km: f64) -> QueryFields {
+        QueryFields {
+            sport: Some(sport.to_string()),
+            distance_m: Some(km * 1_000.0),
+            duration_sec: Some(km * 180.0),
+            ascent_m: Some(km * 10.0),
+            calories: Some(km * 50.0),
+            start_time: Local.with_ymd_and_hms(year, month, day, 12, 0, 0).single(),
+            ..QueryFields::default()
+        }
+    }
+
+    #[test]
+    /// Test adding up the activities by week, with the weeks starting on a Monday or a Sunday
+    fn test_weekly_rollups() {
+        // Sunday 2023-01-01 is in ISO week 2022-W52, and the Monday after starts 2023-W01
+        let activities = [
+            activity("Running", 2023, 1, 1, 10.0),
+            activity("Cycling", 2023, 1, 2, 40.0),
+            activity("Biking", 2023, 1, 3, 20.0),
+            activity("Running", 2023, 1, 17, 5.0),
+        ];
+
+        let weeks = rollups(&activities, StatsPeriod::Week, WeekStart::Monday);
+        assert_eq!(
+            weeks
+                .iter()
+                .map(|week| week.period.as_str())
+                .collect::<Vec<_>>(),
+            ["2022-W52", "2023-W01", "2023-W02", "2023-W03"]
+        );
+        assert_eq!(weeks[1].sports["cycling"].activities, 2);
+        assert_eq!(weeks[1].total().distance_m, 60_000.0);
+        assert!(weeks[2].sports.is_empty());
+
+        let weeks = rollups(&activities, StatsPeriod::Week, WeekStart::Sunday);
+        assert_eq!(weeks[0].period, "2023-W01");
+        assert_eq!(weeks[0].start, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+        assert_eq!(weeks[0].total().activities, 3);
+    }
+
+    #[test]
+    /// Test adding up by month and year, and writing the CSV with a column per sport
+    fn test_rollups_csv() {
+        let activities = [
+            activity("Running", 2023, 1, 1, 10.0),
+            activity("Cycling", 2023, 3, 2, 40.0),
+        ];
+        let months = rollups(&activities, StatsPeriod::Month, WeekStart::Monday);
+        assert_eq!(months.len(), 3);
+        assert_eq!(months[1].period, "2023-02");
+        assert_eq!(
+            rollups(&activities, StatsPeriod::Year, WeekStart::Monday).len(),
+            1
+        );
+
+        let mut csv = Vec::new();
+        write_rollups_csv(&mut csv, &months).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("period,start,total_activities,total_distance_km,total_duration,total_ascent_m,total_calories,total_tss,cycling_activities,cycling_distance_km,cycling_duration,cycling_ascent_m,cycling_calories,cycling_tss,running_activities,running_distance_km,running_duration,running_ascent_m,running_calories,running_tss")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("2023-01,2023-01-01,1,10.00,00:30:00,100,500,0.0,0,0.00,00:00:00,0,0,0.0,1,10.00,00:30:00,100,500,0.0")
+        );
+
+        let mut json = Vec::new();
+        write_rollups_json(&mut json, &months).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json[2]["period"], "2023-03");
+        assert_eq!(json[2]["sports"]["cycling"]["ascent_m"], 400.0);
+        assert_eq!(json[0]["start"], "2023-01-01");
+
+        assert_eq!("Month".parse::<StatsPeriod>(), Ok(StatsPeriod::Month));
+        assert!("fortnight".parse::<StatsPeriod>().is_err());
+        assert_eq!("sunday".parse::<WeekStart>(), Ok(WeekStart::Sunday));
+    }
+}
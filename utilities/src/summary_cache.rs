@@ -0,0 +1,296 @@
+//! A cache of the per-file summaries, so repeated summary runs over large archives only need to parse the files that
+//! are new or have changed since the last run.
+//!
+//! The cache is a JSON file keyed by a hash of each file's contents, so renamed or moved files are still found.
+//! It is opened once for the whole run using `open_summary_cache()` and written back with `save_summary_cache()`.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::analysis::gps_outliers::gps_cleaning;
+use crate::analysis::hr_zones::custom_hr_zones;
+use crate::analysis::power::ftp;
+use crate::atomic_file::write_atomically;
+use crate::input::read_input;
+use crate::privacy::hash_serials;
+use crate::{FITParseStats, ProcessingResult, ProcessingStats};
+
+/// Bumped whenever the summaries change shape, so summaries cached by older versions are parsed again.
+const CACHE_VERSION: u32 = 7;
+
+/// Namespace for the content hashes, so they don't collide with the activity UUIDs.
+const CACHE_NAMESPACE: Uuid = Uuid::from_u128(0x6a1c_2f0e_53b4_4d8e_9c71_0b2e_7f45_d3a9);
+
+/// The cache used for the rest of the run, if any.
+static SUMMARY_CACHE: Mutex<Option<SummaryCache>> = Mutex::new(None);
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The summaries read from the cache file, along with where to write them back.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[allow(clippy::module_name_repetitions)]
+struct SummaryCache {
+    /// The version of the summaries in the cache.
+    version: u32,
+
+    /// The cached summaries, keyed by `CacheKey`.
+    entries: HashMap<String, CacheEntry>,
+
+    /// The file the cache was read from and is written back to.
+    #[serde(skip)]
+    path: PathBuf,
+
+    /// Whether anything was added since the cache was read.
+    #[serde(skip)]
+    changed: bool,
+}
+
+/// What is remembered about each file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CacheEntry {
+    /// The activity summary -- a `FITSession` along with its sessions, `GPXMetadata` or `TCXActivity`.
+    summary: serde_json::Value,
+
+    /// Counts of what was found in the file.
+    stats: ProcessingStats,
+
+    /// Anything noteworthy found in the file.
+    warnings: Vec<String>,
+
+    /// Counts of the messages and unknown fields, if they were requested when the file was parsed.
+    fit_stats: Option<FITParseStats>,
+}
+
+/// Identifies a file's contents, along with the settings that change how its summary is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CacheKey(String);
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Opens the summary cache for the rest of the run. If the file doesn't exist yet, an empty cache is created and
+/// written when `save_summary_cache()` is called. A cache written by an older version of the tools is started over.
+///
+/// # Arguments
+///
+/// `path: &str` -- The cache file.
+///
+/// # Errors
+///
+/// Reading or parsing an existing cache file may fail.
+pub fn open_summary_cache(path: &str) -> Result<(), Box<dyn Error>> {
+    let mut cache = SummaryCache::default();
+    if Path::new(path).exists() {
+        cache = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if cache.version != CACHE_VERSION {
+            log::info!("{path}: Summary cache is from another version and will be rebuilt.");
+            cache = SummaryCache::default();
+        }
+    }
+    cache.version = CACHE_VERSION;
+    cache.path = PathBuf::from(path);
+    log::debug!(
+        "summary_cache::open_summary_cache() -- {} cached summaries",
+        cache.entries.len()
+    );
+
+    if let Ok(mut current) = SUMMARY_CACHE.lock() {
+        *current = Some(cache);
+    }
+    Ok(())
+}
+
+/// Writes the summary cache back to its file, if anything was added during the run.
+///
+/// # Errors
+///
+/// Writing the cache file may fail.
+pub fn save_summary_cache() -> Result<(), Box<dyn Error>> {
+    let Ok(mut current) = SUMMARY_CACHE.lock() else {
+        return Ok(());
+    };
+
+    if let Some(cache) = current.as_mut().filter(|cache| cache.changed) {
+        write_atomically(&cache.path, |file| {
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer(&mut writer, cache)?;
+            Ok(writer.flush()?)
+        })?;
+        cache.changed = false;
+        log::info!("Summary cache written to: {}", cache.path.display());
+    }
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Works out the cache key for the file, if a cache is open.
+///
+/// # Errors
+///
+/// Reading the file may fail.
+pub(crate) fn cache_key(filename: &str) -> Result<Option<CacheKey>, Box<dyn Error>> {
+    if !SUMMARY_CACHE.lock().is_ok_and(|cache| cache.is_some()) {
+        return Ok(None);
+    }
+
+    let contents = Uuid::new_v5(&CACHE_NAMESPACE, &read_input(filename)?);
+    Ok(Some(CacheKey(format!(
+        "{}-{}",
+        contents.simple(),
+        settings_key()
+    ))))
+}
+
+/// The settings of the run that change the summaries, so summaries made with other settings aren't reused.
+fn settings_key() -> String {
+    let mut settings = if hash_serials() { "hashed" } else { "plain" }.to_string();
+    if let Some(zones) = custom_hr_zones() {
+        settings.push_str(&format!("-hr{zones}"));
+    }
+    if let Some(speed) = gps_cleaning() {
+        settings.push_str(&format!("-gps{speed}"));
+    }
+    if let Some(ftp) = ftp() {
+        settings.push_str(&format!("-ftp{ftp}"));
+    }
+    settings
+}
+
+/// Looks up the summary of the file in the cache.
+///
+/// # Arguments
+///
+/// - `key: Option<&CacheKey>` -- The key from `cache_key()`.
+/// - `result: &mut ProcessingResult` -- Gets the cached counts and warnings if the file is found.
+/// - `need_fit_stats: bool` -- Only use the cached summary if it has the FIT message counts.
+///
+/// # Returns
+///
+/// `Option<T>` -- The summary, or `None` if the file isn't cached or the summary can't be read.
+pub(crate) fn cached_summary<T: DeserializeOwned>(
+    key: Option<&CacheKey>,
+    result: &mut ProcessingResult,
+    need_fit_stats: bool,
+) -> Option<T> {
+    let key = key?;
+    let entry = SUMMARY_CACHE
+        .lock()
+        .ok()?
+        .as_ref()?
+        .entries
+        .get(&key.0)
+        .filter(|entry| !need_fit_stats || entry.fit_stats.is_some())
+        .cloned()?;
+    let summary = serde_json::from_value(entry.summary)
+        .map_err(|err| log::debug!("summary_cache::cached_summary() -- {err}"))
+        .ok()?;
+
+    log::debug!(
+        "summary_cache::cached_summary() -- Using the cached summary for {}",
+        result.input.display()
+    );
+    result.stats = entry.stats;
+    result.warnings = entry.warnings;
+    result.fit_stats = entry.fit_stats;
+    Some(summary)
+}
+
+/// Adds the summary of the file to the cache.
+///
+/// # Arguments
+///
+/// - `key: Option<&CacheKey>` -- The key from `cache_key()`. Nothing is stored if `None`.
+/// - `summary: &T` -- The activity summary.
+/// - `result: &ProcessingResult` -- The counts and warnings for the file.
+pub(crate) fn store_summary<T: Serialize>(
+    key: Option<&CacheKey>,
+    summary: &T,
+    result: &ProcessingResult,
+) {
+    let Some(key) = key else {
+        return;
+    };
+    let Ok(summary) = serde_json::to_value(summary) else {
+        return;
+    };
+
+    if let Ok(mut current) = SUMMARY_CACHE.lock() {
+        if let Some(cache) = current.as_mut() {
+            cache.entries.insert(
+                key.0.clone(),
+                CacheEntry {
+                    summary,
+                    stats: result.stats,
+                    warnings: result.warnings.clone(),
+                    fit_stats: result.fit_stats.clone(),
+                },
+            );
+            cache.changed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests for the summary_cache module
+mod tests {
+    use super::*;
+    use crate::FITSession;
+
+    #[test]
+    /// Test storing a summary, writing the cache and finding the summary again after reopening it
+    fn test_summary_cache() {
+        let dir = std::env::temp_dir();
+        let activity = dir.join("fitutils-summary-cache-test.fit");
+        let cache_file = dir.join("fitutils-summary-cache-test.json");
+        let cache_file = cache_file.to_str().unwrap_or_default();
+        std::fs::write(&activity, b"summary cache test").unwrap();
+        let _ = std::fs::remove_file(cache_file);
+
+        open_summary_cache(cache_file).unwrap();
+        let key = cache_key(activity.to_str().unwrap_or_default()).unwrap();
+        assert!(key.is_some());
+
+        let mut result = ProcessingResult::default();
+        assert!(cached_summary::<FITSession>(key.as_ref(), &mut result, false).is_none());
+
+        result.stats.records = 42;
+        result.warnings.push("Test warning".to_string());
+        let session = FITSession {
+            activity_type: Some("Rowing".to_string()),
+            ..FITSession::default()
+        };
+        store_summary(key.as_ref(), &session, &result);
+        save_summary_cache().unwrap();
+
+        open_summary_cache(cache_file).unwrap();
+        let mut cached = ProcessingResult::default();
+        let summary: FITSession = cached_summary(key.as_ref(), &mut cached, false).unwrap();
+        assert_eq!(summary.activity_type.as_deref(), Some("Rowing"));
+        assert_eq!(cached.stats.records, 42);
+        assert_eq!(cached.warnings, vec!["Test warning".to_string()]);
+
+        // The message counts weren't cached, so the file has to be parsed again if they're needed
+        assert!(cached_summary::<FITSession>(key.as_ref(), &mut cached, true).is_none());
+
+        let _ = std::fs::remove_file(activity);
+        let _ = std::fs::remove_file(cache_file);
+    }
+
+    #[test]
+    /// Test that summaries made with another FTP get another key
+    fn test_settings_key() {
+        let key = |ftp: Option<f64>| {
+            let mut settings = crate::Settings::default();
+            settings.set_ftp(ftp);
+            let _settings = settings.apply();
+            settings_key()
+        };
+        assert_eq!(key(None), "plain");
+        assert_eq!(key(Some(250.0)), "plain-ftp250");
+        assert_ne!(key(Some(250.0)), key(Some(300.0)));
+        assert_ne!(key(Some(250.0)), key(None));
+    }
+}
@@ -0,0 +1,374 @@
+//! The fields of the activity summary that FIT, GPX and TCX files have in common, so that a user interface can list
+//! and show them for any activity, e.g. `activity.get(SummaryField::Distance)`, without knowing whether it holds a
+//! `FITSession`, `GPXMetadata` or `TCXActivity`.
+//!
+//! The numbers are always in the base units -- meters, meters per second, beats per minute and watts -- whatever
+//! `Settings::set_unit_system()` says, and `SummaryField::unit()` gives the unit for each.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Activity, Duration, QueryFields};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A field of the activity summary, found in files of any format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SummaryField {
+    /// The name of the file the activity was read from.
+    File,
+    /// The sport, as found in the file.
+    Sport,
+    /// When the activity started.
+    StartTime,
+    /// The elapsed time from the start to the finish.
+    Duration,
+    /// The time spent moving. Not found in TCX files.
+    DurationMoving,
+    /// The distance covered in meters.
+    Distance,
+    /// The average speed in meters per second.
+    SpeedAvg,
+    /// The maximum speed in meters per second.
+    SpeedMax,
+    /// The total ascent in meters.
+    Ascent,
+    /// The total descent in meters. Not found in TCX files.
+    Descent,
+    /// The calories burned. Not found in GPX files.
+    Calories,
+    /// The average heart rate in beats per minute. GPX files have it averaged over the track waypoints.
+    HeartRateAvg,
+    /// The maximum heart rate in beats per minute. Not found in GPX files.
+    HeartRateMax,
+    /// The average power in watts. GPX files have it averaged over the track waypoints. Not found in TCX files.
+    PowerAvg,
+    /// The Training Stress Score. Only found in FIT files.
+    TrainingStressScore,
+    /// The device or application that recorded the activity.
+    Device,
+    /// Where the activity took place: outdoors, indoors or in a virtual world.
+    Environment,
+    /// The UUID of the activity, which stays the same when the file is renamed.
+    Uuid,
+    /// The tags of the activity.
+    Tags,
+}
+
+impl SummaryField {
+    /// All the fields, in the order they are usually shown.
+    pub const ALL: [Self; 19] = [
+        Self::File,
+        Self::Sport,
+        Self::StartTime,
+        Self::Duration,
+        Self::DurationMoving,
+        Self::Distance,
+        Self::SpeedAvg,
+        Self::SpeedMax,
+        Self::Ascent,
+        Self::Descent,
+        Self::Calories,
+        Self::HeartRateAvg,
+        Self::HeartRateMax,
+        Self::PowerAvg,
+        Self::TrainingStressScore,
+        Self::Device,
+        Self::Environment,
+        Self::Uuid,
+        Self::Tags,
+    ];
+
+    /// The name of the field, as used in the exports, e.g. `distance_m`. `FromStr` takes the same names.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Sport => "sport",
+            Self::StartTime => "start_time",
+            Self::Duration => "duration",
+            Self::DurationMoving => "duration_moving",
+            Self::Distance => "distance_m",
+            Self::SpeedAvg => "speed_avg_ms",
+            Self::SpeedMax => "speed_max_ms",
+            Self::Ascent => "ascent_m",
+            Self::Descent => "descent_m",
+            Self::Calories => "calories",
+            Self::HeartRateAvg => "heartrate_avg_bpm",
+            Self::HeartRateMax => "heartrate_max_bpm",
+            Self::PowerAvg => "power_avg_w",
+            Self::TrainingStressScore => "training_stress_score",
+            Self::Device => "device",
+            Self::Environment => "environment",
+            Self::Uuid => "uuid",
+            Self::Tags => "tags",
+        }
+    }
+
+    /// The name of the field for showing to people, e.g. `Average speed`.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::File => "File",
+            Self::Sport => "Sport",
+            Self::StartTime => "Start time",
+            Self::Duration => "Duration",
+            Self::DurationMoving => "Moving time",
+            Self::Distance => "Distance",
+            Self::SpeedAvg => "Average speed",
+            Self::SpeedMax => "Maximum speed",
+            Self::Ascent => "Ascent",
+            Self::Descent => "Descent",
+            Self::Calories => "Calories",
+            Self::HeartRateAvg => "Average heart rate",
+            Self::HeartRateMax => "Maximum heart rate",
+            Self::PowerAvg => "Average power",
+            Self::TrainingStressScore => "Training Stress Score",
+            Self::Device => "Device",
+            Self::Environment => "Environment",
+            Self::Uuid => "UUID",
+            Self::Tags => "Tags",
+        }
+    }
+
+    /// The unit of the numbers in the field, or `None` for fields that aren't numbers or have no unit.
+    #[must_use]
+    pub const fn unit(self) -> Option<&'static str> {
+        match self {
+            Self::Distance | Self::Ascent | Self::Descent => Some("m"),
+            Self::SpeedAvg | Self::SpeedMax => Some("m/s"),
+            Self::HeartRateAvg | Self::HeartRateMax => Some("bpm"),
+            Self::PowerAvg => Some("W"),
+            Self::Calories => Some("kcal"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SummaryField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for SummaryField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|field| field.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|field| field.name()).collect();
+                format!("Unknown summary field {s}. Use {}.", names.join(", "))
+            })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The value of a summary field. It serializes to JSON as a plain string, number or array.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum SummaryValue {
+    /// Text, e.g. the sport or the device.
+    Text(String),
+    /// A number in the unit given by `SummaryField::unit()`.
+    Number(f64),
+    /// A point in time.
+    Time(DateTime<Local>),
+    /// A length of time.
+    Duration(Duration),
+    /// Several values, e.g. the tags.
+    List(Vec<String>),
+}
+
+impl fmt::Display for SummaryValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => write!(f, "{text}"),
+            Self::Number(number) => write!(f, "{number}"),
+            Self::Time(time) => write!(f, "{}", time.format("%Y-%m-%d %H:%M:%S")),
+            Self::Duration(duration) => write!(f, "{duration}"),
+            Self::List(values) => write!(f, "{}", values.join(", ")),
+        }
+    }
+}
+
+impl Activity {
+    /// The value of a summary field, whatever the format the activity was read from.
+    ///
+    /// # Arguments
+    ///
+    /// `field: SummaryField` -- The field to get.
+    ///
+    /// # Returns
+    ///
+    /// `Option<SummaryValue>` -- The value, or `None` if the file doesn't have it. The tags are an empty list if the
+    /// activity has none.
+    ///
+    /// # Example
+    ///
+    /// ```text
+    /// for field in SummaryField::ALL {
+    ///     if let Some(value) = activity.get(field) {
+    ///         println!("{}: {value} {}", field.label(), field.unit().unwrap_or_default());
+    ///     }
+    /// }
+    /// ```
+    #[must_use]
+    pub fn get(&self, field: SummaryField) -> Option<SummaryValue> {
+        let fields = self.query_fields();
+        let text = |value: Option<String>| value.map(SummaryValue::Text);
+        let number = |value: Option<f64>| value.map(SummaryValue::Number);
+
+        match field {
+            SummaryField::File => text(fields.filename),
+            SummaryField::Sport => text(fields.sport),
+            SummaryField::StartTime => fields.start_time.map(SummaryValue::Time),
+            SummaryField::Duration => fields
+                .duration_sec
+                .map(|secs| SummaryValue::Duration(Duration::from_secs_f64(secs))),
+            SummaryField::DurationMoving => self.duration_moving().map(SummaryValue::Duration),
+            SummaryField::Distance => number(fields.distance_m),
+            SummaryField::SpeedAvg => number(self.speeds().0),
+            SummaryField::SpeedMax => number(self.speeds().1),
+            SummaryField::Ascent => number(fields.ascent_m),
+            SummaryField::Descent => number(self.descent_m()),
+            SummaryField::Calories => number(fields.calories),
+            SummaryField::HeartRateAvg => number(self.totals().heartrate_avg_bpm),
+            SummaryField::HeartRateMax => number(self.heartrate_max_bpm()),
+            SummaryField::PowerAvg => number(self.totals().power_avg_w),
+            SummaryField::TrainingStressScore => number(fields.training_stress_score),
+            SummaryField::Device => text(fields.device),
+            SummaryField::Environment => fields
+                .environment
+                .map(|environment| SummaryValue::Text(environment.to_string())),
+            SummaryField::Uuid => self.uuid().map(|uuid| SummaryValue::Text(uuid.to_string())),
+            SummaryField::Tags => Some(SummaryValue::List(fields.tags)),
+        }
+    }
+
+    /// The values the queries are tested against, which cover most of the summary fields.
+    fn query_fields(&self) -> QueryFields {
+        match self {
+            Self::Fit(act) => QueryFields::from_fit(&act.session),
+            Self::Gpx(act) => QueryFields::from_gpx(&act.metadata),
+            Self::Tcx(act, _) => QueryFields::from_tcx(act),
+        }
+    }
+
+    /// The time spent moving.
+    fn duration_moving(&self) -> Option<Duration> {
+        match self {
+            Self::Fit(act) => act.session.duration_moving,
+            Self::Gpx(act) => act.metadata.duration_moving,
+            Self::Tcx(..) => None,
+        }
+    }
+
+    /// The average and maximum speed in meters per second.
+    fn speeds(&self) -> (Option<f64>, Option<f64>) {
+        match self {
+            Self::Fit(act) => (
+                act.session.speed_avg.map(|speed| speed.value),
+                act.session.speed_max.map(|speed| speed.value),
+            ),
+            Self::Gpx(act) => (act.metadata.speed_avg_ms, act.metadata.speed_max_ms),
+            Self::Tcx(act, _) => (act.average_speed, act.maximum_speed),
+        }
+    }
+
+    /// The total descent in meters.
+    fn descent_m(&self) -> Option<f64> {
+        match self {
+            Self::Fit(act) => act.session.descent.map(|descent| f64::from(descent.value)),
+            Self::Gpx(act) => act.metadata.descent_m,
+            Self::Tcx(..) => None,
+        }
+    }
+
+    /// The maximum heart rate in beats per minute.
+    fn heartrate_max_bpm(&self) -> Option<f64> {
+        match self {
+            Self::Fit(act) => act.session.heartrate_max.map(f64::from),
+            Self::Gpx(_) => None,
+            Self::Tcx(act, _) => act.maximum_heart_rate,
+        }
+    }
+
+    /// The UUID of the activity.
+    fn uuid(&self) -> Option<uuid::Uuid> {
+        match self {
+            Self::Fit(act) => act.session.uuid,
+            Self::Gpx(act) => act.metadata.uuid,
+            Self::Tcx(act, _) => act.uuid,
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests for the summary_field module
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test getting the summary fields from activities of each format
+    fn test_get() {
+        let fit = Activity::from_file("../data/rowing.fit").unwrap();
+        assert_eq!(
+            fit.get(SummaryField::Sport),
+            Some(SummaryValue::Text("Rowing".to_string()))
+        );
+        assert!(matches!(
+            fit.get(SummaryField::Duration),
+            Some(SummaryValue::Duration(_))
+        ));
+        assert!(matches!(
+            fit.get(SummaryField::StartTime),
+            Some(SummaryValue::Time(_))
+        ));
+        assert_eq!(
+            fit.get(SummaryField::Tags),
+            Some(SummaryValue::List(Vec::new()))
+        );
+
+        let gpx = Activity::from_file("../data/running.gpx").unwrap();
+        assert!(matches!(
+            gpx.get(SummaryField::Distance),
+            Some(SummaryValue::Number(distance)) if distance > 0.0
+        ));
+        assert!(gpx.get(SummaryField::Calories).is_none());
+        assert!(gpx.get(SummaryField::HeartRateMax).is_none());
+
+        let tcx = Activity::from_file("../data/running.tcx").unwrap();
+        assert!(matches!(
+            tcx.get(SummaryField::HeartRateMax),
+            Some(SummaryValue::Number(_))
+        ));
+        assert!(tcx.get(SummaryField::Descent).is_none());
+        assert!(tcx.get(SummaryField::File).is_some());
+    }
+
+    #[test]
+    /// Test the names of the fields and showing the values
+    fn test_fields() {
+        for field in SummaryField::ALL {
+            assert_eq!(field.name().parse::<SummaryField>(), Ok(field));
+        }
+        assert_eq!("Distance_M".parse(), Ok(SummaryField::Distance));
+        assert!("pace".parse::<SummaryField>().is_err());
+        assert_eq!(SummaryField::SpeedAvg.unit(), Some("m/s"));
+        assert!(SummaryField::Sport.unit().is_none());
+
+        assert_eq!(
+            SummaryValue::List(vec!["race".to_string(), "brevet".to_string()]).to_string(),
+            "race, brevet"
+        );
+        assert_eq!(
+            serde_json::to_string(&SummaryValue::Number(12.5)).unwrap(),
+            "12.5"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Defines the `SummarySplit` enum used to write the activities summary as several CSV files, one per sport, year or
+//! device, along with the sport names the activities are grouped by.
+
+use crate::placeholder::placeholder;
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "fs")]
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Used for activities where the sport, year or device isn't known, unless another placeholder has been set.
+const UNKNOWN: &str = "unknown";
+
+/// Other names used for the same sport by the different file formats and applications.
+const SPORT_ALIASES: [(&str, &str); 11] = [
+    ("biking", "cycling"),
+    ("bike", "cycling"),
+    ("ride", "cycling"),
+    ("run", "running"),
+    ("walk", "walking"),
+    ("hike", "hiking"),
+    ("swim", "swimming"),
+    ("row", "rowing"),
+    ("ski", "cross_country_skiing"),
+    ("xc_skiing", "cross_country_skiing"),
+    ("other", "generic"),
+];
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// What the activities summary is split by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::module_name_repetitions)]
+pub enum SummarySplit {
+    /// One file per sport, e.g. `running`, `cycling`.
+    Sport,
+    /// One file per year the activity started.
+    Year,
+    /// One file per device (product) that recorded the activity.
+    Device,
+}
+
+impl SummarySplit {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 3] = ["sport", "year", "device"];
+
+    /// Works out which group an activity belongs to.
+    ///
+    /// # Arguments
+    ///
+    /// - `sport: Option<&str>` -- The sport of the activity, as found in the file.
+    /// - `year: Option<i32>` -- The year the activity started.
+    /// - `device: Option<&str>` -- The device that recorded the activity.
+    ///
+    /// # Returns
+    ///
+    /// `String` -- A lowercase key that can be used in a file name, or the placeholder if the value isn't known.
+    #[must_use]
+    pub fn key(self, sport: Option<&str>, year: Option<i32>, device: Option<&str>) -> String {
+        match self {
+            Self::Sport => normalized_sport(sport.unwrap_or_default()),
+            Self::Year => year.map_or_else(unknown_key, |y| y.to_string()),
+            Self::Device => file_safe(device.unwrap_or_default()),
+        }
+    }
+}
+
+impl FromStr for SummarySplit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sport" => Ok(Self::Sport),
+            "year" => Ok(Self::Year),
+            "device" => Ok(Self::Device),
+            _ => Err(format!("Unable to split the summary by {s}.")),
+        }
+    }
+}
+
+impl fmt::Display for SummarySplit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sport => write!(f, "sport"),
+            Self::Year => write!(f, "year"),
+            Self::Device => write!(f, "device"),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Normalizes a sport name so the same sport gets the same name regardless of the file format, e.g. the TCX `Biking`
+/// and the FIT `cycling` both become `cycling`.
+///
+/// # Arguments
+///
+/// `sport: &str` -- The sport as found in the file.
+///
+/// # Returns
+///
+/// `String` -- The lowercase, underscore-separated sport name, or the placeholder if the sport is blank.
+#[must_use]
+pub fn normalized_sport(sport: &str) -> String {
+    let sport = file_safe(sport);
+
+    SPORT_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == sport)
+        .map_or(sport, |(_, name)| (*name).to_string())
+}
+
+/// Lowercases the value and replaces anything that doesn't belong in a file name with underscores.
+fn file_safe(value: &str) -> String {
+    let safe = underscored(value);
+    if safe.is_empty() {
+        unknown_key()
+    } else {
+        safe
+    }
+}
+
+/// The key for activities where the value isn't known. The placeholder is used if it can be part of a file name,
+/// since an empty key would give file names like `fit-sessions..csv`.
+fn unknown_key() -> String {
+    let key = underscored(&placeholder(UNKNOWN));
+    if key.is_empty() {
+        UNKNOWN.to_string()
+    } else {
+        key
+    }
+}
+
+/// Lowercases the value and replaces each run of characters other than letters and digits with a single underscore.
+fn underscored(value: &str) -> String {
+    value
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("_")
+}
+
+/// Creates the name of the summary file for a group, e.g. `fit-sessions.csv` becomes `fit-sessions.running.csv`.
+#[cfg(feature = "fs")]
+pub(crate) fn split_filename(filename: &str, key: &str) -> String {
+    let mut path = PathBuf::from(filename);
+    let extension = path
+        .extension()
+        .map_or_else(|| "csv".to_string(), |e| e.to_string_lossy().to_string());
+    path.set_extension(format!("{key}.{extension}"));
+
+    path.to_string_lossy().to_string()
+}
+
+/// Groups the items by key, keeping the original order within each group.
+#[cfg(feature = "fs")]
+pub(crate) fn group_by<T, F>(items: &[T], key: F) -> BTreeMap<String, Vec<&T>>
+where
+    F: Fn(&T) -> String,
+{
+    let mut groups: BTreeMap<String, Vec<&T>> = BTreeMap::new();
+    for item in items {
+        groups.entry(key(item)).or_default().push(item);
+    }
+
+    groups
+}
+
+#[cfg(test)]
+/// Tests for the summary_split module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that the different sport names end up the same
+    fn test_normalized_sport() {
+        assert_eq!(normalized_sport("Running"), "running");
+        assert_eq!(normalized_sport("Biking"), "cycling");
+        assert_eq!(normalized_sport("cycling"), "cycling");
+        assert_eq!(normalized_sport("Indoor Rowing"), "indoor_rowing");
+        assert_eq!(normalized_sport("  "), "unknown");
+    }
+
+    #[test]
+    /// Test the keys for each way of splitting
+    fn test_key() {
+        assert_eq!(
+            SummarySplit::Sport.key(Some("Run"), Some(2021), Some("fenix 7X")),
+            "running"
+        );
+        assert_eq!(SummarySplit::Year.key(None, Some(2021), None), "2021");
+        assert_eq!(SummarySplit::Year.key(None, None, None), "unknown");
+        assert_eq!(
+            SummarySplit::Device.key(None, None, Some("fenix 7X / Sapphire")),
+            "fenix_7x_sapphire"
+        );
+        assert_eq!("Device".parse::<SummarySplit>(), Ok(SummarySplit::Device));
+        assert!("month".parse::<SummarySplit>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test the file names and grouping
+    fn test_split_filename_group_by() {
+        assert_eq!(
+            split_filename("out/fit-sessions.csv", "running"),
+            "out/fit-sessions.running.csv"
+        );
+        assert_eq!(split_filename("summary", "2021"), "summary.2021.csv");
+
+        let groups = group_by(&["b1", "a1", "b2"], |s| s[..1].to_string());
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(groups["b"], vec![&"b1", &"b2"]);
+    }
+}
@@ -0,0 +1,117 @@
+//! Defines the rename tokens that describe the workout itself (`%distance`, `%calories`, `%avg_hr`, `%max_hr`,
+//! `%elevation_gain` and `%duration_hms`), so file names can tell one workout from another. The tokens are filled in
+//! the same way for FIT, GPX and TCX files.
+
+use crate::placeholder::placeholder;
+use crate::Duration;
+use std::collections::HashMap;
+
+/// The meters in a kilometer.
+const METERS_PER_KM: f64 = 1_000.0;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The numbers from the summary of an activity that go into the rename tokens. Anything not in the file is `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct SummaryTokens {
+    /// The distance in meters.
+    pub distance_m: Option<f64>,
+
+    /// The calories burned.
+    pub calories: Option<u16>,
+
+    /// The average heart rate in beats per minute.
+    pub avg_hr: Option<f64>,
+
+    /// The maximum heart rate in beats per minute.
+    pub max_hr: Option<f64>,
+
+    /// The total climb in meters.
+    pub elevation_gain_m: Option<f64>,
+
+    /// The duration of the activity.
+    pub duration: Option<Duration>,
+}
+
+impl SummaryTokens {
+    /// Inserts the tokens into the values, along with their short forms. The distance is in kilometers with one
+    /// decimal, the heart rates and the elevation gain in whole beats per minute and meters, and the duration is
+    /// written as e.g. `01h05m23s`. Values that aren't known are filled with `0`, or the placeholder if one has been
+    /// set.
+    ///
+    /// # Arguments
+    ///
+    /// `values: &mut HashMap<String, String>` -- The rename tokens and their values.
+    pub(crate) fn insert(&self, values: &mut HashMap<String, String>) {
+        let mut insert = |long: &str, short: &str, value: Option<String>| {
+            let value = value.unwrap_or_else(|| placeholder("0"));
+            values.insert(long.to_string(), value.clone());
+            values.insert(short.to_string(), value);
+        };
+
+        insert(
+            "%distance",
+            "%di",
+            self.distance_m
+                .map(|meters| format!("{:.1}", meters / METERS_PER_KM)),
+        );
+        insert(
+            "%calories",
+            "%ca",
+            self.calories.map(|calories| calories.to_string()),
+        );
+        insert("%avg_hr", "%ah", self.avg_hr.map(|hr| format!("{hr:.0}")));
+        insert("%max_hr", "%mh", self.max_hr.map(|hr| format!("{hr:.0}")));
+        insert(
+            "%elevation_gain",
+            "%eg",
+            self.elevation_gain_m.map(|meters| format!("{meters:.0}")),
+        );
+        insert("%duration_hms", "%dh", self.duration.map(hms));
+    }
+}
+
+/// The duration as hours, minutes and seconds that can be used in a file name, e.g. `01h05m23s`.
+fn hms(duration: Duration) -> String {
+    let secs = duration.0.as_secs();
+    format!(
+        "{:02}h{:02}m{:02}s",
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+/// Tests for the summary_tokens module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test filling in the tokens, with and without values
+    fn test_insert() {
+        let mut values = HashMap::new();
+        SummaryTokens {
+            distance_m: Some(10_249.7),
+            calories: Some(642),
+            avg_hr: Some(147.6),
+            max_hr: Some(171.0),
+            elevation_gain_m: Some(88.4),
+            duration: Some(Duration::from_secs_f64(3_923.8)),
+        }
+        .insert(&mut values);
+
+        assert_eq!(values["%distance"], "10.2");
+        assert_eq!(values["%di"], "10.2");
+        assert_eq!(values["%calories"], "642");
+        assert_eq!(values["%avg_hr"], "148");
+        assert_eq!(values["%max_hr"], "171");
+        assert_eq!(values["%elevation_gain"], "88");
+        assert_eq!(values["%duration_hms"], "01h05m23s");
+        assert_eq!(values["%dh"], "01h05m23s");
+
+        let mut values = HashMap::new();
+        SummaryTokens::default().insert(&mut values);
+        assert_eq!(values.len(), 12);
+        assert_eq!(values["%avg_hr"], "0");
+    }
+}
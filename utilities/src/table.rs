@@ -0,0 +1,106 @@
+//! A plain text table for the console output, with the columns lined up.
+
+use std::fmt;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// A table of text cells. Each column is as wide as its widest cell, and the cells are aligned to the right. Lines
+/// ending in empty cells are trimmed. Print it with `{}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    /// The names of the columns.
+    header: Vec<String>,
+
+    /// The cells of each row. Rows with fewer cells than the header are padded with empty cells.
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Creates an empty table with the columns given.
+    ///
+    /// # Arguments
+    ///
+    /// `header: &[&str]` -- The names of the columns.
+    #[must_use]
+    pub fn new(header: &[&str]) -> Self {
+        Self {
+            header: header.iter().map(ToString::to_string).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row to the bottom of the table.
+    ///
+    /// # Arguments
+    ///
+    /// `row: Vec<String>` -- The cells of the row, in the order of the columns.
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// Whether the table has no rows.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The width of each column, in characters.
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self
+            .header
+            .iter()
+            .map(|name| name.chars().count())
+            .collect();
+        for row in &self.rows {
+            for (column, cell) in row.iter().enumerate() {
+                let width = cell.chars().count();
+                match widths.get_mut(column) {
+                    Some(current) => *current = (*current).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        widths
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths = self.widths();
+        for row in std::iter::once(&self.header).chain(&self.rows) {
+            let line: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(column, width)| {
+                    let cell = row.get(column).map_or("", String::as_str);
+                    format!("{cell:>width$}")
+                })
+                .collect();
+            writeln!(f, "{}", line.join("  ").trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+/// Tests for the table module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test lining up the columns, with short rows padded
+    fn test_display() {
+        let mut table = Table::new(&["Lap", "Time", "HR"]);
+        assert!(table.is_empty());
+        table.add_row(vec![
+            "1".to_string(),
+            "00:05:12".to_string(),
+            "151".to_string(),
+        ]);
+        table.add_row(vec!["12".to_string(), "00:04:58".to_string()]);
+
+        assert_eq!(
+            table.to_string(),
+            "Lap      Time   HR\n  1  00:05:12  151\n 12  00:04:58\n"
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Tags for the activities, e.g. `race` or `brevet`, so the history can be sliced by categories of your own.
+//!
+//! The tags are kept in a JSON file next to the activities, keyed by the activity UUID, so they stay with the activity
+//! when the file is renamed or moved, and when it's exported again. The file is opened once for the whole run using
+//! `open_tag_store()`, along with any tags to add to every activity processed, and written back with
+//! `save_tag_store()`. The tags of each activity are added to its summary.
+
+#[cfg(feature = "fs")]
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "fs")]
+use std::error::Error;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::{BufReader, BufWriter, Write};
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "fs")]
+use std::sync::Mutex;
+#[cfg(feature = "fs")]
+use uuid::Uuid;
+
+#[cfg(feature = "fs")]
+use crate::atomic_file::write_atomically;
+
+/// Separates the tags in the `tags` column of the summaries.
+pub const TAG_SEPARATOR: &str = ";";
+
+/// The tag file used if none is given.
+pub const DEFAULT_TAG_FILE: &str = "fitutils-tags.json";
+
+/// The tag store used for the rest of the run, if any.
+#[cfg(feature = "fs")]
+static TAG_STORE: Mutex<Option<TagStore>> = Mutex::new(None);
+
+/// The tags in the `tags` column of a summary.
+pub(crate) fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(TAG_SEPARATOR)
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The tags of each activity, along with the tags to add to the activities processed in this run.
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+struct TagStore {
+    /// The tags of each activity, keyed by the activity UUID.
+    tags: BTreeMap<Uuid, BTreeSet<String>>,
+
+    /// The tags added to every activity processed.
+    added: Vec<String>,
+
+    /// The file the tags were read from and are written back to.
+    path: PathBuf,
+
+    /// Whether any tags were added since the file was read.
+    changed: bool,
+}
+
+#[cfg(feature = "fs")]
+impl TagStore {
+    /// Reads the tags from the file, if it exists.
+    ///
+    /// # Errors
+    ///
+    /// The tags may not be valid, or reading or parsing an existing file may fail.
+    fn open(path: &Path, added: &[String]) -> Result<Self, Box<dyn Error>> {
+        let added = added
+            .iter()
+            .map(|tag| tag.trim().to_string())
+            .collect::<Vec<_>>();
+        if let Some(tag) = added
+            .iter()
+            .find(|tag| tag.is_empty() || tag.contains(TAG_SEPARATOR))
+        {
+            return Err(format!(
+                "Invalid tag \"{tag}\". Tags can't be empty or contain {TAG_SEPARATOR}."
+            )
+            .into());
+        }
+
+        let tags = if path.exists() {
+            serde_json::from_reader(BufReader::new(File::open(path)?))
+                .map_err(|err| format!("{}: Unable to read the tags. {err}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            tags,
+            added,
+            path: path.to_path_buf(),
+            changed: false,
+        })
+    }
+
+    /// Adds the tags for the run to the activity, and returns all of its tags.
+    fn tag(&mut self, uuid: Uuid) -> Option<String> {
+        if !self.added.is_empty() {
+            let tags = self.tags.entry(uuid).or_default();
+            for tag in &self.added {
+                self.changed |= tags.insert(tag.clone());
+            }
+        }
+
+        self.tags
+            .get(&uuid)
+            .filter(|tags| !tags.is_empty())
+            .map(|tags| tags.iter().cloned().collect::<Vec<_>>().join(TAG_SEPARATOR))
+    }
+
+    /// Writes the tags back to the file, if any were added.
+    ///
+    /// # Errors
+    ///
+    /// Writing the file may fail.
+    fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.changed {
+            write_atomically(&self.path, |file| {
+                let mut writer = BufWriter::new(file);
+                serde_json::to_writer_pretty(&mut writer, &self.tags)?;
+                Ok(writer.flush()?)
+            })?;
+            self.changed = false;
+            log::info!("Tags written to: {}", self.path.display());
+        }
+        Ok(())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Opens the tag file for the rest of the run. If the file doesn't exist yet, it's created when tags are added and
+/// `save_tag_store()` is called.
+///
+/// # Arguments
+///
+/// - `path: &str` -- The tag file.
+/// - `add: &[String]` -- The tags to add to every activity processed, if any.
+///
+/// # Errors
+///
+/// The tags may be empty or contain `TAG_SEPARATOR`. Reading or parsing an existing tag file may fail.
+#[cfg(feature = "fs")]
+pub fn open_tag_store(path: &str, add: &[String]) -> Result<(), Box<dyn Error>> {
+    let store = TagStore::open(Path::new(path), add)?;
+    log::debug!(
+        "tags::open_tag_store() -- {} tagged activities",
+        store.tags.len()
+    );
+
+    if let Ok(mut current) = TAG_STORE.lock() {
+        *current = Some(store);
+    }
+    Ok(())
+}
+
+/// Writes the tag file back, if any tags were added during the run.
+///
+/// # Errors
+///
+/// Writing the tag file may fail.
+#[cfg(feature = "fs")]
+pub fn save_tag_store() -> Result<(), Box<dyn Error>> {
+    let Ok(mut current) = TAG_STORE.lock() else {
+        return Ok(());
+    };
+
+    match current.as_mut() {
+        Some(store) => store.save(),
+        None => Ok(()),
+    }
+}
+
+/// Adds the tags for the run to the activity, if a tag store is open, and returns all of its tags.
+///
+/// # Arguments
+///
+/// `uuid: Option<Uuid>` -- The activity UUID. Activities without one can't be tagged.
+///
+/// # Returns
+///
+/// `Option<String>` -- The tags separated by `TAG_SEPARATOR`, or `None` if the activity has none.
+#[cfg(feature = "fs")]
+pub(crate) fn activity_tags(uuid: Option<Uuid>) -> Option<String> {
+    let uuid = uuid?;
+    TAG_STORE.lock().ok()?.as_mut()?.tag(uuid)
+}
+
+#[cfg(test)]
+/// Tests for the tags module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test splitting the tags of a summary
+    fn test_split_tags() {
+        assert_eq!(split_tags("brevet;race"), ["brevet", "race"]);
+        assert_eq!(split_tags(" race ;"), ["race"]);
+        assert!(split_tags("").is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    /// Test adding tags to the activities, writing them and finding them again after reopening the file
+    fn test_tag_store() {
+        let path = std::env::temp_dir().join("fitutils-tag-store-test.json");
+        let _ = std::fs::remove_file(&path);
+        let (race, commute) = (Uuid::from_u128(1), Uuid::from_u128(2));
+
+        let mut store =
+            TagStore::open(&path, &["race".to_string(), " brevet".to_string()]).unwrap();
+        assert_eq!(store.tag(race), Some("brevet;race".to_string()));
+        store.save().unwrap();
+
+        let mut store = TagStore::open(&path, &[]).unwrap();
+        assert_eq!(store.tag(race), Some("brevet;race".to_string()));
+        assert_eq!(store.tag(commute), None);
+        assert!(!store.changed);
+
+        assert!(TagStore::open(&path, &["a;b".to_string()]).is_err());
+        assert!(TagStore::open(&path, &[String::new()]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
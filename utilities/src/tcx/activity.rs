@@ -1,76 +1,245 @@
-use csv::WriterBuilder;
-use serde::Serialize;
-use serde_json;
-use std::fs::File;
-use std::path::PathBuf;
-use std::{error::Error, io::BufReader};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{Read, Write};
 use tcx::{self};
-
-use crate::{set_extension, Duration};
+use uuid::Uuid;
+
+use crate::analysis::decoupling::pace_decoupling;
+use crate::analysis::derived::ratio;
+use crate::analysis::distance_check::{distance_discrepancy, miscalibration_warning, GpsDistance};
+use crate::analysis::hr_zones::{hr_zones, time_in_hr_zones};
+use crate::analysis::indoor::detect_environment;
+use crate::analysis::overlap::ActivityWindow;
+use crate::analysis::pacing::{pacing, PacingSplit};
+use crate::analysis::recording::{RecordingIntervals, RecordingMode};
+use crate::analysis::splits::SplitPoint;
+use crate::columns::{ColumnWriter, CsvFile};
+use crate::placeholder::placeholder;
+use crate::precision::Rounded;
+use crate::privacy::{output_serial, serialize_serial};
+use crate::schema::write_summary_json;
+use crate::tcx::quirks::read_tcx;
+use crate::units::{converted, label, pace_label, Measure};
+use crate::warnings::log_warnings;
+use crate::{
+    activity_uuid, Duration, FITEnvironment, TCXCreator, TCXLap, TCXTrackpoint, TCXTrackpointList,
+};
+
+#[cfg(feature = "fs")]
+use crate::atomic_file::write_atomically;
+#[cfg(feature = "fs")]
+use crate::input::open_input;
+#[cfg(feature = "fs")]
+use crate::output_naming::{output_path, write_output};
+#[cfg(feature = "fs")]
+use crate::summary_split::{group_by, split_filename};
+#[cfg(feature = "fs")]
+use crate::SummarySplit;
+#[cfg(feature = "fs")]
+use chrono::Datelike;
+#[cfg(feature = "fs")]
+use std::{io::BufReader, path::Path};
+
+/// Separates the notes of the laps in the `lap_notes` column.
+pub const LAP_NOTES_SEPARATOR: &str = " | ";
+
+/// The shape of the single-document JSON export.
+#[derive(Serialize)]
+struct TCXBundle<'a> {
+    session: &'a TCXActivity,
+    laps: &'a [TCXLap],
+    trackpoints: &'a [TCXTrackpoint],
+}
 
 /// Holds a summary of the activities in the file
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[allow(clippy::module_name_repetitions)]
 pub struct TCXActivity {
     /// Filename of the original file from which the data was read
+    #[serde(rename = "filename")]
     pub filename: Option<String>,
 
+    /// Device manufacturer, worked out from the `<Creator>` element
+    #[serde(rename = "manufacturer")]
+    pub manufacturer: Option<String>,
+
+    /// Device name from the `<Creator>` element
+    #[serde(rename = "product")]
+    pub product: Option<String>,
+
+    /// Device unit ID from the `<Creator>` element
+    #[serde(serialize_with = "serialize_serial")]
+    #[serde(rename = "serial_number")]
+    pub serial_number: Option<String>,
+
     /// Number of activities in the file - typically 1
+    #[serde(rename = "num_activities")]
     pub num_activities: Option<u16>,
 
     /// Sport
+    #[serde(rename = "sport")]
     pub sport: Option<String>,
 
     /// Activity ID - usually denoted by the start time for the activity
+    #[serde(rename = "start_time")]
     pub start_time: Option<String>,
 
     /// Total activity duration in seconds.
+    #[serde(rename = "duration")]
     pub duration: Option<Duration>,
 
     /// Notes - if there are any
+    #[serde(rename = "notes")]
     pub notes: Option<String>,
 
     /// Number of laps within the activity
+    #[serde(rename = "num_laps")]
     pub num_laps: Option<u16>,
 
     /// Total number of tracks within the activity
+    #[serde(rename = "num_tracks")]
     pub num_tracks: Option<u16>,
 
     /// Total number of trackpoints within the activity
+    #[serde(rename = "num_trackpoints")]
     pub num_trackpoints: Option<u16>,
 
     /// Total distance covered during the lap in meters.
+    #[serde(rename = "distance_meters")]
     pub distance_meters: Option<f64>,
 
+    /// The distance worked out from the trackpoint positions in meters.
+    #[serde(rename = "gps_distance_meters")]
+    pub gps_distance_meters: Option<f64>,
+
+    /// How much the recorded distance differs from the GPS distance, in percent of the GPS distance.
+    #[serde(rename = "distance_discrepancy_pct")]
+    pub distance_discrepancy_pct: Option<f64>,
+
     /// Max ascent in meters from start
+    #[serde(rename = "start_altitude")]
     pub start_altitude: Option<f64>,
 
     /// Max ascent in meters from start
+    #[serde(rename = "max_altitude")]
     pub max_altitude: Option<f64>,
 
     /// Max ascent in meters from start
+    #[serde(rename = "ascent_meters")]
     pub ascent_meters: Option<f64>,
 
     /// Average speed in Meters/Second for the activity
+    #[serde(rename = "average_speed")]
     pub average_speed: Option<f64>,
 
     /// Maximum speed in Meters/Second obtained during the activity.
+    #[serde(rename = "maximum_speed")]
     pub maximum_speed: Option<f64>,
 
     /// Number of calories burned during the activity.
+    #[serde(rename = "calories")]
     pub calories: Option<u16>,
 
     /// Average heart rate in Beats per Minute (BPM) for the activity
+    #[serde(rename = "average_heart_rate")]
     pub average_heart_rate: Option<f64>,
 
     /// Maximum heart rate in Beats per Minute (BPM) for the activity
+    #[serde(rename = "maximum_heart_rate")]
     pub maximum_heart_rate: Option<f64>,
 
     /// Average cadence (typically in Steps, Revolutions or Strokes per Minute) for the activity.
+    #[serde(rename = "average_cadence")]
     pub average_cadence: Option<f64>,
 
     /// Maximum cadence (typically in Steps, Revolutions or Strokes per Minute) for the activity.
+    #[serde(rename = "maximum_cadence")]
     pub maximum_cadence: Option<u16>,
+
+    /// Identifies the activity across all the files exported from it.
+    #[serde(rename = "uuid")]
+    pub uuid: Option<Uuid>,
+
+    /// The average speed over the first half of the distance in meters per second.
+    #[serde(rename = "speed_first_half_ms")]
+    pub speed_first_half_ms: Option<f64>,
+
+    /// The average speed over the second half of the distance in meters per second.
+    #[serde(rename = "speed_second_half_ms")]
+    pub speed_second_half_ms: Option<f64>,
+
+    /// How much longer the second half took than the first, in percent. Negative for a negative split.
+    #[serde(rename = "pacing_index_pct")]
+    pub pacing_index_pct: Option<f64>,
+
+    /// Whether the second half was faster (negative), about the same (even) or slower (positive) than the first.
+    #[serde(rename = "pacing_split")]
+    pub pacing_split: Option<PacingSplit>,
+
+    /// The median time between two records in seconds.
+    #[serde(rename = "recording_interval_sec")]
+    pub recording_interval_sec: Option<f64>,
+
+    /// The share of the intervals between records that are one second, in percent.
+    #[serde(rename = "recording_every_second_pct")]
+    pub recording_every_second_pct: Option<f64>,
+
+    /// Whether the device recorded every second or used smart recording.
+    #[serde(rename = "recording_mode")]
+    pub recording_mode: Option<RecordingMode>,
+
+    /// Notes on the laps, separated by `LAP_NOTES_SEPARATOR`
+    #[serde(rename = "lap_notes")]
+    pub lap_notes: Option<String>,
+
+    /// Device software version from the `<Creator>` element
+    #[serde(rename = "software_version")]
+    pub software_version: Option<String>,
+
+    /// The application that wrote the file, from the `<Author>` element
+    #[serde(rename = "author")]
+    pub author: Option<String>,
+
+    /// Whether the activity took place outdoors or indoors, based on the trackpoint positions.
+    #[serde(rename = "environment")]
+    pub environment: FITEnvironment,
+
+    /// How much the speed to heart rate ratio dropped from the first half to the second, in percent (Pa:HR).
+    #[serde(rename = "decoupling_pace_pct")]
+    pub decoupling_pace_pct: Option<f64>,
+
+    /// The time spent in heart rate zone 0, in seconds.
+    #[serde(rename = "heart_rate_zone0_sec")]
+    pub heart_rate_zone0_sec: Option<f64>,
+
+    /// The time spent in heart rate zone 1, in seconds.
+    #[serde(rename = "heart_rate_zone1_sec")]
+    pub heart_rate_zone1_sec: Option<f64>,
+
+    /// The time spent in heart rate zone 2, in seconds.
+    #[serde(rename = "heart_rate_zone2_sec")]
+    pub heart_rate_zone2_sec: Option<f64>,
+
+    /// The time spent in heart rate zone 3, in seconds.
+    #[serde(rename = "heart_rate_zone3_sec")]
+    pub heart_rate_zone3_sec: Option<f64>,
+
+    /// The time spent in heart rate zone 4, in seconds.
+    #[serde(rename = "heart_rate_zone4_sec")]
+    pub heart_rate_zone4_sec: Option<f64>,
+
+    /// The tags of the activity from the tag file, separated by `TAG_SEPARATOR`.
+    #[serde(rename = "tags")]
+    pub tags: Option<String>,
+
+    /// The summary of each lap (not serialized with the activity summary)
+    #[serde(skip)]
+    pub laps: Vec<TCXLap>,
+
+    /// Anything noteworthy found while parsing the file, e.g. several activities in one file.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
 }
 
 impl TCXActivity {
@@ -97,25 +266,78 @@ impl TCXActivity {
     /// # Panics
     ///
     /// None.
+    #[cfg(feature = "fs")]
     pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
-        let mut tcdb = tcx::read(&mut BufReader::new(File::open(filename)?))?;
-        tcdb.calc_heartrates();
+        Self::from_reader(BufReader::new(open_input(filename)?), filename)
+    }
+
+    /// Reads the summary of the activities from TCX data, e.g. the contents of a file dropped into a browser. Use
+    /// this where there is no file system.
+    ///
+    /// # Arguments
+    ///
+    /// - `reader: R` -- The TCX data.
+    /// - `filename: &str` -- The name of the file the data came from. Used in the summary and the exports.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the data may fail.
+    pub fn from_reader<R: Read>(mut reader: R, filename: &str) -> Result<Self, Box<dyn Error>> {
+        // The data is parsed twice -- once for the activities and once for the creator
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+
+        let tcdb = read_tcx(buffer.as_slice())?;
 
         let mut act;
 
         if let Some(activities) = tcdb.activities {
             act = Self::from_activities(&activities);
             act.filename = Some(filename.to_string());
+            act.set_creator(&TCXCreator::from_reader(buffer.as_slice())?);
         } else {
             act = Self::default();
+            act.warnings.push("No activities found.".to_string());
         }
+        log_warnings(Some(filename), &act.warnings);
 
         // return safely
         Ok(act)
     }
 
+    /// Fills in the device information from the `<Creator>` element of the file.
+    ///
+    /// # Arguments
+    ///
+    /// `creator: &TCXCreator` -- The creator information read from the same file as the activities.
+    pub fn set_creator(&mut self, creator: &TCXCreator) {
+        self.manufacturer = creator.manufacturer();
+        self.product.clone_from(&creator.name);
+        self.serial_number.clone_from(&creator.unit_id);
+        self.software_version.clone_from(&creator.version);
+        self.author.clone_from(&creator.author);
+        self.set_uuid();
+    }
+
+    /// Sets the activity UUID from the start time, serial number and sport, on the summary and the laps.
+    pub fn set_uuid(&mut self) {
+        let start_time = self
+            .start_time
+            .as_deref()
+            .and_then(|st| DateTime::parse_from_rfc3339(st).ok());
+        self.uuid = Some(activity_uuid(
+            start_time.as_ref(),
+            self.serial_number.as_deref(),
+            self.sport.as_deref(),
+        ));
+        for lap in &mut self.laps {
+            lap.activity_uuid = self.uuid;
+        }
+    }
+
     /// Generates a summary from a set of activities in the TCX file.
-    /// Assumes that `tcx::TrainingCenterDatabase::calc_heartrates`() has been run.
+    /// Assumes that `tcx::TrainingCenterDatabase::calc_heartrates`() has been run, e.g. by reading the file with
+    /// `from_reader()`.
     #[must_use]
     #[allow(
         clippy::cast_sign_loss,
@@ -125,22 +347,27 @@ impl TCXActivity {
     pub fn from_activities(activities: &tcx::Activities) -> Self {
         let mut act_s = Self::default();
 
-        let mut hr: f64 = 0.0;
-        let mut cad: f64 = 0.0;
-
-        // Find the altitude of the very first TrackPoint
-        if let Some(act) = activities.activities.first() {
-            if let Some(lap) = act.laps.first() {
-                if let Some(track) = lap.tracks.first() {
-                    if let Some(tp) = track.trackpoints.first() {
-                        if let Some(num) = tp.altitude_meters {
-                            act_s.start_altitude = Some(num);
-                            act_s.max_altitude = Some(num);
-                        }
-                    }
-                }
-            }
-        }
+        // The sums and counts for the averages, since old devices leave the heart rate and cadence out of some
+        // trackpoints
+        let (mut hr, mut num_hr): (f64, usize) = (0.0, 0);
+        let (mut cad, mut num_cad): (f64, usize) = (0.0, 0);
+        let mut gps_distance = GpsDistance::default();
+        let mut split_points = Vec::new();
+        let mut hr_samples = Vec::new();
+        let mut intervals = RecordingIntervals::default();
+        let mut num_trackpoints: usize = 0;
+        let mut duration_overflow = false;
+
+        // Find the altitude of the first TrackPoint that has one. Old devices may have laps without tracks and
+        // trackpoints without an altitude before the GPS has a fix.
+        act_s.start_altitude = activities
+            .activities
+            .iter()
+            .flat_map(|act| &act.laps)
+            .flat_map(|lap| &lap.tracks)
+            .flat_map(|track| &track.trackpoints)
+            .find_map(|tp| tp.altitude_meters);
+        act_s.max_altitude = act_s.start_altitude;
 
         for activity in &activities.activities {
             act_s.num_activities = Some(act_s.num_activities.unwrap_or(0) + 1);
@@ -150,10 +377,18 @@ impl TCXActivity {
 
             for lap in &activity.laps {
                 act_s.num_laps = Some(act_s.num_laps.unwrap_or(0) + 1);
-                act_s.duration = Some(
-                    act_s.duration.unwrap_or_default()
-                        + Duration::from_secs_f64(lap.total_time_seconds),
-                );
+                if let Some(notes) = lap.notes.as_deref().map(str::trim) {
+                    if !notes.is_empty() {
+                        act_s.lap_notes = Some(act_s.lap_notes.map_or_else(
+                            || notes.to_string(),
+                            |prev| format!("{prev}{LAP_NOTES_SEPARATOR}{notes}"),
+                        ));
+                    }
+                }
+                let duration = act_s.duration.unwrap_or_default();
+                let lap_duration = Duration::from_secs_f64(lap.total_time_seconds);
+                duration_overflow |= duration.checked_add(lap_duration).is_none();
+                act_s.duration = Some(duration.saturating_add(lap_duration));
                 act_s.distance_meters =
                     Some(act_s.distance_meters.unwrap_or(0.0) + lap.distance_meters);
                 act_s.calories = Some(act_s.calories.unwrap_or(0) + lap.calories);
@@ -162,30 +397,50 @@ impl TCXActivity {
                         act_s.maximum_speed = Some(max_speed);
                     }
                 }
+                // Check to see if max HR for the lap > current recorded max
+                if let Some(mhr) = lap.maximum_heart_rate {
+                    if act_s.maximum_heart_rate.unwrap_or(0.0) < mhr {
+                        act_s.maximum_heart_rate = Some(mhr);
+                    }
+                }
 
                 for track in &lap.tracks {
                     act_s.num_tracks = Some(act_s.num_tracks.unwrap_or(0) + 1);
+                    num_trackpoints += track.trackpoints.len();
                     act_s.num_trackpoints =
-                        Some(act_s.num_trackpoints.unwrap_or(0) + track.trackpoints.len() as u16);
-                    // Check to see if max HR for the lap > current recorded max
-                    if let Some(mhr) = lap.maximum_heart_rate {
-                        if act_s.maximum_heart_rate.unwrap_or(0.0) < mhr {
-                            act_s.maximum_heart_rate = Some(mhr);
-                        }
-                    }
+                        Some(u16::try_from(num_trackpoints).unwrap_or(u16::MAX));
 
                     for trackpoint in &track.trackpoints {
+                        if let Some(pos) = &trackpoint.position {
+                            gps_distance.add(Some(pos.latitude), Some(pos.longitude));
+                        }
+                        intervals.add(Some(trackpoint.time.with_timezone(&Local)));
+                        hr_samples.push((
+                            trackpoint.time.with_timezone(&Local),
+                            trackpoint.heart_rate.as_ref().map(|hr| hr.value),
+                        ));
+                        if let Some(distance) = trackpoint.distance_meters {
+                            split_points.push(SplitPoint {
+                                time: trackpoint.time.with_timezone(&Local),
+                                distance,
+                                heart_rate: trackpoint.heart_rate.as_ref().map(|hr| hr.value),
+                                altitude: None,
+                            });
+                        }
+
                         // Check if there is a cadence and if it's greater than the current max
                         if let Some(curr_cad) = trackpoint.cadence {
                             if act_s.maximum_cadence.unwrap_or(0) <= u16::from(curr_cad) {
                                 act_s.maximum_cadence = Some(u16::from(curr_cad));
                             }
                             cad += f64::from(curr_cad);
+                            num_cad += 1;
                         }
 
                         // Check if there is a heart rate and record it
                         if let Some(curr_hr) = &trackpoint.heart_rate {
                             hr += curr_hr.value;
+                            num_hr += 1;
                             if act_s.maximum_heart_rate.unwrap_or(0.0) < curr_hr.value {
                                 act_s.maximum_heart_rate = Some(curr_hr.value);
                             }
@@ -193,7 +448,7 @@ impl TCXActivity {
 
                         // Check if there is altitude data and calculate
                         if let Some(altitude) = trackpoint.altitude_meters {
-                            if act_s.max_altitude.unwrap_or(0.0) < altitude {
+                            if act_s.max_altitude.is_none_or(|max| max < altitude) {
                                 act_s.max_altitude = Some(altitude);
                             }
                         }
@@ -202,29 +457,163 @@ impl TCXActivity {
             }
         }
 
-        act_s.ascent_meters =
-            Some(act_s.max_altitude.unwrap_or(0.0) - act_s.start_altitude.unwrap_or(0.0));
-        if act_s.duration.is_some() {
-            act_s.average_speed = Some(
-                act_s.distance_meters.unwrap_or(0.0)
-                    / act_s.duration.unwrap_or_default().0.as_secs() as f64,
+        // Compare the recorded distance with the GPS track
+        act_s.gps_distance_meters = gps_distance.total();
+        act_s.distance_discrepancy_pct = act_s
+            .distance_meters
+            .zip(act_s.gps_distance_meters)
+            .and_then(|(recorded, gps)| distance_discrepancy(recorded, gps));
+        act_s
+            .warnings
+            .extend(miscalibration_warning(act_s.distance_discrepancy_pct));
+        act_s.environment = detect_environment(
+            FITEnvironment::Outdoor,
+            act_s.distance_meters,
+            &gps_distance,
+        );
+
+        // Compare the speed in the two halves
+        let pacing = pacing(&split_points);
+        act_s.speed_first_half_ms = pacing.map(|p| p.first_half_speed);
+        act_s.speed_second_half_ms = pacing.map(|p| p.second_half_speed);
+        act_s.pacing_index_pct = pacing.map(|p| p.index_pct);
+        act_s.pacing_split = pacing.map(|p| p.split);
+        act_s.decoupling_pace_pct = pace_decoupling(&split_points);
+
+        // How often the device recorded
+        let interval = intervals.summary();
+        act_s.recording_interval_sec = interval.map(|i| i.median_sec);
+        act_s.recording_every_second_pct = interval.map(|i| i.every_second_pct);
+        act_s.recording_mode = interval.map(|i| i.mode);
+
+        // The time in each heart rate zone
+        [
+            act_s.heart_rate_zone0_sec,
+            act_s.heart_rate_zone1_sec,
+            act_s.heart_rate_zone2_sec,
+            act_s.heart_rate_zone3_sec,
+            act_s.heart_rate_zone4_sec,
+        ] = time_in_hr_zones(&hr_samples, &hr_zones()).secs();
+
+        act_s.ascent_meters = act_s
+            .max_altitude
+            .zip(act_s.start_altitude)
+            .map(|(max, start)| max - start);
+        // Laps of zero seconds are common in old files, so the duration may be zero
+        act_s.average_speed = act_s.duration.and_then(|duration| {
+            ratio(
+                act_s.distance_meters.unwrap_or(0.0),
+                duration.0.as_secs() as f64,
+            )
+        });
+
+        // Calculate averages for the whole activity set, over the trackpoints that have a value
+        act_s.average_cadence = ratio(cad, num_cad as f64);
+        act_s.average_heart_rate = ratio(hr, num_hr as f64);
+        if num_trackpoints > usize::from(u16::MAX) {
+            act_s.warnings.push(format!(
+                "{num_trackpoints} trackpoints found. The number of trackpoints is capped at {}.",
+                u16::MAX
+            ));
+        }
+        if duration_overflow {
+            act_s.warnings.push(
+                "The lap times add up to more than can be held. The duration is capped."
+                    .to_string(),
             );
         }
-
-        // Calculate averages for the whole activity set
-        if act_s.num_trackpoints.unwrap_or(0) > 0 {
-            act_s.average_cadence = Some(cad / f64::from(act_s.num_trackpoints.unwrap_or(1)));
-            act_s.average_heart_rate = Some(hr / f64::from(act_s.num_trackpoints.unwrap_or(1)));
+        if let Some(n) = act_s.num_activities.filter(|n| *n > 1) {
+            act_s.warnings.push(format!(
+                "{n} activities found. The sport, start time and notes are taken from the last one."
+            ));
         }
 
         // If maximum_cadence = None then set it to the same as average
         if act_s.maximum_cadence.is_none() && act_s.average_cadence.is_some() {
             act_s.maximum_cadence = Some(act_s.average_cadence.unwrap_or(1.0) as u16);
         }
+        act_s.laps = TCXLap::from_activities(activities);
+        act_s.set_uuid();
+
         // return it
         act_s
     } // pub fn from_activities
 
+    /// Export the activity summary and the trackpoints together as a single JSON document named after the TCX file
+    /// with the extension replaced by `bundle.json`.
+    ///
+    /// # Arguments
+    ///
+    /// `trackpoints: &TCXTrackpointList` -- The trackpoints read from the same file as the activity summary.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if everything goes well.
+    ///
+    /// # Errors
+    ///
+    /// Creating the file can fail. Serializing to JSON can fail.
+    #[cfg(feature = "fs")]
+    pub fn export_bundle_json(
+        &self,
+        trackpoints: &TCXTrackpointList,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(filename) = self.filename.as_ref() else {
+            return Err("No filename specified in the ActivitySummary. Unable to export.".into());
+        };
+
+        write_output(&output_path(filename, "bundle.json"), |file| {
+            self.write_bundle_json(trackpoints, file)
+        })?;
+
+        Ok(())
+    }
+
+    /// Write the activity summary and the trackpoints together as a single JSON document to any writer, e.g. an HTTP
+    /// response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// - `trackpoints: &TCXTrackpointList` -- The trackpoints read from the same file as the activity summary.
+    /// - `writer: W` -- Where the JSON is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing or writing the JSON can fail.
+    pub fn write_bundle_json<W: Write>(
+        &self,
+        trackpoints: &TCXTrackpointList,
+        writer: W,
+    ) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(writer, &Rounded(&self.bundle(trackpoints)))?;
+        Ok(())
+    }
+
+    /// The activity summary and the trackpoints together as a single JSON document -- the same document
+    /// `export_bundle_json()` writes to a file.
+    ///
+    /// # Arguments
+    ///
+    /// `trackpoints: &TCXTrackpointList` -- The trackpoints read from the same file as the activity summary.
+    ///
+    /// # Errors
+    ///
+    /// Serializing to JSON can fail.
+    pub fn bundle_json(&self, trackpoints: &TCXTrackpointList) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(&Rounded(
+            &self.bundle(trackpoints),
+        ))?)
+    }
+
+    /// Collects the parts of the single-document JSON export.
+    fn bundle<'a>(&'a self, trackpoints: &'a TCXTrackpointList) -> TCXBundle<'a> {
+        TCXBundle {
+            session: self,
+            laps: &self.laps,
+            trackpoints: &trackpoints.trackpoints,
+        }
+    }
+
     /// Export the activity summary as a JSON file
     ///
     /// # Arguments
@@ -242,21 +631,66 @@ impl TCXActivity {
     /// # Panics
     ///
     /// None.
+    #[cfg(feature = "fs")]
     pub fn export_json(&self) -> Result<(), Box<dyn Error>> {
         if self.filename.is_none() {
             return Err("No filename specified in the ActivitySummary. Unable to export.".into());
         }
 
-        let out_file = set_extension(
-            self.filename
-                .as_ref()
-                .unwrap_or(&"tcx_activity".to_string()),
+        let out_file = output_path(
+            self.filename.as_deref().unwrap_or("tcx_activity"),
             "activity.json",
         );
-        serde_json::to_writer_pretty(
-            &std::fs::File::create(std::path::PathBuf::from(&out_file))?,
-            &self,
-        )?;
+        write_output(&out_file, |file| self.write_json(file))?;
+
+        Ok(())
+    }
+
+    /// Write the activity summary as JSON to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the JSON is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing or writing the JSON can fail.
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        write_summary_json(self, writer)
+    }
+
+    /// Export the lap summaries to a CSV file named after the TCX file with the extension replaced by `laps.csv`.
+    ///
+    /// # Errors
+    ///
+    /// Creating the file can fail. Serialization may fail. Writer flush may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_laps_csv(&self) -> Result<(), Box<dyn Error>> {
+        let Some(filename) = self.filename.as_ref() else {
+            return Err("No filename specified in the ActivitySummary. Unable to export.".into());
+        };
+
+        write_output(&output_path(filename, "laps.csv"), |file| {
+            self.write_laps_csv(file)
+        })
+    }
+
+    /// Write the lap summaries as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Serialization may fail. Writer flush may fail.
+    pub fn write_laps_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut writer = ColumnWriter::new(writer, CsvFile::TcxLaps);
+        writer.write_header()?;
+        for lap in &self.laps {
+            writer.serialize(Rounded(lap))?;
+        }
+        writer.flush()?;
 
         Ok(())
     }
@@ -279,12 +713,34 @@ impl TCXActivity {
     ///
     /// None.
     pub fn print(&self, _detailed: bool) {
-        let unknown = String::new();
+        let unknown = placeholder("");
 
         println!(
             "\nFile:                  {}",
             self.filename.as_ref().unwrap_or(&unknown)
         );
+        println!(
+            "Manufacturer:          {}",
+            self.manufacturer.as_ref().unwrap_or(&unknown)
+        );
+        println!(
+            "Product:               {}",
+            self.product.as_ref().unwrap_or(&unknown)
+        );
+        println!(
+            "Serial number:         {}",
+            self.serial_number
+                .as_deref()
+                .map_or_else(|| unknown.clone(), output_serial)
+        );
+        println!(
+            "Software version:      {}",
+            self.software_version.as_ref().unwrap_or(&unknown)
+        );
+        println!(
+            "Author:                {}",
+            self.author.as_ref().unwrap_or(&unknown)
+        );
         println!(
             "Sport:                 {}",
             self.sport.as_ref().unwrap_or(&unknown)
@@ -301,6 +757,11 @@ impl TCXActivity {
             "Notes:                {}",
             self.notes.as_ref().unwrap_or(&unknown)
         );
+        println!(
+            "Lap notes:            {}",
+            self.lap_notes.as_ref().unwrap_or(&unknown)
+        );
+        println!("Environment:          {:>9}", self.environment.to_string());
         println!(
             "Activities:           {:>9}",
             self.num_activities.unwrap_or_default()
@@ -318,29 +779,63 @@ impl TCXActivity {
             self.num_trackpoints.unwrap_or_default()
         );
         println!(
-            "Distance (m):         {:>9.2}",
-            self.distance_meters.unwrap_or_default()
+            "{:<22}{:>9.2}",
+            label("Distance", Measure::Distance),
+            converted(Measure::Distance, self.distance_meters.unwrap_or_default())
+        );
+        println!(
+            "{:<22}{:>9.2}",
+            label("GPS distance", Measure::Distance),
+            converted(
+                Measure::Distance,
+                self.gps_distance_meters.unwrap_or_default()
+            )
         );
         println!(
-            "Start altitude (m):   {:>9.2}",
-            self.start_altitude.unwrap_or_default()
+            "Distance discr. (%):  {:>9.2}",
+            self.distance_discrepancy_pct.unwrap_or_default()
         );
+        if let (Some(index), Some(split)) = (self.pacing_index_pct, self.pacing_split) {
+            println!("Pacing index (%):     {index:>9.2} ({split} split)");
+        }
+        if let Some(pct) = self.decoupling_pace_pct {
+            println!("Decoupling Pa:HR (%): {pct:>9.2}");
+        }
+        if let (Some(interval), Some(mode)) = (self.recording_interval_sec, self.recording_mode) {
+            println!("Recording int. (s):   {interval:>9.0} ({mode})");
+        }
         println!(
-            "Max altitude (m):     {:>9.2}",
-            self.max_altitude.unwrap_or_default()
+            "{:<22}{:>9.2}",
+            label("Start altitude", Measure::Length),
+            converted(Measure::Length, self.start_altitude.unwrap_or_default())
         );
         println!(
-            "Ascent (m):           {:>9.2}",
-            self.ascent_meters.unwrap_or_default()
+            "{:<22}{:>9.2}",
+            label("Max altitude", Measure::Length),
+            converted(Measure::Length, self.max_altitude.unwrap_or_default())
         );
         println!(
-            "Avg Speed (m/s):      {:>9.2}",
-            self.average_speed.unwrap_or_default()
+            "{:<22}{:>9.2}",
+            label("Ascent", Measure::Length),
+            converted(Measure::Length, self.ascent_meters.unwrap_or_default())
         );
         println!(
-            "Max Speed (m/s):      {:>9.2}",
-            self.maximum_speed.unwrap_or_default()
+            "{:<22}{:>9.2}",
+            label("Avg Speed", Measure::Speed),
+            converted(Measure::Speed, self.average_speed.unwrap_or_default())
         );
+        println!(
+            "{:<22}{:>9.2}",
+            label("Max Speed", Measure::Speed),
+            converted(Measure::Speed, self.maximum_speed.unwrap_or_default())
+        );
+        if let Some((pace_label, pace)) = pace_label(
+            "Avg Pace",
+            self.sport.as_deref(),
+            self.average_speed.unwrap_or_default(),
+        ) {
+            println!("{pace_label:<22}{:>9}", pace.to_string());
+        }
         println!(
             "Calories Burned:      {:>9}",
             self.calories.unwrap_or_default()
@@ -371,6 +866,26 @@ pub struct TCXActivitiesList {
 }
 
 impl TCXActivitiesList {
+    /// When each activity took place and what recorded it, for finding the activities that overlap in time.
+    /// Activities without a start time or duration are left out.
+    #[must_use]
+    pub fn time_windows(&self) -> Vec<ActivityWindow> {
+        self.activities
+            .iter()
+            .filter_map(|act| {
+                ActivityWindow::new(
+                    act.filename.as_deref().unwrap_or_default(),
+                    act.serial_number.as_deref().or(act.product.as_deref()),
+                    act.start_time
+                        .as_deref()
+                        .and_then(|st| DateTime::parse_from_rfc3339(st).ok())
+                        .map(|st| st.with_timezone(&Local)),
+                    act.duration,
+                )
+            })
+            .collect()
+    }
+
     /// Export the activity summary as a JSON file
     ///
     /// # Arguments
@@ -388,13 +903,22 @@ impl TCXActivitiesList {
     /// # Panics
     ///
     /// None.
+    #[cfg(feature = "fs")]
     pub fn export_json(&self, filename: &str) -> Result<(), Box<dyn Error>> {
-        serde_json::to_writer_pretty(
-            &std::fs::File::create(std::path::PathBuf::from(filename))?,
-            &self,
-        )?;
+        write_atomically(Path::new(filename), |file| self.write_json(file))
+    }
 
-        Ok(())
+    /// Write the activity summaries as JSON to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the JSON is written.
+    ///
+    /// # Errors
+    ///
+    /// Serializing or writing the JSON can fail.
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        write_summary_json(self, writer)
     }
 
     /// Export the activity summary as a CSV file
@@ -414,29 +938,94 @@ impl TCXActivitiesList {
     /// # Panics
     ///
     /// None.
+    #[cfg(feature = "fs")]
     pub fn export_csv(&self, filename: &str) -> Result<(), Box<dyn Error>> {
-        // Create a buffer for the CSV
-        let outfile = PathBuf::from(filename);
-        let mut writer = WriterBuilder::new().has_headers(true).from_path(&outfile)?;
+        write_atomically(Path::new(filename), |file| self.write_csv(file))
+    }
 
-        for activity in &self.activities {
-            log::trace!("ActivitiesList::export_csv() -- serializing: {activity:?}");
-            writer.serialize(activity)?;
+    /// Write the activity summaries as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Serialization may fail. Writer flush may fail.
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        write_csv(writer, &self.activities.iter().collect::<Vec<_>>())
+    }
+
+    /// Export the activity summary as one CSV file per sport, year or device. The files are named after the summary
+    /// file, e.g. `tcx-activities.running.csv`.
+    ///
+    /// # Arguments
+    ///
+    /// - `filename: &str` -- The name of the summary file the split file names are based on.
+    /// - `split: SummarySplit` -- What to split the summary by.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Vec<String>, Box<dyn Error>>` -- The names of the files written.
+    ///
+    /// # Errors
+    ///
+    /// The `WriterBuilder` may fail. Serialization may fail. Writer flush may fail.
+    #[cfg(feature = "fs")]
+    pub fn export_csv_split(
+        &self,
+        filename: &str,
+        split: SummarySplit,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let groups = group_by(&self.activities, |activity| {
+            split.key(
+                activity.sport.as_deref(),
+                activity
+                    .start_time
+                    .as_deref()
+                    .and_then(|st| DateTime::parse_from_rfc3339(st).ok())
+                    .map(|st| st.year()),
+                activity.product.as_deref(),
+            )
+        });
+
+        let mut written = Vec::with_capacity(groups.len());
+        for (key, activities) in groups {
+            let split_file = split_filename(filename, &key);
+            write_atomically(Path::new(&split_file), |file| write_csv(file, &activities))?;
+            written.push(split_file);
         }
 
-        log::trace!("ActivitiesList::export_csv() -- information to be written: {writer:?}");
+        Ok(written)
+    }
+}
 
-        // Write the file
-        writer.flush()?;
+/// Writes the activities as a summary CSV.
+fn write_csv<W: Write>(writer: W, activities: &[&TCXActivity]) -> Result<(), Box<dyn Error>> {
+    // Create a buffer for the CSV
+    let mut writer = ColumnWriter::new(writer, CsvFile::TcxSummary);
 
-        Ok(())
+    for activity in activities {
+        log::trace!("ActivitiesList::export_csv() -- serializing: {activity:?}");
+        writer.serialize(Rounded(activity))?;
     }
+
+    log::trace!(
+        "ActivitiesList::export_csv() -- {} activities to be written.",
+        activities.len()
+    );
+
+    // Write the file
+    writer.flush()?;
+
+    Ok(())
 }
 
 #[cfg(test)]
-///
+/// Tests for the activity module
 mod tests {
     use super::*;
+    use crate::tcx::quirks::FORERUNNER_305;
     use assay::assay;
 
     // TODO: Refactor to conform with the `float_cmp` lint: https://rust-lang.github.io/rust-clippy/master/index.html#/float_cmp
@@ -576,8 +1165,8 @@ mod tests {
         assert!(act.calories.is_some());
         assert!(act.average_heart_rate.is_some());
         assert!(act.maximum_heart_rate.is_some());
-        assert!(act.average_cadence.is_some());
-        assert!(act.maximum_cadence.is_some());
+        assert!(act.average_cadence.is_none()); // No cadence in the file
+        assert!(act.maximum_cadence.is_none());
 
         // Verify the actual data - note that the act.notes section is missing since it's None.
         assert_eq!(
@@ -600,7 +1189,95 @@ mod tests {
         assert_eq!(act.calories.unwrap(), 338);
         assert_eq!(act.average_heart_rate.unwrap(), 137.156_226_415_094_35);
         assert_eq!(act.maximum_heart_rate.unwrap(), 170.0);
-        assert_eq!(act.average_cadence.unwrap(), 0.0);
-        assert_eq!(act.maximum_cadence.unwrap(), 0);
+    }
+
+    #[test]
+    /// Test the summary of a file from an old Forerunner, with a lap without a track and trackpoints without a
+    /// position, altitude or heart rate
+    fn test_forerunner_305() {
+        let act = TCXActivity::from_reader(FORERUNNER_305.as_bytes(), "forerunner.tcx").unwrap();
+
+        assert_eq!(act.num_laps, Some(2));
+        assert_eq!(act.num_tracks, Some(1));
+        assert_eq!(act.num_trackpoints, Some(3));
+        assert_eq!(act.duration.unwrap().0.as_secs(), 20);
+        assert_eq!(act.average_speed, Some(3.0));
+        assert_eq!(act.average_heart_rate, Some(130.0));
+        assert_eq!(act.maximum_heart_rate, Some(140.0));
+        assert!(act.average_cadence.is_none());
+        assert!(act.maximum_cadence.is_none());
+        assert_eq!(act.start_altitude, Some(12.0));
+        assert_eq!(act.ascent_meters, Some(3.0));
+        assert_eq!(act.product.as_deref(), Some("Forerunner305"));
+
+        let trackpoints = TCXTrackpointList::from_reader(FORERUNNER_305.as_bytes()).unwrap();
+        assert_eq!(trackpoints.trackpoints.len(), 3);
+        assert!(trackpoints.trackpoints[0].latitude.is_none());
+    }
+
+    #[test]
+    /// Test that laps of zero seconds and laps without trackpoints don't give nonsense averages
+    fn test_empty_laps() {
+        let act = TCXActivity::from_reader(
+            FORERUNNER_305
+                .replace(
+                    "<TotalTimeSeconds>20.0000000",
+                    "<TotalTimeSeconds>0.0000000",
+                )
+                .as_bytes(),
+            "forerunner.tcx",
+        )
+        .unwrap();
+        assert!(act.average_speed.is_none());
+
+        let start = FORERUNNER_305.find("<Track>").unwrap();
+        let end = FORERUNNER_305.rfind("</Track>").unwrap() + "</Track>".len();
+        let trackless = format!("{}{}", &FORERUNNER_305[..start], &FORERUNNER_305[end..]);
+        let act = TCXActivity::from_reader(trackless.as_bytes(), "forerunner.tcx").unwrap();
+        assert_eq!(act.num_laps, Some(2));
+        assert!(act.num_trackpoints.is_none());
+        assert!(act.average_heart_rate.is_none());
+        assert!(act.start_altitude.is_none());
+        assert!(act.ascent_meters.is_none());
+        assert_eq!(act.average_speed, Some(3.0));
+    }
+
+    #[test]
+    /// Test that the notes and the creator survive the summary CSV, commas, quotes, line breaks and all
+    fn test_notes_in_csv() {
+        let tcx = std::fs::read_to_string("../data/running.tcx")
+            .unwrap()
+            .replace(
+                "</Track>\n   </Lap>\n  </Activity>",
+                "</Track>\n<Notes>Hill, \"steep\"</Notes></Lap>\n<Notes>Easy run, \"felt good\"\nthen rain</Notes></Activity>",
+            );
+        let act = TCXActivity::from_reader(tcx.as_bytes(), "running.tcx").unwrap();
+        assert_eq!(
+            act.notes.as_deref(),
+            Some("Easy run, \"felt good\"\nthen rain")
+        );
+        assert_eq!(act.lap_notes.as_deref(), Some("Hill, \"steep\""));
+        assert_eq!(act.product.as_deref(), Some("Wahoo Fitness iOS"));
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &[&act]).unwrap();
+        let mut reader = csv::ReaderBuilder::new().from_reader(csv.as_slice());
+        let headers = reader.headers().unwrap().clone();
+        let row = reader.records().next().unwrap().unwrap();
+        let column = |name: &str| &row[headers.iter().position(|h| h == name).unwrap()];
+
+        assert_eq!(column("notes"), act.notes.as_deref().unwrap());
+        assert_eq!(column("lap_notes"), act.lap_notes.as_deref().unwrap());
+        assert_eq!(column("product"), "Wahoo Fitness iOS");
+    }
+
+    #[test]
+    /// Test that files cut short give an error rather than a panic
+    fn test_from_reader_truncated() {
+        let bytes = std::fs::read("../data/running.tcx").unwrap();
+
+        for len in (0..bytes.len()).step_by(15_013) {
+            assert!(TCXActivity::from_reader(&bytes[..len], "truncated.tcx").is_err());
+        }
     }
 }
@@ -0,0 +1,209 @@
+//! Defines the `TCXCreator` struct which holds the device and application information from a TCX file.
+//!
+//! The `tcx` crate skips the `<Creator>` and `<Author>` elements, so they are read separately here.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::io::Read;
+use xml::reader::{EventReader, XmlEvent};
+
+#[cfg(feature = "fs")]
+use crate::input::open_input;
+
+/// Manufacturers that put their name at the start of the device name, e.g. "Wahoo Fitness iOS".
+const KNOWN_MANUFACTURERS: [&str; 8] = [
+    "Garmin",
+    "Wahoo",
+    "Polar",
+    "Suunto",
+    "Coros",
+    "Bryton",
+    "Hammerhead",
+    "Apple",
+];
+
+/// Garmin part numbers start with this prefix.
+const GARMIN_PART_NUMBER_PREFIX: &str = "006-";
+
+/// Device and application information from the `<Creator>` and `<Author>` elements of a TCX file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct TCXCreator {
+    /// The device name, e.g. "fenix 7X Sapphire Solar".
+    pub name: Option<String>,
+
+    /// The unit ID of the device, which is its serial number.
+    pub unit_id: Option<String>,
+
+    /// The product ID of the device.
+    pub product_id: Option<String>,
+
+    /// The device software version, e.g. "7.20".
+    pub version: Option<String>,
+
+    /// The name of the application that wrote the file, e.g. "Connect Api".
+    pub author: Option<String>,
+
+    /// The part number of the application that wrote the file.
+    pub author_part_number: Option<String>,
+}
+
+impl TCXCreator {
+    /// Reads the creator information from the TCX file.
+    ///
+    /// # Arguments
+    ///
+    /// `filename: &str` -- The name of the TCX file to be read.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Self, Box<dyn Error>>` -- The creator information. Fields not found in the file are `None`.
+    ///
+    /// # Errors
+    ///
+    /// Opening the file may fail. Parsing the XML may fail.
+    #[cfg(feature = "fs")]
+    pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_reader(BufReader::new(open_input(filename)?))
+    }
+
+    /// Reads the creator information from TCX XML. Only the `<Creator>` of the first activity is used.
+    ///
+    /// # Arguments
+    ///
+    /// `reader: R` -- Where to read the XML from.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Self, Box<dyn Error>>` -- The creator information. Fields not found in the XML are `None`.
+    ///
+    /// # Errors
+    ///
+    /// Parsing the XML may fail.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut creator = Self::default();
+        let mut path: Vec<String> = Vec::new();
+        let mut creators_seen = 0;
+        let mut version_major = None;
+        let mut version_minor = None;
+
+        for event in EventReader::new(reader) {
+            match event? {
+                XmlEvent::StartElement { name, .. } => {
+                    if name.local_name == "Creator" {
+                        creators_seen += 1;
+                    }
+                    path.push(name.local_name);
+                }
+                XmlEvent::EndElement { .. } => {
+                    path.pop();
+                }
+                XmlEvent::Characters(text) => {
+                    let text = text.trim().to_string();
+                    let tail: Vec<&str> = path.iter().rev().take(3).map(String::as_str).collect();
+
+                    match tail.as_slice() {
+                        [field, "Creator", "Activity"] if creators_seen == 1 => match *field {
+                            "Name" => creator.name = Some(text),
+                            "UnitId" => creator.unit_id = Some(text),
+                            "ProductID" => creator.product_id = Some(text),
+                            _ => (),
+                        },
+                        [field, "Version", "Creator"] if creators_seen == 1 => match *field {
+                            "VersionMajor" => version_major = Some(text),
+                            "VersionMinor" => version_minor = Some(text),
+                            _ => (),
+                        },
+                        ["Name", "Author", "TrainingCenterDatabase"] => {
+                            creator.author = Some(text);
+                        }
+                        ["PartNumber", "Author", "TrainingCenterDatabase"] => {
+                            creator.author_part_number = Some(text);
+                        }
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        creator.version = version_major.map(|major| {
+            format!(
+                "{major}.{}",
+                version_minor.unwrap_or_else(|| "0".to_string())
+            )
+        });
+
+        log::trace!("TCXCreator::from_reader() -- creator = {creator:?}");
+        Ok(creator)
+    }
+
+    /// Works out the device manufacturer. TCX files don't name the manufacturer directly, so this is taken from the
+    /// start of the device name, or from the part number of the application that wrote the file.
+    ///
+    /// # Returns
+    ///
+    /// `Option<String>` -- The manufacturer, or `None` if it can't be worked out.
+    #[must_use]
+    pub fn manufacturer(&self) -> Option<String> {
+        let from_name = self.name.as_ref().and_then(|name| {
+            let first = name.split_whitespace().next()?;
+            KNOWN_MANUFACTURERS
+                .iter()
+                .find(|m| m.eq_ignore_ascii_case(first))
+                .map(|m| (*m).to_string())
+        });
+
+        from_name.or_else(|| {
+            self.author_part_number
+                .as_ref()
+                .filter(|pn| pn.starts_with(GARMIN_PART_NUMBER_PREFIX))
+                .map(|_| "Garmin".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+/// Tests for the creator module
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test reading the creator from a Garmin file
+    fn test_from_file_garmin() {
+        let creator = TCXCreator::from_file("../data/activity_8250797631.tcx").unwrap();
+
+        assert_eq!(creator.name.as_deref(), Some("fenix 7X Sapphire Solar"));
+        assert_eq!(creator.unit_id.as_deref(), Some("3400586606"));
+        assert_eq!(creator.product_id.as_deref(), Some("3907"));
+        assert_eq!(creator.version.as_deref(), Some("7.20"));
+        assert_eq!(creator.author.as_deref(), Some("Connect Api"));
+        assert_eq!(creator.manufacturer().as_deref(), Some("Garmin"));
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test reading the creator from a Wahoo file, which has no product ID or author
+    fn test_from_file_wahoo() {
+        let creator = TCXCreator::from_file("../data/running.tcx").unwrap();
+
+        assert_eq!(creator.name.as_deref(), Some("Wahoo Fitness iOS"));
+        assert_eq!(creator.unit_id.as_deref(), Some("0123456789"));
+        assert!(creator.product_id.is_none());
+        assert!(creator.author.is_none());
+        assert_eq!(creator.manufacturer().as_deref(), Some("Wahoo"));
+    }
+
+    #[test]
+    /// Test a file without any creator information
+    fn test_no_creator() {
+        let xml = "<TrainingCenterDatabase><Activities/></TrainingCenterDatabase>";
+        let creator = TCXCreator::from_reader(xml.as_bytes()).unwrap();
+
+        assert_eq!(creator, TCXCreator::default());
+        assert!(creator.manufacturer().is_none());
+    }
+}
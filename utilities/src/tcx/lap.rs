@@ -0,0 +1,210 @@
+//! Defines the `TCXLap` struct which holds the summary of each lap in a TCX file, and associated functions.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::analysis::derived::{mean, ratio};
+use crate::analysis::hr_zones::{hr_zones, time_in_hr_zones};
+use crate::{Duration, FITHrZones};
+
+/// Summary information per lap
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct TCXLap {
+    /// The number of the activity in the file that the lap belongs to
+    #[serde(rename = "activity_num")]
+    pub activity_num: usize,
+
+    /// Lap number within the activity
+    #[serde(rename = "lap_num")]
+    pub lap_num: usize,
+
+    /// Sport
+    #[serde(rename = "sport")]
+    pub sport: Option<String>,
+
+    /// The time of the first trackpoint in the lap
+    #[serde(rename = "start_time")]
+    pub start_time: Option<DateTime<Local>>,
+
+    /// Lap duration in seconds
+    #[serde(rename = "duration")]
+    pub duration: Option<Duration>,
+
+    /// Distance covered during the lap in meters
+    #[serde(rename = "distance_meters")]
+    pub distance_meters: Option<f64>,
+
+    /// Average speed in meters per second
+    #[serde(rename = "average_speed")]
+    pub average_speed: Option<f64>,
+
+    /// Maximum speed in meters per second
+    #[serde(rename = "maximum_speed")]
+    pub maximum_speed: Option<f64>,
+
+    /// Calories burned during the lap
+    #[serde(rename = "calories")]
+    pub calories: Option<u16>,
+
+    /// Average heart rate over the trackpoints that have one
+    #[serde(rename = "average_heart_rate")]
+    pub average_heart_rate: Option<f64>,
+
+    /// Maximum heart rate
+    #[serde(rename = "maximum_heart_rate")]
+    pub maximum_heart_rate: Option<f64>,
+
+    /// Average cadence, from the lap or else over the trackpoints that have one
+    #[serde(rename = "average_cadence")]
+    pub average_cadence: Option<f64>,
+
+    /// Maximum cadence
+    #[serde(rename = "maximum_cadence")]
+    pub maximum_cadence: Option<u16>,
+
+    /// Whether the lap was `Active` or `Resting`
+    #[serde(rename = "intensity")]
+    pub intensity: Option<String>,
+
+    /// What ended the lap, e.g. `Manual`, `Distance` or `Time`
+    #[serde(rename = "trigger_method")]
+    pub trigger_method: Option<String>,
+
+    /// Number of trackpoints in the lap
+    #[serde(rename = "num_trackpoints")]
+    pub num_trackpoints: usize,
+
+    /// Notes on the lap - if there are any
+    #[serde(rename = "notes")]
+    pub notes: Option<String>,
+
+    /// The UUID of the activity the lap belongs to.
+    #[serde(rename = "activity_uuid")]
+    pub activity_uuid: Option<Uuid>,
+
+    /// The time spent in each heart rate zone, worked out from the trackpoints with the zones set by
+    /// `Settings::set_hr_zones()`.
+    #[serde(rename = "time_in_hr_zones")]
+    pub time_in_hr_zones: FITHrZones,
+}
+
+impl TCXLap {
+    /// Creates the lap summary from a lap read from the TCX file. The heart rate, cadence and time in each heart rate
+    /// zone are worked out from the trackpoints, since the `tcx` crate doesn't read them from the lap.
+    ///
+    /// # Arguments
+    ///
+    /// - `lap: &tcx::ActivityLap` -- The lap from the TCX file.
+    /// - `sport: &str` -- The sport of the activity the lap belongs to.
+    ///
+    /// # Returns
+    ///
+    /// `Self` -- The lap summary. The activity and lap numbers and the activity UUID aren't set.
+    #[must_use]
+    pub fn from_tcx_lap(lap: &tcx::ActivityLap, sport: &str) -> Self {
+        let trackpoints = || lap.tracks.iter().flat_map(|track| &track.trackpoints);
+        let heart_rates: Vec<f64> = trackpoints()
+            .filter_map(|tp| tp.heart_rate.as_ref().map(|hr| hr.value))
+            .collect();
+        let cadences: Vec<u8> = trackpoints().filter_map(|tp| tp.cadence).collect();
+        let hr_samples: Vec<_> = trackpoints()
+            .map(|tp| {
+                (
+                    tp.time.with_timezone(&Local),
+                    tp.heart_rate.as_ref().map(|hr| hr.value),
+                )
+            })
+            .collect();
+
+        Self {
+            sport: Some(sport.to_string()),
+            start_time: trackpoints().next().map(|tp| tp.time.with_timezone(&Local)),
+            duration: Some(Duration::from_secs_f64(lap.total_time_seconds)),
+            distance_meters: Some(lap.distance_meters),
+            average_speed: ratio(lap.distance_meters, lap.total_time_seconds),
+            maximum_speed: lap.maximum_speed,
+            calories: Some(lap.calories),
+            average_heart_rate: mean(&heart_rates),
+            maximum_heart_rate: heart_rates
+                .iter()
+                .copied()
+                .reduce(f64::max)
+                .or(lap.maximum_heart_rate),
+            average_cadence: lap
+                .cadence
+                .map(f64::from)
+                .or_else(|| mean(&cadences.iter().map(|c| f64::from(*c)).collect::<Vec<_>>())),
+            maximum_cadence: cadences.iter().max().map(|c| u16::from(*c)),
+            intensity: lap.intensity.as_ref().map(|i| format!("{i:?}")),
+            trigger_method: lap.trigger_method.as_ref().map(|t| format!("{t:?}")),
+            num_trackpoints: trackpoints().count(),
+            notes: lap
+                .notes
+                .as_deref()
+                .map(str::trim)
+                .filter(|notes| !notes.is_empty())
+                .map(String::from),
+            time_in_hr_zones: time_in_hr_zones(&hr_samples, &hr_zones()),
+            ..Self::default()
+        }
+    }
+
+    /// Creates the lap summaries of all the activities in the TCX file, numbering the activities and the laps within
+    /// each activity from 1.
+    #[must_use]
+    pub fn from_activities(activities: &tcx::Activities) -> Vec<Self> {
+        let mut laps = Vec::new();
+        for (activity_num, activity) in activities.activities.iter().enumerate() {
+            for (lap_num, lap) in activity.laps.iter().enumerate() {
+                laps.push(Self {
+                    activity_num: activity_num + 1,
+                    lap_num: lap_num + 1,
+                    ..Self::from_tcx_lap(lap, &activity.sport)
+                });
+            }
+        }
+
+        laps
+    }
+}
+
+#[cfg(test)]
+/// Tests for the lap module
+mod tests {
+    use super::*;
+    use crate::tcx::quirks::{read_tcx, FORERUNNER_305};
+
+    #[test]
+    /// Test the lap summaries, including a lap without trackpoints
+    fn test_from_activities() {
+        let tcdb = read_tcx(FORERUNNER_305.as_bytes()).unwrap();
+        let laps = TCXLap::from_activities(&tcdb.activities.unwrap());
+        assert_eq!(laps.len(), 2);
+
+        assert_eq!(laps[0].lap_num, 1);
+        assert_eq!(laps[0].num_trackpoints, 0);
+        assert!(laps[0].start_time.is_none());
+        assert!(laps[0].average_speed.is_none());
+        assert!(laps[0].average_heart_rate.is_none());
+        assert_eq!(laps[0].time_in_hr_zones, FITHrZones::default());
+
+        assert_eq!(laps[1].activity_num, 1);
+        assert_eq!(laps[1].lap_num, 2);
+        assert_eq!(laps[1].sport.as_deref(), Some("Running"));
+        assert_eq!(laps[1].num_trackpoints, 3);
+        assert_eq!(laps[1].average_speed, Some(3.0));
+        assert_eq!(laps[1].calories, Some(3));
+        assert_eq!(laps[1].average_heart_rate, Some(130.0));
+        assert_eq!(laps[1].maximum_heart_rate, Some(140.0));
+        assert!(laps[1].average_cadence.is_none());
+        assert_eq!(laps[1].intensity.as_deref(), Some("Active"));
+        assert_eq!(laps[1].trigger_method.as_deref(), Some("Manual"));
+
+        // 120 bpm is in zone 1 with the default zones, and the trackpoint without a heart rate counts for nothing
+        let zones = laps[1].time_in_hr_zones;
+        assert_eq!(zones.hr_zone_1, Some(Duration::from_secs_f64(10.0)));
+        assert_eq!(zones.hr_zone_2, Some(Duration::from_secs_f64(0.0)));
+    }
+}
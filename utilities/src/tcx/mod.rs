@@ -1,3 +1,8 @@
 pub mod activity;
+pub mod creator;
+pub mod lap;
+pub mod quirks;
+pub mod to_gpx;
+#[cfg(feature = "fs")]
 pub mod to_hashmap;
 pub mod trackpoints;
@@ -0,0 +1,256 @@
+//! Reads TCX files, working around the quirks of those written by old Garmin devices such as the Forerunner 305.
+//!
+//! These files may have laps without a `<Track>`, e.g. when the lap button was pressed twice, and tracks without any
+//! trackpoints. The `tcx` crate can't read either, so such files are rewritten before being read again: tracks without
+//! trackpoints are dropped, and laps without tracks are given a placeholder track which is removed again once the file
+//! has been read. Files without these quirks are read as they are.
+
+use std::error::Error;
+use std::io::{BufReader, Read};
+use xml::reader::{EventReader, XmlEvent};
+use xml::writer::EmitterConfig;
+
+/// The time of the trackpoint in the placeholder tracks. It is never used, since the placeholders are removed again.
+const PLACEHOLDER_TIME: &str = "1970-01-01T00:00:00Z";
+
+/// The index of a lap: the index of its activity, and of the lap within the activity.
+type LapIndex = (usize, usize);
+
+/// Reads a TCX file and works out the heart rates of the laps.
+///
+/// # Arguments
+///
+/// `reader: R` -- The TCX data.
+///
+/// # Returns
+///
+/// `Result<tcx::TrainingCenterDatabase, Box<dyn Error>>` -- The contents of the file. Laps without any trackpoints
+/// have no tracks.
+///
+/// # Errors
+///
+/// Reading or parsing the data may fail.
+pub fn read_tcx<R: Read>(mut reader: R) -> Result<tcx::TrainingCenterDatabase, Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+
+    let mut tcdb = match tcx::read(&mut BufReader::new(buffer.as_slice())) {
+        Ok(tcdb) => tcdb,
+        Err(err) => {
+            // Only keep the rewritten file if it can be read. Otherwise the error is about the original file.
+            let (rewritten, empty_laps) = fill_empty_laps(&buffer)?;
+            let Ok(mut tcdb) = tcx::read(&mut BufReader::new(rewritten.as_slice())) else {
+                return Err(err.into());
+            };
+            log::debug!(
+                "quirks::read_tcx() -- {} laps without trackpoints",
+                empty_laps.len()
+            );
+            clear_placeholder_tracks(&mut tcdb, &empty_laps);
+            tcdb
+        }
+    };
+
+    // The heart rates can only be worked out if there are activities
+    if tcdb.activities.is_some() {
+        tcdb.calc_heartrates();
+    }
+
+    Ok(tcdb)
+}
+
+/// Rewrites the TCX data so the `tcx` crate can read it, dropping the tracks without trackpoints and giving the laps
+/// without trackpoints a placeholder track.
+///
+/// # Returns
+///
+/// `Result<(Vec<u8>, Vec<LapIndex>), Box<dyn Error>>` -- The rewritten data, and the index of each lap given a
+/// placeholder track.
+///
+/// # Errors
+///
+/// Parsing or writing the XML may fail.
+fn fill_empty_laps(data: &[u8]) -> Result<(Vec<u8>, Vec<LapIndex>), Box<dyn Error>> {
+    let mut output = Vec::new();
+    let mut writer = EmitterConfig::new().create_writer(&mut output);
+    let mut path: Vec<String> = Vec::new();
+    let mut track: Option<(Vec<XmlEvent>, bool)> = None; // the events of the current track, and if it has trackpoints
+    let mut lap_has_trackpoints = false;
+    let mut empty_laps = Vec::new();
+    let (mut activity_index, mut lap_index) = (None, 0);
+
+    for event in EventReader::new(data) {
+        let event = event?;
+        match &event {
+            XmlEvent::StartElement { name, .. } => {
+                match (name.local_name.as_str(), path.last().map(String::as_str)) {
+                    ("Activity", Some("Activities")) => {
+                        activity_index = Some(activity_index.map_or(0, |i| i + 1));
+                        lap_index = 0;
+                    }
+                    ("Lap", Some("Activity")) => lap_has_trackpoints = false,
+                    ("Track", Some("Lap")) => track = Some((Vec::new(), false)),
+                    ("Trackpoint", Some("Track")) => {
+                        if let Some((_, has_trackpoints)) = track.as_mut() {
+                            *has_trackpoints = true;
+                        }
+                    }
+                    _ => (),
+                }
+                path.push(name.local_name.clone());
+            }
+            XmlEvent::EndElement { name } => {
+                path.pop();
+                match (name.local_name.as_str(), path.last().map(String::as_str)) {
+                    ("Track", Some("Lap")) => {
+                        if let Some((mut events, has_trackpoints)) = track.take() {
+                            if has_trackpoints {
+                                events.push(event);
+                                for event in &events {
+                                    if let Some(event) = event.as_writer_event() {
+                                        writer.write(event)?;
+                                    }
+                                }
+                                lap_has_trackpoints = true;
+                            }
+                        }
+                        continue;
+                    }
+                    ("Lap", Some("Activity")) => {
+                        if !lap_has_trackpoints {
+                            write_placeholder_track(&mut writer)?;
+                            empty_laps.push((activity_index.unwrap_or_default(), lap_index));
+                        }
+                        lap_index += 1;
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+
+        if let Some((events, _)) = track.as_mut() {
+            events.push(event);
+        } else if let Some(event) = event.as_writer_event() {
+            writer.write(event)?;
+        }
+    }
+
+    Ok((output, empty_laps))
+}
+
+/// Writes a track with a single trackpoint holding nothing but a time.
+fn write_placeholder_track<W: std::io::Write>(
+    writer: &mut xml::writer::EventWriter<W>,
+) -> Result<(), Box<dyn Error>> {
+    use xml::writer::XmlEvent;
+
+    writer.write(XmlEvent::start_element("Track"))?;
+    writer.write(XmlEvent::start_element("Trackpoint"))?;
+    writer.write(XmlEvent::start_element("Time"))?;
+    writer.write(XmlEvent::characters(PLACEHOLDER_TIME))?;
+    writer.write(XmlEvent::end_element())?; // Time
+    writer.write(XmlEvent::end_element())?; // Trackpoint
+    writer.write(XmlEvent::end_element())?; // Track
+    Ok(())
+}
+
+/// Removes the placeholder tracks from the laps they were added to.
+fn clear_placeholder_tracks(tcdb: &mut tcx::TrainingCenterDatabase, empty_laps: &[LapIndex]) {
+    let Some(activities) = tcdb.activities.as_mut() else {
+        return;
+    };
+    for (activity_index, lap_index) in empty_laps {
+        if let Some(lap) = activities
+            .activities
+            .get_mut(*activity_index)
+            .and_then(|activity| activity.laps.get_mut(*lap_index))
+        {
+            lap.tracks.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+/// The shape of a file from a Forerunner 305: a lap without a track from pressing the lap button twice, an empty
+/// track, and trackpoints without a position or heart rate.
+pub(crate) const FORERUNNER_305: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no" ?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Activities>
+    <Activity Sport="Running">
+      <Id>2008-03-15T14:02:11Z</Id>
+      <Lap StartTime="2008-03-15T14:02:11Z">
+        <TotalTimeSeconds>0.0000000</TotalTimeSeconds>
+        <DistanceMeters>0.0000000</DistanceMeters>
+        <MaximumSpeed>0.0000000</MaximumSpeed>
+        <Calories>0</Calories>
+        <Intensity>Active</Intensity>
+        <TriggerMethod>Manual</TriggerMethod>
+      </Lap>
+      <Lap StartTime="2008-03-15T14:02:11Z">
+        <TotalTimeSeconds>20.0000000</TotalTimeSeconds>
+        <DistanceMeters>60.0000000</DistanceMeters>
+        <MaximumSpeed>3.5000000</MaximumSpeed>
+        <Calories>3</Calories>
+        <Intensity>Active</Intensity>
+        <TriggerMethod>Manual</TriggerMethod>
+        <Track>
+        </Track>
+        <Track>
+          <Trackpoint>
+            <Time>2008-03-15T14:02:11Z</Time>
+            <HeartRateBpm xsi:type="HeartRateInBeatsPerMinute_t"><Value>120</Value></HeartRateBpm>
+            <SensorState>Absent</SensorState>
+          </Trackpoint>
+          <Trackpoint>
+            <Time>2008-03-15T14:02:21Z</Time>
+            <Position><LatitudeDegrees>59.9100000</LatitudeDegrees><LongitudeDegrees>10.7500000</LongitudeDegrees></Position>
+            <AltitudeMeters>12.0</AltitudeMeters>
+            <DistanceMeters>30.0</DistanceMeters>
+            <SensorState>Absent</SensorState>
+          </Trackpoint>
+          <Trackpoint>
+            <Time>2008-03-15T14:02:31Z</Time>
+            <Position><LatitudeDegrees>59.9102700</LatitudeDegrees><LongitudeDegrees>10.7500000</LongitudeDegrees></Position>
+            <AltitudeMeters>15.0</AltitudeMeters>
+            <DistanceMeters>60.0</DistanceMeters>
+            <HeartRateBpm xsi:type="HeartRateInBeatsPerMinute_t"><Value>140</Value></HeartRateBpm>
+            <SensorState>Absent</SensorState>
+          </Trackpoint>
+        </Track>
+      </Lap>
+      <Creator xsi:type="Device_t">
+        <Name>Forerunner305</Name>
+        <UnitId>3412345678</UnitId>
+        <ProductID>484</ProductID>
+      </Creator>
+    </Activity>
+  </Activities>
+</TrainingCenterDatabase>"#;
+
+#[cfg(test)]
+/// Tests for the quirks module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test reading a file with a lap without a track and an empty track
+    fn test_read_tcx() {
+        assert!(tcx::read(&mut BufReader::new(FORERUNNER_305.as_bytes())).is_err());
+
+        let tcdb = read_tcx(FORERUNNER_305.as_bytes()).unwrap();
+        let laps = &tcdb.activities.unwrap().activities[0].laps;
+        assert_eq!(laps.len(), 2);
+        assert!(laps[0].tracks.is_empty());
+        assert_eq!(laps[1].tracks.len(), 1);
+        assert_eq!(laps[1].tracks[0].trackpoints.len(), 3);
+    }
+
+    #[test]
+    /// Test that files without activities and files that aren't TCX give the expected result
+    fn test_read_tcx_other() {
+        let empty = "<TrainingCenterDatabase></TrainingCenterDatabase>";
+        assert!(read_tcx(empty.as_bytes()).unwrap().activities.is_none());
+        assert!(read_tcx("<gpx>".as_bytes()).is_err());
+    }
+}
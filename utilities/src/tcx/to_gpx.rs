@@ -0,0 +1,260 @@
+//! Converts the trackpoints of a TCX file to GPX. Each activity becomes a track and each lap a track segment, or each lap
+//! a track of its own depending on the `LapMapping` set for the run. The heart rate and cadence are written as a Garmin `TrackPointExtension`, which most applications that read GPX understand.
+//! The tracks are given the color of their sport as a `gpx_style:line` extension, for viewers such as GPXSee.
+
+use chrono::{DateTime, Local};
+use std::error::Error;
+use std::io::Write;
+use xml::writer::XmlEvent;
+
+use crate::lap_mapping::lap_mapping;
+use crate::track_colors::track_color;
+use crate::xml_writer::{write_element, xml_time, xml_writer};
+use crate::{LapMapping, TCXTrackpointList, TrackPoint};
+
+/// The GPX 1.1 namespace.
+pub(crate) const GPX_NAMESPACE: &str = "http://www.topografix.com/GPX/1/1";
+
+/// The namespace of the Garmin extension holding the heart rate and cadence.
+pub(crate) const TRACKPOINT_EXTENSION_NAMESPACE: &str =
+    "http://www.garmin.com/xmlschemas/TrackPointExtension/v1";
+
+/// The namespace of the extension holding the line color of a track.
+const GPX_STYLE_NAMESPACE: &str = "http://www.topografix.com/GPX/gpx_style/0/2";
+
+/// What the GPX files say they were created by.
+pub(crate) const GPX_CREATOR: &str = "fitutils";
+
+/// A point to write to GPX, along with the activity and lap it belongs to.
+pub(crate) struct GpxPoint<'a> {
+    /// The activity, or session, the point belongs to. Each activity becomes a track.
+    pub(crate) activity: usize,
+
+    /// The number of the lap the point belongs to, used to tell the laps apart and to name the lap tracks.
+    pub(crate) lap: usize,
+
+    /// The sport, used to name and color the track.
+    pub(crate) sport: &'a str,
+
+    /// The point itself.
+    pub(crate) point: TrackPoint,
+}
+
+impl TCXTrackpointList {
+    /// Writes the trackpoints as GPX to any writer. Each activity becomes a track named and colored after the sport,
+    /// and each lap becomes a track segment -- or, with `LapMapping::Tracks`, each lap becomes a track named after the
+    /// sport and the lap number. Trackpoints without a position can't be written to GPX, so they are left out.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the GPX is written.
+    ///
+    /// # Returns
+    ///
+    /// `Result<usize, Box<dyn Error>>` -- The number of trackpoints left out because they have no position.
+    ///
+    /// # Errors
+    ///
+    /// Writing may fail.
+    pub fn write_gpx<W: Write>(&self, writer: W) -> Result<usize, Box<dyn Error>> {
+        self.write_gpx_mapped(writer, lap_mapping())
+    }
+
+    /// Writes the trackpoints as GPX with the laps laid out as given, rather than as set for the run.
+    fn write_gpx_mapped<W: Write>(
+        &self,
+        writer: W,
+        mapping: LapMapping,
+    ) -> Result<usize, Box<dyn Error>> {
+        let points = self.trackpoints.iter().map(|tp| GpxPoint {
+            activity: tp.activity_num,
+            lap: tp.lap_num,
+            sport: &tp.sport,
+            point: TrackPoint::from(tp),
+        });
+        let start_time = self.trackpoints.first().map(|first| first.start_time);
+        write_gpx_tracks(writer, points, start_time, mapping)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Writes the points as GPX tracks. Each activity becomes a track named and colored after the sport, and each lap a
+/// track segment -- or, with `LapMapping::Tracks`, each lap becomes a track named after the sport and the lap number.
+/// Points without a position can't be written to GPX, so they are left out.
+///
+/// # Arguments
+///
+/// - `writer: W` -- Where the GPX is written.
+/// - `points: I` -- The points in the order they were recorded.
+/// - `start_time: Option<DateTime<Local>>` -- When the activity started, written as the time of the GPX file.
+/// - `mapping: LapMapping` -- Whether the laps become track segments or tracks.
+///
+/// # Returns
+///
+/// `Result<usize, Box<dyn Error>>` -- The number of points left out because they have no position.
+///
+/// # Errors
+///
+/// Writing may fail.
+pub(crate) fn write_gpx_tracks<'a, W, I>(
+    writer: W,
+    points: I,
+    start_time: Option<DateTime<Local>>,
+    mapping: LapMapping,
+) -> Result<usize, Box<dyn Error>>
+where
+    W: Write,
+    I: IntoIterator<Item = GpxPoint<'a>>,
+{
+    let mut writer = xml_writer(writer);
+    writer.write(
+        XmlEvent::start_element("gpx")
+            .attr("version", "1.1")
+            .attr("creator", GPX_CREATOR)
+            .default_ns(GPX_NAMESPACE)
+            .ns("gpxtpx", TRACKPOINT_EXTENSION_NAMESPACE)
+            .ns("gpx_style", GPX_STYLE_NAMESPACE),
+    )?;
+
+    if let Some(start_time) = start_time {
+        writer.write(XmlEvent::start_element("metadata"))?;
+        write_element(&mut writer, "time", &xml_time(&start_time))?;
+        writer.write(XmlEvent::end_element())?;
+    }
+
+    let laps_as_tracks = mapping == LapMapping::Tracks;
+    let mut skipped = 0;
+    let mut current: Option<(usize, usize)> = None; // activity and lap
+    for gp in points {
+        let (Some(lat), Some(lon)) = (gp.point.latitude, gp.point.longitude) else {
+            skipped += 1;
+            continue;
+        };
+
+        // Start a new track for each activity, and a new segment for each lap -- or a new track for each lap
+        let new_lap = current.map(|(_, lap)| lap) != Some(gp.lap);
+        if current.map(|(activity, _)| activity) != Some(gp.activity) || (laps_as_tracks && new_lap)
+        {
+            if current.is_some() {
+                writer.write(XmlEvent::end_element())?; // trkseg
+                writer.write(XmlEvent::end_element())?; // trk
+            }
+            writer.write(XmlEvent::start_element("trk"))?;
+            if laps_as_tracks {
+                write_element(&mut writer, "name", &format!("{} lap {}", gp.sport, gp.lap))?;
+            } else {
+                write_element(&mut writer, "name", gp.sport)?;
+            }
+            write_element(&mut writer, "type", gp.sport)?;
+            if let Some(color) = track_color(gp.sport) {
+                writer.write(XmlEvent::start_element("extensions"))?;
+                writer.write(XmlEvent::start_element("gpx_style:line"))?;
+                write_element(&mut writer, "gpx_style:color", &color)?;
+                writer.write(XmlEvent::end_element())?; // line
+                writer.write(XmlEvent::end_element())?; // extensions
+            }
+            writer.write(XmlEvent::start_element("trkseg"))?;
+        } else if new_lap {
+            writer.write(XmlEvent::end_element())?; // trkseg
+            writer.write(XmlEvent::start_element("trkseg"))?;
+        }
+        current = Some((gp.activity, gp.lap));
+
+        write_trkpt(&mut writer, &gp.point, lat, lon)?;
+    }
+
+    if current.is_some() {
+        writer.write(XmlEvent::end_element())?; // trkseg
+        writer.write(XmlEvent::end_element())?; // trk
+    }
+    writer.write(XmlEvent::end_element())?; // gpx
+
+    if skipped > 0 {
+        log::warn!("{skipped} trackpoints have no position and were left out of the GPX.");
+    }
+    Ok(skipped)
+}
+
+/// Writes a single point as a GPX track point.
+fn write_trkpt<W: Write>(
+    writer: &mut xml::writer::EventWriter<W>,
+    point: &TrackPoint,
+    lat: f64,
+    lon: f64,
+) -> Result<(), Box<dyn Error>> {
+    writer.write(
+        XmlEvent::start_element("trkpt")
+            .attr("lat", &lat.to_string())
+            .attr("lon", &lon.to_string()),
+    )?;
+    if let Some(elevation) = point.elevation {
+        write_element(writer, "ele", &elevation.to_string())?;
+    }
+    if let Some(time) = point.time {
+        write_element(writer, "time", &xml_time(&time))?;
+    }
+
+    if point.heart_rate.is_some() || point.cadence.is_some() {
+        writer.write(XmlEvent::start_element("extensions"))?;
+        writer.write(XmlEvent::start_element("gpxtpx:TrackPointExtension"))?;
+        if let Some(hr) = point.heart_rate {
+            write_element(writer, "gpxtpx:hr", &format!("{hr:.0}"))?;
+        }
+        if let Some(cadence) = point.cadence {
+            write_element(writer, "gpxtpx:cad", &format!("{cadence:.0}"))?;
+        }
+        writer.write(XmlEvent::end_element())?; // TrackPointExtension
+        writer.write(XmlEvent::end_element())?; // extensions
+    }
+
+    writer.write(XmlEvent::end_element())?; // trkpt
+    Ok(())
+}
+
+#[cfg(all(test, feature = "fs"))]
+/// Tests for the to_gpx module
+mod tests {
+    use crate::{GPXActivity, LapMapping, TCXTrackpointList};
+
+    #[test]
+    /// Test that the GPX written can be read back with the same track points
+    fn test_write_gpx() {
+        let trackpoints = TCXTrackpointList::from_file("../data/running.tcx").unwrap();
+        let mut gpx = Vec::new();
+        assert_eq!(trackpoints.write_gpx(&mut gpx).unwrap(), 0);
+
+        let text = String::from_utf8(gpx.clone()).unwrap();
+        assert!(text.contains("<gpxtpx:hr>"));
+        assert!(text.contains("<gpx_style:color>E53935</gpx_style:color>"));
+
+        let activity = GPXActivity::from_reader(gpx.as_slice(), "running.gpx").unwrap();
+        assert_eq!(activity.tracks.len(), 1);
+        assert_eq!(
+            activity.tracks[0].waypoints.len(),
+            trackpoints.trackpoints.len()
+        );
+        assert_eq!(
+            activity.tracks[0].waypoints[0].time,
+            Some(trackpoints.trackpoints[0].time)
+        );
+    }
+
+    #[test]
+    /// Test writing each lap as a track segment, and as a track of its own
+    fn test_lap_mapping() {
+        let trackpoints = TCXTrackpointList::from_file("../data/walking.tcx").unwrap();
+        for (mapping, tracks, segments) in
+            [(LapMapping::Segments, 1, 2), (LapMapping::Tracks, 2, 2)]
+        {
+            let mut gpx = Vec::new();
+            trackpoints.write_gpx_mapped(&mut gpx, mapping).unwrap();
+
+            let text = String::from_utf8(gpx).unwrap();
+            assert_eq!(text.matches("<trk>").count(), tracks);
+            assert_eq!(text.matches("<trkseg>").count(), segments);
+            if mapping == LapMapping::Tracks {
+                assert!(text.contains("lap 2</name>"));
+            }
+        }
+    }
+}
@@ -1,7 +1,14 @@
-use std::{collections::HashMap, error::Error};
-
-use crate::TCXActivity;
-use chrono::{DateTime, Datelike, Timelike};
+use std::{collections::HashMap, error::Error, io::BufReader};
+
+use crate::analysis::track_stats::{track_stats, StatsPoint};
+use crate::date_source::{date_source, insert_date_tokens};
+use crate::input::open_input;
+use crate::placeholder::placeholder;
+use crate::privacy::output_serial;
+use crate::summary_tokens::SummaryTokens;
+use crate::tcx::quirks::read_tcx;
+use crate::{TCXActivity, TCXCreator};
+use chrono::DateTime;
 use convert_case::{Case, Casing};
 
 /// Iterates through a TCX file and saves the information to a `HashMap`
@@ -26,7 +33,10 @@ pub fn tcx_to_hashmap(filename: &str) -> Result<HashMap<String, String>, Box<dyn
     let mut values = HashMap::<String, String>::new();
 
     // Make sure we can open the file correctly
-    let tcdb = match tcx::read_file(filename) {
+    let read = || -> Result<tcx::TrainingCenterDatabase, Box<dyn Error>> {
+        read_tcx(BufReader::new(open_input(filename)?))
+    };
+    let tcdb = match read() {
         Ok(res) => res,
         Err(err) => return Err(format!("Unable to open {filename}. Error: {err}").into()),
     };
@@ -34,101 +44,102 @@ pub fn tcx_to_hashmap(filename: &str) -> Result<HashMap<String, String>, Box<dyn
     if let Some(activities) = tcdb.activities {
         let mut act = TCXActivity::from_activities(&activities);
         act.filename = Some(filename.to_string());
+        act.set_creator(&TCXCreator::from_file(filename)?);
 
         log::debug!("act = {act:?}");
 
         // Insert values into HashMap
         // Insert "unknown" into all the fields that don't have a corresponding field in the TCX.
-        let unknown = "unknown".to_string();
-        values.insert("%manufacturer".to_string(), unknown.to_case(Case::Title));
+        let unknown = placeholder("unknown");
         values.insert("%unknown".to_string(), unknown.clone());
-        values.insert("%product".to_string(), unknown.to_case(Case::Title));
-        values.insert("%pr".to_string(), unknown.to_case(Case::Title));
-        values.insert("%serial_number".to_string(), unknown.clone());
-        values.insert("%sn".to_string(), unknown.clone());
+
+        let mf = act
+            .manufacturer
+            .map_or_else(|| placeholder("Unknown"), |mf| mf.to_case(Case::Title));
+        values.insert("%manufacturer".to_string(), mf.clone());
+        values.insert("%mf".to_string(), mf);
+
+        // The device name is already meant for humans, so it's used as-is
+        let pr = act.product.unwrap_or_else(|| placeholder("Unknown"));
+        values.insert("%product".to_string(), pr.clone());
+        values.insert("%pr".to_string(), pr);
+
+        let sn = act
+            .serial_number
+            .as_deref()
+            .map_or_else(|| unknown.clone(), output_serial);
+        values.insert("%serial_number".to_string(), sn.clone());
+        values.insert("%sn".to_string(), sn);
 
         let ac = act
             .sport
-            .unwrap_or_else(|| "unknown".to_string())
-            .to_case(Case::Title);
+            .map_or_else(|| placeholder("Unknown"), |ac| ac.to_case(Case::Title));
         values.insert("%activity".to_string(), ac.clone());
         values.insert("%at".to_string(), ac);
 
         values.insert("%activity_detailed".to_string(), unknown.clone());
         values.insert("%ad".to_string(), unknown);
 
-        if let Some(st) = act.start_time {
+        // TCX files only have the start time of the activity
+        let start = match act.start_time {
             // TODO: May want to switch to TimeStamp for consistency.
-            let tc = DateTime::parse_from_rfc3339(&st)?.with_timezone(&chrono::Local);
-
-            values.insert("%year".to_string(), format!("{:04}", tc.year()));
-            values.insert("%yr".to_string(), format!("{:04}", tc.year()));
-            values.insert("%month".to_string(), format!("{:02}", tc.month()));
-            values.insert("%mn".to_string(), format!("{:02}", tc.month()));
-            values.insert("%day".to_string(), format!("{:02}", tc.day()));
-            values.insert("%dy".to_string(), format!("{:02}", tc.day()));
-
-            values.insert("%hour".to_string(), format!("{:02}", tc.hour()));
-            values.insert("%hr".to_string(), format!("{:02}", tc.hour()));
-            values.insert("%24hour".to_string(), format!("{:02}", tc.hour()));
-            values.insert("%24".to_string(), format!("{:02}", tc.hour()));
-
-            let (am, hrs) = tc.hour12();
-            let hr = format!("{hrs:02}");
-            values.insert("%12hour".to_string(), hr.clone());
-            values.insert("%12".to_string(), hr);
-            if am {
-                values.insert("%ampm".to_string(), "pm".to_string());
-                values.insert("%ap".to_string(), "pm".to_string());
-            } else {
-                values.insert("%ampm".to_string(), "am".to_string());
-                values.insert("%ap".to_string(), "am".to_string());
-            }
-
-            values.insert("%minute".to_string(), format!("{:02}", tc.minute()));
-            values.insert("%mt".to_string(), format!("{:02}", tc.minute()));
-            values.insert("%second".to_string(), format!("{:02}", tc.second()));
-            values.insert("%sc".to_string(), format!("{:02}", tc.second()));
-            values.insert("%weekday".to_string(), tc.weekday().to_string());
-            values.insert("%wd".to_string(), tc.weekday().to_string());
-        } else {
-            values.insert("%year".to_string(), "0000".to_string());
-            values.insert("%yr".to_string(), "0000".to_string());
-            values.insert("%month".to_string(), "00".to_string());
-            values.insert("%mn".to_string(), "00".to_string());
-            values.insert("%day".to_string(), "00".to_string());
-            values.insert("%dy".to_string(), "00".to_string());
-            values.insert("%hour".to_string(), "00".to_string());
-            values.insert("%hr".to_string(), "00".to_string());
-            values.insert("%24hour".to_string(), "00".to_string());
-            values.insert("%24".to_string(), "00".to_string());
-            values.insert("%12hour".to_string(), "00".to_string());
-            values.insert("%12".to_string(), "00".to_string());
-            values.insert("%ampm".to_string(), "ampm".to_string());
-            values.insert("%ap".to_string(), "ampm".to_string());
-            values.insert("%minute".to_string(), "00".to_string());
-            values.insert("%mt".to_string(), "00".to_string());
-            values.insert("%second".to_string(), "00".to_string());
-            values.insert("%sc".to_string(), "00".to_string());
-            values.insert("%weekday".to_string(), "00".to_string());
-            values.insert("%wd".to_string(), "00".to_string());
-        }
+            Some(st) => Some(
+                DateTime::parse_from_rfc3339(&st)?
+                    .with_timezone(&chrono::Local)
+                    .fixed_offset(),
+            ),
+            None => None,
+        };
+        insert_date_tokens(&mut values, date_source().pick(start, None, filename));
 
         if let Some(dur) = act.duration {
             values.insert("%duration".to_string(), (dur.0.as_secs()).to_string());
             values.insert("%du".to_string(), (dur.0.as_secs()).to_string());
         } else {
-            values.insert("%duration".to_string(), "0".to_string());
-            values.insert("%du".to_string(), "0".to_string());
+            values.insert("%duration".to_string(), placeholder("0"));
+            values.insert("%du".to_string(), placeholder("0"));
         }
+
+        SummaryTokens {
+            distance_m: act.distance_meters,
+            calories: act.calories,
+            avg_hr: act.average_heart_rate,
+            max_hr: act.maximum_heart_rate,
+            elevation_gain_m: elevation_gain(&activities),
+            duration: act.duration,
+        }
+        .insert(&mut values);
     }
 
     // Return safely
     Ok(values)
 }
 
+/// The total climb of the tracks in meters, since the TCX summary only has the highest point above the start.
+fn elevation_gain(activities: &tcx::Activities) -> Option<f64> {
+    let tracks: Vec<Vec<StatsPoint>> = activities
+        .activities
+        .iter()
+        .flat_map(|activity| &activity.laps)
+        .flat_map(|lap| &lap.tracks)
+        .map(|track| {
+            track
+                .trackpoints
+                .iter()
+                .map(|tp| StatsPoint {
+                    time: Some(tp.time.with_timezone(&chrono::Local)),
+                    position: tp.position.as_ref().map(|p| (p.latitude, p.longitude)),
+                    elevation: tp.altitude_meters,
+                })
+                .collect()
+        })
+        .collect();
+
+    track_stats(&tracks).ascent_m
+}
+
 #[cfg(test)]
-///
+/// Tests for the to_hashmap module
 mod tests {
     use super::*;
     use assay::assay;
@@ -146,7 +157,7 @@ mod tests {
         println!("tm.len() = {}", tm.len());
 
         // Perform the actual tests
-        assert_eq!(tm.len(), 32);
+        assert_eq!(tm.len(), 47);
         assert_eq!(
             tm.get("%activity").unwrap().to_string(),
             "Running".to_string()
@@ -171,24 +182,28 @@ mod tests {
         assert_eq!(tm.get("%24hour").unwrap().to_string(), "06".to_string());
         assert_eq!(
             tm.get("%manufacturer").unwrap().to_string(),
-            "Unknown".to_string()
+            "Wahoo".to_string()
         );
+        assert_eq!(tm.get("%mf").unwrap().to_string(), "Wahoo".to_string());
         assert_eq!(tm.get("%minute").unwrap().to_string(), "35".to_string());
         assert_eq!(tm.get("%mt").unwrap().to_string(), "35".to_string());
         assert_eq!(tm.get("%month").unwrap().to_string(), "06".to_string());
         assert_eq!(tm.get("%mn").unwrap().to_string(), "06".to_string());
-        assert_eq!(tm.get("%pr").unwrap().to_string(), "Unknown".to_string());
+        assert_eq!(
+            tm.get("%pr").unwrap().to_string(),
+            "Wahoo Fitness iOS".to_string()
+        );
         assert_eq!(
             tm.get("%product").unwrap().to_string(),
-            "Unknown".to_string()
+            "Wahoo Fitness iOS".to_string()
         );
         assert_eq!(tm.get("%sc").unwrap().to_string(), "49".to_string());
         assert_eq!(tm.get("%second").unwrap().to_string(), "49".to_string());
         assert_eq!(
             tm.get("%serial_number").unwrap().to_string(),
-            "unknown".to_string()
+            "0123456789".to_string()
         );
-        assert_eq!(tm.get("%sn").unwrap().to_string(), "unknown".to_string());
+        assert_eq!(tm.get("%sn").unwrap().to_string(), "0123456789".to_string());
         assert_eq!(
             tm.get("%unknown").unwrap().to_string(),
             "unknown".to_string()
@@ -197,5 +212,6 @@ mod tests {
         assert_eq!(tm.get("%weekday").unwrap().to_string(), "Fri".to_string());
         assert_eq!(tm.get("%year").unwrap().to_string(), "2018".to_string());
         assert_eq!(tm.get("%yr").unwrap().to_string(), "2018".to_string());
+        assert_eq!(tm["%duration_hms"], "00h22m05s");
     }
 }
@@ -1,12 +1,28 @@
 use chrono::{DateTime, Local, TimeZone};
-use csv::WriterBuilder;
 use serde::Serialize;
 use std::error::Error;
-use std::path::PathBuf;
+use std::io::{Read, Write};
 use tcx;
+use uuid::Uuid;
 
+use crate::analysis::hr_scatter::{hr_scatter, HrScatter};
+use crate::analysis::mean_max::{mean_max, MeanMax, MeanMaxSample};
+use crate::analysis::splits::{splits, Split, SplitPoint, SplitUnit};
+use crate::columns::{ColumnWriter, CsvFile};
+use crate::exporters::{write_rows, ExportFormat};
 use crate::Duration;
 
+#[cfg(feature = "fs")]
+use crate::input::open_input;
+#[cfg(feature = "fs")]
+use crate::output_naming::write_output;
+use crate::precision::Rounded;
+use crate::profile_svg::{profile_points, ProfilePoint};
+use crate::tcx::quirks::read_tcx;
+use crate::TrackPoint;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
 /// Holds each Trackpoint as a Record
 #[derive(Serialize, Debug, Clone, Default)]
 pub struct TCXTrackpoint {
@@ -51,6 +67,9 @@ pub struct TCXTrackpoint {
 
     /// Cadence in BPM, RPM or SPM.
     pub cadence: Option<u8>,
+
+    /// The UUID of the activity the trackpoint belongs to.
+    pub activity_uuid: Option<Uuid>,
 }
 
 /// Contains the list of activity trackpoints from the TCX file
@@ -65,56 +84,112 @@ impl TCXTrackpointList {
     pub fn from_activities(activities: &tcx::Activities) -> Self {
         let mut tpl = Self::default();
 
-        let mut a_num = 0;
-
-        for activity in &activities.activities {
-            a_num += 1;
-            let mut l_num = 0;
-            for lap in &activity.laps {
-                l_num += 1;
-                let mut track_num = 0;
-                for track in &lap.tracks {
-                    track_num += 1;
-                    let mut trackpoint_num = 0;
-                    for trackpoint in &track.trackpoints {
-                        trackpoint_num += 1;
-
-                        // Extract a new Trackpoint
-                        let mut tp = TCXTrackpoint::default();
-                        tp.sport = activity.sport.clone();
-                        tp.start_time = DateTime::parse_from_rfc3339(&activity.id)
-                            .unwrap_or_else(|_| Local.timestamp_opt(0, 0).unwrap().into())
-                            .into();
-                        tp.time = trackpoint.time.with_timezone(&chrono::Local);
-                        tp.duration = Duration::between(&tp.start_time, &tp.time);
-                        tp.activity_num = a_num;
-                        tp.lap_num = l_num;
-                        tp.track_num = track_num;
-                        tp.trackpoint_num = trackpoint_num;
-                        if let Some(pos) = &trackpoint.position {
-                            tp.latitude = Some(pos.latitude);
-                            tp.longitude = Some(pos.longitude);
-                        }
-                        tp.altitude_meters = trackpoint.altitude_meters;
-                        tp.distance_meters = trackpoint.distance_meters;
-                        if let Some(hr) = &trackpoint.heart_rate {
-                            tp.heart_rate = Some(hr.value);
-                        }
-                        if let Some(cad) = &trackpoint.cadence {
-                            tp.cadence = Some(*cad);
-                        }
-
-                        // Push the TP into the list
-                        tpl.trackpoints.push(tp);
-                    }
-                }
-            }
-        }
+        // Collecting into a Vec can't fail
+        let _ = for_each_trackpoint(activities, |tp| {
+            tpl.trackpoints.push(tp);
+            Ok(())
+        });
 
         // Return it
         tpl
     }
 
+    /// Reads the trackpoints from all the activities in a TCX file. The activity UUID isn't set, since it comes from
+    /// the activity summary -- use `set_activity_uuid()` if needed.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the file may fail.
+    #[cfg(feature = "fs")]
+    pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_reader(open_input(filename)?)
+    }
+
+    /// Reads the trackpoints from all the activities in TCX data, e.g. an uploaded file. The activity UUID isn't set,
+    /// since it comes from the activity summary -- use `set_activity_uuid()` if needed.
+    ///
+    /// # Errors
+    ///
+    /// Reading or parsing the data may fail.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        let tcdb = read_tcx(reader)?;
+
+        Ok(tcdb
+            .activities
+            .as_ref()
+            .map(Self::from_activities)
+            .unwrap_or_default())
+    }
+
+    /// Sets the activity UUID on all the trackpoints.
+    ///
+    /// # Arguments
+    ///
+    /// `activity_uuid: Option<Uuid>` -- The UUID of the activity, usually `TCXActivity::uuid`.
+    pub fn set_activity_uuid(&mut self, activity_uuid: Option<Uuid>) {
+        for trackpoint in &mut self.trackpoints {
+            trackpoint.activity_uuid = activity_uuid;
+        }
+    }
+
+    /// Splits the activity into kilometers or miles, using the trackpoints rather than the laps.
+    ///
+    /// # Arguments
+    ///
+    /// `unit: SplitUnit` -- Whether to split by kilometer or mile.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<Split>` -- The splits. Trackpoints without a distance are left out.
+    #[must_use]
+    pub fn splits(&self, unit: SplitUnit) -> Vec<Split> {
+        splits(&self.split_points(), unit)
+    }
+
+    /// The elevation against the distance, from the trackpoints, for drawing with `profile_svg()`.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<ProfilePoint>` -- The profile. Trackpoints without a distance or altitude are left out.
+    #[must_use]
+    pub fn elevation_profile(&self) -> Vec<ProfilePoint> {
+        profile_points(&self.split_points())
+    }
+
+    /// Works out the mean-maximal heart rate curve from the trackpoints. The trackpoints don't hold the power, so the
+    /// curve only has the heart rate.
+    #[must_use]
+    pub fn mean_max(&self) -> Vec<MeanMax> {
+        mean_max(&self.mean_max_samples())
+    }
+
+    /// Works out the heart rate against the speed over each half minute of the trackpoints. The trackpoints don't
+    /// hold the power, so it is left empty.
+    #[must_use]
+    pub fn hr_scatter(&self) -> Vec<HrScatter> {
+        hr_scatter(&self.mean_max_samples(), &self.split_points())
+    }
+
+    /// The trackpoints as track points, the view shared with the other formats. The trackpoints don't hold the power
+    /// or the speed, so they are left empty.
+    pub fn track_points(&self) -> impl Iterator<Item = TrackPoint> + '_ {
+        self.trackpoints.iter().map(TrackPoint::from)
+    }
+
+    /// The trackpoints as samples for the mean-maximal curve.
+    pub(crate) fn mean_max_samples(&self) -> Vec<MeanMaxSample> {
+        self.track_points()
+            .filter_map(|point| point.mean_max_sample())
+            .collect()
+    }
+
+    /// The trackpoints with a distance, as points for the split analysis.
+    pub(crate) fn split_points(&self) -> Vec<SplitPoint> {
+        self.track_points()
+            .filter_map(|point| point.split_point())
+            .collect()
+    }
+
     /// Export the activity summary as a CSV file
     ///
     /// # Arguments
@@ -137,36 +212,71 @@ impl TCXTrackpointList {
     ///
     ///
     ///
+    #[cfg(feature = "fs")]
     pub fn export_csv(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        write_output(Path::new(filename), |file| self.write_csv(file))
+    }
+
+    /// Export the trackpoints to the file given in the format given.
+    ///
+    /// # Arguments
+    ///
+    /// - `filename: &str` -- The file to write, e.g. `running.trackpoints.parquet`.
+    /// - `format: ExportFormat` -- The format to write.
+    ///
+    /// # Errors
+    ///
+    /// Creating the file may fail. Parquet may not be available in this build. Serialization or writing may fail.
+    #[cfg(feature = "fs")]
+    pub fn export(&self, filename: &str, format: ExportFormat) -> Result<(), Box<dyn Error>> {
+        write_output(Path::new(filename), |file| self.write(file, format))
+    }
+
+    /// Write the trackpoints in the format given to any writer.
+    ///
+    /// # Arguments
+    ///
+    /// - `writer: W` -- Where the trackpoints are written.
+    /// - `format: ExportFormat` -- The format to write.
+    ///
+    /// # Errors
+    ///
+    /// Parquet may not be available in this build. Serialization or writing may fail.
+    pub fn write<W: Write + Send>(
+        &self,
+        writer: W,
+        format: ExportFormat,
+    ) -> Result<(), Box<dyn Error>> {
+        write_rows(
+            self.trackpoints.iter().map(Rounded),
+            CsvFile::TcxTrackpoints,
+            format,
+            writer,
+        )
+    }
+
+    /// Write the trackpoints as CSV to any writer, e.g. an HTTP response or an in-memory buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `writer: W` -- Where the CSV is written.
+    ///
+    /// # Errors
+    ///
+    /// Serialization may fail. Writer flush may fail.
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
         // Create a buffer for the CSV
-        let outfile = PathBuf::from(filename);
-        let mut writer = WriterBuilder::new()
-            .has_headers(false)
-            .from_path(&outfile)?;
-
-        writer.write_record([
-            "sport",
-            "start_time",
-            "time",
-            "duration",
-            "activity_num",
-            "lap_num",
-            "track_num",
-            "trackpoint_num",
-            "latitude",
-            "longitude",
-            "altitude_meters",
-            "distance_meters",
-            "heart_rate",
-            "cadence",
-        ])?;
+        let mut writer = trackpoints_writer(writer)?;
 
         for trackpoint in &self.trackpoints {
             log::trace!("TrackpointsList::export_csv() -- serializing: {trackpoint:?}");
-            writer.serialize(trackpoint)?;
+            writer.serialize(Rounded(trackpoint))?;
         }
 
-        log::trace!("TrackpointsList::export_csv() -- information to be written: {writer:?}");
+        log::trace!(
+            "TrackpointsList::export_csv() -- {} trackpoints to be written.",
+            self.trackpoints.len()
+        );
 
         // Write the file
         writer.flush()?;
@@ -174,3 +284,64 @@ impl TCXTrackpointList {
         Ok(())
     }
 }
+
+/// Creates the trackpoints CSV writer and writes the header.
+fn trackpoints_writer<W: Write>(writer: W) -> Result<ColumnWriter<W>, Box<dyn Error>> {
+    let mut writer = ColumnWriter::new(writer, CsvFile::TcxTrackpoints);
+    writer.write_header()?;
+
+    Ok(writer)
+}
+
+/// Walks through all the trackpoints in the activities, converts each to a `TCXTrackpoint` and hands it to `f`.
+fn for_each_trackpoint<F>(activities: &tcx::Activities, mut f: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(TCXTrackpoint) -> Result<(), Box<dyn Error>>,
+{
+    let mut a_num = 0;
+
+    for activity in &activities.activities {
+        a_num += 1;
+        let mut l_num = 0;
+        for lap in &activity.laps {
+            l_num += 1;
+            let mut track_num = 0;
+            for track in &lap.tracks {
+                track_num += 1;
+                let mut trackpoint_num = 0;
+                for trackpoint in &track.trackpoints {
+                    trackpoint_num += 1;
+
+                    // Extract a new Trackpoint
+                    let mut tp = TCXTrackpoint::default();
+                    tp.sport = activity.sport.clone();
+                    tp.start_time = DateTime::parse_from_rfc3339(&activity.id)
+                        .unwrap_or_else(|_| Local.timestamp_opt(0, 0).unwrap().into())
+                        .into();
+                    tp.time = trackpoint.time.with_timezone(&chrono::Local);
+                    tp.duration = Duration::between(&tp.start_time, &tp.time);
+                    tp.activity_num = a_num;
+                    tp.lap_num = l_num;
+                    tp.track_num = track_num;
+                    tp.trackpoint_num = trackpoint_num;
+                    if let Some(pos) = &trackpoint.position {
+                        tp.latitude = Some(pos.latitude);
+                        tp.longitude = Some(pos.longitude);
+                    }
+                    tp.altitude_meters = trackpoint.altitude_meters;
+                    tp.distance_meters = trackpoint.distance_meters;
+                    if let Some(hr) = &trackpoint.heart_rate {
+                        tp.heart_rate = Some(hr.value);
+                    }
+                    if let Some(cad) = &trackpoint.cadence {
+                        tp.cadence = Some(*cad);
+                    }
+
+                    f(tp)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Builds human-readable titles for activities from a template, using the same tokens as the `fitrename` patterns
+//! (e.g. `%activity %year-%month-%day`), so reports and other generated files name the activities consistently.
+
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+#[cfg(feature = "fs")]
+use std::error::Error;
+
+#[cfg(feature = "fs")]
+use crate::{fit_to_hashmap, gpx_to_hashmap, tcx_to_hashmap, ActivityFormat};
+
+/// The title used when no template is given, e.g. `Running 2024-05-01 07:00`.
+pub const DEFAULT_TITLE_TEMPLATE: &str = "%activity %year-%month-%day %hour:%minute";
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Replaces the tokens in the template with their values. The longest tokens are replaced first, so `%activity`
+/// doesn't eat the start of `%activity_detailed`. Each token can also be written in braces, e.g. `{year}` for `%year`.
+///
+/// # Arguments
+///
+/// - `template: &str` -- The template, e.g. `%activity %year-%month-%day` or `{activity} {year}-{month}-{day}`.
+/// - `values: &HashMap<String, String, S>` -- The tokens and their values, as returned by `fit_to_hashmap()` etc.
+///
+/// # Returns
+///
+/// `String` -- The template with the tokens replaced by their trimmed values. Unknown tokens are left as they are.
+#[must_use]
+pub fn fill_template<S: BuildHasher>(
+    template: &str,
+    values: &HashMap<String, String, S>,
+) -> String {
+    let mut tokens: Vec<(&String, &String)> = values.iter().collect();
+    tokens.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+
+    tokens
+        .into_iter()
+        .fold(template.to_string(), |filled, (token, value)| {
+            let braced = format!("{{{}}}", token.trim_start_matches('%'));
+            filled
+                .replace(token.as_str(), value.trim())
+                .replace(&braced, value.trim())
+        })
+}
+
+/// Reads the activity file and builds its title from the template.
+///
+/// # Arguments
+///
+/// - `filename: &str` -- The FIT, GPX or TCX file to read.
+/// - `template: &str` -- The template, e.g. `DEFAULT_TITLE_TEMPLATE`.
+///
+/// # Errors
+///
+/// The file may not be a FIT, GPX or TCX file. Reading or parsing the file may fail.
+#[cfg(feature = "fs")]
+pub fn activity_title(filename: &str, template: &str) -> Result<String, Box<dyn Error>> {
+    let values = match ActivityFormat::from_filename(filename) {
+        Some(ActivityFormat::Fit) => fit_to_hashmap(filename)?,
+        Some(ActivityFormat::Gpx) => gpx_to_hashmap(filename)?,
+        Some(ActivityFormat::Tcx) => tcx_to_hashmap(filename)?,
+        None => return Err(format!("{filename}: Unknown file type.").into()),
+    };
+
+    Ok(fill_template(template, &values))
+}
+
+#[cfg(test)]
+/// Tests for the title module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test replacing the tokens, with the longest first
+    fn test_fill_template() {
+        let values: HashMap<String, String> = [
+            ("%activity", "Running"),
+            ("%activity_detailed", " Treadmill "),
+            ("%year", "2024"),
+        ]
+        .into_iter()
+        .map(|(token, value)| (token.to_string(), value.to_string()))
+        .collect();
+
+        assert_eq!(
+            fill_template("%activity (%activity_detailed) %year %unknown", &values),
+            "Running (Treadmill) 2024 %unknown"
+        );
+        assert_eq!(
+            fill_template("{activity_detailed}-{year}.json {unknown}", &values),
+            "Treadmill-2024.json {unknown}"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test building the title from a file
+    fn test_activity_title() {
+        let title = activity_title("../data/running.gpx", "%activity %year").unwrap();
+        assert!(title.ends_with("2018"));
+        assert!(activity_title("../data/readme.md", DEFAULT_TITLE_TEMPLATE).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! The colors given to the tracks when writing GPX, so viewers such as GPXSee draw each sport in its own color. The
+//! color is written as a `gpx_style:line` extension of the track.
+//!
+//! Each sport has a default color, which can be changed for the whole run using `Settings::set_track_color()`. Sports
+//! without a color get no extension, and are drawn in the viewer's own color.
+
+use std::error::Error;
+
+use crate::Settings;
+
+/// The colors of the sports, as `RRGGBB` hex, used unless changed with `Settings::set_track_color()`.
+const DEFAULT_COLORS: [(&str, &str); 7] = [
+    ("running", "E53935"),
+    ("biking", "1E88E5"),
+    ("cycling", "1E88E5"),
+    ("walking", "43A047"),
+    ("hiking", "6D4C41"),
+    ("swimming", "00ACC1"),
+    ("rowing", "8E24AA"),
+];
+
+impl Settings {
+    /// Sets the color of the tracks of a sport.
+    ///
+    /// # Arguments
+    ///
+    /// `setting: &str` -- The sport and the color as `SPORT=RRGGBB`, e.g. `running=ff0000`. The sport isn't case
+    /// sensitive. Use `SPORT=none` to write the tracks of the sport without a color.
+    ///
+    /// # Errors
+    ///
+    /// The setting may not be `SPORT=COLOR`, or the color may not be six hex digits.
+    pub fn set_track_color(&mut self, setting: &str) -> Result<(), Box<dyn Error>> {
+        let (sport, color) = parse_track_color(setting)?;
+        self.track_colors.insert(sport, color);
+        Ok(())
+    }
+}
+
+/// Splits a `SPORT=COLOR` setting into the lowercase sport and the uppercase color, or `None` for `none`.
+fn parse_track_color(setting: &str) -> Result<(String, Option<String>), Box<dyn Error>> {
+    let Some((sport, color)) = setting.split_once('=') else {
+        return Err(format!("{setting}: The track color must be given as SPORT=RRGGBB.").into());
+    };
+    let (sport, color) = (
+        sport.trim().to_lowercase(),
+        color.trim().trim_start_matches('#'),
+    );
+    if sport.is_empty() {
+        return Err(format!("{setting}: The sport of the track color is missing.").into());
+    }
+
+    if color.eq_ignore_ascii_case("none") {
+        Ok((sport, None))
+    } else if color.len() == 6 && color.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok((sport, Some(color.to_uppercase())))
+    } else {
+        Err(
+            format!("{setting}: The track color must be six hex digits, e.g. FF0000, or none.")
+                .into(),
+        )
+    }
+}
+
+/// The color of the tracks of a sport.
+///
+/// # Arguments
+///
+/// `sport: &str` -- The sport, e.g. `Running`. Not case sensitive.
+///
+/// # Returns
+///
+/// `Option<String>` -- The color as `RRGGBB` hex, or `None` if the sport has no color.
+pub(crate) fn track_color(sport: &str) -> Option<String> {
+    let sport = sport.trim().to_lowercase();
+    if let Some(color) = Settings::current().track_colors.get(&sport).cloned() {
+        return color;
+    }
+
+    DEFAULT_COLORS
+        .iter()
+        .find(|(name, _)| *name == sport)
+        .map(|(_, color)| (*color).to_string())
+}
+
+#[cfg(test)]
+/// Tests for the track_colors module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test reading the track color settings, and rejecting those that aren't colors
+    fn test_parse_track_color() {
+        assert_eq!(
+            parse_track_color("Running=#ff0000").unwrap(),
+            ("running".to_string(), Some("FF0000".to_string()))
+        );
+        assert_eq!(
+            parse_track_color("other=none").unwrap(),
+            ("other".to_string(), None)
+        );
+        assert!(parse_track_color("running").is_err());
+        assert!(parse_track_color("=ff0000").is_err());
+        assert!(parse_track_color("running=red").is_err());
+        assert!(parse_track_color("running=ff00").is_err());
+    }
+
+    #[test]
+    /// Test the default colors
+    fn test_track_color() {
+        assert_eq!(track_color("Running"), Some("E53935".to_string()));
+        assert_eq!(track_color("Other"), None);
+    }
+}
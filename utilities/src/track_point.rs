@@ -0,0 +1,176 @@
+//! A common view of the points of an activity, whatever the format it was read from. FIT records, GPX track
+//! waypoints and TCX trackpoints all turn into `TrackPoint`s, so the analysis and the exporters can walk the points of
+//! any activity the same way.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::analysis::mean_max::MeanMaxSample;
+use crate::analysis::splits::SplitPoint;
+use crate::geojson::geojson_position;
+use crate::{FITRecord, GPXWaypoint, TCXTrackpoint};
+
+/// A point of an activity, with the values every format may have. Values the file doesn't have are left empty, e.g.
+/// the power and speed of TCX trackpoints.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrackPoint {
+    /// When the point was recorded.
+    pub time: Option<DateTime<Local>>,
+
+    /// Latitude in degrees.
+    pub latitude: Option<f64>,
+
+    /// Longitude in degrees.
+    pub longitude: Option<f64>,
+
+    /// Elevation in meters.
+    pub elevation: Option<f64>,
+
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<f64>,
+
+    /// Cadence in beats (or revolutions) per minute.
+    pub cadence: Option<f64>,
+
+    /// Power in watts.
+    pub power: Option<f64>,
+
+    /// Speed in meters per second.
+    pub speed: Option<f64>,
+
+    /// Distance covered since the start in meters, where the file records it.
+    pub distance: Option<f64>,
+}
+
+impl TrackPoint {
+    /// The GeoJSON position of the point, which is longitude first, with the elevation added if known.
+    ///
+    /// # Returns
+    ///
+    /// `Option<Vec<f64>>` -- The position, or `None` if the point has no latitude or longitude.
+    #[must_use]
+    pub fn position(&self) -> Option<Vec<f64>> {
+        geojson_position(self.latitude, self.longitude, self.elevation)
+    }
+
+    /// The point as a sample for the mean-maximal curve, if it has a time.
+    pub(crate) fn mean_max_sample(&self) -> Option<MeanMaxSample> {
+        Some(MeanMaxSample {
+            time: self.time?,
+            power: self.power,
+            heart_rate: self.heart_rate,
+        })
+    }
+
+    /// The point as a point for the split analysis, if it has both a time and a distance.
+    pub(crate) fn split_point(&self) -> Option<SplitPoint> {
+        Some(SplitPoint {
+            time: self.time?,
+            distance: self.distance?,
+            heart_rate: self.heart_rate,
+            altitude: self.elevation,
+        })
+    }
+}
+
+impl From<&FITRecord> for TrackPoint {
+    fn from(rec: &FITRecord) -> Self {
+        Self {
+            time: rec.timestamp,
+            latitude: rec.lat,
+            longitude: rec.lon,
+            elevation: rec.altitude.map(|altitude| altitude.value),
+            heart_rate: rec.heartrate.map(f64::from),
+            cadence: rec.cadence.map(f64::from),
+            power: rec.power.map(f64::from),
+            speed: rec.speed.map(|speed| speed.value),
+            distance: rec.distance.map(|distance| distance.value),
+        }
+    }
+}
+
+impl From<&GPXWaypoint> for TrackPoint {
+    fn from(wpt: &GPXWaypoint) -> Self {
+        Self {
+            time: wpt.time,
+            latitude: wpt.latitude,
+            longitude: wpt.longitude,
+            elevation: wpt.elevation,
+            heart_rate: wpt.heart_rate.map(f64::from),
+            cadence: wpt.cadence.map(f64::from),
+            power: wpt.power.map(f64::from),
+            speed: wpt.speed,
+            distance: None,
+        }
+    }
+}
+
+impl From<&TCXTrackpoint> for TrackPoint {
+    fn from(tp: &TCXTrackpoint) -> Self {
+        Self {
+            time: Some(tp.time),
+            latitude: tp.latitude,
+            longitude: tp.longitude,
+            elevation: tp.altitude_meters,
+            heart_rate: tp.heart_rate,
+            cadence: tp.cadence.map(f64::from),
+            power: None,
+            speed: None,
+            distance: tp.distance_meters,
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests for the track_point module
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::{Activity, FITActivity, GPXActivity, TCXTrackpointList};
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test reading the points of each format
+    fn test_track_points() {
+        let fit = FITActivity::from_file("../data/rowing.fit").unwrap();
+        let points: Vec<TrackPoint> = fit.track_points().collect();
+        assert_eq!(points.len(), fit.records.len());
+        assert!(points.iter().any(|point| point.heart_rate.is_some()));
+        assert!(points.iter().any(|point| point.distance.is_some()));
+
+        let gpx = GPXActivity::from_file("../data/running.gpx").unwrap();
+        let points: Vec<TrackPoint> = gpx.track_points().collect();
+        assert_eq!(
+            points.len(),
+            gpx.tracks
+                .iter()
+                .map(|track| track.waypoints.len())
+                .sum::<usize>()
+        );
+        assert!(points.iter().all(|point| point.distance.is_none()));
+        assert!(points[0].position().is_some());
+
+        let tcx = TCXTrackpointList::from_file("../data/running.tcx").unwrap();
+        let points: Vec<TrackPoint> = tcx.track_points().collect();
+        assert_eq!(points.len(), tcx.trackpoints.len());
+        assert!(points
+            .iter()
+            .all(|point| point.time.is_some() && point.power.is_none()));
+
+        let activity = Activity::from_file("../data/running.tcx").unwrap();
+        assert_eq!(activity.track_points().count(), tcx.trackpoints.len());
+    }
+
+    #[test]
+    /// Test the GeoJSON position of a point
+    fn test_position() {
+        let point = TrackPoint {
+            latitude: Some(59.0),
+            longitude: Some(10.0),
+            elevation: Some(12.5),
+            ..TrackPoint::default()
+        };
+        assert_eq!(point.position(), Some(vec![10.0, 59.0, 12.5]));
+        assert!(TrackPoint::default().position().is_none());
+    }
+}
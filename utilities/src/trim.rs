@@ -0,0 +1,375 @@
+//! Trims an activity down to the part that matters: the standing still before the start and after the finish, e.g.
+//! while waiting for the GPS or forgetting to stop the watch, and optionally anything outside a time or distance range.
+//!
+//! Unlike cutting out a part with `extract`, the laps and the sports of a multisport FIT file are kept, each with its
+//! totals worked out again from the records left. GPX and TCX files keep their track segments.
+
+use std::error::Error;
+use uuid::Uuid;
+
+use crate::analysis::track_stats::MOVING_SPEED_MIN_MS;
+#[cfg(feature = "fs")]
+use crate::extract::write_part;
+use crate::extract::{sliced_lap, sliced_session, RangePoint, TimeWindow};
+use crate::{Activity, ExtractRange, FITActivity, FITLap, FITRecord, GPXActivity};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// What to trim off an activity.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrimOptions {
+    /// Trim the standing still at the start and the finish, i.e. everything before the activity first moves and after
+    /// it last moves.
+    pub idle: bool,
+
+    /// Only keep the part of the activity in this range, if any.
+    pub range: Option<ExtractRange>,
+}
+
+impl TrimOptions {
+    /// The times of the activity to keep.
+    ///
+    /// # Arguments
+    ///
+    /// - `points: &[RangePoint]` -- The records or track points of the activity, in order.
+    /// - `laps: &[TimeWindow]` -- The times of each lap, in order.
+    ///
+    /// # Errors
+    ///
+    /// The range may not be found in the activity, or the activity may never move.
+    fn window(
+        &self,
+        points: &[RangePoint],
+        laps: &[TimeWindow],
+    ) -> Result<TimeWindow, Box<dyn Error>> {
+        let window = match &self.range {
+            Some(range) => range.window(points, laps)?,
+            None => TimeWindow::default(),
+        };
+        if self.idle {
+            moving_window(points, &window).ok_or_else(|| "The activity never moves.".into())
+        } else {
+            Ok(window)
+        }
+    }
+}
+
+/// The part of the window from the last point before the activity starts moving to the last point where it moves.
+/// Activities without any speeds are left as they are.
+///
+/// # Arguments
+///
+/// - `points: &[RangePoint]` -- The records or track points of the activity, in order.
+/// - `window: &TimeWindow` -- The times to look within.
+///
+/// # Returns
+///
+/// `Option<TimeWindow>` -- The times the activity moves, or `None` if it never moves within the window.
+fn moving_window(points: &[RangePoint], window: &TimeWindow) -> Option<TimeWindow> {
+    let points: Vec<&RangePoint> = points
+        .iter()
+        .filter(|point| window.contains(point.time))
+        .collect();
+    if points.iter().all(|point| point.speed_ms.is_none()) {
+        return Some(*window);
+    }
+
+    let moving = |point: &&RangePoint| {
+        point
+            .speed_ms
+            .is_some_and(|speed| speed >= MOVING_SPEED_MIN_MS)
+    };
+    let first = points.iter().position(moving)?;
+    let last = points.iter().rposition(moving)?;
+    Some(TimeWindow {
+        from: points[first.saturating_sub(1)].time,
+        to: points[last].time,
+    })
+}
+
+impl Activity {
+    /// Trims the activity. TCX activities are read through GPX, so the activity trimmed is a GPX activity.
+    ///
+    /// # Arguments
+    ///
+    /// `options: &TrimOptions` -- What to trim off.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Activity, Box<dyn Error>>` -- The activity trimmed, with its summary worked out again.
+    ///
+    /// # Errors
+    ///
+    /// The range may not be found in the activity, or there may be nothing left after trimming.
+    pub fn trim(&self, options: &TrimOptions) -> Result<Self, Box<dyn Error>> {
+        match self {
+            Self::Fit(activity) => Ok(Self::Fit(activity.trim(options)?)),
+            Self::Gpx(activity) => Ok(Self::Gpx(activity.trim(options)?)),
+            Self::Tcx(..) => Ok(Self::Gpx(self.tcx_as_gpx()?.trim(options)?)),
+        }
+    }
+}
+
+impl FITActivity {
+    /// Trims the activity. The laps and the sessions of each sport are kept where they have records left, with their
+    /// totals worked out again from them. The events and device splits are left out.
+    ///
+    /// # Arguments
+    ///
+    /// `options: &TrimOptions` -- What to trim off.
+    ///
+    /// # Errors
+    ///
+    /// The range may not be found in the activity, or there may be no records left after trimming.
+    pub fn trim(&self, options: &TrimOptions) -> Result<Self, Box<dyn Error>> {
+        let window = options.window(&self.range_points(), &self.lap_windows())?;
+        let mut records: Vec<FITRecord> = self
+            .records
+            .iter()
+            .filter(|record| window.contains(record.timestamp))
+            .cloned()
+            .collect();
+        if records.is_empty() {
+            return Err("There are no records left after trimming.".into());
+        }
+
+        let mut session = sliced_session(&self.session, &records);
+        for record in &mut records {
+            record.activity_uuid = session.uuid;
+        }
+
+        let laps = self.laps_of(&records, session.uuid);
+
+        // Multisport files keep a session per sport
+        let mut sessions = Vec::new();
+        if self.sessions.len() > 1 {
+            for original in &self.sessions {
+                let sport_records: Vec<FITRecord> = records
+                    .iter()
+                    .filter(|record| record.session_num == original.session_num)
+                    .cloned()
+                    .collect();
+                if sport_records.is_empty() {
+                    continue;
+                }
+                let mut sport = sliced_session(original, &sport_records);
+                sport.session_num = original.session_num;
+                sport.num_laps = u16::try_from(
+                    laps.iter()
+                        .filter(|lap| lap.session_num == original.session_num)
+                        .count(),
+                )
+                .ok();
+                sessions.push(sport);
+            }
+        }
+        session.session_num = Some(1);
+        session.num_laps = u16::try_from(laps.len()).ok();
+        session.num_sessions = u16::try_from(sessions.len().max(1)).ok();
+        if sessions.is_empty() {
+            sessions.push(session.clone());
+        }
+
+        Ok(Self {
+            session,
+            sessions,
+            laps,
+            records,
+            events: Vec::new(),
+            device_splits: Vec::new(),
+            devices: self.devices.clone(),
+            warnings: Vec::new(),
+        })
+    }
+
+    /// The laps of some of the records of the activity, each worked out again from its records. Each record belongs to
+    /// the last lap started before it, and laps without any of the records are left out.
+    ///
+    /// # Arguments
+    ///
+    /// - `records: &[FITRecord]` -- The records kept, in order.
+    /// - `uuid: Option<Uuid>` -- The activity UUID of the activity the laps go in.
+    pub(crate) fn laps_of(&self, records: &[FITRecord], uuid: Option<Uuid>) -> Vec<FITLap> {
+        let mut laps: Vec<FITLap> = Vec::new();
+        let lap_of = |record: &FITRecord| {
+            self.laps
+                .iter()
+                .rposition(|lap| {
+                    lap.start_time
+                        .is_some_and(|start| Some(start) <= record.timestamp)
+                })
+                .unwrap_or_default()
+        };
+        for lap_records in records.chunk_by(|a, b| lap_of(a) == lap_of(b)) {
+            let original = self.laps.get(lap_of(&lap_records[0]));
+            laps.push(FITLap {
+                lap_num: u64::try_from(laps.len() + 1).ok(),
+                activity_uuid: uuid,
+                session_num: original.and_then(|lap| lap.session_num).or(Some(1)),
+                ..sliced_lap(&sliced_session(&self.session, lap_records))
+            });
+        }
+        laps
+    }
+}
+
+impl GPXActivity {
+    /// Trims the activity. The track segments are kept where they have track points left.
+    ///
+    /// # Arguments
+    ///
+    /// `options: &TrimOptions` -- What to trim off.
+    ///
+    /// # Errors
+    ///
+    /// The range may not be found in the activity, or there may be no track points left after trimming.
+    pub fn trim(&self, options: &TrimOptions) -> Result<Self, Box<dyn Error>> {
+        let window = options.window(&self.range_points(), &self.lap_windows())?;
+        self.between(&window)?
+            .ok_or_else(|| "There are no track points left after trimming.".into())
+    }
+}
+
+/// Trims an activity file and writes it to a new file. The format of the new file is worked out from its extension:
+/// FIT files can only be trimmed into FIT files, and GPX and TCX files into GPX or TCX.
+///
+/// # Arguments
+///
+/// - `input: &str` -- The FIT, GPX or TCX file to read.
+/// - `output: &str` -- The file to write.
+/// - `options: &TrimOptions` -- What to trim off.
+///
+/// # Returns
+///
+/// `Result<Activity, Box<dyn Error>>` -- The activity trimmed, e.g. for printing its summary.
+///
+/// # Errors
+///
+/// The input may not be a FIT, GPX or TCX file, or the output may not be a format the input can be written as. The
+/// range may not be found, or there may be nothing left after trimming. Reading, parsing or writing may fail.
+#[cfg(feature = "fs")]
+pub fn trim_file(
+    input: &str,
+    output: &str,
+    options: &TrimOptions,
+) -> Result<Activity, Box<dyn Error>> {
+    let trimmed = Activity::from_file(input)?.trim(options)?;
+    write_part(&trimmed, output)?;
+
+    Ok(trimmed)
+}
+
+#[cfg(test)]
+/// Tests for the trim module
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::Duration;
+    use chrono::{Local, TimeZone};
+    #[cfg(feature = "fs")]
+    use uom::si::{f64::Length, f64::Velocity, length::meter, velocity::meter_per_second};
+
+    /// A point `secs` into the activity at the speed given.
+    fn point(secs: i64, speed: f64) -> RangePoint {
+        RangePoint {
+            time: Local.timestamp_opt(1_600_000_000 + secs, 0).single(),
+            distance_m: None,
+            speed_ms: Some(speed),
+        }
+    }
+
+    #[test]
+    /// Test finding the times the activity moves
+    fn test_moving_window() {
+        let points = [
+            point(0, 0.0),
+            point(10, 0.1),
+            point(20, 3.0),
+            point(30, 3.0),
+            point(40, 0.0),
+        ];
+        let window = moving_window(&points, &TimeWindow::default()).unwrap();
+        assert_eq!(window.from, points[1].time);
+        assert_eq!(window.to, points[3].time);
+
+        let still = [point(0, 0.0), point(10, 0.2)];
+        assert!(moving_window(&still, &TimeWindow::default()).is_none());
+
+        let no_speed = [RangePoint::default()];
+        assert_eq!(
+            moving_window(&no_speed, &TimeWindow::default()),
+            Some(TimeWindow::default())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test trimming the standing still and a distance off a FIT file, keeping its laps
+    fn test_trim_fit() {
+        // Stand still for the first and last minute, and move at 2 m/s in between
+        let mut activity = FITActivity::from_file("../data/test.fit").unwrap();
+        let num_records = activity.records.len();
+        let mut distance = 0.0;
+        for (num, record) in activity.records.iter_mut().enumerate() {
+            let speed = if (60..num_records - 60).contains(&num) {
+                2.0
+            } else {
+                0.0
+            };
+            distance += speed;
+            record.speed = Some(Velocity::new::<meter_per_second>(speed));
+            record.distance = Some(Length::new::<meter>(distance));
+        }
+
+        let idle = TrimOptions {
+            idle: true,
+            range: None,
+        };
+        let trimmed = activity.trim(&idle).unwrap();
+        assert_eq!(trimmed.records.len(), num_records - 119);
+        assert_eq!(
+            trimmed.session.num_laps,
+            u16::try_from(trimmed.laps.len()).ok()
+        );
+        assert!(trimmed.session.duration < activity.session.duration);
+
+        let options = TrimOptions {
+            idle: true,
+            range: Some(ExtractRange::Distance {
+                from: Some(crate::parse_distance("500m").unwrap()),
+                to: None,
+            }),
+        };
+        let trimmed = activity.trim(&options).unwrap();
+        assert!(trimmed.records[0].distance.unwrap().value >= 500.0);
+        assert!(trimmed.session.distance.unwrap().value <= distance - 500.0);
+
+        let too_far = TrimOptions {
+            idle: false,
+            range: Some(ExtractRange::Time {
+                from: Some(Duration::from_secs_f64(1_000_000.0)),
+                to: None,
+            }),
+        };
+        assert!(activity.trim(&too_far).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    /// Test trimming a time range off a GPX file
+    fn test_trim_gpx() {
+        let activity = GPXActivity::from_file("../data/walking2.gpx").unwrap();
+        let options = TrimOptions {
+            idle: true,
+            range: Some(ExtractRange::Time {
+                from: None,
+                to: Some(Duration::from_secs_f64(600.0)),
+            }),
+        };
+        let trimmed = activity.trim(&options).unwrap();
+
+        let start = activity.tracks[0].waypoints[0].time.unwrap();
+        let finish = trimmed.tracks[0].waypoints.last().unwrap().time.unwrap();
+        assert!(finish <= start + chrono::Duration::seconds(600));
+        assert!(trimmed.metadata.distance_m < activity.metadata.distance_m);
+    }
+}
@@ -0,0 +1,412 @@
+//! The units the distances, lengths and speeds are shown in. The files record meters and meters per second, which is
+//! what the summaries and exports use unless a unit system is set for the run using `Settings::set_unit_system()`.
+//!
+//! With a unit system set, the console output shows kilometers or miles, meters or feet, km/h or mph, and the pace of
+//! foot sports. The CSV, Parquet and JSON Lines columns holding distances, lengths and speeds are converted as well,
+//! and renamed after the new unit, e.g. `distance_m` becomes `distance_km` or `distance_mi`.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{normalized_sport, Duration, Settings};
+
+/// The sports shown with a pace as well as a speed.
+const FOOT_SPORTS: [&str; 3] = ["running", "walking", "hiking"];
+
+/// Meters in a kilometer.
+const METERS_PER_KM: f64 = 1_000.0;
+
+/// Meters in a mile.
+const METERS_PER_MILE: f64 = 1_609.344;
+
+/// Meters in a foot.
+const METERS_PER_FOOT: f64 = 0.3048;
+
+/// Columns holding distances, lengths or speeds that don't say so in their names.
+const UNSUFFIXED_COLUMNS: [(&str, Measure); 6] = [
+    ("average_speed", Measure::Speed),
+    ("maximum_speed", Measure::Speed),
+    ("speed", Measure::Speed),
+    ("elevation", Measure::Length),
+    ("start_altitude", Measure::Length),
+    ("max_altitude", Measure::Length),
+];
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The unit systems the distances, lengths and speeds can be shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSystem {
+    /// Kilometers, meters and km/h, with the pace in minutes per kilometer.
+    Metric,
+    /// Miles, feet and mph, with the pace in minutes per mile.
+    Imperial,
+}
+
+impl UnitSystem {
+    /// The values accepted on the command line.
+    pub const VALUES: [&'static str; 2] = ["metric", "imperial"];
+
+    /// The unit of the pace, e.g. `min/km`.
+    #[must_use]
+    pub const fn pace_unit(self) -> &'static str {
+        match self {
+            Self::Metric => "min/km",
+            Self::Imperial => "min/mi",
+        }
+    }
+
+    /// Works out the pace from the speed.
+    ///
+    /// # Arguments
+    ///
+    /// `speed_ms: f64` -- The speed in meters per second.
+    ///
+    /// # Returns
+    ///
+    /// `Option<Duration>` -- The time taken per kilometer or mile, or `None` if the speed is zero.
+    #[must_use]
+    pub fn pace(self, speed_ms: f64) -> Option<Duration> {
+        let meters = match self {
+            Self::Metric => METERS_PER_KM,
+            Self::Imperial => METERS_PER_MILE,
+        };
+        (speed_ms > 0.0).then(|| Duration::from_secs_f64(meters / speed_ms))
+    }
+}
+
+impl FromStr for UnitSystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "metric" => Ok(Self::Metric),
+            "imperial" => Ok(Self::Imperial),
+            _ => Err(format!("Unknown unit system {s}. Use metric or imperial.")),
+        }
+    }
+}
+
+impl fmt::Display for UnitSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Metric => write!(f, "metric"),
+            Self::Imperial => write!(f, "imperial"),
+        }
+    }
+}
+
+impl Settings {
+    /// Sets the unit system used by the console output and the exports.
+    ///
+    /// # Arguments
+    ///
+    /// `units: Option<UnitSystem>` -- The unit system to use. `None` goes back to the units recorded in the files.
+    pub fn set_unit_system(&mut self, units: Option<UnitSystem>) {
+        self.unit_system = units;
+    }
+}
+
+/// The unit system set with `Settings::set_unit_system()`, if any.
+pub(crate) fn unit_system() -> Option<UnitSystem> {
+    Settings::current().unit_system
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The kinds of values that change with the unit system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    /// The distance covered: meters, kilometers or miles.
+    Distance,
+    /// Heights and changes in height: meters or feet.
+    Length,
+    /// Speeds: m/s, km/h or mph.
+    Speed,
+}
+
+impl Measure {
+    /// The unit shown in the console output.
+    ///
+    /// # Arguments
+    ///
+    /// `units: Option<UnitSystem>` -- The unit system, or `None` for the units recorded in the files.
+    #[must_use]
+    pub const fn unit(self, units: Option<UnitSystem>) -> &'static str {
+        match (self, units) {
+            (Self::Distance | Self::Length, None) | (Self::Length, Some(UnitSystem::Metric)) => "m",
+            (Self::Speed, None) => "m/s",
+            (Self::Distance, Some(UnitSystem::Metric)) => "km",
+            (Self::Speed, Some(UnitSystem::Metric)) => "km/h",
+            (Self::Distance, Some(UnitSystem::Imperial)) => "mi",
+            (Self::Length, Some(UnitSystem::Imperial)) => "ft",
+            (Self::Speed, Some(UnitSystem::Imperial)) => "mph",
+        }
+    }
+
+    /// Converts a value from the units recorded in the files.
+    ///
+    /// # Arguments
+    ///
+    /// - `value: f64` -- The value in meters or meters per second.
+    /// - `units: Option<UnitSystem>` -- The unit system, or `None` to leave the value as it is.
+    #[must_use]
+    pub fn convert(self, value: f64, units: Option<UnitSystem>) -> f64 {
+        value * self.factor(units)
+    }
+
+    /// What the values in meters or meters per second are multiplied by.
+    fn factor(self, units: Option<UnitSystem>) -> f64 {
+        match (self, units) {
+            (_, None) | (Self::Length, Some(UnitSystem::Metric)) => 1.0,
+            (Self::Distance, Some(UnitSystem::Metric)) => 1.0 / METERS_PER_KM,
+            (Self::Speed, Some(UnitSystem::Metric)) => 3_600.0 / METERS_PER_KM,
+            (Self::Distance, Some(UnitSystem::Imperial)) => 1.0 / METERS_PER_MILE,
+            (Self::Length, Some(UnitSystem::Imperial)) => 1.0 / METERS_PER_FOOT,
+            (Self::Speed, Some(UnitSystem::Imperial)) => 3_600.0 / METERS_PER_MILE,
+        }
+    }
+
+    /// The unit as used at the end of a column name, e.g. `kmh` for `speed_avg_kmh`.
+    const fn column_suffix(self, units: UnitSystem) -> &'static str {
+        match (self, units) {
+            (Self::Distance, UnitSystem::Metric) => "km",
+            (Self::Length, UnitSystem::Metric) => "m",
+            (Self::Speed, UnitSystem::Metric) => "kmh",
+            (Self::Distance, UnitSystem::Imperial) => "mi",
+            (Self::Length, UnitSystem::Imperial) => "ft",
+            (Self::Speed, UnitSystem::Imperial) => "mph",
+        }
+    }
+
+    /// The number of decimals to add when converting, so kilometers and miles keep the resolution of the meters.
+    const fn extra_decimals(self, units: UnitSystem) -> usize {
+        match (self, units) {
+            (Self::Distance, UnitSystem::Metric) => 3,
+            (Self::Distance, UnitSystem::Imperial) => 4,
+            _ => 0,
+        }
+    }
+
+    /// What a column holds, from its name, along with the name without its unit.
+    fn of_column(name: &str) -> Option<(Self, &str)> {
+        if let Some((_, measure)) = UNSUFFIXED_COLUMNS
+            .iter()
+            .find(|(column, _)| *column == name)
+        {
+            return Some((*measure, name));
+        }
+
+        let (stem, measure) = if let Some(stem) = name.strip_suffix("_ms") {
+            (stem, Self::Speed)
+        } else {
+            let stem = name
+                .strip_suffix("_meters")
+                .or_else(|| name.strip_suffix("_m"))?;
+            if stem.ends_with("distance") {
+                (stem, Self::Distance)
+            } else {
+                (stem, Self::Length)
+            }
+        };
+        Some((measure, stem))
+    }
+}
+
+/// The unit of a column holding a distance, length or speed, in the unit system given, e.g. `km` for `distance_m`.
+pub(crate) fn measured_unit(column: &str, units: Option<UnitSystem>) -> Option<&'static str> {
+    Measure::of_column(column).map(|(measure, _)| measure.unit(units))
+}
+
+/// The label of a value in the console output, with the unit of the run, e.g. `Distance (km):`.
+pub(crate) fn label(name: &str, measure: Measure) -> String {
+    format!("{name} ({}):", measure.unit(unit_system()))
+}
+
+/// The value converted from meters or meters per second to the unit system of the run, for the console output.
+pub(crate) fn converted(measure: Measure, value: f64) -> f64 {
+    measure.convert(value, unit_system())
+}
+
+/// The pace at the speed in the unit system of the run, for foot sports such as running. Other sports, and runs
+/// without a unit system, have no pace.
+///
+/// # Arguments
+///
+/// - `name: &str` -- The name of the value, e.g. `Pace Avg`.
+/// - `sport: Option<&str>` -- The sport as found in the file.
+/// - `speed_ms: f64` -- The speed in meters per second.
+///
+/// # Returns
+///
+/// `Option<(String, Duration)>` -- The label and the pace, e.g. `Pace Avg (min/km):` and 5 minutes.
+pub(crate) fn pace_label(
+    name: &str,
+    sport: Option<&str>,
+    speed_ms: f64,
+) -> Option<(String, Duration)> {
+    let units = unit_system()?;
+    let sport = normalized_sport(sport?);
+    if !FOOT_SPORTS.contains(&sport.as_str()) {
+        return None;
+    }
+    Some((
+        format!("{name} ({}):", units.pace_unit()),
+        units.pace(speed_ms)?,
+    ))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// The columns of an export with the names and values changed to the unit system set with
+/// `Settings::set_unit_system()`.
+#[derive(Debug, Clone)]
+pub(crate) struct UnitColumns {
+    /// The names of the columns, renamed after their new units.
+    pub(crate) names: Vec<String>,
+
+    /// What each column holds, if it changes with the unit system.
+    measures: Vec<Option<Measure>>,
+
+    /// The unit system, or `None` to leave the columns as they are.
+    units: Option<UnitSystem>,
+}
+
+impl UnitColumns {
+    /// Works out the names and conversions of the columns.
+    ///
+    /// # Arguments
+    ///
+    /// `columns: &[S]` -- The names of the columns as recorded, e.g. `distance_m`.
+    pub(crate) fn new<S: AsRef<str>>(columns: &[S]) -> Self {
+        Self::with_units(columns, unit_system())
+    }
+
+    /// Works out the names and conversions of the columns for the unit system given.
+    fn with_units<S: AsRef<str>>(columns: &[S], units: Option<UnitSystem>) -> Self {
+        let mut names = Vec::with_capacity(columns.len());
+        let mut measures = Vec::with_capacity(columns.len());
+        for column in columns {
+            let column = column.as_ref();
+            match (units, Measure::of_column(column)) {
+                (Some(units), Some((measure, stem))) => {
+                    names.push(format!("{stem}_{}", measure.column_suffix(units)));
+                    measures.push(Some(measure));
+                }
+                _ => {
+                    names.push(column.to_string());
+                    measures.push(None);
+                }
+            }
+        }
+
+        Self {
+            names,
+            measures,
+            units,
+        }
+    }
+
+    /// Whether any of the values are converted.
+    pub(crate) fn converts(&self) -> bool {
+        self.units.is_some() && self.measures.iter().any(Option::is_some)
+    }
+
+    /// Converts a cell of the column given. Cells that aren't numbers are left as they are.
+    ///
+    /// # Arguments
+    ///
+    /// - `index: usize` -- The position of the column.
+    /// - `cell: &str` -- The cell as written to the CSV.
+    pub(crate) fn convert<'a>(&self, index: usize, cell: &'a str) -> Cow<'a, str> {
+        let (Some(units), Some(Some(measure))) = (self.units, self.measures.get(index)) else {
+            return Cow::Borrowed(cell);
+        };
+        let Some(value) = cell.parse::<f64>().ok().filter(|value| value.is_finite()) else {
+            return Cow::Borrowed(cell);
+        };
+
+        let converted = measure.convert(value, Some(units));
+        if cell.contains(['e', 'E']) {
+            return Cow::Owned(converted.to_string());
+        }
+        let decimals = cell
+            .split_once('.')
+            .map_or(0, |(_, decimals)| decimals.len());
+        Cow::Owned(format!(
+            "{converted:.*}",
+            (decimals + measure.extra_decimals(units)).min(15)
+        ))
+    }
+}
+
+#[cfg(test)]
+/// Tests for the units module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the unit system names
+    fn test_unit_system() {
+        for value in UnitSystem::VALUES {
+            assert_eq!(value.parse::<UnitSystem>().unwrap().to_string(), value);
+        }
+        assert!("nautical".parse::<UnitSystem>().is_err());
+
+        assert_eq!(
+            UnitSystem::Metric.pace(1_000.0 / 300.0),
+            Some(Duration::from_secs_f64(300.0))
+        );
+        assert!(UnitSystem::Imperial.pace(0.0).is_none());
+    }
+
+    #[test]
+    /// Test converting the values shown in the console
+    fn test_convert() {
+        let imperial = Some(UnitSystem::Imperial);
+        assert!((Measure::Distance.convert(1_609.344, imperial) - 1.0).abs() < 1e-9);
+        assert!((Measure::Length.convert(3.048, imperial) - 10.0).abs() < 1e-9);
+        assert!((Measure::Speed.convert(10.0, Some(UnitSystem::Metric)) - 36.0).abs() < 1e-9);
+        assert!((Measure::Speed.convert(10.0, None) - 10.0).abs() < f64::EPSILON);
+
+        assert_eq!(Measure::Speed.unit(None), "m/s");
+        assert_eq!(Measure::Length.unit(Some(UnitSystem::Metric)), "m");
+        assert_eq!(Measure::Distance.unit(imperial), "mi");
+    }
+
+    #[test]
+    /// Test renaming and converting the columns
+    fn test_unit_columns() {
+        let columns = [
+            "distance_m",
+            "altitude_meters",
+            "speed_avg_ms",
+            "average_speed",
+            "heartrate_avg_bpm",
+        ];
+        let metric = UnitColumns::with_units(&columns, Some(UnitSystem::Metric));
+        assert_eq!(
+            metric.names,
+            [
+                "distance_km",
+                "altitude_m",
+                "speed_avg_kmh",
+                "average_speed_kmh",
+                "heartrate_avg_bpm"
+            ]
+        );
+        assert!(metric.converts());
+        assert_eq!(metric.convert(0, "2796.4"), "2.7964");
+        assert_eq!(metric.convert(1, "12.5"), "12.5");
+        assert_eq!(metric.convert(2, "2.5"), "9.0");
+        assert_eq!(metric.convert(2, ""), "");
+        assert_eq!(metric.convert(4, "150"), "150");
+
+        let imperial = UnitColumns::with_units(&columns, Some(UnitSystem::Imperial));
+        assert_eq!(imperial.names[1], "altitude_ft");
+        assert_eq!(imperial.convert(0, "1609.344"), "1.0000000");
+
+        let recorded = UnitColumns::with_units(&columns, None);
+        assert_eq!(recorded.names, columns);
+        assert!(!recorded.converts());
+        assert_eq!(recorded.convert(0, "2796.4"), "2796.4");
+    }
+}
@@ -0,0 +1,14 @@
+//! Warnings found while parsing a file. The parsers collect them on the activity, so the tools can show them and
+//! include them in the manifest, rather than them only ending up in the log.
+
+/// Passes the warnings found in the file on to the log.
+///
+/// # Arguments
+///
+/// - `filename: Option<&str>` -- The file the warnings were found in.
+/// - `warnings: &[String]` -- The warnings.
+pub(crate) fn log_warnings(filename: Option<&str>, warnings: &[String]) {
+    for warning in warnings {
+        log::warn!("{}: {warning}", filename.unwrap_or("unknown"));
+    }
+}
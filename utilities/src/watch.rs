@@ -0,0 +1,263 @@
+//! Watches a directory for new activity files, e.g. the `GARMIN/Activity` folder the watch syncs into, and processes
+//! each one once it has been written. The files processed are recorded in a ledger file, so a file is only processed
+//! once, also across runs, and the files added while nothing was watching are caught up on at the start.
+
+use notify_debouncer_mini::notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::ActivityFormat;
+
+/// How long a file has to be left alone before it is processed, so a file being synced is only read once it is
+/// complete. A file that still can't be read is tried again the next time it changes.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// The files processed so far, read from and appended to the ledger file. The files are recorded relative to the
+/// directory watched, one per line.
+#[derive(Debug)]
+struct Ledger {
+    /// The directory watched.
+    dir: PathBuf,
+
+    /// The ledger file.
+    path: PathBuf,
+
+    /// The files processed, relative to the directory.
+    processed: HashSet<String>,
+}
+
+impl Ledger {
+    /// Reads the ledger, or starts an empty one if the file doesn't exist yet.
+    fn open(dir: &Path, path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut processed = HashSet::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    processed.insert(line.trim().to_string());
+                }
+            }
+        }
+        log::debug!(
+            "watch::Ledger::open() -- {} files already processed according to {}",
+            processed.len(),
+            path.display()
+        );
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            path: path.to_path_buf(),
+            processed,
+        })
+    }
+
+    /// The name the file is recorded under.
+    fn key(&self, filename: &str) -> String {
+        let path = Path::new(filename);
+        path.strip_prefix(&self.dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Whether the file has been processed.
+    fn contains(&self, filename: &str) -> bool {
+        self.processed.contains(&self.key(filename))
+    }
+
+    /// Records the file as processed, adding it to the ledger file straight away so it isn't processed again if the
+    /// run is stopped.
+    fn record(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let key = self.key(filename);
+        if self.processed.insert(key.clone()) {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            writeln!(file, "{key}")?;
+        }
+        Ok(())
+    }
+
+    /// Processes the file unless it has been already, and records it along with the activity files written.
+    fn process<F>(&mut self, filename: &str, process: &mut F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&str) -> Result<Vec<String>, Box<dyn Error>>,
+    {
+        if self.contains(filename) {
+            log::trace!("watch::Ledger::process() -- {filename} already processed");
+            return Ok(());
+        }
+
+        match process(filename) {
+            Ok(written) => {
+                self.record(filename)?;
+                for written in &written {
+                    self.record(written)?;
+                }
+            }
+            Err(err) => log::warn!("{filename}: Unable to process the file. {err}"),
+        }
+        Ok(())
+    }
+}
+
+/// The activity files of the formats given in the directory and below it that haven't been processed, by name.
+fn pending_files(
+    dir: &Path,
+    formats: &[ActivityFormat],
+    ledger: &Ledger,
+) -> Result<BTreeSet<String>, Box<dyn Error>> {
+    let mut pending = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            pending.extend(pending_files(&path, formats, ledger)?);
+        } else if is_watched(&path, formats) {
+            let filename = path.to_string_lossy().to_string();
+            if !ledger.contains(&filename) {
+                pending.insert(filename);
+            }
+        }
+    }
+    Ok(pending)
+}
+
+/// Whether the file is an activity file of one of the formats given.
+fn is_watched(path: &Path, formats: &[ActivityFormat]) -> bool {
+    path.is_file()
+        && ActivityFormat::from_filename(&path.to_string_lossy())
+            .is_some_and(|format| formats.contains(&format))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Watches the directory, and the directories below it, for activity files of the formats given, and processes each
+/// file once it has been left alone for a couple of seconds. The files already in the directory that aren't in the
+/// ledger are processed first. Runs until the program is stopped.
+///
+/// # Arguments
+///
+/// - `dir: &str` -- The directory to watch.
+/// - `ledger: &str` -- The file recording the files processed, e.g. `.fit2csv-processed` in the directory.
+/// - `formats: &[ActivityFormat]` -- The formats to process. Other files are left alone.
+/// - `process: F` -- Processes a file, returning the activity files it wrote, e.g. the new name of a renamed file,
+///   so they aren't processed in turn. A file that fails is logged and tried again the next time it changes.
+///
+/// # Errors
+///
+/// The directory may not exist or can't be watched, or the ledger can't be read or written.
+pub fn watch_directory<F>(
+    dir: &str,
+    ledger: &str,
+    formats: &[ActivityFormat],
+    mut process: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&str) -> Result<Vec<String>, Box<dyn Error>>,
+{
+    let dir = fs::canonicalize(dir)?;
+    let mut ledger = Ledger::open(&dir, Path::new(ledger))?;
+
+    // Catch up on the files added while nothing was watching
+    for filename in pending_files(&dir, formats, &ledger)? {
+        ledger.process(&filename, &mut process)?;
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE, tx)?;
+    debouncer.watcher().watch(&dir, RecursiveMode::Recursive)?;
+    log::info!(
+        "Watching {} for new files. Press Ctrl-C to stop.",
+        dir.display()
+    );
+
+    for events in rx {
+        match events {
+            Ok(events) => {
+                // Files still being written are left until they settle
+                let changed: BTreeSet<PathBuf> = events
+                    .into_iter()
+                    .filter(|event| event.kind == DebouncedEventKind::Any)
+                    .map(|event| event.path)
+                    .filter(|path| is_watched(path, formats))
+                    .collect();
+                for path in changed {
+                    ledger.process(&path.to_string_lossy(), &mut process)?;
+                }
+            }
+            Err(err) => log::warn!("Unable to watch {}. {err}", dir.display()),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+/// Tests for the watch module
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test catching up on the files not in the ledger, and recording them
+    fn test_ledger() {
+        let dir = std::env::temp_dir().join("fitutils_test_ledger");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Activity")).unwrap();
+        fs::copy("../data/rowing.fit", dir.join("Activity/rowing.fit")).unwrap();
+        fs::copy("../data/running.gpx", dir.join("running.gpx")).unwrap();
+        fs::write(dir.join("notes.txt"), "Not an activity").unwrap();
+        let ledger_file = dir.join(".processed");
+
+        let mut ledger = Ledger::open(&dir, &ledger_file).unwrap();
+        let pending =
+            pending_files(&dir, &[ActivityFormat::Fit, ActivityFormat::Gpx], &ledger).unwrap();
+        let fit = dir
+            .join("Activity/rowing.fit")
+            .to_string_lossy()
+            .to_string();
+        let gpx = dir.join("running.gpx").to_string_lossy().to_string();
+        assert_eq!(pending, BTreeSet::from([fit.clone(), gpx.clone()]));
+        assert_eq!(
+            pending_files(&dir, &[ActivityFormat::Fit], &ledger).unwrap(),
+            BTreeSet::from([fit.clone()])
+        );
+
+        // Only the files that were processed are recorded, along with what was written
+        let mut calls = Vec::new();
+        let mut process = |filename: &str| -> Result<Vec<String>, Box<dyn Error>> {
+            calls.push(filename.to_string());
+            if filename.ends_with(".gpx") {
+                return Err("Not yet".into());
+            }
+            Ok(vec![dir
+                .join("2024-01-01.fit")
+                .to_string_lossy()
+                .to_string()])
+        };
+        for filename in &pending {
+            ledger.process(filename, &mut process).unwrap();
+        }
+        ledger.process(&fit, &mut process).unwrap();
+        assert_eq!(calls, vec![fit.clone(), gpx.clone()]);
+
+        let ledger = Ledger::open(&dir, &ledger_file).unwrap();
+        assert!(ledger.contains(&fit));
+        assert!(ledger.contains(&dir.join("2024-01-01.fit").to_string_lossy()));
+        assert!(!ledger.contains(&gpx));
+        assert_eq!(
+            fs::read_to_string(&ledger_file).unwrap(),
+            format!(
+                "{}\n2024-01-01.fit\n",
+                Path::new("Activity").join("rowing.fit").display()
+            )
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+//! Small helpers for writing XML, shared by the conversions between GPX and TCX.
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use std::error::Error;
+use std::io::Write;
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+/// Creates an XML writer that indents the elements, so the files are readable.
+pub(crate) fn xml_writer<W: Write>(writer: W) -> EventWriter<W> {
+    EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(writer)
+}
+
+/// Writes an element that only holds text, e.g. `<ele>12.5</ele>`.
+///
+/// # Errors
+///
+/// Writing may fail.
+pub(crate) fn write_element<W: Write>(
+    writer: &mut EventWriter<W>,
+    name: &str,
+    text: &str,
+) -> Result<(), Box<dyn Error>> {
+    writer.write(XmlEvent::start_element(name))?;
+    writer.write(XmlEvent::characters(text))?;
+    writer.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// Formats the time the way both GPX and TCX expect it, e.g. `2018-06-15T13:35:50Z`.
+pub(crate) fn xml_time(time: &DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}